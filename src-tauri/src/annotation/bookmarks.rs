@@ -0,0 +1,141 @@
+//! Moving between a document's bookmarks in reading order
+
+use super::Bookmark;
+use crate::document::Document;
+use crate::voice::ReadingPosition;
+use std::collections::HashMap;
+
+/// Direction to look for the adjacent bookmark
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookmarkDirection {
+    Next,
+    Previous,
+}
+
+/// Reading order of paragraphs within their page, used to order bookmarks on one page
+#[derive(Debug, Clone, Default)]
+pub struct ParagraphOrder(HashMap<String, usize>);
+
+impl ParagraphOrder {
+    pub fn from_document(doc: &Document) -> Self {
+        Self(
+            doc.pages
+                .iter()
+                .flat_map(|page| page.paragraphs.iter().enumerate())
+                .map(|(index, paragraph)| (paragraph.id.clone(), index))
+                .collect(),
+        )
+    }
+
+    /// Sort bookmarks into reading order
+    pub fn sort(&self, bookmarks: &mut [Bookmark]) {
+        bookmarks.sort_by_key(|b| self.key(b.page_number, &b.paragraph_id, b.word_index));
+    }
+
+    /// Sort key for a position; paragraphs not in the document sort first on their page
+    fn key(&self, page: u32, paragraph_id: &str, word_index: u32) -> (u32, usize, u32) {
+        let paragraph = self.0.get(paragraph_id).copied().unwrap_or(0);
+        (page, paragraph, word_index)
+    }
+}
+
+/// The bookmark strictly after (or before) `current` in reading order. With `wrap`, moving
+/// past the last bookmark continues from the first, and the reverse.
+pub fn adjacent_bookmark<'a>(
+    bookmarks: &'a [Bookmark],
+    current: &ReadingPosition,
+    direction: BookmarkDirection,
+    wrap: bool,
+    order: &ParagraphOrder,
+) -> Option<&'a Bookmark> {
+    let key = |b: &&Bookmark| order.key(b.page_number, &b.paragraph_id, b.word_index);
+    let mut sorted: Vec<&Bookmark> = bookmarks.iter().collect();
+    sorted.sort_by_key(key);
+
+    let here = order.key(current.page, &current.paragraph_id, current.word_index);
+    let found = match direction {
+        BookmarkDirection::Next => sorted.iter().find(|b| key(b) > here),
+        BookmarkDirection::Previous => sorted.iter().rev().find(|b| key(b) < here),
+    };
+
+    match (found, direction) {
+        (Some(bookmark), _) => Some(bookmark),
+        (None, _) if !wrap => None,
+        (None, BookmarkDirection::Next) => sorted.first(),
+        (None, BookmarkDirection::Previous) => sorted.last(),
+    }
+    .copied()
+}
+
+impl From<&Bookmark> for ReadingPosition {
+    fn from(bookmark: &Bookmark) -> Self {
+        Self {
+            document_id: bookmark.document_id.clone(),
+            page: bookmark.page_number,
+            paragraph_id: bookmark.paragraph_id.clone(),
+            word_index: bookmark.word_index,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(page: u32, paragraph_id: &str, word_index: u32) -> ReadingPosition {
+        ReadingPosition {
+            document_id: "doc1".to_string(),
+            page,
+            paragraph_id: paragraph_id.to_string(),
+            word_index,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_navigation_follows_reading_order_and_wraps() {
+        // Page 2's paragraphs read "p-b" before "p-a", whatever their ids
+        let order = ParagraphOrder(HashMap::from([
+            ("p-b".to_string(), 0),
+            ("p-a".to_string(), 1),
+        ]));
+        let bookmark = |page, paragraph: &str, word| {
+            Bookmark::new("doc1".into(), page, paragraph.into(), word, None)
+        };
+        let bookmarks = [
+            bookmark(5, "p-x", 0),
+            bookmark(2, "p-a", 4),
+            bookmark(2, "p-b", 10),
+            bookmark(2, "p-a", 0),
+        ];
+        let step = |from: &ReadingPosition, direction, wrap| {
+            adjacent_bookmark(&bookmarks, from, direction, wrap, &order)
+                .map(|b| (b.page_number, b.paragraph_id.clone(), b.word_index))
+        };
+        let at = |page, paragraph: &str, word| Some((page, paragraph.to_string(), word));
+
+        let start = position(1, "", 0);
+        assert_eq!(step(&start, BookmarkDirection::Next, true), at(2, "p-b", 10));
+
+        let mid = position(2, "p-a", 0);
+        assert_eq!(step(&mid, BookmarkDirection::Next, true), at(2, "p-a", 4));
+        assert_eq!(step(&mid, BookmarkDirection::Previous, true), at(2, "p-b", 10));
+
+        let end = position(5, "p-x", 0);
+        assert_eq!(step(&end, BookmarkDirection::Next, false), None);
+        assert_eq!(step(&end, BookmarkDirection::Next, true), at(2, "p-b", 10));
+        assert_eq!(step(&start, BookmarkDirection::Previous, false), None);
+        assert_eq!(step(&start, BookmarkDirection::Previous, true), at(5, "p-x", 0));
+
+        assert_eq!(adjacent_bookmark(&[], &mid, BookmarkDirection::Next, true, &order), None);
+
+        let mut listed = bookmarks.to_vec();
+        order.sort(&mut listed);
+        let listed: Vec<_> = listed
+            .iter()
+            .map(|b| (b.paragraph_id.as_str(), b.word_index))
+            .collect();
+        assert_eq!(listed, [("p-b", 10), ("p-a", 0), ("p-a", 4), ("p-x", 0)]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Threaded discussion attached to a paragraph
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// A comment on a paragraph, or a reply to another comment
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Comment {
+    pub id: Uuid,
+    pub document_id: String,
+    pub paragraph_id: String,
+    /// Comment this replies to; `None` for comments that start a thread
+    pub parent_id: Option<Uuid>,
+    pub author: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Comment {
+    pub fn new(
+        document_id: String,
+        paragraph_id: String,
+        parent_id: Option<Uuid>,
+        author: String,
+        body: String,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            document_id,
+            paragraph_id,
+            parent_id,
+            author,
+            body,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// A comment with the replies to it, each with their own replies
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommentThread {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub replies: Vec<CommentThread>,
+}
+
+/// Nest `comments` under the comments they reply to. Order is kept among siblings, so
+/// comments given oldest first give threads and replies oldest first. Replies whose
+/// parent is missing start their own thread.
+pub fn build_threads(comments: Vec<Comment>) -> Vec<CommentThread> {
+    let ids: Vec<Uuid> = comments.iter().map(|c| c.id).collect();
+    let mut children: HashMap<Option<Uuid>, Vec<Comment>> = HashMap::new();
+    for comment in comments {
+        let parent = comment.parent_id.filter(|parent| ids.contains(parent));
+        children.entry(parent).or_default().push(comment);
+    }
+
+    fn attach(
+        parent: Option<Uuid>,
+        children: &mut HashMap<Option<Uuid>, Vec<Comment>>,
+    ) -> Vec<CommentThread> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|comment| CommentThread {
+                replies: attach(Some(comment.id), children),
+                comment,
+            })
+            .collect()
+    }
+    attach(None, &mut children)
+}
@@ -1,8 +1,21 @@
 //! Annotation export functionality
 
-use super::Annotation;
+use super::{Annotation, AnnotationKind};
+use crate::document::Section;
 use crate::error::AppError;
 
+/// Styles embedded in study pages, so the file needs nothing else to display
+const STUDY_PAGE_STYLE: &str = "\
+body { font-family: Georgia, serif; max-width: 46rem; margin: 2rem auto; padding: 0 1rem; \
+color: #1f2937; line-height: 1.5; }
+header { border-bottom: 1px solid #d1d5db; margin-bottom: 1.5rem; }
+h2 { font-size: 1.2rem; margin-top: 2rem; }
+.highlight { margin: 1rem 0; }
+.highlight blockquote { margin: 0; padding: 0.4rem 0.6rem; border-radius: 4px; }
+.highlight .note { margin: 0.3rem 0 0 0.6rem; font-style: italic; }
+.highlight .page { margin: 0.2rem 0 0 0.6rem; font-size: 0.8rem; color: #6b7280; }
+";
+
 /// Export annotations to Markdown format
 pub fn to_markdown(annotations: &[Annotation]) -> String {
     let mut output = String::from("# Document Annotations\n\n");
@@ -22,12 +35,23 @@ pub fn to_markdown(annotations: &[Annotation]) -> String {
         output.push_str(&format!("## Page {}\n\n", page));
 
         for annotation in page_annotations {
+            if annotation.kind == AnnotationKind::PageNote {
+                if annotation.has_note() {
+                    output.push_str(&format!(
+                        "📄 **Page note:** {}\n",
+                        annotation.note.as_ref().unwrap()
+                    ));
+                    output.push_str("\n---\n\n");
+                }
+                continue;
+            }
+
             // Add highlighted text
             if annotation.has_highlight() {
                 let color_name = annotation
                     .highlight_color
                     .as_ref()
-                    .map(|c| format!("{:?}", c).to_lowercase())
+                    .map(|c| c.key())
                     .unwrap_or_else(|| "default".to_string());
 
                 output.push_str(&format!(
@@ -58,3 +82,281 @@ pub fn to_json(annotations: &[Annotation]) -> Result<String, AppError> {
         crate::error::StorageError::Serialization(e.to_string()).into()
     })
 }
+
+/// Export noted highlights as an Anki-importable TSV: the highlighted text on the
+/// front, the note on the back, tagged with the source title
+pub fn to_anki_tsv(annotations: &[Annotation], source_title: &str) -> String {
+    let tag = anki_tag(source_title);
+    let mut output = String::from("#separator:tab\n#html:true\n#tags column:3\n");
+
+    for annotation in annotations {
+        if annotation.kind == AnnotationKind::PageNote
+            || annotation.selected_text.trim().is_empty()
+            || !annotation.has_note()
+        {
+            continue;
+        }
+        output.push_str(&format!(
+            "{}\t{}\t{}\n",
+            anki_field(&annotation.selected_text),
+            anki_field(annotation.note.as_ref().unwrap()),
+            tag
+        ));
+    }
+
+    output
+}
+
+/// Export highlights as a self-contained HTML page for review: the document title,
+/// then each highlight with its color, note and page, grouped under the section it
+/// falls in. Without sections, all highlights are listed under one heading.
+pub fn to_study_page(annotations: &[Annotation], title: &str, sections: &[Section]) -> String {
+    let mut highlights: Vec<&Annotation> =
+        annotations.iter().filter(|a| on_study_page(a)).collect();
+    highlights.sort_by_key(|a| (a.page_number, a.start_offset));
+
+    // Group by section, keeping the sections' order
+    let mut groups: Vec<(&str, Vec<&Annotation>)> = Vec::new();
+    for annotation in highlights {
+        let group = study_section(annotation, sections).map_or("Highlights", |s| &s.title);
+        match groups.iter_mut().find(|(title, _)| *title == group) {
+            Some((_, members)) => members.push(annotation),
+            None => groups.push((group, vec![annotation])),
+        }
+    }
+    let position = |title: &str| sections.iter().position(|s| s.title == title);
+    groups.sort_by_key(|(title, _)| position(title));
+
+    let count: usize = groups.iter().map(|(_, members)| members.len()).sum();
+    let mut output = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title} - Highlights</title>\n<style>\n{style}</style>\n</head>\n<body>\n\
+         <header>\n<h1>{title}</h1>\n<p>{count} highlight{plural}</p>\n</header>\n",
+        title = escape_html(title),
+        style = STUDY_PAGE_STYLE,
+        count = count,
+        plural = if count == 1 { "" } else { "s" },
+    );
+
+    for (section, members) in groups {
+        output.push_str(&format!("<section>\n<h2>{}</h2>\n", escape_html(section)));
+        for annotation in members {
+            let color = annotation.highlight_color.clone().unwrap_or_default();
+            output.push_str(&format!(
+                "<article class=\"highlight\">\n\
+                 <blockquote style=\"background-color: {}\">{}</blockquote>\n",
+                escape_html(&color.to_css()),
+                escape_html(annotation.selected_text.trim())
+            ));
+            if let Some(note) = annotation.note.as_deref().filter(|n| !n.trim().is_empty()) {
+                output.push_str(&format!("<p class=\"note\">{}</p>\n", escape_html(note.trim())));
+            }
+            output.push_str(&format!(
+                "<p class=\"page\">Page {}</p>\n</article>\n",
+                annotation.page_number
+            ));
+        }
+        output.push_str("</section>\n");
+    }
+
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+/// Whether `to_study_page` lists an annotation: highlights, but not page notes
+pub fn on_study_page(annotation: &Annotation) -> bool {
+    annotation.kind != AnnotationKind::PageNote && annotation.has_highlight()
+}
+
+/// Section holding an annotation: the one containing its paragraph, otherwise the
+/// last section starting on or before its page
+fn study_section<'a>(annotation: &Annotation, sections: &'a [Section]) -> Option<&'a Section> {
+    let by_paragraph = annotation
+        .paragraph_id
+        .as_ref()
+        .and_then(|id| sections.iter().find(|s| s.paragraph_ids.contains(id)));
+    by_paragraph.or_else(|| sections.iter().rev().find(|s| s.page <= annotation.page_number))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Escape a field for Anki's HTML import; tabs would split the field, newlines the
+/// card, and a leading `#` would read as a header line
+fn anki_field(text: &str) -> String {
+    let field = text
+        .trim()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\t', " ")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>");
+    match field.strip_prefix('#') {
+        Some(rest) => format!("&#35;{}", rest),
+        None => field,
+    }
+}
+
+/// Anki tags are space separated, so join the title's words
+fn anki_tag(title: &str) -> String {
+    title
+        .split_whitespace()
+        .map(|word| word.replace(|c: char| c.is_control(), ""))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotation::HighlightColor;
+
+    #[test]
+    fn test_markdown_includes_page_notes() {
+        let highlight = Annotation::new(
+            "doc1".into(),
+            3,
+            0,
+            9,
+            "key claim".into(),
+            Some(HighlightColor::Yellow),
+            None,
+        );
+        let page_note = Annotation::page_note("doc1".into(), 3, "Whole page is background".into());
+
+        let markdown = to_markdown(&[page_note, highlight]);
+        assert!(markdown.contains("📄 **Page note:** Whole page is background"));
+        assert!(markdown.contains("> **[yellow]** \"key claim\""));
+        // Page notes have no selection to quote
+        assert!(!markdown.contains("\"\""));
+    }
+
+    #[test]
+    fn test_anki_export_has_one_card_per_noted_highlight() {
+        let noted = |text: &str, note: Option<&str>| {
+            Annotation::new(
+                "doc1".into(),
+                1,
+                0,
+                text.len(),
+                text.into(),
+                Some(HighlightColor::Green),
+                note.map(Into::into),
+            )
+        };
+        let annotations = [
+            noted("Attention is all\tyou need", Some("Transformers drop <recurrence>")),
+            noted("Unnoted highlight", None),
+            noted("#1 Layer norm", Some("Applied before\neach sublayer")),
+            Annotation::page_note("doc1".into(), 2, "Skim this page".into()),
+        ];
+
+        let tsv = to_anki_tsv(&annotations, "Attention Is All You Need");
+        let cards: Vec<Vec<&str>> = tsv
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split('\t').collect())
+            .collect();
+
+        assert!(tsv.starts_with("#separator:tab\n#html:true\n#tags column:3\n"));
+        assert_eq!(
+            cards,
+            [
+                [
+                    "Attention is all you need",
+                    "Transformers drop &lt;recurrence&gt;",
+                    "Attention_Is_All_You_Need",
+                ],
+                [
+                    "&#35;1 Layer norm",
+                    "Applied before<br>each sublayer",
+                    "Attention_Is_All_You_Need",
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_study_page_lists_each_highlight_with_its_color() {
+        let section = |title: &str, page, paragraph_ids: &[&str]| Section {
+            title: title.into(),
+            level: 1,
+            page,
+            start_paragraph: 0,
+            end_paragraph: 0,
+            paragraph_ids: paragraph_ids.iter().map(|id| id.to_string()).collect(),
+        };
+        let sections = [
+            section("Introduction", 1, &["p1"]),
+            section("Method", 3, &["p7"]),
+        ];
+        let highlight = |page, text: &str, color, note: Option<&str>| {
+            let note = note.map(Into::into);
+            Annotation::new("doc1".into(), page, 0, text.len(), text.into(), color, note)
+        };
+        let mut in_method = highlight(2, "Scaled dot-product", Some(HighlightColor::Blue), None);
+        in_method.paragraph_id = Some("p7".into());
+        let orange = HighlightColor::Custom("#ff8800".into());
+        let annotations = [
+            highlight(4, "Multi-head attention", Some(HighlightColor::Green), Some("Key <idea>")),
+            highlight(1, "Recurrence is slow", Some(orange.clone()), None),
+            in_method,
+            highlight(1, "Not highlighted", None, Some("Just a note")),
+            Annotation::page_note("doc1".into(), 1, "Page note".into()),
+        ];
+
+        let html = to_study_page(&annotations, "Attention & Transformers", &sections);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Attention &amp; Transformers</h1>"));
+        assert!(!html.contains("<link") && !html.contains("<script") && !html.contains("src="));
+        assert_eq!(html.matches("<article class=\"highlight\">").count(), 3);
+        assert!(!html.contains("Not highlighted") && !html.contains("Page note"));
+
+        let entry = |color: &HighlightColor, text: &str| {
+            let css = color.to_css();
+            format!("<blockquote style=\"background-color: {}\">{}</blockquote>", css, text)
+        };
+        let intro = html.find("<h2>Introduction</h2>").unwrap();
+        let method = html.find("<h2>Method</h2>").unwrap();
+        let recurrence = html.find(&entry(&orange, "Recurrence is slow")).unwrap();
+        let scaled = html.find(&entry(&HighlightColor::Blue, "Scaled dot-product")).unwrap();
+        let multi_head = html.find(&entry(&HighlightColor::Green, "Multi-head attention")).unwrap();
+        assert!(intro < recurrence && recurrence < method);
+        assert!(method < scaled && scaled < multi_head);
+        let noted = "<p class=\"note\">Key &lt;idea&gt;</p>\n<p class=\"page\">Page 4</p>";
+        assert!(html.contains(noted));
+    }
+
+    #[test]
+    fn test_study_page_drops_malformed_custom_colors() {
+        let highlight = |text: &str, color: &str| {
+            let color = Some(HighlightColor::Custom(color.into()));
+            Annotation::new("doc1".into(), 1, 0, text.len(), text.into(), color, None)
+        };
+        let mut page_note = Annotation::page_note("doc1".into(), 1, "Colored".into());
+        page_note.highlight_color = Some(HighlightColor::Blue);
+        let annotations = [
+            highlight("Short hex", "#f80"),
+            highlight("Injected", "red\" onmouseover=\"alert(1)"),
+            highlight("Not hex", "#ggg"),
+            page_note,
+        ];
+
+        let html = to_study_page(&annotations, "Paper", &[]);
+        assert!(html.contains("background-color: #f80\">Short hex"));
+        let default = HighlightColor::default().to_css();
+        assert!(html.contains(&format!("background-color: {}\">Injected", default)));
+        assert!(html.contains(&format!("background-color: {}\">Not hex", default)));
+        assert!(!html.contains("alert"));
+
+        // The page note is left off the page, so it isn't counted either
+        assert_eq!(annotations.iter().filter(|a| on_study_page(a)).count(), 3);
+        assert!(html.contains("<p>3 highlights</p>"));
+    }
+}
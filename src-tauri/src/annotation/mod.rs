@@ -1,7 +1,11 @@
 //! Annotation management module
 
+pub mod bookmarks;
+pub mod comments;
 pub mod export;
+pub mod timeline;
 
+use crate::error::AnnotationError;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -15,6 +19,8 @@ pub enum HighlightColor {
     Blue,
     Purple,
     Red,
+    /// User-chosen hex color such as "#ff8800"
+    Custom(String),
 }
 
 impl Default for HighlightColor {
@@ -24,13 +30,140 @@ impl Default for HighlightColor {
 }
 
 impl HighlightColor {
-    pub fn to_css(&self) -> &'static str {
+    /// CSS color for the highlight. A custom color that isn't a `#` followed by 3, 4, 6
+    /// or 8 hex digits is drawn in the default color rather than written into the CSS.
+    pub fn to_css(&self) -> String {
         match self {
-            Self::Yellow => "rgba(250, 204, 21, 0.4)",
-            Self::Green => "rgba(34, 197, 94, 0.4)",
-            Self::Blue => "rgba(59, 130, 246, 0.4)",
-            Self::Purple => "rgba(168, 85, 247, 0.4)",
-            Self::Red => "rgba(239, 68, 68, 0.4)",
+            Self::Yellow => "rgba(250, 204, 21, 0.4)".to_string(),
+            Self::Green => "rgba(34, 197, 94, 0.4)".to_string(),
+            Self::Blue => "rgba(59, 130, 246, 0.4)".to_string(),
+            Self::Purple => "rgba(168, 85, 247, 0.4)".to_string(),
+            Self::Red => "rgba(239, 68, 68, 0.4)".to_string(),
+            Self::Custom(hex) if is_hex_color(hex) => hex.clone(),
+            Self::Custom(_) => Self::default().to_css(),
+        }
+    }
+
+    /// Stored form: the preset name, or the lowercased hex of a custom color
+    pub fn key(&self) -> String {
+        match self {
+            Self::Yellow => "yellow".to_string(),
+            Self::Green => "green".to_string(),
+            Self::Blue => "blue".to_string(),
+            Self::Purple => "purple".to_string(),
+            Self::Red => "red".to_string(),
+            Self::Custom(hex) => hex.to_lowercase(),
+        }
+    }
+
+    /// Parse a stored color key
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "yellow" => Some(Self::Yellow),
+            "green" => Some(Self::Green),
+            "blue" => Some(Self::Blue),
+            "purple" => Some(Self::Purple),
+            "red" => Some(Self::Red),
+            hex if hex.starts_with('#') => Some(Self::Custom(hex.to_string())),
+            _ => None,
+        }
+    }
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color.strip_prefix('#').is_some_and(|digits| {
+        matches!(digits.len(), 3 | 4 | 6 | 8) && digits.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+/// Number of annotations using a highlight color
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ColorCount {
+    pub color: HighlightColor,
+    pub count: u32,
+}
+
+/// An annotation matching a library-wide search, with the document it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotationSearchHit {
+    pub annotation: Annotation,
+    /// Title of the document, or its file path when it has no title
+    pub document_title: String,
+    /// Excerpt of the matching note or selected text
+    pub snippet: String,
+}
+
+/// A saved reading position, such as one marked by saying "bookmark this"
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Bookmark {
+    pub id: Uuid,
+    pub document_id: String,
+    pub page_number: u32,
+    /// Paragraph being read; empty when only the page is known
+    pub paragraph_id: String,
+    /// Word within the paragraph
+    pub word_index: u32,
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Bookmark {
+    pub fn new(
+        document_id: String,
+        page_number: u32,
+        paragraph_id: String,
+        word_index: u32,
+        label: Option<String>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            document_id,
+            page_number,
+            paragraph_id,
+            word_index,
+            label,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Outcome of pointing a document's annotations at regenerated paragraph ids
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ParagraphRemap {
+    /// Annotations moved to their paragraph's new id
+    pub remapped: usize,
+    /// Annotations flagged because their paragraph could not be found
+    pub unmatched: usize,
+}
+
+/// What an annotation is attached to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationKind {
+    /// Highlighted text range, optionally with a note
+    #[default]
+    Highlight,
+    /// Note on a text range without a highlight
+    RangeNote,
+    /// Note about a whole page, not tied to a text range
+    PageNote,
+}
+
+impl AnnotationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Highlight => "highlight",
+            Self::RangeNote => "range_note",
+            Self::PageNote => "page_note",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "highlight" => Some(Self::Highlight),
+            "range_note" => Some(Self::RangeNote),
+            "page_note" => Some(Self::PageNote),
+            _ => None,
         }
     }
 }
@@ -46,11 +179,18 @@ pub struct Annotation {
     pub page_number: u32,
     /// Paragraph ID (optional, for more precise location)
     pub paragraph_id: Option<String>,
-    /// Start character offset in the text
-    pub start_offset: usize,
-    /// End character offset in the text
-    pub end_offset: usize,
-    /// The selected/highlighted text
+    /// Set when paragraph ids were regenerated and this annotation's paragraph could
+    /// not be found, so `paragraph_id` is out of date
+    #[serde(default)]
+    pub paragraph_unmatched: bool,
+    /// Highlight, range note, or page note
+    #[serde(default)]
+    pub kind: AnnotationKind,
+    /// Start character offset in the text (None for page notes)
+    pub start_offset: Option<usize>,
+    /// End character offset in the text (None for page notes)
+    pub end_offset: Option<usize>,
+    /// The selected/highlighted text (empty for page notes)
     pub selected_text: String,
     /// Highlight color (None if note-only)
     pub highlight_color: Option<HighlightColor>,
@@ -73,14 +213,22 @@ impl Annotation {
         highlight_color: Option<HighlightColor>,
         note: Option<String>,
     ) -> Self {
+        let kind = if highlight_color.is_none() && note.is_some() {
+            AnnotationKind::RangeNote
+        } else {
+            AnnotationKind::Highlight
+        };
+
         let now = Utc::now();
         Self {
             id: Uuid::new_v4(),
             document_id,
             page_number,
             paragraph_id: None,
-            start_offset,
-            end_offset,
+            paragraph_unmatched: false,
+            kind,
+            start_offset: Some(start_offset),
+            end_offset: Some(end_offset),
             selected_text,
             highlight_color,
             note,
@@ -89,6 +237,26 @@ impl Annotation {
         }
     }
 
+    /// Create a note about a whole page
+    pub fn page_note(document_id: String, page_number: u32, note: String) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            document_id,
+            page_number,
+            paragraph_id: None,
+            paragraph_unmatched: false,
+            kind: AnnotationKind::PageNote,
+            start_offset: None,
+            end_offset: None,
+            selected_text: String::new(),
+            highlight_color: None,
+            note: Some(note),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
     /// Check if this annotation has a highlight
     pub fn has_highlight(&self) -> bool {
         self.highlight_color.is_some()
@@ -100,6 +268,72 @@ impl Annotation {
     }
 }
 
+/// Limits applied when annotations are created or edited, and bookmark navigation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotationConfig {
+    /// Longest note accepted, in characters
+    pub max_note_length: usize,
+    /// Whether moving past the last bookmark continues from the first, and the reverse
+    #[serde(default = "default_wrap_bookmarks")]
+    pub wrap_bookmarks: bool,
+}
+
+fn default_wrap_bookmarks() -> bool {
+    true
+}
+
+impl Default for AnnotationConfig {
+    fn default() -> Self {
+        Self {
+            max_note_length: 10_000,
+            wrap_bookmarks: default_wrap_bookmarks(),
+        }
+    }
+}
+
+impl AnnotationConfig {
+    /// Check a note's content against the configured limits
+    pub fn validate_note(&self, note: &str) -> Result<(), AnnotationError> {
+        if note.trim().is_empty() {
+            return Err(AnnotationError::EmptyNote);
+        }
+
+        let length = note.chars().count();
+        if length > self.max_note_length {
+            return Err(AnnotationError::NoteTooLong {
+                length,
+                max: self.max_note_length,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check a new annotation's range and note.
+    ///
+    /// `page_length` is the page's text length in characters, when known.
+    pub fn validate(
+        &self,
+        annotation: &Annotation,
+        page_length: Option<usize>,
+    ) -> Result<(), AnnotationError> {
+        if let (Some(start), Some(end)) = (annotation.start_offset, annotation.end_offset) {
+            if start > end {
+                return Err(AnnotationError::InvalidRange { start, end });
+            }
+            if let Some(page_length) = page_length.filter(|&len| end > len) {
+                return Err(AnnotationError::RangeOutOfBounds { end, page_length });
+            }
+        }
+
+        if let Some(note) = &annotation.note {
+            self.validate_note(note)?;
+        }
+
+        Ok(())
+    }
+}
+
 /// Update payload for modifying annotations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnnotationUpdate {
@@ -121,3 +355,66 @@ impl AnnotationUpdate {
         annotation.updated_at = Utc::now();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: usize, end: usize, note: Option<&str>) -> Annotation {
+        Annotation::new(
+            "doc1".into(),
+            1,
+            start,
+            end,
+            "text".into(),
+            Some(HighlightColor::Yellow),
+            note.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn test_validation_rejects_reversed_and_out_of_bounds_ranges() {
+        let config = AnnotationConfig::default();
+
+        assert!(matches!(
+            config.validate(&range(10, 4, None), Some(100)),
+            Err(AnnotationError::InvalidRange { start: 10, end: 4 })
+        ));
+        assert!(matches!(
+            config.validate(&range(90, 120, None), Some(100)),
+            Err(AnnotationError::RangeOutOfBounds { end: 120, page_length: 100 })
+        ));
+        // Without a known page length only the ordering is checked
+        assert!(config.validate(&range(90, 120, None), None).is_ok());
+    }
+
+    #[test]
+    fn test_validation_rejects_over_length_and_empty_notes() {
+        let config = AnnotationConfig {
+            max_note_length: 5,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            config.validate(&range(0, 4, Some("too long")), Some(100)),
+            Err(AnnotationError::NoteTooLong { length: 8, max: 5 })
+        ));
+        assert!(matches!(
+            config.validate(&range(0, 4, Some("  ")), Some(100)),
+            Err(AnnotationError::EmptyNote)
+        ));
+        // Limit counts characters, not bytes
+        assert!(config.validate_note("héllo").is_ok());
+    }
+
+    #[test]
+    fn test_validation_accepts_valid_annotations() {
+        let config = AnnotationConfig::default();
+
+        assert!(config.validate(&range(0, 100, Some("fine")), Some(100)).is_ok());
+        assert!(config.validate(&range(7, 7, None), Some(100)).is_ok());
+
+        let page_note = Annotation::page_note("doc1".into(), 1, "About this page".into());
+        assert!(config.validate(&page_note, Some(0)).is_ok());
+    }
+}
@@ -0,0 +1,145 @@
+//! Annotations in the order they were made, for reviewing a study session
+
+use super::Annotation;
+use crate::document::Document;
+use chrono::TimeZone;
+use serde::{Deserialize, Serialize};
+
+/// Characters of page text shown on each side of an annotation
+const CONTEXT_CHARS: usize = 60;
+
+/// An annotation with the page text around it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub annotation: Annotation,
+    /// Page text around the selection, or the start of the page for page notes
+    pub context: Option<String>,
+}
+
+/// Consecutive timeline entries, optionally all made on one day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineGroup {
+    /// Date (`YYYY-MM-DD`) the entries were made on; `None` when not grouped by day
+    pub day: Option<String>,
+    pub entries: Vec<TimelineEntry>,
+}
+
+/// Order annotations by creation time, with page context from the parsed document when
+/// it is available. Annotations made at the same moment keep their given order.
+pub fn timeline(mut annotations: Vec<Annotation>, doc: Option<&Document>) -> Vec<TimelineEntry> {
+    annotations.sort_by_key(|a| a.created_at);
+    annotations
+        .into_iter()
+        .map(|annotation| TimelineEntry {
+            context: doc.and_then(|doc| context(doc, &annotation)),
+            annotation,
+        })
+        .collect()
+}
+
+/// Split a timeline into the days, in `tz`, its entries were made on
+pub fn group_by_day<Tz: TimeZone>(entries: Vec<TimelineEntry>, tz: &Tz) -> Vec<TimelineGroup> {
+    let mut groups: Vec<TimelineGroup> = Vec::new();
+    for entry in entries {
+        let day = entry
+            .annotation
+            .created_at
+            .with_timezone(tz)
+            .date_naive()
+            .to_string();
+        match groups.last_mut() {
+            Some(group) if group.day.as_deref() == Some(day.as_str()) => group.entries.push(entry),
+            _ => groups.push(TimelineGroup {
+                day: Some(day),
+                entries: vec![entry],
+            }),
+        }
+    }
+    groups
+}
+
+/// Page text around an annotation, with whitespace collapsed
+fn context(doc: &Document, annotation: &Annotation) -> Option<String> {
+    let page = doc.pages.iter().find(|p| p.number == annotation.page_number)?;
+    let chars: Vec<char> = page.text.chars().collect();
+    let (start, end) = match (annotation.start_offset, annotation.end_offset) {
+        (Some(start), Some(end)) if start <= end && end <= chars.len() => (
+            start.saturating_sub(CONTEXT_CHARS),
+            (end + CONTEXT_CHARS).min(chars.len()),
+        ),
+        _ => (0, (2 * CONTEXT_CHARS).min(chars.len())),
+    };
+
+    let snippet: String = chars[start..end].iter().collect();
+    let snippet = snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!snippet.is_empty()).then_some(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotation::HighlightColor;
+    use crate::document::test_document;
+    use chrono::{Duration, Utc};
+    use rusqlite::Connection;
+
+    fn document() -> Document {
+        Document {
+            id: "doc1".to_string(),
+            ..test_document(&[
+                &["Page 1 opens here. Key idea 1 follows."],
+                &["Page 2 opens here. Key idea 2 follows."],
+                &["Page 3 opens here. Key idea 3 follows."],
+            ])
+        }
+    }
+
+    #[test]
+    fn test_timeline_orders_by_creation_not_page() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::run_migrations(&conn).unwrap();
+        conn.execute("INSERT INTO documents (id, file_path) VALUES ('doc1', 'notes.txt')", [])
+            .unwrap();
+
+        // Read page 3 first, went back to page 1, then noted page 2 the next day
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 21, 0, 0).unwrap();
+        let made = [(3, 0), (1, 10), (2, 26 * 60)];
+        for (page, minutes) in made {
+            let mut annotation = if page == 2 {
+                Annotation::page_note("doc1".into(), page, "Revisit".into())
+            } else {
+                let color = Some(HighlightColor::Yellow);
+                Annotation::new("doc1".into(), page, 20, 30, "Key idea".into(), color, None)
+            };
+            annotation.created_at = start + Duration::minutes(minutes);
+            crate::storage::insert_annotation(&conn, &annotation).unwrap();
+        }
+
+        let annotations = crate::storage::list_annotations(&conn, "doc1").unwrap();
+        let pages = |entries: &[TimelineEntry]| -> Vec<u32> {
+            entries.iter().map(|e| e.annotation.page_number).collect()
+        };
+        assert_eq!(annotations.iter().map(|a| a.page_number).collect::<Vec<_>>(), [1, 2, 3]);
+
+        let doc = document();
+        let entries = timeline(annotations, Some(&doc));
+        assert_eq!(pages(&entries), [3, 1, 2]);
+        assert_eq!(
+            entries[0].context.as_deref(),
+            Some("Page 3 opens here. Key idea 3 follows.")
+        );
+
+        let days = group_by_day(entries, &Utc);
+        let summary: Vec<_> = days
+            .iter()
+            .map(|d| (d.day.clone().unwrap(), pages(&d.entries)))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("2024-03-01".to_string(), vec![3, 1]),
+                ("2024-03-02".to_string(), vec![2]),
+            ]
+        );
+    }
+}
@@ -1,14 +1,86 @@
 //! Annotation-related Tauri commands
 
-use crate::annotation::{Annotation, AnnotationUpdate, HighlightColor};
+use crate::annotation::bookmarks::{adjacent_bookmark, BookmarkDirection, ParagraphOrder};
+use crate::annotation::comments::{build_threads, Comment, CommentThread};
+use crate::annotation::timeline::{self, TimelineGroup};
+use crate::annotation::{
+    Annotation, AnnotationConfig, AnnotationSearchHit, AnnotationUpdate, Bookmark, ColorCount,
+    HighlightColor,
+};
+use crate::document::Document;
 use crate::error::AppError;
-use tauri::AppHandle;
+use crate::voice::ReadingPosition;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
 use uuid::Uuid;
 
+/// Most hits returned by a library-wide annotation search
+const ANNOTATION_SEARCH_LIMIT: usize = 100;
+
+/// Annotation limits, and the page lengths and paragraph order of opened documents
+pub struct AnnotationState {
+    config: Mutex<AnnotationConfig>,
+    /// Character count of each page, by document id then page number
+    page_lengths: Mutex<HashMap<String, HashMap<u32, usize>>>,
+    /// Reading order of paragraphs, by document id, for stepping through bookmarks
+    paragraph_orders: Mutex<HashMap<String, ParagraphOrder>>,
+}
+
+impl AnnotationState {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(AnnotationConfig::default()),
+            page_lengths: Mutex::new(HashMap::new()),
+            paragraph_orders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remember page lengths so annotation ranges can be bounds-checked, and the
+    /// paragraph order so bookmarks can be stepped through without parsing again
+    pub(crate) fn record_document(&self, document: &Document) {
+        let lengths = document
+            .pages
+            .iter()
+            .map(|page| (page.number, page.text.chars().count()))
+            .collect();
+        self.page_lengths
+            .lock()
+            .unwrap()
+            .insert(document.id.clone(), lengths);
+        self.paragraph_orders
+            .lock()
+            .unwrap()
+            .insert(document.id.clone(), ParagraphOrder::from_document(document));
+    }
+
+    fn validate(&self, annotation: &Annotation) -> Result<(), AppError> {
+        let page_length = self
+            .page_lengths
+            .lock()
+            .unwrap()
+            .get(&annotation.document_id)
+            .and_then(|pages| pages.get(&annotation.page_number).copied());
+
+        self.config
+            .lock()
+            .unwrap()
+            .validate(annotation, page_length)
+            .map_err(Into::into)
+    }
+}
+
+impl Default for AnnotationState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Add a new annotation to a document
 #[tauri::command]
 pub async fn add_annotation(
     app: AppHandle,
+    state: State<'_, AnnotationState>,
     document_id: String,
     page_number: u32,
     start_offset: usize,
@@ -32,12 +104,31 @@ pub async fn add_annotation(
         highlight_color,
         note,
     );
+    state.validate(&annotation)?;
 
     crate::storage::save_annotation(&app, &annotation).await?;
 
     Ok(annotation)
 }
 
+/// Add a note about a whole page, not tied to a text selection
+#[tauri::command]
+pub async fn add_page_note(
+    app: AppHandle,
+    state: State<'_, AnnotationState>,
+    document_id: String,
+    page: u32,
+    note: String,
+) -> Result<Annotation, AppError> {
+    tracing::info!("Adding page note to document {} on page {}", document_id, page);
+
+    let annotation = Annotation::page_note(document_id, page, note);
+    state.validate(&annotation)?;
+    crate::storage::save_annotation(&app, &annotation).await?;
+
+    Ok(annotation)
+}
+
 /// Get all annotations for a document
 #[tauri::command]
 pub async fn get_annotations(
@@ -49,14 +140,94 @@ pub async fn get_annotations(
     crate::storage::get_annotations(&app, &document_id).await
 }
 
+/// Get a document's annotations in the order they were made, with the page text around
+/// each, optionally grouped by the local day they were made on
+#[tauri::command]
+pub async fn get_annotations_timeline(
+    app: AppHandle,
+    document_id: String,
+    group_by_day: Option<bool>,
+) -> Result<Vec<TimelineGroup>, AppError> {
+    let (annotations, path) = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        (
+            crate::storage::list_annotations(&conn, &document_id)?,
+            crate::storage::get_document_path(&conn, &document_id)?,
+        )
+    };
+    if annotations.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Context is a convenience; the timeline is still useful if the file has moved
+    let document = match crate::document::parser::parse_document(&path).await {
+        Ok(document) => Some(document),
+        Err(e) => {
+            tracing::warn!("No page context for the timeline of {}: {}", document_id, e);
+            None
+        }
+    };
+    let entries = timeline::timeline(annotations, document.as_ref());
+
+    if group_by_day.unwrap_or(false) {
+        Ok(timeline::group_by_day(entries, &chrono::Local))
+    } else {
+        Ok(vec![TimelineGroup { day: None, entries }])
+    }
+}
+
+/// Search the notes and highlighted text of annotations in every document
+#[tauri::command]
+pub async fn search_all_annotations(
+    app: AppHandle,
+    query: String,
+) -> Result<Vec<AnnotationSearchHit>, AppError> {
+    tracing::debug!("Searching all annotations for {:?}", query);
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::search_annotations(&conn, &query, ANNOTATION_SEARCH_LIMIT)
+}
+
+/// Get a document's annotations highlighted with one color
+#[tauri::command]
+pub async fn get_annotations_by_color(
+    app: AppHandle,
+    document_id: String,
+    color: HighlightColor,
+) -> Result<Vec<Annotation>, AppError> {
+    tracing::debug!("Getting {} annotations for document {}", color.key(), document_id);
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_annotations_by_color(&conn, &document_id, &color)
+}
+
+/// Count a document's highlights per color
+#[tauri::command]
+pub async fn get_annotation_color_counts(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Vec<ColorCount>, AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_annotation_color_counts(&conn, &document_id)
+}
+
 /// Update an existing annotation
 #[tauri::command]
 pub async fn update_annotation(
     app: AppHandle,
+    state: State<'_, AnnotationState>,
     annotation_id: String,
     update: AnnotationUpdate,
 ) -> Result<Annotation, AppError> {
     tracing::info!("Updating annotation {}", annotation_id);
+
+    if let Some(Some(note)) = &update.note {
+        state.config.lock().unwrap().validate_note(note)?;
+    }
     
     let id = Uuid::parse_str(&annotation_id)
         .map_err(|_| crate::error::AnnotationError::NotFound(annotation_id.clone()))?;
@@ -78,6 +249,182 @@ pub async fn delete_annotation(
     crate::storage::delete_annotation(&app, id).await
 }
 
+/// Get a document's bookmarks, in reading order
+#[tauri::command]
+pub async fn get_bookmarks(app: AppHandle, document_id: String) -> Result<Vec<Bookmark>, AppError> {
+    let (bookmarks, _) = ordered_bookmarks(&app, &document_id).await?;
+    Ok(bookmarks)
+}
+
+/// A document's bookmarks in reading order, with the paragraph order they were sorted by
+async fn ordered_bookmarks(
+    app: &AppHandle,
+    document_id: &str,
+) -> Result<(Vec<Bookmark>, ParagraphOrder), AppError> {
+    let (mut bookmarks, path) = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        let bookmarks = crate::storage::get_bookmarks(&conn, document_id)?;
+        if bookmarks.is_empty() {
+            return Ok((bookmarks, ParagraphOrder::default()));
+        }
+        (bookmarks, crate::storage::get_document_path(&conn, document_id)?)
+    };
+
+    // Paragraph ids carry no order, so bookmarks on one page are ordered by the parse,
+    // kept from when the document was opened
+    let state = app.state::<AnnotationState>();
+    let known = state.paragraph_orders.lock().unwrap().contains_key(document_id);
+    if !known {
+        let document = crate::document::parser::parse_document(&path).await?;
+        state.record_document(&document);
+    }
+
+    let order = state
+        .paragraph_orders
+        .lock()
+        .unwrap()
+        .get(document_id)
+        .cloned()
+        .unwrap_or_default();
+    order.sort(&mut bookmarks);
+    Ok((bookmarks, order))
+}
+
+/// Position of the adjacent bookmark, or `None` if there is none in that direction
+async fn step_bookmark(
+    app: &AppHandle,
+    document_id: &str,
+    current_position: &ReadingPosition,
+    direction: BookmarkDirection,
+) -> Result<Option<ReadingPosition>, AppError> {
+    let wrap = app.state::<AnnotationState>().config.lock().unwrap().wrap_bookmarks;
+    let (bookmarks, order) = ordered_bookmarks(app, document_id).await?;
+    let bookmark = adjacent_bookmark(&bookmarks, current_position, direction, wrap, &order);
+    Ok(bookmark.map(ReadingPosition::from))
+}
+
+/// Position of the first bookmark after the current reading position
+#[tauri::command]
+pub async fn next_bookmark(
+    app: AppHandle,
+    document_id: String,
+    current_position: ReadingPosition,
+) -> Result<Option<ReadingPosition>, AppError> {
+    step_bookmark(&app, &document_id, &current_position, BookmarkDirection::Next).await
+}
+
+/// Position of the last bookmark before the current reading position
+#[tauri::command]
+pub async fn previous_bookmark(
+    app: AppHandle,
+    document_id: String,
+    current_position: ReadingPosition,
+) -> Result<Option<ReadingPosition>, AppError> {
+    step_bookmark(&app, &document_id, &current_position, BookmarkDirection::Previous).await
+}
+
+/// Delete a bookmark
+#[tauri::command]
+pub async fn delete_bookmark(app: AppHandle, bookmark_id: String) -> Result<(), AppError> {
+    let not_found = || crate::error::AnnotationError::BookmarkNotFound(bookmark_id.clone());
+    let id = Uuid::parse_str(&bookmark_id).map_err(|_| not_found())?;
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    if !crate::storage::delete_bookmark(&conn, id)? {
+        return Err(not_found().into());
+    }
+    Ok(())
+}
+
+/// Comment on a paragraph, or reply to `parent_id`. Replies are attached to the
+/// parent's paragraph.
+#[tauri::command]
+pub async fn add_comment(
+    app: AppHandle,
+    document_id: String,
+    paragraph_id: String,
+    author: String,
+    body: String,
+    parent_id: Option<String>,
+) -> Result<Comment, AppError> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Err(crate::error::AnnotationError::EmptyComment.into());
+    }
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    let comment = match parent_id {
+        Some(parent_id) => {
+            let not_found = || crate::error::AnnotationError::CommentNotFound(parent_id.clone());
+            let id = Uuid::parse_str(&parent_id).map_err(|_| not_found())?;
+            let parent = crate::storage::get_comment(&conn, id)?.ok_or_else(not_found)?;
+            Comment::new(
+                parent.document_id,
+                parent.paragraph_id,
+                Some(parent.id),
+                author,
+                body.to_string(),
+            )
+        }
+        None => Comment::new(document_id, paragraph_id, None, author, body.to_string()),
+    };
+
+    tracing::info!("Adding comment {} on paragraph {}", comment.id, comment.paragraph_id);
+    crate::storage::save_comment(&conn, &comment)?;
+    Ok(comment)
+}
+
+/// Comment threads on a paragraph, oldest first, with replies nested under the
+/// comments they answer
+#[tauri::command]
+pub async fn get_comment_thread(
+    app: AppHandle,
+    document_id: String,
+    paragraph_id: String,
+) -> Result<Vec<CommentThread>, AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    let comments = crate::storage::get_paragraph_comments(&conn, &document_id, &paragraph_id)?;
+    Ok(build_threads(comments))
+}
+
+/// Delete a comment and every reply beneath it, returning how many comments were removed
+#[tauri::command]
+pub async fn delete_comment(app: AppHandle, comment_id: String) -> Result<usize, AppError> {
+    let not_found = || crate::error::AnnotationError::CommentNotFound(comment_id.clone());
+    let id = Uuid::parse_str(&comment_id).map_err(|_| not_found())?;
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    match crate::storage::delete_comment(&conn, id)? {
+        0 => Err(not_found().into()),
+        deleted => Ok(deleted),
+    }
+}
+
+/// Get the limits applied to new and edited annotations
+#[tauri::command]
+pub async fn get_annotation_config(
+    state: State<'_, AnnotationState>,
+) -> Result<AnnotationConfig, AppError> {
+    Ok(state.config.lock().unwrap().clone())
+}
+
+/// Set the limits applied to new and edited annotations
+#[tauri::command]
+pub async fn set_annotation_config(
+    state: State<'_, AnnotationState>,
+    config: AnnotationConfig,
+) -> Result<(), AppError> {
+    tracing::info!("Setting max annotation note length to {}", config.max_note_length);
+
+    *state.config.lock().unwrap() = config;
+    Ok(())
+}
+
 /// Export annotations for a document
 #[tauri::command]
 pub async fn export_annotations(
@@ -99,3 +446,77 @@ pub async fn export_annotations(
         _ => Ok(crate::annotation::export::to_markdown(&annotations)),
     }
 }
+
+/// Write a document's noted highlights to a TSV file Anki can import, returning the
+/// number of cards written
+#[tauri::command]
+pub async fn export_annotations_to_anki(
+    app: AppHandle,
+    document_id: String,
+    output_path: String,
+) -> Result<usize, AppError> {
+    tracing::info!(
+        "Exporting annotations for document {} to Anki at {}",
+        document_id,
+        output_path
+    );
+
+    let (title, _) = export_title_and_path(&app, &document_id)?;
+
+    let annotations = crate::storage::get_annotations(&app, &document_id).await?;
+    let tsv = crate::annotation::export::to_anki_tsv(&annotations, &title);
+    std::fs::write(&output_path, &tsv)?;
+
+    Ok(tsv.lines().filter(|line| !line.starts_with('#')).count())
+}
+
+/// Write a document's highlights to a self-contained HTML review page, grouped by
+/// section, returning the number of highlights listed
+#[tauri::command]
+pub async fn export_study_page(
+    app: AppHandle,
+    document_id: String,
+    output_path: String,
+) -> Result<usize, AppError> {
+    tracing::info!(
+        "Exporting study page for document {} to {}",
+        document_id,
+        output_path
+    );
+
+    let (title, path) = export_title_and_path(&app, &document_id)?;
+    // Highlights are still exported, ungrouped, if the file can no longer be read
+    let sections = match crate::document::parser::parse_document(&path).await {
+        Ok(doc) => crate::document::segment_sections(&doc),
+        Err(e) => {
+            tracing::warn!("Exporting study page without sections: {}", e);
+            Vec::new()
+        }
+    };
+
+    let annotations = crate::storage::get_annotations(&app, &document_id).await?;
+    let html = crate::annotation::export::to_study_page(&annotations, &title, &sections);
+    std::fs::write(&output_path, html)?;
+
+    let listed = annotations.iter().filter(|a| crate::annotation::export::on_study_page(a));
+    Ok(listed.count())
+}
+
+/// Title to head an export with, falling back to the file name, and the document's path
+fn export_title_and_path(app: &AppHandle, document_id: &str) -> Result<(String, String), AppError> {
+    let (title, path) = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        (
+            crate::storage::get_document_title(&conn, document_id)?,
+            crate::storage::get_document_path(&conn, document_id)?,
+        )
+    };
+    let title = title.filter(|t| !t.trim().is_empty()).unwrap_or_else(|| {
+        std::path::Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or(path.clone())
+    });
+    Ok((title, path))
+}
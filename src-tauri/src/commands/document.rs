@@ -1,25 +1,195 @@
 //! Document-related Tauri commands
 
-use crate::document::{Document, DocumentMetadata, RecentDocument};
+use crate::document::{
+    Document, DocumentLink, DocumentMetadata, PageMatches, PageSource, Paragraph,
+    ParagraphChange, ParseOptions, ReadingAnalytics, ReadingMode, RecentDocument, Section,
+    SectionDifficulty, SelectionContext, TOCEntry,
+};
+use crate::annotation::ParagraphRemap;
+use crate::document::editor::{watch_dir, FileWatcher};
+use crate::document::DocumentType;
 use crate::error::AppError;
-use tauri::AppHandle;
+use crate::storage::Database;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::oneshot;
+use tokio::task::JoinSet;
 
-/// Open a document and return its parsed content
+/// Interval between size checks while waiting for a new file to finish writing
+const STABLE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Give up on a new file that is still changing after this long
+const STABLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Pages `ocr_document` recognizes at the same time
+const OCR_CONCURRENCY: usize = 4;
+
+/// Active folder watchers, keyed by folder path
+pub struct LibraryWatchers {
+    watchers: Mutex<HashMap<String, FileWatcher>>,
+}
+
+impl LibraryWatchers {
+    pub fn new() -> Self {
+        Self {
+            watchers: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for LibraryWatchers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A running `ocr_document` job
+struct OcrJob {
+    cancel: oneshot::Sender<()>,
+    /// Set on cancellation so page recognition already handed to a blocking thread
+    /// stops before its next pdftoppm or Tesseract run
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Cancellation handles for running `ocr_document` jobs, keyed by document id
+pub struct OcrJobs {
+    jobs: Mutex<HashMap<String, OcrJob>>,
+}
+
+impl OcrJobs {
+    pub fn new() -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Register a job for a document, failing if one is already running for it
+    fn start(
+        &self,
+        document_id: &str,
+    ) -> Result<(oneshot::Receiver<()>, Arc<AtomicBool>), AppError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.contains_key(document_id) {
+            return Err(crate::error::DocumentError::ParseError(format!(
+                "OCR is already running for document {}",
+                document_id
+            ))
+            .into());
+        }
+        let (cancel, cancel_rx) = oneshot::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        jobs.insert(
+            document_id.to_string(),
+            OcrJob {
+                cancel,
+                cancelled: cancelled.clone(),
+            },
+        );
+        Ok((cancel_rx, cancelled))
+    }
+
+    /// Drop a finished job's entry, unless it was cancelled and another job started since
+    fn finish(&self, document_id: &str, cancelled: &Arc<AtomicBool>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.get(document_id).is_some_and(|job| Arc::ptr_eq(&job.cancelled, cancelled)) {
+            jobs.remove(document_id);
+        }
+    }
+
+    /// Cancel a document's job; returns whether one was running
+    fn cancel(&self, document_id: &str) -> bool {
+        let Some(job) = self.jobs.lock().unwrap().remove(document_id) else {
+            return false;
+        };
+        job.cancelled.store(true, Ordering::SeqCst);
+        job.cancel.send(()).is_ok()
+    }
+}
+
+impl Default for OcrJobs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Open a document and return its parsed content. A summary is generated in the
+/// background if `auto_summary` is set, or when it is absent, if the setting is on.
 #[tauri::command]
 pub async fn open_document(
     app: AppHandle,
     path: String,
+    normalize_unicode: Option<bool>,
+    auto_summary: Option<bool>,
 ) -> Result<Document, AppError> {
     tracing::info!("Opening document: {}", path);
+
+    let mut options = ParseOptions::default();
+    if let Some(normalize) = normalize_unicode {
+        options.normalize_unicode = normalize;
+    }
+    // Reuse text recognized by an earlier `ocr_document` rather than OCR'ing again
+    if path.to_lowercase().ends_with(".pdf") {
+        let hash_path = path.clone();
+        let id = tokio::task::spawn_blocking(move || crate::document::hash_file(&hash_path))
+            .await
+            .map_err(std::io::Error::other)??;
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        options.ocr_pages = crate::storage::get_ocr_pages(&conn, &id)?;
+        // Pages a cancelled or partly failed run missed wait for the next `ocr_document`
+        options.ocr_uncached_pages = options.ocr_pages.is_empty();
+    }
+    let (document, id_map) =
+        crate::document::parser::parse_document_with_id_map(&path, &options).await?;
     
-    let document = crate::document::parser::parse_document(&path).await?;
-    
+    app.state::<crate::commands::annotation::AnnotationState>()
+        .record_document(&document);
+
     // Store in recent documents
     crate::storage::add_recent_document(&app, &document).await?;
+    crate::storage::save_page_sources(&app, &document.id, &document.page_sources()).await?;
+
+    // Point annotations saved against positional paragraph ids at the stable ids
+    crate::storage::migrate_annotation_paragraph_ids(&app, &document.id, &id_map).await?;
+
+    let auto_summary = match auto_summary {
+        Some(auto_summary) => auto_summary,
+        None => {
+            let db = app.state::<Database>();
+            let conn = db.conn.lock().unwrap();
+            crate::storage::get_setting(&conn, crate::settings::AUTO_SUMMARY)?
+        }
+    };
+    if auto_summary {
+        crate::commands::llm::spawn_document_summary(app.clone(), document.clone());
+    }
     
     Ok(document)
 }
 
+/// Get a document's id without parsing it
+#[tauri::command]
+pub async fn get_document_id(path: String) -> Result<String, AppError> {
+    tracing::debug!("Hashing document: {}", path);
+
+    tokio::task::spawn_blocking(move || crate::document::hash_file(&path))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// Check whether a PDF is scanned, and so will need OCR, before opening it
+#[tauri::command]
+pub async fn is_scanned_pdf(path: String) -> Result<crate::document::ScanStatus, AppError> {
+    tokio::task::spawn_blocking(move || crate::document::is_scanned_pdf(&path))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
 /// Get the content of a specific page
 #[tauri::command]
 pub async fn get_document_content(
@@ -43,6 +213,890 @@ pub async fn get_document_metadata(
     Ok(DocumentMetadata::default())
 }
 
+/// Set a custom metadata field, such as "read_status", on a document
+#[tauri::command]
+pub async fn set_document_meta(
+    app: AppHandle,
+    document_id: String,
+    key: String,
+    value: String,
+) -> Result<(), AppError> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(crate::error::DocumentError::EmptyMetadataKey.into());
+    }
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    if !crate::storage::document_exists(&conn, &document_id)? {
+        return Err(crate::error::DocumentError::InvalidId.into());
+    }
+    crate::storage::set_document_meta(&conn, &document_id, key, &value)
+}
+
+/// Get a document's custom metadata fields, by key
+#[tauri::command]
+pub async fn get_document_meta(
+    app: AppHandle,
+    document_id: String,
+) -> Result<BTreeMap<String, String>, AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_document_meta(&conn, &document_id)
+}
+
+/// Tag a document with a topic, e.g. one accepted from `suggest_tags`
+#[tauri::command]
+pub async fn add_tag(app: AppHandle, document_id: String, tag: String) -> Result<(), AppError> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(crate::error::DocumentError::EmptyTag.into());
+    }
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    if !crate::storage::document_exists(&conn, &document_id)? {
+        return Err(crate::error::DocumentError::InvalidId.into());
+    }
+    crate::storage::add_tag(&conn, &document_id, tag)
+}
+
+/// Get a document's tags
+#[tauri::command]
+pub async fn get_tags(app: AppHandle, document_id: String) -> Result<Vec<String>, AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_tags(&conn, &document_id)
+}
+
+/// Start timing on-screen reading of a document, returning the session id
+#[tauri::command]
+pub async fn start_manual_reading(
+    app: AppHandle,
+    document_id: String,
+    page: u32,
+) -> Result<String, AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    let session_id = crate::storage::start_reading_session(
+        &conn,
+        &document_id,
+        ReadingMode::Manual,
+        chrono::Utc::now(),
+    )?;
+    crate::storage::record_reading_pages(&conn, &session_id, &[page])?;
+
+    Ok(session_id)
+}
+
+/// Note that the reader turned to `page` during a manual reading session
+#[tauri::command]
+pub async fn record_page_read(
+    app: AppHandle,
+    session_id: String,
+    page: u32,
+) -> Result<(), AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_open_reading_session(&conn, &session_id)?;
+    crate::storage::record_reading_pages(&conn, &session_id, &[page])
+}
+
+/// End a manual reading session, counting the words on the pages it covered
+#[tauri::command]
+pub async fn stop_manual_reading(app: AppHandle, session_id: String) -> Result<(), AppError> {
+    let ended_at = chrono::Utc::now();
+    let (path, pages) = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        let (document_id, pages) = crate::storage::get_open_reading_session(&conn, &session_id)?;
+        (crate::storage::get_document_path(&conn, &document_id)?, pages)
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+    let words_read = document
+        .pages
+        .iter()
+        .filter(|p| pages.contains(&p.number))
+        .map(|p| p.text.split_whitespace().count() as u32)
+        .sum();
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::finish_reading_session(&conn, &session_id, ended_at, words_read)
+}
+
+/// Total reading time, pages covered and average pace for a document, over both spoken
+/// and manual reading
+#[tauri::command]
+pub async fn get_reading_analytics(
+    app: AppHandle,
+    document_id: String,
+) -> Result<ReadingAnalytics, AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    let sessions = crate::storage::get_reading_sessions(&conn, &document_id)?;
+
+    Ok(ReadingAnalytics::from_sessions(&document_id, &sessions))
+}
+
+/// Report per page whether its text came from the PDF's text layer or from OCR
+#[tauri::command]
+pub async fn get_page_sources(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Vec<PageSource>, AppError> {
+    let path = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        let sources = crate::storage::get_page_sources(&conn, &document_id)?;
+        if !sources.is_empty() {
+            return Ok(sources);
+        }
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+
+    // Opened before sources were recorded; parse once and remember the result
+    let document = crate::document::parser::parse_document(&path).await?;
+    let sources = document.page_sources();
+    crate::storage::save_page_sources(&app, &document_id, &sources).await?;
+
+    Ok(sources)
+}
+
+/// Progress of `ocr_document`, the payload of `ocr:progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrProgress {
+    pub document_id: String,
+    /// Page that just finished
+    pub page: u32,
+    pub pages_done: u32,
+    pub page_count: u32,
+    /// Share of pages finished, from 0 to 1
+    pub progress: f32,
+    /// Why the page could not be recognized
+    pub error: Option<String>,
+}
+
+/// Outcome of `ocr_document`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrReport {
+    pub document_id: String,
+    pub page_count: u32,
+    /// Pages whose text was recognized and cached
+    pub recognized: Vec<u32>,
+    pub failed: Vec<u32>,
+    /// Whether the run was stopped before every page finished
+    pub cancelled: bool,
+}
+
+/// Recognize pages 1 to `page_count` with `ocr_page`, a few at a time, caching each
+/// page's text as it finishes and reporting it to `on_progress`. When `cancel` fires,
+/// pages still running are abandoned and those already cached are kept.
+async fn ocr_pages_into<F, Fut>(
+    db: &Database,
+    document_id: &str,
+    page_count: u32,
+    language: &str,
+    ocr_page: F,
+    mut on_progress: impl FnMut(&OcrProgress),
+    mut cancel: oneshot::Receiver<()>,
+) -> Result<OcrReport, AppError>
+where
+    F: Fn(u32) -> Fut,
+    Fut: std::future::Future<Output = Result<String, AppError>> + Send + 'static,
+{
+    let mut report = OcrReport {
+        document_id: document_id.to_string(),
+        page_count,
+        recognized: Vec::new(),
+        failed: Vec::new(),
+        cancelled: false,
+    };
+
+    let mut pages = 1..=page_count;
+    let mut running = JoinSet::new();
+    let start = |running: &mut JoinSet<_>, page: u32| {
+        let recognition = ocr_page(page);
+        running.spawn(async move { (page, recognition.await) });
+    };
+    for page in pages.by_ref().take(OCR_CONCURRENCY) {
+        start(&mut running, page);
+    }
+
+    loop {
+        let finished = tokio::select! {
+            finished = running.join_next() => finished,
+            _ = &mut cancel => {
+                running.abort_all();
+                report.cancelled = true;
+                break;
+            }
+        };
+        let Some(finished) = finished else {
+            break;
+        };
+        let (page, result) = finished.map_err(std::io::Error::other)?;
+        if let Some(next) = pages.next() {
+            start(&mut running, next);
+        }
+
+        let error = match result {
+            Ok(text) => {
+                let conn = db.conn.lock().unwrap();
+                crate::storage::save_ocr_page(&conn, document_id, page, language, &text)?;
+                report.recognized.push(page);
+                None
+            }
+            Err(e) => {
+                tracing::warn!("OCR failed for page {} of {}: {}", page, document_id, e);
+                report.failed.push(page);
+                Some(e.to_string())
+            }
+        };
+        let pages_done = (report.recognized.len() + report.failed.len()) as u32;
+        on_progress(&OcrProgress {
+            document_id: document_id.to_string(),
+            page,
+            pages_done,
+            page_count,
+            progress: pages_done as f32 / page_count as f32,
+            error,
+        });
+    }
+
+    report.recognized.sort_unstable();
+    report.failed.sort_unstable();
+    Ok(report)
+}
+
+/// OCR every page of a scanned PDF, a few pages at a time, emitting `ocr:progress` as
+/// each page finishes. The text is cached, so the document opens with it from then on,
+/// and the stored page count, word count and page sources are updated. Stop with
+/// `cancel_ocr`; pages finished by then stay cached. Only one run per document is
+/// allowed at a time. Annotations are pointed at the reparsed paragraph ids afterwards.
+#[tauri::command]
+pub async fn ocr_document(
+    app: AppHandle,
+    jobs: State<'_, OcrJobs>,
+    document_id: String,
+    language: String,
+) -> Result<OcrReport, AppError> {
+    let path = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+    let page_count = lopdf::Document::load(&path)
+        .map_err(|e| crate::error::DocumentError::ParseError(format!("{}: {}", path, e)))?
+        .get_pages()
+        .len() as u32;
+    tracing::info!("OCR'ing {} pages of {} in {}", page_count, path, language);
+
+    let (cancel_rx, cancelled) = jobs.start(&document_id)?;
+    let config = crate::document::ocr::OcrConfig {
+        language: language.clone(),
+        cancelled: Some(cancelled.clone()),
+        ..Default::default()
+    };
+    let runtime = tokio::runtime::Handle::current();
+    let ocr_page = |page: u32| {
+        let (path, config, runtime) = (path.clone(), config.clone(), runtime.clone());
+        async move {
+            // pdftoppm and Tesseract run as blocking child processes, which aborting
+            // the task cannot stop; `config.cancelled` ends them between runs instead
+            let result = tokio::task::spawn_blocking(move || {
+                runtime.block_on(crate::document::ocr::ocr_pdf_page(&path, page, &config))
+            })
+            .await
+            .map_err(std::io::Error::other)??;
+            Ok(result.text)
+        }
+    };
+
+    let db = app.state::<Database>();
+    let result = ocr_pages_into(
+        &db,
+        &document_id,
+        page_count,
+        &language,
+        ocr_page,
+        |progress| {
+            let _ = app.emit("ocr:progress", progress);
+        },
+        cancel_rx,
+    )
+    .await;
+    jobs.finish(&document_id, &cancelled);
+    let report = result?;
+
+    if !report.recognized.is_empty() {
+        let ocr_pages = {
+            let conn = db.conn.lock().unwrap();
+            crate::storage::get_ocr_pages(&conn, &document_id)?
+        };
+        // Pages the run did not recognize are left for a later run, not OCR'd here
+        let options = ParseOptions {
+            ocr_pages,
+            ocr_uncached_pages: false,
+            ..Default::default()
+        };
+        let (document, id_map) =
+            crate::document::parser::parse_document_with_id_map(&path, &options).await?;
+        let conn = db.conn.lock().unwrap();
+        crate::storage::update_document_counts(
+            &conn,
+            &document_id,
+            document.metadata.page_count,
+            document.metadata.word_count,
+        )?;
+        crate::storage::replace_page_sources(&conn, &document_id, &document.page_sources())?;
+        crate::storage::remap_paragraph_ids(&conn, &document_id, &id_map)?;
+    }
+
+    Ok(report)
+}
+
+/// Stop a running `ocr_document`; returns whether one was running for the document
+#[tauri::command]
+pub async fn cancel_ocr(jobs: State<'_, OcrJobs>, document_id: String) -> Result<bool, AppError> {
+    Ok(jobs.cancel(&document_id))
+}
+
+/// Get a unified outline (bookmarks, TOC, or headings) for a document
+#[tauri::command]
+pub async fn get_document_outline(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Vec<TOCEntry>, AppError> {
+    tracing::debug!("Getting outline for document {}", document_id);
+
+    let path = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    Ok(crate::document::get_outline(&document))
+}
+
+/// Estimate per-section difficulty and reading time for study planning
+#[tauri::command]
+pub async fn get_section_difficulty(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Vec<SectionDifficulty>, AppError> {
+    tracing::debug!("Estimating section difficulty for document {}", document_id);
+
+    let path = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    Ok(crate::document::section_difficulty(&document))
+}
+
+/// Split a document into sections under its headings, or by topic when it has none
+#[tauri::command]
+pub async fn get_document_sections(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Vec<Section>, AppError> {
+    let path = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    Ok(crate::document::segment_sections(&document))
+}
+
+/// Find a query in a document's page text, returning the character offsets of each
+/// match per page so it can be highlighted in place
+#[tauri::command]
+pub async fn search_in_document(
+    app: AppHandle,
+    document_id: String,
+    query: String,
+    case_sensitive: bool,
+) -> Result<Vec<PageMatches>, AppError> {
+    let path = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    Ok(crate::document::search_document(&document, &query, case_sensitive))
+}
+
+/// Reparse a document and point its annotations at the current paragraph ids.
+///
+/// `previous_paragraphs` are the paragraphs the annotations were made against, e.g. from
+/// the copy of the document the frontend still holds; their ids are matched to new
+/// paragraphs by text. Annotations whose paragraph cannot be found are flagged unmatched.
+#[tauri::command]
+pub async fn regenerate_paragraph_ids(
+    app: AppHandle,
+    document_id: String,
+    previous_paragraphs: Option<Vec<Paragraph>>,
+) -> Result<ParagraphRemap, AppError> {
+    let path = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+    let (document, mut id_map) =
+        crate::document::parser::parse_document_with_id_map(&path, &ParseOptions::default())
+            .await?;
+
+    if let Some(previous) = &previous_paragraphs {
+        let current = document.pages.iter().flat_map(|page| &page.paragraphs);
+        id_map.extend(crate::document::parser::match_paragraph_ids(previous, current));
+    }
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::remap_paragraph_ids(&conn, &document_id, &id_map)
+}
+
+/// Paragraphs added, removed or changed between two versions of a document, such as an
+/// old and an updated preprint
+#[tauri::command]
+pub async fn compare_document_versions(
+    path_a: String,
+    path_b: String,
+) -> Result<Vec<ParagraphChange>, AppError> {
+    tracing::info!("Comparing {} with {}", path_a, path_b);
+
+    let old = crate::document::parser::parse_document(&path_a).await?;
+    let new = crate::document::parser::parse_document(&path_b).await?;
+
+    Ok(crate::document::diff_documents(&old, &new))
+}
+
+/// List the URLs, DOIs and arXiv ids mentioned in a document, with the pages they are on
+#[tauri::command]
+pub async fn get_document_links(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Vec<DocumentLink>, AppError> {
+    let path = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    Ok(crate::document::extract_links(&document))
+}
+
+/// Resolve a selection's character offsets on a page to its text and paragraph
+#[tauri::command]
+pub async fn get_selection_context(
+    app: AppHandle,
+    document_id: String,
+    page: u32,
+    start_offset: usize,
+    end_offset: usize,
+) -> Result<SelectionContext, AppError> {
+    let path = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    Ok(crate::document::resolve_selection(
+        &document,
+        page,
+        start_offset,
+        end_offset,
+    )?)
+}
+
+/// Outcome of importing one file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    Imported,
+    /// Same content as an already registered document
+    Duplicate,
+    Failed,
+}
+
+/// Per-file result of a folder import
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedFile {
+    pub path: String,
+    pub status: ImportStatus,
+    pub document_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Progress of a folder import, emitted as `document:import_progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportProgress {
+    /// Files handled so far, including this one
+    pub processed: usize,
+    pub total: usize,
+    pub file: ImportedFile,
+}
+
+/// Supported document files under `dir`, in path order
+fn find_supported_files(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>, AppError> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if hidden {
+                continue;
+            }
+
+            if path.is_dir() {
+                if recursive {
+                    dirs.push(path);
+                }
+            } else if is_importable(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Hash, parse and register one file unless its content is already known
+async fn import_file(
+    db: &Database,
+    path: &str,
+    seen: &mut HashSet<String>,
+) -> Result<(ImportStatus, String), AppError> {
+    let hash_path = path.to_string();
+    let id = tokio::task::spawn_blocking(move || crate::document::hash_file(&hash_path))
+        .await
+        .map_err(std::io::Error::other)??;
+
+    let known = {
+        let conn = db.conn.lock().unwrap();
+        crate::storage::document_exists(&conn, &id)?
+    };
+    if known || !seen.insert(id.clone()) {
+        return Ok((ImportStatus::Duplicate, id));
+    }
+
+    // Files that differ only in metadata have different bytes but the same text
+    let document = crate::document::parser::parse_document(path).await?;
+    let fingerprint = crate::document::content_fingerprint(&document);
+    let conn = db.conn.lock().unwrap();
+    if let Some(existing) = crate::storage::find_document_by_fingerprint(&conn, &fingerprint)? {
+        return Ok((ImportStatus::Duplicate, existing));
+    }
+    crate::storage::upsert_document(&conn, &document)?;
+    crate::storage::replace_page_sources(&conn, &document.id, &document.page_sources())?;
+
+    Ok((ImportStatus::Imported, document.id))
+}
+
+/// Import every supported file in a folder, reporting each file through `on_progress`
+async fn import_folder_into<F>(
+    db: &Database,
+    dir: &Path,
+    recursive: bool,
+    mut on_progress: F,
+) -> Result<Vec<ImportedFile>, AppError>
+where
+    F: FnMut(&ImportProgress),
+{
+    if !dir.is_dir() {
+        return Err(crate::error::DocumentError::FileNotFound(dir.display().to_string()).into());
+    }
+
+    let files = find_supported_files(dir, recursive)?;
+    let total = files.len();
+    let mut seen = HashSet::new();
+    let mut report = Vec::with_capacity(total);
+
+    for (i, path) in files.iter().enumerate() {
+        let path = path.to_string_lossy().to_string();
+        let file = match import_file(db, &path, &mut seen).await {
+            Ok((status, id)) => ImportedFile {
+                path,
+                status,
+                document_id: Some(id),
+                error: None,
+            },
+            Err(e) => {
+                tracing::warn!("Failed to import {}: {}", path, e);
+                ImportedFile {
+                    path,
+                    status: ImportStatus::Failed,
+                    document_id: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+
+        on_progress(&ImportProgress {
+            processed: i + 1,
+            total,
+            file: file.clone(),
+        });
+        report.push(file);
+    }
+
+    Ok(report)
+}
+
+/// Register every supported document in a folder as a recent document
+#[tauri::command]
+pub async fn import_folder(
+    app: AppHandle,
+    path: String,
+    recursive: bool,
+) -> Result<Vec<ImportedFile>, AppError> {
+    tracing::info!("Importing documents from {} (recursive: {})", path, recursive);
+
+    let db = app.state::<Database>();
+    let report = import_folder_into(&db, Path::new(&path), recursive, |progress| {
+        let _ = app.emit("document:import_progress", progress);
+    })
+    .await?;
+
+    let imported = report
+        .iter()
+        .filter(|f| f.status == ImportStatus::Imported)
+        .count();
+    tracing::info!("Imported {} of {} files from {}", imported, report.len(), path);
+
+    Ok(report)
+}
+
+/// Whether `path` is a regular, non-hidden file of a supported type
+fn is_importable(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+    !hidden
+        && path.is_file()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(DocumentType::from_extension)
+            .is_some()
+}
+
+/// Wait until a file's size and modification time stop changing, so
+/// half-copied downloads are not parsed
+async fn wait_until_stable(path: &Path) -> Result<(), AppError> {
+    let snapshot = |path: &Path| {
+        std::fs::metadata(path).map(|m| (m.len(), m.modified().ok()))
+    };
+
+    let start = std::time::Instant::now();
+    let mut last = snapshot(path)?;
+    loop {
+        tokio::time::sleep(STABLE_POLL_INTERVAL).await;
+        let current = snapshot(path)?;
+        if current == last && current.0 > 0 {
+            return Ok(());
+        }
+        if start.elapsed() > STABLE_TIMEOUT {
+            return Err(crate::error::DocumentError::ParseError(format!(
+                "{} is still being written",
+                path.display()
+            ))
+            .into());
+        }
+        last = current;
+    }
+}
+
+/// Import a file that appeared in a watched folder once it has finished writing
+async fn import_when_stable(db: &Database, path: &Path) -> Result<ImportedFile, AppError> {
+    wait_until_stable(path).await?;
+
+    let path = path.to_string_lossy().to_string();
+    let (status, id) = import_file(db, &path, &mut HashSet::new()).await?;
+
+    Ok(ImportedFile {
+        path,
+        status,
+        document_id: Some(id),
+        error: None,
+    })
+}
+
+/// Watch `dir` and call `on_new_file` for each supported file created or changed in it
+fn watch_library_folder<F>(dir: &str, on_new_file: F) -> Result<FileWatcher, AppError>
+where
+    F: Fn(PathBuf) + Send + 'static,
+{
+    watch_dir(dir, move |paths| {
+        for path in paths.into_iter().filter(|p| is_importable(p)) {
+            on_new_file(path);
+        }
+    })
+    .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()).into())
+}
+
+/// Paths with an import already waiting for the file to settle
+#[derive(Clone, Default)]
+struct PendingImports(Arc<Mutex<HashSet<PathBuf>>>);
+
+impl PendingImports {
+    /// Claim `path`, or `None` when an import of it is already pending
+    fn claim(&self, path: &Path) -> Option<PendingImport> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf())
+            .then(|| PendingImport {
+                pending: self.clone(),
+                path: path.to_path_buf(),
+            })
+    }
+}
+
+/// A claimed path, released when the import finishes
+struct PendingImport {
+    pending: PendingImports,
+    path: PathBuf,
+}
+
+impl Drop for PendingImport {
+    fn drop(&mut self) {
+        self.pending.0.lock().unwrap().remove(&self.path);
+    }
+}
+
+/// Start watching a folder, importing new files and emitting `library:document_added`
+fn start_folder_watch(app: &AppHandle, dir: &str) -> Result<(), AppError> {
+    let handle = app.clone();
+    let pending = PendingImports::default();
+    let watcher = watch_library_folder(dir, move |path| {
+        // A file being written fires several events; one import waits for all of them
+        let Some(claim) = pending.claim(&path) else {
+            return;
+        };
+        let app = handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let _claim = claim;
+            let db = app.state::<Database>();
+            match import_when_stable(&db, &path).await {
+                Ok(file) if file.status == ImportStatus::Imported => {
+                    tracing::info!("Imported new document {}", file.path);
+                    let _ = app.emit("library:document_added", &file);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to import {}: {}", path.display(), e),
+            }
+        });
+    })?;
+
+    app.state::<LibraryWatchers>()
+        .watchers
+        .lock()
+        .unwrap()
+        .insert(dir.to_string(), watcher);
+    Ok(())
+}
+
+/// Watch a folder and import supported documents added to it; persists across restarts
+#[tauri::command]
+pub async fn watch_folder(app: AppHandle, path: String) -> Result<(), AppError> {
+    tracing::info!("Watching folder {}", path);
+
+    if !Path::new(&path).is_dir() {
+        return Err(crate::error::DocumentError::FileNotFound(path).into());
+    }
+
+    start_folder_watch(&app, &path)?;
+
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::add_watched_folder(&conn, &path)
+}
+
+/// Stop watching a folder
+#[tauri::command]
+pub async fn unwatch_folder(app: AppHandle, path: String) -> Result<(), AppError> {
+    tracing::info!("No longer watching folder {}", path);
+
+    app.state::<LibraryWatchers>().watchers.lock().unwrap().remove(&path);
+
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::remove_watched_folder(&conn, &path)
+}
+
+/// List the folders being watched for new documents
+#[tauri::command]
+pub async fn get_watched_folders(app: AppHandle) -> Result<Vec<String>, AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_watched_folders(&conn)
+}
+
+/// Restart watchers for the folders persisted by `watch_folder`
+pub fn resume_watched_folders(app: &AppHandle) {
+    let folders = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        match crate::storage::get_watched_folders(&conn) {
+            Ok(folders) => folders,
+            Err(e) => {
+                tracing::error!("Failed to load watched folders: {}", e);
+                return;
+            }
+        }
+    };
+
+    for folder in folders {
+        match start_folder_watch(app, &folder) {
+            Ok(()) => tracing::info!("Resumed watching {}", folder),
+            Err(e) => tracing::warn!("Could not resume watching {}: {}", folder, e),
+        }
+    }
+}
+
+/// Compute the content fingerprints missing from documents registered before they were
+/// stored, so imports recognize those documents. Files that are gone or have changed
+/// since they were registered are left for `open_document` to fingerprint.
+pub async fn backfill_fingerprints(db: &Database) -> Result<usize, AppError> {
+    let missing = {
+        let conn = db.conn.lock().unwrap();
+        crate::storage::documents_without_fingerprint(&conn)?
+    };
+
+    let mut filled = 0;
+    for (id, path) in missing {
+        let document = match crate::document::parser::parse_document(&path).await {
+            Ok(document) if document.id == id => document,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::debug!("Cannot fingerprint {}: {}", path, e);
+                continue;
+            }
+        };
+        let fingerprint = crate::document::content_fingerprint(&document);
+        let conn = db.conn.lock().unwrap();
+        crate::storage::set_content_fingerprint(&conn, &id, &fingerprint)?;
+        filled += 1;
+    }
+    Ok(filled)
+}
+
 /// Get list of recently opened documents
 #[tauri::command]
 pub async fn get_recent_documents(
@@ -54,3 +1108,297 @@ pub async fn get_recent_documents(
     
     crate::storage::get_recent_documents(&app, limit).await
 }
+
+/// Remove a document from the library with its annotations, comments, chats and other
+/// saved data. The file itself is left alone.
+#[tauri::command]
+pub async fn delete_document(app: AppHandle, document_id: String) -> Result<(), AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    if !crate::storage::delete_document(&conn, &document_id)? {
+        return Err(crate::error::DocumentError::InvalidId.into());
+    }
+    tracing::info!("Deleted document {}", document_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    #[tokio::test]
+    async fn test_import_folder_skips_unsupported_and_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("a.txt"), "First document.").unwrap();
+        std::fs::write(root.join("a-copy.txt"), "First document.").unwrap();
+        std::fs::write(root.join("b.md"), "# Second\n\nBody text.").unwrap();
+        std::fs::write(root.join("image.png"), [0x89, 0x50, 0x4e, 0x47]).unwrap();
+        std::fs::write(root.join("notes.xyz"), "not a document").unwrap();
+        std::fs::create_dir(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub").join("c.txt"), "Nested document.").unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::run_migrations(&conn).unwrap();
+        let db = Database::new(conn);
+
+        let mut progress = Vec::new();
+        let report = import_folder_into(&db, root, true, |p| progress.push(p.processed))
+            .await
+            .unwrap();
+
+        let names: Vec<(String, ImportStatus)> = report
+            .iter()
+            .map(|f| {
+                let name = Path::new(&f.path).strip_prefix(root).unwrap();
+                (name.to_string_lossy().replace('\\', "/"), f.status)
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("a-copy.txt".to_string(), ImportStatus::Imported),
+                ("a.txt".to_string(), ImportStatus::Duplicate),
+                ("b.md".to_string(), ImportStatus::Imported),
+                ("sub/c.txt".to_string(), ImportStatus::Imported),
+            ]
+        );
+        assert_eq!(progress, [1, 2, 3, 4]);
+        {
+            let conn = db.conn.lock().unwrap();
+            let count: i64 = conn
+                .query_row("SELECT COUNT(*) FROM documents", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(count, 3);
+        }
+
+        // Re-importing finds everything already registered
+        let again = import_folder_into(&db, root, false, |_| {}).await.unwrap();
+        assert_eq!(again.len(), 3);
+        assert!(again.iter().all(|f| f.status == ImportStatus::Duplicate));
+    }
+
+    #[tokio::test]
+    async fn test_fingerprints_backfilled_for_existing_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "Registered before fingerprints.").unwrap();
+        let path = path.to_str().unwrap();
+        let document = crate::document::parser::parse_document(path).await.unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::run_migrations(&conn).unwrap();
+        crate::storage::upsert_document(&conn, &document).unwrap();
+        conn.execute("UPDATE documents SET content_fingerprint = NULL", []).unwrap();
+        conn.execute(
+            "INSERT INTO documents (id, file_path) VALUES ('gone', '/missing/file.txt')",
+            [],
+        )
+        .unwrap();
+        let db = Database::new(conn);
+
+        assert_eq!(backfill_fingerprints(&db).await.unwrap(), 1);
+        let conn = db.conn.lock().unwrap();
+        let fingerprint = crate::document::content_fingerprint(&document);
+        let found = crate::storage::find_document_by_fingerprint(&conn, &fingerprint).unwrap();
+        assert_eq!(found, Some(document.id));
+    }
+
+    #[tokio::test]
+    async fn test_pdfs_differing_only_in_metadata_share_fingerprint() {
+        use crate::document::editor::{ConversionUtils, PDFUtils};
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("paper.md");
+        std::fs::write(&source, "# Sparse Attention\n\nWe make attention cheaper.").unwrap();
+        let original = dir.path().join("a.pdf");
+        let redownloaded = dir.path().join("b.pdf");
+        let (original, redownloaded) =
+            (original.to_str().unwrap(), redownloaded.to_str().unwrap());
+        ConversionUtils::markdown_to_pdf(source.to_str().unwrap(), original)
+            .await
+            .unwrap();
+        let update = crate::document::PdfMetadataUpdate {
+            title: Some("Sparse Attention".to_string()),
+            ..Default::default()
+        };
+        PDFUtils::set_metadata(original, redownloaded, &update).await.unwrap();
+        std::fs::remove_file(&source).unwrap();
+
+        let a = crate::document::parser::parse_document(original).await.unwrap();
+        let b = crate::document::parser::parse_document(redownloaded).await.unwrap();
+        assert_ne!(a.id, b.id);
+        assert_eq!(
+            crate::document::content_fingerprint(&a),
+            crate::document::content_fingerprint(&b)
+        );
+
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::run_migrations(&conn).unwrap();
+        let db = Database::new(conn);
+        let report = import_folder_into(&db, dir.path(), false, |_| {}).await.unwrap();
+        let statuses: Vec<ImportStatus> = report.iter().map(|f| f.status).collect();
+        assert_eq!(statuses, [ImportStatus::Imported, ImportStatus::Duplicate]);
+        assert_eq!(report[1].document_id, Some(a.id));
+    }
+
+    #[tokio::test]
+    async fn test_new_file_in_watched_folder_is_imported() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::run_migrations(&conn).unwrap();
+        let db = std::sync::Arc::new(Database::new(conn));
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let runtime = tokio::runtime::Handle::current();
+        let import_db = db.clone();
+        let _watcher = watch_library_folder(dir.path().to_str().unwrap(), move |path| {
+            let db = import_db.clone();
+            let tx = tx.clone();
+            runtime.spawn(async move {
+                let _ = tx.send(import_when_stable(&db, &path).await);
+            });
+        })
+        .unwrap();
+
+        std::fs::write(dir.path().join("ignored.xyz"), "not a document").unwrap();
+        std::fs::write(dir.path().join("paper.md"), "# New paper\n\nFresh results.").unwrap();
+
+        let added = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .expect("expected an import event")
+            .unwrap()
+            .unwrap();
+        assert_eq!(added.status, ImportStatus::Imported);
+        assert!(added.path.ends_with("paper.md"));
+
+        let conn = db.conn.lock().unwrap();
+        let id = added.document_id.unwrap();
+        assert!(crate::storage::document_exists(&conn, &id).unwrap());
+    }
+
+    #[test]
+    fn test_repeated_events_for_a_pending_file_are_skipped() {
+        let pending = PendingImports::default();
+        let path = Path::new("/library/paper.pdf");
+
+        let claim = pending.claim(path).unwrap();
+        assert!(pending.claim(path).is_none());
+        assert!(pending.claim(Path::new("/library/other.pdf")).is_some());
+
+        // Once the import finishes, a later change is imported again
+        drop(claim);
+        assert!(pending.claim(path).is_some());
+    }
+
+    fn ocr_test_db() -> Database {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::storage::run_migrations(&conn).unwrap();
+        Database::new(conn)
+    }
+
+    #[tokio::test]
+    async fn test_ocr_document_processes_every_page_and_caches_text() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let db = ocr_test_db();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most_in_flight = Arc::new(AtomicUsize::new(0));
+        let ocr_page = |page: u32| {
+            let (in_flight, most_in_flight) = (in_flight.clone(), most_in_flight.clone());
+            async move {
+                let running = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                most_in_flight.fetch_max(running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5 * u64::from(page % 3))).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                match page {
+                    7 => Err(crate::error::DocumentError::ParseError("blank".to_string()).into()),
+                    _ => Ok(format!("Text of page {}", page)),
+                }
+            }
+        };
+
+        let mut progress = Vec::new();
+        let (_cancel_tx, cancel_rx) = oneshot::channel();
+        let report = ocr_pages_into(
+            &db,
+            "scan",
+            10,
+            "eng",
+            ocr_page,
+            |p| progress.push(p.clone()),
+            cancel_rx,
+        )
+        .await
+        .unwrap();
+
+        assert!(!report.cancelled);
+        assert_eq!(report.recognized, [1, 2, 3, 4, 5, 6, 8, 9, 10]);
+        assert_eq!(report.failed, [7]);
+        let most = most_in_flight.load(Ordering::SeqCst);
+        assert!(most > 1 && most <= OCR_CONCURRENCY, "{} pages at once", most);
+
+        let mut pages: Vec<u32> = progress.iter().map(|p| p.page).collect();
+        pages.sort_unstable();
+        assert_eq!(pages, (1..=10).collect::<Vec<_>>());
+        assert!(progress.windows(2).all(|w| w[0].progress < w[1].progress));
+        assert_eq!(progress.last().unwrap().progress, 1.0);
+        assert!(progress.iter().any(|p| p.page == 7 && p.error.is_some()));
+
+        let cached = crate::storage::get_ocr_pages(&db.conn.lock().unwrap(), "scan").unwrap();
+        assert_eq!(cached.len(), 9);
+        assert_eq!(cached[&3], "Text of page 3");
+        assert!(!cached.contains_key(&7));
+    }
+
+    #[test]
+    fn test_ocr_jobs_one_run_per_document() {
+        let jobs = OcrJobs::new();
+        let (_first_rx, first) = jobs.start("scan").unwrap();
+        assert!(jobs.start("scan").is_err());
+        assert!(jobs.start("other").is_ok());
+
+        // A run started after a cancellation keeps its entry when the old one finishes
+        assert!(jobs.cancel("scan"));
+        assert!(first.load(Ordering::SeqCst));
+        let (_second_rx, second) = jobs.start("scan").unwrap();
+        jobs.finish("scan", &first);
+        assert!(jobs.start("scan").is_err());
+
+        jobs.finish("scan", &second);
+        assert!(!jobs.cancel("scan"));
+        assert!(jobs.start("scan").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_ocr_keeps_finished_pages() {
+        let db = ocr_test_db();
+        // The first two pages finish at once; the rest would take far longer than the test
+        let ocr_page = |page: u32| async move {
+            if page > 2 {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            }
+            Ok(format!("Page {}", page))
+        };
+
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let mut cancel_tx = Some(cancel_tx);
+        let cancel_after_two = |p: &OcrProgress| {
+            if p.pages_done == 2 {
+                let _ = cancel_tx.take().unwrap().send(());
+            }
+        };
+        let run = ocr_pages_into(&db, "scan", 8, "eng", ocr_page, cancel_after_two, cancel_rx);
+        let report = tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .expect("cancellation should stop the run promptly")
+            .unwrap();
+
+        assert!(report.cancelled);
+        assert_eq!(report.recognized, [1, 2]);
+        let cached = crate::storage::get_ocr_pages(&db.conn.lock().unwrap(), "scan").unwrap();
+        assert_eq!(cached.len(), 2);
+    }
+}
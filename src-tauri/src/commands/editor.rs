@@ -4,16 +4,20 @@
 //! - PDF, Text/Markdown, DOCX, LaTeX, EPUB
 
 use crate::document::editor::{
-    CommonEditOperation, ConversionUtils, DOCXEditOperation, DOCXEditor, DocumentEditor,
-    EPUBEditOperation, EPUBEditor, EditOperation, EditOperationInfo, EditorConfig, EditorError,
-    ImageFormat, LaTeXEditOperation, LaTeXEditor, PDFEditOperation, PDFEditor, PDFUtils,
-    TextEditOperation, TextEditor, WordStats,
+    watch_file, CombineReport, CommonEditOperation, ConversionUtils, DOCXEditOperation,
+    DOCXEditor, DocumentEditor, EPUBEditOperation, EPUBEditor, EditOperation, EditOperationInfo,
+    EditorConfig, EditorError, FileWatcher, HtmlAllowlist, ImageFormat, LaTeXEditOperation,
+    LaTeXEditor, PDFEditOperation, PDFEditor, PDFUtils, PdfMetadataUpdate, PreviewUpdate,
+    SourceRange, TextEditOperation, TextEditor, TextPosition, WordStats,
 };
-use crate::document::DocumentType;
+use crate::document::model::{DocxModel, EpubModel};
+use crate::document::{DocumentType, OutputSettings};
 use crate::error::AppError;
+use crate::llm::{code_check, CodeSnippet};
+use serde::Serialize;
 use std::collections::HashMap;
 use tokio::sync::Mutex;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
 
 // ============================================================================
 // Editor Manager
@@ -50,21 +54,134 @@ impl EditorInstance {
             EditorInstance::Epub(e) => e,
         }
     }
+
+    /// Pending operations in the unified format, oldest first
+    fn operations(&self) -> Vec<EditOperation> {
+        match self {
+            EditorInstance::Pdf(e) => {
+                e.get_operations().iter().cloned().map(EditOperation::Pdf).collect()
+            }
+            EditorInstance::Text(e) => {
+                e.get_operations().iter().cloned().map(EditOperation::Text).collect()
+            }
+            EditorInstance::Docx(e) => {
+                e.get_operations().iter().cloned().map(EditOperation::Docx).collect()
+            }
+            EditorInstance::LaTeX(e) => {
+                e.get_operations().iter().cloned().map(EditOperation::Latex).collect()
+            }
+            EditorInstance::Epub(e) => {
+                e.get_operations().iter().cloned().map(EditOperation::Epub).collect()
+            }
+        }
+    }
+
+    /// Describe the pending operations, oldest first
+    fn operations_info(&self) -> Vec<EditOperationInfo> {
+        self.operations().iter().map(EditOperationInfo::from_operation).collect()
+    }
+
+    /// Queue operations, e.g. from `export_operations`, in order. Nothing is queued
+    /// unless every operation is for this editor's document type.
+    fn replay_operations(&mut self, operations: Vec<EditOperation>) -> Result<usize, EditorError> {
+        let accepts = |op: &EditOperation| {
+            matches!(
+                (&*self, op),
+                (EditorInstance::Pdf(_), EditOperation::Pdf(_))
+                    | (EditorInstance::Text(_), EditOperation::Text(_))
+                    | (EditorInstance::Docx(_), EditOperation::Docx(_))
+                    | (EditorInstance::LaTeX(_), EditOperation::Latex(_))
+                    | (EditorInstance::Epub(_), EditOperation::Epub(_))
+            )
+        };
+        if let Some(op) = operations.iter().find(|op| !accepts(op)) {
+            return Err(EditorError::UnsupportedOperation(format!(
+                "{} operation cannot be applied to this document",
+                EditOperationInfo::from_operation(op).doc_type
+            )));
+        }
+
+        let count = operations.len();
+        if let EditorInstance::Pdf(e) = self {
+            // PDF pages are checked against the whole batch before any is queued
+            let operations = operations.into_iter().filter_map(|op| match op {
+                EditOperation::Pdf(op) => Some(op),
+                _ => None,
+            });
+            e.add_operations(operations.collect())?;
+            return Ok(count);
+        }
+
+        for op in operations {
+            match (&mut *self, op) {
+                (EditorInstance::Text(e), EditOperation::Text(op)) => e.add_operation(op),
+                (EditorInstance::Docx(e), EditOperation::Docx(op)) => e.add_operation(op),
+                (EditorInstance::LaTeX(e), EditOperation::Latex(op)) => e.add_operation(op),
+                (EditorInstance::Epub(e), EditOperation::Epub(op)) => e.add_operation(op),
+                _ => unreachable!("operation types checked above"),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Wrap a shared operation in this editor's format-specific variant and apply it
+    fn apply_common(
+        &mut self,
+        operation: CommonEditOperation,
+    ) -> Result<EditOperationInfo, EditorError> {
+        let op = match self {
+            EditorInstance::Pdf(_) => {
+                return Err(EditorError::UnsupportedOperation(
+                    "PDFs have no text positions; use a PDF operation instead".to_string(),
+                ))
+            }
+            EditorInstance::Text(e) => {
+                let op = TextEditOperation::Common(operation);
+                e.add_operation(op.clone());
+                EditOperation::Text(op)
+            }
+            EditorInstance::Docx(e) => {
+                let op = DOCXEditOperation::Common(operation);
+                e.add_operation(op.clone());
+                EditOperation::Docx(op)
+            }
+            EditorInstance::LaTeX(e) => {
+                let op = LaTeXEditOperation::Common(operation);
+                e.add_operation(op.clone());
+                EditOperation::Latex(op)
+            }
+            EditorInstance::Epub(e) => {
+                let op = EPUBEditOperation::Common(operation);
+                e.add_operation(op.clone());
+                EditOperation::Epub(op)
+            }
+        };
+        Ok(EditOperationInfo::from_operation(&op))
+    }
 }
 
 /// Editor state manager for all document types
 pub struct EditorManager {
     editors: Mutex<HashMap<String, EditorInstance>>,
+    watchers: Mutex<HashMap<String, FileWatcher>>,
 }
 
 impl EditorManager {
     pub fn new() -> Self {
         Self {
             editors: Mutex::new(HashMap::new()),
+            watchers: Mutex::new(HashMap::new()),
         }
     }
 }
 
+/// Payload for `editor:file_changed_externally`
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalChangeEvent {
+    pub document_id: String,
+    pub path: String,
+}
+
 impl Default for EditorManager {
     fn default() -> Self {
         Self::new()
@@ -81,6 +198,7 @@ pub async fn open_editor(
     app: AppHandle,
     document_id: String,
     path: String,
+    watch: Option<bool>,
 ) -> Result<String, AppError> {
     let manager = app.state::<EditorManager>();
     let mut editors = manager.editors.lock().await;
@@ -125,17 +243,101 @@ pub async fn open_editor(
     };
 
     let doc_type_str = format!("{:?}", doc_type).to_lowercase();
-    editors.insert(document_id, editor);
+    editors.insert(document_id.clone(), editor);
+
+    if watch.unwrap_or(false) {
+        let watcher = watch_external_changes(&app, &document_id, &path)
+            .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
+        manager.watchers.lock().await.insert(document_id, watcher);
+    }
+
+    Ok(doc_type_str)
+}
+
+/// Open several text/markdown files as one editor buffer under `document_id`. Saving
+/// the editor writes each file's part back to it.
+#[tauri::command]
+pub async fn open_merged_editor(
+    app: AppHandle,
+    document_id: String,
+    paths: Vec<String>,
+) -> Result<String, AppError> {
+    let manager = app.state::<EditorManager>();
+    let mut editors = manager.editors.lock().await;
+
+    if editors.contains_key(&document_id) {
+        return Ok("already_open".to_string());
+    }
+
+    let editor = TextEditor::open_merged(&paths)
+        .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
+    let doc_type_str = format!("{:?}", editor.document_type()).to_lowercase();
+    editors.insert(document_id, EditorInstance::Text(editor));
 
     Ok(doc_type_str)
 }
 
+/// Get where each source file's text currently sits in a merged editor buffer
+#[tauri::command]
+pub async fn get_merged_source_ranges(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Vec<SourceRange>, AppError> {
+    let manager = app.state::<EditorManager>();
+    let editors = manager.editors.lock().await;
+
+    match editors.get(&document_id) {
+        Some(EditorInstance::Text(editor)) if editor.is_merged() => editor
+            .source_ranges()
+            .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()).into()),
+        Some(_) => Err(crate::error::DocumentError::ParseError(
+            "Editor is not a merged buffer".to_string(),
+        )
+        .into()),
+        None => Err(crate::error::DocumentError::InvalidId.into()),
+    }
+}
+
+/// Emit `editor:file_changed_externally` when the file changes on disk outside the editor
+fn watch_external_changes(
+    app: &AppHandle,
+    document_id: &str,
+    path: &str,
+) -> Result<FileWatcher, EditorError> {
+    let app = app.clone();
+    let event = ExternalChangeEvent {
+        document_id: document_id.to_string(),
+        path: path.to_string(),
+    };
+
+    watch_file(path, move || {
+        let app = app.clone();
+        let event = event.clone();
+        tauri::async_runtime::spawn(async move {
+            // Our own saves also touch the file; only report content we didn't write
+            let manager = app.state::<EditorManager>();
+            let changed = manager
+                .editors
+                .lock()
+                .await
+                .get(&event.document_id)
+                .map(|e| e.as_editor().changed_on_disk())
+                .unwrap_or(false);
+
+            if changed {
+                let _ = app.emit("editor:file_changed_externally", &event);
+            }
+        });
+    })
+}
+
 /// Close editor and discard changes
 #[tauri::command]
 pub async fn close_editor(app: AppHandle, document_id: String) -> Result<(), AppError> {
     let manager = app.state::<EditorManager>();
     let mut editors = manager.editors.lock().await;
     editors.remove(&document_id);
+    manager.watchers.lock().await.remove(&document_id);
     Ok(())
 }
 
@@ -205,12 +407,13 @@ pub async fn clear_operations(app: AppHandle, document_id: String) -> Result<(),
     Ok(())
 }
 
-/// Save changes to the document
+/// Save changes to the document, refusing to clobber external edits unless `force` is set
 #[tauri::command]
 pub async fn save_document(
     app: AppHandle,
     document_id: String,
     output_path: Option<String>,
+    force: Option<bool>,
 ) -> Result<String, AppError> {
     let manager = app.state::<EditorManager>();
     let mut editors = manager.editors.lock().await;
@@ -229,13 +432,84 @@ pub async fn save_document(
     } else {
         editor
             .as_editor_mut()
-            .save()
+            .save(force.unwrap_or(false))
             .await
             .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
         Ok("saved".to_string())
     }
 }
 
+/// Get descriptions of the pending operations for any editor type
+#[tauri::command]
+pub async fn get_operations(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Vec<EditOperationInfo>, AppError> {
+    let manager = app.state::<EditorManager>();
+    let editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+    Ok(editor.operations_info())
+}
+
+/// Serialize the pending operations, oldest first, as JSON that `import_operations`
+/// can replay
+#[tauri::command]
+pub async fn export_operations(app: AppHandle, document_id: String) -> Result<String, AppError> {
+    let manager = app.state::<EditorManager>();
+    let editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+    serde_json::to_string_pretty(&editor.operations())
+        .map_err(|e| crate::error::StorageError::Serialization(e.to_string()).into())
+}
+
+/// Replay operations exported by `export_operations` onto the open editor, returning
+/// how many were queued
+#[tauri::command]
+pub async fn import_operations(
+    app: AppHandle,
+    document_id: String,
+    operations: String,
+) -> Result<usize, AppError> {
+    let operations: Vec<EditOperation> = serde_json::from_str(&operations)
+        .map_err(|e| crate::error::StorageError::Serialization(e.to_string()))?;
+
+    let manager = app.state::<EditorManager>();
+    let mut editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get_mut(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+    editor
+        .replay_operations(operations)
+        .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()).into())
+}
+
+/// Apply a shared text operation to whichever editor type is open for the document
+#[tauri::command]
+pub async fn apply_common_operation(
+    app: AppHandle,
+    document_id: String,
+    operation: CommonEditOperation,
+) -> Result<EditOperationInfo, AppError> {
+    let manager = app.state::<EditorManager>();
+    let mut editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get_mut(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+
+    let info = editor
+        .apply_common(operation)
+        .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
+    Ok(info)
+}
+
 // ============================================================================
 // PDF Editor Commands
 // ============================================================================
@@ -257,7 +531,9 @@ pub async fn add_pdf_operation(
     match editor {
         EditorInstance::Pdf(pdf_editor) => {
             let info = EditOperationInfo::from_operation(&EditOperation::Pdf(operation.clone()));
-            pdf_editor.add_operation(operation);
+            pdf_editor
+                .add_operation(operation)
+                .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
             Ok(info)
         }
         _ => Err(crate::error::DocumentError::ParseError(
@@ -281,14 +557,7 @@ pub async fn get_pdf_operations(
         .ok_or(crate::error::DocumentError::InvalidId)?;
 
     match editor {
-        EditorInstance::Pdf(pdf_editor) => {
-            let operations: Vec<EditOperationInfo> = pdf_editor
-                .get_operations()
-                .iter()
-                .map(|op| EditOperationInfo::from_operation(&EditOperation::Pdf(op.clone())))
-                .collect();
-            Ok(operations)
-        }
+        EditorInstance::Pdf(_) => Ok(editor.operations_info()),
         _ => Err(crate::error::DocumentError::ParseError(
             "Document is not a PDF".to_string(),
         )
@@ -296,6 +565,69 @@ pub async fn get_pdf_operations(
     }
 }
 
+/// Get the number of pages in an open PDF
+#[tauri::command]
+pub async fn get_pdf_page_count(app: AppHandle, document_id: String) -> Result<u32, AppError> {
+    let manager = app.state::<EditorManager>();
+    let mut editors = manager.editors.lock().await;
+
+    match editors.get_mut(&document_id) {
+        Some(EditorInstance::Pdf(pdf_editor)) => pdf_editor
+            .page_count()
+            .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()).into()),
+        Some(_) => Err(crate::error::DocumentError::ParseError(
+            "Document is not a PDF".to_string(),
+        )
+        .into()),
+        None => Err(crate::error::DocumentError::InvalidId.into()),
+    }
+}
+
+/// Get the width and height of a PDF page in points, from its MediaBox
+#[tauri::command]
+pub async fn get_pdf_page_size(
+    app: AppHandle,
+    document_id: String,
+    page: u32,
+) -> Result<(f32, f32), AppError> {
+    let manager = app.state::<EditorManager>();
+    let mut editors = manager.editors.lock().await;
+
+    match editors.get_mut(&document_id) {
+        Some(EditorInstance::Pdf(pdf_editor)) => pdf_editor
+            .page_size(page)
+            .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()).into()),
+        Some(_) => Err(crate::error::DocumentError::ParseError(
+            "Document is not a PDF".to_string(),
+        )
+        .into()),
+        None => Err(crate::error::DocumentError::InvalidId.into()),
+    }
+}
+
+/// Write a copy of an open PDF with its annotations, and the pending highlights and
+/// notes, drawn into the page content. Returns the number of marks drawn.
+#[tauri::command]
+pub async fn flatten_pdf_annotations(
+    app: AppHandle,
+    document_id: String,
+    output_path: String,
+) -> Result<usize, AppError> {
+    let manager = app.state::<EditorManager>();
+    let editors = manager.editors.lock().await;
+
+    match editors.get(&document_id) {
+        Some(EditorInstance::Pdf(pdf_editor)) => pdf_editor
+            .flatten_annotations(&output_path)
+            .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()).into()),
+        Some(_) => Err(crate::error::DocumentError::ParseError(
+            "Document is not a PDF".to_string(),
+        )
+        .into()),
+        None => Err(crate::error::DocumentError::InvalidId.into()),
+    }
+}
+
 // ============================================================================
 // Text/Markdown Editor Commands
 // ============================================================================
@@ -327,6 +659,71 @@ pub async fn add_text_operation(
     }
 }
 
+/// Insert generated code into an open text or markdown note as a fenced block, at
+/// `position` (the cursor) or at the end. It can be undone like any other edit.
+#[tauri::command]
+pub async fn insert_code_snippet(
+    app: AppHandle,
+    document_id: String,
+    snippet: CodeSnippet,
+    position: Option<TextPosition>,
+) -> Result<EditOperationInfo, AppError> {
+    let manager = app.state::<EditorManager>();
+    let mut editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get_mut(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+
+    match editor {
+        EditorInstance::Text(text_editor) => {
+            let position = position.unwrap_or_else(|| text_editor.end_position());
+            let operation = snippet_operation(&snippet, position);
+            let info = EditOperationInfo::from_operation(&EditOperation::Text(operation.clone()));
+            text_editor.add_operation(operation);
+            Ok(info)
+        }
+        _ => Err(crate::error::DocumentError::ParseError(
+            "Document is not a text file".to_string(),
+        )
+        .into()),
+    }
+}
+
+/// A code block with the snippet's code, headed by its description as a comment
+fn snippet_operation(snippet: &CodeSnippet, position: TextPosition) -> TextEditOperation {
+    let language = snippet.language.trim().to_lowercase();
+    let comment = line_comment(&language);
+
+    let mut description = snippet.description.trim().to_string();
+    if let Some(section) = &snippet.section_reference {
+        description = format!("{} ({})", description, section.trim());
+    }
+    let mut code = String::new();
+    for line in description.lines().filter(|l| !l.trim().is_empty()) {
+        code.push_str(&format!("{} {}\n", comment, line.trim()));
+    }
+    code.push_str(&code_check::extract_code(&language, &snippet.code));
+
+    TextEditOperation::InsertCodeBlock {
+        position,
+        language: language.split_whitespace().next().unwrap_or("").to_string(),
+        code,
+    }
+}
+
+/// Line comment marker of a programming language, `//` when unknown
+fn line_comment(language: &str) -> &'static str {
+    match language {
+        "python" | "py" | "python3" | "r" | "ruby" | "julia" | "perl" | "bash" | "sh"
+        | "shell" | "zsh" | "powershell" | "yaml" | "toml" | "elixir" => "#",
+        "sql" | "lua" | "haskell" => "--",
+        "matlab" | "octave" | "latex" | "tex" => "%",
+        "lisp" | "scheme" | "clojure" => ";",
+        _ => "//",
+    }
+}
+
 /// Get text content
 #[tauri::command]
 pub async fn get_text_content(app: AppHandle, document_id: String) -> Result<String, AppError> {
@@ -414,6 +811,62 @@ pub async fn render_markdown_preview(
     }
 }
 
+/// Render markdown preview incrementally, returning HTML only for changed blocks.
+/// Pass `full` to re-send every block, e.g. when the preview pane is reopened.
+#[tauri::command]
+pub async fn render_markdown_preview_blocks(
+    app: AppHandle,
+    document_id: String,
+    full: Option<bool>,
+) -> Result<PreviewUpdate, AppError> {
+    let manager = app.state::<EditorManager>();
+    let mut editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get_mut(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+
+    match editor {
+        EditorInstance::Text(text_editor) => {
+            if full.unwrap_or(false) {
+                text_editor.reset_preview_cache();
+            }
+            Ok(text_editor.render_markdown_preview_incremental())
+        }
+        _ => Err(crate::error::DocumentError::ParseError(
+            "Document is not a text file".to_string(),
+        )
+        .into()),
+    }
+}
+
+/// Set the tags, attributes and URL schemes kept in a text editor's preview
+#[tauri::command]
+pub async fn set_preview_allowlist(
+    app: AppHandle,
+    document_id: String,
+    allowlist: HtmlAllowlist,
+) -> Result<(), AppError> {
+    let manager = app.state::<EditorManager>();
+    let mut editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get_mut(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+
+    match editor {
+        EditorInstance::Text(text_editor) => {
+            text_editor
+                .set_html_allowlist(allowlist)
+                .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()).into())
+        }
+        _ => Err(crate::error::DocumentError::ParseError(
+            "Document is not a text file".to_string(),
+        )
+        .into()),
+    }
+}
+
 // ============================================================================
 // DOCX Editor Commands
 // ============================================================================
@@ -445,6 +898,25 @@ pub async fn add_docx_operation(
     }
 }
 
+/// Current DOCX content with the pending operations applied, for previewing edits
+#[tauri::command]
+pub async fn get_docx_model(app: AppHandle, document_id: String) -> Result<DocxModel, AppError> {
+    let manager = app.state::<EditorManager>();
+    let editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+
+    match editor {
+        EditorInstance::Docx(docx_editor) => Ok(docx_editor.model().clone()),
+        _ => Err(crate::error::DocumentError::ParseError(
+            "Document is not a DOCX file".to_string(),
+        )
+        .into()),
+    }
+}
+
 // ============================================================================
 // LaTeX Editor Commands
 // ============================================================================
@@ -499,6 +971,31 @@ pub async fn get_latex_completions(
     }
 }
 
+/// Add a BibTeX entry to a LaTeX document's bibliography, returning the `.bib` path
+#[tauri::command]
+pub async fn add_bib_entry(
+    app: AppHandle,
+    document_id: String,
+    entry: String,
+) -> Result<String, AppError> {
+    let manager = app.state::<EditorManager>();
+    let editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+
+    match editor {
+        EditorInstance::LaTeX(latex_editor) => latex_editor
+            .add_bib_entry(&entry)
+            .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()).into()),
+        _ => Err(crate::error::DocumentError::ParseError(
+            "Document is not a LaTeX file".to_string(),
+        )
+        .into()),
+    }
+}
+
 // ============================================================================
 // EPUB Editor Commands
 // ============================================================================
@@ -530,6 +1027,26 @@ pub async fn add_epub_operation(
     }
 }
 
+/// Current EPUB structure and metadata with the pending operations applied, for
+/// previewing edits
+#[tauri::command]
+pub async fn get_epub_model(app: AppHandle, document_id: String) -> Result<EpubModel, AppError> {
+    let manager = app.state::<EditorManager>();
+    let editors = manager.editors.lock().await;
+
+    let editor = editors
+        .get(&document_id)
+        .ok_or(crate::error::DocumentError::InvalidId)?;
+
+    match editor {
+        EditorInstance::Epub(epub_editor) => Ok(epub_editor.model().clone()),
+        _ => Err(crate::error::DocumentError::ParseError(
+            "Document is not an EPUB file".to_string(),
+        )
+        .into()),
+    }
+}
+
 // ============================================================================
 // PDF Utility Commands
 // ============================================================================
@@ -583,6 +1100,16 @@ pub async fn compress_pdf(
     Ok(())
 }
 
+fn parse_image_format(format: &str) -> ImageFormat {
+    match format.to_lowercase().as_str() {
+        "png" => ImageFormat::Png,
+        "jpeg" | "jpg" => ImageFormat::Jpeg,
+        "webp" => ImageFormat::Webp,
+        "tiff" => ImageFormat::Tiff,
+        _ => ImageFormat::Png,
+    }
+}
+
 /// Convert PDF to images
 #[tauri::command]
 pub async fn pdf_to_images(
@@ -591,18 +1118,63 @@ pub async fn pdf_to_images(
     format: String,
     dpi: u32,
 ) -> Result<Vec<String>, AppError> {
-    let img_format = match format.to_lowercase().as_str() {
-        "png" => ImageFormat::Png,
-        "jpeg" | "jpg" => ImageFormat::Jpeg,
-        "webp" => ImageFormat::Webp,
-        "tiff" => ImageFormat::Tiff,
-        _ => ImageFormat::Png,
+    let result = PDFUtils::to_images(&input_path, &output_dir, parse_image_format(&format), dpi)
+        .await
+        .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
+    Ok(result)
+}
+
+/// Render a single PDF page to an image and return its path
+#[tauri::command]
+pub async fn render_pdf_page(
+    input_path: String,
+    page: u32,
+    dpi: u32,
+    format: String,
+) -> Result<String, AppError> {
+    let rendered = PDFUtils::render_page(&input_path, page, dpi, parse_image_format(&format))
+        .await
+        .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
+    Ok(rendered.path)
+}
+
+/// Update a PDF's title, authors, subject and keywords, in the file and the library.
+///
+/// Rewriting the file changes its hash, so the document and everything attached to it
+/// move to a new id, which is returned.
+#[tauri::command]
+pub async fn set_pdf_metadata(
+    app: AppHandle,
+    document_id: String,
+    update: PdfMetadataUpdate,
+) -> Result<String, AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let path = {
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
     };
 
-    let result = PDFUtils::to_images(&input_path, &output_dir, img_format, dpi)
+    PDFUtils::set_metadata(&path, &path, &update)
         .await
         .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
-    Ok(result)
+    let new_id = crate::document::hash_file(&path)?;
+
+    let conn = db.conn.lock().unwrap();
+    crate::storage::update_document_metadata(&conn, &document_id, &update)?;
+    if new_id != document_id {
+        crate::storage::rekey_document(&conn, &document_id, &new_id)?;
+    }
+    Ok(new_id)
+}
+
+/// Report each digital signature in a PDF and whether its signed bytes are unchanged
+#[tauri::command]
+pub async fn verify_pdf_signatures(
+    input_path: String,
+) -> Result<Vec<crate::document::SignatureStatus>, AppError> {
+    let statuses = PDFUtils::verify_signatures(&input_path)
+        .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
+    Ok(statuses)
 }
 
 /// Convert images to PDF
@@ -619,49 +1191,126 @@ pub async fn images_to_pdf(image_paths: Vec<String>, output_path: String) -> Res
 // Conversion Commands
 // ============================================================================
 
-/// Convert Markdown to PDF
+/// Get the default output directory and file name template used by conversions
 #[tauri::command]
-pub async fn convert_markdown_to_pdf(input: String, output: String) -> Result<(), AppError> {
+pub async fn get_output_settings(app: AppHandle) -> Result<OutputSettings, AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_output_settings(&conn)
+}
+
+/// Save the default output directory and file name template used by conversions
+#[tauri::command]
+pub async fn set_output_settings(app: AppHandle, settings: OutputSettings) -> Result<(), AppError> {
+    if settings.filename_template.trim().is_empty() {
+        return Err(crate::error::DocumentError::ParseError(
+            "Output file name template is empty".to_string(),
+        )
+        .into());
+    }
+
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::set_output_settings(&conn, &settings)
+}
+
+/// The requested output path, or one derived from the output settings
+fn conversion_output(
+    app: &AppHandle,
+    input: &str,
+    output: Option<String>,
+    ext: &str,
+) -> Result<String, AppError> {
+    if let Some(output) = output {
+        return Ok(output);
+    }
+
+    let db = app.state::<crate::storage::Database>();
+    let settings = crate::storage::get_output_settings(&db.conn.lock().unwrap())?;
+    let date = chrono::Local::now().date_naive();
+    Ok(settings.resolve(input, ext, date).to_string_lossy().into_owned())
+}
+
+/// Convert Markdown to PDF, returning the path written
+#[tauri::command]
+pub async fn convert_markdown_to_pdf(
+    app: AppHandle,
+    input: String,
+    output: Option<String>,
+) -> Result<String, AppError> {
+    let output = conversion_output(&app, &input, output, "pdf")?;
     ConversionUtils::markdown_to_pdf(&input, &output)
         .await
         .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
-    Ok(())
+    Ok(output)
 }
 
-/// Convert Markdown to DOCX
+/// Convert Markdown to DOCX, returning the path written
 #[tauri::command]
-pub async fn convert_markdown_to_docx(input: String, output: String) -> Result<(), AppError> {
+pub async fn convert_markdown_to_docx(
+    app: AppHandle,
+    input: String,
+    output: Option<String>,
+) -> Result<String, AppError> {
+    let output = conversion_output(&app, &input, output, "docx")?;
     ConversionUtils::markdown_to_docx(&input, &output)
         .await
         .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
-    Ok(())
+    Ok(output)
 }
 
-/// Convert DOCX to PDF
+/// Convert DOCX to PDF, returning the path written
 #[tauri::command]
-pub async fn convert_docx_to_pdf(input: String, output: String) -> Result<(), AppError> {
+pub async fn convert_docx_to_pdf(
+    app: AppHandle,
+    input: String,
+    output: Option<String>,
+) -> Result<String, AppError> {
+    let output = conversion_output(&app, &input, output, "pdf")?;
     ConversionUtils::docx_to_pdf(&input, &output)
         .await
         .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
-    Ok(())
+    Ok(output)
 }
 
-/// Convert LaTeX to PDF
+/// Convert LaTeX to PDF, returning the path written
 #[tauri::command]
-pub async fn convert_latex_to_pdf(input: String, output: String) -> Result<(), AppError> {
+pub async fn convert_latex_to_pdf(
+    app: AppHandle,
+    input: String,
+    output: Option<String>,
+) -> Result<String, AppError> {
+    let output = conversion_output(&app, &input, output, "pdf")?;
     ConversionUtils::latex_to_pdf(&input, &output)
         .await
         .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
-    Ok(())
+    Ok(output)
 }
 
-/// Convert TXT to Markdown
+/// Convert TXT to Markdown, returning the path written
 #[tauri::command]
-pub async fn convert_txt_to_markdown(input: String, output: String) -> Result<(), AppError> {
+pub async fn convert_txt_to_markdown(
+    app: AppHandle,
+    input: String,
+    output: Option<String>,
+) -> Result<String, AppError> {
+    let output = conversion_output(&app, &input, output, "md")?;
     ConversionUtils::txt_to_markdown(&input, &output)
         .await
         .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
-    Ok(())
+    Ok(output)
+}
+
+/// Convert Markdown, DOCX, LaTeX and PDF files into one merged PDF, in order
+#[tauri::command]
+pub async fn combine_documents_to_pdf(
+    inputs: Vec<String>,
+    output: String,
+) -> Result<CombineReport, AppError> {
+    let report = ConversionUtils::combine_to_pdf(inputs, output)
+        .await
+        .map_err(|e| crate::error::DocumentError::ParseError(e.to_string()))?;
+    Ok(report)
 }
 
 /// Compile content to PDF (using LaTeX or pdflatex)
@@ -735,3 +1384,272 @@ pub async fn compile_to_pdf(content: String, output_path: String) -> Result<(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::editor::{TextPosition, TextRange};
+
+    fn at(line: u32) -> TextPosition {
+        TextPosition { line, column: 0 }
+    }
+
+    fn replace_first_word() -> CommonEditOperation {
+        CommonEditOperation::ReplaceText {
+            range: TextRange {
+                start: at(0),
+                end: TextPosition { line: 0, column: 5 },
+            },
+            new_text: "Howdy".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_replace_text_routes_to_text_editor() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"Hello world").unwrap();
+        let path = file.path().to_str().unwrap();
+        let mut editor = EditorInstance::Text(TextEditor::new(path).unwrap());
+
+        let info = editor.apply_common(replace_first_word()).unwrap();
+
+        assert_eq!(info.doc_type, "text");
+        assert_eq!(info.operation_type, "replace_text");
+        let EditorInstance::Text(text) = &editor else {
+            unreachable!()
+        };
+        assert_eq!(text.get_content(), "Howdy world");
+    }
+
+    #[tokio::test]
+    async fn test_pdf_operations_round_trip_through_export_and_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let markdown = dir.path().join("doc.md");
+        let pdf_path = dir.path().join("doc.pdf");
+        std::fs::write(&markdown, "# Title\n\nOne page of text.").unwrap();
+        ConversionUtils::markdown_to_pdf(markdown.to_str().unwrap(), pdf_path.to_str().unwrap())
+            .await
+            .unwrap();
+        let path = pdf_path.to_str().unwrap();
+        let mut original = EditorInstance::Pdf(PDFEditor::new(path).unwrap());
+        let EditorInstance::Pdf(pdf) = &mut original else {
+            unreachable!()
+        };
+        pdf.add_operation(PDFEditOperation::AddText {
+            page: 1,
+            x: 72.0,
+            y: 700.0,
+            text: "Reviewed".to_string(),
+            font_size: 12.0,
+            font_family: "Helvetica".to_string(),
+            color: "#000000".to_string(),
+        })
+        .unwrap();
+        pdf.add_operation(PDFEditOperation::AddHighlight {
+            page: 1,
+            x: 10.0,
+            y: 20.0,
+            width: 100.0,
+            height: 12.0,
+            color: "#FFFF00".to_string(),
+        })
+        .unwrap();
+        pdf.add_operation(PDFEditOperation::RotatePage { page: 1, degrees: 90 }).unwrap();
+        pdf.add_operation(PDFEditOperation::DeletePage { page: 1 }).unwrap();
+
+        let exported = serde_json::to_string_pretty(&original.operations()).unwrap();
+        let mut replayed = EditorInstance::Pdf(PDFEditor::new(path).unwrap());
+        let count = replayed
+            .replay_operations(serde_json::from_str(&exported).unwrap())
+            .unwrap();
+
+        assert_eq!(count, 4);
+        assert_eq!(
+            serde_json::to_value(replayed.operations()).unwrap(),
+            serde_json::to_value(original.operations()).unwrap()
+        );
+        let types: Vec<String> = replayed
+            .operations_info()
+            .into_iter()
+            .map(|info| info.operation_type)
+            .collect();
+        assert_eq!(types, ["add_text", "add_highlight", "rotate_page", "delete_page"]);
+        assert!(replayed.as_editor().has_unsaved_changes());
+
+        // Operations for another document type are rejected without queuing any
+        let mut text = EditorInstance::Text(TextEditor::new(markdown.to_str().unwrap()).unwrap());
+        let err = text
+            .replay_operations(serde_json::from_str(&exported).unwrap())
+            .unwrap_err();
+        assert!(err.to_string().contains("pdf operation"));
+        assert!(text.operations().is_empty());
+    }
+
+    fn snippet(language: &str, code: &str) -> CodeSnippet {
+        CodeSnippet {
+            language: language.to_string(),
+            framework: None,
+            code: code.to_string(),
+            description: "Scaled dot-product attention".to_string(),
+            section_reference: Some("Section 3.2".to_string()),
+            warnings: None,
+        }
+    }
+
+    #[test]
+    fn test_code_snippet_inserted_as_fenced_block_at_end() {
+        let mut file = tempfile::NamedTempFile::with_suffix(".md").unwrap();
+        std::io::Write::write_all(&mut file, b"# Notes\n\nAttention weighs values.").unwrap();
+        let mut editor = TextEditor::new(file.path().to_str().unwrap()).unwrap();
+
+        let response = "Here it is:\n\n```python\ndef attention(q, k, v):\n    \
+            return softmax(q @ k.T) @ v\n```\n\nNote: no masking.";
+        let position = editor.end_position();
+        editor.add_operation(snippet_operation(&snippet("Python", response), position));
+        assert_eq!(
+            editor.get_content(),
+            "# Notes\n\nAttention weighs values.\n\
+             ```python\n\
+             # Scaled dot-product attention (Section 3.2)\n\
+             def attention(q, k, v):\n    return softmax(q @ k.T) @ v\n\
+             ```\n"
+        );
+
+        // Recorded like any other edit, so it can be undone and redone
+        editor.undo().unwrap();
+        assert_eq!(editor.get_content(), "# Notes\n\nAttention weighs values.");
+        editor.redo().unwrap();
+        assert!(editor.get_content().ends_with("return softmax(q @ k.T) @ v\n```\n"));
+    }
+
+    #[test]
+    fn test_code_snippet_inserted_at_cursor() {
+        let mut file = tempfile::NamedTempFile::with_suffix(".txt").unwrap();
+        std::io::Write::write_all(&mut file, b"before\nafter\n").unwrap();
+        let mut editor = TextEditor::new(file.path().to_str().unwrap()).unwrap();
+
+        let mut rust = snippet("Rust", "fn relu(x: f32) -> f32 {\n    x.max(0.0)\n}");
+        rust.section_reference = None;
+        editor.add_operation(snippet_operation(&rust, at(1)));
+        assert_eq!(
+            editor.get_content(),
+            "before\n```rust\n// Scaled dot-product attention\n\
+             fn relu(x: f32) -> f32 {\n    x.max(0.0)\n}\n```\nafter\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_content_undoes_and_clears_to_saved_text() {
+        // Saving writes a backup beside the file, so keep both in a directory
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "# Notes\n").unwrap();
+        let mut editor = TextEditor::new(path.to_str().unwrap()).unwrap();
+
+        let position = editor.end_position();
+        editor.add_operation(snippet_operation(&snippet("Rust", "let x = 1;"), position));
+        let with_snippet = editor.get_content().to_string();
+        editor.set_content("# Notes\n\nRewritten.".to_string());
+        assert_eq!(editor.operation_count(), 2);
+
+        // Typed edits are undone on their own, then the snippet
+        editor.undo().unwrap();
+        assert_eq!(editor.get_content(), with_snippet);
+        editor.undo().unwrap();
+        assert_eq!(editor.get_content(), "# Notes\n");
+        editor.redo().unwrap();
+        editor.redo().unwrap();
+        assert_eq!(editor.get_content(), "# Notes\n\nRewritten.");
+
+        // Clearing drops the edits since the last save, not those before it
+        editor.save(false).await.unwrap();
+        editor.set_content("Unsaved draft".to_string());
+        editor.clear_operations();
+        assert_eq!(editor.get_content(), "# Notes\n\nRewritten.");
+        assert!(!editor.has_unsaved_changes());
+        assert!(editor.undo().is_none());
+    }
+
+    #[test]
+    fn test_replace_text_routes_to_docx_editor() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let mut editor = EditorInstance::Docx(DOCXEditor::new(path).unwrap());
+
+        let info = editor.apply_common(replace_first_word()).unwrap();
+
+        assert_eq!(info.doc_type, "docx");
+        let EditorInstance::Docx(docx) = &editor else {
+            unreachable!()
+        };
+        assert!(matches!(
+            docx.get_operations(),
+            [DOCXEditOperation::Common(CommonEditOperation::ReplaceText { new_text, .. })]
+                if new_text == "Howdy"
+        ));
+    }
+
+    #[test]
+    fn test_common_operation_rejected_for_pdf() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let mut editor = EditorInstance::Pdf(PDFEditor::new(path).unwrap());
+
+        let err = editor.apply_common(replace_first_word()).unwrap_err();
+        assert!(matches!(err, EditorError::UnsupportedOperation(_)));
+        assert!(editor.operations_info().is_empty());
+    }
+
+    #[test]
+    fn test_docx_operations_are_described() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut editor = DOCXEditor::new(file.path().to_str().unwrap()).unwrap();
+        editor.add_operation(DOCXEditOperation::InsertTable {
+            position: at(0),
+            rows: 3,
+            cols: 2,
+        });
+        editor.add_operation(DOCXEditOperation::InsertPageBreak { position: at(4) });
+
+        let info = EditorInstance::Docx(editor).operations_info();
+        let described: Vec<_> = info
+            .iter()
+            .map(|i| (i.doc_type.as_str(), i.operation_type.as_str(), i.description.as_str()))
+            .collect();
+        assert_eq!(
+            described,
+            [
+                ("docx", "insert_table", "Insert 3x2 table"),
+                ("docx", "page_break", "Insert page break"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_latex_operations_are_described() {
+        let mut editor = LaTeXEditor::new("missing.tex").unwrap();
+        editor.add_operation(LaTeXEditOperation::InsertCitation {
+            position: at(2),
+            cite_key: "vaswani2017".to_string(),
+            cite_type: "cite".to_string(),
+        });
+        editor.add_operation(LaTeXEditOperation::InsertCommand {
+            position: at(3),
+            command: "\\section".to_string(),
+            args: vec!["Results".to_string()],
+        });
+
+        let info = EditorInstance::LaTeX(editor).operations_info();
+        let described: Vec<_> = info
+            .iter()
+            .map(|i| (i.doc_type.as_str(), i.operation_type.as_str(), i.description.as_str()))
+            .collect();
+        assert_eq!(
+            described,
+            [
+                ("latex", "insert_cite", "Cite: vaswani2017"),
+                ("latex", "insert_cmd", "Insert \\section"),
+            ]
+        );
+    }
+}
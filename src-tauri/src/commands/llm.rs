@@ -1,27 +1,314 @@
 //! LLM-related Tauri commands
 
+use crate::document::{
+    segment_sections, DetectedHeading, Document, ImageDescription, LatexError, PageImage,
+    Paragraph, Section,
+};
 use crate::error::AppError;
-use crate::llm::prompts;
-use crate::llm::{CodeGenerationRequest, CodeSnippet, LlmResponse, ModelStatus, QueryMode};
+use crate::llm::audit::{AuditSink, AuditingClient, LlmAuditEntry};
+use crate::llm::{code_check, prompts, tokens};
+use crate::llm::{
+    CodeGenerationRequest, CodeSnippet, Flashcard, GenerationParams, LlmResponse, ModelStatus,
+    QueryMode,
+};
 use crate::llm::providers::{
-    create_client, get_available_models, AvailableModels, ChatMessage, LLMProvider, ProviderConfig,
+    create_client, get_available_models, AvailableModels, ChatImage, ChatMessage, FallbackClient,
+    LLMClient, LLMError, LLMProvider, OllamaClient, ProviderConfig,
 };
+use crate::storage::{self, Database};
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::task::JoinSet;
+
+/// Maximum number of stored turns loaded for a follow-up question
+const FOLLOWUP_HISTORY_LIMIT: usize = 20;
+
+/// Character budget for prior turns sent with a follow-up question
+const FOLLOWUP_HISTORY_CHAR_BUDGET: usize = 12_000;
+
+/// Character budget for document text sent when generating flashcards
+const FLASHCARD_CONTEXT_CHAR_BUDGET: usize = 24_000;
+
+/// Upper bound on flashcards generated per request
+const MAX_FLASHCARDS: usize = 50;
+
+/// Upper bound on tags suggested per request
+const MAX_TAG_SUGGESTIONS: usize = 20;
+
+/// Character budget for document text sent when summarizing on open
+const SUMMARY_CONTEXT_CHAR_BUDGET: usize = 8_000;
+
+/// Section titles read first when building summary context
+const SUMMARY_KEY_SECTIONS: &[&str] = &["abstract", "introduction", "conclusion"];
+
+/// Character budget for document text used as query context when none is given
+const QUERY_CONTEXT_CHAR_BUDGET: usize = 24_000;
+
+/// Per-message overhead (role and separators) added to token estimates
+const TOKENS_PER_MESSAGE: usize = 4;
+
+/// Paragraphs translated at once when translating a whole document
+const TRANSLATION_CONCURRENCY: usize = 4;
 
 /// Application-wide LLM state
 pub struct LLMState {
     config: Mutex<ProviderConfig>,
+    /// Providers tried in order after the primary fails
+    fallback_chain: Mutex<Vec<ProviderConfig>>,
+    /// Sampling overrides layered over the provider config for each query mode; none by
+    /// default, so the provider config's own settings apply
+    mode_params: Mutex<HashMap<QueryMode, GenerationParams>>,
+    /// Cancellation handles for streaming requests, keyed by request id
+    in_flight: Mutex<HashMap<String, oneshot::Sender<()>>>,
+    /// Values last passed to `set_llm_config`, used to report where settings come from
+    stored: Mutex<Option<StoredLlmConfig>>,
+    /// Receives a record of every call while the audit log is enabled
+    audit: Mutex<Option<AuditSink>>,
+    /// API keys saved for each provider, used by configs without a key of their own
+    keys: Mutex<Vec<(LLMProvider, String)>>,
 }
 
 impl LLMState {
     pub fn new() -> Self {
         Self {
             config: Mutex::new(ProviderConfig::from_env()),
+            fallback_chain: Mutex::new(Vec::new()),
+            mode_params: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            stored: Mutex::new(None),
+            audit: Mutex::new(None),
+            keys: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Remember `api_key` as the key for `provider`, replacing any it had
+    fn set_provider_key(&self, provider: LLMProvider, api_key: String) {
+        let mut keys = self.keys.lock().unwrap();
+        keys.retain(|(p, _)| *p != provider);
+        keys.push((provider, api_key));
+    }
+
+    /// `config` with its API key filled in when it has none: the key saved for its
+    /// provider, then the provider's environment variable
+    fn with_key(&self, mut config: ProviderConfig) -> ProviderConfig {
+        if config.api_key.is_none() {
+            config.api_key = self
+                .keys
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(provider, _)| *provider == config.provider)
+                .map(|(_, key)| key.clone())
+                .or_else(|| env_api_key(&config.provider));
+        }
+        config
+    }
+
+    /// The primary provider's config, with its API key resolved
+    fn active_config(&self) -> ProviderConfig {
+        let config = self.config.lock().unwrap().clone();
+        self.with_key(config)
+    }
+
+    /// Where `active_config` gets the primary provider's API key
+    fn api_key_source(&self) -> ConfigSource {
+        let config = self.config.lock().unwrap().clone();
+        if config.api_key.is_some() {
+            // Stored settings carry their key; otherwise `from_env` read it
+            return if self.stored.lock().unwrap().is_some() {
+                ConfigSource::Stored
+            } else {
+                ConfigSource::Env
+            };
+        }
+        let keys = self.keys.lock().unwrap();
+        if keys.iter().any(|(p, _)| *p == config.provider) {
+            ConfigSource::Stored
+        } else if env_api_key(&config.provider).is_some() {
+            ConfigSource::Env
+        } else {
+            ConfigSource::Default
         }
     }
+
+    /// Use `stored` as the primary provider
+    fn activate(&self, stored: StoredLlmConfig) {
+        *self.config.lock().unwrap() = stored.provider_config();
+        *self.stored.lock().unwrap() = Some(stored);
+    }
+
+    /// Client for the primary provider, wrapped in a fallback chain when one is configured
+    fn client(&self) -> (Box<dyn LLMClient>, ProviderConfig) {
+        self.client_with(&GenerationParams::default())
+    }
+
+    /// Like `client`, with the mode's sampling overrides applied to every provider
+    fn client_for_mode(&self, mode: QueryMode) -> (Box<dyn LLMClient>, ProviderConfig) {
+        let params = self.generation_params(mode);
+        self.client_with(&params)
+    }
+
+    fn generation_params(&self, mode: QueryMode) -> GenerationParams {
+        self.mode_params
+            .lock()
+            .unwrap()
+            .get(&mode)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn client_with(&self, params: &GenerationParams) -> (Box<dyn LLMClient>, ProviderConfig) {
+        let config = params.apply_to(&self.active_config());
+        let fallbacks = self.fallback_chain.lock().unwrap().clone();
+
+        let client: Box<dyn LLMClient> = if fallbacks.is_empty() {
+            create_client(&config.provider)
+        } else {
+            let chain = std::iter::once(config.clone())
+                .chain(fallbacks.into_iter().map(|c| params.apply_to(&self.with_key(c))))
+                .map(|c| (c.provider.clone(), c))
+                .collect();
+            Box::new(FallbackClient::new(chain))
+        };
+
+        match self.audit.lock().unwrap().clone() {
+            Some(sink) => (Box::new(AuditingClient::new(client, sink)), config),
+            None => (client, config),
+        }
+    }
+}
+
+/// Short summary of a document, emitted as `document:summary_ready`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentSummary {
+    pub document_id: String,
+    pub summary: String,
+    /// Whether the summary came from the cache rather than a fresh LLM call
+    pub cached: bool,
+}
+
+/// Outcome of translating a document's paragraphs into one language
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranslationSummary {
+    pub document_id: String,
+    pub language: String,
+    /// Paragraphs translated by this request
+    pub translated: usize,
+    /// Paragraphs that already had a stored translation
+    pub cached: usize,
+    /// Paragraphs the LLM failed to translate; translating again retries them
+    pub failed: usize,
+}
+
+/// A streamed answer token, emitted as `llm:token`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmToken {
+    pub request_id: String,
+    pub token: String,
+}
+
+/// Result of a streaming query; also the payload of `llm:done` or `llm:cancelled`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamedAnswer {
+    pub request_id: String,
+    /// The full answer, or the part received before cancellation
+    pub answer: String,
+    pub cancelled: bool,
+    pub inference_time_ms: u64,
+}
+
+/// Errors parsed from a LaTeX compile log, with an optional suggested fix
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatexErrorExplanation {
+    pub errors: Vec<LatexError>,
+    pub suggestion: Option<String>,
+}
+
+/// The request a query would send, returned without calling the provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptPreview {
+    pub mode: QueryMode,
+    pub provider: LLMProvider,
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub estimated_tokens: usize,
+}
+
+/// Settings given explicitly through `set_llm_config` or `switch_llm_provider`
+#[derive(Debug, Clone)]
+struct StoredLlmConfig {
+    provider: LLMProvider,
+    model: String,
+    api_key: Option<String>,
+    api_url: Option<String>,
+}
+
+impl StoredLlmConfig {
+    fn from_saved(saved: ProviderConfig, api_key: Option<String>) -> Self {
+        Self {
+            provider: saved.provider,
+            model: saved.model,
+            api_key,
+            api_url: saved.api_url,
+        }
+    }
+
+    /// Config to send requests with. Without a stored key, `LLMState` resolves one when
+    /// the request is made.
+    fn provider_config(&self) -> ProviderConfig {
+        ProviderConfig {
+            provider: self.provider.clone(),
+            api_key: self.api_key.clone(),
+            api_url: self.api_url.clone(),
+            model: self.model.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Persist as the active provider, saving the API key under the provider
+    fn save_active(&self, conn: &rusqlite::Connection) -> Result<(), AppError> {
+        if let Some(api_key) = &self.api_key {
+            storage::save_provider_key(conn, &self.provider, api_key)?;
+        }
+        storage::save_active_llm_provider(conn, &self.provider_config())
+    }
+}
+
+/// Where an effective config value came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    Stored,
+    Env,
+    Default,
+}
+
+/// A config value with its source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourcedValue<T> {
+    pub value: T,
+    pub source: ConfigSource,
+}
+
+impl<T> SourcedValue<T> {
+    fn new(value: T, source: ConfigSource) -> Self {
+        Self { value, source }
+    }
+}
+
+/// LLM configuration in effect, with the API key redacted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveLlmConfig {
+    pub provider: SourcedValue<LLMProvider>,
+    pub model: SourcedValue<String>,
+    pub api_key: SourcedValue<Option<String>>,
+    pub api_url: SourcedValue<Option<String>>,
+    pub max_tokens: SourcedValue<u32>,
+    pub temperature: SourcedValue<f32>,
 }
 
 /// Current LLM configuration for serialization
@@ -31,8 +318,109 @@ pub struct LLMConfig {
     pub config: ProviderConfig,
 }
 
+/// System prompt plus a user turn carrying the document context
+fn query_messages(system_prompt: &str, context: &str, user_query: &str) -> Vec<ChatMessage> {
+    vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: system_prompt.to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: prompts::build_prompt("", context, user_query),
+        },
+    ]
+}
+
+/// Longest prefix of `text` within `budget` bytes, cut on a char boundary
+fn clip_to_budget(text: &str, budget: usize) -> &str {
+    let mut end = text.len().min(budget);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Build the preview for a query in `mode`, using the same messages as `query_llm`
+fn build_prompt_preview(
+    mode: QueryMode,
+    config: &ProviderConfig,
+    context: &str,
+    question: &str,
+) -> PromptPreview {
+    let messages = query_messages(system_prompt_for(mode), context, question);
+    let estimated_tokens = messages
+        .iter()
+        .map(|m| prompts::estimate_tokens(&m.content) + TOKENS_PER_MESSAGE)
+        .sum();
+
+    PromptPreview {
+        mode,
+        provider: config.provider.clone(),
+        model: config.model.clone(),
+        messages,
+        estimated_tokens,
+    }
+}
+
+fn system_prompt_for(mode: QueryMode) -> &'static str {
+    match mode {
+        QueryMode::QuickAnswer => prompts::QA_PROMPT,
+        QueryMode::Explain => prompts::PROFESSOR_PROMPT,
+        QueryMode::Summarize => prompts::SUMMARIZE_PROMPT,
+        QueryMode::GenerateCode => prompts::CODE_GENERATOR_PROMPT,
+    }
+}
+
+/// Stream a chat until it completes or `cancel` fires.
+///
+/// Cancelling aborts the provider task, which drops its HTTP request, and closes the
+/// token channel. Returns the answer (partial when cancelled) and whether it was cancelled.
+async fn stream_until_cancelled<F>(
+    client: Box<dyn LLMClient>,
+    config: ProviderConfig,
+    messages: Vec<ChatMessage>,
+    mut cancel: oneshot::Receiver<()>,
+    mut on_token: F,
+) -> Result<(String, bool), LLMError>
+where
+    F: FnMut(&str),
+{
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let task = tokio::spawn(async move { client.chat_stream(messages, &config, tx).await });
+    let mut received = String::new();
+
+    loop {
+        tokio::select! {
+            token = rx.recv() => match token {
+                Some(token) => {
+                    on_token(&token);
+                    received.push_str(&token);
+                }
+                None => break,
+            },
+            _ = &mut cancel => {
+                task.abort();
+                rx.close();
+                // Keep whatever was already buffered
+                while let Ok(token) = rx.try_recv() {
+                    on_token(&token);
+                    received.push_str(&token);
+                }
+                return Ok((received, true));
+            }
+        }
+    }
+
+    match task.await {
+        Ok(result) => result.map(|answer| (answer, false)),
+        Err(e) => Err(LLMError::ApiError(e.to_string())),
+    }
+}
+
 /// Helper: build messages and call the LLM
 async fn call_llm(
+    client: &dyn LLMClient,
     config: &ProviderConfig,
     system_prompt: &str,
     context: &str,
@@ -45,18 +433,7 @@ async fn call_llm(
         config.api_key.is_some()
     );
 
-    let client = create_client(&config.provider);
-
-    let messages = vec![
-        ChatMessage {
-            role: "system".to_string(),
-            content: system_prompt.to_string(),
-        },
-        ChatMessage {
-            role: "user".to_string(),
-            content: prompts::build_prompt("", context, user_query),
-        },
-    ];
+    let messages = query_messages(system_prompt, context, user_query);
 
     let start = Instant::now();
     let answer = client.chat(messages, config).await.map_err(|e| {
@@ -69,27 +446,28 @@ async fn call_llm(
     Ok((answer, elapsed))
 }
 
-/// Query the LLM with a question about the document
+/// Query the LLM with a question about the document. Without a `context` (such as the
+/// selected text), the start of the document given by `document_id` is sent.
 #[tauri::command]
 pub async fn query_llm(
-    _app: AppHandle,
+    app: AppHandle,
     state: State<'_, LLMState>,
     question: String,
-    context: String,
+    context: Option<String>,
     mode: QueryMode,
+    document_id: Option<String>,
 ) -> Result<LlmResponse, AppError> {
     tracing::info!("LLM query in {:?} mode: {}", mode, question);
 
-    let config = state.config.lock().unwrap().clone();
-
-    let system_prompt = match mode {
-        QueryMode::QuickAnswer => prompts::QA_PROMPT,
-        QueryMode::Explain => prompts::PROFESSOR_PROMPT,
-        QueryMode::Summarize => prompts::SUMMARIZE_PROMPT,
-        QueryMode::GenerateCode => prompts::CODE_GENERATOR_PROMPT,
+    let context = {
+        let db = app.state::<Database>();
+        query_context(&db, document_id.as_deref(), context).await?
     };
+    let (client, config) = state.client_for_mode(mode);
+    let system_prompt = system_prompt_for(mode);
 
-    let (answer, elapsed) = call_llm(&config, system_prompt, &context, &question).await?;
+    let (answer, elapsed) =
+        call_llm(client.as_ref(), &config, system_prompt, &context, &question).await?;
 
     Ok(LlmResponse {
         answer,
@@ -98,6 +476,257 @@ pub async fn query_llm(
     })
 }
 
+/// Query the LLM, emitting the answer as `llm:token` events while it streams and
+/// `llm:done` once it is complete.
+///
+/// The request can be stopped with `cancel_llm_request(request_id)`.
+#[tauri::command]
+pub async fn query_llm_stream(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    request_id: String,
+    question: String,
+    context: Option<String>,
+    mode: QueryMode,
+    document_id: Option<String>,
+) -> Result<StreamedAnswer, AppError> {
+    tracing::info!("Streaming LLM query {} in {:?} mode: {}", request_id, mode, question);
+
+    let context = {
+        let db = app.state::<Database>();
+        query_context(&db, document_id.as_deref(), context).await?
+    };
+    let (client, config) = state.client_for_mode(mode);
+    let messages = query_messages(system_prompt_for(mode), &context, &question);
+
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    state
+        .in_flight
+        .lock()
+        .unwrap()
+        .insert(request_id.clone(), cancel_tx);
+
+    let start = Instant::now();
+    let result = stream_until_cancelled(client, config, messages, cancel_rx, |token| {
+        let _ = app.emit(
+            "llm:token",
+            LlmToken {
+                request_id: request_id.clone(),
+                token: token.to_string(),
+            },
+        );
+    })
+    .await;
+    state.in_flight.lock().unwrap().remove(&request_id);
+
+    let (answer, cancelled) = result.map_err(|e| {
+        tracing::error!("Streaming LLM query failed: {}", e);
+        crate::error::LlmError::InferenceError(e.to_string())
+    })?;
+    let streamed = StreamedAnswer {
+        request_id,
+        answer,
+        cancelled,
+        inference_time_ms: start.elapsed().as_millis() as u64,
+    };
+
+    if cancelled {
+        tracing::info!("LLM request {} cancelled", streamed.request_id);
+        let _ = app.emit("llm:cancelled", &streamed);
+    } else {
+        let _ = app.emit("llm:done", &streamed);
+    }
+    Ok(streamed)
+}
+
+/// Context sent with a query: `context` when given, otherwise the start of the
+/// document's text, or nothing without a document
+async fn query_context(
+    db: &Database,
+    document_id: Option<&str>,
+    context: Option<String>,
+) -> Result<String, AppError> {
+    if let Some(context) = context {
+        return Ok(context);
+    }
+    let Some(document_id) = document_id else {
+        return Ok(String::new());
+    };
+
+    let path = {
+        let conn = db.conn.lock().unwrap();
+        storage::get_document_path(&conn, document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+    let text = document
+        .pages
+        .iter()
+        .map(|p| p.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok(clip_to_budget(&text, QUERY_CONTEXT_CHAR_BUDGET).to_string())
+}
+
+/// Show the prompt a query would send, without calling the provider.
+///
+/// Takes the same `context` and `document_id` as `query_llm`, so the preview shows the
+/// context the query would use.
+#[tauri::command]
+pub async fn preview_llm_prompt(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    mode: QueryMode,
+    document_id: String,
+    question: String,
+    context: Option<String>,
+) -> Result<PromptPreview, AppError> {
+    let context = {
+        let db = app.state::<Database>();
+        query_context(&db, Some(&document_id), context).await?
+    };
+
+    let config = state.generation_params(mode).apply_to(&state.config.lock().unwrap());
+    Ok(build_prompt_preview(mode, &config, &context, &question))
+}
+
+/// Cancel an in-flight streaming query; returns false if it already finished
+#[tauri::command]
+pub async fn cancel_llm_request(
+    state: State<'_, LLMState>,
+    request_id: String,
+) -> Result<bool, AppError> {
+    let handle = state.in_flight.lock().unwrap().remove(&request_id);
+    Ok(handle.is_some_and(|cancel| cancel.send(()).is_ok()))
+}
+
+/// Build the message list for a follow-up, keeping the newest turns that fit the budget
+fn build_followup_messages(
+    system_prompt: &str,
+    history: Vec<ChatMessage>,
+    question: &str,
+) -> Vec<ChatMessage> {
+    let mut used = 0;
+    let mut kept: Vec<ChatMessage> = history
+        .into_iter()
+        .rev()
+        .take_while(|msg| {
+            used += msg.content.len();
+            used <= FOLLOWUP_HISTORY_CHAR_BUDGET
+        })
+        .collect();
+    kept.reverse();
+
+    // Conversations must open with a user turn after trimming
+    let first_user = kept.iter().position(|m| m.role == "user").unwrap_or(kept.len());
+    kept.drain(..first_user);
+
+    let mut messages = Vec::with_capacity(kept.len() + 2);
+    messages.push(ChatMessage {
+        role: "system".to_string(),
+        content: system_prompt.to_string(),
+    });
+    messages.extend(kept);
+    messages.push(ChatMessage {
+        role: "user".to_string(),
+        content: question.to_string(),
+    });
+    messages
+}
+
+/// Send a follow-up with the stored conversation and persist the new exchange
+async fn run_followup(
+    client: &dyn LLMClient,
+    config: &ProviderConfig,
+    db: &Database,
+    document_id: &str,
+    system_prompt: &str,
+    question: &str,
+) -> Result<LlmResponse, AppError> {
+    let history = {
+        let conn = db.conn.lock().unwrap();
+        storage::recent_chat_messages(&conn, document_id, FOLLOWUP_HISTORY_LIMIT)?
+    };
+    let messages = build_followup_messages(system_prompt, history, question);
+
+    let start = Instant::now();
+    let answer = client.chat(messages, config).await.map_err(|e| {
+        tracing::error!("LLM follow-up failed: {}", e);
+        crate::error::LlmError::InferenceError(e.to_string())
+    })?;
+    let elapsed = start.elapsed().as_millis() as u64;
+
+    {
+        let conn = db.conn.lock().unwrap();
+        storage::insert_chat_message(&conn, document_id, "user", question, None)?;
+        storage::insert_chat_message(&conn, document_id, "assistant", &answer, None)?;
+    }
+
+    Ok(LlmResponse {
+        answer,
+        tokens_used: 0,
+        inference_time_ms: elapsed,
+    })
+}
+
+/// Ask a follow-up question using the document's recent conversation
+#[tauri::command]
+pub async fn query_llm_followup(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    document_id: String,
+    question: String,
+) -> Result<LlmResponse, AppError> {
+    tracing::info!("LLM follow-up for {}: {}", document_id, question);
+
+    let (client, config) = state.client_for_mode(QueryMode::QuickAnswer);
+    let db = app.state::<Database>();
+
+    run_followup(
+        client.as_ref(),
+        &config,
+        &db,
+        &document_id,
+        prompts::QA_PROMPT,
+        &question,
+    )
+    .await
+}
+
+/// Rephrase a previous answer at a target level (e.g. "high school"), continuing
+/// the document's conversation
+#[tauri::command]
+pub async fn simplify_explanation(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    document_id: String,
+    previous_answer: String,
+    level: String,
+) -> Result<LlmResponse, AppError> {
+    tracing::info!("Simplifying explanation for {} at level {}", document_id, level);
+
+    let level = level.trim();
+    if level.is_empty() || previous_answer.trim().is_empty() {
+        return Err(crate::error::LlmError::InferenceError(
+            "A previous answer and a target level are required".to_string(),
+        )
+        .into());
+    }
+
+    let (client, config) = state.client_for_mode(QueryMode::Explain);
+    let db = app.state::<Database>();
+    let request = prompts::simplify_request(&previous_answer, level);
+
+    run_followup(
+        client.as_ref(),
+        &config,
+        &db,
+        &document_id,
+        prompts::PROFESSOR_PROMPT,
+        &request,
+    )
+    .await
+}
+
 /// Get a detailed explanation of selected text (Professor Mode)
 #[tauri::command]
 pub async fn explain_text(
@@ -108,10 +737,16 @@ pub async fn explain_text(
 ) -> Result<LlmResponse, AppError> {
     tracing::info!("Explaining text: {}...", &text[..text.len().min(50)]);
 
-    let config = state.config.lock().unwrap().clone();
+    let (client, config) = state.client_for_mode(QueryMode::Explain);
     let query = format!("Please explain the following text in detail:\n\n\"{}\"", text);
-    let (answer, elapsed) =
-        call_llm(&config, prompts::PROFESSOR_PROMPT, &document_context, &query).await?;
+    let (answer, elapsed) = call_llm(
+        client.as_ref(),
+        &config,
+        prompts::PROFESSOR_PROMPT,
+        &document_context,
+        &query,
+    )
+    .await?;
 
     Ok(LlmResponse {
         answer,
@@ -133,7 +768,7 @@ pub async fn generate_code(
         request.description
     );
 
-    let config = state.config.lock().unwrap().clone();
+    let (client, config) = state.client_for_mode(QueryMode::GenerateCode);
     let query = format!(
         "Generate a {} implementation for: {}\n\nFramework: {}\nSection reference: {}",
         request.language,
@@ -142,8 +777,24 @@ pub async fn generate_code(
         request.section_reference.as_deref().unwrap_or("general"),
     );
 
-    let (code, _elapsed) =
-        call_llm(&config, prompts::CODE_GENERATOR_PROMPT, &request.context, &query).await?;
+    let (code, _elapsed) = call_llm(
+        client.as_ref(),
+        &config,
+        prompts::CODE_GENERATOR_PROMPT,
+        &request.context,
+        &query,
+    )
+    .await?;
+
+    // Problems are only reported, never a reason to fail
+    let warnings = if request.validate {
+        code_check::check_syntax(&request.language, &code)
+    } else {
+        None
+    };
+    if let Some(warnings) = warnings.as_ref().filter(|w| !w.is_empty()) {
+        tracing::warn!("Generated code has {} syntax problems", warnings.len());
+    }
 
     Ok(CodeSnippet {
         language: request.language,
@@ -151,7 +802,776 @@ pub async fn generate_code(
         code,
         description: request.description,
         section_reference: request.section_reference,
+        warnings,
+    })
+}
+
+/// Parse and validate the LLM's flashcard JSON
+fn parse_flashcards(response: &str, count: usize) -> Result<Vec<Flashcard>, AppError> {
+    // Models sometimes wrap the array in prose or code fences
+    let json = match (response.find('['), response.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => {
+            return Err(crate::error::LlmError::InferenceError(
+                "Flashcard response did not contain a JSON array".to_string(),
+            )
+            .into())
+        }
+    };
+
+    let mut cards: Vec<Flashcard> = serde_json::from_str(json).map_err(|e| {
+        crate::error::LlmError::InferenceError(format!("Invalid flashcard JSON: {}", e))
+    })?;
+
+    if cards
+        .iter()
+        .any(|c| c.question.trim().is_empty() || c.answer.trim().is_empty())
+    {
+        return Err(crate::error::LlmError::InferenceError(
+            "Flashcard with empty question or answer".to_string(),
+        )
+        .into());
+    }
+    if cards.len() < count {
+        return Err(crate::error::LlmError::InferenceError(format!(
+            "Expected {} flashcards, got {}",
+            count,
+            cards.len()
+        ))
+        .into());
+    }
+
+    cards.truncate(count);
+    Ok(cards)
+}
+
+/// Keep the candidates the LLM marked as headings, with the levels it assigned
+fn parse_detected_headings(
+    response: &str,
+    candidates: &[DetectedHeading],
+) -> Result<Vec<DetectedHeading>, AppError> {
+    #[derive(Deserialize)]
+    struct Marked {
+        id: String,
+        level: usize,
+    }
+
+    let json = match (response.find('['), response.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => {
+            return Err(crate::error::LlmError::InferenceError(
+                "Heading response did not contain a JSON array".to_string(),
+            )
+            .into())
+        }
+    };
+    let marked: Vec<Marked> = serde_json::from_str(json).map_err(|e| {
+        crate::error::LlmError::InferenceError(format!("Invalid heading JSON: {}", e))
+    })?;
+
+    let levels: HashMap<&str, usize> = marked
+        .iter()
+        .map(|m| (m.id.as_str(), m.level.clamp(1, 6)))
+        .collect();
+    Ok(candidates
+        .iter()
+        .filter_map(|c| {
+            levels.get(c.paragraph_id.as_str()).map(|level| DetectedHeading {
+                level: *level,
+                ..c.clone()
+            })
+        })
+        .collect())
+}
+
+/// Ask the LLM which heading-shaped paragraphs really are headings
+async fn request_headings(
+    client: &dyn LLMClient,
+    config: &ProviderConfig,
+    candidates: &[DetectedHeading],
+) -> Result<Vec<DetectedHeading>, AppError> {
+    let listing: Vec<serde_json::Value> = candidates
+        .iter()
+        .map(|c| serde_json::json!({ "id": c.paragraph_id, "text": c.title }))
+        .collect();
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: prompts::HEADING_PROMPT.to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: serde_json::to_string(&listing).unwrap_or_default(),
+        },
+    ];
+
+    let response = client.chat(messages, config).await.map_err(|e| {
+        tracing::error!("Heading detection failed: {}", e);
+        crate::error::LlmError::InferenceError(e.to_string())
+    })?;
+
+    parse_detected_headings(&response, candidates)
+}
+
+/// Refine the detected outline of a document with the LLM and cache the result
+#[tauri::command]
+pub async fn detect_headings_with_llm(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    document_id: String,
+) -> Result<Vec<DetectedHeading>, AppError> {
+    tracing::info!("Detecting headings with the LLM for {}", document_id);
+
+    let path = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+    let candidates = crate::document::headings::heading_candidates(&document);
+    if candidates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (client, config) = state.client();
+    let headings = request_headings(client.as_ref(), &config, &candidates).await?;
+    crate::document::headings::cache_headings(&document.id, headings.clone());
+
+    Ok(headings)
+}
+
+/// Ask the LLM how to fix errors from a LaTeX compile
+async fn request_latex_fix(
+    client: &dyn LLMClient,
+    config: &ProviderConfig,
+    errors: &[LatexError],
+) -> Result<String, AppError> {
+    let listing: Vec<String> = errors
+        .iter()
+        .map(|e| {
+            let line = e.line.map_or("unknown line".to_string(), |n| format!("line {}", n));
+            match &e.context {
+                Some(context) => format!("{}: {}\n  source: {}", line, e.message, context),
+                None => format!("{}: {}", line, e.message),
+            }
+        })
+        .collect();
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: prompts::LATEX_FIX_PROMPT.to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: listing.join("\n"),
+        },
+    ];
+
+    let response = client.chat(messages, config).await.map_err(|e| {
+        tracing::error!("LaTeX fix suggestion failed: {}", e);
+        crate::error::LlmError::InferenceError(e.to_string())
+    })?;
+    Ok(response.trim().to_string())
+}
+
+/// Parse the errors from a LaTeX compile log and, if asked, have the LLM suggest a
+/// fix. The parsed errors are returned even when the LLM is unavailable.
+#[tauri::command]
+pub async fn explain_latex_error(
+    state: State<'_, LLMState>,
+    log: String,
+    use_llm: bool,
+) -> Result<LatexErrorExplanation, AppError> {
+    let errors = crate::document::latex::parse_errors(&log);
+
+    let mut suggestion = None;
+    if use_llm && !errors.is_empty() {
+        let (client, config) = state.client();
+        match request_latex_fix(client.as_ref(), &config, &errors).await {
+            Ok(fix) => suggestion = Some(fix),
+            Err(e) => tracing::warn!("Returning LaTeX errors without a suggestion: {}", e),
+        }
+    }
+
+    Ok(LatexErrorExplanation { errors, suggestion })
+}
+
+/// Ask the LLM for flashcards grounded in the given document text
+async fn request_flashcards(
+    client: &dyn LLMClient,
+    config: &ProviderConfig,
+    document_text: &str,
+    count: usize,
+) -> Result<Vec<Flashcard>, AppError> {
+    let query = format!(
+        "Create exactly {} flashcards covering this document.",
+        count
+    );
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: prompts::FLASHCARD_PROMPT.to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: prompts::build_prompt(
+                "",
+                clip_to_budget(document_text, FLASHCARD_CONTEXT_CHAR_BUDGET),
+                &query,
+            ),
+        },
+    ];
+
+    let response = client.chat(messages, config).await.map_err(|e| {
+        tracing::error!("Flashcard generation failed: {}", e);
+        crate::error::LlmError::InferenceError(e.to_string())
+    })?;
+
+    parse_flashcards(&response, count)
+}
+
+/// Generate study flashcards from a document, optionally saving them
+#[tauri::command]
+pub async fn generate_flashcards(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    document_id: String,
+    count: usize,
+    save: Option<bool>,
+) -> Result<Vec<Flashcard>, AppError> {
+    tracing::info!("Generating {} flashcards for {}", count, document_id);
+
+    if count == 0 || count > MAX_FLASHCARDS {
+        return Err(crate::error::LlmError::InferenceError(format!(
+            "Flashcard count must be between 1 and {}",
+            MAX_FLASHCARDS
+        ))
+        .into());
+    }
+
+    let path = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+    let text = document
+        .pages
+        .iter()
+        .map(|p| p.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let (client, config) = state.client();
+    let cards = request_flashcards(client.as_ref(), &config, &text, count).await?;
+
+    if save.unwrap_or(false) {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        storage::insert_flashcards(&conn, &document_id, &cards)?;
+    }
+
+    Ok(cards)
+}
+
+/// Tags from an LLM response: lowercased, without a leading '#', deduplicated and, when a
+/// vocabulary is given, limited to it (spelled as in the vocabulary)
+fn parse_tags(
+    response: &str,
+    count: usize,
+    vocabulary: Option<&[String]>,
+) -> Result<Vec<String>, AppError> {
+    // Models sometimes wrap the array in prose or code fences
+    let json = match (response.find('['), response.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => {
+            return Err(crate::error::LlmError::InferenceError(
+                "Tag response did not contain a JSON array".to_string(),
+            )
+            .into())
+        }
+    };
+    let raw: Vec<String> = serde_json::from_str(json).map_err(|e| {
+        crate::error::LlmError::InferenceError(format!("Invalid tag JSON: {}", e))
+    })?;
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw {
+        let tag = tag.trim().trim_start_matches('#').split_whitespace().collect::<Vec<_>>();
+        let tag = tag.join(" ").to_lowercase();
+        let tag = match vocabulary {
+            Some(vocabulary) => match vocabulary.iter().find(|v| v.to_lowercase() == tag) {
+                Some(allowed) => allowed.clone(),
+                None => continue,
+            },
+            None => tag,
+        };
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    tags.truncate(count);
+    Ok(tags)
+}
+
+/// Ask the LLM for topic tags for a document, given its summary and opening text
+async fn request_tags(
+    client: &dyn LLMClient,
+    config: &ProviderConfig,
+    document_text: &str,
+    count: usize,
+    vocabulary: Option<&[String]>,
+) -> Result<Vec<String>, AppError> {
+    let mut query = format!("Suggest up to {} topic tags for this document.", count);
+    if let Some(vocabulary) = vocabulary {
+        query.push_str(&format!(
+            " Choose only from these tags: {}.",
+            serde_json::to_string(vocabulary).unwrap_or_default()
+        ));
+    }
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: prompts::TAG_PROMPT.to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: prompts::build_prompt("", document_text, &query),
+        },
+    ];
+
+    let response = client.chat(messages, config).await.map_err(|e| {
+        tracing::error!("Tag suggestion failed: {}", e);
+        crate::error::LlmError::InferenceError(e.to_string())
+    })?;
+
+    parse_tags(&response, count, vocabulary)
+}
+
+/// Suggest topic tags for a document that it does not have yet. Accepted suggestions are
+/// applied with `add_tag`. With a `vocabulary`, only tags from it are suggested.
+#[tauri::command]
+pub async fn suggest_tags(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    document_id: String,
+    count: usize,
+    vocabulary: Option<Vec<String>>,
+) -> Result<Vec<String>, AppError> {
+    tracing::info!("Suggesting {} tags for {}", count, document_id);
+
+    if count == 0 || count > MAX_TAG_SUGGESTIONS {
+        return Err(crate::error::LlmError::InferenceError(format!(
+            "Tag count must be between 1 and {}",
+            MAX_TAG_SUGGESTIONS
+        ))
+        .into());
+    }
+
+    let (path, existing) = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        (
+            storage::get_document_path(&conn, &document_id)?,
+            storage::get_tags(&conn, &document_id)?,
+        )
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    let (client, config) = state.client_for_mode(QueryMode::Summarize);
+    let db = app.state::<Database>();
+    let summary = summarize_document(client.as_ref(), &config, &db, &document).await?;
+    let context = format!(
+        "Summary: {}\n\n{}",
+        summary.summary,
+        clip_to_budget(&summary_context(&document), SUMMARY_CONTEXT_CHAR_BUDGET)
+    );
+
+    // Ask for extra in case some are tags the document already has
+    let (client, config) = state.client();
+    let vocabulary = vocabulary.filter(|v| !v.is_empty());
+    let tags = request_tags(
+        client.as_ref(),
+        &config,
+        &context,
+        count + existing.len(),
+        vocabulary.as_deref(),
+    )
+    .await?;
+
+    Ok(tags
+        .into_iter()
+        .filter(|tag| !existing.iter().any(|e| e.eq_ignore_ascii_case(tag)))
+        .take(count)
+        .collect())
+}
+
+/// Document text for a summary: the abstract, introduction and conclusion sections
+/// first, then the rest in reading order, until the budget is filled
+fn summary_context(document: &Document) -> String {
+    let is_key = |section: &Section| {
+        let title = section.title.to_lowercase();
+        SUMMARY_KEY_SECTIONS.iter().any(|key| title.contains(key))
+    };
+    let (key, rest): (Vec<Section>, Vec<Section>) =
+        segment_sections(document).into_iter().partition(is_key);
+
+    let mut text = String::new();
+    for section in key.iter().chain(&rest) {
+        if text.len() >= SUMMARY_CONTEXT_CHAR_BUDGET {
+            break;
+        }
+        text.push_str(&section.text(document));
+        text.push_str("\n\n");
+    }
+    text
+}
+
+/// Return the cached summary for a document, generating and caching one if needed
+async fn summarize_document(
+    client: &dyn LLMClient,
+    config: &ProviderConfig,
+    db: &Database,
+    document: &Document,
+) -> Result<DocumentSummary, AppError> {
+    let cached = {
+        let conn = db.conn.lock().unwrap();
+        storage::get_summary(&conn, &document.id)?
+    };
+    if let Some(summary) = cached {
+        return Ok(DocumentSummary {
+            document_id: document.id.clone(),
+            summary,
+            cached: true,
+        });
+    }
+
+    let text = summary_context(document);
+    let (answer, _) = call_llm(
+        client,
+        config,
+        prompts::SHORT_SUMMARY_PROMPT,
+        clip_to_budget(&text, SUMMARY_CONTEXT_CHAR_BUDGET),
+        "Summarize this document in one sentence.",
+    )
+    .await?;
+
+    let summary = answer
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches('"')
+        .to_string();
+    if summary.is_empty() {
+        return Err(crate::error::LlmError::InferenceError(
+            "Summary response was empty".to_string(),
+        )
+        .into());
+    }
+
+    {
+        let conn = db.conn.lock().unwrap();
+        storage::save_summary(&conn, &document.id, &summary)?;
+    }
+
+    Ok(DocumentSummary {
+        document_id: document.id.clone(),
+        summary,
+        cached: false,
+    })
+}
+
+/// Summarize a document and emit `document:summary_ready` through `emit`
+async fn run_document_summary<F>(
+    client: &dyn LLMClient,
+    config: &ProviderConfig,
+    db: &Database,
+    document: &Document,
+    emit: F,
+) where
+    F: FnOnce(&DocumentSummary),
+{
+    match summarize_document(client, config, db, document).await {
+        Ok(summary) => {
+            tracing::info!(
+                "Summary ready for {} (cached: {})",
+                document.id,
+                summary.cached
+            );
+            emit(&summary);
+        }
+        Err(e) => tracing::warn!("Auto-summary failed for {}: {}", document.id, e),
+    }
+}
+
+/// Generate the document's short summary in the background after it is opened
+pub(crate) fn spawn_document_summary(app: AppHandle, document: Document) {
+    tauri::async_runtime::spawn(async move {
+        let (client, config) = app.state::<LLMState>().client_for_mode(QueryMode::Summarize);
+        let db = app.state::<Database>();
+        run_document_summary(client.as_ref(), &config, &db, &document, |summary| {
+            let _ = app.emit("document:summary_ready", summary);
+        })
+        .await;
+    });
+}
+
+/// Get the cached summary for a document, if one has been generated
+#[tauri::command]
+pub async fn get_document_summary(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Option<String>, AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    storage::get_summary(&conn, &document_id)
+}
+
+/// Ask the LLM to translate a single paragraph
+async fn request_translation(
+    client: &dyn LLMClient,
+    config: &ProviderConfig,
+    text: &str,
+    language: &str,
+) -> Result<String, AppError> {
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: prompts::translation_prompt(language),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: text.to_string(),
+        },
+    ];
+
+    let response = client
+        .chat(messages, config)
+        .await
+        .map_err(|e| crate::error::LlmError::InferenceError(e.to_string()))?;
+    let translation = response.trim();
+    if translation.is_empty() {
+        return Err(crate::error::LlmError::InferenceError(
+            "Translation response was empty".to_string(),
+        )
+        .into());
+    }
+    Ok(translation.to_string())
+}
+
+/// Translate every paragraph without a stored translation, a few at a time, storing
+/// each as it arrives
+async fn translate_paragraphs(
+    client: Arc<dyn LLMClient>,
+    config: &ProviderConfig,
+    db: &Database,
+    document: &Document,
+    language: &str,
+) -> Result<TranslationSummary, AppError> {
+    let done = {
+        let conn = db.conn.lock().unwrap();
+        storage::get_translated_paragraph_ids(&conn, &document.id, language)?
+    };
+
+    let mut seen = HashSet::new();
+    let paragraphs: Vec<&Paragraph> = document
+        .pages
+        .iter()
+        .flat_map(|page| &page.paragraphs)
+        .filter(|p| !p.text.trim().is_empty() && seen.insert(p.id.as_str()))
+        .collect();
+    let mut summary = TranslationSummary {
+        document_id: document.id.clone(),
+        language: language.to_string(),
+        translated: 0,
+        cached: paragraphs.iter().filter(|p| done.contains(&p.id)).count(),
+        failed: 0,
+    };
+
+    let semaphore = Arc::new(Semaphore::new(TRANSLATION_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for paragraph in paragraphs.into_iter().filter(|p| !done.contains(&p.id)) {
+        let (client, config, semaphore) = (client.clone(), config.clone(), semaphore.clone());
+        let (paragraph_id, text) = (paragraph.id.clone(), paragraph.text.clone());
+        let language = language.to_string();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let result = request_translation(client.as_ref(), &config, &text, &language).await;
+            (paragraph_id, result)
+        });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((paragraph_id, Ok(translation))) => {
+                let conn = db.conn.lock().unwrap();
+                let id = &paragraph_id;
+                storage::save_translation(&conn, &document.id, id, language, &translation)?;
+                summary.translated += 1;
+            }
+            Ok((paragraph_id, Err(e))) => {
+                tracing::warn!("Translating paragraph {} failed: {}", paragraph_id, e);
+                summary.failed += 1;
+            }
+            Err(e) => {
+                tracing::warn!("Translation task failed: {}", e);
+                summary.failed += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Translate each paragraph of a document into a language and store the results.
+/// Paragraphs translated earlier are reused rather than sent again.
+#[tauri::command]
+pub async fn translate_document(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    document_id: String,
+    target_language: String,
+) -> Result<TranslationSummary, AppError> {
+    let language = target_language.trim().to_lowercase();
+    if language.is_empty() {
+        return Err(crate::error::LlmError::InferenceError(
+            "No target language given".to_string(),
+        )
+        .into());
+    }
+    tracing::info!("Translating {} into {}", document_id, language);
+
+    let path = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    let (client, config) = state.client();
+    let db = app.state::<Database>();
+    translate_paragraphs(Arc::from(client), &config, &db, &document, &language).await
+}
+
+/// Get the stored translation of a paragraph, if it has been translated
+#[tauri::command]
+pub async fn get_translation(
+    app: AppHandle,
+    document_id: String,
+    paragraph_id: String,
+    language: String,
+) -> Result<Option<String>, AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    storage::get_translation(
+        &conn,
+        &document_id,
+        &paragraph_id,
+        &language.trim().to_lowercase(),
+    )
+}
+
+/// Whether the configured model accepts images, going by its provider's model list
+/// (for Ollama, the models installed on the server)
+async fn model_supports_vision(config: &ProviderConfig) -> bool {
+    let models = if config.provider == LLMProvider::Ollama {
+        OllamaClient::new().list_models(config).await.unwrap_or_default()
+    } else {
+        get_available_models(&config.provider).models
+    };
+    models.iter().any(|m| m.id == config.model && m.supports_vision)
+}
+
+/// Ask the LLM to describe each image, saving every description as it arrives.
+/// Stops at the first failure, keeping the descriptions saved before it.
+async fn describe_page_images(
+    client: &dyn LLMClient,
+    config: &ProviderConfig,
+    db: &Database,
+    document_id: &str,
+    images: Vec<PageImage>,
+) -> Result<Vec<ImageDescription>, AppError> {
+    let mut descriptions = Vec::with_capacity(images.len());
+    for image in images {
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: prompts::IMAGE_DESCRIPTION_PROMPT.to_string(),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: format!("Describe this image from page {}.", image.page),
+            },
+        ];
+        let chat_image = ChatImage {
+            mime_type: image.mime_type.to_string(),
+            data: image.data,
+        };
+        let answer = client
+            .chat_with_image(messages, &chat_image, config)
+            .await
+            .map_err(|e| {
+                tracing::error!("Describing image on page {} failed: {}", image.page, e);
+                crate::error::LlmError::InferenceError(e.to_string())
+            })?;
+
+        let description = ImageDescription {
+            page: image.page,
+            image_index: image.index,
+            description: answer.trim().to_string(),
+            model: config.model.clone(),
+        };
+        {
+            let conn = db.conn.lock().unwrap();
+            storage::save_image_description(&conn, document_id, &description)?;
+        }
+        descriptions.push(description);
+    }
+    Ok(descriptions)
+}
+
+/// Describe the images in a PDF with the active model, which must accept images.
+/// Descriptions are stored by page and image, replacing earlier ones.
+#[tauri::command]
+pub async fn describe_images(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    document_id: String,
+) -> Result<Vec<ImageDescription>, AppError> {
+    let (client, config) = state.client();
+    if !model_supports_vision(&config).await {
+        return Err(crate::error::LlmError::VisionUnsupported(config.model).into());
+    }
+
+    let path = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        storage::get_document_path(&conn, &document_id)?
+    };
+    if !path.to_lowercase().ends_with(".pdf") {
+        return Err(crate::error::DocumentError::UnsupportedFormat(path).into());
+    }
+    let images = tokio::task::spawn_blocking(move || {
+        crate::document::extract_page_images(&path)
     })
+    .await
+    .map_err(std::io::Error::other)??;
+    tracing::info!("Describing {} images in {}", images.len(), document_id);
+
+    let db = app.state::<Database>();
+    describe_page_images(client.as_ref(), &config, &db, &document_id, images).await
+}
+
+/// Get the stored image descriptions of a document, in page order
+#[tauri::command]
+pub async fn get_image_descriptions(
+    app: AppHandle,
+    document_id: String,
+) -> Result<Vec<ImageDescription>, AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    storage::get_image_descriptions(&conn, &document_id)
 }
 
 /// Get the current status of the LLM model
@@ -160,7 +1580,7 @@ pub async fn get_model_status(
     _app: AppHandle,
     state: State<'_, LLMState>,
 ) -> Result<ModelStatus, AppError> {
-    let config = state.config.lock().unwrap();
+    let config = state.active_config();
     Ok(ModelStatus {
         loaded: config.api_key.is_some() || config.provider == LLMProvider::Bedrock || config.provider == LLMProvider::Ollama,
         model_name: Some(config.model.clone()),
@@ -229,17 +1649,80 @@ pub struct ProviderInfo {
     pub supports_streaming: bool,
 }
 
-/// Get available models for a provider
+/// Get available models for a provider. For Ollama these are the models pulled onto
+/// the server, falling back to the suggested list when it cannot be reached.
 #[tauri::command]
-pub async fn get_provider_models(provider: String) -> Result<AvailableModels, AppError> {
+pub async fn get_provider_models(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    provider: String,
+) -> Result<AvailableModels, AppError> {
     let llm_provider = parse_provider(&provider);
+    if llm_provider == LLMProvider::Ollama {
+        let active = state.config.lock().unwrap().clone();
+        let config = if active.provider == LLMProvider::Ollama {
+            active
+        } else {
+            let db = app.state::<Database>();
+            let conn = db.conn.lock().unwrap();
+            storage::get_llm_provider(&conn, &LLMProvider::Ollama)?
+                .unwrap_or_else(|| ProviderConfig::ollama(""))
+        };
+        match OllamaClient::new().list_models(&config).await {
+            Ok(models) => {
+                return Ok(AvailableModels {
+                    provider: llm_provider,
+                    models,
+                })
+            }
+            Err(e) => tracing::warn!("Could not list installed Ollama models: {}", e),
+        }
+    }
     Ok(get_available_models(&llm_provider))
 }
 
-/// Set LLM configuration
+/// Projected size and price of a query, from `estimate_query_cost`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryCostEstimate {
+    pub input_tokens: u32,
+    /// Answer length the cost assumes
+    pub output_tokens: u32,
+    /// Dollars; `None` for free and local models, or models without listed pricing
+    pub cost: Option<f64>,
+}
+
+/// Estimate the tokens and dollar cost of sending an assembled `prompt` to `model`,
+/// before sending it. The answer is priced at `output_tokens`, or at the configured
+/// maximum answer length when not given.
+#[tauri::command]
+pub async fn estimate_query_cost(
+    state: State<'_, LLMState>,
+    provider: String,
+    model: String,
+    prompt: String,
+    output_tokens: Option<u32>,
+) -> Result<QueryCostEstimate, AppError> {
+    let provider = parse_provider(&provider);
+    let input_tokens = tokens::estimate_tokens(&prompt, &provider);
+    let output_tokens = output_tokens.unwrap_or_else(|| state.config.lock().unwrap().max_tokens);
+    let cost = get_available_models(&provider)
+        .models
+        .iter()
+        .find(|m| m.id == model)
+        .and_then(|m| tokens::estimate_cost(input_tokens, output_tokens, m));
+
+    Ok(QueryCostEstimate {
+        input_tokens,
+        output_tokens,
+        cost,
+    })
+}
+
+/// Set LLM configuration and save it as the active provider. Without an API key the
+/// key saved for the provider is reused, then its environment variable.
 #[tauri::command]
 pub async fn set_llm_config(
-    _app: AppHandle,
+    app: AppHandle,
     state: State<'_, LLMState>,
     provider: String,
     model: String,
@@ -249,50 +1732,286 @@ pub async fn set_llm_config(
     tracing::info!("Setting LLM config: provider={}, model={}", provider, model);
 
     let llm_provider = parse_provider(&provider);
+    let stored = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        let saved_key = storage::get_provider_key(&conn, &llm_provider)?;
+        let stored = StoredLlmConfig {
+            provider: llm_provider,
+            model,
+            api_key: api_key.or(saved_key),
+            api_url,
+        };
+        stored.save_active(&conn)?;
+        stored
+    };
+
+    if let Some(api_key) = &stored.api_key {
+        state.set_provider_key(stored.provider.clone(), api_key.clone());
+    }
+    state.activate(stored);
+    tracing::info!("LLM config updated successfully");
+
+    Ok(())
+}
+
+/// Make `provider` the active provider with `model`, keeping the API key and URL saved
+/// for it. The model must be one `get_available_models` lists for the provider, except
+/// with Ollama.
+fn switch_provider(
+    conn: &rusqlite::Connection,
+    provider: LLMProvider,
+    model: String,
+) -> Result<StoredLlmConfig, AppError> {
+    // Ollama serves whatever has been pulled, which the server checks itself
+    let available = get_available_models(&provider);
+    if provider != LLMProvider::Ollama && !available.models.iter().any(|m| m.id == model) {
+        return Err(crate::error::LlmError::UnknownModel(format!(
+            "{} is not available for {:?}",
+            model, provider
+        ))
+        .into());
+    }
+
+    let api_key = storage::get_provider_key(conn, &provider)?;
+    let stored = match storage::get_llm_provider(conn, &provider)? {
+        Some(saved) => StoredLlmConfig {
+            model,
+            ..StoredLlmConfig::from_saved(saved, api_key)
+        },
+        None => StoredLlmConfig {
+            provider,
+            model,
+            api_key,
+            api_url: None,
+        },
+    };
+    stored.save_active(conn)?;
+    Ok(stored)
+}
+
+/// Switch the active provider and model, reusing the API key saved for that provider
+#[tauri::command]
+pub async fn switch_llm_provider(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    provider: String,
+    model: String,
+) -> Result<(), AppError> {
+    tracing::info!("Switching LLM provider to {} with model {}", provider, model);
+
+    let stored = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        switch_provider(&conn, parse_provider(&provider), model)?
+    };
+    state.activate(stored);
+
+    Ok(())
+}
+
+/// Load the saved API keys and use the provider saved as active by `set_llm_config` or
+/// `switch_llm_provider`, if any
+pub fn restore_llm_provider(app: &AppHandle) {
+    let loaded = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        storage::get_provider_keys(&conn)
+            .and_then(|keys| Ok((keys, storage::get_active_llm_provider(&conn)?)))
+    };
+    let (keys, saved) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            tracing::error!("Failed to load the saved LLM provider: {}", e);
+            return;
+        }
+    };
+
+    let state = app.state::<LLMState>();
+    if let Some(saved) = saved {
+        tracing::info!("Restored LLM provider {:?} ({})", saved.provider, saved.model);
+        let api_key = keys
+            .iter()
+            .find(|(provider, _)| *provider == saved.provider)
+            .map(|(_, key)| key.clone());
+        state.activate(StoredLlmConfig::from_saved(saved, api_key));
+    }
+    *state.keys.lock().unwrap() = keys;
+}
 
-    // Resolve API key: use provided key, or fall back to env var
-    let resolved_key = api_key.or_else(|| match llm_provider {
-        LLMProvider::OpenAI => std::env::var("OPENAI_API_KEY").ok(),
-        LLMProvider::Anthropic => std::env::var("ANTHROPIC_API_KEY").ok(),
-        LLMProvider::Gemini => std::env::var("GEMINI_API_KEY").ok(),
-        LLMProvider::Groq => std::env::var("GROQ_API_KEY").ok(),
+/// Environment variable holding a provider's API key
+fn api_key_var(provider: &LLMProvider) -> Option<&'static str> {
+    match provider {
+        LLMProvider::OpenAI => Some("OPENAI_API_KEY"),
+        LLMProvider::Anthropic => Some("ANTHROPIC_API_KEY"),
+        LLMProvider::Gemini => Some("GEMINI_API_KEY"),
+        LLMProvider::Groq => Some("GROQ_API_KEY"),
         _ => None,
-    });
+    }
+}
+
+/// API key for a provider from its environment variable
+fn env_api_key(provider: &LLMProvider) -> Option<String> {
+    std::env::var(api_key_var(provider)?).ok()
+}
+
+/// Show only the ends of an API key, or nothing for short keys
+fn redact_api_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}...{}", head, tail)
+}
 
-    let config = ProviderConfig {
-        provider: llm_provider,
-        api_key: resolved_key,
-        api_url,
-        model,
-        ..Default::default()
+/// Report `config`, the config requests are sent with, noting where each value came
+/// from: the stored settings, the environment (read by `ProviderConfig::from_env` when
+/// nothing is stored), or the defaults.
+fn resolve_effective_config(
+    stored: Option<&StoredLlmConfig>,
+    config: &ProviderConfig,
+    key_source: ConfigSource,
+) -> EffectiveLlmConfig {
+    use ConfigSource::{Default as FromDefault, Env, Stored};
+
+    let defaults = ProviderConfig::default();
+    // Without stored settings the config is `from_env`'s, which is the default unless
+    // the environment picked a provider
+    let unstored = if config.provider != defaults.provider
+        || config.model != defaults.model
+        || config.api_url != defaults.api_url
+    {
+        Env
+    } else {
+        FromDefault
+    };
+    let (chosen, tuned) = match stored {
+        Some(_) => (Stored, FromDefault),
+        None => (unstored, unstored),
+    };
+    let api_url_source = match stored {
+        Some(s) if s.api_url.is_some() => Stored,
+        _ => tuned,
     };
 
-    *state.config.lock().unwrap() = config;
-    tracing::info!("LLM config updated successfully");
+    EffectiveLlmConfig {
+        provider: SourcedValue::new(config.provider.clone(), chosen),
+        model: SourcedValue::new(config.model.clone(), chosen),
+        api_key: SourcedValue::new(config.api_key.as_deref().map(redact_api_key), key_source),
+        api_url: SourcedValue::new(config.api_url.clone(), api_url_source),
+        max_tokens: SourcedValue::new(config.max_tokens, tuned),
+        temperature: SourcedValue::new(config.temperature, tuned),
+    }
+}
+
+/// Set the providers to fail over to, in order, when the primary provider errors.
+/// An empty chain disables failover. Providers given without an API key use the key
+/// saved for them, then their environment variable.
+#[tauri::command]
+pub async fn set_llm_fallback_chain(
+    state: State<'_, LLMState>,
+    chain: Vec<ProviderConfig>,
+) -> Result<(), AppError> {
+    tracing::info!(
+        "Setting LLM fallback chain: {:?}",
+        chain.iter().map(|c| &c.provider).collect::<Vec<_>>()
+    );
+    *state.fallback_chain.lock().unwrap() = chain;
+
+    Ok(())
+}
+
+/// Get the sampling overrides used for each query mode
+#[tauri::command]
+pub async fn get_generation_params(
+    state: State<'_, LLMState>,
+) -> Result<HashMap<QueryMode, GenerationParams>, AppError> {
+    Ok(state.mode_params.lock().unwrap().clone())
+}
+
+/// Set the sampling overrides for one query mode
+#[tauri::command]
+pub async fn set_generation_params(
+    state: State<'_, LLMState>,
+    mode: QueryMode,
+    params: GenerationParams,
+) -> Result<(), AppError> {
+    tracing::info!("Setting generation params for {:?}: {:?}", mode, params);
+
+    state.mode_params.lock().unwrap().insert(mode, params);
+    Ok(())
+}
+
+/// Turn the LLM audit log on or off; while on, every request and response is stored
+#[tauri::command]
+pub async fn set_llm_audit_enabled(
+    app: AppHandle,
+    state: State<'_, LLMState>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    tracing::info!("LLM audit log {}", if enabled { "enabled" } else { "disabled" });
 
+    let sink: Option<AuditSink> = enabled.then(|| {
+        let app = app.clone();
+        Arc::new(move |entry: LlmAuditEntry| {
+            let db = app.state::<Database>();
+            let conn = db.conn.lock().unwrap();
+            if let Err(e) = storage::insert_llm_audit(&conn, &entry) {
+                tracing::warn!("Failed to record LLM audit entry: {}", e);
+            }
+        }) as AuditSink
+    });
+    *state.audit.lock().unwrap() = sink;
     Ok(())
 }
 
+/// Most recent LLM audit entries, newest first
+#[tauri::command]
+pub async fn get_llm_audit(app: AppHandle, limit: usize) -> Result<Vec<LlmAuditEntry>, AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    storage::get_llm_audit(&conn, limit)
+}
+
+/// Delete the LLM audit log, returning the number of entries removed
+#[tauri::command]
+pub async fn clear_llm_audit(app: AppHandle) -> Result<usize, AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    storage::clear_llm_audit(&conn)
+}
+
 /// Get current LLM configuration
 #[tauri::command]
 pub async fn get_llm_config(
     _app: AppHandle,
     state: State<'_, LLMState>,
 ) -> Result<LLMConfig, AppError> {
-    let config = state.config.lock().unwrap().clone();
+    let config = state.active_config();
     // Return config with API key redacted for security
     let mut safe_config = config.clone();
-    if let Some(ref key) = safe_config.api_key {
-        if key.len() > 8 {
-            safe_config.api_key = Some(format!("{}...{}", &key[..4], &key[key.len()-4..]));
-        }
-    }
+    safe_config.api_key = safe_config.api_key.as_deref().map(redact_api_key);
     Ok(LLMConfig {
         provider: safe_config.provider.clone(),
         config: safe_config,
     })
 }
 
+/// Get the LLM configuration in effect, with the source (stored/env/default) of each field
+#[tauri::command]
+pub async fn get_effective_llm_config(
+    state: State<'_, LLMState>,
+) -> Result<EffectiveLlmConfig, AppError> {
+    let stored = state.stored.lock().unwrap().clone();
+    Ok(resolve_effective_config(
+        stored.as_ref(),
+        &state.active_config(),
+        state.api_key_source(),
+    ))
+}
+
 /// Test the LLM connection
 #[tauri::command]
 pub async fn test_llm_connection(
@@ -301,7 +2020,7 @@ pub async fn test_llm_connection(
 ) -> Result<String, AppError> {
     tracing::info!("Testing LLM connection...");
 
-    let config = state.config.lock().unwrap().clone();
+    let config = state.active_config();
     let client = create_client(&config.provider);
 
     let messages = vec![ChatMessage {
@@ -337,3 +2056,813 @@ fn parse_provider(provider: &str) -> LLMProvider {
         _ => LLMProvider::OpenAI,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::providers::OpenAIClient;
+    use rusqlite::Connection;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Records the messages it receives and replies with a fixed answer
+    struct MockClient {
+        received: Mutex<Vec<ChatMessage>>,
+        reply: String,
+    }
+
+    impl MockClient {
+        fn new(reply: &str) -> Self {
+            Self {
+                received: Mutex::new(Vec::new()),
+                reply: reply.to_string(),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LLMClient for MockClient {
+        async fn chat(
+            &self,
+            messages: Vec<ChatMessage>,
+            _config: &ProviderConfig,
+        ) -> Result<String, LLMError> {
+            *self.received.lock().unwrap() = messages;
+            Ok(self.reply.clone())
+        }
+    }
+
+    fn test_db() -> Database {
+        let conn = Connection::open_in_memory().unwrap();
+        storage::run_migrations(&conn).unwrap();
+        Database::new(conn)
+    }
+
+    fn msg(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_context_falls_back_to_document_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "Attention replaces recurrence.\n\nIt is faster to train.").unwrap();
+        let document = crate::document::parser::parse_document(path.to_str().unwrap())
+            .await
+            .unwrap();
+        let db = test_db();
+        storage::upsert_document(&db.conn.lock().unwrap(), &document).unwrap();
+
+        let selected = query_context(&db, Some(&document.id), Some("Selected.".to_string()));
+        assert_eq!(selected.await.unwrap(), "Selected.");
+        let whole = query_context(&db, Some(&document.id), None).await.unwrap();
+        assert_eq!(whole, "Attention replaces recurrence.\n\nIt is faster to train.");
+        assert_eq!(query_context(&db, None, None).await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn test_followup_includes_prior_turns_and_persists() {
+        let db = test_db();
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute_batch(
+                "INSERT INTO documents (id, file_path) VALUES ('doc1', 'a.pdf'), ('doc2', 'b.pdf');",
+            )
+            .unwrap();
+            storage::insert_chat_message(&conn, "doc1", "user", "What is the main result?", None)
+                .unwrap();
+            storage::insert_chat_message(&conn, "doc1", "assistant", "A 10% gain.", None)
+                .unwrap();
+            storage::insert_chat_message(&conn, "doc2", "user", "Unrelated", None).unwrap();
+        }
+
+        let client = MockClient::new("Because of the ablation results.");
+        let config = ProviderConfig::default();
+        let response = run_followup(&client, &config, &db, "doc1", prompts::QA_PROMPT, "And why?")
+            .await
+            .unwrap();
+        assert_eq!(response.answer, "Because of the ablation results.");
+
+        let sent = client.received.lock().unwrap().clone();
+        let contents: Vec<&str> = sent.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(sent[0].role, "system");
+        assert_eq!(
+            &contents[1..],
+            &["What is the main result?", "A 10% gain.", "And why?"]
+        );
+
+        let conn = db.conn.lock().unwrap();
+        let stored = storage::recent_chat_messages(&conn, "doc1", 10).unwrap();
+        assert_eq!(stored.len(), 4);
+        assert_eq!(stored[2].content, "And why?");
+        assert_eq!(stored[3].role, "assistant");
+        assert_eq!(stored[3].content, "Because of the ablation results.");
+    }
+
+    #[tokio::test]
+    async fn test_simplify_includes_prior_answer_level_and_conversation() {
+        let db = test_db();
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute("INSERT INTO documents (id, file_path) VALUES ('doc1', 'a.pdf')", [])
+                .unwrap();
+            storage::insert_chat_message(&conn, "doc1", "user", "What is attention?", None)
+                .unwrap();
+        }
+
+        let previous = "Attention computes softmax(QK^T / sqrt(d)) V over token embeddings.";
+        let client = MockClient::new("It lets each word look at the other words.");
+        let request = prompts::simplify_request(previous, "high school");
+        let config = ProviderConfig::default();
+        let system = prompts::PROFESSOR_PROMPT;
+        let response = run_followup(&client, &config, &db, "doc1", system, &request)
+            .await
+            .unwrap();
+        assert_eq!(response.answer, "It lets each word look at the other words.");
+
+        let sent = client.received.lock().unwrap().clone();
+        assert_eq!(sent[0].content, prompts::PROFESSOR_PROMPT);
+        assert_eq!(sent[1].content, "What is attention?");
+        let last = &sent.last().unwrap().content;
+        assert_eq!(sent.last().unwrap().role, "user");
+        assert!(last.contains("for a high school reader"), "{}", last);
+        assert!(last.contains(previous), "{}", last);
+    }
+
+    #[test]
+    fn test_followup_trims_old_turns() {
+        let long = "x".repeat(FOLLOWUP_HISTORY_CHAR_BUDGET);
+        let history = vec![
+            msg("user", &long),
+            msg("assistant", "old answer"),
+            msg("user", "recent question"),
+            msg("assistant", "recent answer"),
+        ];
+
+        let messages = build_followup_messages("sys", history, "next");
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["sys", "recent question", "recent answer", "next"]);
+    }
+
+    #[tokio::test]
+    async fn test_flashcards_parsed_with_requested_count() {
+        let reply = r#"Here you go:
+```json
+[
+  {"question": "What does the paper propose?", "answer": "A sparse attention scheme."},
+  {"question": "Which dataset is used?", "answer": "ImageNet."},
+  {"question": "What is the speedup?", "answer": "3x."}
+]
+```"#;
+        let client = MockClient::new(reply);
+        let cards = request_flashcards(&client, &ProviderConfig::default(), "paper text", 2)
+            .await
+            .unwrap();
+
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[0].question, "What does the paper propose?");
+        assert_eq!(cards[1].answer, "ImageNet.");
+
+        let sent = client.received.lock().unwrap();
+        assert!(sent[1].content.contains("paper text"));
+        assert!(sent[1].content.contains("exactly 2 flashcards"));
+    }
+
+    #[tokio::test]
+    async fn test_tags_parsed_and_limited_to_count() {
+        let reply = "Tags:\n```json\n[\"Transformers\", \"#Attention\", \"machine  translation\", \
+                     \"attention\", \"nlp\"]\n```";
+        let client = MockClient::new(reply);
+        let tags = request_tags(&client, &ProviderConfig::default(), "paper text", 3, None)
+            .await
+            .unwrap();
+        assert_eq!(tags, ["transformers", "attention", "machine translation"]);
+
+        let sent = client.received.lock().unwrap();
+        assert_eq!(sent[0].content, prompts::TAG_PROMPT);
+        assert!(sent[1].content.contains("paper text"));
+        assert!(sent[1].content.contains("up to 3 topic tags"));
+    }
+
+    #[tokio::test]
+    async fn test_tags_constrained_to_vocabulary() {
+        let client = MockClient::new(r#"["transformers", "NLP", "vision", "nlp"]"#);
+        let vocabulary = ["NLP".to_string(), "Vision".to_string(), "Robotics".to_string()];
+        let tags =
+            request_tags(&client, &ProviderConfig::default(), "paper text", 5, Some(&vocabulary))
+                .await
+                .unwrap();
+        assert_eq!(tags, ["NLP", "Vision"]);
+
+        let sent = client.received.lock().unwrap();
+        assert!(sent[1].content.contains(r#"["NLP","Vision","Robotics"]"#));
+        assert!(parse_tags("no json here", 3, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_latex_fix_request_lists_errors_with_source() {
+        let log = "! Undefined control sequence.\nl.8 The value is \\alpah\n    + 1.\n";
+        let errors = crate::document::latex::parse_errors(log);
+        let client = MockClient::new("  Use \\alpha instead of \\alpah.\n");
+
+        let fix = request_latex_fix(&client, &ProviderConfig::default(), &errors)
+            .await
+            .unwrap();
+        assert_eq!(fix, "Use \\alpha instead of \\alpah.");
+
+        let sent = client.received.lock().unwrap();
+        assert_eq!(sent[0].content, prompts::LATEX_FIX_PROMPT);
+        assert_eq!(
+            sent[1].content,
+            "line 8: Undefined control sequence.\n  source: The value is \\alpah"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_llm_keeps_only_marked_heading_candidates() {
+        let candidate = |id: &str, title: &str| DetectedHeading {
+            paragraph_id: id.to_string(),
+            page: 1,
+            title: title.to_string(),
+            level: 1,
+        };
+        let candidates = [
+            candidate("p1", "Method"),
+            candidate("p4", "Sparse Attention"),
+            candidate("p9", "Accuracy vs Speed"),
+        ];
+        let client = MockClient::new(
+            r#"[{"id": "p1", "level": 1}, {"id": "p4", "level": 2}, {"id": "p7", "level": 1}]"#,
+        );
+
+        let headings = request_headings(&client, &ProviderConfig::default(), &candidates)
+            .await
+            .unwrap();
+        let subsection = DetectedHeading {
+            level: 2,
+            ..candidate("p4", "Sparse Attention")
+        };
+        assert_eq!(headings, [candidate("p1", "Method"), subsection]);
+
+        let sent = client.received.lock().unwrap();
+        assert!(sent[1].content.contains(r#"{"id":"p9","text":"Accuracy vs Speed"}"#));
+        assert!(parse_detected_headings("no headings here", &candidates).is_err());
+    }
+
+    #[test]
+    fn test_flashcards_reject_bad_shape() {
+        assert!(parse_flashcards("no json here", 1).is_err());
+        assert!(parse_flashcards(r#"[{"question": "Q only"}]"#, 1).is_err());
+        assert!(parse_flashcards(r#"[{"question": "Q", "answer": " "}]"#, 1).is_err());
+        assert!(parse_flashcards(r#"[{"question": "Q", "answer": "A"}]"#, 2).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_summary_cached_and_event_fires() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("paper.txt");
+        std::fs::write(&path, "We propose a sparse attention scheme.\n\nIt is 3x faster.").unwrap();
+        let document = crate::document::parser::parse_document(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let db = test_db();
+        db.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO documents (id, file_path) VALUES (?1, ?2)",
+                [&document.id, &document.path],
+            )
+            .unwrap();
+
+        let client = MockClient::new("\"A sparse attention scheme that\n runs 3x faster.\"");
+        let mut emitted = Vec::new();
+        run_document_summary(&client, &ProviderConfig::default(), &db, &document, |s| {
+            emitted.push(s.clone())
+        })
+        .await;
+
+        assert_eq!(emitted.len(), 1);
+        assert_eq!(emitted[0].document_id, document.id);
+        assert_eq!(emitted[0].summary, "A sparse attention scheme that runs 3x faster.");
+        assert!(!emitted[0].cached);
+        assert!(client.received.lock().unwrap()[1].content.contains("sparse attention"));
+
+        let stored = storage::get_summary(&db.conn.lock().unwrap(), &document.id).unwrap();
+        assert_eq!(stored.as_deref(), Some("A sparse attention scheme that runs 3x faster."));
+
+        // Opening again is served from the cache without calling the LLM
+        let second = MockClient::new("unused");
+        let mut emitted = Vec::new();
+        run_document_summary(&second, &ProviderConfig::default(), &db, &document, |s| {
+            emitted.push(s.clone())
+        })
+        .await;
+
+        assert!(second.received.lock().unwrap().is_empty());
+        assert_eq!(emitted.len(), 1);
+        assert!(emitted[0].cached);
+        assert_eq!(emitted[0].summary, "A sparse attention scheme that runs 3x faster.");
+    }
+
+    /// Replies with the paragraph it was sent, tagged, tracking how many calls overlap
+    #[derive(Default)]
+    struct TaggingClient {
+        in_flight: std::sync::atomic::AtomicUsize,
+        max_in_flight: std::sync::atomic::AtomicUsize,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMClient for TaggingClient {
+        async fn chat(
+            &self,
+            messages: Vec<ChatMessage>,
+            _config: &ProviderConfig,
+        ) -> Result<String, LLMError> {
+            use std::sync::atomic::Ordering;
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(format!("[fr] {}", messages[1].content))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paragraph_translations_stored_and_reused() {
+        use std::sync::atomic::Ordering;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        let paragraphs: Vec<String> = (1..=10).map(|i| format!("Paragraph {}.", i)).collect();
+        std::fs::write(&path, paragraphs.join("\n\n")).unwrap();
+        let document = crate::document::parser::parse_document(path.to_str().unwrap())
+            .await
+            .unwrap();
+        let db = test_db();
+        db.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO documents (id, file_path) VALUES (?1, ?2)",
+                [&document.id, &document.path],
+            )
+            .unwrap();
+
+        let client = Arc::new(TaggingClient::default());
+        let config = ProviderConfig::default();
+        let summary = translate_paragraphs(client.clone(), &config, &db, &document, "french")
+            .await
+            .unwrap();
+        assert_eq!((summary.translated, summary.cached, summary.failed), (10, 0, 0));
+        assert!(client.max_in_flight.load(Ordering::SeqCst) <= TRANSLATION_CONCURRENCY);
+
+        {
+            let conn = db.conn.lock().unwrap();
+            for paragraph in document.pages.iter().flat_map(|p| &p.paragraphs) {
+                let stored = storage::get_translation(&conn, &document.id, &paragraph.id, "french");
+                assert_eq!(stored.unwrap(), Some(format!("[fr] {}", paragraph.text)));
+            }
+            let first = &document.pages[0].paragraphs[0].id;
+            let german = storage::get_translation(&conn, &document.id, first, "german");
+            assert_eq!(german.unwrap(), None);
+        }
+
+        // A second run only reads the stored translations
+        let again = translate_paragraphs(client.clone(), &config, &db, &document, "french")
+            .await
+            .unwrap();
+        assert_eq!((again.translated, again.cached), (0, 10));
+        assert_eq!(client.calls.load(Ordering::SeqCst), 10);
+    }
+
+    #[tokio::test]
+    async fn test_summary_context_puts_key_sections_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("paper.md");
+        let method = "We measured everything carefully. ".repeat(300);
+        let source = format!(
+            "# Introduction\n\nSparse attention.\n\n# Method\n\n{}\n\n\
+             # Conclusion\n\nIt is 3x faster.",
+            method.trim()
+        );
+        std::fs::write(&path, source).unwrap();
+        let document = crate::document::parser::parse_document(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let context = summary_context(&document);
+        let clipped = clip_to_budget(&context, SUMMARY_CONTEXT_CHAR_BUDGET);
+        assert!(clipped.starts_with("Introduction\n\nSparse attention.\n\nConclusion\n\nIt is"));
+        assert!(clipped.contains("We measured everything"));
+    }
+
+    #[test]
+    fn test_effective_params_differ_per_mode() {
+        let state = LLMState::new();
+        state.mode_params.lock().unwrap().insert(
+            QueryMode::GenerateCode,
+            GenerationParams {
+                temperature: Some(0.1),
+                top_p: Some(0.9),
+                stop: Some(vec!["```\n\n".to_string()]),
+            },
+        );
+
+        let (_, quick) = state.client_for_mode(QueryMode::QuickAnswer);
+        let (_, explain) = state.client_for_mode(QueryMode::Explain);
+        let (_, code) = state.client_for_mode(QueryMode::GenerateCode);
+        let (_, base) = state.client();
+
+        // Modes without overrides use the configured temperature
+        assert_eq!(quick.temperature, base.temperature);
+        assert_eq!(explain.temperature, base.temperature);
+        assert_eq!(code.temperature, 0.1);
+        assert_eq!(code.top_p, Some(0.9));
+        assert_eq!(code.stop, ["```\n\n"]);
+        assert_eq!(quick.top_p, None);
+        assert!(quick.stop.is_empty());
+        // Overrides don't leak into the base config
+        assert_eq!(base.temperature, state.config.lock().unwrap().temperature);
+        assert_eq!(base.top_p, None);
+    }
+
+    /// Serves one SSE token, then stalls; returns the base URL and a flag set when the
+    /// client hangs up
+    async fn slow_sse_server() -> (String, tokio::sync::oneshot::Receiver<()>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (closed_tx, closed_rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 8192];
+            let _ = socket.read(&mut buf).await;
+
+            let event = "data: {\"choices\":[{\"delta\":{\"content\":\"Partial \"}}]}\n\n";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                 Transfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n",
+                event.len(),
+                event
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+
+            // Never finish the stream; a read of 0 bytes means the client dropped us
+            let _ = tokio::time::timeout(Duration::from_secs(30), socket.read(&mut buf)).await;
+            let _ = closed_tx.send(());
+        });
+
+        (url, closed_rx)
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_stream_and_keeps_partial_output() {
+        let (url, closed) = slow_sse_server().await;
+        let config = ProviderConfig {
+            api_url: Some(url),
+            api_key: Some("test-key".to_string()),
+            ..Default::default()
+        };
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        let (first_tx, first_rx) = oneshot::channel();
+        let mut first_tx = Some(first_tx);
+
+        let stream = tokio::spawn(stream_until_cancelled(
+            Box::new(OpenAIClient::new()),
+            config,
+            query_messages("Be brief.", "", "Tell me a long story"),
+            cancel_rx,
+            move |_| {
+                if let Some(tx) = first_tx.take() {
+                    let _ = tx.send(());
+                }
+            },
+        ));
+
+        tokio::time::timeout(Duration::from_secs(5), first_rx)
+            .await
+            .expect("first token should arrive")
+            .unwrap();
+        let cancelled_at = Instant::now();
+        cancel_tx.send(()).unwrap();
+
+        let (answer, cancelled) = tokio::time::timeout(Duration::from_secs(1), stream)
+            .await
+            .expect("cancellation should stop the stream promptly")
+            .unwrap()
+            .unwrap();
+        assert!(cancelled);
+        assert_eq!(answer, "Partial ");
+        assert!(cancelled_at.elapsed() < Duration::from_secs(1));
+
+        // Aborting the task drops the HTTP request
+        tokio::time::timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("server should see the connection close")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_uncancelled_stream_returns_full_answer() {
+        let (_cancel_tx, cancel_rx) = oneshot::channel();
+        let mut tokens = Vec::new();
+
+        let (answer, cancelled) = stream_until_cancelled(
+            Box::new(MockClient::new("Whole answer")),
+            ProviderConfig::default(),
+            Vec::new(),
+            cancel_rx,
+            |token| tokens.push(token.to_string()),
+        )
+        .await
+        .unwrap();
+
+        assert!(!cancelled);
+        assert_eq!(answer, "Whole answer");
+        assert_eq!(tokens, ["Whole answer"]);
+    }
+
+    #[tokio::test]
+    async fn test_preview_matches_messages_sent_by_query() {
+        let config = ProviderConfig::default();
+        let context = "Transformers replace recurrence with attention.";
+        let question = "What replaces recurrence?";
+
+        for mode in QueryMode::ALL {
+            let preview = build_prompt_preview(mode, &config, context, question);
+
+            let client = MockClient::new("Attention.");
+            call_llm(&client, &config, system_prompt_for(mode), context, question)
+                .await
+                .unwrap();
+            let sent = client.received.lock().unwrap().clone();
+
+            assert_eq!(
+                serde_json::to_value(&preview.messages).unwrap(),
+                serde_json::to_value(&sent).unwrap(),
+                "{:?}",
+                mode
+            );
+            assert_eq!(preview.mode, mode);
+        }
+    }
+
+    #[test]
+    fn test_preview_estimates_tokens() {
+        let config = ProviderConfig::default();
+        let short = build_prompt_preview(QueryMode::QuickAnswer, &config, "", "Hi?");
+        let context = "word ".repeat(400);
+        let long = build_prompt_preview(QueryMode::QuickAnswer, &config, &context, "Hi?");
+
+        assert!(short.estimated_tokens > 2 * TOKENS_PER_MESSAGE);
+        // 2000 extra characters is roughly 500 extra tokens
+        assert_eq!(long.estimated_tokens - short.estimated_tokens, 500);
+        assert_eq!(short.model, config.model);
+    }
+
+    #[test]
+    fn test_effective_config_precedence() {
+        use ConfigSource::{Default as FromDefault, Env, Stored};
+
+        let defaults = resolve_effective_config(None, &ProviderConfig::default(), FromDefault);
+        assert_eq!(defaults.provider.source, FromDefault);
+        assert_eq!(defaults.model.value, ProviderConfig::default().model);
+        assert_eq!(defaults.api_key.value, None);
+
+        // What from_env builds when OPENAI_API_KEY is set
+        let from_env = ProviderConfig::openai("sk-env-0123456789".to_string(), "gpt-4o-mini");
+        let effective = resolve_effective_config(None, &from_env, Env);
+        assert_eq!(effective.provider.value, LLMProvider::OpenAI);
+        assert_eq!(effective.provider.source, Env);
+        assert_eq!(effective.api_key.source, Env);
+        assert_eq!(effective.api_url.value, from_env.api_url);
+        assert_eq!(effective.max_tokens.value, from_env.max_tokens);
+        assert_eq!(effective.max_tokens.source, Env);
+
+        // A stored provider and key win over the environment
+        let stored = StoredLlmConfig {
+            provider: LLMProvider::Anthropic,
+            model: "claude-3-haiku-20240307".to_string(),
+            api_key: Some("sk-ant-stored-abcdef".to_string()),
+            api_url: None,
+        };
+        let effective = resolve_effective_config(Some(&stored), &stored.provider_config(), Stored);
+        assert_eq!(effective.provider.value, LLMProvider::Anthropic);
+        assert_eq!(effective.provider.source, Stored);
+        assert_eq!(effective.model.source, Stored);
+        assert_eq!(effective.api_key.source, Stored);
+        assert_eq!(effective.api_url.source, FromDefault);
+        assert_eq!(effective.temperature.source, FromDefault);
+    }
+
+    #[test]
+    fn test_effective_config_matches_active_config() {
+        let state = LLMState::new();
+        state.activate(StoredLlmConfig {
+            provider: LLMProvider::Groq,
+            model: "llama-3.1-8b-instant".to_string(),
+            api_key: None,
+            api_url: Some("http://localhost:9000/v1".to_string()),
+        });
+        state.set_provider_key(LLMProvider::Groq, "gsk-saved-key-123456".to_string());
+
+        let active = state.active_config();
+        let stored = state.stored.lock().unwrap().clone();
+        let effective =
+            resolve_effective_config(stored.as_ref(), &active, state.api_key_source());
+        assert_eq!(effective.model.value, active.model);
+        assert_eq!(effective.api_url.value, active.api_url);
+        assert_eq!(effective.api_url.source, ConfigSource::Stored);
+        assert_eq!(effective.max_tokens.value, active.max_tokens);
+        assert_eq!(effective.temperature.value, active.temperature);
+        assert_eq!(effective.api_key.value.as_deref(), Some("gsk-...3456"));
+        assert_eq!(effective.api_key.source, ConfigSource::Stored);
+    }
+
+    #[test]
+    fn test_switching_keeps_each_providers_key() {
+        let db = test_db();
+        let conn = db.conn.lock().unwrap();
+        for (provider, model, key) in [
+            (LLMProvider::OpenAI, "gpt-4o", "sk-openai-key"),
+            (LLMProvider::Anthropic, "claude-3-haiku-20240307", "sk-ant-key"),
+        ] {
+            StoredLlmConfig {
+                provider,
+                model: model.to_string(),
+                api_key: Some(key.to_string()),
+                api_url: None,
+            }
+            .save_active(&conn)
+            .unwrap();
+        }
+
+        let openai = switch_provider(&conn, LLMProvider::OpenAI, "gpt-4o-mini".into()).unwrap();
+        assert_eq!(openai.api_key.as_deref(), Some("sk-openai-key"));
+        assert_eq!(openai.model, "gpt-4o-mini");
+
+        let anthropic =
+            switch_provider(&conn, LLMProvider::Anthropic, "claude-3-haiku-20240307".into())
+                .unwrap();
+        assert_eq!(anthropic.api_key.as_deref(), Some("sk-ant-key"));
+
+        // The switch is persisted, and the other provider's key is untouched
+        let active = storage::get_active_llm_provider(&conn).unwrap().unwrap();
+        assert_eq!(active.provider, LLMProvider::Anthropic);
+        let saved = storage::get_llm_provider(&conn, &LLMProvider::OpenAI).unwrap().unwrap();
+        assert_eq!(saved.model, "gpt-4o-mini");
+        assert_eq!(
+            storage::get_provider_key(&conn, &LLMProvider::OpenAI).unwrap().as_deref(),
+            Some("sk-openai-key")
+        );
+    }
+
+    #[test]
+    fn test_provider_keys_coexist_and_follow_active_provider() {
+        let db = test_db();
+        let conn = db.conn.lock().unwrap();
+        storage::save_provider_key(&conn, &LLMProvider::OpenAI, "sk-old-openai").unwrap();
+        storage::save_provider_key(&conn, &LLMProvider::OpenAI, "sk-openai-key").unwrap();
+        storage::save_provider_key(&conn, &LLMProvider::Anthropic, "sk-ant-key").unwrap();
+
+        let keys = storage::get_provider_keys(&conn).unwrap();
+        assert_eq!(keys.len(), 2);
+
+        let state = LLMState::new();
+        *state.keys.lock().unwrap() = keys;
+        for (provider, model, key) in [
+            (LLMProvider::Anthropic, "claude-3-haiku-20240307", "sk-ant-key"),
+            (LLMProvider::OpenAI, "gpt-4o", "sk-openai-key"),
+        ] {
+            state.activate(StoredLlmConfig {
+                provider: provider.clone(),
+                model: model.to_string(),
+                api_key: None,
+                api_url: None,
+            });
+            let (_, config) = state.client();
+            assert_eq!(config.provider, provider);
+            assert_eq!(config.api_key.as_deref(), Some(key));
+        }
+    }
+
+    #[test]
+    fn test_switching_to_unknown_model_rejected() {
+        let db = test_db();
+        let conn = db.conn.lock().unwrap();
+        let result = switch_provider(&conn, LLMProvider::OpenAI, "claude-3-haiku-20240307".into());
+        assert!(matches!(
+            result,
+            Err(AppError::Llm(crate::error::LlmError::UnknownModel(_)))
+        ));
+        assert!(storage::get_active_llm_provider(&conn).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_effective_config_redacts_api_key() {
+        let config = ProviderConfig::openai("sk-secret-value-9876".to_string(), "gpt-4o");
+        let effective = resolve_effective_config(None, &config, ConfigSource::Env);
+        assert_eq!(effective.api_key.value.as_deref(), Some("sk-s...9876"));
+
+        let json = serde_json::to_string(&effective).unwrap();
+        assert!(!json.contains("secret"));
+        assert_eq!(redact_api_key("short"), "****");
+    }
+    /// Describes each image it is sent by its MIME type and the prompt's page
+    struct VisionClient {
+        images: Mutex<Vec<ChatImage>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMClient for VisionClient {
+        async fn chat(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _config: &ProviderConfig,
+        ) -> Result<String, LLMError> {
+            Err(LLMError::ApiError("expected an image".to_string()))
+        }
+
+        async fn chat_with_image(
+            &self,
+            messages: Vec<ChatMessage>,
+            image: &ChatImage,
+            _config: &ProviderConfig,
+        ) -> Result<String, LLMError> {
+            assert_eq!(messages[0].content, prompts::IMAGE_DESCRIPTION_PROMPT);
+            self.images.lock().unwrap().push(image.clone());
+            let page = messages[1].content.trim_end_matches('.').rsplit(' ').next().unwrap();
+            Ok(format!(" A {} figure from page {}. \n", image.mime_type, page))
+        }
+    }
+
+    fn page_image(page: u32, index: u32, mime_type: &'static str) -> PageImage {
+        PageImage {
+            page,
+            index,
+            width: 64,
+            height: 64,
+            mime_type,
+            data: vec![page as u8, index as u8],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_images_are_described_and_stored_per_image() {
+        let db = test_db();
+        let client = VisionClient {
+            images: Mutex::new(Vec::new()),
+        };
+        let config = ProviderConfig {
+            model: "gpt-4o".to_string(),
+            ..Default::default()
+        };
+        assert!(model_supports_vision(&config).await);
+
+        let images = vec![
+            page_image(1, 0, "image/jpeg"),
+            page_image(3, 0, "image/png"),
+            page_image(3, 2, "image/jpeg"),
+        ];
+        let described = describe_page_images(&client, &config, &db, "doc", images)
+            .await
+            .unwrap();
+        assert_eq!(described.len(), 3);
+        let sent: Vec<Vec<u8>> =
+            client.images.lock().unwrap().iter().map(|i| i.data.clone()).collect();
+        assert_eq!(sent, [vec![1, 0], vec![3, 0], vec![3, 2]]);
+
+        let conn = db.conn.lock().unwrap();
+        let stored = storage::get_image_descriptions(&conn, "doc").unwrap();
+        assert_eq!(stored, described);
+        let keys: Vec<(u32, u32)> = stored.iter().map(|d| (d.page, d.image_index)).collect();
+        assert_eq!(keys, [(1, 0), (3, 0), (3, 2)]);
+        assert_eq!(stored[1].description, "A image/png figure from page 3.");
+        assert!(stored.iter().all(|d| d.model == "gpt-4o"));
+        assert!(storage::get_image_descriptions(&conn, "other").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_only_vision_models_describe_images() {
+        for (provider, model, vision) in [
+            (LLMProvider::OpenAI, "gpt-4o", true),
+            (LLMProvider::Anthropic, "claude-3-haiku-20240307", true),
+            (LLMProvider::Local, "mistral-7b-instruct", false),
+            (LLMProvider::OpenAI, "not-a-model", false),
+        ] {
+            let config = ProviderConfig {
+                provider,
+                model: model.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(model_supports_vision(&config).await, vision, "{}", model);
+        }
+    }
+}
@@ -7,4 +7,5 @@ pub mod document;
 pub mod annotation;
 pub mod llm;
 pub mod editor;
-pub mod voice;
\ No newline at end of file
+pub mod voice;
+pub mod settings;
@@ -0,0 +1,46 @@
+//! App settings Tauri commands
+
+use crate::error::AppError;
+use crate::settings::DEFAULT_WPM;
+use crate::storage::Database;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tauri::{AppHandle, Manager};
+
+/// Every known setting with its current value, defaults included
+#[tauri::command]
+pub async fn get_all_settings(app: AppHandle) -> Result<BTreeMap<String, Value>, AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_all_settings(&conn)
+}
+
+/// Save a setting. Unknown keys and values of the wrong type or range are rejected.
+#[tauri::command]
+pub async fn set_setting(app: AppHandle, key: String, value: Value) -> Result<(), AppError> {
+    {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::set_setting(&conn, &key, &value)?;
+    }
+    if key == DEFAULT_WPM {
+        crate::commands::voice::restore_voice_settings(&app).await?;
+    }
+    Ok(())
+}
+
+/// Return every setting to its default, returning the settings as they now are
+#[tauri::command]
+pub async fn reset_settings(app: AppHandle) -> Result<BTreeMap<String, Value>, AppError> {
+    let cleared = {
+        let db = app.state::<Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::reset_settings(&conn)?
+    };
+    tracing::info!("Reset {} saved settings", cleared);
+    crate::commands::voice::restore_voice_settings(&app).await?;
+
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_all_settings(&conn)
+}
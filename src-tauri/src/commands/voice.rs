@@ -6,13 +6,21 @@
 //! - Voice command processing
 //! - Reading position synchronization
 
+use crate::annotation::Bookmark;
+use crate::document::ReadingMode;
 use crate::error::AppError;
 use crate::voice::{
-    providers::{STTProvider, TTSProvider, VoiceInfo},
-    ReadingPosition, VoiceAction, VoiceCommand, VoiceConfig, VoiceError, VoiceManager,
-    VoiceResponse, VoiceState, WhisperModel, WordTiming,
+    audio,
+    audiobook::{self, ChapterAudio},
+    calibration,
+    providers::{create_tts_provider, STTProvider, TTSProvider, VoiceInfo},
+    self_test,
+    AudioData, CaptionBuilder, Pronunciation, ReadingPosition, TranscriptionResult, VoiceAction,
+    VoiceCommand, VoiceConfig, VoiceError, VoiceManager, VoiceResponse, VoiceSelfTestReport,
+    VoiceState, WhisperModel, WordTiming, WpmCalibration,
 };
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, Manager, State};
 use tokio::sync::{mpsc, Mutex, RwLock};
@@ -45,6 +53,30 @@ impl VoiceManagerState {
             reading_sessions: Arc::new(Mutex::new(HashMap::new())),
         }
     }
+
+    #[cfg(test)]
+    fn with_manager(manager: VoiceManager) -> Self {
+        Self {
+            manager: Arc::new(Mutex::new(manager)),
+            ..Self::new()
+        }
+    }
+
+    /// Read at `wpm` words per minute, as near as the voice allows; returns the WPM
+    /// applied
+    async fn set_wpm(&self, wpm: f32) -> f32 {
+        let mut config = self.config.write().await;
+        config.reading_speed = config.wpm_to_rate(wpm);
+        self.manager.lock().await.update_config(config.clone());
+        config.rate_to_wpm(config.reading_speed)
+    }
+
+    /// Stop all voice activity, drop pending sessions and return the manager to `Idle`
+    async fn reset(&self) {
+        self.manager.lock().await.reset().await;
+        self.transcription_sessions.lock().await.clear();
+        self.reading_sessions.lock().await.clear();
+    }
 }
 
 impl Default for VoiceManagerState {
@@ -53,6 +85,65 @@ impl Default for VoiceManagerState {
     }
 }
 
+/// Records a spoken reading for analytics as its word positions arrive. Storage failures
+/// are logged rather than interrupting playback.
+struct SpokenSession {
+    app: AppHandle,
+    id: Option<String>,
+    pages: HashSet<u32>,
+    words: u32,
+}
+
+impl SpokenSession {
+    fn start(app: &AppHandle, document_id: &str) -> Self {
+        let id = record_reading(app, |conn| {
+            crate::storage::start_reading_session(
+                conn,
+                document_id,
+                ReadingMode::Tts,
+                chrono::Utc::now(),
+            )
+        });
+        Self {
+            app: app.clone(),
+            id,
+            pages: HashSet::new(),
+            words: 0,
+        }
+    }
+
+    /// Count a spoken word
+    fn observe(&mut self, position: &ReadingPosition) {
+        self.words += 1;
+        if let Some(id) = &self.id {
+            if self.pages.insert(position.page) {
+                record_reading(&self.app, |conn| {
+                    crate::storage::record_reading_pages(conn, id, &[position.page])
+                });
+            }
+        }
+    }
+
+    fn finish(self) {
+        if let Some(id) = &self.id {
+            record_reading(&self.app, |conn| {
+                crate::storage::finish_reading_session(conn, id, chrono::Utc::now(), self.words)
+            });
+        }
+    }
+}
+
+fn record_reading<T>(
+    app: &AppHandle,
+    record: impl FnOnce(&rusqlite::Connection) -> Result<T, AppError>,
+) -> Option<T> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    record(&conn)
+        .map_err(|e| tracing::warn!("Failed to record reading session: {}", e))
+        .ok()
+}
+
 // ============================================================================
 // Configuration Commands
 // ============================================================================
@@ -67,14 +158,17 @@ pub async fn get_voice_config(state: State<'_, VoiceManagerState>) -> Result<Voi
 /// Update voice configuration
 #[tauri::command]
 pub async fn set_voice_config(
+    app: AppHandle,
     state: State<'_, VoiceManagerState>,
     config: VoiceConfig,
 ) -> Result<(), AppError> {
     // Update stored config
-    {
+    let voice_changed = {
         let mut stored_config = state.config.write().await;
+        let voice_changed = stored_config.tts_provider != config.tts_provider;
         *stored_config = config.clone();
-    }
+        voice_changed
+    };
 
     // Update manager
     {
@@ -82,18 +176,37 @@ pub async fn set_voice_config(
         manager.update_config(config);
     }
 
+    // A new voice speaks at its own pace, so time it in the background
+    if voice_changed {
+        tokio::spawn(async move {
+            let state = app.state::<VoiceManagerState>();
+            if let Err(e) = calibrate_voice(&app, &state).await {
+                tracing::warn!("Failed to calibrate reading speed for the new voice: {}", e);
+            }
+        });
+    }
+
     Ok(())
 }
 
 /// Initialize voice system with current configuration
 #[tauri::command]
-pub async fn initialize_voice(state: State<'_, VoiceManagerState>) -> Result<bool, AppError> {
+pub async fn initialize_voice(
+    app: AppHandle,
+    state: State<'_, VoiceManagerState>,
+) -> Result<bool, AppError> {
+    let pronunciations = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_pronunciations(&conn)?
+    };
     let mut manager = state.manager.lock().await;
 
     manager
         .initialize()
         .await
         .map_err(|e| AppError::Voice(e.to_string()))?;
+    manager.set_pronunciations(pronunciations);
 
     Ok(true)
 }
@@ -112,6 +225,13 @@ pub async fn get_voice_state(state: State<'_, VoiceManagerState>) -> Result<Voic
     Ok(manager.get_state().await)
 }
 
+/// Stop listening, reading and speaking at once and return to `Idle`
+#[tauri::command]
+pub async fn reset_voice(state: State<'_, VoiceManagerState>) -> Result<(), AppError> {
+    state.reset().await;
+    Ok(())
+}
+
 // ============================================================================
 // Speech-to-Text Commands
 // ============================================================================
@@ -125,8 +245,8 @@ pub async fn start_voice_listening(
 ) -> Result<(), AppError> {
     let mut manager = state.manager.lock().await;
 
-    let rx = manager
-        .start_listening()
+    let (rx, timed_out) = manager
+        .start_listening_with_timeout()
         .await
         .map_err(|e| AppError::Voice(e.to_string()))?;
 
@@ -160,6 +280,13 @@ pub async fn start_voice_listening(
         }
     });
 
+    // Listening stops by itself after a stretch without speech
+    tokio::spawn(async move {
+        if timed_out.await.is_ok() {
+            let _ = app.emit("voice:listening_timeout", &session_id);
+        }
+    });
+
     Ok(())
 }
 
@@ -217,7 +344,8 @@ pub async fn speak_text(state: State<'_, VoiceManagerState>, text: String) -> Re
     Ok(())
 }
 
-/// Start reading document content with cursor sync
+/// Start reading document content with cursor sync, emitting `voice:caption` cues as
+/// it is read. With `captions_path` the whole reading is also saved there as WebVTT.
 #[tauri::command]
 pub async fn start_reading(
     app: AppHandle,
@@ -225,6 +353,7 @@ pub async fn start_reading(
     document_id: String,
     content: String,
     start_position: ReadingPosition,
+    captions_path: Option<String>,
 ) -> Result<(), AppError> {
     let mut manager = state.manager.lock().await;
 
@@ -232,6 +361,7 @@ pub async fn start_reading(
         .read_content(&content, start_position)
         .await
         .map_err(|e| AppError::Voice(e.to_string()))?;
+    let word_timings = manager.word_timings().to_vec();
 
     // Store the receiver
     {
@@ -250,9 +380,27 @@ pub async fn start_reading(
         };
 
         if let Some(ref mut receiver) = rx {
+            let mut session = SpokenSession::start(&app, &doc_id_clone);
+            let mut captions = CaptionBuilder::new();
             while let Some(position) = receiver.recv().await {
+                session.observe(&position);
                 // Emit position update event
                 let _ = app.emit("voice:reading_position", &position);
+
+                let timing = word_timings.get(position.word_index as usize);
+                if let Some(cue) = timing.and_then(|timing| captions.push(timing)) {
+                    let _ = app.emit("voice:caption", &cue);
+                }
+            }
+            session.finish();
+            if let Some(cue) = captions.finish() {
+                let _ = app.emit("voice:caption", &cue);
+            }
+            if let Some(path) = &captions_path {
+                let vtt = crate::voice::captions::to_webvtt(captions.cues());
+                if let Err(e) = std::fs::write(path, vtt) {
+                    tracing::warn!("Failed to write captions to {}: {}", path, e);
+                }
             }
 
             // Emit reading complete event
@@ -263,6 +411,70 @@ pub async fn start_reading(
     Ok(())
 }
 
+/// Resolve a selection and start speaking its text, returning word position updates
+/// indexed within whichever paragraph of the selection is being spoken
+async fn speak_document_selection(
+    manager: &mut VoiceManager,
+    document: &crate::document::Document,
+    page: u32,
+    start_offset: usize,
+    end_offset: usize,
+) -> Result<mpsc::Receiver<ReadingPosition>, AppError> {
+    let selection = crate::document::resolve_selection(document, page, start_offset, end_offset)?;
+    let words = document
+        .pages
+        .iter()
+        .find(|p| p.number == page)
+        .map(|p| crate::document::selected_words(p, start_offset, end_offset))
+        .unwrap_or_default();
+    let start_position = ReadingPosition {
+        document_id: document.id.clone(),
+        page,
+        paragraph_id: selection.paragraph_id.unwrap_or_default(),
+        ..Default::default()
+    };
+
+    manager
+        .speak_selection(&selection.text, start_position, words)
+        .await
+        .map_err(|e| AppError::Voice(e.to_string()))
+}
+
+/// Read a selected range of a page aloud, emitting word positions as it is spoken
+#[tauri::command]
+pub async fn read_selection(
+    app: AppHandle,
+    state: State<'_, VoiceManagerState>,
+    document_id: String,
+    page: u32,
+    start_offset: usize,
+    end_offset: usize,
+) -> Result<(), AppError> {
+    let path = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::get_document_path(&conn, &document_id)?
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    let mut rx = {
+        let mut manager = state.manager.lock().await;
+        speak_document_selection(&mut manager, &document, page, start_offset, end_offset).await?
+    };
+
+    tokio::spawn(async move {
+        let mut session = SpokenSession::start(&app, &document_id);
+        while let Some(position) = rx.recv().await {
+            session.observe(&position);
+            let _ = app.emit("voice:reading_position", &position);
+        }
+        session.finish();
+        let _ = app.emit("voice:selection_complete", &document_id);
+    });
+
+    Ok(())
+}
+
 /// Stop reading
 #[tauri::command]
 pub async fn stop_reading(state: State<'_, VoiceManagerState>) -> Result<(), AppError> {
@@ -285,6 +497,22 @@ pub async fn get_reading_position(
     Ok(manager.get_reading_position().await)
 }
 
+/// Map a clicked transcript word back to a reading position in the document
+#[tauri::command]
+pub async fn position_from_word_timing(
+    state: State<'_, VoiceManagerState>,
+    document_id: String,
+    page: u32,
+    paragraph_id: String,
+    word_index: u32,
+) -> Result<ReadingPosition, AppError> {
+    let manager = state.manager.lock().await;
+
+    manager
+        .position_for_word(&document_id, page, &paragraph_id, word_index)
+        .map_err(|e| AppError::Voice(e.to_string()))
+}
+
 /// Set reading speed
 #[tauri::command]
 pub async fn set_reading_speed(
@@ -301,10 +529,258 @@ pub async fn set_reading_speed(
     Ok(())
 }
 
+/// Set reading speed as a target words-per-minute; returns the WPM actually applied
+#[tauri::command]
+pub async fn set_reading_wpm(
+    state: State<'_, VoiceManagerState>,
+    wpm: f32,
+) -> Result<f32, AppError> {
+    Ok(state.set_wpm(wpm).await)
+}
+
+/// Use the saved calibration of the voice and read at the default reading speed from
+/// the settings. Called at startup and when the default speed changes.
+pub async fn restore_voice_settings(app: &AppHandle) -> Result<(), AppError> {
+    let (calibration, wpm) = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        let calibration = crate::storage::get_wpm_calibration(&conn)?;
+        let wpm: f32 = crate::storage::get_setting(&conn, crate::settings::DEFAULT_WPM)?;
+        (calibration, wpm)
+    };
+
+    let state = app.state::<VoiceManagerState>();
+    if calibration.is_some() {
+        state.config.write().await.wpm_calibration = calibration;
+    }
+    let applied = state.set_wpm(wpm).await;
+    tracing::debug!("Reading at {:.0} wpm", applied);
+    Ok(())
+}
+
+/// Get reading speed in words per minute
+#[tauri::command]
+pub async fn get_reading_wpm(state: State<'_, VoiceManagerState>) -> Result<f32, AppError> {
+    let config = state.config.read().await;
+    Ok(config.rate_to_wpm(config.reading_speed))
+}
+
+/// Time the current voice reading a sample so WPM settings match how fast it really
+/// speaks. Runs again on its own whenever the voice changes.
+#[tauri::command]
+pub async fn calibrate_reading_speed(
+    app: AppHandle,
+    state: State<'_, VoiceManagerState>,
+) -> Result<WpmCalibration, AppError> {
+    let calibration = calibrate_voice(&app, &state).await?;
+    tracing::info!("Voice reads at {:.0} wpm at 1.0x", calibration.base_wpm);
+    Ok(calibration)
+}
+
+/// Measure the configured voice and keep the result in the config and the database,
+/// unless the voice changed while it was being measured
+async fn calibrate_voice(
+    app: &AppHandle,
+    state: &VoiceManagerState,
+) -> Result<WpmCalibration, AppError> {
+    let provider = state.config.read().await.tts_provider.clone();
+    let mut tts = create_tts_provider(&provider).await?;
+    let calibration = calibration::calibrate(tts.as_mut(), &provider).await?;
+
+    let mut config = state.config.write().await;
+    if config.tts_provider == provider {
+        config.wpm_calibration = Some(calibration.clone());
+        state.manager.lock().await.update_config(config.clone());
+
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::set_wpm_calibration(&conn, &calibration)?;
+    }
+    Ok(calibration)
+}
+
+// ============================================================================
+// Pronunciation Commands
+// ============================================================================
+
+/// Add or replace how a term is spoken, e.g. "SQL" as "sequel"
+#[tauri::command]
+pub async fn add_pronunciation(
+    app: AppHandle,
+    state: State<'_, VoiceManagerState>,
+    term: String,
+    replacement: String,
+) -> Result<(), AppError> {
+    let term = term.trim();
+    if term.is_empty() {
+        return Err(AppError::Voice("Pronunciation term cannot be empty".to_string()));
+    }
+
+    let pronunciations = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::save_pronunciation(
+            &conn,
+            &Pronunciation {
+                term: term.to_string(),
+                replacement: replacement.trim().to_string(),
+            },
+        )?;
+        crate::storage::get_pronunciations(&conn)?
+    };
+    state.manager.lock().await.set_pronunciations(pronunciations);
+
+    Ok(())
+}
+
+/// List the pronunciation lexicon
+#[tauri::command]
+pub async fn list_pronunciations(app: AppHandle) -> Result<Vec<Pronunciation>, AppError> {
+    let db = app.state::<crate::storage::Database>();
+    let conn = db.conn.lock().unwrap();
+    crate::storage::get_pronunciations(&conn)
+}
+
 // ============================================================================
 // Voice Provider Commands
 // ============================================================================
 
+/// Phrase spoken when a preview is requested without sample text
+const DEFAULT_PREVIEW_TEXT: &str = "This is how I will sound when reading your documents.";
+
+/// Longest sample, in characters, a preview will synthesize
+const MAX_PREVIEW_CHARS: usize = 300;
+
+/// Synthesize a short sample with `voice_id` on a throwaway provider, leaving `config` untouched
+async fn synthesize_voice_preview(
+    config: &VoiceConfig,
+    voice_id: &str,
+    sample_text: &str,
+) -> Result<AudioData, AppError> {
+    let sample = match sample_text.trim() {
+        "" => DEFAULT_PREVIEW_TEXT,
+        text => text,
+    };
+    let sample: String = sample.chars().take(MAX_PREVIEW_CHARS).collect();
+
+    let provider = config.tts_provider.with_voice(voice_id);
+    let mut tts = create_tts_provider(&provider).await.map_err(|e| match e {
+        VoiceError::ModelNotFound(_) => {
+            AppError::Voice(format!("Voice '{}' is not installed", voice_id))
+        }
+        e => AppError::Voice(format!("Voice '{}' is not available: {}", voice_id, e)),
+    })?;
+    tts.set_rate(config.reading_speed);
+
+    Ok(tts.synthesize(&sample).await?)
+}
+
+/// Play a short sample in the given voice without changing the saved voice settings
+#[tauri::command]
+pub async fn preview_voice(
+    state: State<'_, VoiceManagerState>,
+    voice_id: String,
+    sample_text: Option<String>,
+) -> Result<(), AppError> {
+    let config = state.config.read().await.clone();
+    let audio =
+        synthesize_voice_preview(&config, &voice_id, sample_text.as_deref().unwrap_or("")).await?;
+
+    tracing::info!("Previewing voice {} ({} samples)", voice_id, audio.samples.len());
+    audio::play_audio(&audio).await?;
+
+    Ok(())
+}
+
+/// Chapter files written by `export_narration_by_chapter`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NarrationExport {
+    pub chapters: Vec<ChapterAudio>,
+    /// Chapter index for joining the files into an m4b, when one was requested
+    pub chapter_index_path: Option<String>,
+}
+
+/// Narrate a document into `output_dir` as one audio file per chapter, named
+/// `chapter_{n}_{title}.{format}`. With `chapter_index` a `chapters.txt` listing each
+/// chapter's start time is written as well. Only WAV output is supported.
+#[tauri::command]
+pub async fn export_narration_by_chapter(
+    app: AppHandle,
+    state: State<'_, VoiceManagerState>,
+    document_id: String,
+    output_dir: String,
+    format: String,
+    chapter_index: Option<bool>,
+) -> Result<NarrationExport, AppError> {
+    if !format.eq_ignore_ascii_case("wav") {
+        return Err(AppError::Voice(format!(
+            "Narration can only be exported as WAV, not {}",
+            format
+        )));
+    }
+
+    let (path, pronunciations) = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        (
+            crate::storage::get_document_path(&conn, &document_id)?,
+            crate::storage::get_pronunciations(&conn)?,
+        )
+    };
+    let document = crate::document::parser::parse_document(&path).await?;
+
+    // A separate engine, so reading aloud can carry on while the export runs
+    let config = state.config.read().await.clone();
+    let mut tts = create_tts_provider(&config.tts_provider).await?;
+    tts.set_rate(config.reading_speed);
+
+    let output_dir = std::path::Path::new(&output_dir);
+    let chapters = audiobook::narrate_chapters(
+        tts.as_ref(),
+        &document,
+        &config.narration,
+        &pronunciations,
+        output_dir,
+    )
+    .await?;
+    tracing::info!("Exported {} narrated chapters to {}", chapters.len(), output_dir.display());
+
+    let chapter_index_path = match chapter_index {
+        Some(true) => {
+            let index_path = output_dir.join(audiobook::CHAPTER_INDEX_FILE);
+            std::fs::write(&index_path, audiobook::chapter_index(&chapters))?;
+            Some(index_path.to_string_lossy().into_owned())
+        }
+        _ => None,
+    };
+
+    Ok(NarrationExport {
+        chapters,
+        chapter_index_path,
+    })
+}
+
+/// Speak a known phrase with TTS and transcribe it back with STT, reporting how closely
+/// the transcription matched, how long each stage took and which stage failed
+#[tauri::command]
+pub async fn voice_self_test(
+    state: State<'_, VoiceManagerState>,
+    phrase: Option<String>,
+) -> Result<VoiceSelfTestReport, AppError> {
+    let manager = state.manager.lock().await;
+    let phrase = phrase.unwrap_or_else(|| self_test::DEFAULT_SELF_TEST_PHRASE.to_string());
+
+    let report = manager.self_test(&phrase).await?;
+    tracing::info!(
+        "Voice self-test {}: similarity {:.2}, TTS {} ms, STT {:?} ms",
+        if report.passed { "passed" } else { "failed" },
+        report.similarity,
+        report.tts_ms,
+        report.stt_ms
+    );
+    Ok(report)
+}
+
 /// Get available TTS voices
 #[tauri::command]
 pub async fn get_available_voices(
@@ -395,27 +871,95 @@ pub async fn download_voice_model(
 // Voice Command Processing
 // ============================================================================
 
+/// Turn dictated note content into an annotation, or ask to repeat if the STT was unsure
+fn note_down_response(
+    content: String,
+    position: ReadingPosition,
+    transcription: Option<&TranscriptionResult>,
+    threshold: f32,
+) -> VoiceResponse {
+    if let Some(t) = transcription.filter(|t| !t.is_confident(threshold)) {
+        tracing::info!("Low-confidence note ({:.2}), asking to repeat", t.confidence);
+        return VoiceResponse {
+            text: "Sorry, I didn't catch that clearly. Please repeat your note.".to_string(),
+            should_speak: true,
+            action: Some(VoiceAction::RequestRepeat {
+                heard: content,
+                unclear_words: t.low_confidence_words(threshold),
+            }),
+        };
+    }
+
+    VoiceResponse {
+        text: format!("Added note: {}", content),
+        should_speak: true,
+        action: Some(VoiceAction::AddAnnotation {
+            position,
+            content,
+            color: Some("yellow".to_string()),
+        }),
+    }
+}
+
+/// Save a bookmark at the position being read, labelled if the user gave a label
+fn bookmark_response(
+    app: &AppHandle,
+    label: Option<String>,
+    position: Option<ReadingPosition>,
+) -> Result<VoiceResponse, AppError> {
+    let Some(position) = position.filter(|p| !p.document_id.is_empty()) else {
+        return Ok(VoiceResponse {
+            text: "Open a document to bookmark your place".to_string(),
+            should_speak: true,
+            action: None,
+        });
+    };
+
+    let bookmark = Bookmark::new(
+        position.document_id,
+        position.page,
+        position.paragraph_id,
+        position.word_index,
+        label,
+    );
+    let bookmark = {
+        let db = app.state::<crate::storage::Database>();
+        let conn = db.conn.lock().unwrap();
+        crate::storage::save_bookmark(&conn, &bookmark)?
+    };
+
+    Ok(VoiceResponse {
+        text: match &bookmark.label {
+            Some(label) => format!("Bookmarked page {} as {}", bookmark.page_number, label),
+            None => format!("Bookmarked page {}", bookmark.page_number),
+        },
+        should_speak: true,
+        action: Some(VoiceAction::BookmarkCreated { bookmark }),
+    })
+}
+
 /// Process a voice command and return the action to take
 #[tauri::command]
 pub async fn process_voice_command(
+    app: AppHandle,
     state: State<'_, VoiceManagerState>,
     command: VoiceCommand,
     current_position: Option<ReadingPosition>,
+    transcription: Option<TranscriptionResult>,
 ) -> Result<VoiceResponse, AppError> {
     match command {
         VoiceCommand::NoteDown { content } => {
-            let position = current_position.unwrap_or_default();
-            Ok(VoiceResponse {
-                text: format!("Added note: {}", content),
-                should_speak: true,
-                action: Some(VoiceAction::AddAnnotation {
-                    position,
-                    content,
-                    color: Some("yellow".to_string()),
-                }),
-            })
+            let threshold = state.config.read().await.note_confidence_threshold;
+            Ok(note_down_response(
+                content,
+                current_position.unwrap_or_default(),
+                transcription.as_ref(),
+                threshold,
+            ))
         }
 
+        VoiceCommand::Bookmark { label } => bookmark_response(&app, label, current_position),
+
         VoiceCommand::Highlight { color } => {
             let position = current_position.unwrap_or_default();
             Ok(VoiceResponse {
@@ -551,3 +1095,402 @@ pub async fn get_word_timings(
         config.reading_speed,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    fn transcription(confidence: f32, words: &[(&str, f32)]) -> TranscriptionResult {
+        TranscriptionResult {
+            text: words.iter().map(|(w, _)| *w).collect::<Vec<_>>().join(" "),
+            is_final: true,
+            confidence,
+            timestamp_ms: 0,
+            words: words
+                .iter()
+                .map(|(word, confidence)| WordTiming {
+                    word: word.to_string(),
+                    start_ms: 0,
+                    end_ms: 0,
+                    confidence: *confidence,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_low_confidence_note_is_flagged() {
+        let t = transcription(0.9, &[("check", 0.4), ("the", 0.5), ("lemma", 0.3)]);
+        let response = note_down_response(
+            "check the lemma".to_string(),
+            ReadingPosition::default(),
+            Some(&t),
+            0.6,
+        );
+
+        match response.action {
+            Some(VoiceAction::RequestRepeat { heard, unclear_words }) => {
+                assert_eq!(heard, "check the lemma");
+                assert_eq!(unclear_words, ["check", "the", "lemma"]);
+            }
+            other => panic!("Expected RequestRepeat, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_confident_note_is_added() {
+        let t = transcription(0.95, &[("check", 0.9), ("the", 0.97), ("lemma", 0.55)]);
+        let response = note_down_response(
+            "check the lemma".to_string(),
+            ReadingPosition::default(),
+            Some(&t),
+            0.6,
+        );
+        assert!(matches!(response.action, Some(VoiceAction::AddAnnotation { .. })));
+
+        // Without STT metadata (typed command) the note is kept
+        let response =
+            note_down_response("typed".to_string(), ReadingPosition::default(), None, 0.6);
+        assert!(matches!(response.action, Some(VoiceAction::AddAnnotation { .. })));
+    }
+
+    /// Idle timeout notifier handed to the mock STT, fired by tests to simulate silence
+    type IdleNotifier = Arc<std::sync::Mutex<Option<oneshot::Sender<()>>>>;
+
+    /// Providers that do nothing except count how often they were stopped and
+    /// record the text sent for synthesis
+    #[derive(Default)]
+    struct MockProviders {
+        stt_stops: Arc<std::sync::atomic::AtomicUsize>,
+        stt_idle: IdleNotifier,
+        tts_stops: Arc<std::sync::atomic::AtomicUsize>,
+        tts_text: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    struct MockStt(Arc<std::sync::atomic::AtomicUsize>, IdleNotifier);
+    struct MockTts(Arc<std::sync::atomic::AtomicUsize>, Arc<std::sync::Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl crate::voice::SpeechToText for MockStt {
+        async fn start_listening(
+            &mut self,
+        ) -> Result<mpsc::Receiver<TranscriptionResult>, VoiceError> {
+            Ok(mpsc::channel(1).1)
+        }
+
+        async fn stop_listening(&mut self) -> Result<(), VoiceError> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn transcribe(
+            &self,
+            _audio: &[f32],
+            _sample_rate: u32,
+        ) -> Result<TranscriptionResult, VoiceError> {
+            Err(VoiceError::NotInitialized)
+        }
+
+        fn is_listening(&self) -> bool {
+            false
+        }
+
+        fn supported_languages(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        fn set_idle_timeout(&mut self, _timeout: Duration, on_timeout: oneshot::Sender<()>) {
+            *self.1.lock().unwrap() = Some(on_timeout);
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::voice::TextToSpeech for MockTts {
+        async fn synthesize(&self, _text: &str) -> Result<crate::voice::AudioData, VoiceError> {
+            Err(VoiceError::NotInitialized)
+        }
+
+        async fn synthesize_stream(
+            &self,
+            text: &str,
+        ) -> Result<mpsc::Receiver<crate::voice::AudioChunk>, VoiceError> {
+            self.1.lock().unwrap().push(text.to_string());
+            Ok(mpsc::channel(1).1)
+        }
+
+        async fn get_word_timings(&self, text: &str) -> Result<Vec<WordTiming>, VoiceError> {
+            // Far enough apart that reading is still in progress when reset runs
+            Ok(text
+                .split_whitespace()
+                .enumerate()
+                .map(|(i, word)| WordTiming {
+                    word: word.to_string(),
+                    start_ms: 60_000 * i as u64,
+                    end_ms: 60_000 * (i as u64 + 1),
+                    confidence: 1.0,
+                })
+                .collect())
+        }
+
+        async fn stop(&mut self) -> Result<(), VoiceError> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn available_voices(&self) -> Vec<VoiceInfo> {
+            Vec::new()
+        }
+
+        fn set_rate(&mut self, _rate: f32) {}
+
+        fn set_voice(&mut self, _voice_id: &str) -> Result<(), VoiceError> {
+            Ok(())
+        }
+    }
+
+    impl MockProviders {
+        fn state(&self) -> VoiceManagerState {
+            VoiceManagerState::with_manager(VoiceManager::with_providers(
+                VoiceConfig::default(),
+                Box::new(MockStt(self.stt_stops.clone(), self.stt_idle.clone())),
+                Box::new(MockTts(self.tts_stops.clone(), self.tts_text.clone())),
+            ))
+        }
+    }
+
+    /// Put the manager into `target` and leave a pending session in each map
+    async fn drive_into(state: &VoiceManagerState, target: VoiceState) {
+        let mut manager = state.manager.lock().await;
+        match target {
+            VoiceState::Listening => {
+                let rx = manager.start_listening().await.unwrap();
+                state.transcription_sessions.lock().await.insert("s1".to_string(), rx);
+            }
+            VoiceState::Reading => {
+                let position = ReadingPosition {
+                    document_id: "doc".to_string(),
+                    ..Default::default()
+                };
+                let rx = manager.read_content("one two three", position).await.unwrap();
+                state.reading_sessions.lock().await.insert("doc".to_string(), rx);
+            }
+            // No provider-free way to hold these states, so set them directly
+            VoiceState::Processing | VoiceState::Speaking => manager.force_state(target).await,
+            VoiceState::Idle => unreachable!(),
+        }
+
+        state
+            .transcription_sessions
+            .lock()
+            .await
+            .entry("pending".to_string())
+            .or_insert_with(|| mpsc::channel(1).1);
+        state
+            .reading_sessions
+            .lock()
+            .await
+            .entry("pending".to_string())
+            .or_insert_with(|| mpsc::channel(1).1);
+
+        assert_eq!(manager.get_state().await, target);
+    }
+
+    #[tokio::test]
+    async fn test_reset_returns_every_active_state_to_idle() {
+        for target in [
+            VoiceState::Listening,
+            VoiceState::Processing,
+            VoiceState::Speaking,
+            VoiceState::Reading,
+        ] {
+            let providers = MockProviders::default();
+            let state = providers.state();
+            drive_into(&state, target).await;
+
+            state.reset().await;
+
+            let manager = state.manager.lock().await;
+            assert_eq!(manager.get_state().await, VoiceState::Idle, "from {:?}", target);
+            assert!(manager.get_reading_position().await.is_none());
+            assert!(state.transcription_sessions.lock().await.is_empty());
+            assert!(state.reading_sessions.lock().await.is_empty());
+            assert_eq!(providers.stt_stops.load(std::sync::atomic::Ordering::SeqCst), 1);
+            assert_eq!(providers.tts_stops.load(std::sync::atomic::Ordering::SeqCst), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listening_returns_to_idle_after_timeout() {
+        let providers = MockProviders::default();
+        let state = providers.state();
+        let mut manager = state.manager.lock().await;
+
+        let (_rx, timed_out) = manager.start_listening_with_timeout().await.unwrap();
+        assert_eq!(manager.get_state().await, VoiceState::Listening);
+
+        // The provider heard nothing for the whole timeout
+        let on_timeout = providers.stt_idle.lock().unwrap().take().unwrap();
+        on_timeout.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), timed_out)
+            .await
+            .expect("timeout notice should arrive")
+            .unwrap();
+        assert_eq!(manager.get_state().await, VoiceState::Idle);
+
+        // With the timeout disabled, listening continues until stopped
+        let config = VoiceConfig {
+            listening_timeout_secs: 0,
+            ..Default::default()
+        };
+        let mut manager = VoiceManager::with_providers(
+            config,
+            Box::new(MockStt(providers.stt_stops.clone(), providers.stt_idle.clone())),
+            Box::new(MockTts(providers.tts_stops.clone(), providers.tts_text.clone())),
+        );
+        let (_rx, timed_out) = manager.start_listening_with_timeout().await.unwrap();
+        assert!(providers.stt_idle.lock().unwrap().is_none());
+        assert!(timed_out.await.is_err());
+        assert_eq!(manager.get_state().await, VoiceState::Listening);
+    }
+
+    #[tokio::test]
+    async fn test_reset_without_providers() {
+        let state = VoiceManagerState::new();
+        state.reset().await;
+        assert_eq!(state.manager.lock().await.get_state().await, VoiceState::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_set_wpm_uses_the_voice_pace() {
+        let state = VoiceManagerState::new();
+        let base_wpm = state.config.read().await.base_wpm();
+
+        let applied = state.set_wpm(base_wpm * 1.5).await;
+        assert!((applied - base_wpm * 1.5).abs() < 0.01);
+        assert!((state.config.read().await.reading_speed - 1.5).abs() < 0.001);
+        // Beyond the fastest rate the voice manages
+        assert!((state.set_wpm(base_wpm * 10.0).await - base_wpm * 3.0).abs() < 0.01);
+    }
+
+    #[tokio::test]
+    async fn test_preview_known_piper_voice() {
+        let config = VoiceConfig::default();
+        let TTSProvider::PiperLocal { model_path } =
+            config.tts_provider.with_voice("en_US-lessac-medium")
+        else {
+            unreachable!()
+        };
+        if !std::path::Path::new(&model_path).exists() {
+            println!("Piper voice not installed at {}, skipping", model_path);
+            return;
+        }
+
+        let audio = synthesize_voice_preview(&config, "en_US-lessac-medium", "Hello there.")
+            .await
+            .unwrap();
+        assert!(!audio.samples.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_preview_unknown_voice_errors() {
+        let config = VoiceConfig::default();
+        let err = synthesize_voice_preview(&config, "xx_XX-nobody-medium", "")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("'xx_XX-nobody-medium' is not installed"));
+    }
+
+    #[tokio::test]
+    async fn test_pronunciations_applied_to_text_sent_to_tts() {
+        let providers = MockProviders::default();
+        let state = providers.state();
+        let mut manager = state.manager.lock().await;
+        manager.set_pronunciations(vec![Pronunciation {
+            term: "SQL".to_string(),
+            replacement: "sequel".to_string(),
+        }]);
+
+        let start = ReadingPosition {
+            document_id: "doc".to_string(),
+            ..Default::default()
+        };
+        manager.read_content("Query sql, not MySQL.", start).await.unwrap();
+
+        assert_eq!(*providers.tts_text.lock().unwrap(), ["Query sequel, not MySQL."]);
+    }
+
+    #[tokio::test]
+    async fn test_selection_text_is_synthesized_while_speaking() {
+        let document = crate::document::test_document(&[&[
+            "Results were mixed.",
+            "The second sentence is read back.",
+        ]]);
+
+        let providers = MockProviders::default();
+        let state = providers.state();
+        let mut manager = state.manager.lock().await;
+        let mut rx = speak_document_selection(&mut manager, &document, 1, 25, 40)
+            .await
+            .unwrap();
+
+        assert_eq!(*providers.tts_text.lock().unwrap(), ["second sentence"]);
+        assert_eq!(manager.get_state().await, VoiceState::Speaking);
+        let first = rx.recv().await.unwrap();
+        assert_eq!((first.page, first.paragraph_id.as_str(), first.word_index), (1, "p2", 1));
+
+        // Words after a paragraph break are indexed within the next paragraph
+        let mut rx = speak_document_selection(&mut manager, &document, 1, 13, 31)
+            .await
+            .unwrap();
+        let first = rx.recv().await.unwrap();
+        assert_eq!((first.paragraph_id.as_str(), first.word_index), ("p1", 2));
+        let second = manager.position_for_word(&document.id, 1, "p2", 1).unwrap();
+        assert_eq!((second.word_index, second.character_offset), (1, 4));
+        assert_eq!(second.timestamp_ms, 120_000);
+        assert!(manager.position_for_word(&document.id, 1, "p2", 2).is_err());
+
+        assert!(speak_document_selection(&mut manager, &document, 1, 25, 25)
+            .await
+            .is_err());
+        assert!(speak_document_selection(&mut manager, &document, 2, 0, 1)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clicked_word_maps_back_to_reading_position() {
+        let providers = MockProviders::default();
+        let state = providers.state();
+        let mut manager = state.manager.lock().await;
+
+        let start = ReadingPosition {
+            document_id: "doc".to_string(),
+            page: 3,
+            paragraph_id: "p-abc".to_string(),
+            ..Default::default()
+        };
+        let mut rx = manager.read_content("alpha beta gamma", start).await.unwrap();
+
+        for word_index in 0..3 {
+            let position = manager.position_for_word("doc", 3, "p-abc", word_index).unwrap();
+            assert_eq!(position.word_index, word_index);
+            assert_eq!(position.timestamp_ms, 60_000 * word_index as u64);
+            assert_eq!((position.page, position.paragraph_id.as_str()), (3, "p-abc"));
+        }
+
+        // Matches what reading emits for the same word
+        let emitted = rx.recv().await.unwrap();
+        let mapped = manager.position_for_word("doc", 3, "p-abc", emitted.word_index).unwrap();
+        assert_eq!(mapped, emitted);
+
+        assert_eq!(manager.position_for_word("doc", 3, "p-abc", 2).unwrap().character_offset, 11);
+        assert!(manager.position_for_word("doc", 3, "p-abc", 3).is_err());
+        assert!(manager.position_for_word("doc", 3, "p-other", 0).is_err());
+        assert!(manager.position_for_word("doc", 4, "p-abc", 0).is_err());
+        assert!(manager.position_for_word("other", 3, "p-abc", 0).is_err());
+        manager.reset().await;
+        assert!(manager.position_for_word("doc", 3, "p-abc", 0).is_err());
+    }
+}
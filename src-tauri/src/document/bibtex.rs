@@ -0,0 +1,322 @@
+//! BibTeX files referenced by LaTeX documents
+//!
+//! Entries are checked before they are written so a malformed entry can't break
+//! the bibliography for every other citation in the document.
+
+use super::editor::EditorError;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Entry types that define macros or comments rather than citable works
+const NON_CITABLE_TYPES: &[&str] = &["comment", "preamble", "string"];
+
+fn bibliography_command() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\\(bibliography|addbibresource)\s*(?:\[[^\]]*\])?\s*\{([^}]*)\}").unwrap()
+    })
+}
+
+fn entry_start() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"@\s*([A-Za-z]+)\s*[{(]\s*([^,\s{}()]+)\s*,").unwrap())
+}
+
+/// `.bib` files named by `\bibliography{}` or `\addbibresource{}`, in order
+pub fn bibliography_files(source: &str) -> Vec<String> {
+    let uncommented: String = source
+        .lines()
+        .map(strip_comment)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    bibliography_command()
+        .captures_iter(&uncommented)
+        .flat_map(|caps| {
+            let is_biblatex = &caps[1] == "addbibresource";
+            caps[2]
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(|name| {
+                    if is_biblatex || name.ends_with(".bib") {
+                        name.to_string()
+                    } else {
+                        format!("{}.bib", name)
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Text of a line before an unescaped `%`
+fn strip_comment(line: &str) -> &str {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '%' if !escaped => return &line[..i],
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    line
+}
+
+/// Keys of the citable entries in a `.bib` file
+pub fn entry_keys(bib: &str) -> Vec<String> {
+    entry_start()
+        .captures_iter(bib)
+        .filter(|caps| !NON_CITABLE_TYPES.contains(&caps[1].to_lowercase().as_str()))
+        .map(|caps| caps[2].to_string())
+        .collect()
+}
+
+/// Check the syntax of a single BibTeX entry and return its cite key
+pub fn parse_entry(entry: &str) -> Result<String, EditorError> {
+    check_entry(entry.trim())
+        .map_err(|reason| EditorError::ParseError(format!("Invalid BibTeX entry: {}", reason)))
+}
+
+fn check_entry(entry: &str) -> Result<String, String> {
+    let rest = entry.strip_prefix('@').ok_or("it must start with @")?;
+    let type_len = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    let entry_type = rest[..type_len].to_lowercase();
+    if entry_type.is_empty() {
+        return Err("the entry type is missing".to_string());
+    }
+    if NON_CITABLE_TYPES.contains(&entry_type.as_str()) {
+        return Err(format!("@{} entries can't be cited", entry_type));
+    }
+
+    let rest = rest[type_len..].trim_start();
+    let close = match rest.chars().next() {
+        Some('{') => '}',
+        Some('(') => ')',
+        _ => return Err("expected { after the entry type".to_string()),
+    };
+    let body = rest[1..]
+        .strip_suffix(close)
+        .ok_or(format!("the entry must end with {}", close))?;
+
+    let (key, fields) = body.split_once(',').unwrap_or((body, ""));
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("the cite key is missing".to_string());
+    }
+    if let Some(c) = key
+        .chars()
+        .find(|c| c.is_whitespace() || "{}()\",#%'=".contains(*c))
+    {
+        return Err(format!("the cite key can't contain '{}'", c));
+    }
+
+    check_fields(fields)?;
+    Ok(key.to_string())
+}
+
+/// Check `name = value` pairs separated by commas, where values are braced, quoted,
+/// numbers or macro names, optionally joined with `#`
+fn check_fields(fields: &str) -> Result<(), String> {
+    let mut chars = fields.chars().peekable();
+    let skip_whitespace = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    };
+
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.peek().is_none() {
+            return Ok(());
+        }
+
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || "_-:.".contains(*c)) {
+            name.push(c);
+        }
+        if name.is_empty() {
+            return Err("expected a field name".to_string());
+        }
+        skip_whitespace(&mut chars);
+        if chars.next() != Some('=') {
+            return Err(format!("expected = after field {}", name));
+        }
+
+        loop {
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some('{') => skip_braced(&mut chars, '}')
+                    .ok_or(format!("unbalanced braces in field {}", name))?,
+                Some('"') => skip_braced(&mut chars, '"')
+                    .ok_or(format!("unterminated quote in field {}", name))?,
+                Some(c) if c.is_alphanumeric() => {
+                    while chars.next_if(|c| c.is_alphanumeric() || "_-:.".contains(*c)).is_some() {}
+                }
+                _ => return Err(format!("field {} has no value", name)),
+            }
+            skip_whitespace(&mut chars);
+            if chars.next_if_eq(&'#').is_none() {
+                break;
+            }
+        }
+
+        match chars.next() {
+            Some(',') | None => {}
+            Some(c) => return Err(format!("unexpected '{}' after field {}", c, name)),
+        }
+    }
+}
+
+/// Consume a value up to `end` at brace depth zero
+fn skip_braced(chars: &mut impl Iterator<Item = char>, end: char) -> Option<()> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for c in chars {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == end && depth == 0 => return Some(()),
+            '{' => depth += 1,
+            '}' if depth == 0 => return None,
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Contents of a `.bib` file, empty if it doesn't exist yet
+fn read_bib(bib_path: &Path) -> Result<String, EditorError> {
+    match std::fs::read_to_string(bib_path) {
+        Ok(existing) => Ok(existing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(EditorError::IoError(e.to_string())),
+    }
+}
+
+/// Append an entry to the first of a document's `.bib` files, creating it if it doesn't
+/// exist yet. Keys already defined in any of the files are rejected, since the document
+/// sees them all. Returns the entry's cite key.
+pub fn append_entry(bib_paths: &[PathBuf], entry: &str) -> Result<String, EditorError> {
+    let key = parse_entry(entry)?;
+    let bib_path = bib_paths.first().ok_or_else(|| {
+        EditorError::UnsupportedOperation("No bibliography file to add to".to_string())
+    })?;
+
+    let mut existing = String::new();
+    for path in bib_paths {
+        let contents = read_bib(path)?;
+        // BibTeX compares keys case-insensitively
+        if entry_keys(&contents)
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(&key))
+        {
+            return Err(EditorError::DuplicateCiteKey(key));
+        }
+        if path == bib_path {
+            existing = contents;
+        }
+    }
+
+    let separator = if existing.trim_end().is_empty() {
+        ""
+    } else if existing.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    let updated = format!("{}{}{}\n", existing, separator, entry.trim());
+    std::fs::write(bib_path, updated).map_err(|e| EditorError::IoError(e.to_string()))?;
+
+    tracing::info!("Added BibTeX entry {} to {}", key, bib_path.display());
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY: &str = "@article{vaswani2017,\n  \
+                         title = {Attention Is {All} You Need},\n  \
+                         author = \"Vaswani, Ashish and Shazeer, Noam\",\n  \
+                         year = 2017,\n  \
+                         journal = nips # { 30},\n\
+                         }";
+
+    #[test]
+    fn test_bibliography_files_found_in_source() {
+        let source = "\\documentclass{article}\n\
+                      % \\bibliography{old}\n\
+                      \\addbibresource[label=main]{refs/main.bib}\n\
+                      \\bibliography{extra, more.bib}\n";
+        assert_eq!(
+            bibliography_files(source),
+            ["refs/main.bib", "extra.bib", "more.bib"]
+        );
+    }
+
+    #[test]
+    fn test_entry_syntax_validated() {
+        assert_eq!(parse_entry(ENTRY).unwrap(), "vaswani2017");
+        assert_eq!(parse_entry("@misc(note, howpublished = {Online})").unwrap(), "note");
+
+        for bad in [
+            "article{key, title = {X}}",
+            "@article{key, title = {Unclosed}",
+            "@article{key, title = {X}}}",
+            "@article{, title = {X}}",
+            "@article{two words, title = {X}}",
+            "@article{key, title {X}}",
+            "@article{key, title = }",
+            "@article{key, title = \"X}",
+            "@string{nips = {NeurIPS}}",
+        ] {
+            assert!(
+                matches!(parse_entry(bad), Err(EditorError::ParseError(_))),
+                "accepted {}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_entry_appended_and_duplicate_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let bib = dir.path().join("refs.bib");
+        let existing = "@string{nips = {NeurIPS}}\n@book{knuth1984, title = {TeX}}";
+        std::fs::write(&bib, existing).unwrap();
+        let bibs = [bib.clone()];
+
+        assert_eq!(append_entry(&bibs, ENTRY).unwrap(), "vaswani2017");
+        let written = std::fs::read_to_string(&bib).unwrap();
+        assert!(written.starts_with(&format!("{}\n\n", existing)));
+        assert!(written.ends_with(&format!("{}\n", ENTRY)));
+        assert_eq!(entry_keys(&written), ["knuth1984", "vaswani2017"]);
+
+        let duplicate = "@misc{Knuth1984, title = {Other}}";
+        assert!(matches!(
+            append_entry(&bibs, duplicate),
+            Err(EditorError::DuplicateCiteKey(key)) if key == "Knuth1984"
+        ));
+        assert_eq!(std::fs::read_to_string(&bib).unwrap(), written);
+    }
+
+    #[test]
+    fn test_key_defined_in_another_bibliography_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.bib");
+        let extra = dir.path().join("extra.bib");
+        std::fs::write(&extra, "@book{knuth1984, title = {TeX}}\n").unwrap();
+        let bibs = [main.clone(), extra];
+
+        assert!(matches!(
+            append_entry(&bibs, "@misc{knuth1984, title = {Other}}"),
+            Err(EditorError::DuplicateCiteKey(_))
+        ));
+        assert!(!main.exists());
+        assert_eq!(append_entry(&bibs, ENTRY).unwrap(), "vaswani2017");
+        assert_eq!(entry_keys(&std::fs::read_to_string(&main).unwrap()), ["vaswani2017"]);
+    }
+}
@@ -0,0 +1,157 @@
+//! Paragraph-level differences between two versions of a document
+
+use super::parser::normalize_text;
+use super::Document;
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+/// How a paragraph differs between the two versions
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A paragraph that was added, removed or edited in the newer version
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ParagraphChange {
+    pub kind: ChangeKind,
+    /// Page of the paragraph in the older version; `None` for added paragraphs
+    pub old_page: Option<u32>,
+    /// Page of the paragraph in the newer version; `None` for removed paragraphs
+    pub new_page: Option<u32>,
+    pub old_text: Option<String>,
+    pub new_text: Option<String>,
+}
+
+struct Block<'a> {
+    page: u32,
+    text: &'a str,
+}
+
+fn blocks(doc: &Document) -> Vec<Block<'_>> {
+    doc.pages
+        .iter()
+        .flat_map(|page| {
+            page.paragraphs.iter().map(|paragraph| Block {
+                page: page.number,
+                text: &paragraph.text,
+            })
+        })
+        .collect()
+}
+
+/// Text compared between versions: ligatures expanded and whitespace collapsed, so
+/// re-extraction noise is not reported as an edit
+fn comparison_key(text: &str) -> String {
+    normalize_text(text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Paragraphs added, removed or changed from `old` to `new`, in document order.
+/// Unchanged paragraphs are left out. Within a replaced run, paragraphs are paired
+/// in order as changes and any surplus is reported as added or removed.
+pub fn diff_documents(old: &Document, new: &Document) -> Vec<ParagraphChange> {
+    let old_blocks = blocks(old);
+    let new_blocks = blocks(new);
+    let old_keys: Vec<String> = old_blocks.iter().map(|b| comparison_key(b.text)).collect();
+    let new_keys: Vec<String> = new_blocks.iter().map(|b| comparison_key(b.text)).collect();
+
+    let removed = |b: &Block| ParagraphChange {
+        kind: ChangeKind::Removed,
+        old_page: Some(b.page),
+        new_page: None,
+        old_text: Some(b.text.to_string()),
+        new_text: None,
+    };
+    let added = |b: &Block| ParagraphChange {
+        kind: ChangeKind::Added,
+        old_page: None,
+        new_page: Some(b.page),
+        old_text: None,
+        new_text: Some(b.text.to_string()),
+    };
+
+    let mut changes = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys) {
+        match op {
+            DiffOp::Equal { .. } => {}
+            DiffOp::Delete { old_index, old_len, .. } => {
+                changes.extend(old_blocks[old_index..old_index + old_len].iter().map(removed));
+            }
+            DiffOp::Insert { new_index, new_len, .. } => {
+                changes.extend(new_blocks[new_index..new_index + new_len].iter().map(added));
+            }
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => {
+                let old_run = &old_blocks[old_index..old_index + old_len];
+                let new_run = &new_blocks[new_index..new_index + new_len];
+                let paired = old_len.min(new_len);
+                changes.extend(old_run.iter().zip(new_run).map(|(o, n)| ParagraphChange {
+                    kind: ChangeKind::Changed,
+                    old_page: Some(o.page),
+                    new_page: Some(n.page),
+                    old_text: Some(o.text.to_string()),
+                    new_text: Some(n.text.to_string()),
+                }));
+                changes.extend(old_run[paired..].iter().map(removed));
+                changes.extend(new_run[paired..].iter().map(added));
+            }
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    #[test]
+    fn test_changed_paragraph_reported_and_unchanged_left_out() {
+        let old = test_document(&[
+            &["Abstract.", "We train on 10k examples.", "Results follow."],
+            &["Related work."],
+        ]);
+        let new = test_document(&[
+            &["Abstract.", "We train on 50k examples.", "Results follow."],
+            &["Related work.", "Limitations are discussed."],
+        ]);
+
+        let changes = diff_documents(&old, &new);
+        assert_eq!(
+            changes,
+            [
+                ParagraphChange {
+                    kind: ChangeKind::Changed,
+                    old_page: Some(1),
+                    new_page: Some(1),
+                    old_text: Some("We train on 10k examples.".to_string()),
+                    new_text: Some("We train on 50k examples.".to_string()),
+                },
+                ParagraphChange {
+                    kind: ChangeKind::Added,
+                    old_page: None,
+                    new_page: Some(2),
+                    old_text: None,
+                    new_text: Some("Limitations are discussed.".to_string()),
+                },
+            ]
+        );
+        assert_eq!(diff_documents(&new, &old)[1].kind, ChangeKind::Removed);
+    }
+
+    #[test]
+    fn test_whitespace_and_ligatures_are_not_changes() {
+        let old = test_document(&[&["The \u{FB01}lter stage\nruns first.", "Unchanged."]]);
+        let new = test_document(&[&["The filter  stage runs first.", "Unchanged."]]);
+
+        assert!(diff_documents(&old, &new).is_empty());
+    }
+}
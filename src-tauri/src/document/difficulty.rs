@@ -0,0 +1,219 @@
+//! Per-section difficulty estimates for study planning
+
+use super::editor::find_math_spans;
+use super::{segment_sections, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Reading speed for the easiest material, in words per minute
+const BASE_WORDS_PER_MINUTE: f32 = 220.0;
+
+/// Share of the reading speed lost on the hardest material
+const MAX_SLOWDOWN: f32 = 0.7;
+
+/// Minimum length for a word to count as a (potentially technical) term
+const TERM_MIN_CHARS: usize = 7;
+
+/// Difficulty estimate for one section. Component scores run from 0 (easy) to 1 (hard).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectionDifficulty {
+    pub title: String,
+    /// Page the section starts on
+    pub page: u32,
+    pub word_count: usize,
+    /// Inverted Flesch reading ease
+    pub readability: f32,
+    /// Share of the text that is math
+    pub equation_density: f32,
+    /// Share of the section's terms not seen in earlier sections
+    pub term_novelty: f32,
+    /// Weighted combination of the components
+    pub score: f32,
+    pub estimated_minutes: f32,
+}
+
+/// Estimate difficulty for each section of the document
+pub fn section_difficulty(doc: &Document) -> Vec<SectionDifficulty> {
+    let mut seen_terms = HashSet::new();
+    segment_sections(doc)
+        .into_iter()
+        .map(|section| {
+            let text = section.text(doc);
+            score_section(section.title, section.page, &text, &mut seen_terms)
+        })
+        .collect()
+}
+
+fn score_section(
+    title: String,
+    page: u32,
+    text: &str,
+    seen_terms: &mut HashSet<String>,
+) -> SectionDifficulty {
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .filter(|w| w.chars().any(char::is_alphabetic))
+        .collect();
+
+    let readability = readability_difficulty(text, &words);
+    let equation_density = equation_density(text);
+
+    let terms: HashSet<String> = words
+        .iter()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|w| w.chars().count() >= TERM_MIN_CHARS && w.chars().all(char::is_alphabetic))
+        .collect();
+    let new_terms = terms.iter().filter(|t| !seen_terms.contains(*t)).count();
+    let term_novelty = if words.is_empty() {
+        0.0
+    } else {
+        (new_terms as f32 * 10.0 / words.len() as f32).min(1.0)
+    };
+    seen_terms.extend(terms);
+
+    let score = 0.4 * readability + 0.4 * equation_density + 0.2 * term_novelty;
+    let words_per_minute = BASE_WORDS_PER_MINUTE * (1.0 - MAX_SLOWDOWN * score);
+
+    SectionDifficulty {
+        title,
+        page,
+        word_count: words.len(),
+        readability,
+        equation_density,
+        term_novelty,
+        score,
+        estimated_minutes: words.len() as f32 / words_per_minute,
+    }
+}
+
+/// Flesch reading ease mapped so 100+ (very easy) is 0 and 0 or below (very hard) is 1
+fn readability_difficulty(text: &str, words: &[&str]) -> f32 {
+    if words.is_empty() {
+        return 0.0;
+    }
+    let sentences = text
+        .split(['.', '!', '?'])
+        .filter(|s| s.chars().any(char::is_alphabetic))
+        .count()
+        .max(1);
+    let syllables: usize = words.iter().map(|w| count_syllables(w)).sum();
+
+    let words_per_sentence = words.len() as f32 / sentences as f32;
+    let syllables_per_word = syllables as f32 / words.len() as f32;
+    let ease = 206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word;
+    ((100.0 - ease) / 100.0).clamp(0.0, 1.0)
+}
+
+/// Vowel groups in a word, ignoring a silent trailing "e"
+fn count_syllables(word: &str) -> usize {
+    let word = word.to_lowercase();
+    let is_vowel = |c: char| "aeiouy".contains(c);
+
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for c in word.chars().filter(|c| c.is_alphabetic()) {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    if word.ends_with('e') && !word.ends_with("le") && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+/// Fraction of characters inside delimited math or made of math symbols, scaled so a
+/// quarter of the text being math counts as fully dense
+fn equation_density(text: &str) -> f32 {
+    let total = text.chars().filter(|c| !c.is_whitespace()).count();
+    if total == 0 {
+        return 0.0;
+    }
+
+    let spans = find_math_spans(text);
+    let in_spans: usize = spans
+        .iter()
+        .map(|s| text[s.start..s.end].chars().filter(|c| !c.is_whitespace()).count())
+        .sum();
+
+    // Undelimited math, as extracted from PDFs
+    let outside_spans = text.char_indices().filter(|(i, c)| {
+        !spans.iter().any(|s| (s.start..s.end).contains(i)) && is_math_symbol(*c)
+    });
+
+    let math = in_spans + outside_spans.count();
+    (math as f32 / total as f32 * 4.0).min(1.0)
+}
+
+fn is_math_symbol(c: char) -> bool {
+    matches!(c, '=' | '+' | '^' | '<' | '>' | '±' | '×' | '÷' | '√')
+        || ('\u{2200}'..='\u{22FF}').contains(&c) // Mathematical operators
+        || ('\u{0391}'..='\u{03C9}').contains(&c) // Greek letters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{test_document, DocumentType};
+
+    /// Markdown document at `path` with the given pages of paragraphs
+    fn document(path: &std::path::Path, pages: &[&[&str]]) -> Document {
+        Document {
+            doc_type: DocumentType::Markdown,
+            path: path.to_str().unwrap().to_string(),
+            ..test_document(pages)
+        }
+    }
+
+    #[test]
+    fn test_equation_heavy_section_scores_harder_than_prose() {
+        let math_text = "Let $x^2 + y^2 = z^2$ hold. \
+                         Then $$\\sum_{i=1}^{n} i = \\frac{n(n+1)}{2}$$ \
+                         and $\\int_0^1 f(x)\\,dx \\le \\|f\\|_\\infty$ for every bounded $f$.";
+        let prose_text = "The cat sat on the mat. It was a warm day. The dog came by and sat too. \
+                          They both had a nap in the sun.";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        let source = format!("# Proof\n\n{}\n\n# Story\n\n{}", math_text, prose_text);
+        std::fs::write(&path, source).unwrap();
+        let doc = document(&path, &[&["Proof", math_text], &["Story", prose_text]]);
+
+        let sections = section_difficulty(&doc);
+        assert_eq!(sections.len(), 2);
+        let (math, prose) = (&sections[0], &sections[1]);
+
+        assert_eq!((math.title.as_str(), prose.page), ("Proof", 2));
+        assert!(math.equation_density > 0.5, "{:?}", math);
+        assert_eq!(prose.equation_density, 0.0);
+        assert!(math.score > prose.score, "{:?} vs {:?}", math, prose);
+
+        // Harder material is read more slowly
+        let math_rate = math.word_count as f32 / math.estimated_minutes;
+        let prose_rate = prose.word_count as f32 / prose.estimated_minutes;
+        assert!(math_rate < prose_rate);
+    }
+
+    #[test]
+    fn test_sections_follow_outline_headings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "# Intro\n\nPlain words.\n\n# Proof\n\n$a = b$").unwrap();
+
+        let doc = document(&path, &[&["Intro", "Plain words.", "Proof", "$a = b$"]]);
+
+        let sections = section_difficulty(&doc);
+        let titles: Vec<_> = sections.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["Intro", "Proof"]);
+        assert!(sections[1].equation_density > sections[0].equation_density);
+    }
+
+    #[test]
+    fn test_count_syllables() {
+        assert_eq!(count_syllables("cat"), 1);
+        assert_eq!(count_syllables("make"), 1);
+        assert_eq!(count_syllables("table"), 2);
+        assert_eq!(count_syllables("probability"), 5);
+    }
+}
@@ -8,9 +8,14 @@
 //! - EPUB: Content and metadata editing
 
 use async_trait::async_trait;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
+use super::model::{DocxModel, EpubModel, SnapshotHistory};
+
 // ============================================================================
 // Common Types
 // ============================================================================
@@ -54,6 +59,128 @@ pub struct WordStats {
     pub paragraphs: u32,
 }
 
+/// Kind of math found in Markdown/LaTeX source
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MathKind {
+    /// `$...$`
+    Inline,
+    /// `$$...$$` or `\[...\]`
+    Display,
+}
+
+/// A math span in source text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MathSpan {
+    pub kind: MathKind,
+    /// Byte range of the span including delimiters
+    pub start: usize,
+    pub end: usize,
+    /// TeX source between the delimiters
+    pub tex: String,
+}
+
+impl MathSpan {
+    /// Render as delimited TeX for a frontend math renderer (e.g. KaTeX auto-render)
+    pub fn to_html(&self) -> String {
+        let tex = self
+            .tex
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        match self.kind {
+            MathKind::Inline => format!("<span class=\"math math-inline\">\\({}\\)</span>", tex),
+            MathKind::Display => format!("<div class=\"math math-display\">\\[{}\\]</div>", tex),
+        }
+    }
+}
+
+/// Find inline and display math, skipping code spans/blocks and escaped `\$`
+pub fn find_math_spans(text: &str) -> Vec<MathSpan> {
+    let bytes = text.as_bytes();
+    let find = |needle: &[u8], from: usize| {
+        bytes[from.min(bytes.len())..]
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map(|p| p + from)
+    };
+
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if bytes[i..].starts_with(b"\\[") => match find(b"\\]", i + 2) {
+                Some(close) => {
+                    spans.push(MathSpan {
+                        kind: MathKind::Display,
+                        start: i,
+                        end: close + 2,
+                        tex: text[i + 2..close].trim().to_string(),
+                    });
+                    i = close + 2;
+                }
+                None => i += 2,
+            },
+            // Escaped character, e.g. a literal \$
+            b'\\' => i += 2,
+            b'`' => {
+                let run = bytes[i..].iter().take_while(|&&b| b == b'`').count();
+                let fence = &bytes[i..i + run];
+                i = match find(fence, i + run) {
+                    Some(close) => close + run,
+                    // Unclosed fences run to the end; a stray backtick is literal
+                    None if run >= 3 => bytes.len(),
+                    None => i + run,
+                };
+            }
+            b'$' if bytes[i..].starts_with(b"$$") => match find(b"$$", i + 2) {
+                Some(close) if close > i + 2 => {
+                    spans.push(MathSpan {
+                        kind: MathKind::Display,
+                        start: i,
+                        end: close + 2,
+                        tex: text[i + 2..close].trim().to_string(),
+                    });
+                    i = close + 2;
+                }
+                _ => i += 2,
+            },
+            b'$' => {
+                // Opening `$` must hug its content: "$x$" but not "$ 5"
+                let opens = bytes.get(i + 1).is_some_and(|b| !b.is_ascii_whitespace());
+                let close = opens
+                    .then(|| {
+                        (i + 1..bytes.len())
+                            .take_while(|&j| !bytes[j..].starts_with(b"\n\n"))
+                            .find(|&j| {
+                                bytes[j] == b'$'
+                                    && !bytes[j - 1].is_ascii_whitespace()
+                                    && bytes[j - 1] != b'\\'
+                                    && !bytes.get(j + 1).is_some_and(|b| b.is_ascii_digit())
+                            })
+                    })
+                    .flatten();
+
+                match close {
+                    Some(close) if close > i + 1 => {
+                        spans.push(MathSpan {
+                            kind: MathKind::Inline,
+                            start: i,
+                            end: close + 1,
+                            tex: text[i + 1..close].to_string(),
+                        });
+                        i = close + 1;
+                    }
+                    _ => i += 1,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    spans
+}
+
 // ============================================================================
 // Common Edit Operations (shared across formats)
 // ============================================================================
@@ -407,10 +534,13 @@ pub enum MetadataField {
 }
 
 /// Table of contents entry
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TOCEntry {
     pub title: String,
     pub href: String,
+    /// Target page (1-indexed), when the entry points at a page
+    #[serde(default)]
+    pub page: Option<u32>,
     pub children: Vec<TOCEntry>,
 }
 
@@ -485,6 +615,109 @@ pub struct EditorConfig {
     pub tab_size: u8,
     /// Use spaces instead of tabs
     pub use_spaces: bool,
+    /// Markup kept when sanitizing rendered markdown
+    #[serde(default)]
+    pub html_allowlist: HtmlAllowlist,
+}
+
+/// Tags, attributes and URL schemes allowed in rendered previews. Everything
+/// else, including scripts and event handler attributes, is stripped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct HtmlAllowlist {
+    pub tags: Vec<String>,
+    /// Attributes allowed on any tag
+    pub attributes: Vec<String>,
+    /// Attributes allowed only on specific tags
+    pub tag_attributes: HashMap<String, Vec<String>>,
+    pub url_schemes: Vec<String>,
+}
+
+impl Default for HtmlAllowlist {
+    fn default() -> Self {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        Self {
+            tags: strings(&[
+                "a", "b", "blockquote", "br", "code", "del", "div", "em", "h1", "h2", "h3", "h4",
+                "h5", "h6", "hr", "i", "img", "li", "ol", "p", "pre", "s", "span", "strong",
+                "sub", "sup", "table", "tbody", "td", "th", "thead", "tr", "ul",
+            ]),
+            attributes: strings(&["class", "title"]),
+            tag_attributes: HashMap::from([
+                ("a".to_string(), strings(&["href"])),
+                ("img".to_string(), strings(&["src", "alt"])),
+            ]),
+            url_schemes: strings(&["http", "https", "mailto"]),
+        }
+    }
+}
+
+impl HtmlAllowlist {
+    /// Reject tags and attributes that can't be allowed in previews: `script` and
+    /// `style`, SVG animation tags, which can rewrite attributes after sanitizing, and
+    /// `rel`, which is set on links by the sanitizer itself
+    pub fn validate(&self) -> Result<(), EditorError> {
+        let mut tags = self.tags.iter().chain(self.tag_attributes.keys());
+        if let Some(tag) = tags.find(|t| is_forbidden_tag(t)) {
+            return Err(EditorError::UnsupportedOperation(format!(
+                "Allowing <{}> in previews",
+                tag
+            )));
+        }
+        let mut attributes = self.attributes.iter().chain(self.tag_attributes.values().flatten());
+        if attributes.any(|a| is_forbidden_attribute(a)) {
+            return Err(EditorError::UnsupportedOperation(
+                "Allowing the rel attribute in previews".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Tags never allowed in previews
+fn is_forbidden_tag(tag: &str) -> bool {
+    let tag = tag.to_ascii_lowercase();
+    matches!(
+        tag.as_str(),
+        "script" | "style" | "animate" | "animatemotion" | "animatetransform" | "set"
+    )
+}
+
+/// Attributes never allowed in previews
+fn is_forbidden_attribute(attribute: &str) -> bool {
+    attribute.eq_ignore_ascii_case("rel")
+}
+
+/// Strip markup not in the allowlist from untrusted HTML. Entries `validate` rejects
+/// are ignored, as ammonia panics on some of them.
+pub fn sanitize_html(html: &str, allowlist: &HtmlAllowlist) -> String {
+    fn allowed(attrs: &[String]) -> HashSet<&str> {
+        attrs
+            .iter()
+            .map(String::as_str)
+            .filter(|a| !is_forbidden_attribute(a))
+            .collect()
+    }
+    let tags = allowlist
+        .tags
+        .iter()
+        .map(String::as_str)
+        .filter(|t| !is_forbidden_tag(t))
+        .collect();
+    let tag_attributes = allowlist
+        .tag_attributes
+        .iter()
+        .filter(|(tag, _)| !is_forbidden_tag(tag))
+        .map(|(tag, attrs)| (tag.as_str(), allowed(attrs)))
+        .collect();
+
+    ammonia::Builder::default()
+        .tags(tags)
+        .generic_attributes(allowed(&allowlist.attributes))
+        .tag_attributes(tag_attributes)
+        .url_schemes(allowlist.url_schemes.iter().map(String::as_str).collect())
+        .clean(html)
+        .to_string()
 }
 
 impl Default for EditorConfig {
@@ -500,6 +733,7 @@ impl Default for EditorConfig {
             create_backup: true,
             tab_size: 4,
             use_spaces: true,
+            html_allowlist: HtmlAllowlist::default(),
         }
     }
 }
@@ -534,6 +768,27 @@ pub enum EditorError {
 
     #[error("Parse error: {0}")]
     ParseError(String),
+
+    #[error("File changed on disk since it was opened: {0}")]
+    ExternallyModified(String),
+
+    #[error("Citation key already exists: {0}")]
+    DuplicateCiteKey(String),
+}
+
+/// Hash a file's bytes, or `None` if it can't be read
+fn hash_file(path: &str) -> Option<String> {
+    super::hash_file(path).ok()
+}
+
+/// Refuse to overwrite a file whose content changed since the editor last saw it
+fn ensure_unchanged_on_disk(path: &str, known_hash: &Option<String>) -> Result<(), EditorError> {
+    match hash_file(path) {
+        Some(current) if known_hash.as_ref() != Some(&current) => {
+            Err(EditorError::ExternallyModified(path.to_string()))
+        }
+        _ => Ok(()),
+    }
 }
 
 /// Unified document editor trait
@@ -560,13 +815,78 @@ pub trait DocumentEditor: Send + Sync {
     /// Clear all pending operations
     fn clear_operations(&mut self);
 
-    /// Save changes to original file
-    async fn save(&mut self) -> Result<(), EditorError>;
+    /// Check whether the source file changed on disk since it was opened or last saved
+    fn changed_on_disk(&self) -> bool;
+
+    /// Save changes to original file.
+    ///
+    /// Fails with `ExternallyModified` if the file changed on disk since it was
+    /// opened, unless `force` is set.
+    async fn save(&mut self, force: bool) -> Result<(), EditorError>;
 
     /// Save changes to a new file
     async fn save_as(&self, path: &str) -> Result<(), EditorError>;
 }
 
+// ============================================================================
+// File Watching
+// ============================================================================
+
+/// Quiet period used to coalesce bursts of file system events
+const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Handle to a file watcher; watching stops when it is dropped
+pub type FileWatcher = Debouncer<RecommendedWatcher>;
+
+/// Watch a file and call `on_change` once per debounced burst of changes
+pub fn watch_file<F>(path: &str, on_change: F) -> Result<FileWatcher, EditorError>
+where
+    F: Fn() + Send + 'static,
+{
+    let mut debouncer = new_debouncer(
+        WATCH_DEBOUNCE,
+        move |result: DebounceEventResult| match result {
+            Ok(events) if !events.is_empty() => on_change(),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("File watcher error: {:?}", e),
+        },
+    )
+    .map_err(|e| EditorError::IoError(e.to_string()))?;
+
+    debouncer
+        .watcher()
+        .watch(Path::new(path), RecursiveMode::NonRecursive)
+        .map_err(|e| EditorError::IoError(e.to_string()))?;
+
+    Ok(debouncer)
+}
+
+/// Watch a directory (not its subdirectories) and call `on_change` with the
+/// paths touched in each debounced burst of changes
+pub fn watch_dir<F>(path: &str, on_change: F) -> Result<FileWatcher, EditorError>
+where
+    F: Fn(Vec<std::path::PathBuf>) + Send + 'static,
+{
+    let mut debouncer = new_debouncer(
+        WATCH_DEBOUNCE,
+        move |result: DebounceEventResult| match result {
+            Ok(events) if !events.is_empty() => {
+                on_change(events.into_iter().map(|e| e.path).collect())
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Folder watcher error: {:?}", e),
+        },
+    )
+    .map_err(|e| EditorError::IoError(e.to_string()))?;
+
+    debouncer
+        .watcher()
+        .watch(Path::new(path), RecursiveMode::NonRecursive)
+        .map_err(|e| EditorError::IoError(e.to_string()))?;
+
+    Ok(debouncer)
+}
+
 // ============================================================================
 // PDF Editor Implementation
 // ============================================================================
@@ -584,6 +904,10 @@ pub struct PDFEditor {
     config: EditorConfig,
     /// Whether document has unsaved changes
     has_changes: bool,
+    /// Hash of the file on disk when opened or last saved
+    disk_hash: Option<String>,
+    /// Width and height of each page of the source PDF, read on first use
+    page_sizes: Option<Vec<(f32, f32)>>,
 }
 
 impl PDFEditor {
@@ -598,15 +922,76 @@ impl PDFEditor {
             operations: Vec::new(),
             undo_stack: Vec::new(),
             config: EditorConfig::default(),
+            disk_hash: hash_file(path),
             has_changes: false,
+            page_sizes: None,
         })
     }
 
-    /// Add an edit operation
-    pub fn add_operation(&mut self, operation: PDFEditOperation) {
-        self.operations.push(operation);
+    /// Add an edit operation, rejecting pages the document won't have once the
+    /// operations already queued are applied
+    pub fn add_operation(&mut self, operation: PDFEditOperation) -> Result<(), EditorError> {
+        self.add_operations(vec![operation])
+    }
+
+    /// Add several edit operations in order. Nothing is queued if any targets a
+    /// missing page.
+    pub fn add_operations(&mut self, operations: Vec<PDFEditOperation>) -> Result<(), EditorError> {
+        let mut page_count = self.pending_page_count()?;
+        for operation in &operations {
+            check_pages(operation, page_count)?;
+            page_count = page_count_after(page_count, operation);
+        }
+
+        self.operations.extend(operations);
         self.undo_stack.clear(); // Clear redo stack on new operation
         self.has_changes = true;
+        Ok(())
+    }
+
+    /// Number of pages in the source PDF, before pending operations
+    pub fn page_count(&mut self) -> Result<u32, EditorError> {
+        Ok(self.load_page_sizes()?.len() as u32)
+    }
+
+    /// Width and height of a page in points, from its MediaBox
+    pub fn page_size(&mut self, page: u32) -> Result<(f32, f32), EditorError> {
+        let sizes = self.load_page_sizes()?;
+        page.checked_sub(1)
+            .and_then(|index| sizes.get(index as usize))
+            .copied()
+            .ok_or(EditorError::PageOutOfRange(page))
+    }
+
+    fn load_page_sizes(&mut self) -> Result<&[(f32, f32)], EditorError> {
+        if self.page_sizes.is_none() {
+            let doc = load_pdf(&self.source_path)?;
+            let sizes = doc
+                .get_pages()
+                .into_values()
+                .map(|page_id| media_box_size(&doc, page_id))
+                .collect();
+            self.page_sizes = Some(sizes);
+        }
+        Ok(self.page_sizes.as_deref().unwrap_or_default())
+    }
+
+    /// Page count once the queued operations are applied
+    fn pending_page_count(&mut self) -> Result<u32, EditorError> {
+        let source_pages = self.page_count()?;
+        Ok(self.operations.iter().fold(source_pages, page_count_after))
+    }
+
+    /// Write a copy of the PDF with the queued operations applied as on save, then its
+    /// annotations, including queued highlights and notes, drawn into the page content.
+    /// Returns the number of marks drawn.
+    pub fn flatten_annotations(&self, output_path: &str) -> Result<usize, EditorError> {
+        let mut doc = load_pdf(&self.source_path)?;
+        super::pdf_edit::apply_operations(&mut doc, &self.operations)?;
+        let drawn = crate::document::flatten::flatten_annotations(&mut doc)?;
+        save_pdf(doc, output_path)?;
+        tracing::info!("Flattened {} annotation marks into {}", drawn, output_path);
+        Ok(drawn)
     }
 
     /// Get pending operations
@@ -671,7 +1056,15 @@ impl DocumentEditor for PDFEditor {
         self.has_changes = false;
     }
 
-    async fn save(&mut self) -> Result<(), EditorError> {
+    fn changed_on_disk(&self) -> bool {
+        ensure_unchanged_on_disk(&self.source_path, &self.disk_hash).is_err()
+    }
+
+    async fn save(&mut self, force: bool) -> Result<(), EditorError> {
+        if !force {
+            ensure_unchanged_on_disk(&self.source_path, &self.disk_hash)?;
+        }
+
         if self.config.create_backup {
             let backup_path = format!("{}.backup", self.source_path);
             tokio::fs::copy(&self.source_path, &backup_path)
@@ -680,14 +1073,25 @@ impl DocumentEditor for PDFEditor {
         }
 
         self.save_as(&self.source_path.clone()).await?;
+        self.disk_hash = hash_file(&self.source_path);
+        self.page_sizes = None;
+        // The file now contains the edits, so replaying them again would double them
+        self.operations.clear();
+        self.undo_stack.clear();
         self.has_changes = false;
         Ok(())
     }
 
     async fn save_as(&self, output_path: &str) -> Result<(), EditorError> {
-        // TODO: Implement actual PDF manipulation with lopdf/pdfium
+        if self.config.flatten_annotations {
+            return self.flatten_annotations(output_path).map(|_| ());
+        }
+
+        let mut doc = load_pdf(&self.source_path)?;
+        super::pdf_edit::apply_operations(&mut doc, &self.operations)?;
+        save_pdf(doc, output_path)?;
         tracing::info!(
-            "Saving PDF with {} operations to {}",
+            "Saved PDF with {} operations to {}",
             self.operations.len(),
             output_path
         );
@@ -695,6 +1099,50 @@ impl DocumentEditor for PDFEditor {
     }
 }
 
+/// Page count after applying `operation` to a document of `page_count` pages
+fn page_count_after(page_count: u32, operation: &PDFEditOperation) -> u32 {
+    match operation {
+        PDFEditOperation::InsertPage { .. } => page_count + 1,
+        PDFEditOperation::DeletePage { .. } => page_count.saturating_sub(1),
+        _ => page_count,
+    }
+}
+
+/// Check that every page `operation` targets exists in a document of `page_count`
+/// pages. Pages are numbered from 1; a blank page may be inserted after page 0.
+pub(super) fn check_pages(
+    operation: &PDFEditOperation,
+    page_count: u32,
+) -> Result<(), EditorError> {
+    let pages = match operation {
+        PDFEditOperation::AddText { page, .. }
+        | PDFEditOperation::AddImage { page, .. }
+        | PDFEditOperation::AddHighlight { page, .. }
+        | PDFEditOperation::AddAnnotation { page, .. }
+        | PDFEditOperation::AddShape { page, .. }
+        | PDFEditOperation::AddLine { page, .. }
+        | PDFEditOperation::DeletePage { page }
+        | PDFEditOperation::RotatePage { page, .. }
+        | PDFEditOperation::Redact { page, .. }
+        | PDFEditOperation::AddSignature { page, .. }
+        | PDFEditOperation::AddLink { page, .. }
+        | PDFEditOperation::AddBookmark { page, .. } => vec![*page],
+        PDFEditOperation::InsertPage { after_page, .. } => {
+            if *after_page > page_count {
+                return Err(EditorError::PageOutOfRange(*after_page));
+            }
+            return Ok(());
+        }
+        PDFEditOperation::AddWatermark { pages, .. } => pages.clone().unwrap_or_default(),
+        PDFEditOperation::FillFormField { .. } => Vec::new(),
+    };
+
+    match pages.into_iter().find(|page| *page == 0 || *page > page_count) {
+        Some(page) => Err(EditorError::PageOutOfRange(page)),
+        None => Ok(()),
+    }
+}
+
 // ============================================================================
 // Text/Markdown Editor Implementation
 // ============================================================================
@@ -710,12 +1158,142 @@ pub struct TextEditor {
     original_content: String,
     /// Pending edit operations
     operations: Vec<TextEditOperation>,
-    /// Undo stack
-    undo_stack: Vec<(String, TextEditOperation)>, // (previous_content, operation)
+    /// Undone operations, for redo
+    undo_stack: Vec<TextEditOperation>,
+    /// Content before each applied operation
+    history: SnapshotHistory<String>,
     /// Whether this is a markdown file
     is_markdown: bool,
     /// Editor configuration
     config: EditorConfig,
+    /// Hash of the file on disk when opened or last saved
+    disk_hash: Option<String>,
+    /// Rendered HTML of the blocks sent by the last incremental preview, by block id
+    preview_cache: HashMap<String, String>,
+    /// Files this buffer was assembled from, in order; empty for a single-file editor
+    merged_sources: Vec<MergedSource>,
+}
+
+/// A file contributing to a merged text buffer
+#[derive(Debug)]
+struct MergedSource {
+    path: String,
+    /// Hash of the file on disk when opened or last saved
+    disk_hash: Option<String>,
+}
+
+/// Where one source file's text currently sits in a merged buffer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SourceRange {
+    pub path: String,
+    /// Byte offsets of the file's text, excluding its marker line
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Line that introduces a source file in a merged buffer
+fn merge_marker(path: &str) -> String {
+    format!("<!-- source: {} -->", path)
+}
+
+/// One rendered block of an incremental markdown preview
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PreviewBlock {
+    pub block_id: String,
+    pub html: String,
+}
+
+/// Result of an incremental markdown preview
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PreviewUpdate {
+    /// Ids of every block, in document order
+    pub order: Vec<String>,
+    /// Blocks that were not in the previous render
+    pub updates: Vec<PreviewBlock>,
+}
+
+/// Split markdown into blank-line separated blocks, keeping fenced code and
+/// display math together
+fn split_markdown_blocks(content: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current = String::new();
+    let mut in_fence = false;
+    let mut in_display_math = false;
+
+    for line in content.lines() {
+        if line.trim().is_empty() && !in_fence && !in_display_math {
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence && line.matches("$$").count() % 2 == 1 {
+            in_display_math = !in_display_math;
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
+}
+
+/// Block id derived from its content, with a suffix for repeated blocks
+fn preview_block_id(block: &str, seen: &mut HashMap<u64, usize>) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    block.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    let occurrence = seen.entry(hash).or_insert(0);
+    *occurrence += 1;
+    match *occurrence {
+        1 => format!("{:016x}", hash),
+        n => format!("{:016x}-{}", hash, n),
+    }
+}
+
+/// Render markdown (CommonMark with tables and strikethrough) to HTML. Fenced code
+/// blocks keep their language as a `language-*` class for syntax highlighting.
+fn markdown_to_html(content: &str) -> String {
+    use pulldown_cmark::{html, Options, Parser};
+
+    // Swap math out for placeholders so `*`, `_` and `\` inside equations are not
+    // read as markdown. Private use characters have no markdown meaning.
+    let math = find_math_spans(content);
+    let placeholder = |n: usize| format!("\u{E000}{}\u{E001}", n);
+    let mut source = String::with_capacity(content.len());
+    let mut last = 0;
+    for (n, span) in math.iter().enumerate() {
+        source.push_str(&content[last..span.start]);
+        source.push_str(&placeholder(n));
+        last = span.end;
+    }
+    source.push_str(&content[last..]);
+
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let mut html = String::with_capacity(source.len() * 3 / 2);
+    html::push_html(&mut html, Parser::new_ext(&source, options));
+
+    for (n, span) in math.iter().enumerate() {
+        let placeholder = placeholder(n);
+        // Display math on its own line becomes its block instead of a paragraph
+        let paragraph = format!("<p>{}</p>", placeholder);
+        if span.kind == MathKind::Display && html.contains(&paragraph) {
+            html = html.replace(&paragraph, &span.to_html());
+        } else {
+            html = html.replace(&placeholder, &span.to_html());
+        }
+    }
+    html
 }
 
 impl TextEditor {
@@ -735,19 +1313,145 @@ impl TextEditor {
             content,
             operations: Vec::new(),
             undo_stack: Vec::new(),
+            history: SnapshotHistory::default(),
+            is_markdown,
+            config: EditorConfig::default(),
+            disk_hash: hash_file(path),
+            preview_cache: HashMap::new(),
+            merged_sources: Vec::new(),
+        })
+    }
+
+    /// Open several text or markdown files as one buffer. Each file is introduced by a
+    /// marker line naming it and separated from the previous one by a newline; saving
+    /// writes each file's part back to it.
+    pub fn open_merged(paths: &[String]) -> Result<Self, EditorError> {
+        let (first, _) = paths.split_first().ok_or_else(|| {
+            EditorError::UnsupportedOperation("No files to merge".to_string())
+        })?;
+
+        let mut content = String::new();
+        let mut merged_sources = Vec::with_capacity(paths.len());
+        for (i, path) in paths.iter().enumerate() {
+            let text = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => EditorError::FileNotFound(path.clone()),
+                _ => EditorError::IoError(e.to_string()),
+            })?;
+            if i > 0 {
+                content.push('\n');
+            }
+            content.push_str(&merge_marker(path));
+            content.push('\n');
+            content.push_str(&text);
+            merged_sources.push(MergedSource {
+                path: path.clone(),
+                disk_hash: hash_file(path),
+            });
+        }
+
+        let is_markdown = paths
+            .iter()
+            .all(|p| p.ends_with(".md") || p.ends_with(".markdown"));
+
+        Ok(Self {
+            source_path: first.clone(),
+            original_content: content.clone(),
+            content,
+            operations: Vec::new(),
+            undo_stack: Vec::new(),
+            history: SnapshotHistory::default(),
             is_markdown,
             config: EditorConfig::default(),
+            disk_hash: None,
+            preview_cache: HashMap::new(),
+            merged_sources,
         })
     }
 
+    /// Whether this buffer was assembled from several files
+    pub fn is_merged(&self) -> bool {
+        !self.merged_sources.is_empty()
+    }
+
+    /// Locate each source file's text in a merged buffer. Fails if a marker line was
+    /// edited away or moved out of order, since the buffer can then no longer be split.
+    pub fn source_ranges(&self) -> Result<Vec<SourceRange>, EditorError> {
+        let mut markers = Vec::with_capacity(self.merged_sources.len());
+        let mut from = 0;
+        for source in &self.merged_sources {
+            let marker = format!("{}\n", merge_marker(&source.path));
+            let at = self.content[from..]
+                .match_indices(&marker)
+                .map(|(i, _)| from + i)
+                .find(|&i| i == 0 || self.content.as_bytes()[i - 1] == b'\n')
+                .ok_or_else(|| {
+                    let message = format!("Missing source marker for {}", source.path);
+                    EditorError::InvalidDocument(message)
+                })?;
+            from = at + marker.len();
+            markers.push((at, from));
+        }
+
+        Ok(self
+            .merged_sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| {
+                let start = markers[i].1;
+                // Drop the newline that separates this part from the next marker
+                let end = markers
+                    .get(i + 1)
+                    .map_or(self.content.len(), |&(next, _)| (next - 1).max(start));
+                SourceRange {
+                    path: source.path.clone(),
+                    start,
+                    end,
+                }
+            })
+            .collect())
+    }
+
+    /// Write each part of a merged buffer back to the file it came from
+    async fn save_merged(&mut self, force: bool) -> Result<(), EditorError> {
+        let ranges = self.source_ranges()?;
+        if !force {
+            for source in &self.merged_sources {
+                ensure_unchanged_on_disk(&source.path, &source.disk_hash)?;
+            }
+        }
+
+        for (source, range) in self.merged_sources.iter_mut().zip(&ranges) {
+            if self.config.create_backup && Path::new(&source.path).exists() {
+                tokio::fs::copy(&source.path, format!("{}.backup", source.path))
+                    .await
+                    .map_err(|e| EditorError::IoError(e.to_string()))?;
+            }
+            tokio::fs::write(&source.path, &self.content[range.start..range.end])
+                .await
+                .map_err(|e| EditorError::IoError(e.to_string()))?;
+            source.disk_hash = hash_file(&source.path);
+        }
+
+        self.original_content = self.content.clone();
+        Ok(())
+    }
+
     /// Get current content
     pub fn get_content(&self) -> &str {
         &self.content
     }
 
-    /// Set content directly
+    /// Set content directly, recorded as a replacement of the whole buffer so it can
+    /// be undone like any other edit
     pub fn set_content(&mut self, content: String) {
-        self.content = content;
+        let range = TextRange {
+            start: TextPosition { line: 0, column: 0 },
+            end: self.end_position(),
+        };
+        self.add_operation(TextEditOperation::Common(CommonEditOperation::ReplaceText {
+            range,
+            new_text: content,
+        }));
     }
 
     /// Check if file is markdown
@@ -755,9 +1459,14 @@ impl TextEditor {
         self.is_markdown
     }
 
+    /// Get applied operations
+    pub fn get_operations(&self) -> &[TextEditOperation] {
+        &self.operations
+    }
+
     /// Add an edit operation
     pub fn add_operation(&mut self, operation: TextEditOperation) {
-        let previous_content = self.content.clone();
+        self.history.record(self.content.clone());
 
         // Apply the operation to content
         self.apply_operation(&operation);
@@ -799,7 +1508,13 @@ impl TextEditor {
                 code,
             } => {
                 let offset = self.position_to_offset(position);
-                let block = format!("```{}\n{}\n```\n", language, code);
+                // Fences only count at the start of a line
+                let newline = if offset == 0 || self.content[..offset].ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                };
+                let block = format!("{}```{}\n{}\n```\n", newline, language, code);
                 self.content.insert_str(offset, &block);
             }
             TextEditOperation::InsertLink {
@@ -830,6 +1545,14 @@ impl TextEditor {
         }
     }
 
+    /// Position just past the end of the content
+    pub fn end_position(&self) -> TextPosition {
+        TextPosition {
+            line: self.content.lines().count() as u32,
+            column: 0,
+        }
+    }
+
     /// Convert position to byte offset
     fn position_to_offset(&self, position: &TextPosition) -> usize {
         let mut offset = 0;
@@ -865,25 +1588,61 @@ impl TextEditor {
 
     /// Render markdown preview (returns HTML)
     pub fn render_markdown_preview(&self) -> String {
-        if !self.is_markdown {
-            return format!("<pre>{}</pre>", self.content);
-        }
+        let html = if self.is_markdown {
+            format!("<div class=\"markdown-preview\">{}</div>", markdown_to_html(&self.content))
+        } else {
+            format!("<pre>{}</pre>", self.content)
+        };
+        sanitize_html(&html, &self.config.html_allowlist)
+    }
+
+    /// Replace the markup allowed in previews, failing if `allowlist` is invalid
+    pub fn set_html_allowlist(&mut self, allowlist: HtmlAllowlist) -> Result<(), EditorError> {
+        allowlist.validate()?;
+        self.config.html_allowlist = allowlist;
+        self.preview_cache.clear();
+        Ok(())
+    }
 
-        // Basic markdown to HTML conversion
-        // TODO: Use pulldown-cmark for proper rendering
-        let mut html = self.content.clone();
+    /// Render the preview block by block, re-rendering only blocks that changed
+    /// since the previous call
+    pub fn render_markdown_preview_incremental(&mut self) -> PreviewUpdate {
+        let blocks = if self.is_markdown {
+            split_markdown_blocks(&self.content)
+        } else {
+            vec![self.content.clone()]
+        };
 
-        // Headers
-        for i in (1..=6).rev() {
-            let pattern = format!("\n{} ", "#".repeat(i));
-            let replacement = format!("\n<h{}> ", i);
-            html = html.replace(&pattern, &replacement);
+        let mut seen = HashMap::new();
+        let mut order = Vec::with_capacity(blocks.len());
+        let mut updates = Vec::new();
+        for block in &blocks {
+            let block_id = preview_block_id(block, &mut seen);
+            if !self.preview_cache.contains_key(&block_id) {
+                let html = if self.is_markdown {
+                    markdown_to_html(block)
+                } else {
+                    format!("<pre>{}</pre>", block)
+                };
+                let html = sanitize_html(&html, &self.config.html_allowlist);
+                self.preview_cache.insert(block_id.clone(), html.clone());
+                updates.push(PreviewBlock {
+                    block_id: block_id.clone(),
+                    html,
+                });
+            }
+            order.push(block_id);
         }
 
-        // Bold and italic
-        html = html.replace("**", "<strong>").replace("*", "<em>");
+        let current: HashSet<&String> = order.iter().collect();
+        self.preview_cache.retain(|id, _| current.contains(id));
+
+        PreviewUpdate { order, updates }
+    }
 
-        format!("<div class=\"markdown-preview\">{}</div>", html)
+    /// Forget previously rendered blocks so the next incremental preview is complete
+    pub fn reset_preview_cache(&mut self) {
+        self.preview_cache.clear();
     }
 }
 
@@ -902,18 +1661,17 @@ impl DocumentEditor for TextEditor {
     }
 
     fn undo(&mut self) -> Option<()> {
-        // Simple undo - would need proper implementation with stored states
-        if !self.operations.is_empty() {
-            self.operations.pop();
-            Some(())
-        } else {
-            None
-        }
+        let op = self.operations.pop()?;
+        self.undo_stack.push(op);
+        self.history.undo(&mut self.content);
+        Some(())
     }
 
     fn redo(&mut self) -> Option<()> {
-        // Would need proper redo implementation
-        None
+        let op = self.undo_stack.pop()?;
+        self.operations.push(op);
+        self.history.redo(&mut self.content);
+        Some(())
     }
 
     fn has_unsaved_changes(&self) -> bool {
@@ -925,11 +1683,32 @@ impl DocumentEditor for TextEditor {
     }
 
     fn clear_operations(&mut self) {
+        // Edits are applied as they are added, so discarding them means going back to
+        // the content last opened or saved
         self.operations.clear();
         self.undo_stack.clear();
+        self.history = SnapshotHistory::default();
+        self.content = self.original_content.clone();
+    }
+
+    fn changed_on_disk(&self) -> bool {
+        if self.is_merged() {
+            return self
+                .merged_sources
+                .iter()
+                .any(|s| ensure_unchanged_on_disk(&s.path, &s.disk_hash).is_err());
+        }
+        ensure_unchanged_on_disk(&self.source_path, &self.disk_hash).is_err()
     }
 
-    async fn save(&mut self) -> Result<(), EditorError> {
+    async fn save(&mut self, force: bool) -> Result<(), EditorError> {
+        if self.is_merged() {
+            return self.save_merged(force).await;
+        }
+        if !force {
+            ensure_unchanged_on_disk(&self.source_path, &self.disk_hash)?;
+        }
+
         if self.config.create_backup && Path::new(&self.source_path).exists() {
             let backup_path = format!("{}.backup", self.source_path);
             tokio::fs::copy(&self.source_path, &backup_path)
@@ -938,14 +1717,14 @@ impl DocumentEditor for TextEditor {
         }
 
         self.save_as(&self.source_path.clone()).await?;
+        self.disk_hash = hash_file(&self.source_path);
         self.original_content = self.content.clone();
         Ok(())
     }
 
     async fn save_as(&self, output_path: &str) -> Result<(), EditorError> {
-        tokio::fs::write(output_path, &self.content)
-            .await
-            .map_err(|e| EditorError::IoError(e.to_string()))?;
+        let source_ext = if self.is_markdown { "md" } else { "txt" };
+        save_source_as(&self.content, source_ext, output_path).await?;
         tracing::info!("Saved text file to {}", output_path);
         Ok(())
     }
@@ -964,10 +1743,16 @@ pub struct DOCXEditor {
     operations: Vec<DOCXEditOperation>,
     /// Undo stack
     undo_stack: Vec<DOCXEditOperation>,
+    /// Content with the pending operations applied
+    model: DocxModel,
+    /// Snapshots of the content before each operation
+    history: SnapshotHistory<DocxModel>,
     /// Editor configuration
     config: EditorConfig,
     /// Whether document has unsaved changes
     has_changes: bool,
+    /// Hash of the file on disk when opened or last saved
+    disk_hash: Option<String>,
 }
 
 impl DOCXEditor {
@@ -981,13 +1766,18 @@ impl DOCXEditor {
             source_path: path.to_string(),
             operations: Vec::new(),
             undo_stack: Vec::new(),
+            model: DocxModel::load(path),
+            history: SnapshotHistory::default(),
             config: EditorConfig::default(),
+            disk_hash: hash_file(path),
             has_changes: false,
         })
     }
 
     /// Add an edit operation
     pub fn add_operation(&mut self, operation: DOCXEditOperation) {
+        self.history.record(self.model.clone());
+        self.model.apply(&operation);
         self.operations.push(operation);
         self.undo_stack.clear();
         self.has_changes = true;
@@ -997,6 +1787,11 @@ impl DOCXEditor {
     pub fn get_operations(&self) -> &[DOCXEditOperation] {
         &self.operations
     }
+
+    /// Current content, with the pending operations applied
+    pub fn model(&self) -> &DocxModel {
+        &self.model
+    }
 }
 
 #[async_trait]
@@ -1012,6 +1807,7 @@ impl DocumentEditor for DOCXEditor {
     fn undo(&mut self) -> Option<()> {
         if let Some(op) = self.operations.pop() {
             self.undo_stack.push(op);
+            self.history.undo(&mut self.model);
             if self.operations.is_empty() {
                 self.has_changes = false;
             }
@@ -1024,6 +1820,7 @@ impl DocumentEditor for DOCXEditor {
     fn redo(&mut self) -> Option<()> {
         if let Some(op) = self.undo_stack.pop() {
             self.operations.push(op);
+            self.history.redo(&mut self.model);
             self.has_changes = true;
             Some(())
         } else {
@@ -1042,12 +1839,22 @@ impl DocumentEditor for DOCXEditor {
     fn clear_operations(&mut self) {
         self.operations.clear();
         self.undo_stack.clear();
+        self.history.reset(&mut self.model);
         self.has_changes = false;
     }
 
-    async fn save(&mut self) -> Result<(), EditorError> {
+    fn changed_on_disk(&self) -> bool {
+        ensure_unchanged_on_disk(&self.source_path, &self.disk_hash).is_err()
+    }
+
+    async fn save(&mut self, force: bool) -> Result<(), EditorError> {
+        if !force {
+            ensure_unchanged_on_disk(&self.source_path, &self.disk_hash)?;
+        }
+
         // TODO: Implement with docx-rs
         self.save_as(&self.source_path.clone()).await?;
+        self.disk_hash = hash_file(&self.source_path);
         self.has_changes = false;
         Ok(())
     }
@@ -1082,6 +1889,8 @@ pub struct LaTeXEditor {
     undo_stack: Vec<LaTeXEditOperation>,
     /// Editor configuration
     config: EditorConfig,
+    /// Hash of the file on disk when opened or last saved
+    disk_hash: Option<String>,
 }
 
 impl LaTeXEditor {
@@ -1100,6 +1909,7 @@ impl LaTeXEditor {
             operations: Vec::new(),
             undo_stack: Vec::new(),
             config: EditorConfig::default(),
+            disk_hash: hash_file(path),
         })
     }
 
@@ -1114,6 +1924,11 @@ impl LaTeXEditor {
         self.undo_stack.clear();
     }
 
+    /// Get pending operations
+    pub fn get_operations(&self) -> &[LaTeXEditOperation] {
+        &self.operations
+    }
+
     /// Get LaTeX command completions
     pub fn get_completions(&self, prefix: &str) -> Vec<String> {
         let commands = vec![
@@ -1130,6 +1945,28 @@ impl LaTeXEditor {
             .map(String::from)
             .collect()
     }
+
+    /// Add a BibTeX entry to the document's bibliography so its key can be cited.
+    ///
+    /// The entry goes to the first file named by `\bibliography{}` or
+    /// `\addbibresource{}`, resolved next to the source and created if missing. Its key
+    /// must not be defined in any of the named files. Returns the path of the updated
+    /// `.bib` file.
+    pub fn add_bib_entry(&self, entry: &str) -> Result<String, EditorError> {
+        let source_dir = Path::new(&self.source_path).parent().unwrap_or(Path::new(""));
+        let bib_paths: Vec<std::path::PathBuf> = super::bibtex::bibliography_files(&self.content)
+            .into_iter()
+            .map(|name| source_dir.join(name))
+            .collect();
+        if bib_paths.is_empty() {
+            return Err(EditorError::UnsupportedOperation(
+                "Document has no \\bibliography or \\addbibresource".to_string(),
+            ));
+        }
+
+        super::bibtex::append_entry(&bib_paths, entry)?;
+        Ok(bib_paths[0].to_string_lossy().to_string())
+    }
 }
 
 #[async_trait]
@@ -1168,16 +2005,23 @@ impl DocumentEditor for LaTeXEditor {
         self.undo_stack.clear();
     }
 
-    async fn save(&mut self) -> Result<(), EditorError> {
+    fn changed_on_disk(&self) -> bool {
+        ensure_unchanged_on_disk(&self.source_path, &self.disk_hash).is_err()
+    }
+
+    async fn save(&mut self, force: bool) -> Result<(), EditorError> {
+        if !force {
+            ensure_unchanged_on_disk(&self.source_path, &self.disk_hash)?;
+        }
+
         self.save_as(&self.source_path.clone()).await?;
+        self.disk_hash = hash_file(&self.source_path);
         self.original_content = self.content.clone();
         Ok(())
     }
 
     async fn save_as(&self, output_path: &str) -> Result<(), EditorError> {
-        tokio::fs::write(output_path, &self.content)
-            .await
-            .map_err(|e| EditorError::IoError(e.to_string()))?;
+        save_source_as(&self.content, "tex", output_path).await?;
         tracing::info!("Saved LaTeX file to {}", output_path);
         Ok(())
     }
@@ -1196,10 +2040,16 @@ pub struct EPUBEditor {
     operations: Vec<EPUBEditOperation>,
     /// Undo stack
     undo_stack: Vec<EPUBEditOperation>,
+    /// Content with the pending operations applied
+    model: EpubModel,
+    /// Snapshots of the content before each operation
+    history: SnapshotHistory<EpubModel>,
     /// Editor configuration
     config: EditorConfig,
     /// Whether document has unsaved changes
     has_changes: bool,
+    /// Hash of the file on disk when opened or last saved
+    disk_hash: Option<String>,
 }
 
 impl EPUBEditor {
@@ -1213,13 +2063,18 @@ impl EPUBEditor {
             source_path: path.to_string(),
             operations: Vec::new(),
             undo_stack: Vec::new(),
+            model: EpubModel::load(path),
+            history: SnapshotHistory::default(),
             config: EditorConfig::default(),
+            disk_hash: hash_file(path),
             has_changes: false,
         })
     }
 
     /// Add an edit operation
     pub fn add_operation(&mut self, operation: EPUBEditOperation) {
+        self.history.record(self.model.clone());
+        self.model.apply(&operation);
         self.operations.push(operation);
         self.undo_stack.clear();
         self.has_changes = true;
@@ -1229,6 +2084,11 @@ impl EPUBEditor {
     pub fn get_operations(&self) -> &[EPUBEditOperation] {
         &self.operations
     }
+
+    /// Current content, with the pending operations applied
+    pub fn model(&self) -> &EpubModel {
+        &self.model
+    }
 }
 
 #[async_trait]
@@ -1244,6 +2104,7 @@ impl DocumentEditor for EPUBEditor {
     fn undo(&mut self) -> Option<()> {
         if let Some(op) = self.operations.pop() {
             self.undo_stack.push(op);
+            self.history.undo(&mut self.model);
             if self.operations.is_empty() {
                 self.has_changes = false;
             }
@@ -1256,6 +2117,7 @@ impl DocumentEditor for EPUBEditor {
     fn redo(&mut self) -> Option<()> {
         if let Some(op) = self.undo_stack.pop() {
             self.operations.push(op);
+            self.history.redo(&mut self.model);
             self.has_changes = true;
             Some(())
         } else {
@@ -1274,12 +2136,22 @@ impl DocumentEditor for EPUBEditor {
     fn clear_operations(&mut self) {
         self.operations.clear();
         self.undo_stack.clear();
+        self.history.reset(&mut self.model);
         self.has_changes = false;
     }
 
-    async fn save(&mut self) -> Result<(), EditorError> {
+    fn changed_on_disk(&self) -> bool {
+        ensure_unchanged_on_disk(&self.source_path, &self.disk_hash).is_err()
+    }
+
+    async fn save(&mut self, force: bool) -> Result<(), EditorError> {
+        if !force {
+            ensure_unchanged_on_disk(&self.source_path, &self.disk_hash)?;
+        }
+
         // TODO: Implement with epub crate
         self.save_as(&self.source_path.clone()).await?;
+        self.disk_hash = hash_file(&self.source_path);
         self.has_changes = false;
         Ok(())
     }
@@ -1309,6 +2181,117 @@ pub enum ImageFormat {
     Tiff,
 }
 
+impl ImageFormat {
+    /// Output format name understood by pdftoppm, if it can produce this format
+    fn pdftoppm_format(&self) -> Option<&'static str> {
+        match self {
+            Self::Png => Some("png"),
+            Self::Jpeg => Some("jpeg"),
+            Self::Tiff => Some("tiff"),
+            Self::Webp => None,
+        }
+    }
+}
+
+/// A rendered page image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedPage {
+    /// Path of the image file
+    pub path: String,
+    /// Whether the image was reused from an earlier render
+    pub cached: bool,
+}
+
+/// Document information fields to write into a PDF; `None` leaves a field unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PdfMetadataUpdate {
+    pub title: Option<String>,
+    pub authors: Option<Vec<String>>,
+    pub subject: Option<String>,
+    pub keywords: Option<Vec<String>>,
+}
+
+/// Directory holding rendered page images, keyed by document hash, page and DPI
+fn page_cache_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join("intellidoc-page-cache")
+}
+
+/// Size the page cache is trimmed to after each render, least recently used first
+const PAGE_CACHE_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Return the cached image for (document hash, page, DPI, extension), rendering it
+/// with `render` on a miss. `render` receives the output path without extension and
+/// returns the written image path. Blocks on file IO, so call it off the async runtime.
+fn render_page_cached<F>(
+    input_path: &str,
+    page: u32,
+    dpi: u32,
+    extension: &str,
+    cache_dir: &Path,
+    render: F,
+) -> Result<RenderedPage, EditorError>
+where
+    F: FnOnce(&Path) -> Result<std::path::PathBuf, EditorError>,
+{
+    let hash = hash_file(input_path)
+        .ok_or_else(|| EditorError::FileNotFound(input_path.to_string()))?;
+    let base = cache_dir.join(format!("{}-p{}-{}dpi", hash, page, dpi));
+    let cached = base.with_extension(extension);
+    if cached.is_file() {
+        // Eviction goes by modification time, so mark the image as recently used
+        if let Err(e) = std::fs::File::options()
+            .append(true)
+            .open(&cached)
+            .and_then(|file| file.set_modified(std::time::SystemTime::now()))
+        {
+            tracing::debug!("Could not mark {} as used: {}", cached.display(), e);
+        }
+        return Ok(RenderedPage {
+            path: cached.to_string_lossy().to_string(),
+            cached: true,
+        });
+    }
+
+    let page_count = load_pdf(input_path)?.get_pages().len() as u32;
+    if page == 0 || page > page_count {
+        return Err(EditorError::PageOutOfRange(page));
+    }
+
+    std::fs::create_dir_all(cache_dir).map_err(|e| EditorError::IoError(e.to_string()))?;
+    let path = render(&base)?;
+    if let Err(e) = evict_page_cache(cache_dir, PAGE_CACHE_MAX_BYTES) {
+        tracing::warn!("Could not trim the page cache: {}", e);
+    }
+    Ok(RenderedPage {
+        path: path.to_string_lossy().to_string(),
+        cached: false,
+    })
+}
+
+/// Delete the least recently used images in `cache_dir` until it holds at most
+/// `max_bytes`
+fn evict_page_cache(cache_dir: &Path, max_bytes: u64) -> std::io::Result<()> {
+    let mut images = Vec::new();
+    for entry in std::fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            images.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+
+    let mut total: u64 = images.iter().map(|(_, len, _)| len).sum();
+    images.sort_unstable_by_key(|(modified, _, _)| *modified);
+    for (_, len, path) in images {
+        if total <= max_bytes {
+            break;
+        }
+        std::fs::remove_file(&path)?;
+        total -= len;
+    }
+    Ok(())
+}
+
 /// PDF merging and splitting utilities
 pub struct PDFUtils;
 
@@ -1321,8 +2304,12 @@ impl PDFUtils {
             }
         }
         tracing::info!("Merging {} PDFs into {}", input_paths.len(), output_path);
-        // TODO: Implement with lopdf
-        Ok(())
+
+        let docs = input_paths
+            .iter()
+            .map(|path| load_pdf(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        save_pdf(merge_pdf_documents(docs)?, output_path)
     }
 
     /// Split a PDF into multiple files
@@ -1386,11 +2373,70 @@ impl PDFUtils {
         Ok(vec![])
     }
 
-    /// Convert images to PDF
-    pub async fn from_images(image_paths: &[&str], output_path: &str) -> Result<(), EditorError> {
-        for path in image_paths {
-            if !Path::new(path).exists() {
-                return Err(EditorError::FileNotFound(path.to_string()));
+    /// Render one page (1-indexed) to an image, reusing an earlier render when the
+    /// document content, page and DPI are unchanged
+    pub async fn render_page(
+        input_path: &str,
+        page: u32,
+        dpi: u32,
+        format: ImageFormat,
+    ) -> Result<RenderedPage, EditorError> {
+        if !Path::new(input_path).exists() {
+            return Err(EditorError::FileNotFound(input_path.to_string()));
+        }
+        let pdftoppm_format = format.pdftoppm_format().ok_or_else(|| {
+            EditorError::UnsupportedOperation(format!("Rendering pages as {:?}", format))
+        })?;
+        let extension = crate::document::ocr::pdftoppm_extension(pdftoppm_format)
+            .ok_or_else(|| EditorError::UnsupportedOperation(pdftoppm_format.to_string()))?;
+
+        // Hashing, loading and pdftoppm all block, so keep them off the runtime threads
+        let input_path = input_path.to_string();
+        tokio::task::spawn_blocking(move || {
+            let cache_dir = page_cache_dir();
+            render_page_cached(&input_path, page, dpi, extension, &cache_dir, |output_base| {
+                let format = pdftoppm_format;
+                crate::document::ocr::render_pdf_page(&input_path, page, dpi, format, output_base)
+                    .map_err(|e| EditorError::IoError(e.to_string()))
+            })
+        })
+        .await
+        .map_err(|e| EditorError::IoError(e.to_string()))?
+    }
+
+    /// Write title, author, subject and keywords into the PDF `/Info` dictionary
+    pub async fn set_metadata(
+        input_path: &str,
+        output_path: &str,
+        update: &PdfMetadataUpdate,
+    ) -> Result<(), EditorError> {
+        if !Path::new(input_path).exists() {
+            return Err(EditorError::FileNotFound(input_path.to_string()));
+        }
+        tracing::info!("Updating PDF metadata of {}", input_path);
+
+        let mut doc = load_pdf(input_path)?;
+        set_pdf_info(&mut doc, update)?;
+        save_pdf(doc, output_path)
+    }
+
+    /// Check whether each digital signature still covers unmodified bytes. Read-only;
+    /// the signer's certificate is not validated.
+    pub fn verify_signatures(
+        input_path: &str,
+    ) -> Result<Vec<crate::document::SignatureStatus>, EditorError> {
+        if !Path::new(input_path).exists() {
+            return Err(EditorError::FileNotFound(input_path.to_string()));
+        }
+        tracing::info!("Verifying signatures of {}", input_path);
+        crate::document::signature::verify_signatures(input_path)
+    }
+
+    /// Convert images to PDF
+    pub async fn from_images(image_paths: &[&str], output_path: &str) -> Result<(), EditorError> {
+        for path in image_paths {
+            if !Path::new(path).exists() {
+                return Err(EditorError::FileNotFound(path.to_string()));
             }
         }
         tracing::info!("Creating PDF from {} images", image_paths.len());
@@ -1399,52 +2445,591 @@ impl PDFUtils {
     }
 }
 
+fn load_pdf(path: &str) -> Result<lopdf::Document, EditorError> {
+    lopdf::Document::load(path)
+        .map_err(|e| EditorError::InvalidDocument(format!("{}: {}", path, e)))
+}
+
+fn save_pdf(mut doc: lopdf::Document, path: &str) -> Result<(), EditorError> {
+    doc.save(path)
+        .map(|_| ())
+        .map_err(|e| EditorError::IoError(e.to_string()))
+}
+
+/// Apply a metadata update to the document's `/Info` dictionary, creating it if missing
+fn set_pdf_info(doc: &mut lopdf::Document, update: &PdfMetadataUpdate) -> Result<(), EditorError> {
+    use lopdf::{Dictionary, Object};
+
+    let info_id = match doc.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(id) => id,
+        Err(_) => {
+            let id = doc.add_object(Dictionary::new());
+            doc.trailer.set("Info", id);
+            id
+        }
+    };
+
+    let mut fields = Vec::new();
+    if let Some(title) = &update.title {
+        fields.push(("Title", title.clone()));
+    }
+    if let Some(authors) = &update.authors {
+        fields.push(("Author", authors.join("; ")));
+    }
+    if let Some(subject) = &update.subject {
+        fields.push(("Subject", subject.clone()));
+    }
+    if let Some(keywords) = &update.keywords {
+        fields.push(("Keywords", keywords.join(", ")));
+    }
+    fields.push((
+        "ModDate",
+        chrono::Utc::now().format("D:%Y%m%d%H%M%SZ").to_string(),
+    ));
+
+    let info = doc
+        .get_object_mut(info_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?;
+    for (key, value) in fields {
+        info.set(key, pdf_text_string(&value));
+    }
+    Ok(())
+}
+
+/// Encode a PDF text string: literal for ASCII, UTF-16BE with a byte order mark otherwise
+pub(super) fn pdf_text_string(text: &str) -> lopdf::Object {
+    if text.is_ascii() {
+        return lopdf::Object::string_literal(text);
+    }
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    lopdf::Object::String(bytes, lopdf::StringFormat::Hexadecimal)
+}
+
+pub(super) fn pdf_error(e: lopdf::Error) -> EditorError {
+    EditorError::InvalidDocument(e.to_string())
+}
+
+/// Look up a page attribute, following the page tree up for inherited values
+pub(super) fn inherited_page_attribute(
+    doc: &lopdf::Document,
+    page_id: lopdf::ObjectId,
+    key: &[u8],
+) -> Option<lopdf::Object> {
+    let mut node = doc.get_dictionary(page_id).ok()?;
+    // Bound the walk in case of a malformed, cyclic tree
+    for _ in 0..32 {
+        if let Ok(value) = node.get(key) {
+            return Some(value.clone());
+        }
+        let parent = node.get(b"Parent").and_then(|p| p.as_reference()).ok()?;
+        node = doc.get_dictionary(parent).ok()?;
+    }
+    None
+}
+
+/// Page size assumed for pages without a MediaBox (US Letter, in points)
+const DEFAULT_PAGE_SIZE: (f32, f32) = (612.0, 792.0);
+
+/// Width and height of a page's MediaBox
+pub(super) fn media_box_size(doc: &lopdf::Document, page_id: lopdf::ObjectId) -> (f32, f32) {
+    let media_box = inherited_page_attribute(doc, page_id, b"MediaBox");
+    let corners: Option<Vec<f32>> = media_box.as_ref().and_then(|media_box| {
+        let (_, media_box) = doc.dereference(media_box).ok()?;
+        media_box
+            .as_array()
+            .ok()?
+            .iter()
+            .map(|n| doc.dereference(n).ok()?.1.as_float().ok())
+            .collect()
+    });
+
+    match corners.as_deref() {
+        Some([x0, y0, x1, y1]) => ((x1 - x0).abs(), (y1 - y0).abs()),
+        _ => DEFAULT_PAGE_SIZE,
+    }
+}
+
+/// Concatenate the pages of several PDFs, in order, into a single document
+fn merge_pdf_documents(docs: Vec<lopdf::Document>) -> Result<lopdf::Document, EditorError> {
+    use lopdf::{dictionary, Object};
+
+    let mut merged = lopdf::Document::with_version("1.5");
+    let mut page_ids = Vec::new();
+
+    for mut doc in docs {
+        doc.renumber_objects_with(merged.max_id + 1);
+
+        // Pages are re-parented below, so copy inherited attributes onto each page first
+        let pages: Vec<_> = doc.get_pages().into_values().collect();
+        for &page_id in &pages {
+            for key in [&b"Resources"[..], b"MediaBox", b"CropBox", b"Rotate"] {
+                if let Some(value) = inherited_page_attribute(&doc, page_id, key) {
+                    doc.get_dictionary_mut(page_id)
+                        .map_err(pdf_error)?
+                        .set(key, value);
+                }
+            }
+        }
+
+        merged.max_id = merged.max_id.max(doc.max_id);
+        merged.objects.extend(doc.objects);
+        page_ids.extend(pages);
+    }
+
+    let pages_id = merged.new_object_id();
+    for &page_id in &page_ids {
+        merged
+            .get_dictionary_mut(page_id)
+            .map_err(pdf_error)?
+            .set("Parent", pages_id);
+    }
+    merged.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => page_ids.iter().map(|&id| Object::Reference(id)).collect::<Vec<_>>(),
+            "Count" => page_ids.len() as i64,
+        }),
+    );
+    let catalog_id = merged.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    merged.trailer.set("Root", catalog_id);
+
+    // Drop the source catalogs, page trees and anything only they referenced
+    merged.prune_objects();
+    merged.renumber_objects();
+    merged.compress();
+
+    Ok(merged)
+}
+
 // ============================================================================
 // Conversion Utilities
 // ============================================================================
 
+/// Page size (US Letter, in points) for text rendered to PDF
+const TEXT_PDF_PAGE_SIZE: (f32, f32) = (612.0, 792.0);
+const TEXT_PDF_MARGIN: f32 = 72.0;
+const TEXT_PDF_FONT_SIZE: f32 = 11.0;
+const TEXT_PDF_LEADING: f32 = 14.0;
+/// Characters per line before wrapping
+const TEXT_PDF_LINE_WIDTH: usize = 90;
+
+/// Indent of each nesting level, e.g. of list items, in points
+const TEXT_PDF_INDENT: f32 = 18.0;
+const TEXT_PDF_CODE_FONT_SIZE: f32 = 10.0;
+/// Width of a Courier glyph as a fraction of the font size
+const COURIER_GLYPH_WIDTH: f32 = 0.6;
+
+/// How a line is set when text is rendered to PDF
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LineStyle {
+    Body,
+    /// Bold, sized by heading level (1 to 6)
+    Heading(u8),
+    /// Monospaced, keeping its whitespace
+    Code,
+}
+
+/// A line of text to render to PDF, before wrapping
+#[derive(Debug, Clone, PartialEq)]
+struct TextLine {
+    text: String,
+    style: LineStyle,
+    /// Nesting depth, e.g. of list items
+    indent: usize,
+}
+
+impl TextLine {
+    fn body(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            style: LineStyle::Body,
+            indent: 0,
+        }
+    }
+
+    /// Font resource name and size
+    fn font(&self) -> (&'static str, f32) {
+        match self.style {
+            LineStyle::Body => ("F1", TEXT_PDF_FONT_SIZE),
+            LineStyle::Heading(1) => ("F2", 20.0),
+            LineStyle::Heading(2) => ("F2", 16.0),
+            LineStyle::Heading(3) => ("F2", 13.0),
+            LineStyle::Heading(_) => ("F2", TEXT_PDF_FONT_SIZE),
+            LineStyle::Code => ("F3", TEXT_PDF_CODE_FONT_SIZE),
+        }
+    }
+
+    fn leading(&self) -> f32 {
+        self.font().1 / TEXT_PDF_FONT_SIZE * TEXT_PDF_LEADING
+    }
+
+    fn left(&self) -> f32 {
+        TEXT_PDF_MARGIN + self.indent as f32 * TEXT_PDF_INDENT
+    }
+
+    /// Split into lines that fit the page width. Code is cut at the width so its
+    /// spacing is kept; other text wraps at word boundaries.
+    fn wrap(&self) -> Vec<String> {
+        let (width, _) = TEXT_PDF_PAGE_SIZE;
+        let room = width - TEXT_PDF_MARGIN - self.left();
+        let size = self.font().1;
+        if self.style == LineStyle::Code {
+            let columns = (room / (size * COURIER_GLYPH_WIDTH)).max(1.0) as usize;
+            let chars: Vec<char> = self.text.chars().collect();
+            if chars.is_empty() {
+                return vec![String::new()];
+            }
+            return chars.chunks(columns).map(|c| c.iter().collect()).collect();
+        }
+        // TEXT_PDF_LINE_WIDTH body-size characters fill the full text width
+        let columns = TEXT_PDF_LINE_WIDTH as f32 * TEXT_PDF_FONT_SIZE / size * room
+            / (width - 2.0 * TEXT_PDF_MARGIN);
+        wrap_line(&self.text, (columns as usize).max(1))
+    }
+}
+
+/// Lay Markdown out as lines: headings and code blocks keep their style, list items
+/// are numbered or bulleted and indented by depth, and blocks are separated by a
+/// blank line
+fn markdown_to_lines(source: &str) -> Vec<TextLine> {
+    use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+    /// Move the pending block into `lines`, optionally followed by a blank separator
+    fn flush(
+        current: &mut String,
+        lines: &mut Vec<TextLine>,
+        style: LineStyle,
+        indent: usize,
+        gap: bool,
+    ) {
+        if !current.is_empty() {
+            let text = std::mem::take(current);
+            let line = |text: &str| TextLine {
+                text: text.to_string(),
+                style,
+                indent,
+            };
+            if style == LineStyle::Code {
+                lines.extend(text.lines().map(line));
+            } else {
+                lines.push(line(&text));
+            }
+        }
+        if gap && lines.last().is_some_and(|l| !l.text.is_empty()) {
+            lines.push(TextLine::body(""));
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut style = LineStyle::Body;
+    // Next number of each open list, `None` for bulleted lists
+    let mut lists: Vec<Option<u64>> = Vec::new();
+
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    for event in Parser::new_ext(source, options) {
+        // List items are indented one level less than the lists they are in
+        let indent = lists.len().saturating_sub(1);
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => style = LineStyle::Heading(level as u8),
+            Event::Start(Tag::CodeBlock(_)) => style = LineStyle::Code,
+            Event::Text(text) | Event::Code(text) => current.push_str(&text),
+            Event::SoftBreak => current.push(' '),
+            Event::HardBreak => flush(&mut current, &mut lines, style, indent, false),
+            Event::Start(Tag::List(first)) => {
+                // A nested list starts after its parent item's own text
+                flush(&mut current, &mut lines, style, indent, false);
+                lists.push(first);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                flush(&mut current, &mut lines, style, indent, lists.is_empty());
+            }
+            Event::Start(Tag::Item) => match lists.last_mut() {
+                Some(Some(number)) => {
+                    current.push_str(&format!("{}. ", number));
+                    *number += 1;
+                }
+                _ => current.push_str("- "),
+            },
+            Event::End(TagEnd::Item | TagEnd::TableHead | TagEnd::TableRow) => {
+                flush(&mut current, &mut lines, style, indent, false)
+            }
+            Event::End(TagEnd::TableCell) => current.push_str("  "),
+            Event::Rule => {
+                flush(&mut current, &mut lines, style, indent, false);
+                lines.push(TextLine::body("-".repeat(40)));
+                lines.push(TextLine::body(""));
+            }
+            Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock | TagEnd::Table,
+            ) => {
+                // Paragraphs inside list items stay with the item
+                let gap = lists.is_empty();
+                flush(&mut current, &mut lines, style, indent, gap);
+                style = LineStyle::Body;
+            }
+            _ => {}
+        }
+    }
+    flush(&mut current, &mut lines, style, 0, false);
+
+    lines
+}
+
+/// Word-wrap a line to `width` characters
+pub(super) fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let mut wrapped = Vec::new();
+    let mut current = String::new();
+
+    for word in line.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            wrapped.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    wrapped.push(current);
+
+    wrapped
+}
+
+/// Render lines as a paginated PDF in the standard Helvetica and Courier fonts
+fn text_lines_to_pdf(lines: &[TextLine]) -> Result<lopdf::Document, EditorError> {
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream, StringFormat};
+
+    let (width, height) = TEXT_PDF_PAGE_SIZE;
+    let page_height = height - 2.0 * TEXT_PDF_MARGIN;
+
+    // Lines placed on each page, with the distance from the top margin to their baseline
+    let mut pages: Vec<Vec<(&TextLine, String, f32)>> = vec![Vec::new()];
+    let mut used = 0.0;
+    for line in lines {
+        let leading = line.leading();
+        for text in line.wrap() {
+            let page = pages.last_mut().expect("there is always a page");
+            if used + leading > page_height && !page.is_empty() {
+                pages.push(Vec::new());
+                used = 0.0;
+            }
+            pages.last_mut().expect("there is always a page").push((line, text, used));
+            used += leading;
+        }
+    }
+
+    let mut doc = lopdf::Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font = |doc: &mut lopdf::Document, base_font: &str| {
+        doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => base_font,
+            "Encoding" => "WinAnsiEncoding",
+        })
+    };
+    let fonts = dictionary! {
+        "F1" => font(&mut doc, "Helvetica"),
+        "F2" => font(&mut doc, "Helvetica-Bold"),
+        "F3" => font(&mut doc, "Courier"),
+    };
+    let resources_id = doc.add_object(dictionary! { "Font" => fonts });
+
+    let mut kids = Vec::new();
+    for page in pages {
+        let mut operations = Vec::new();
+        for (line, text, offset) in page {
+            let (font, size) = line.font();
+            // Standard fonts only cover Latin-1; substitute anything else
+            let bytes: Vec<u8> = text
+                .chars()
+                .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+                .collect();
+            operations.extend([
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec![font.into(), size.into()]),
+                Operation::new(
+                    "Td",
+                    vec![line.left().into(), (height - TEXT_PDF_MARGIN - offset).into()],
+                ),
+                Operation::new("Tj", vec![Object::String(bytes, StringFormat::Literal)]),
+                Operation::new("ET", vec![]),
+            ]);
+        }
+
+        let content = Content { operations }.encode().map_err(pdf_error)?;
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content));
+        kids.push(Object::Reference(doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        })));
+    }
+
+    let count = kids.len() as i64;
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc.compress();
+
+    Ok(doc)
+}
+
+/// Result of combining several documents into one PDF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CombineReport {
+    /// Path of the merged PDF
+    pub output: String,
+    /// Inputs included in the output, in order
+    pub included: Vec<String>,
+    /// Inputs that were skipped
+    pub failed: Vec<CombineFailure>,
+}
+
+/// An input that could not be added to a combined PDF
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CombineFailure {
+    pub input: String,
+    pub error: String,
+}
+
+/// Converter to run when saving source text under a different format's extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SaveConversion {
+    MarkdownToPdf,
+}
+
+impl SaveConversion {
+    /// Pick a converter from the source format and the output path's extension.
+    ///
+    /// `None` means the text is written as is. Formats without a working converter
+    /// fail rather than writing source text under their extension.
+    fn for_output(source_ext: &str, output_path: &str) -> Result<Option<Self>, EditorError> {
+        let Some(target) = Path::new(output_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+        else {
+            return Ok(None);
+        };
+        match (source_ext, target.as_str()) {
+            // Plain text renders as Markdown without any markup
+            ("md" | "txt", "pdf") => Ok(Some(Self::MarkdownToPdf)),
+            ("md" | "txt", "docx") | ("tex", "pdf") => Err(EditorError::UnsupportedOperation(
+                format!("Saving .{} as .{}", source_ext, target),
+            )),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Write editor source text to `output_path`, converting it when the extension asks
+/// for another format (e.g. Markdown saved as `.pdf`)
+async fn save_source_as(
+    content: &str,
+    source_ext: &str,
+    output_path: &str,
+) -> Result<(), EditorError> {
+    let Some(conversion) = SaveConversion::for_output(source_ext, output_path)? else {
+        return tokio::fs::write(output_path, content)
+            .await
+            .map_err(|e| EditorError::IoError(e.to_string()));
+    };
+
+    // Converters read from a file, so stage the unsaved content first
+    let staged = tempfile::Builder::new()
+        .suffix(&format!(".{}", source_ext))
+        .tempfile()
+        .map_err(|e| EditorError::IoError(e.to_string()))?;
+    tokio::fs::write(staged.path(), content)
+        .await
+        .map_err(|e| EditorError::IoError(e.to_string()))?;
+    let input = staged.path().to_string_lossy();
+
+    match conversion {
+        SaveConversion::MarkdownToPdf => {
+            ConversionUtils::markdown_to_pdf(&input, output_path).await
+        }
+    }
+}
+
 /// Document conversion utilities
 pub struct ConversionUtils;
 
 impl ConversionUtils {
-    /// Convert Markdown to PDF
+    /// Convert Markdown to PDF, keeping headings, lists and code blocks distinct.
+    /// Fails with `ParseError` if the input is not UTF-8 text and `IoError` if the
+    /// output cannot be written.
     pub async fn markdown_to_pdf(input: &str, output: &str) -> Result<(), EditorError> {
         if !Path::new(input).exists() {
             return Err(EditorError::FileNotFound(input.to_string()));
         }
         tracing::info!("Converting {} to PDF: {}", input, output);
-        // TODO: Implement with pulldown-cmark + pdf generation
-        Ok(())
+
+        let bytes = tokio::fs::read(input)
+            .await
+            .map_err(|e| EditorError::IoError(e.to_string()))?;
+        let source = String::from_utf8(bytes)
+            .map_err(|e| EditorError::ParseError(format!("{}: {}", input, e)))?;
+        save_pdf(text_lines_to_pdf(&markdown_to_lines(&source))?, output)
     }
 
     /// Convert Markdown to DOCX
-    pub async fn markdown_to_docx(input: &str, output: &str) -> Result<(), EditorError> {
+    pub async fn markdown_to_docx(input: &str, _output: &str) -> Result<(), EditorError> {
         if !Path::new(input).exists() {
             return Err(EditorError::FileNotFound(input.to_string()));
         }
-        tracing::info!("Converting {} to DOCX: {}", input, output);
-        // TODO: Implement
-        Ok(())
+        // TODO: Implement; until then nothing is written to `output`
+        Err(EditorError::UnsupportedOperation(format!(
+            "Markdown to DOCX conversion of {}",
+            input
+        )))
     }
 
     /// Convert DOCX to PDF
-    pub async fn docx_to_pdf(input: &str, output: &str) -> Result<(), EditorError> {
+    pub async fn docx_to_pdf(input: &str, _output: &str) -> Result<(), EditorError> {
         if !Path::new(input).exists() {
             return Err(EditorError::FileNotFound(input.to_string()));
         }
-        tracing::info!("Converting {} to PDF: {}", input, output);
-        // TODO: Implement
-        Ok(())
+        // TODO: Implement; until then nothing is written to `output`
+        Err(EditorError::UnsupportedOperation(format!(
+            "DOCX to PDF conversion of {}",
+            input
+        )))
     }
 
     /// Convert LaTeX to PDF
-    pub async fn latex_to_pdf(input: &str, output: &str) -> Result<(), EditorError> {
+    pub async fn latex_to_pdf(input: &str, _output: &str) -> Result<(), EditorError> {
         if !Path::new(input).exists() {
             return Err(EditorError::FileNotFound(input.to_string()));
         }
-        tracing::info!("Converting {} to PDF: {}", input, output);
-        // TODO: Shell out to pdflatex or use tectonic
-        Ok(())
+        // TODO: Shell out to pdflatex or use tectonic; until then nothing is written to `output`
+        Err(EditorError::UnsupportedOperation(format!(
+            "LaTeX to PDF conversion of {}",
+            input
+        )))
     }
 
     /// Convert TXT to Markdown
@@ -1461,12 +3046,98 @@ impl ConversionUtils {
             .map_err(|e| EditorError::IoError(e.to_string()))?;
         Ok(())
     }
+
+    /// Convert each input to PDF and merge them, in order, into `output`.
+    ///
+    /// Inputs that fail to convert are skipped and listed in the report; the call
+    /// only fails if nothing could be converted or the merged file can't be written.
+    pub async fn combine_to_pdf(
+        inputs: Vec<String>,
+        output: String,
+    ) -> Result<CombineReport, EditorError> {
+        let scratch = tempfile::tempdir().map_err(|e| EditorError::IoError(e.to_string()))?;
+        let mut docs = Vec::new();
+        let mut report = CombineReport {
+            output: output.clone(),
+            included: Vec::new(),
+            failed: Vec::new(),
+        };
+
+        for (index, input) in inputs.into_iter().enumerate() {
+            let target = scratch.path().join(format!("{}.pdf", index));
+            match Self::convert_for_combine(&input, &target).await {
+                Ok(doc) => {
+                    docs.push(doc);
+                    report.included.push(input);
+                }
+                Err(e) => {
+                    tracing::warn!("Skipping {} when combining to PDF: {}", input, e);
+                    report.failed.push(CombineFailure {
+                        input,
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        if docs.is_empty() {
+            return Err(EditorError::InvalidDocument(
+                "None of the inputs could be converted to PDF".to_string(),
+            ));
+        }
+
+        tracing::info!("Combining {} documents into {}", docs.len(), output);
+        save_pdf(merge_pdf_documents(docs)?, &output)?;
+        Ok(report)
+    }
+
+    /// Load `input` as a PDF, converting it into `target` first if needed
+    async fn convert_for_combine(
+        input: &str,
+        target: &Path,
+    ) -> Result<lopdf::Document, EditorError> {
+        if !Path::new(input).exists() {
+            return Err(EditorError::FileNotFound(input.to_string()));
+        }
+
+        let extension = Path::new(input)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let target_str = target.to_string_lossy().to_string();
+
+        match extension.as_str() {
+            "pdf" => return load_pdf(input),
+            "md" | "markdown" => Self::markdown_to_pdf(input, &target_str).await?,
+            "docx" => Self::docx_to_pdf(input, &target_str).await?,
+            "tex" | "latex" => Self::latex_to_pdf(input, &target_str).await?,
+            _ => {
+                return Err(EditorError::UnsupportedOperation(format!(
+                    "Cannot convert .{} files to PDF",
+                    extension
+                )))
+            }
+        }
+
+        load_pdf(&target_str)
+    }
 }
 
 // ============================================================================
 // Operation Info for Frontend
 // ============================================================================
 
+/// Characters of user text shown in an operation description
+const PREVIEW_CHARS: usize = 20;
+
+/// Shorten text for a description, cutting on characters rather than bytes
+fn preview(text: &str) -> String {
+    match text.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
 /// Serializable version of edit operation for frontend
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EditOperationInfo {
@@ -1493,8 +3164,7 @@ impl EditOperationInfo {
     fn from_pdf_operation(op: &PDFEditOperation) -> Self {
         let (op_type, page, desc) = match op {
             PDFEditOperation::AddText { page, text, .. } => {
-                let preview = if text.len() > 20 { &text[..20] } else { text };
-                ("add_text", Some(*page), format!("Add text: {}...", preview))
+                ("add_text", Some(*page), format!("Add text: {}", preview(text)))
             }
             PDFEditOperation::AddImage { page, .. } => {
                 ("add_image", Some(*page), "Add image".to_string())
@@ -1503,8 +3173,7 @@ impl EditOperationInfo {
                 ("add_highlight", Some(*page), "Add highlight".to_string())
             }
             PDFEditOperation::AddAnnotation { page, content, .. } => {
-                let preview = if content.len() > 20 { &content[..20] } else { content };
-                ("add_annotation", Some(*page), format!("Add note: {}...", preview))
+                ("add_annotation", Some(*page), format!("Add note: {}", preview(content)))
             }
             PDFEditOperation::DeletePage { page } => {
                 ("delete_page", Some(*page), format!("Delete page {}", page))
@@ -1554,15 +3223,13 @@ impl EditOperationInfo {
     fn from_text_operation(op: &TextEditOperation) -> Self {
         let (op_type, desc) = match op {
             TextEditOperation::Common(CommonEditOperation::InsertText { text, .. }) => {
-                let preview = if text.len() > 20 { &text[..20] } else { text };
-                ("insert_text", format!("Insert: {}...", preview))
+                ("insert_text", format!("Insert: {}", preview(text)))
             }
             TextEditOperation::Common(CommonEditOperation::DeleteText { .. }) => {
                 ("delete_text", "Delete text".to_string())
             }
             TextEditOperation::Common(CommonEditOperation::ReplaceText { new_text, .. }) => {
-                let preview = if new_text.len() > 20 { &new_text[..20] } else { new_text };
-                ("replace_text", format!("Replace with: {}...", preview))
+                ("replace_text", format!("Replace with: {}", preview(new_text)))
             }
             TextEditOperation::InsertHeading { level, text, .. } => {
                 ("insert_heading", format!("H{}: {}", level, text))
@@ -1666,3 +3333,894 @@ impl EditOperationInfo {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_render_page_cache_hit_skips_rendering() {
+        let dir = tempfile::tempdir().unwrap();
+        let pdf = dir.path().join("doc.pdf");
+        let markdown = dir.path().join("doc.md");
+        std::fs::write(&markdown, "# Title\n\nOne page of text.").unwrap();
+        ConversionUtils::markdown_to_pdf(markdown.to_str().unwrap(), pdf.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let pdf = pdf.to_str().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let renders = std::cell::Cell::new(0);
+        let fake_render = |base: &Path| {
+            renders.set(renders.get() + 1);
+            let path = base.with_extension("png");
+            std::fs::write(&path, b"png").unwrap();
+            Ok(path)
+        };
+
+        let first = render_page_cached(pdf, 1, 150, "png", &cache_dir, fake_render)
+            .unwrap();
+        assert!(!first.cached);
+        assert!(Path::new(&first.path).exists());
+
+        let second = render_page_cached(pdf, 1, 150, "png", &cache_dir, fake_render)
+            .unwrap();
+        assert!(second.cached);
+        assert_eq!(second.path, first.path);
+        assert_eq!(renders.get(), 1);
+
+        // A different DPI is a different cache entry
+        let other = render_page_cached(pdf, 1, 300, "png", &cache_dir, fake_render)
+            .unwrap();
+        assert!(!other.cached);
+        assert!(matches!(
+            render_page_cached(pdf, 2, 150, "png", &cache_dir, fake_render),
+            Err(EditorError::PageOutOfRange(2))
+        ));
+    }
+
+    #[test]
+    fn test_page_cache_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = std::time::SystemTime::now();
+        for (age, name) in [(30, "old.png"), (20, "used.png"), (10, "new.png")] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, [0u8; 100]).unwrap();
+            let file = std::fs::File::options().append(true).open(&path).unwrap();
+            file.set_modified(now - std::time::Duration::from_secs(age)).unwrap();
+        }
+
+        evict_page_cache(dir.path(), 250).unwrap();
+        assert!(!dir.path().join("old.png").exists());
+        assert!(dir.path().join("used.png").exists());
+        assert!(dir.path().join("new.png").exists());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs pdftoppm from Poppler"]
+    async fn test_render_single_pdf_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let pdf = dir.path().join("doc.pdf");
+        let markdown = dir.path().join("doc.md");
+        std::fs::write(&markdown, "# Title\n\nOne page of text.").unwrap();
+        ConversionUtils::markdown_to_pdf(markdown.to_str().unwrap(), pdf.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let rendered = PDFUtils::render_page(pdf.to_str().unwrap(), 1, 72, ImageFormat::Png)
+            .await
+            .unwrap();
+        assert!(rendered.path.ends_with(".png"));
+        assert!(std::fs::metadata(&rendered.path).unwrap().len() > 0);
+
+        let again = PDFUtils::render_page(pdf.to_str().unwrap(), 1, 72, ImageFormat::Png)
+            .await
+            .unwrap();
+        assert!(again.cached);
+        assert_eq!(again.path, rendered.path);
+    }
+
+    #[tokio::test]
+    async fn test_set_metadata_writes_info_dictionary() {
+        let dir = tempfile::tempdir().unwrap();
+        let pdf = dir.path().join("paper.pdf");
+        let mut doc = text_lines_to_pdf(&[TextLine::body("Attention is all you need")]).unwrap();
+        doc.save(&pdf).unwrap();
+        let pdf = pdf.to_str().unwrap();
+
+        let update = PdfMetadataUpdate {
+            title: Some("Attention Is All You Need".to_string()),
+            authors: Some(vec!["Vaswani".to_string(), "Shazeer".to_string()]),
+            subject: Some("Sequence transduction".to_string()),
+            keywords: Some(vec!["transformer".to_string(), "çà".to_string()]),
+        };
+        PDFUtils::set_metadata(pdf, pdf, &update).await.unwrap();
+
+        // Only the subject changes on a second edit
+        let subject_only = PdfMetadataUpdate {
+            subject: Some("Machine translation".to_string()),
+            ..Default::default()
+        };
+        PDFUtils::set_metadata(pdf, pdf, &subject_only).await.unwrap();
+
+        let saved = lopdf::Document::load(pdf).unwrap();
+        let info_id = saved.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let info = saved.get_dictionary(info_id).unwrap();
+        let field = |key: &[u8]| lopdf::decode_text_string(info.get(key).unwrap()).unwrap();
+
+        assert_eq!(field(b"Title"), "Attention Is All You Need");
+        assert_eq!(field(b"Author"), "Vaswani; Shazeer");
+        assert_eq!(field(b"Subject"), "Machine translation");
+        assert_eq!(field(b"Keywords"), "transformer, çà");
+        assert!(field(b"ModDate").starts_with("D:"));
+    }
+
+    #[test]
+    fn test_incremental_preview_rerenders_only_edited_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "# Title\n\nFirst *paragraph*.\n\nSecond one.\n").unwrap();
+        let mut editor = TextEditor::new(path.to_str().unwrap()).unwrap();
+
+        let first = editor.render_markdown_preview_incremental();
+        assert_eq!(first.order.len(), 3);
+        assert_eq!(first.updates.len(), 3);
+        assert_eq!(first.updates[0].html, "<h1>Title</h1>\n");
+        assert_eq!(first.updates[1].html, "<p>First <em>paragraph</em>.</p>\n");
+
+        let unchanged = editor.render_markdown_preview_incremental();
+        assert_eq!(unchanged.order, first.order);
+        assert!(unchanged.updates.is_empty());
+
+        editor.set_content("# Title\n\nFirst *paragraph*.\n\nSecond one, edited.\n".to_string());
+        let edited = editor.render_markdown_preview_incremental();
+        assert_eq!(edited.order[..2], first.order[..2]);
+        assert_ne!(edited.order[2], first.order[2]);
+        assert_eq!(
+            edited.updates,
+            vec![PreviewBlock {
+                block_id: edited.order[2].clone(),
+                html: "<p>Second one, edited.</p>\n".to_string(),
+            }]
+        );
+
+        editor.reset_preview_cache();
+        assert_eq!(editor.render_markdown_preview_incremental().updates.len(), 3);
+    }
+
+    #[test]
+    fn test_preview_strips_scripts_and_handlers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(
+            &path,
+            "Some **bold** text <script>alert('x')</script>\n\n\
+             <a href=\"https://example.com\" onclick=\"steal()\">a link</a> \
+             <a href=\"javascript:steal()\">bad</a> $x < y$",
+        )
+        .unwrap();
+        let mut editor = TextEditor::new(path.to_str().unwrap()).unwrap();
+
+        let html = editor.render_markdown_preview();
+        assert!(!html.contains("<script") && !html.contains("alert"), "{}", html);
+        assert!(!html.contains("onclick") && !html.contains("javascript:"), "{}", html);
+        assert!(html.contains("<strong>bold"), "{}", html);
+        assert!(html.contains("<a href=\"https://example.com\""), "{}", html);
+        assert!(html.contains("<span class=\"math math-inline\">"), "{}", html);
+
+        let blocks = editor.render_markdown_preview_incremental();
+        assert!(blocks.updates.iter().all(|b| !b.html.contains("<script")));
+
+        // Narrowing the allowlist applies to later renders
+        let mut allowlist = HtmlAllowlist::default();
+        allowlist.tags.retain(|t| t != "strong");
+        editor.set_html_allowlist(allowlist).unwrap();
+        assert!(!editor.render_markdown_preview().contains("<strong>"));
+        let blocks = editor.render_markdown_preview_incremental();
+        assert!(blocks.updates[0].html.starts_with("<p>Some bold text"));
+    }
+
+    #[test]
+    fn test_allowlist_entries_ammonia_rejects_are_refused() {
+        let with = |edit: fn(&mut HtmlAllowlist)| {
+            let mut allowlist = HtmlAllowlist::default();
+            edit(&mut allowlist);
+            allowlist
+        };
+        let invalid = [
+            with(|a| a.attributes.push("rel".to_string())),
+            with(|a| a.tags.push("Script".to_string())),
+            with(|a| {
+                a.tag_attributes.insert("style".to_string(), vec!["media".to_string()]);
+            }),
+            with(|a| a.tags.push("animate".to_string())),
+        ];
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "[link](https://example.com)").unwrap();
+        let mut editor = TextEditor::new(path.to_str().unwrap()).unwrap();
+        for allowlist in invalid {
+            assert!(allowlist.validate().is_err(), "{:?}", allowlist);
+            assert!(editor.set_html_allowlist(allowlist.clone()).is_err());
+            // Sanitizing with it directly skips the bad entries rather than panicking
+            let html = sanitize_html("<a href=\"https://example.com\" rel=\"x\">a</a>", &allowlist);
+            assert!(html.starts_with("<a href=\"https://example.com\""), "{}", html);
+        }
+        assert!(editor.render_markdown_preview().contains("<a href="));
+    }
+
+    #[test]
+    fn test_preview_renders_well_formed_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(
+            &path,
+            "# Setup\n\
+             - Install **Rust with *nested* emphasis**\n\
+             - See [the book](https://doc.rust-lang.org/book/)\n\n\
+             ```rust\nfn main() { println!(\"<hi>\"); }\n```\n\n\
+             | a | b |\n|---|---|\n| 1 | 2 |\n",
+        )
+        .unwrap();
+        let editor = TextEditor::new(path.to_str().unwrap()).unwrap();
+
+        let html = editor.render_markdown_preview();
+        assert!(html.starts_with("<div class=\"markdown-preview\"><h1>Setup</h1>"), "{}", html);
+        assert!(html.contains("<strong>Rust with <em>nested</em> emphasis</strong>"), "{}", html);
+        assert!(html.contains("<a href=\"https://doc.rust-lang.org/book/\""), "{}", html);
+        assert!(
+            html.contains("<pre><code class=\"language-rust\">fn main() { println!(\"&lt;hi&gt;"),
+            "{}",
+            html
+        );
+        assert!(html.contains("<td>1</td>"), "{}", html);
+
+        // Every opened tag is closed, innermost first
+        let tag = regex::Regex::new(r"<(/?)([a-z0-9]+)[^>]*>").unwrap();
+        let mut open = Vec::new();
+        for caps in tag.captures_iter(&html) {
+            let name = caps[2].to_string();
+            if &caps[1] == "/" {
+                assert_eq!(open.pop(), Some(name), "{}", html);
+            } else {
+                open.push(name);
+            }
+        }
+        assert!(open.is_empty(), "unclosed {:?} in {}", open, html);
+    }
+
+    #[tokio::test]
+    async fn test_merged_buffer_splits_back_to_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<String> = [("a.md", "# A\n\nFirst.\n"), ("b.md", "No newline"), ("c.md", "")]
+            .iter()
+            .map(|(name, text)| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, text).unwrap();
+                path.to_str().unwrap().to_string()
+            })
+            .collect();
+
+        let mut editor = TextEditor::open_merged(&paths).unwrap();
+        assert!(editor.is_markdown());
+        let content = editor.get_content().to_string();
+        assert!(content.contains("First.") && content.contains("No newline"));
+        assert!(content.starts_with(&format!("<!-- source: {} -->\n# A", paths[0])));
+
+        let ranges = editor.source_ranges().unwrap();
+        let parts: Vec<_> = ranges.iter().map(|r| &content[r.start..r.end]).collect();
+        assert_eq!(parts, ["# A\n\nFirst.\n", "No newline", ""]);
+        assert_eq!(ranges[1].path, paths[1]);
+
+        // Edits to one part land in that file only
+        editor.set_content(content.replace("No newline", "Now edited\n"));
+        editor.save(false).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&paths[0]).unwrap(), "# A\n\nFirst.\n");
+        assert_eq!(std::fs::read_to_string(&paths[1]).unwrap(), "Now edited\n");
+        assert_eq!(std::fs::read_to_string(&paths[2]).unwrap(), "");
+        assert!(!editor.has_unsaved_changes());
+
+        let without_marker = editor.get_content().replace(&merge_marker(&paths[1]), "");
+        editor.set_content(without_marker);
+        assert!(editor.source_ranges().is_err());
+        assert!(editor.save(false).await.is_err());
+    }
+
+    #[test]
+    fn test_markdown_blocks_keep_fences_and_display_math_together() {
+        let blocks = split_markdown_blocks("```\na\n\nb\n```\n\n$$\nx\n\ny\n$$\n\nSame\n\nSame");
+        assert_eq!(blocks, ["```\na\n\nb\n```", "$$\nx\n\ny\n$$", "Same", "Same"]);
+
+        let mut seen = HashMap::new();
+        let ids: Vec<_> = blocks.iter().map(|b| preview_block_id(b, &mut seen)).collect();
+        assert_ne!(ids[2], ids[3]);
+        assert!(ids[3].starts_with(&ids[2]));
+    }
+
+    #[test]
+    fn test_operation_preview_truncates_multibyte_text() {
+        let position = TextPosition { line: 0, column: 0 };
+        let cjk = "注意力机制是变换器模型的核心组成部分之一，它允许模型关注输入的不同位置";
+        let emoji = "📚📖✍️ notes on chapter three 🎉🎉";
+
+        let info = EditOperationInfo::from_operation(&EditOperation::Text(
+            TextEditOperation::Common(CommonEditOperation::InsertText {
+                position,
+                text: cjk.to_string(),
+            }),
+        ));
+        assert_eq!(info.description, format!("Insert: {}…", &cjk[..60]));
+
+        let info = EditOperationInfo::from_operation(&EditOperation::Pdf(
+            PDFEditOperation::AddAnnotation {
+                page: 1,
+                x: 0.0,
+                y: 0.0,
+                content: emoji.to_string(),
+                author: None,
+            },
+        ));
+        assert_eq!(
+            info.description,
+            format!("Add note: {}…", emoji.chars().take(20).collect::<String>())
+        );
+
+        // Short text is shown whole
+        assert_eq!(preview("短い"), "短い");
+    }
+
+    #[tokio::test]
+    async fn test_markdown_saved_as_pdf_is_converted() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("notes.md");
+        std::fs::write(&source, "# Notes\n\nSaved from the editor.").unwrap();
+        let mut editor = TextEditor::new(source.to_str().unwrap()).unwrap();
+        editor.set_content("# Notes\n\nEdited before export.".to_string());
+
+        let output = dir.path().join("notes.pdf");
+        editor.save_as(output.to_str().unwrap()).await.unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        assert!(bytes.starts_with(b"%PDF-"));
+        let pdf = lopdf::Document::load(&output).unwrap();
+        assert!(pdf.extract_text(&[1]).unwrap().contains("Edited before export."));
+
+        // Text-format targets are still written verbatim
+        let copy = dir.path().join("copy.markdown");
+        editor.save_as(copy.to_str().unwrap()).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&copy).unwrap(),
+            "# Notes\n\nEdited before export."
+        );
+    }
+
+    #[test]
+    fn test_save_conversion_inferred_from_extension() {
+        assert_eq!(
+            SaveConversion::for_output("md", "out/Notes.PDF").unwrap(),
+            Some(SaveConversion::MarkdownToPdf)
+        );
+        assert!(SaveConversion::for_output("tex", "paper.pdf").is_err());
+        assert_eq!(SaveConversion::for_output("md", "notes.txt").unwrap(), None);
+        assert_eq!(SaveConversion::for_output("tex", "paper.tex").unwrap(), None);
+        assert_eq!(SaveConversion::for_output("md", "no_extension").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_unimplemented_converters_fail_without_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("paper.tex");
+        std::fs::write(&input, "\\documentclass{article}").unwrap();
+        let input = input.to_str().unwrap();
+        let output = dir.path().join("paper.out");
+        let output_str = output.to_str().unwrap();
+
+        for result in [
+            ConversionUtils::markdown_to_docx(input, output_str).await,
+            ConversionUtils::docx_to_pdf(input, output_str).await,
+            ConversionUtils::latex_to_pdf(input, output_str).await,
+        ] {
+            assert!(matches!(result, Err(EditorError::UnsupportedOperation(_))));
+        }
+        assert!(!output.exists());
+    }
+
+    #[tokio::test]
+    async fn test_save_as_docx_fails_without_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("notes.md");
+        std::fs::write(&source, "# Notes").unwrap();
+        let editor = TextEditor::new(source.to_str().unwrap()).unwrap();
+
+        let output = dir.path().join("notes.docx");
+        let result = editor.save_as(output.to_str().unwrap()).await;
+        assert!(matches!(result, Err(EditorError::UnsupportedOperation(_))));
+        assert!(!output.exists());
+    }
+
+    const MARKDOWN_FIXTURE: &str = "# Setup\n\nInstall the tools:\n\n\
+        1. Rust\n   - rustup\n2. Node\n\n```sh\ncargo build\n    --release\n```\n";
+
+    #[test]
+    fn test_markdown_lines_keep_structure() {
+        let line = |text: &str, style, indent| TextLine {
+            text: text.to_string(),
+            style,
+            indent,
+        };
+        assert_eq!(
+            markdown_to_lines(MARKDOWN_FIXTURE),
+            [
+                line("Setup", LineStyle::Heading(1), 0),
+                TextLine::body(""),
+                TextLine::body("Install the tools:"),
+                TextLine::body(""),
+                line("1. Rust", LineStyle::Body, 0),
+                line("- rustup", LineStyle::Body, 1),
+                line("2. Node", LineStyle::Body, 0),
+                TextLine::body(""),
+                line("cargo build", LineStyle::Code, 0),
+                line("    --release", LineStyle::Code, 0),
+                TextLine::body(""),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_markdown_to_pdf_writes_pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let markdown = dir.path().join("setup.md");
+        std::fs::write(&markdown, MARKDOWN_FIXTURE).unwrap();
+        let markdown = markdown.to_str().unwrap();
+
+        let pdf = dir.path().join("setup.pdf");
+        ConversionUtils::markdown_to_pdf(markdown, pdf.to_str().unwrap()).await.unwrap();
+        assert!(std::fs::read(&pdf).unwrap().starts_with(b"%PDF-"));
+        let text = lopdf::Document::load(&pdf).unwrap().extract_text(&[1]).unwrap();
+        for expected in ["Setup", "1. Rust", "- rustup", "cargo build"] {
+            assert!(text.contains(expected), "{:?} missing from {:?}", expected, text);
+        }
+
+        // The output's directory is a file, so it cannot be written
+        let blocked = dir.path().join("setup.md").join("setup.pdf");
+        assert!(matches!(
+            ConversionUtils::markdown_to_pdf(markdown, blocked.to_str().unwrap()).await,
+            Err(EditorError::IoError(_))
+        ));
+
+        let binary = dir.path().join("binary.md");
+        std::fs::write(&binary, [0xff, 0xfe, 0x00, 0x80]).unwrap();
+        assert!(matches!(
+            ConversionUtils::markdown_to_pdf(binary.to_str().unwrap(), pdf.to_str().unwrap()).await,
+            Err(EditorError::ParseError(_))
+        ));
+    }
+
+    /// Save a PDF of `pages` full pages of text
+    fn pdf_with_pages(dir: &Path, pages: usize) -> String {
+        let path = dir.join("pages.pdf");
+        let (_, height) = TEXT_PDF_PAGE_SIZE;
+        let lines_per_page = ((height - 2.0 * TEXT_PDF_MARGIN) / TEXT_PDF_LEADING) as usize;
+        let lines = vec![TextLine::body("line"); lines_per_page * pages];
+        let mut doc = text_lines_to_pdf(&lines).unwrap();
+        assert_eq!(doc.get_pages().len(), pages);
+        doc.save(&path).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_out_of_range_page_rejected_when_added() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut editor = PDFEditor::new(&pdf_with_pages(dir.path(), 3)).unwrap();
+
+        let far = PDFEditOperation::AddText {
+            page: 999,
+            x: 72.0,
+            y: 700.0,
+            text: "Note".to_string(),
+            font_size: 12.0,
+            font_family: "Helvetica".to_string(),
+            color: "#000000".to_string(),
+        };
+        assert!(matches!(editor.add_operation(far), Err(EditorError::PageOutOfRange(999))));
+        assert!(matches!(
+            editor.add_operation(PDFEditOperation::RotatePage { page: 0, degrees: 90 }),
+            Err(EditorError::PageOutOfRange(0))
+        ));
+        assert!(matches!(
+            editor.add_operation(PDFEditOperation::AddWatermark {
+                text: "DRAFT".to_string(),
+                font_size: 48.0,
+                color: "#CCCCCC".to_string(),
+                opacity: 0.3,
+                position: WatermarkPosition::Diagonal,
+                pages: Some(vec![1, 4]),
+            }),
+            Err(EditorError::PageOutOfRange(4))
+        ));
+        assert!(editor.get_operations().is_empty());
+        assert!(!editor.has_unsaved_changes());
+
+        editor.add_operation(PDFEditOperation::RotatePage { page: 3, degrees: 90 }).unwrap();
+        assert_eq!(editor.get_operations().len(), 1);
+    }
+
+    #[test]
+    fn test_page_range_follows_queued_inserts_and_deletes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut editor = PDFEditor::new(&pdf_with_pages(dir.path(), 2)).unwrap();
+
+        editor.add_operation(PDFEditOperation::DeletePage { page: 2 }).unwrap();
+        assert!(matches!(
+            editor.add_operation(PDFEditOperation::RotatePage { page: 2, degrees: 90 }),
+            Err(EditorError::PageOutOfRange(2))
+        ));
+
+        let blank = |after_page| PDFEditOperation::InsertPage {
+            after_page,
+            width: 612.0,
+            height: 792.0,
+        };
+        assert!(matches!(editor.add_operation(blank(2)), Err(EditorError::PageOutOfRange(2))));
+        // A whole batch is rejected when a later operation is out of range
+        assert!(editor
+            .add_operations(vec![blank(0), PDFEditOperation::DeletePage { page: 3 }])
+            .is_err());
+        assert_eq!(editor.get_operations().len(), 1);
+
+        editor.add_operations(vec![blank(1), PDFEditOperation::DeletePage { page: 2 }]).unwrap();
+        assert_eq!(editor.get_operations().len(), 3);
+    }
+
+    #[test]
+    fn test_page_count_and_size_read_from_pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut editor = PDFEditor::new(&pdf_with_pages(dir.path(), 2)).unwrap();
+
+        assert_eq!(editor.page_count().unwrap(), 2);
+        assert_eq!(editor.page_size(2).unwrap(), (612.0, 792.0));
+        assert!(matches!(editor.page_size(3), Err(EditorError::PageOutOfRange(3))));
+        assert!(matches!(editor.page_size(0), Err(EditorError::PageOutOfRange(0))));
+    }
+
+    #[test]
+    fn test_page_size_inherited_from_page_tree() {
+        use lopdf::Object;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = pdf_with_pages(dir.path(), 1);
+        let mut doc = lopdf::Document::load(&path).unwrap();
+        let page_id = doc.get_pages()[&1];
+        let page = doc.get_dictionary_mut(page_id).unwrap();
+        page.remove(b"MediaBox");
+        let parent = page.get(b"Parent").unwrap().as_reference().unwrap();
+        let a4: Vec<Object> = vec![0.into(), 0.into(), 595.0.into(), 842.0.into()];
+        doc.get_dictionary_mut(parent).unwrap().set("MediaBox", a4);
+        doc.save(&path).unwrap();
+
+        let mut editor = PDFEditor::new(&path).unwrap();
+        assert_eq!(editor.page_count().unwrap(), 1);
+        assert_eq!(editor.page_size(1).unwrap(), (595.0, 842.0));
+
+        // Sizes are cached until the editor saves
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(editor.page_size(1).unwrap(), (595.0, 842.0));
+    }
+
+    #[tokio::test]
+    async fn test_annotations_flattened_into_page_content() {
+        use lopdf::{dictionary, Object, Stream};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = pdf_with_pages(dir.path(), 2);
+        let mut doc = lopdf::Document::load(&path).unwrap();
+        let appearance = doc.add_object(Stream::new(
+            dictionary! { "BBox" => vec![0.into(), 0.into(), 50.into(), 20.into()] },
+            b"0 0 1 RG 0 0 50 20 re S".to_vec(),
+        ));
+        let annots: Vec<Object> = vec![
+            dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Highlight",
+                "Rect" => vec![72.into(), 700.into(), 200.into(), 712.into()],
+                "QuadPoints" => [72, 712, 200, 712, 72, 700, 200, 700].map(Object::from).to_vec(),
+                "C" => vec![1.into(), 1.into(), 0.into()],
+            }
+            .into(),
+            doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Square",
+                "Rect" => vec![300.into(), 500.into(), 400.into(), 540.into()],
+                "AP" => dictionary! { "N" => appearance },
+            })
+            .into(),
+            doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Popup",
+                "Rect" => vec![300.into(), 600.into(), 400.into(), 650.into()],
+            })
+            .into(),
+        ];
+        let page_id = doc.get_pages()[&1];
+        doc.get_dictionary_mut(page_id).unwrap().set("Annots", annots);
+        doc.save(&path).unwrap();
+
+        let mut editor = PDFEditor::new(&path).unwrap();
+        editor
+            .add_operations(vec![
+                PDFEditOperation::AddAnnotation {
+                    page: 1,
+                    x: 72.0,
+                    y: 400.0,
+                    content: "Check this proof".to_string(),
+                    author: None,
+                },
+                PDFEditOperation::AddHighlight {
+                    page: 2,
+                    x: 72.0,
+                    y: 650.0,
+                    width: 100.0,
+                    height: 12.0,
+                    color: "#0000FF".to_string(),
+                },
+            ])
+            .unwrap();
+
+        let output = dir.path().join("flat.pdf");
+        assert_eq!(editor.flatten_annotations(output.to_str().unwrap()).unwrap(), 4);
+
+        let flat = lopdf::Document::load(&output).unwrap();
+        let annotation_objects = flat.objects.values().filter(|o| {
+            o.as_dict()
+                .is_ok_and(|d| d.get(b"Type").and_then(Object::as_name).ok() == Some(b"Annot"))
+        });
+        assert_eq!(annotation_objects.count(), 0);
+
+        let pages = flat.get_pages();
+        let content = |page: u32| {
+            assert!(!flat.get_dictionary(pages[&page]).unwrap().has(b"Annots"));
+            String::from_utf8_lossy(&flat.get_page_content(pages[&page]).unwrap()).into_owned()
+        };
+        let first = content(1);
+        assert!(first.contains("1 1 0 rg\n72 700 128 12 re\nf"));
+        assert!(first.contains("/FlatForm0 Do"));
+        assert!(first.contains("(Check this proof) Tj"));
+        assert!(content(2).contains("0 0 1 rg\n72 650 100 12 re\nf"));
+        // The page's own text is still drawn
+        assert!(flat.extract_text(&[1]).unwrap().contains("line"));
+
+        // Saving flattens too when the editor is configured to, after applying the
+        // other queued operations
+        editor.set_config(EditorConfig {
+            flatten_annotations: true,
+            ..EditorConfig::default()
+        });
+        editor
+            .add_operation(PDFEditOperation::AddText {
+                page: 2,
+                x: 72.0,
+                y: 100.0,
+                text: "Appendix".to_string(),
+                font_size: 12.0,
+                font_family: "Helvetica".to_string(),
+                color: "#000000".to_string(),
+            })
+            .unwrap();
+        let saved = dir.path().join("saved.pdf");
+        editor.save_as(saved.to_str().unwrap()).await.unwrap();
+        let saved = lopdf::Document::load(&saved).unwrap();
+        let pages = saved.get_pages();
+        assert!(saved.get_page_annotations(pages[&1]).unwrap().is_empty());
+        let second = String::from_utf8_lossy(&saved.get_page_content(pages[&2]).unwrap())
+            .into_owned();
+        assert!(second.contains("(Appendix) Tj"));
+        assert!(second.contains("0 0 1 rg\n72 650 100 12 re\nf"));
+    }
+
+    #[tokio::test]
+    async fn test_saved_highlights_and_notes_stay_annotations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = pdf_with_pages(dir.path(), 2);
+        let mut editor = PDFEditor::new(&path).unwrap();
+        editor
+            .add_operations(vec![
+                PDFEditOperation::AddHighlight {
+                    page: 1,
+                    x: 72.0,
+                    y: 650.0,
+                    width: 100.0,
+                    height: 12.0,
+                    color: "#0000FF".to_string(),
+                },
+                PDFEditOperation::AddAnnotation {
+                    page: 1,
+                    x: 72.0,
+                    y: 400.0,
+                    content: "Check this proof".to_string(),
+                    author: Some("Reviewer".to_string()),
+                },
+                PDFEditOperation::AddHighlight {
+                    page: 2,
+                    x: 72.0,
+                    y: 650.0,
+                    width: 100.0,
+                    height: 12.0,
+                    color: "#FFFF00".to_string(),
+                },
+                PDFEditOperation::DeletePage { page: 2 },
+            ])
+            .unwrap();
+
+        let saved = dir.path().join("saved.pdf");
+        editor.save_as(saved.to_str().unwrap()).await.unwrap();
+        let saved = lopdf::Document::load(&saved).unwrap();
+        let page_id = saved.get_pages()[&1];
+        let annotations = saved.get_page_annotations(page_id).unwrap();
+        let field = |i: usize, key: &[u8]| annotations[i].get(key).unwrap().clone();
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(field(0, b"Subtype").as_name().unwrap(), b"Highlight");
+        let quads: Vec<f32> = field(0, b"QuadPoints")
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| n.as_float().unwrap())
+            .collect();
+        assert_eq!(quads, [72.0, 662.0, 172.0, 662.0, 72.0, 650.0, 172.0, 650.0]);
+        assert_eq!(field(1, b"Subtype").as_name().unwrap(), b"Text");
+        assert_eq!(lopdf::decode_text_string(&field(1, b"Contents")).unwrap(), "Check this proof");
+        assert_eq!(lopdf::decode_text_string(&field(1, b"T")).unwrap(), "Reviewer");
+        // Nothing was drawn into the page itself
+        let content = String::from_utf8_lossy(&saved.get_page_content(page_id).unwrap())
+            .into_owned();
+        assert!(!content.contains("re\nf"), "{}", content);
+
+        // Marks on the deleted page are not counted as flattened
+        let flat = dir.path().join("flat.pdf");
+        assert_eq!(editor.flatten_annotations(flat.to_str().unwrap()).unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_operation_fails_save_and_stays_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = pdf_with_pages(dir.path(), 1);
+        let original = std::fs::read(&path).unwrap();
+        let mut editor = PDFEditor::new(&path).unwrap();
+        editor
+            .add_operations(vec![
+                PDFEditOperation::RotatePage { page: 1, degrees: 90 },
+                PDFEditOperation::Redact {
+                    page: 1,
+                    x: 72.0,
+                    y: 700.0,
+                    width: 100.0,
+                    height: 12.0,
+                },
+            ])
+            .unwrap();
+
+        assert!(matches!(
+            editor.save(false).await,
+            Err(EditorError::UnsupportedOperation(_))
+        ));
+        assert_eq!(editor.get_operations().len(), 2);
+        assert!(editor.has_unsaved_changes());
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_bib_entry_creates_declared_bibliography() {
+        let dir = tempfile::tempdir().unwrap();
+        let tex = dir.path().join("paper.tex");
+        std::fs::write(&tex, "See \\cite{knuth1984}.\n\\bibliography{refs}\n").unwrap();
+        let editor = LaTeXEditor::new(tex.to_str().unwrap()).unwrap();
+
+        let bib = editor.add_bib_entry("@book{knuth1984, title = {The TeXbook}}").unwrap();
+        assert_eq!(Path::new(&bib), dir.path().join("refs.bib"));
+        assert_eq!(
+            std::fs::read_to_string(&bib).unwrap(),
+            "@book{knuth1984, title = {The TeXbook}}\n"
+        );
+
+        let without = dir.path().join("draft.tex");
+        std::fs::write(&without, "No references yet.").unwrap();
+        let editor = LaTeXEditor::new(without.to_str().unwrap()).unwrap();
+        assert!(matches!(
+            editor.add_bib_entry("@book{knuth1984, title = {The TeXbook}}"),
+            Err(EditorError::UnsupportedOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_docx_undo_restores_content_model() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("letter.docx");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        zip.start_file("word/document.xml", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(
+            br#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+                <w:body>
+                  <w:p><w:r><w:t>Dear </w:t></w:r><w:r><w:t>reader,</w:t></w:r></w:p>
+                  <w:p><w:r><w:t>Thanks for the draft.</w:t></w:r></w:p>
+                </w:body>
+              </w:document>"#,
+        )
+        .unwrap();
+        zip.finish().unwrap();
+
+        let mut editor = DOCXEditor::new(path.to_str().unwrap()).unwrap();
+        let before = editor.model().clone();
+        assert_eq!(before.paragraphs, ["Dear reader,", "Thanks for the draft."]);
+
+        editor.add_operation(DOCXEditOperation::Common(CommonEditOperation::InsertText {
+            position: TextPosition { line: 1, column: 20 },
+            text: " and notes".to_string(),
+        }));
+        editor.add_operation(DOCXEditOperation::SetHeaderFooter {
+            is_header: true,
+            content: "Draft".to_string(),
+        });
+        let after = editor.model().clone();
+        assert_eq!(after.paragraphs[1], "Thanks for the draft and notes.");
+        assert_eq!(after.header.as_deref(), Some("Draft"));
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(editor.model(), &before);
+        assert!(editor.undo().is_none());
+
+        editor.redo().unwrap();
+        editor.redo().unwrap();
+        assert_eq!(editor.model(), &after);
+
+        // Snapshots serialize, so the history can be persisted with the model
+        let json = serde_json::to_string(&editor.history).unwrap();
+        let mut restored: SnapshotHistory<DocxModel> = serde_json::from_str(&json).unwrap();
+        let mut model = after.clone();
+        assert!(restored.undo(&mut model));
+        assert_eq!(model.header, None);
+
+        editor.clear_operations();
+        assert_eq!(editor.model(), &before);
+    }
+
+    #[test]
+    fn test_epub_undo_restores_content_model() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut editor = EPUBEditor::new(file.path().to_str().unwrap()).unwrap();
+        let before = editor.model().clone();
+
+        editor.add_operation(EPUBEditOperation::AddChapter {
+            title: "Preface".to_string(),
+            content: "<p>Why this book.</p>".to_string(),
+            after_chapter: None,
+        });
+        let with_chapter = editor.model().clone();
+        editor.add_operation(EPUBEditOperation::ModifyMetadata {
+            field: MetadataField::Author,
+            value: "A. Writer".to_string(),
+        });
+        editor.add_operation(EPUBEditOperation::DeleteChapter {
+            chapter_id: "chapter-1".to_string(),
+        });
+        assert!(editor.model().chapters.is_empty());
+        assert_eq!(editor.model().metadata["creator"], "A. Writer");
+
+        editor.undo().unwrap();
+        editor.undo().unwrap();
+        assert_eq!(editor.model(), &with_chapter);
+        assert_eq!(editor.model().chapters[0].title.as_deref(), Some("Preface"));
+        editor.undo().unwrap();
+        assert_eq!(editor.model(), &before);
+
+        // A new operation after undoing discards the redo history
+        editor.add_operation(EPUBEditOperation::SetCoverImage {
+            image_path: "cover.png".to_string(),
+        });
+        assert!(editor.redo().is_none());
+        assert_eq!(editor.model().cover_image.as_deref(), Some("cover.png"));
+    }
+}
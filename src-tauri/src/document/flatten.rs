@@ -0,0 +1,493 @@
+//! Flattening PDF annotations into page content
+//!
+//! An annotation is drawn from its normal appearance stream when it has one. Markup
+//! annotations without an appearance, as written by simpler tools, are drawn from
+//! their rectangle, quad points and colour instead. The annotation objects are then
+//! removed, along with the interactive form whose widgets were drawn.
+
+use super::editor::{inherited_page_attribute, pdf_error, wrap_line, EditorError};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use std::collections::HashMap;
+
+/// Annotation flags for annotations that are never shown: Hidden and NoView
+const HIDDEN_FLAGS: i64 = 2 | 32;
+
+pub(super) const HIGHLIGHT_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+const HIGHLIGHT_OPACITY: f32 = 0.5;
+pub(super) const NOTE_COLOR: [f32; 3] = [1.0, 0.85, 0.3];
+/// Side of the icon drawn for a sticky note, in points
+pub(super) const NOTE_ICON_SIZE: f32 = 18.0;
+const NOTE_FONT_SIZE: f32 = 8.0;
+/// Characters per line of a note's text
+const NOTE_LINE_WIDTH: usize = 48;
+
+/// A rectangle as (left, bottom, right, top)
+type Rect = [f32; 4];
+
+/// Draw every visible annotation into the content of its page, then remove the
+/// annotations. Returns the number of marks drawn.
+pub fn flatten_annotations(doc: &mut Document) -> Result<usize, EditorError> {
+    let mut drawn = 0;
+
+    for page_id in doc.get_pages().into_values() {
+        let annotations = page_annotations(doc, page_id);
+        let mut canvas = Canvas::new(doc, page_id);
+        for annotation in &annotations {
+            draw_annotation(doc, annotation, &mut canvas);
+        }
+
+        let page = doc
+            .get_object_mut(page_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?;
+        page.remove(b"Annots");
+        drawn += canvas.marks;
+        if canvas.marks > 0 {
+            canvas.finish(doc, page_id)?;
+        }
+    }
+
+    // The form's widgets were drawn above, so nothing is left to fill in
+    if let Ok(catalog) = doc.catalog_mut() {
+        catalog.remove(b"AcroForm");
+    }
+    doc.prune_objects();
+    Ok(drawn)
+}
+
+/// Annotation dictionaries of a page, whether stored inline or by reference
+fn page_annotations(doc: &Document, page_id: ObjectId) -> Vec<Dictionary> {
+    let Ok(page) = doc.get_dictionary(page_id) else {
+        return Vec::new();
+    };
+    let annots = page
+        .get(b"Annots")
+        .and_then(|annots| doc.dereference(annots))
+        .and_then(|(_, annots)| annots.as_array());
+    annots
+        .map(|annots| {
+            annots
+                .iter()
+                .filter_map(|a| doc.dereference(a).ok()?.1.as_dict().ok().cloned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn draw_annotation(doc: &mut Document, annotation: &Dictionary, canvas: &mut Canvas) {
+    let flags = annotation.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+    let subtype = annotation.get(b"Subtype").and_then(Object::as_name).unwrap_or_default();
+    // Popups only show the text of their parent, which is drawn on its own
+    if flags & HIDDEN_FLAGS != 0 || subtype == b"Popup" {
+        return;
+    }
+    let Some(rect) = annotation.get(b"Rect").ok().and_then(|r| number_array(doc, r)) else {
+        return;
+    };
+    let rect = match rect.as_slice() {
+        [x0, y0, x1, y1] => [x0.min(*x1), y0.min(*y1), x0.max(*x1), y0.max(*y1)],
+        _ => return,
+    };
+
+    if let Some(appearance) = appearance_stream(doc, annotation) {
+        if canvas.form(doc, appearance, rect) {
+            return;
+        }
+    }
+
+    // An empty /C means the annotation is transparent
+    let color = match annotation.get(b"C") {
+        Ok(c) => match number_array(doc, c).and_then(|c| rgb(&c)) {
+            Some(color) => Some(color),
+            None => return,
+        },
+        Err(_) => None,
+    };
+    let quads = quad_rects(doc, annotation).unwrap_or_else(|| vec![rect]);
+    match subtype {
+        b"Highlight" => {
+            let color = color.unwrap_or(HIGHLIGHT_COLOR);
+            quads.iter().for_each(|q| canvas.highlight(doc, *q, color));
+        }
+        b"Underline" | b"Squiggly" => {
+            let color = color.unwrap_or_default();
+            for [x0, y0, x1, y1] in quads {
+                let y = y0 + (y1 - y0) * 0.1;
+                canvas.line([x0, y, x1, y], color);
+            }
+        }
+        b"StrikeOut" => {
+            let color = color.unwrap_or_default();
+            for [x0, y0, x1, y1] in quads {
+                let y = (y0 + y1) / 2.0;
+                canvas.line([x0, y, x1, y], color);
+            }
+        }
+        b"Square" => canvas.outline(rect, color.unwrap_or_default()),
+        b"Text" => {
+            let contents = annotation
+                .get(b"Contents")
+                .ok()
+                .and_then(|c| lopdf::decode_text_string(c).ok())
+                .unwrap_or_default();
+            let [x0, _, _, y1] = rect;
+            let color = color.unwrap_or(NOTE_COLOR);
+            canvas.note(doc, x0, y1 - NOTE_ICON_SIZE, &contents, color);
+        }
+        _ => {}
+    }
+}
+
+/// The normal appearance of an annotation, picking its current state when it has several
+fn appearance_stream(doc: &Document, annotation: &Dictionary) -> Option<ObjectId> {
+    let (_, appearances) = doc.dereference(annotation.get(b"AP").ok()?).ok()?;
+    let normal = appearances.as_dict().ok()?.get(b"N").ok()?;
+    let id = match normal {
+        Object::Reference(id) => *id,
+        _ => return None,
+    };
+
+    match doc.get_object(id).ok()? {
+        Object::Stream(_) => Some(id),
+        Object::Dictionary(states) => {
+            let state = annotation.get(b"AS").and_then(Object::as_name).ok()?;
+            states.get(state).and_then(Object::as_reference).ok()
+        }
+        _ => None,
+    }
+}
+
+/// `/QuadPoints` as the rectangles bounding each quadrilateral
+fn quad_rects(doc: &Document, annotation: &Dictionary) -> Option<Vec<Rect>> {
+    let points = number_array(doc, annotation.get(b"QuadPoints").ok()?)?;
+    let rects: Vec<Rect> = points
+        .chunks_exact(8)
+        .map(|quad| {
+            let xs = quad.iter().step_by(2);
+            let ys = quad.iter().skip(1).step_by(2);
+            let (x0, x1) = xs.fold((f32::MAX, f32::MIN), |(lo, hi), x| (lo.min(*x), hi.max(*x)));
+            let (y0, y1) = ys.fold((f32::MAX, f32::MIN), |(lo, hi), y| (lo.min(*y), hi.max(*y)));
+            [x0, y0, x1, y1]
+        })
+        .collect();
+    (!rects.is_empty()).then_some(rects)
+}
+
+fn number_array(doc: &Document, object: &Object) -> Option<Vec<f32>> {
+    doc.dereference(object)
+        .ok()?
+        .1
+        .as_array()
+        .ok()?
+        .iter()
+        .map(|n| doc.dereference(n).ok()?.1.as_float().ok())
+        .collect()
+}
+
+/// An annotation colour (gray, RGB or CMYK) as RGB; `None` when transparent
+fn rgb(components: &[f32]) -> Option<[f32; 3]> {
+    match *components {
+        [gray] => Some([gray; 3]),
+        [r, g, b] => Some([r, g, b]),
+        [c, m, y, k] => Some([(1.0 - c) * (1.0 - k), (1.0 - m) * (1.0 - k), (1.0 - y) * (1.0 - k)]),
+        _ => None,
+    }
+}
+
+/// Parse a `#RRGGBB` colour
+pub(super) fn hex_color(color: &str) -> Option<[f32; 3]> {
+    let hex = color.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok().map(|c| c as f32 / 255.0);
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Text in a standard font, as bytes of the font's Latin-1 based encoding
+fn latin1(text: &str) -> Vec<u8> {
+    // Standard fonts only cover Latin-1; substitute anything else
+    text.chars()
+        .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+        .collect()
+}
+
+/// A line of text drawn by `Canvas::text`
+pub(super) struct TextMark<'a> {
+    pub text: &'a str,
+    /// One of the standard 14 fonts, e.g. `Helvetica`
+    pub base_font: &'a str,
+    pub size: f32,
+    pub color: [f32; 3],
+    /// Fill opacity; `None` draws opaque text
+    pub opacity: Option<f32>,
+    /// Text matrix placing the start of the baseline, as given to `Tm`
+    pub matrix: [f32; 6],
+}
+
+/// Marks drawn on one page, with a copy of the page's resources extended for them
+pub(super) struct Canvas {
+    operations: Vec<Operation>,
+    resources: Dictionary,
+    highlight_state: Option<String>,
+    /// Resource names of the standard fonts used so far, by base font
+    fonts: HashMap<String, String>,
+    marks: usize,
+}
+
+impl Canvas {
+    pub(super) fn new(doc: &Document, page_id: ObjectId) -> Self {
+        let resources = inherited_page_attribute(doc, page_id, b"Resources")
+            .and_then(|r| doc.dereference(&r).ok()?.1.as_dict().ok().cloned())
+            .unwrap_or_default();
+        Self {
+            operations: Vec::new(),
+            resources,
+            highlight_state: None,
+            fonts: HashMap::new(),
+            marks: 0,
+        }
+    }
+
+    /// Register a resource under an unused name in the given category
+    fn add_resource(
+        &mut self,
+        doc: &Document,
+        category: &str,
+        prefix: &str,
+        value: Object,
+    ) -> String {
+        let mut entries = self
+            .resources
+            .get(category.as_bytes())
+            .ok()
+            .and_then(|e| doc.dereference(e).ok()?.1.as_dict().ok().cloned())
+            .unwrap_or_default();
+        let name = (0..)
+            .map(|i| format!("{}{}", prefix, i))
+            .find(|name| !entries.has(name.as_bytes()))
+            .unwrap_or_default();
+        entries.set(name.clone(), value);
+        self.resources.set(category, entries);
+        name
+    }
+
+    fn push(&mut self, operator: &str, operands: Vec<Object>) {
+        self.operations.push(Operation::new(operator, operands));
+    }
+
+    fn set_color(&mut self, operator: &str, [r, g, b]: [f32; 3]) {
+        self.push(operator, vec![r.into(), g.into(), b.into()]);
+    }
+
+    fn rectangle(&mut self, [x0, y0, x1, y1]: Rect) {
+        self.push("re", vec![x0.into(), y0.into(), (x1 - x0).into(), (y1 - y0).into()]);
+    }
+
+    /// Draw an appearance stream scaled into `rect`. Returns false if the stream has no
+    /// usable bounding box.
+    fn form(&mut self, doc: &mut Document, stream_id: ObjectId, rect: Rect) -> bool {
+        let Ok(Object::Stream(stream)) = doc.get_object_mut(stream_id) else {
+            return false;
+        };
+        let bbox = stream.dict.get(b"BBox").ok().cloned();
+        let matrix = stream.dict.get(b"Matrix").ok().cloned();
+        // Appearance streams are form XObjects, but not every writer says so
+        stream.dict.set("Type", "XObject");
+        stream.dict.set("Subtype", "Form");
+
+        let Some([bx0, by0, bx1, by1]) = bbox
+            .and_then(|b| number_array(doc, &b))
+            .and_then(|b| <[f32; 4]>::try_from(b).ok())
+        else {
+            return false;
+        };
+        let [a, b, c, d, e, f] = matrix
+            .and_then(|m| number_array(doc, &m))
+            .and_then(|m| <[f32; 6]>::try_from(m).ok())
+            .unwrap_or([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+        // Map the transformed bounding box onto the annotation rectangle (PDF 32000-1,
+        // 12.5.5)
+        let corners = [(bx0, by0), (bx0, by1), (bx1, by0), (bx1, by1)]
+            .map(|(x, y)| (a * x + c * y + e, b * x + d * y + f));
+        let min_x = corners.iter().map(|p| p.0).fold(f32::MAX, f32::min);
+        let max_x = corners.iter().map(|p| p.0).fold(f32::MIN, f32::max);
+        let min_y = corners.iter().map(|p| p.1).fold(f32::MAX, f32::min);
+        let max_y = corners.iter().map(|p| p.1).fold(f32::MIN, f32::max);
+        if max_x <= min_x || max_y <= min_y {
+            return false;
+        }
+        let sx = (rect[2] - rect[0]) / (max_x - min_x);
+        let sy = (rect[3] - rect[1]) / (max_y - min_y);
+
+        let name = self.add_resource(doc, "XObject", "FlatForm", Object::Reference(stream_id));
+        self.push("q", vec![]);
+        self.push(
+            "cm",
+            vec![
+                sx.into(),
+                0.into(),
+                0.into(),
+                sy.into(),
+                (rect[0] - min_x * sx).into(),
+                (rect[1] - min_y * sy).into(),
+            ],
+        );
+        self.push("Do", vec![Object::Name(name.into_bytes())]);
+        self.push("Q", vec![]);
+        self.marks += 1;
+        true
+    }
+
+    /// Fill a translucent box that darkens, rather than covers, the text beneath
+    fn highlight(&mut self, doc: &Document, rect: Rect, color: [f32; 3]) {
+        let state = match self.highlight_state.clone() {
+            Some(state) => state,
+            None => {
+                let state = dictionary! {
+                    "Type" => "ExtGState",
+                    "BM" => "Multiply",
+                    "ca" => HIGHLIGHT_OPACITY,
+                };
+                let name = self.add_resource(doc, "ExtGState", "FlatHighlight", state.into());
+                self.highlight_state = Some(name.clone());
+                name
+            }
+        };
+        self.push("q", vec![]);
+        self.push("gs", vec![Object::Name(state.into_bytes())]);
+        self.set_color("rg", color);
+        self.rectangle(rect);
+        self.push("f", vec![]);
+        self.push("Q", vec![]);
+        self.marks += 1;
+    }
+
+    fn line(&mut self, [x0, y0, x1, y1]: Rect, color: [f32; 3]) {
+        self.push("q", vec![]);
+        self.set_color("RG", color);
+        self.push("w", vec![1.into()]);
+        self.push("m", vec![x0.into(), y0.into()]);
+        self.push("l", vec![x1.into(), y1.into()]);
+        self.push("S", vec![]);
+        self.push("Q", vec![]);
+        self.marks += 1;
+    }
+
+    fn outline(&mut self, rect: Rect, color: [f32; 3]) {
+        self.push("q", vec![]);
+        self.set_color("RG", color);
+        self.push("w", vec![1.into()]);
+        self.rectangle(rect);
+        self.push("S", vec![]);
+        self.push("Q", vec![]);
+        self.marks += 1;
+    }
+
+    /// Draw a note icon with its bottom-left corner at (x, y) and the note's text beside it
+    fn note(&mut self, doc: &Document, x: f32, y: f32, text: &str, color: [f32; 3]) {
+        self.push("q", vec![]);
+        self.set_color("rg", color);
+        self.push("G", vec![0.into()]);
+        self.push("w", vec![0.5.into()]);
+        self.rectangle([x, y, x + NOTE_ICON_SIZE, y + NOTE_ICON_SIZE]);
+        self.push("B", vec![]);
+        self.push("Q", vec![]);
+        self.marks += 1;
+
+        let lines: Vec<String> = text
+            .lines()
+            .flat_map(|line| wrap_line(line, NOTE_LINE_WIDTH))
+            .filter(|line| !line.is_empty())
+            .collect();
+        if lines.is_empty() {
+            return;
+        }
+        let font = self.standard_font(doc, "Helvetica");
+
+        self.push("BT", vec![]);
+        self.push("g", vec![0.into()]);
+        self.push("Tf", vec![Object::Name(font.into_bytes()), NOTE_FONT_SIZE.into()]);
+        self.push("TL", vec![(NOTE_FONT_SIZE * 1.25).into()]);
+        let top = y + NOTE_ICON_SIZE - NOTE_FONT_SIZE;
+        self.push("Td", vec![(x + NOTE_ICON_SIZE + 4.0).into(), top.into()]);
+        for line in lines {
+            self.push("Tj", vec![Object::String(latin1(&line), StringFormat::Literal)]);
+            self.push("T*", vec![]);
+        }
+        self.push("ET", vec![]);
+    }
+
+    /// Draw a line of text
+    pub(super) fn text(&mut self, doc: &Document, mark: &TextMark) {
+        let font = self.standard_font(doc, mark.base_font);
+
+        self.push("q", vec![]);
+        if let Some(opacity) = mark.opacity {
+            let state = dictionary! { "Type" => "ExtGState", "ca" => opacity };
+            let state = self.add_resource(doc, "ExtGState", "FlatOpacity", state.into());
+            self.push("gs", vec![Object::Name(state.into_bytes())]);
+        }
+        self.push("BT", vec![]);
+        self.set_color("rg", mark.color);
+        self.push("Tf", vec![Object::Name(font.into_bytes()), mark.size.into()]);
+        self.push("Tm", mark.matrix.iter().map(|&n| n.into()).collect());
+        self.push("Tj", vec![Object::String(latin1(mark.text), StringFormat::Literal)]);
+        self.push("ET", vec![]);
+        self.push("Q", vec![]);
+        self.marks += 1;
+    }
+
+    /// Resource name of a standard font, added to the page's resources on first use
+    fn standard_font(&mut self, doc: &Document, base_font: &str) -> String {
+        if let Some(name) = self.fonts.get(base_font) {
+            return name.clone();
+        }
+        let font = dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => base_font,
+            "Encoding" => "WinAnsiEncoding",
+        };
+        let prefix = format!("Flat{}", base_font.replace('-', ""));
+        let name = self.add_resource(doc, "Font", &prefix, font.into());
+        self.fonts.insert(base_font.to_string(), name.clone());
+        name
+    }
+
+    /// Append the marks to the page, isolating them from the graphics state the page's
+    /// own content leaves behind
+    pub(super) fn finish(self, doc: &mut Document, page_id: ObjectId) -> Result<(), EditorError> {
+        let marks = Content {
+            operations: self.operations,
+        }
+        .encode()
+        .map_err(pdf_error)?;
+
+        let existing = match doc.get_dictionary(page_id).and_then(|p| p.get(b"Contents")) {
+            Ok(Object::Array(streams)) => streams.clone(),
+            Ok(Object::Reference(id)) => match doc.get_object(*id) {
+                Ok(Object::Array(streams)) => streams.clone(),
+                _ => vec![Object::Reference(*id)],
+            },
+            _ => Vec::new(),
+        };
+        let save = doc.add_object(Stream::new(dictionary! {}, b"q\n".to_vec()));
+        let restore = [b"Q\n".as_slice(), &marks].concat();
+        let restore = doc.add_object(Stream::new(dictionary! {}, restore));
+
+        let mut contents = vec![Object::Reference(save)];
+        contents.extend(existing);
+        contents.push(Object::Reference(restore));
+
+        let page = doc
+            .get_object_mut(page_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?;
+        page.set("Contents", contents);
+        page.set("Resources", self.resources);
+        Ok(())
+    }
+}
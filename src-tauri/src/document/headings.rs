@@ -0,0 +1,342 @@
+//! Heading detection for PDFs without bookmarks
+//!
+//! Paragraphs are scored on length, capitalization, section numbering and what
+//! follows them. An optional LLM pass can replace the heuristic result; either way
+//! the headings are cached per document so the outline is only computed once.
+
+use super::{Document, Paragraph};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+/// Longest paragraph, in words, that can be a heading
+const MAX_HEADING_WORDS: usize = 12;
+
+/// Longest paragraph, in characters, that can be a heading
+const MAX_HEADING_CHARS: usize = 100;
+
+/// Score a paragraph needs to be treated as a heading
+const HEADING_THRESHOLD: u32 = 3;
+
+/// Documents whose headings are kept cached; the least recently used are dropped first
+const MAX_CACHED_DOCUMENTS: usize = 32;
+
+/// Unnumbered titles that commonly head sections of papers and reports
+const SECTION_NAMES: &[&str] = &[
+    "abstract",
+    "introduction",
+    "background",
+    "related work",
+    "method",
+    "methods",
+    "methodology",
+    "experiments",
+    "results",
+    "evaluation",
+    "discussion",
+    "conclusion",
+    "conclusions",
+    "future work",
+    "acknowledgments",
+    "acknowledgements",
+    "references",
+    "bibliography",
+    "appendix",
+];
+
+/// A paragraph recognized as a section heading
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DetectedHeading {
+    pub paragraph_id: String,
+    pub page: u32,
+    pub title: String,
+    /// Heading depth (1 = top level), from the section number when there is one
+    pub level: usize,
+}
+
+fn numbering() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"^(?:(\d{1,2}(?:\.\d{1,2})*)\.?|([IVX]+)\.|Appendix\s+[A-Z0-9]+[.:]?)",
+            r"\s+\S",
+        ))
+        .unwrap()
+    })
+}
+
+fn caption() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(Figure|Fig\.|Table)\s*\d").unwrap())
+}
+
+/// Headings by document id, holding at most `capacity` documents
+struct HeadingCache {
+    capacity: usize,
+    entries: HashMap<String, Vec<DetectedHeading>>,
+    /// Document ids, least recently used first
+    recency: VecDeque<String>,
+}
+
+impl HeadingCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: VecDeque::new(),
+        }
+    }
+
+    fn get(&mut self, document_id: &str) -> Option<Vec<DetectedHeading>> {
+        let headings = self.entries.get(document_id)?.clone();
+        self.touch(document_id);
+        Some(headings)
+    }
+
+    fn insert(&mut self, document_id: &str, headings: Vec<DetectedHeading>) {
+        self.entries.insert(document_id.to_string(), headings);
+        self.touch(document_id);
+        while self.recency.len() > self.capacity {
+            if let Some(oldest) = self.recency.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn touch(&mut self, document_id: &str) {
+        self.recency.retain(|id| id != document_id);
+        self.recency.push_back(document_id.to_string());
+    }
+}
+
+fn cache() -> &'static Mutex<HeadingCache> {
+    static CACHE: OnceLock<Mutex<HeadingCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HeadingCache::new(MAX_CACHED_DOCUMENTS)))
+}
+
+/// Headings for a document, detected heuristically on first use and cached by id
+pub fn cached_headings(doc: &Document) -> Vec<DetectedHeading> {
+    let mut cache = cache().lock().unwrap();
+    if let Some(headings) = cache.get(&doc.id) {
+        return headings;
+    }
+    let headings = detect_headings(doc);
+    cache.insert(&doc.id, headings.clone());
+    headings
+}
+
+/// Replace the cached headings for a document, e.g. with the result of an LLM pass
+pub fn cache_headings(document_id: &str, headings: Vec<DetectedHeading>) {
+    cache().lock().unwrap().insert(document_id, headings);
+}
+
+/// Classify paragraphs as headings from their shape and position
+pub fn detect_headings(doc: &Document) -> Vec<DetectedHeading> {
+    let paragraphs = doc.paragraphs();
+    paragraphs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (page, paragraph))| {
+            let next = paragraphs.get(i + 1).map(|(_, p)| p.text.trim());
+            (heading_score(&paragraph.text, next) >= HEADING_THRESHOLD)
+                .then(|| heading(*page, paragraph))
+        })
+        .collect()
+}
+
+/// Paragraphs short enough and shaped like headings, for an LLM to classify
+pub fn heading_candidates(doc: &Document) -> Vec<DetectedHeading> {
+    doc.paragraphs()
+        .into_iter()
+        .filter(|(_, p)| heading_shaped(p.text.trim()))
+        .map(|(page, p)| heading(page, p))
+        .collect()
+}
+
+fn heading(page: u32, paragraph: &Paragraph) -> DetectedHeading {
+    let title = paragraph.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    DetectedHeading {
+        paragraph_id: paragraph.id.clone(),
+        page,
+        level: numbering_level(&title).unwrap_or(1),
+        title,
+    }
+}
+
+/// Depth of a section number ("2.3" is 2); roman numerals and appendices are top level
+fn numbering_level(text: &str) -> Option<usize> {
+    numbering().captures(text).map(|caps| match caps.get(1) {
+        Some(number) => number.as_str().split('.').count(),
+        None => 1,
+    })
+}
+
+/// Short, starts with a capital or digit, and doesn't end like a sentence
+fn heading_shaped(text: &str) -> bool {
+    let words = text.split_whitespace().count();
+    words > 0
+        && words <= MAX_HEADING_WORDS
+        && text.chars().count() <= MAX_HEADING_CHARS
+        && text.chars().next().is_some_and(|c| c.is_uppercase() || c.is_ascii_digit())
+        && !text.ends_with(['.', ',', ';', '?', '!'])
+        && !caption().is_match(text)
+}
+
+fn heading_score(text: &str, next: Option<&str>) -> u32 {
+    let text = text.trim();
+    if !heading_shaped(text) {
+        return 0;
+    }
+
+    let name = numbering()
+        .find(text)
+        .map_or(text, |m| text[m.end() - 1..].trim())
+        .trim_end_matches(':');
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let letters: Vec<char> = name.chars().filter(|c| c.is_alphabetic()).collect();
+
+    let numbered = numbering_level(text).is_some();
+    let known = SECTION_NAMES.contains(&name.to_lowercase().as_str());
+    let all_caps = letters.len() > 1 && letters.iter().all(|c| c.is_uppercase());
+    // Most longer words capitalized, allowing for "of", "and", "the"
+    let long_words: Vec<&&str> = words.iter().filter(|w| w.chars().count() > 3).collect();
+    let title_case = !long_words.is_empty()
+        && long_words
+            .iter()
+            .filter(|w| w.chars().next().is_some_and(char::is_uppercase))
+            .count()
+            * 4
+            >= long_words.len() * 3;
+    let followed_by_body = next.is_some_and(|n| n.chars().count() >= 2 * text.chars().count());
+
+    let mut score = 0;
+    if numbered {
+        score += 2;
+    }
+    if known {
+        score += 2;
+    }
+    if all_caps || title_case {
+        score += 1;
+    }
+    if words.len() <= 6 {
+        score += 1;
+    }
+    if followed_by_body {
+        score += 1;
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{get_outline, test_document};
+
+    fn document(id: &str, texts: &[&str]) -> Document {
+        Document {
+            id: id.to_string(),
+            ..test_document(&[texts])
+        }
+    }
+
+    const PAPER: &[&str] = &[
+        "Learning to Rank with Sparse Features",
+        "We study ranking when most features are missing and propose a simple, cheap fix.",
+        "1. Introduction",
+        "Ranking models are trained on click logs that are noisy and incomplete.",
+        "2. Method",
+        "We impute missing features from neighbouring documents before training.",
+        "2.1 Feature Imputation",
+        "Each missing value is replaced by the mean over the nearest neighbours.",
+        "1. We propose a new imputation scheme.",
+        "Figure 2: Accuracy by feature sparsity",
+        "3 CONCLUSION",
+        "Imputation closes most of the gap to fully observed features.",
+    ];
+
+    #[test]
+    fn test_numbered_section_titles_detected_as_headings() {
+        let doc = document("headings-numbered", PAPER);
+        let headings = detect_headings(&doc);
+
+        let found: Vec<(&str, &str, usize)> = headings
+            .iter()
+            .map(|h| (h.paragraph_id.as_str(), h.title.as_str(), h.level))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("p1", "Learning to Rank with Sparse Features", 1),
+                ("p3", "1. Introduction", 1),
+                ("p5", "2. Method", 1),
+                ("p7", "2.1 Feature Imputation", 2),
+                ("p11", "3 CONCLUSION", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pdf_without_bookmarks_gets_detected_outline() {
+        let doc = document("headings-outline", PAPER);
+        let outline = get_outline(&doc);
+
+        let titles: Vec<&str> = outline.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Learning to Rank with Sparse Features",
+                "1. Introduction",
+                "2. Method",
+                "3 CONCLUSION"
+            ]
+        );
+        assert_eq!(outline[2].children[0].title, "2.1 Feature Imputation");
+        assert_eq!(outline[2].children[0].href, "#p7");
+        assert_eq!(outline[2].children[0].page, Some(1));
+    }
+
+    #[test]
+    fn test_cached_headings_are_reused() {
+        let doc = document("headings-cached", PAPER);
+        let mut refined = heading_candidates(&doc);
+        refined.retain(|h| h.paragraph_id == "p3");
+        cache_headings(&doc.id, refined);
+
+        let outline = get_outline(&doc);
+        assert_eq!(outline.len(), 1);
+        assert_eq!(outline[0].title, "1. Introduction");
+        assert_eq!(cached_headings(&doc).len(), 1);
+    }
+
+    #[test]
+    fn test_cache_drops_least_recently_used_document() {
+        let mut cache = HeadingCache::new(2);
+        let headings = detect_headings(&document("a", PAPER));
+        cache.insert("a", headings.clone());
+        cache.insert("b", Vec::new());
+        // Reading "a" makes "b" the oldest
+        assert!(cache.get("a").is_some());
+        cache.insert("c", Vec::new());
+
+        assert_eq!(cache.get("a"), Some(headings));
+        assert_eq!(cache.get("b"), None);
+        assert!(cache.get("c").is_some());
+        assert_eq!(cache.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_prose_paragraphs_are_not_headings() {
+        let doc = document(
+            "headings-prose",
+            &[
+                "The experiment ran for three weeks.",
+                "Results were mixed, with some gains.",
+                "we then repeated it",
+            ],
+        );
+        assert!(detect_headings(&doc).is_empty());
+        assert!(heading_candidates(&doc).is_empty());
+    }
+}
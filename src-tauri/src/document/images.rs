@@ -0,0 +1,232 @@
+//! Pictures embedded in PDF pages, extracted as files a vision model can read
+//!
+//! Images are found by walking each page's content, as when sampling for scans, so only
+//! images a page actually draws are returned. JPEGs are passed through unchanged and
+//! 8-bit gray or RGB pixels are encoded as PNG; other encodings (JPEG 2000, CMYK,
+//! indexed colour, 1-bit masks) are skipped.
+
+use super::scan::page_images;
+use crate::error::{AppError, DocumentError};
+use lopdf::{Object, Stream};
+use serde::{Deserialize, Serialize};
+
+/// Images narrower or shorter than this, in pixels, are decoration (bullets, rules, icons)
+const MIN_IMAGE_SIDE: u32 = 32;
+
+/// An image drawn on a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageImage {
+    /// 1-based page number
+    pub page: u32,
+    /// Position among the images the page draws, from 0
+    pub index: u32,
+    pub width: u32,
+    pub height: u32,
+    /// `image/jpeg` or `image/png`
+    pub mime_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// A generated description of one image
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageDescription {
+    pub page: u32,
+    pub image_index: u32,
+    pub description: String,
+    /// Model that wrote the description
+    pub model: String,
+}
+
+/// Images drawn on the pages of the PDF at `path`, in page and drawing order
+pub fn extract_page_images(path: &str) -> Result<Vec<PageImage>, AppError> {
+    if !std::path::Path::new(path).exists() {
+        return Err(DocumentError::FileNotFound(path.to_string()).into());
+    }
+    let doc = lopdf::Document::load(path)
+        .map_err(|e| DocumentError::ParseError(format!("{}: {}", path, e)))?;
+
+    let mut images = Vec::new();
+    for (page, page_id) in doc.get_pages() {
+        for (index, image) in page_images(&doc, page_id).into_iter().enumerate() {
+            let side = |key: &[u8]| image.dict.get(key).and_then(Object::as_i64).unwrap_or(0);
+            let (width, height) = (side(b"Width") as u32, side(b"Height") as u32);
+            if width < MIN_IMAGE_SIDE || height < MIN_IMAGE_SIDE {
+                continue;
+            }
+            match encode_image(&doc, image, width, height) {
+                Some((mime_type, data)) => images.push(PageImage {
+                    page,
+                    index: index as u32,
+                    width,
+                    height,
+                    mime_type,
+                    data,
+                }),
+                None => tracing::debug!("Skipping image {} on page {}", index, page),
+            }
+        }
+    }
+    Ok(images)
+}
+
+/// The image as a JPEG or PNG file, with its MIME type
+fn encode_image(
+    doc: &lopdf::Document,
+    image: &Stream,
+    width: u32,
+    height: u32,
+) -> Option<(&'static str, Vec<u8>)> {
+    let filters = image.filters().unwrap_or_default();
+    if filters.iter().any(|f| f == "DCTDecode") {
+        return (filters.len() == 1).then(|| ("image/jpeg", image.content.clone()));
+    }
+
+    if image.dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok() != Some(8) {
+        return None;
+    }
+    let (color, channels) = match color_channels(doc, image)? {
+        1 => (png::ColorType::Grayscale, 1),
+        3 => (png::ColorType::Rgb, 3),
+        _ => return None,
+    };
+    let pixels = if filters.is_empty() {
+        image.content.clone()
+    } else {
+        // `decompressed_content` refuses image streams, so decode a copy not marked as one
+        let mut raw = image.clone();
+        raw.dict.remove(b"Subtype");
+        raw.decompressed_content().ok()?
+    };
+    let size = width as usize * height as usize * channels;
+    if pixels.len() < size {
+        return None;
+    }
+
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, width, height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header().ok()?.write_image_data(&pixels[..size]).ok()?;
+    Some(("image/png", data))
+}
+
+/// Colour components per pixel of the image's colour space, when it is gray or RGB
+/// (including ICC-based spaces with that many components)
+fn color_channels(doc: &lopdf::Document, image: &Stream) -> Option<i64> {
+    let (_, space) = doc.dereference(image.dict.get(b"ColorSpace").ok()?).ok()?;
+    let (name, params) = match space {
+        Object::Name(name) => (name.as_slice(), None),
+        Object::Array(parts) => (parts.first()?.as_name().ok()?, parts.get(1)),
+        _ => return None,
+    };
+    match name {
+        b"DeviceGray" | b"CalGray" => Some(1),
+        b"DeviceRGB" | b"CalRGB" => Some(3),
+        b"ICCBased" => {
+            let (_, profile) = doc.dereference(params?).ok()?;
+            profile.as_stream().ok()?.dict.get(b"N").and_then(Object::as_i64).ok()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::{Content, Operation};
+    use lopdf::dictionary;
+
+    fn image_stream(width: i64, height: i64, filter: Option<&str>, content: Vec<u8>) -> Stream {
+        let mut dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => width,
+            "Height" => height,
+            "ColorSpace" => "DeviceRGB",
+            "BitsPerComponent" => 8,
+        };
+        if let Some(filter) = filter {
+            dict.set("Filter", Object::Name(filter.as_bytes().to_vec()));
+        }
+        Stream::new(dict, content)
+    }
+
+    #[test]
+    fn test_extracts_jpeg_and_rgb_images_and_skips_icons() {
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+
+        // Not a real JPEG: its bytes are passed through without being decoded
+        let jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 1, 2, 3, 0xFF, 0xD9];
+        let jpeg_id = doc.add_object(image_stream(64, 48, Some("DCTDecode"), jpeg.clone()));
+        let pixels: Vec<u8> = (0..40 * 40 * 3).map(|i| (i % 251) as u8).collect();
+        let mut rgb = image_stream(40, 40, None, pixels.clone());
+        rgb.compress().unwrap();
+        let rgb_id = doc.add_object(rgb);
+        let icon_id = doc.add_object(image_stream(8, 8, None, vec![0; 8 * 8 * 3]));
+
+        let draw = |name: &str| {
+            vec![
+                Operation::new("q", vec![]),
+                Operation::new(
+                    "cm",
+                    vec![100.into(), 0.into(), 0.into(), 100.into(), 0.into(), 0.into()],
+                ),
+                Operation::new("Do", vec![Object::Name(name.as_bytes().to_vec())]),
+                Operation::new("Q", vec![]),
+            ]
+        };
+        let mut kids = Vec::new();
+        for names in [vec!["Icon", "Photo"], vec!["Chart", "Photo"]] {
+            let operations: Vec<_> = names.into_iter().flat_map(draw).collect();
+            let content = Content { operations }.encode().unwrap();
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content));
+            kids.push(Object::Reference(doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "Resources" => dictionary! {
+                    "XObject" => dictionary! {
+                        "Photo" => jpeg_id,
+                        "Chart" => rgb_id,
+                        "Icon" => icon_id,
+                    },
+                },
+            })));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("figures.pdf");
+        doc.save(&path).unwrap();
+
+        let images = extract_page_images(path.to_str().unwrap()).unwrap();
+        let keys: Vec<(u32, u32, &str)> =
+            images.iter().map(|i| (i.page, i.index, i.mime_type)).collect();
+        assert_eq!(
+            keys,
+            [(1, 1, "image/jpeg"), (2, 0, "image/png"), (2, 1, "image/jpeg")]
+        );
+        assert_eq!(images[0].data, jpeg);
+        assert_eq!((images[0].width, images[0].height), (64, 48));
+
+        let mut reader = png::Decoder::new(images[1].data.as_slice()).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+        assert_eq!((info.width, info.height), (40, 40));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(decoded, pixels);
+    }
+}
@@ -0,0 +1,214 @@
+//! Readable errors from LaTeX compile logs
+//!
+//! pdflatex reports errors as a `! message` line followed by an `l.<n>` line giving
+//! the source line and the text up to the error. With `-file-line-error` the message
+//! line is `file:line: message` instead. Both forms are recognized.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Log lines after an error searched for its `l.<n>` location
+const LOCATION_SEARCH_LINES: usize = 12;
+
+/// Advice for common errors, keyed by a fragment of the message
+const HINTS: &[(&str, &str)] = &[
+    (
+        "Undefined control sequence",
+        "A command is misspelled, or it comes from a package that isn't loaded with \\usepackage.",
+    ),
+    (
+        "Missing $ inserted",
+        "Math-only syntax such as ^, _ or \\alpha is used outside math mode; wrap it in $...$.",
+    ),
+    ("Missing } inserted", "A { is never closed; add the matching }."),
+    ("Extra }", "A } has no matching {; remove it or add the missing {."),
+    ("Too many }'s", "A } has no matching {; remove it or add the missing {."),
+    (
+        "Missing \\begin{document}",
+        "Text appears before \\begin{document}; move it into the body of the document.",
+    ),
+    (
+        "Paragraph ended before",
+        "A command's argument runs into a blank line; check for a missing }.",
+    ),
+    (
+        "Extra alignment tab",
+        "A table row has more & separators than the column specification allows.",
+    ),
+    (
+        "LaTeX Error: Environment",
+        "The environment is misspelled, or its package isn't loaded with \\usepackage.",
+    ),
+    (
+        "LaTeX Error: File `",
+        "A package or input file isn't installed, or its name is misspelled.",
+    ),
+];
+
+/// An error reported in a LaTeX compile log
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LatexError {
+    /// Source file the error was raised in, when the log names it
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+    /// Source text leading up to the error
+    pub context: Option<String>,
+    /// Likely cause of a common error
+    pub hint: Option<String>,
+}
+
+fn file_line_error() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(\S[^:]*\.(?:tex|sty|cls|bib)):(\d+): (.+)$").unwrap())
+}
+
+fn location() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^l\.(\d+)(?: (.*))?$").unwrap())
+}
+
+fn opened_file() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\(([^\s()]+\.tex)").unwrap())
+}
+
+/// Extract the errors from a pdflatex log, in the order they were reported
+pub fn parse_errors(log: &str) -> Vec<LatexError> {
+    let lines: Vec<&str> = log.lines().collect();
+    let mut errors = Vec::new();
+
+    for (i, text) in lines.iter().enumerate() {
+        let (file, line, message) = if let Some(message) = text.strip_prefix("! ") {
+            (last_opened_file(&lines[..i]), None, message.trim())
+        } else if let Some(caps) = file_line_error().captures(text) {
+            let file = caps.get(1).map(|m| clean_path(m.as_str()));
+            let line = caps[2].parse().ok();
+            (file, line, caps.get(3).map_or("", |m| m.as_str().trim()))
+        } else {
+            continue;
+        };
+        // Follow-on notices from the run being aborted, not errors in the source
+        if message.starts_with("==>") || message.starts_with("Emergency stop") {
+            continue;
+        }
+
+        let location = lines[i + 1..]
+            .iter()
+            .take(LOCATION_SEARCH_LINES)
+            .take_while(|l| !l.starts_with("! ") && !file_line_error().is_match(l))
+            .find_map(|l| location().captures(l));
+        let context = location
+            .as_ref()
+            .and_then(|caps| caps.get(2))
+            .map(|m| m.as_str().trim().to_string())
+            .filter(|context| !context.is_empty());
+
+        errors.push(LatexError {
+            file,
+            line: line.or_else(|| location.and_then(|caps| caps[1].parse().ok())),
+            message: message.to_string(),
+            context,
+            hint: hint(message).map(str::to_string),
+        });
+    }
+
+    errors
+}
+
+/// Advice for a common error message
+pub fn hint(message: &str) -> Option<&'static str> {
+    HINTS
+        .iter()
+        .find(|(fragment, _)| message.contains(fragment))
+        .map(|(_, hint)| *hint)
+}
+
+/// The `.tex` file opened most recently before an error
+fn last_opened_file(lines: &[&str]) -> Option<String> {
+    lines.iter().rev().find_map(|line| {
+        opened_file()
+            .captures_iter(line)
+            .last()
+            .map(|caps| clean_path(&caps[1]))
+    })
+}
+
+fn clean_path(path: &str) -> String {
+    path.strip_prefix("./").unwrap_or(path).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = r"This is pdfTeX, Version 3.141592653-2.6-1.40.25 (TeX Live 2023)
+ restricted \write18 enabled.
+entering extended mode
+(./intellidoc_compile.tex
+LaTeX2e <2023-11-01>
+(/usr/share/texlive/texmf-dist/tex/latex/base/article.cls
+Document Class: article 2023/05/17 v1.4n Standard LaTeX document class
+(/usr/share/texlive/texmf-dist/tex/latex/base/size10.clo))
+(/usr/share/texlive/texmf-dist/tex/latex/amsmath/amsmath.sty)
+No file intellidoc_compile.aux.
+! Undefined control sequence.
+l.8 The value is \alpah
+                        + 1.
+The control sequence at the end of the top line
+of your error message was never \def'ed.
+
+! Missing $ inserted.
+<inserted text>
+                $
+l.10 where x^
+             2 is the square.
+I've inserted a begin-math/end-math symbol since I think
+you left one out. Proceed, with fingers crossed.
+
+[1{/usr/share/texlive/texmf-var/fonts/map/pdftex/updmap/pdftex.map}] )
+! Emergency stop.
+<*> intellidoc_compile.tex
+";
+
+    #[test]
+    fn test_undefined_control_sequence_and_missing_dollar_parsed() {
+        let errors = parse_errors(LOG);
+        assert_eq!(errors.len(), 2);
+
+        let undefined = &errors[0];
+        assert_eq!(undefined.message, "Undefined control sequence.");
+        assert_eq!(undefined.line, Some(8));
+        assert_eq!(undefined.file.as_deref(), Some("intellidoc_compile.tex"));
+        assert_eq!(undefined.context.as_deref(), Some("The value is \\alpah"));
+        assert!(undefined.hint.as_deref().unwrap().contains("misspelled"));
+
+        let dollar = &errors[1];
+        assert_eq!(dollar.message, "Missing $ inserted.");
+        assert_eq!(dollar.line, Some(10));
+        assert_eq!(dollar.context.as_deref(), Some("where x^"));
+        assert!(dollar.hint.as_deref().unwrap().contains("math mode"));
+    }
+
+    #[test]
+    fn test_file_line_error_format_parsed() {
+        let log = "(./chapters/intro.tex\n\
+                   ./chapters/intro.tex:14: LaTeX Error: Environment theorm undefined.\n\
+                   \n\
+                   l.14 \\begin{theorm}\n";
+
+        let errors = parse_errors(log);
+        assert_eq!(
+            errors,
+            [LatexError {
+                file: Some("chapters/intro.tex".to_string()),
+                line: Some(14),
+                message: "LaTeX Error: Environment theorm undefined.".to_string(),
+                context: Some("\\begin{theorm}".to_string()),
+                hint: hint("LaTeX Error: Environment").map(str::to_string),
+            }]
+        );
+        assert!(parse_errors("Output written on doc.pdf (1 page).").is_empty());
+    }
+}
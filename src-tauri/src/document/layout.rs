@@ -0,0 +1,199 @@
+//! PDF page text with the position of every glyph, for placing paragraphs on the page
+//!
+//! Text is laid out as `pdf_extract` does for plain text: a space when a word starts
+//! well after the previous one ends, a line break when it starts on a lower line and a
+//! blank line after a larger gap. Each glyph's box is kept alongside, in PDF points from
+//! the page's top-left corner, so paragraphs split from the text can be found again.
+
+use super::{BoundingBox, Paragraph};
+use pdf_extract::{MediaBox, OutputDev, OutputError, Transform};
+use unicode_normalization::UnicodeNormalization;
+
+/// Share of the font size drawn above the baseline, and below it, in typical fonts
+const ASCENT: f64 = 0.8;
+const DESCENT: f64 = 0.2;
+
+/// Extracted text of one page and where its glyphs are drawn
+#[derive(Debug, Clone, Default)]
+pub struct PageLayout {
+    pub text: String,
+    glyphs: Vec<Glyph>,
+}
+
+#[derive(Debug, Clone)]
+struct Glyph {
+    text: String,
+    left: f64,
+    top: f64,
+    right: f64,
+    bottom: f64,
+}
+
+/// Lay out every page of a PDF. A page whose content can't be read comes back empty,
+/// so the pages after it keep their numbers.
+pub fn extract_page_layouts(content: &[u8]) -> Result<Vec<PageLayout>, OutputError> {
+    let mut doc = pdf_extract::Document::load_mem(content)?;
+    if doc.is_encrypted() {
+        doc.decrypt("")?;
+    }
+
+    let page_count = doc.get_pages().len() as u32;
+    let mut layouts = Vec::with_capacity(page_count as usize);
+    for page in 1..=page_count {
+        let mut output = LayoutOutput::default();
+        match pdf_extract::output_doc_page(&doc, &mut output, page) {
+            Ok(()) => layouts.push(output.layout),
+            Err(e) => {
+                tracing::warn!("Could not extract text of page {}: {}", page, e);
+                layouts.push(PageLayout::default());
+            }
+        }
+    }
+    Ok(layouts)
+}
+
+impl PageLayout {
+    /// Give each paragraph the box around its glyphs, matching paragraphs in order.
+    ///
+    /// Only letters and digits are compared, so whitespace, rejoined hyphenation and
+    /// expanded ligatures in the paragraph text don't matter. Paragraphs that can't be
+    /// found keep no box.
+    pub fn locate(&self, paragraphs: &mut [Paragraph]) {
+        let mut letters = Vec::new();
+        let mut owners = Vec::new();
+        for (i, glyph) in self.glyphs.iter().enumerate() {
+            for c in letters_of(&glyph.text) {
+                letters.push(c);
+                owners.push(i);
+            }
+        }
+
+        let mut cursor = 0;
+        for paragraph in paragraphs {
+            let wanted: Vec<char> = letters_of(&paragraph.text).collect();
+            if wanted.is_empty() {
+                continue;
+            }
+            let Some(start) = letters[cursor..]
+                .windows(wanted.len())
+                .position(|window| window == wanted.as_slice())
+            else {
+                continue;
+            };
+            let (start, end) = (cursor + start, cursor + start + wanted.len());
+            paragraph.bounding_box = Some(self.bounds(&owners[start..end]));
+            cursor = end;
+        }
+    }
+
+    /// Smallest box around the glyphs at `indices`
+    fn bounds(&self, indices: &[usize]) -> BoundingBox {
+        let (mut left, mut top) = (f64::MAX, f64::MAX);
+        let (mut right, mut bottom) = (f64::MIN, f64::MIN);
+        for glyph in indices.iter().map(|&i| &self.glyphs[i]) {
+            left = left.min(glyph.left);
+            top = top.min(glyph.top);
+            right = right.max(glyph.right);
+            bottom = bottom.max(glyph.bottom);
+        }
+        BoundingBox {
+            x: left as f32,
+            y: top as f32,
+            width: (right - left) as f32,
+            height: (bottom - top) as f32,
+        }
+    }
+}
+
+/// Letters and digits of `text`, lowercased, with ligatures and other compatibility
+/// characters expanded
+fn letters_of(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.nfkc()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+}
+
+/// Collects a page's text and glyph boxes as `pdf_extract` walks its content
+#[derive(Default)]
+struct LayoutOutput {
+    layout: PageLayout,
+    /// Top-left corner of the media box, in PDF user space
+    origin: (f64, f64),
+    last_end: f64,
+    last_y: f64,
+    first_char: bool,
+}
+
+impl OutputDev for LayoutOutput {
+    fn begin_page(
+        &mut self,
+        _page_num: u32,
+        media_box: &MediaBox,
+        _art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.origin = (media_box.llx, media_box.ury);
+        self.last_end = 100000.0;
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        _spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        let x = trm.m31 - self.origin.0;
+        let y = self.origin.1 - trm.m32;
+        // Font size under the text matrix, as the side of a square of the same area
+        let size = ((trm.m11 + trm.m21) * font_size * (trm.m12 + trm.m22) * font_size).sqrt();
+
+        if self.first_char {
+            let text = &mut self.layout.text;
+            if (y - self.last_y).abs() > size * 1.5 {
+                text.push('\n');
+            }
+            if x < self.last_end && (y - self.last_y).abs() > size * 0.5 {
+                text.push('\n');
+            }
+            if x > self.last_end + size * 0.1 {
+                text.push(' ');
+            }
+        }
+        self.layout.text.push_str(char);
+
+        // Scaled separately along each axis, so rotated or skewed text still gets a box
+        let advance = width * font_size * trm.m11.hypot(trm.m12);
+        let height = font_size * trm.m21.hypot(trm.m22);
+        self.layout.glyphs.push(Glyph {
+            text: char.to_string(),
+            left: x,
+            top: y - height * ASCENT,
+            right: x + advance,
+            bottom: y + height * DESCENT,
+        });
+
+        self.first_char = false;
+        self.last_y = y;
+        self.last_end = x + width * size;
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.first_char = true;
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
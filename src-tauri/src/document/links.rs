@@ -0,0 +1,195 @@
+//! Links, DOIs and arXiv identifiers mentioned in a document's text
+
+use super::Document;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// What a link points at
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Url,
+    Doi,
+    Arxiv,
+}
+
+/// A link found in a document, with every page it appears on
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DocumentLink {
+    pub kind: LinkKind,
+    /// Normalized URL; DOIs become `https://doi.org/...` and arXiv ids
+    /// `https://arxiv.org/abs/...`
+    pub url: String,
+    /// Text of the first occurrence, as written in the document
+    pub text: String,
+    /// Pages the link appears on, ascending
+    pub pages: Vec<u32>,
+}
+
+fn link_pattern() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r#"(?i)(?P<url>\b(?:https?://|www\.)[^\s<>"'`{}|\\^]+)"#,
+            r#"|(?P<doi>\b(?:doi:\s*)?10\.\d{4,9}/[^\s<>"']+)"#,
+            r"|\barxiv:\s*(?P<arxiv>\d{4}\.\d{4,5}(?:v\d+)?|[a-z-]+(?:\.[a-z]{2})?/\d{7}(?:v\d+)?)",
+        ))
+        .unwrap()
+    })
+}
+
+/// Find the URLs, DOIs and arXiv ids in a document's page text, in order of first
+/// appearance. Links that normalize to the same URL are listed once.
+pub fn extract_links(doc: &Document) -> Vec<DocumentLink> {
+    let mut links: Vec<DocumentLink> = Vec::new();
+    let mut by_url: HashMap<String, usize> = HashMap::new();
+
+    for page in &doc.pages {
+        for caps in link_pattern().captures_iter(&page.text) {
+            let found = if let Some(m) = caps.name("url") {
+                classify_url(trim_trailing(m.as_str()))
+            } else if let Some(m) = caps.name("doi") {
+                let text = trim_trailing(m.as_str());
+                Some((LinkKind::Doi, doi_url(text), text))
+            } else {
+                caps.name("arxiv").map(|m| {
+                    let whole = trim_trailing(caps.get(0).map_or("", |c| c.as_str()));
+                    (LinkKind::Arxiv, arxiv_url(trim_trailing(m.as_str())), whole)
+                })
+            };
+            let Some((kind, url, text)) = found else {
+                continue;
+            };
+
+            // Pages are visited in order, so only the last one can repeat
+            match by_url.get(&url) {
+                Some(&i) => {
+                    if links[i].pages.last() != Some(&page.number) {
+                        links[i].pages.push(page.number);
+                    }
+                }
+                None => {
+                    by_url.insert(url.clone(), links.len());
+                    links.push(DocumentLink {
+                        kind,
+                        url,
+                        text: text.to_string(),
+                        pages: vec![page.number],
+                    });
+                }
+            }
+        }
+    }
+
+    links
+}
+
+/// Treat links to doi.org and arxiv.org as the identifiers they resolve
+fn classify_url(text: &str) -> Option<(LinkKind, String, &str)> {
+    let url = if text.to_lowercase().starts_with("www.") {
+        format!("https://{}", text)
+    } else {
+        text.to_string()
+    };
+    let rest = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let host = host.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    if host.is_empty() || !host.contains('.') {
+        return None;
+    }
+
+    let arxiv_id = path.strip_prefix("abs/").or_else(|| path.strip_prefix("pdf/"));
+    let (kind, normalized) = match (host, arxiv_id) {
+        ("doi.org" | "dx.doi.org", _) if path.starts_with("10.") => {
+            (LinkKind::Doi, doi_url(path))
+        }
+        ("arxiv.org", Some(id)) if !id.is_empty() => {
+            (LinkKind::Arxiv, arxiv_url(id.trim_end_matches(".pdf")))
+        }
+        _ => (LinkKind::Url, url),
+    };
+    Some((kind, normalized, text))
+}
+
+/// DOIs are case-insensitive, so they are lowercased to compare equal
+fn doi_url(doi: &str) -> String {
+    let doi = doi.trim();
+    let doi = if doi.len() > 4 && doi[..4].eq_ignore_ascii_case("doi:") {
+        doi[4..].trim_start()
+    } else {
+        doi
+    };
+    format!("https://doi.org/{}", doi.to_lowercase())
+}
+
+fn arxiv_url(id: &str) -> String {
+    format!("https://arxiv.org/abs/{}", id.to_lowercase())
+}
+
+/// Drop sentence punctuation and unbalanced closing brackets that follow a link
+fn trim_trailing(mut text: &str) -> &str {
+    loop {
+        let unbalanced = |open: char, close: char| {
+            text.ends_with(close) && text.matches(open).count() < text.matches(close).count()
+        };
+        if text.ends_with(['.', ',', ';', ':', '!', '?', '"', '\''])
+            || unbalanced('(', ')')
+            || unbalanced('[', ']')
+        {
+            text = &text[..text.len() - 1];
+        } else {
+            return text;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    /// Document with one paragraph per page
+    fn document(pages: &[&str]) -> Document {
+        let pages: Vec<&[&str]> = pages.iter().map(std::slice::from_ref).collect();
+        test_document(&pages)
+    }
+
+    #[test]
+    fn test_urls_dois_and_arxiv_ids_extracted_and_normalized() {
+        let doc = document(&[
+            "Code is at https://github.com/org/repo. See (www.example.org/docs) for more.",
+            "Published as doi:10.1145/3292500.3330701, preprint arXiv:1706.03762v5.",
+            "Also https://doi.org/10.1145/3292500.3330701 and https://arxiv.org/abs/1706.03762v5; \
+             the repo again: https://github.com/org/repo",
+        ]);
+
+        let links = extract_links(&doc);
+        let summary: Vec<_> = links
+            .iter()
+            .map(|l| (l.kind, l.url.as_str(), l.pages.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (LinkKind::Url, "https://github.com/org/repo", vec![1, 3]),
+                (LinkKind::Url, "https://www.example.org/docs", vec![1]),
+                (LinkKind::Doi, "https://doi.org/10.1145/3292500.3330701", vec![2, 3]),
+                (LinkKind::Arxiv, "https://arxiv.org/abs/1706.03762v5", vec![2, 3]),
+            ]
+        );
+        assert_eq!(links[2].text, "doi:10.1145/3292500.3330701");
+        assert_eq!(links[3].text, "arXiv:1706.03762v5");
+    }
+
+    #[test]
+    fn test_brackets_kept_only_when_balanced() {
+        let doc = document(&["See https://en.wikipedia.org/wiki/Rust_(programming_language)."]);
+        assert_eq!(
+            extract_links(&doc)[0].url,
+            "https://en.wikipedia.org/wiki/Rust_(programming_language)"
+        );
+    }
+}
@@ -1,18 +1,55 @@
 //! Document parsing and management module
 
+pub mod bibtex;
+pub mod compare;
+pub mod difficulty;
 pub mod editor;
+pub mod flatten;
+pub mod headings;
+pub mod images;
+pub mod layout;
+pub mod latex;
+pub mod links;
+pub mod model;
 pub mod ocr;
+pub mod outline;
+pub mod output_path;
 pub mod parser;
+pub mod pdf_edit;
+pub mod reading;
+pub mod scan;
+pub mod sections;
+pub mod search;
+pub mod selection;
+pub mod signature;
+
+pub use compare::{diff_documents, ChangeKind, ParagraphChange};
+pub use difficulty::{section_difficulty, SectionDifficulty};
+pub use headings::DetectedHeading;
+pub use images::{extract_page_images, ImageDescription, PageImage};
+pub use latex::LatexError;
+pub use links::{extract_links, DocumentLink, LinkKind};
+pub use outline::get_outline;
+pub use output_path::OutputSettings;
+pub use sections::{segment_sections, Section};
+pub use reading::{ReadingAnalytics, ReadingMode, ReadingSession};
+pub use scan::{is_scanned_pdf, PageSample, ScanStatus};
+pub use search::{search_document, PageMatches, TextMatch};
+pub use selection::{
+    paragraph_words, resolve_selection, selected_words, ParagraphWord, SelectionContext,
+};
+pub use signature::SignatureStatus;
 
 // Re-export editor types
 pub use editor::{
     // Common types
-    BoundingBox, CommonEditOperation, EditorConfig, EditorError, TextFormat, TextPosition,
-    TextRange, WordStats,
+    BoundingBox, CommonEditOperation, EditorConfig, EditorError, HtmlAllowlist, MathKind, MathSpan,
+    TextFormat, TextPosition, TextRange, WordStats,
     // PDF types
-    ImageFormat, PDFEditOperation, PDFEditor, PDFUtils, ShapeType, WatermarkPosition,
+    ImageFormat, PDFEditOperation, PDFEditor, PDFUtils, PdfMetadataUpdate, RenderedPage,
+    ShapeType, WatermarkPosition,
     // Text/Markdown types
-    TextEditOperation, TextEditor,
+    PreviewBlock, PreviewUpdate, SourceRange, TextEditOperation, TextEditor,
     // DOCX types
     DOCXEditOperation, DOCXEditor, TableOperation,
     // LaTeX types
@@ -20,10 +57,71 @@ pub use editor::{
     // EPUB types
     EPUBEditOperation, EPUBEditor, MetadataField, TOCEntry,
     // Unified types
-    ConversionUtils, DocumentEditor, EditOperation, EditOperationInfo,
+    CombineFailure, CombineReport, ConversionUtils, DocumentEditor, EditOperation,
+    EditOperationInfo,
 };
 
+use crate::error::AppError;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Stream a reader through SHA-256, handing each chunk to `on_chunk`
+fn hash_chunks<R: Read>(mut reader: R, mut on_chunk: impl FnMut(&[u8])) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buf[..n]);
+        on_chunk(&buf[..n]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Compute a document's id (SHA-256 of its bytes) by streaming the file
+pub fn hash_file(path: &str) -> Result<String, AppError> {
+    Ok(hash_chunks(std::fs::File::open(path)?, |_| {})?)
+}
+
+/// Read a file and compute its id in a single pass
+pub(crate) fn read_and_hash(path: &str) -> Result<(Vec<u8>, String), AppError> {
+    let file = std::fs::File::open(path)?;
+    let size = file.metadata().map(|m| m.len() as usize).unwrap_or(0);
+    let mut content = Vec::with_capacity(size);
+    let id = hash_chunks(file, |chunk| content.extend_from_slice(chunk))?;
+    Ok((content, id))
+}
+
+/// Hash of a document's extracted text with whitespace collapsed, so files that differ
+/// only in metadata (e.g. a PDF's producer timestamp) share a fingerprint.
+///
+/// Documents without any text fall back to their byte hash, so unrelated scans are not
+/// treated as the same document.
+pub fn content_fingerprint(doc: &Document) -> String {
+    let mut hasher = Sha256::new();
+    let mut empty = true;
+    for word in doc.pages.iter().flat_map(|p| p.text.split_whitespace()) {
+        if !empty {
+            hasher.update(b" ");
+        }
+        hasher.update(word.as_bytes());
+        empty = false;
+    }
+
+    if empty {
+        doc.id.clone()
+    } else {
+        format!("{:x}", hasher.finalize())
+    }
+}
 
 /// Supported document types
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -131,6 +229,69 @@ pub struct Page {
     pub text: String,
     /// Structured paragraphs
     pub paragraphs: Vec<Paragraph>,
+    /// Where the page text came from
+    #[serde(default)]
+    pub source: TextSource,
+}
+
+/// Origin of a page's text
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextSource {
+    /// Text layer stored in the file
+    #[default]
+    Native,
+    /// Recognized from the rendered page image
+    Ocr,
+    /// No text could be extracted
+    Empty,
+}
+
+impl TextSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Ocr => "ocr",
+            Self::Empty => "empty",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "native" => Some(Self::Native),
+            "ocr" => Some(Self::Ocr),
+            "empty" => Some(Self::Empty),
+            _ => None,
+        }
+    }
+}
+
+/// Text source of a single page
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PageSource {
+    pub page: u32,
+    pub source: TextSource,
+}
+
+impl Document {
+    /// Per-page text sources, in page order
+    pub fn page_sources(&self) -> Vec<PageSource> {
+        self.pages
+            .iter()
+            .map(|p| PageSource {
+                page: p.number,
+                source: p.source,
+            })
+            .collect()
+    }
+
+    /// Every paragraph with its page number, in reading order
+    pub fn paragraphs(&self) -> Vec<(u32, &Paragraph)> {
+        self.pages
+            .iter()
+            .flat_map(|page| page.paragraphs.iter().map(move |p| (page.number, p)))
+            .collect()
+    }
 }
 
 /// Paragraph within a page
@@ -140,7 +301,8 @@ pub struct Paragraph {
     pub id: String,
     /// Paragraph text
     pub text: String,
-    /// Position information
+    /// Where the paragraph is drawn, in PDF points from the page's top-left corner.
+    /// Only known for PDF pages with a text layer.
     pub bounding_box: Option<editor::BoundingBox>,
 }
 
@@ -149,12 +311,41 @@ pub struct Paragraph {
 pub struct DocumentMetadata {
     pub page_count: u32,
     pub word_count: u32,
+    /// Title stored in the file's own properties
+    pub title: Option<String>,
     pub creation_date: Option<String>,
     pub modification_date: Option<String>,
     pub subject: Option<String>,
     pub keywords: Vec<String>,
 }
 
+/// Options controlling how documents are parsed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParseOptions {
+    /// Apply NFKC normalization to extracted text, expanding ligatures such as "ﬁ".
+    /// Disable to keep the text exactly as stored in the file.
+    pub normalize_unicode: bool,
+    /// Text already recognized for pages of a scanned PDF, by page number, used in
+    /// place of running OCR on them again
+    #[serde(skip)]
+    pub ocr_pages: HashMap<u32, String>,
+    /// OCR pages of a PDF that have neither a text layer nor cached text. When off,
+    /// such pages are left empty.
+    #[serde(skip)]
+    pub ocr_uncached_pages: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            normalize_unicode: true,
+            ocr_pages: HashMap::new(),
+            ocr_uncached_pages: true,
+        }
+    }
+}
+
 /// Recent document info for display
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecentDocument {
@@ -166,3 +357,75 @@ pub struct RecentDocument {
     pub last_opened: String,
     pub page_count: u32,
 }
+
+/// Document with one page per entry of `pages`, each holding the given paragraphs.
+/// Paragraph ids run p1, p2, ... across the whole document.
+#[cfg(test)]
+pub(crate) fn test_document(pages: &[&[&str]]) -> Document {
+    let mut next_id = 0;
+    let pages = pages
+        .iter()
+        .enumerate()
+        .map(|(i, texts)| Page {
+            number: i as u32 + 1,
+            text: texts.join("\n\n"),
+            paragraphs: texts
+                .iter()
+                .map(|text| {
+                    next_id += 1;
+                    Paragraph {
+                        id: format!("p{}", next_id),
+                        text: text.to_string(),
+                        bounding_box: None,
+                    }
+                })
+                .collect(),
+            source: TextSource::Native,
+        })
+        .collect();
+
+    Document {
+        id: "doc".to_string(),
+        doc_type: DocumentType::Pdf,
+        path: "paper.pdf".to_string(),
+        title: "Paper".to_string(),
+        authors: Vec::new(),
+        pages,
+        metadata: DocumentMetadata::default(),
+        category: Category::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_file_matches_in_memory_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.txt");
+        // Larger than the copy buffer so the hash spans several reads
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let streamed = hash_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(streamed, parser::generate_document_id(&content));
+    }
+
+    #[test]
+    fn test_read_and_hash_single_pass() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doc.txt");
+        let content = "Some paragraph.\n\n".repeat(10_000).into_bytes();
+        std::fs::write(&path, &content).unwrap();
+
+        let (bytes, id) = read_and_hash(path.to_str().unwrap()).unwrap();
+        assert_eq!(bytes, content);
+        assert_eq!(id, parser::generate_document_id(&content));
+    }
+
+    #[test]
+    fn test_hash_file_missing() {
+        assert!(hash_file("/nonexistent/file.pdf").is_err());
+    }
+}
@@ -0,0 +1,438 @@
+//! In-memory content models for DOCX and EPUB editing
+//!
+//! Editors apply queued operations to these models as they are added, and record a
+//! snapshot of the model before each one so undo and redo restore the content
+//! itself rather than only the operation queue. Previews read the model, so they show
+//! the edits before they are saved.
+
+use super::editor::{
+    CommonEditOperation, DOCXEditOperation, EPUBEditOperation, MetadataField, TOCEntry,
+    TextPosition,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+
+/// Undo and redo stacks of whole-model snapshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotHistory<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+}
+
+impl<T> Default for SnapshotHistory<T> {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+impl<T> SnapshotHistory<T> {
+    /// Record the state before a new change, discarding anything that could be redone
+    pub fn record(&mut self, before: T) {
+        self.undo.push(before);
+        self.redo.clear();
+    }
+
+    /// Restore the state before the last change. Returns false if there is none.
+    pub fn undo(&mut self, current: &mut T) -> bool {
+        match self.undo.pop() {
+            Some(previous) => {
+                self.redo.push(std::mem::replace(current, previous));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Restore the state after the last undone change. Returns false if there is none.
+    pub fn redo(&mut self, current: &mut T) -> bool {
+        match self.redo.pop() {
+            Some(next) => {
+                self.undo.push(std::mem::replace(current, next));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Restore the state before the first recorded change and forget the history
+    pub fn reset(&mut self, current: &mut T) {
+        if !self.undo.is_empty() {
+            *current = self.undo.swap_remove(0);
+        }
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Page margins in points
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PageMargins {
+    pub top: f32,
+    pub bottom: f32,
+    pub left: f32,
+    pub right: f32,
+}
+
+/// Editable content of a DOCX document
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DocxModel {
+    /// Paragraph texts in body order; text positions address these as lines
+    pub paragraphs: Vec<String>,
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    /// Margins set while editing; `None` keeps the document's own
+    pub margins: Option<PageMargins>,
+}
+
+impl DocxModel {
+    /// Read paragraph text from a `.docx`, or an empty model if it can't be read
+    pub fn load(path: &str) -> Self {
+        match read_docx_paragraphs(path) {
+            Some(paragraphs) => Self {
+                paragraphs,
+                ..Default::default()
+            },
+            None => {
+                tracing::debug!("No DOCX body read from {}", path);
+                Self::default()
+            }
+        }
+    }
+
+    /// Apply an operation. Formatting, tables and tracked changes are not modelled
+    /// and leave the model unchanged.
+    pub fn apply(&mut self, operation: &DOCXEditOperation) {
+        match operation {
+            DOCXEditOperation::Common(common) => apply_text_edit(&mut self.paragraphs, common),
+            DOCXEditOperation::SetPageMargins {
+                top,
+                bottom,
+                left,
+                right,
+            } => {
+                self.margins = Some(PageMargins {
+                    top: *top,
+                    bottom: *bottom,
+                    left: *left,
+                    right: *right,
+                });
+            }
+            DOCXEditOperation::SetHeaderFooter { is_header, content } => {
+                let target = if *is_header {
+                    &mut self.header
+                } else {
+                    &mut self.footer
+                };
+                *target = Some(content.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn read_docx_paragraphs(path: &str) -> Option<Vec<String>> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut archive = zip::ZipArchive::new(file).ok()?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .ok()?
+        .read_to_string(&mut xml)
+        .ok()?;
+    let doc = roxmltree::Document::parse(&xml).ok()?;
+
+    let paragraphs = doc
+        .descendants()
+        .filter(|n| n.tag_name().name() == "p")
+        .map(|p| {
+            p.descendants()
+                .filter(|n| n.tag_name().name() == "t")
+                .filter_map(|n| n.text())
+                .collect()
+        })
+        .collect();
+    Some(paragraphs)
+}
+
+/// Apply a text edit to lines, addressing columns in characters
+fn apply_text_edit(lines: &mut Vec<String>, operation: &CommonEditOperation) {
+    let edited = match operation {
+        CommonEditOperation::InsertText { position, text } => {
+            replace_between(lines, position, position, text)
+        }
+        CommonEditOperation::DeleteText { range } => {
+            replace_between(lines, &range.start, &range.end, "")
+        }
+        CommonEditOperation::ReplaceText { range, new_text } => {
+            replace_between(lines, &range.start, &range.end, new_text)
+        }
+        CommonEditOperation::FindReplace {
+            pattern,
+            replacement,
+            use_regex,
+            case_sensitive,
+            whole_word,
+        } => {
+            let mut pattern = if *use_regex {
+                pattern.clone()
+            } else {
+                regex::escape(pattern)
+            };
+            if *whole_word {
+                pattern = format!(r"\b(?:{})\b", pattern);
+            }
+            let Ok(re) = regex::RegexBuilder::new(&pattern)
+                .case_insensitive(!case_sensitive)
+                .build()
+            else {
+                return;
+            };
+            lines
+                .iter()
+                .map(|line| {
+                    // Literal replacements must not expand `$` group references
+                    if *use_regex {
+                        re.replace_all(line, replacement.as_str()).into_owned()
+                    } else {
+                        re.replace_all(line, regex::NoExpand(replacement)).into_owned()
+                    }
+                })
+                .collect()
+        }
+        CommonEditOperation::SetFormat { .. } | CommonEditOperation::InsertImage { .. } => return,
+    };
+    *lines = edited;
+}
+
+fn replace_between(
+    lines: &[String],
+    start: &TextPosition,
+    end: &TextPosition,
+    replacement: &str,
+) -> Vec<String> {
+    let text = lines.join("\n");
+    let offset = |position: &TextPosition| {
+        let line_start: usize = lines
+            .iter()
+            .take(position.line as usize)
+            .map(|line| line.len() + 1)
+            .sum::<usize>()
+            .min(text.len());
+        let line = lines.get(position.line as usize).map_or("", String::as_str);
+        line_start
+            + line
+                .char_indices()
+                .nth(position.column as usize)
+                .map_or(line.len(), |(i, _)| i)
+    };
+    let (start, end) = (offset(start), offset(end));
+    let (start, end) = (start.min(end), start.max(end));
+
+    let edited = format!("{}{}{}", &text[..start], replacement, &text[end..]);
+    if lines.is_empty() && edited.is_empty() {
+        return Vec::new();
+    }
+    edited.split('\n').map(str::to_string).collect()
+}
+
+/// A chapter in an EPUB's reading order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EpubChapter {
+    pub id: String,
+    pub title: Option<String>,
+    /// Content of chapters added while editing; existing chapters keep theirs on disk
+    pub content: Option<String>,
+}
+
+/// Editable structure and metadata of an EPUB
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EpubModel {
+    /// Dublin Core metadata by field name
+    pub metadata: BTreeMap<String, String>,
+    pub chapters: Vec<EpubChapter>,
+    /// Replacement CSS by stylesheet id
+    pub stylesheets: BTreeMap<String, String>,
+    pub cover_image: Option<String>,
+    /// Replacement table of contents, if one has been set
+    pub toc: Option<Vec<TOCEntry>>,
+}
+
+impl EpubModel {
+    /// Read metadata and reading order from an EPUB, or an empty model if it can't be read
+    pub fn load(path: &str) -> Self {
+        read_epub_model(path).unwrap_or_else(|| {
+            tracing::debug!("No EPUB package read from {}", path);
+            Self::default()
+        })
+    }
+
+    /// Apply an operation. Text edits inside chapters are not modelled.
+    pub fn apply(&mut self, operation: &EPUBEditOperation) {
+        match operation {
+            EPUBEditOperation::Common(_) => {}
+            EPUBEditOperation::ModifyMetadata { field, value } => {
+                self.metadata
+                    .insert(metadata_key(field).to_string(), value.clone());
+            }
+            EPUBEditOperation::UpdateTOC { entries } => self.toc = Some(entries.clone()),
+            EPUBEditOperation::ModifyCSS { stylesheet_id, css } => {
+                self.stylesheets.insert(stylesheet_id.clone(), css.clone());
+            }
+            EPUBEditOperation::ReorderChapters { new_order } => {
+                // Chapters missing from the new order keep their relative order at the end
+                let rank = |chapter: &EpubChapter| {
+                    new_order
+                        .iter()
+                        .position(|id| *id == chapter.id)
+                        .unwrap_or(new_order.len())
+                };
+                self.chapters.sort_by_key(rank);
+            }
+            EPUBEditOperation::SetCoverImage { image_path } => {
+                self.cover_image = Some(image_path.clone());
+            }
+            EPUBEditOperation::AddChapter {
+                title,
+                content,
+                after_chapter,
+            } => {
+                let chapter = EpubChapter {
+                    id: self.new_chapter_id(),
+                    title: Some(title.clone()),
+                    content: Some(content.clone()),
+                };
+                let index = after_chapter
+                    .as_ref()
+                    .and_then(|after| self.chapters.iter().position(|c| c.id == *after))
+                    .map_or(self.chapters.len(), |i| i + 1);
+                self.chapters.insert(index, chapter);
+            }
+            EPUBEditOperation::DeleteChapter { chapter_id } => {
+                self.chapters.retain(|c| c.id != *chapter_id);
+            }
+        }
+    }
+
+    fn new_chapter_id(&self) -> String {
+        (self.chapters.len() + 1..)
+            .map(|n| format!("chapter-{}", n))
+            .find(|id| !self.chapters.iter().any(|c| c.id == *id))
+            .unwrap_or_default()
+    }
+}
+
+fn metadata_key(field: &MetadataField) -> &'static str {
+    match field {
+        MetadataField::Title => "title",
+        MetadataField::Author => "creator",
+        MetadataField::Publisher => "publisher",
+        MetadataField::Language => "language",
+        MetadataField::Description => "description",
+        MetadataField::Subject => "subject",
+        MetadataField::Date => "date",
+        MetadataField::Rights => "rights",
+        MetadataField::Identifier => "identifier",
+    }
+}
+
+fn read_epub_model(path: &str) -> Option<EpubModel> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut archive = zip::ZipArchive::new(file).ok()?;
+    let mut read_entry = |name: &str| {
+        let mut content = String::new();
+        archive.by_name(name).ok()?.read_to_string(&mut content).ok()?;
+        Some(content)
+    };
+
+    let container = read_entry("META-INF/container.xml")?;
+    let container = super::outline::parse_xml(&container)?;
+    let opf_path = container
+        .descendants()
+        .find(|n| n.has_tag_name("rootfile"))?
+        .attribute("full-path")?
+        .to_string();
+    let opf = read_entry(&opf_path)?;
+    let opf = super::outline::parse_xml(&opf)?;
+
+    const DC: &str = "http://purl.org/dc/elements/1.1/";
+    let mut model = EpubModel::default();
+    for node in opf.descendants().filter(|n| n.tag_name().namespace() == Some(DC)) {
+        if let Some(text) = node.text().map(str::trim).filter(|t| !t.is_empty()) {
+            model
+                .metadata
+                .entry(node.tag_name().name().to_string())
+                .or_insert_with(|| text.to_string());
+        }
+    }
+    model.chapters = opf
+        .descendants()
+        .filter(|n| n.has_tag_name("itemref"))
+        .filter_map(|n| n.attribute("idref"))
+        .map(|id| EpubChapter {
+            id: id.to_string(),
+            title: None,
+            content: None,
+        })
+        .collect();
+    Some(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::editor::TextRange;
+
+    fn position(line: u32, column: u32) -> TextPosition {
+        TextPosition { line, column }
+    }
+
+    #[test]
+    fn test_text_edits_address_paragraphs_as_lines() {
+        let mut model = DocxModel {
+            paragraphs: vec!["Première ligne".to_string(), "Second line".to_string()],
+            ..Default::default()
+        };
+
+        model.apply(&DOCXEditOperation::Common(CommonEditOperation::ReplaceText {
+            range: TextRange {
+                start: position(0, 9),
+                end: position(1, 6),
+            },
+            new_text: "note\nFinal".to_string(),
+        }));
+        assert_eq!(model.paragraphs, ["Première note", "Final line"]);
+
+        model.apply(&DOCXEditOperation::Common(CommonEditOperation::FindReplace {
+            pattern: "NOTE".to_string(),
+            replacement: "entrée".to_string(),
+            use_regex: false,
+            case_sensitive: false,
+            whole_word: true,
+        }));
+        assert_eq!(model.paragraphs, ["Première entrée", "Final line"]);
+    }
+
+    #[test]
+    fn test_snapshot_history_undo_redo_and_reset() {
+        let mut history = SnapshotHistory::default();
+        let mut state = 1;
+        for next in [2, 3] {
+            history.record(state);
+            state = next;
+        }
+
+        assert!(history.undo(&mut state));
+        assert_eq!(state, 2);
+        assert!(history.redo(&mut state));
+        assert_eq!(state, 3);
+        assert!(!history.redo(&mut state));
+
+        history.reset(&mut state);
+        assert_eq!(state, 1);
+        assert!(!history.undo(&mut state));
+    }
+}
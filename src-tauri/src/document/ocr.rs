@@ -5,6 +5,8 @@
 
 use crate::error::AppError;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tempfile::TempDir;
 use tracing::{info, warn};
 
@@ -15,6 +17,12 @@ pub struct OcrConfig {
     pub language: String,
     /// DPI for PDF to image conversion
     pub dpi: u32,
+    /// Highest DPI to escalate to when a pass recognizes too little text
+    pub max_dpi: u32,
+    /// Characters a pass must recognize before it is accepted without escalating
+    pub min_chars: usize,
+    /// When set, OCR stops before starting another pdftoppm or Tesseract run
+    pub cancelled: Option<Arc<AtomicBool>>,
 }
 
 impl Default for OcrConfig {
@@ -22,6 +30,33 @@ impl Default for OcrConfig {
         Self {
             language: "eng".to_string(),
             dpi: 300,
+            // Page images grow with the square of the DPI, so keep the ceiling modest
+            max_dpi: 600,
+            min_chars: 50,
+            cancelled: None,
+        }
+    }
+}
+
+impl OcrConfig {
+    /// DPIs to try in order: the base DPI, then 1.5x steps up to `max_dpi`
+    fn dpi_ladder(&self) -> Vec<u32> {
+        let mut ladder = vec![self.dpi];
+        let mut dpi = self.dpi;
+        while dpi < self.max_dpi {
+            dpi = (dpi * 3 / 2).max(dpi + 1).min(self.max_dpi);
+            ladder.push(dpi);
+        }
+        ladder
+    }
+
+    /// Fail if the run was cancelled, so no further child process is started
+    fn check_cancelled(&self) -> Result<(), AppError> {
+        match &self.cancelled {
+            Some(cancelled) if cancelled.load(Ordering::SeqCst) => {
+                Err(crate::error::DocumentError::ParseError("OCR was cancelled".to_string()).into())
+            }
+            _ => Ok(()),
         }
     }
 }
@@ -37,6 +72,58 @@ pub struct OcrResult {
     pub success: bool,
     /// Any warnings or notes
     pub notes: Vec<String>,
+    /// DPI of the pass whose text was kept
+    pub dpi: u32,
+}
+
+/// Run `attempt` at increasing DPIs until it recognizes at least `config.min_chars`
+/// characters, returning the best result and the DPI that produced it.
+///
+/// Errors are only returned if no pass succeeded at all.
+async fn with_dpi_escalation<T, F, Fut>(
+    config: &OcrConfig,
+    measure: impl Fn(&T) -> usize,
+    mut attempt: F,
+) -> Result<(T, u32), AppError>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: std::future::Future<Output = Result<T, AppError>>,
+{
+    let mut best: Option<(T, u32, usize)> = None;
+    let mut last_error = None;
+
+    for dpi in config.dpi_ladder() {
+        config.check_cancelled()?;
+        match attempt(dpi).await {
+            Ok(result) => {
+                let chars = measure(&result);
+                if chars >= config.min_chars {
+                    if dpi != config.dpi {
+                        info!("OCR recognized {} chars after escalating to {} DPI", chars, dpi);
+                    }
+                    return Ok((result, dpi));
+                }
+                warn!("OCR at {} DPI recognized only {} chars", dpi, chars);
+                if best.as_ref().map_or(true, |(_, _, c)| chars > *c) {
+                    best = Some((result, dpi, chars));
+                }
+            }
+            Err(e) => {
+                warn!("OCR at {} DPI failed: {}", dpi, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    match (best, last_error) {
+        (Some((result, dpi, _)), _) => Ok((result, dpi)),
+        (None, Some(e)) => Err(e),
+        (None, None) => unreachable!("the DPI ladder always has at least one step"),
+    }
+}
+
+fn recognized_chars(text: &str) -> usize {
+    text.chars().filter(|c| !c.is_whitespace()).count()
 }
 
 /// Check if Tesseract is available on the system
@@ -68,6 +155,7 @@ pub async fn ocr_pdf(pdf_path: &str, config: &OcrConfig) -> Result<OcrResult, Ap
             page_count: 0,
             success: false,
             notes: vec!["Poppler (pdftoppm) is not installed. Run: brew install poppler".to_string()],
+            dpi: config.dpi,
         });
     }
 
@@ -77,9 +165,22 @@ pub async fn ocr_pdf(pdf_path: &str, config: &OcrConfig) -> Result<OcrResult, Ap
             page_count: 0,
             success: false,
             notes: vec!["Tesseract OCR is not installed. Run: brew install tesseract".to_string()],
+            dpi: config.dpi,
         });
     }
 
+    let (result, dpi) = with_dpi_escalation(
+        config,
+        |r: &OcrResult| recognized_chars(&r.text),
+        |dpi| ocr_pdf_at_dpi(pdf_path, config, dpi),
+    )
+    .await?;
+
+    Ok(OcrResult { dpi, ..result })
+}
+
+/// One OCR pass over every page of a PDF rendered at `dpi`
+async fn ocr_pdf_at_dpi(pdf_path: &str, config: &OcrConfig, dpi: u32) -> Result<OcrResult, AppError> {
     // Create temp directory for images
     let temp_dir = TempDir::new()
         .map_err(|e| crate::error::DocumentError::ParseError(format!("Failed to create temp dir: {}", e)))?;
@@ -88,11 +189,11 @@ pub async fn ocr_pdf(pdf_path: &str, config: &OcrConfig) -> Result<OcrResult, Ap
     let image_prefix = temp_path.join("page");
 
     // Convert PDF to images using pdftoppm
-    info!("Converting PDF to images at {} DPI...", config.dpi);
+    info!("Converting PDF to images at {} DPI...", dpi);
     let pdf_convert = Command::new("pdftoppm")
         .args([
             "-png",
-            "-r", &config.dpi.to_string(),
+            "-r", &dpi.to_string(),
             pdf_path,
             image_prefix.to_str().unwrap(),
         ])
@@ -120,6 +221,7 @@ pub async fn ocr_pdf(pdf_path: &str, config: &OcrConfig) -> Result<OcrResult, Ap
             page_count: 0,
             success: false,
             notes: vec!["No pages could be extracted from PDF".to_string()],
+            dpi,
         });
     }
 
@@ -130,6 +232,7 @@ pub async fn ocr_pdf(pdf_path: &str, config: &OcrConfig) -> Result<OcrResult, Ap
     let mut notes = Vec::new();
 
     for (i, entry) in image_files.iter().enumerate() {
+        config.check_cancelled()?;
         let image_path = entry.path();
         let output_base = temp_path.join(format!("ocr_output_{}", i));
 
@@ -180,9 +283,101 @@ pub async fn ocr_pdf(pdf_path: &str, config: &OcrConfig) -> Result<OcrResult, Ap
         page_count,
         success,
         notes,
+        dpi,
     })
 }
 
+/// Perform OCR on one page (1-indexed) of a PDF, escalating DPI on a poor result
+pub async fn ocr_pdf_page(pdf_path: &str, page: u32, config: &OcrConfig) -> Result<OcrResult, AppError> {
+    if !is_poppler_available() || !is_tesseract_available() {
+        return Err(crate::error::DocumentError::ParseError(
+            "OCR requires Tesseract and Poppler to be installed".to_string(),
+        )
+        .into());
+    }
+
+    let (text, dpi) = with_dpi_escalation(
+        config,
+        |text: &String| recognized_chars(text),
+        |dpi| ocr_pdf_page_at_dpi(pdf_path, page, config, dpi),
+    )
+    .await?;
+
+    Ok(OcrResult {
+        success: !text.trim().is_empty(),
+        text,
+        page_count: 1,
+        notes: Vec::new(),
+        dpi,
+    })
+}
+
+async fn ocr_pdf_page_at_dpi(
+    pdf_path: &str,
+    page: u32,
+    config: &OcrConfig,
+    dpi: u32,
+) -> Result<String, AppError> {
+    let temp_dir = TempDir::new()
+        .map_err(|e| crate::error::DocumentError::ParseError(format!("Failed to create temp dir: {}", e)))?;
+    let image_path = render_pdf_page(pdf_path, page, dpi, "png", &temp_dir.path().join("page"))?;
+
+    config.check_cancelled()?;
+    ocr_image(&image_path.to_string_lossy(), &config.language).await
+}
+
+/// Render one page (1-indexed) of a PDF with pdftoppm.
+///
+/// `format` is a pdftoppm output format ("png", "jpeg" or "tiff"); the image is written
+/// next to `output_base` with pdftoppm's extension for that format, and its path returned.
+pub fn render_pdf_page(
+    pdf_path: &str,
+    page: u32,
+    dpi: u32,
+    format: &str,
+    output_base: &std::path::Path,
+) -> Result<std::path::PathBuf, AppError> {
+    let extension = pdftoppm_extension(format)
+        .ok_or_else(|| crate::error::DocumentError::UnsupportedFormat(format.to_string()))?;
+    let page_arg = page.to_string();
+
+    let convert = Command::new("pdftoppm")
+        .args([
+            &format!("-{}", format),
+            "-r", &dpi.to_string(),
+            "-f", &page_arg,
+            "-l", &page_arg,
+            "-singlefile",
+            pdf_path,
+            output_base.to_str().unwrap(),
+        ])
+        .output()
+        .map_err(|e| crate::error::DocumentError::ParseError(format!("pdftoppm failed: {}", e)))?;
+
+    let image_path = output_base.with_extension(extension);
+    if !convert.status.success() || !image_path.exists() {
+        let stderr = String::from_utf8_lossy(&convert.stderr);
+        return Err(crate::error::DocumentError::ParseError(format!(
+            "Could not render page {}: {}",
+            page,
+            stderr.trim()
+        ))
+        .into());
+    }
+
+    Ok(image_path)
+}
+
+/// Extension pdftoppm gives images written in `format`
+pub fn pdftoppm_extension(format: &str) -> Option<&'static str> {
+    match format {
+        "png" => Some("png"),
+        "jpeg" => Some("jpg"),
+        "tiff" => Some("tif"),
+        _ => None,
+    }
+}
+
 /// Perform OCR on a single image file using command-line tesseract
 pub async fn ocr_image(image_path: &str, language: &str) -> Result<String, AppError> {
     let temp_dir = TempDir::new()
@@ -249,4 +444,76 @@ mod tests {
         println!("Available OCR languages: {:?}", langs);
         assert!(!langs.is_empty());
     }
+
+    #[test]
+    fn test_dpi_ladder_capped() {
+        let config = OcrConfig::default();
+        assert_eq!(config.dpi_ladder(), [300, 450, 600]);
+
+        let no_escalation = OcrConfig {
+            max_dpi: 300,
+            ..OcrConfig::default()
+        };
+        assert_eq!(no_escalation.dpi_ladder(), [300]);
+    }
+
+    #[tokio::test]
+    async fn test_escalation_retries_at_higher_dpi() {
+        let config = OcrConfig::default();
+        let mut tried = Vec::new();
+
+        // Mock OCR: blank at low resolution, legible from 450 DPI up
+        let (text, dpi) = with_dpi_escalation(&config, |t: &String| recognized_chars(t), |dpi| {
+            tried.push(dpi);
+            let text = if dpi >= 450 {
+                "The quick brown fox jumps over the lazy dog near the river bank."
+            } else {
+                "  "
+            };
+            async move { Ok(text.to_string()) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(dpi, 450);
+        assert!(text.starts_with("The quick brown fox"));
+        assert_eq!(tried, [300, 450]);
+    }
+
+    #[tokio::test]
+    async fn test_escalation_stops_at_cap_and_keeps_best() {
+        let config = OcrConfig::default();
+        let mut tried = Vec::new();
+
+        let (text, dpi) = with_dpi_escalation(&config, |t: &String| recognized_chars(t), |dpi| {
+            tried.push(dpi);
+            let text = if dpi == 450 { "faint text" } else { "" };
+            async move { Ok(text.to_string()) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(tried, [300, 450, 600]);
+        assert_eq!((text.as_str(), dpi), ("faint text", 450));
+    }
+    #[tokio::test]
+    async fn test_escalation_stops_once_cancelled() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let config = OcrConfig {
+            cancelled: Some(cancelled.clone()),
+            ..OcrConfig::default()
+        };
+        let mut tried = Vec::new();
+
+        // Cancelled while the first pass runs, which finds too little text to accept
+        let result = with_dpi_escalation(&config, |t: &String| recognized_chars(t), |dpi| {
+            tried.push(dpi);
+            cancelled.store(true, Ordering::SeqCst);
+            async move { Ok(String::new()) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(tried, [300]);
+    }
 }
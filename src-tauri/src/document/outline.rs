@@ -0,0 +1,316 @@
+//! Unified document outline (PDF bookmarks, EPUB TOC, Markdown/LaTeX headings)
+//!
+//! PDFs without bookmarks fall back to headings detected from paragraph shape.
+
+use super::{Document, DocumentType, Paragraph, TOCEntry};
+use std::io::Read;
+use std::path::Path;
+
+/// Build a navigation outline for a document.
+///
+/// Entries target a page (`page`) and/or an anchor (`href`). Paragraph anchors use
+/// the form `#<paragraph id>`; EPUB entries keep the href from the book's nav.
+pub fn get_outline(doc: &Document) -> Vec<TOCEntry> {
+    let flat = match doc.doc_type {
+        DocumentType::Pdf => {
+            let bookmarks = pdf_bookmarks(&doc.path);
+            if bookmarks.is_empty() {
+                detected_headings(doc)
+            } else {
+                bookmarks
+            }
+        }
+        DocumentType::Epub => epub_toc(&doc.path),
+        DocumentType::Markdown => markdown_headings(doc),
+        DocumentType::Latex => latex_headings(doc),
+        DocumentType::Txt | DocumentType::Docx => Vec::new(),
+    };
+
+    nest_entries(flat)
+}
+
+/// Turn a flat list of (level, entry) pairs into a tree
+fn nest_entries(flat: Vec<(usize, TOCEntry)>) -> Vec<TOCEntry> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<(usize, TOCEntry)> = Vec::new();
+
+    fn attach(stack: &mut [(usize, TOCEntry)], roots: &mut Vec<TOCEntry>, entry: TOCEntry) {
+        match stack.last_mut() {
+            Some((_, parent)) => parent.children.push(entry),
+            None => roots.push(entry),
+        }
+    }
+
+    for (level, entry) in flat {
+        while stack.last().is_some_and(|(l, _)| *l >= level) {
+            let (_, done) = stack.pop().unwrap();
+            attach(&mut stack, &mut roots, done);
+        }
+        stack.push((level, entry));
+    }
+    while let Some((_, done)) = stack.pop() {
+        attach(&mut stack, &mut roots, done);
+    }
+
+    roots
+}
+
+/// Find the next paragraph (in reading order, after `cursor`) matching `pred`
+fn find_anchor<F>(doc: &Document, cursor: &mut usize, pred: F) -> (String, Option<u32>)
+where
+    F: Fn(&Paragraph) -> bool,
+{
+    let found = doc
+        .pages
+        .iter()
+        .flat_map(|page| page.paragraphs.iter().map(move |p| (page.number, p)))
+        .enumerate()
+        .skip(*cursor)
+        .find(|(_, (_, p))| pred(p));
+
+    match found {
+        Some((index, (page, paragraph))) => {
+            *cursor = index + 1;
+            (format!("#{}", paragraph.id), Some(page))
+        }
+        None => (String::new(), None),
+    }
+}
+
+fn entry(title: String, href: String, page: Option<u32>) -> TOCEntry {
+    TOCEntry {
+        title,
+        href,
+        page,
+        children: Vec::new(),
+    }
+}
+
+/// Headings detected from paragraph shape, for PDFs without bookmarks
+fn detected_headings(doc: &Document) -> Vec<(usize, TOCEntry)> {
+    super::headings::cached_headings(doc)
+        .into_iter()
+        .map(|h| {
+            let href = format!("#{}", h.paragraph_id);
+            (h.level, entry(h.title, href, Some(h.page)))
+        })
+        .collect()
+}
+
+/// Headings from Markdown source, anchored to their parsed paragraphs
+fn markdown_headings(doc: &Document) -> Vec<(usize, TOCEntry)> {
+    use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+    let Ok(source) = std::fs::read_to_string(&doc.path) else {
+        return Vec::new();
+    };
+
+    let mut headings = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for event in Parser::new(&source) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current = Some((level as usize, String::new()));
+            }
+            // Mirror the parser so titles match the end of heading paragraphs
+            Event::Text(text) => {
+                if let Some((_, title)) = current.as_mut() {
+                    title.push_str(&text);
+                }
+            }
+            Event::Code(code) => {
+                if let Some((_, title)) = current.as_mut() {
+                    title.push('`');
+                    title.push_str(&code);
+                    title.push('`');
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some((_, title)) = current.as_mut() {
+                    title.push(' ');
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(heading) = current.take() {
+                    headings.push(heading);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut cursor = 0;
+    headings
+        .into_iter()
+        .filter(|(_, title)| !title.trim().is_empty())
+        .map(|(level, title)| {
+            let (href, page) = find_anchor(doc, &mut cursor, |p| p.text.ends_with(&title));
+            (level, entry(title.trim().to_string(), href, page))
+        })
+        .collect()
+}
+
+/// Sectioning commands from LaTeX source, anchored to the paragraph containing them
+fn latex_headings(doc: &Document) -> Vec<(usize, TOCEntry)> {
+    let Ok(source) = std::fs::read_to_string(&doc.path) else {
+        return Vec::new();
+    };
+    let re = regex::Regex::new(
+        r"\\(part|chapter|section|subsection|subsubsection)\*?\{([^}]*)\}",
+    )
+    .unwrap();
+
+    let mut cursor = 0;
+    re.captures_iter(&source)
+        .map(|caps| {
+            let level = match &caps[1] {
+                "part" => 1,
+                "chapter" => 2,
+                "section" => 3,
+                "subsection" => 4,
+                _ => 5,
+            };
+            let command = caps[0].to_string();
+            let (href, page) = find_anchor(doc, &mut cursor, |p| p.text.contains(&command));
+            (level, entry(caps[2].trim().to_string(), href, page))
+        })
+        .collect()
+}
+
+/// Bookmarks from the PDF outline dictionary
+fn pdf_bookmarks(path: &str) -> Vec<(usize, TOCEntry)> {
+    let toc = match lopdf::Document::load(path).and_then(|pdf| pdf.get_toc()) {
+        Ok(toc) => toc,
+        Err(e) => {
+            tracing::debug!("No PDF outline for {}: {}", path, e);
+            return Vec::new();
+        }
+    };
+
+    toc.toc
+        .into_iter()
+        .map(|item| {
+            let page = item.page as u32;
+            (
+                item.level,
+                entry(item.title, format!("#page={}", page), Some(page)),
+            )
+        })
+        .collect()
+}
+
+/// Table of contents from an EPUB's nav document (EPUB 3) or NCX (EPUB 2)
+fn epub_toc(path: &str) -> Vec<(usize, TOCEntry)> {
+    match read_epub_toc(path) {
+        Some(entries) => entries,
+        None => {
+            tracing::debug!("No EPUB table of contents for {}", path);
+            Vec::new()
+        }
+    }
+}
+
+fn read_epub_toc(path: &str) -> Option<Vec<(usize, TOCEntry)>> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut archive = zip::ZipArchive::new(file).ok()?;
+
+    let read_entry = |archive: &mut zip::ZipArchive<std::fs::File>, name: &str| {
+        let mut content = String::new();
+        archive.by_name(name).ok()?.read_to_string(&mut content).ok()?;
+        Some(content)
+    };
+
+    let container = read_entry(&mut archive, "META-INF/container.xml")?;
+    let container = parse_xml(&container)?;
+    let opf_path = container
+        .descendants()
+        .find(|n| n.has_tag_name("rootfile"))?
+        .attribute("full-path")?
+        .to_string();
+
+    let opf = read_entry(&mut archive, &opf_path)?;
+    let opf = parse_xml(&opf)?;
+    let items: Vec<_> = opf.descendants().filter(|n| n.has_tag_name("item")).collect();
+    let opf_dir = Path::new(&opf_path).parent().unwrap_or(Path::new(""));
+    let resolve = |href: &str| opf_dir.join(href).to_string_lossy().replace('\\', "/");
+
+    let nav = items.iter().find(|n| {
+        n.attribute("properties")
+            .is_some_and(|p| p.split_whitespace().any(|p| p == "nav"))
+    });
+    if let Some(href) = nav.and_then(|n| n.attribute("href")) {
+        let nav = read_entry(&mut archive, &resolve(href))?;
+        let nav = parse_xml(&nav)?;
+        let toc = nav
+            .descendants()
+            .filter(|n| n.has_tag_name("nav"))
+            .find(|n| n.attributes().any(|a| a.name() == "type" && a.value() == "toc"))
+            .or_else(|| nav.descendants().find(|n| n.has_tag_name("nav")))?;
+        let list = toc.children().find(|n| n.has_tag_name("ol"))?;
+
+        let mut entries = Vec::new();
+        collect_nav_list(list, 1, &mut entries);
+        return Some(entries);
+    }
+
+    let ncx = items
+        .iter()
+        .find(|n| n.attribute("media-type") == Some("application/x-dtbncx+xml"))?
+        .attribute("href")?;
+    let ncx = read_entry(&mut archive, &resolve(ncx))?;
+    let ncx = parse_xml(&ncx)?;
+    let nav_map = ncx.descendants().find(|n| n.has_tag_name("navMap"))?;
+
+    let mut entries = Vec::new();
+    collect_nav_points(nav_map, 1, &mut entries);
+    Some(entries)
+}
+
+/// Parse EPUB XML, which commonly carries a DOCTYPE
+pub(super) fn parse_xml(text: &str) -> Option<roxmltree::Document<'_>> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    roxmltree::Document::parse_with_options(text, options).ok()
+}
+
+/// Walk an EPUB 3 `<ol>` of `<li><a href>` entries
+fn collect_nav_list(list: roxmltree::Node, level: usize, out: &mut Vec<(usize, TOCEntry)>) {
+    for li in list.children().filter(|n| n.has_tag_name("li")) {
+        let link = li
+            .children()
+            .find(|n| n.has_tag_name("a") || n.has_tag_name("span"));
+        if let Some(link) = link {
+            let title: String = link
+                .descendants()
+                .filter(|n| n.is_text())
+                .filter_map(|n| n.text())
+                .collect();
+            let href = link.attribute("href").unwrap_or_default().to_string();
+            out.push((level, entry(title.trim().to_string(), href, None)));
+        }
+        if let Some(sublist) = li.children().find(|n| n.has_tag_name("ol")) {
+            collect_nav_list(sublist, level + 1, out);
+        }
+    }
+}
+
+/// Walk EPUB 2 NCX `<navPoint>` elements
+fn collect_nav_points(parent: roxmltree::Node, level: usize, out: &mut Vec<(usize, TOCEntry)>) {
+    for point in parent.children().filter(|n| n.has_tag_name("navPoint")) {
+        let title = point
+            .descendants()
+            .find(|n| n.has_tag_name("text"))
+            .and_then(|n| n.text())
+            .unwrap_or_default();
+        let href = point
+            .children()
+            .find(|n| n.has_tag_name("content"))
+            .and_then(|n| n.attribute("src"))
+            .unwrap_or_default();
+        out.push((level, entry(title.trim().to_string(), href.to_string(), None)));
+        collect_nav_points(point, level + 1, out);
+    }
+}
@@ -0,0 +1,107 @@
+//! Output paths derived for conversions run without an explicit destination
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Where conversions write when no output path is given
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutputSettings {
+    /// Directory for converted files; `None` writes next to the input
+    pub default_dir: Option<String>,
+    /// File name with `{name}` (input file stem), `{date}` (`YYYY-MM-DD`) and `{ext}`
+    /// (target extension) placeholders
+    pub filename_template: String,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            default_dir: None,
+            filename_template: "{name}.{ext}".to_string(),
+        }
+    }
+}
+
+impl OutputSettings {
+    /// Path to write the conversion of `input` to a file with extension `ext`. A counter
+    /// is appended to the file stem (`notes-1.pdf`) if the templated path already exists.
+    pub fn resolve(&self, input: &str, ext: &str, date: NaiveDate) -> PathBuf {
+        let input = Path::new(input);
+        let name = input.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+        let file_name = self
+            .filename_template
+            .replace("{name}", name)
+            .replace("{date}", &date.format("%Y-%m-%d").to_string())
+            .replace("{ext}", ext)
+            // The template names a file, not a location
+            .replace(['/', '\\'], "_");
+
+        let dir = match &self.default_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => input.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        unused_path(dir.join(file_name))
+    }
+}
+
+/// `path`, or the first of `stem-1.ext`, `stem-2.ext`, ... that does not exist yet
+fn unused_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+    let ext = path.extension().and_then(|e| e.to_str()).map(|e| format!(".{}", e));
+    let mut counter = 1;
+    loop {
+        let candidate =
+            path.with_file_name(format!("{}-{}{}", stem, counter, ext.as_deref().unwrap_or("")));
+        if !candidate.exists() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, 3).unwrap()
+    }
+
+    #[test]
+    fn test_template_resolves_placeholders() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = OutputSettings {
+            default_dir: Some(dir.path().to_str().unwrap().to_string()),
+            filename_template: "{name}-{date}.{ext}".to_string(),
+        };
+
+        assert_eq!(
+            settings.resolve("/notes/week 1.md", "pdf", date()),
+            dir.path().join("week 1-2024-06-03.pdf")
+        );
+        // Without a default directory the output goes next to the input
+        assert_eq!(
+            OutputSettings::default().resolve("/notes/week1.md", "docx", date()),
+            PathBuf::from("/notes/week1.docx")
+        );
+    }
+
+    #[test]
+    fn test_collisions_get_a_counter_suffix() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("paper.tex");
+        let input = input.to_str().unwrap();
+        let settings = OutputSettings::default();
+
+        std::fs::write(dir.path().join("paper.pdf"), b"").unwrap();
+        assert_eq!(settings.resolve(input, "pdf", date()), dir.path().join("paper-1.pdf"));
+
+        std::fs::write(dir.path().join("paper-1.pdf"), b"").unwrap();
+        assert_eq!(settings.resolve(input, "pdf", date()), dir.path().join("paper-2.pdf"));
+    }
+}
@@ -1,12 +1,29 @@
 //! Document parsing implementation
 
-use super::{Category, Document, DocumentMetadata, DocumentType, Page, Paragraph};
+use super::layout::{extract_page_layouts, PageLayout};
+use super::{
+    Category, Document, DocumentMetadata, DocumentType, Page, Paragraph, ParseOptions, TextSource,
+};
 use crate::error::{AppError, DocumentError};
 use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
 use std::path::Path;
 
+/// Mapping from legacy positional paragraph ids (e.g. `p1-2`) to stable ids
+pub type ParagraphIdMap = HashMap<String, String>;
+
 /// Parse a document from a file path
 pub async fn parse_document(path: &str) -> Result<Document, AppError> {
+    parse_document_with_id_map(path, &ParseOptions::default())
+        .await
+        .map(|(doc, _)| doc)
+}
+
+/// Parse a document and also return how legacy paragraph ids map to stable ones
+pub async fn parse_document_with_id_map(
+    path: &str,
+    options: &ParseOptions,
+) -> Result<(Document, ParagraphIdMap), AppError> {
     let path_obj = Path::new(path);
 
     if !path_obj.exists() {
@@ -21,72 +38,341 @@ pub async fn parse_document(path: &str) -> Result<Document, AppError> {
     let doc_type = DocumentType::from_extension(extension)
         .ok_or_else(|| DocumentError::UnsupportedFormat(extension.to_string()))?;
 
-    let content = tokio::fs::read(path).await?;
-    let id = generate_document_id(&content);
+    // Hash while reading so large files are only read (and held) once, off the runtime
+    let read_path = path.to_string();
+    let (content, id) = tokio::task::spawn_blocking(move || super::read_and_hash(&read_path))
+        .await
+        .map_err(std::io::Error::other)??;
 
-    let (pages, metadata) = match doc_type {
-        DocumentType::Pdf => parse_pdf(&content, path).await?,
+    let (mut pages, metadata) = match doc_type {
+        DocumentType::Pdf => parse_pdf(&content, path, options).await?,
         DocumentType::Markdown => parse_markdown(&content).await?,
-        DocumentType::Txt => parse_txt(&content).await?,
-        DocumentType::Latex => parse_txt(&content).await?, // LaTeX as text
+        DocumentType::Docx => parse_docx(&content).await?,
+        DocumentType::Txt => parse_txt(content).await?,
+        DocumentType::Latex => parse_txt(content).await?, // LaTeX as text
         _ => {
             tracing::warn!("Using fallback parser for {:?}", doc_type);
-            parse_txt(&content).await?
+            parse_txt(content).await?
         }
     };
 
-    let title = extract_title(&pages, path_obj);
+    if options.normalize_unicode {
+        for page in &mut pages {
+            page.text = normalize_text(&page.text);
+            for paragraph in &mut page.paragraphs {
+                paragraph.text = normalize_text(&paragraph.text);
+            }
+        }
+    }
+
+    let id_map = assign_stable_paragraph_ids(&mut pages);
+    let title = metadata
+        .title
+        .clone()
+        .unwrap_or_else(|| extract_title(&pages, path_obj));
     let category = detect_category(&pages);
 
-    Ok(Document {
-        id,
-        doc_type,
-        path: path.to_string(),
-        title,
-        authors: vec![],
-        pages,
-        metadata,
-        category,
-    })
+    Ok((
+        Document {
+            id,
+            doc_type,
+            path: path.to_string(),
+            title,
+            authors: vec![],
+            pages,
+            metadata,
+            category,
+        },
+        id_map,
+    ))
+}
+
+/// NFKC-normalize text, which also expands typographic ligatures ("ﬁ" -> "fi")
+pub fn normalize_text(text: &str) -> String {
+    use unicode_normalization::{is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+    if is_nfkc_quick(text.chars()) == IsNormalized::Yes {
+        return text.to_string();
+    }
+    text.nfkc().collect()
 }
 
-fn generate_document_id(content: &[u8]) -> String {
+/// In-memory document id, used to check the streaming `hash_file` against
+#[cfg(test)]
+pub(crate) fn generate_document_id(content: &[u8]) -> String {
     let mut hasher = Sha256::new();
     hasher.update(content);
     let result = hasher.finalize();
     format!("{:x}", result)
 }
 
-/// Parse PDF document using pdf-extract for text extraction, with OCR fallback
-async fn parse_pdf(content: &[u8], pdf_path: &str) -> Result<(Vec<Page>, DocumentMetadata), AppError> {
+/// Hash of a paragraph's normalized text, shared by identical paragraphs
+pub fn paragraph_content_hash(text: &str) -> String {
+    let normalized = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let digest = Sha256::digest(normalized.as_bytes());
+    format!("{:x}", digest)[..16].to_string()
+}
+
+/// Replace positional paragraph ids with content-derived ones.
+///
+/// Repeated paragraphs get a numeric suffix in document order so ids stay unique.
+fn assign_stable_paragraph_ids(pages: &mut [Page]) -> ParagraphIdMap {
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut id_map = ParagraphIdMap::new();
+
+    for paragraph in pages.iter_mut().flat_map(|p| p.paragraphs.iter_mut()) {
+        let hash = paragraph_content_hash(&paragraph.text);
+        let seen = occurrences.entry(hash.clone()).or_insert(0);
+        *seen += 1;
+
+        let stable_id = if *seen == 1 {
+            format!("p-{}", hash)
+        } else {
+            format!("p-{}-{}", hash, seen)
+        };
+        let legacy_id = std::mem::replace(&mut paragraph.id, stable_id.clone());
+        id_map.insert(legacy_id, stable_id);
+    }
+
+    id_map
+}
+
+/// Map old paragraph ids to the ids of new paragraphs with the same text, ignoring case
+/// and whitespace. Repeated paragraphs are paired in document order; old paragraphs
+/// whose text no longer appears are left out.
+pub fn match_paragraph_ids<'a>(
+    old: impl IntoIterator<Item = &'a Paragraph>,
+    new: impl IntoIterator<Item = &'a Paragraph>,
+) -> ParagraphIdMap {
+    let mut by_text: HashMap<String, VecDeque<&str>> = HashMap::new();
+    for paragraph in new {
+        by_text
+            .entry(paragraph_content_hash(&paragraph.text))
+            .or_default()
+            .push_back(&paragraph.id);
+    }
+
+    old.into_iter()
+        .filter_map(|paragraph| {
+            let candidates = by_text.get_mut(&paragraph_content_hash(&paragraph.text))?;
+            Some((paragraph.id.clone(), candidates.pop_front()?.to_string()))
+        })
+        .collect()
+}
+
+/// Blocks with at least this many lines are checked for missing paragraph breaks
+const SEGMENT_MIN_LINES: usize = 4;
+/// Paragraphs longer than this with no layout cues are split into sentence groups
+const MAX_PARAGRAPH_CHARS: usize = 1200;
+const SENTENCES_PER_PARAGRAPH: usize = 5;
+/// A line shorter than this fraction of the block width can end a paragraph
+const SHORT_LINE_RATIO: f32 = 0.8;
+
+/// Split extracted text into paragraphs.
+///
+/// Blank lines always separate paragraphs. Blocks without them (typical of
+/// single-newline-wrapped PDF text) are further broken at indented lines and at
+/// short lines ending a sentence, and overlong results are split into groups of
+/// sentences. Ids are `<prefix><block>` with `.<n>` appended for later segments.
+fn split_paragraphs(text: &str, id_prefix: &str) -> Vec<Paragraph> {
+    let mut paragraphs = Vec::new();
+
+    for (j, block) in text.split("\n\n").enumerate() {
+        let block = block.trim();
+        if block.is_empty() {
+            continue;
+        }
+
+        let segments = if block.lines().count() >= SEGMENT_MIN_LINES
+            || block.chars().count() > MAX_PARAGRAPH_CHARS
+        {
+            segment_block(block)
+        } else {
+            Vec::new()
+        };
+
+        // Keep the original text when no extra breaks were found
+        if segments.len() <= 1 {
+            paragraphs.push(Paragraph {
+                id: format!("{}{}", id_prefix, j + 1),
+                text: block.to_string(),
+                bounding_box: None,
+            });
+            continue;
+        }
+
+        for (k, segment) in segments.into_iter().enumerate() {
+            let id = match k {
+                0 => format!("{}{}", id_prefix, j + 1),
+                _ => format!("{}{}.{}", id_prefix, j + 1, k + 1),
+            };
+            paragraphs.push(Paragraph {
+                id,
+                text: segment,
+                bounding_box: None,
+            });
+        }
+    }
+
+    paragraphs
+}
+
+/// Break a blank-line-free block on layout cues, then split overlong pieces by sentence
+fn segment_block(block: &str) -> Vec<String> {
+    let lines: Vec<&str> = block.lines().map(str::trim_end).collect();
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let short_line = (width as f32 * SHORT_LINE_RATIO) as usize;
+    let is_indented = |line: &str| line.starts_with([' ', '\t']);
+
+    let mut pieces: Vec<String> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut prev: Option<&str> = None;
+
+    for &line in &lines {
+        let breaks = line.trim().is_empty()
+            || prev.is_some_and(|prev| {
+                (is_indented(line) && !is_indented(prev))
+                    || (ends_sentence(prev) && prev.chars().count() < short_line)
+            });
+        if breaks && !current.is_empty() {
+            pieces.push(current.join(" "));
+            current.clear();
+        }
+        if !line.trim().is_empty() {
+            current.push(line.trim());
+        }
+        prev = Some(line);
+    }
+    if !current.is_empty() {
+        pieces.push(current.join(" "));
+    }
+
+    pieces
+        .into_iter()
+        .flat_map(|piece| {
+            if piece.chars().count() > MAX_PARAGRAPH_CHARS {
+                split_sentences(&piece)
+                    .chunks(SENTENCES_PER_PARAGRAPH)
+                    .map(|group| group.join(" "))
+                    .collect()
+            } else {
+                vec![piece]
+            }
+        })
+        .collect()
+}
+
+/// Whether a line ends with sentence-final punctuation, allowing closing quotes/brackets
+fn ends_sentence(line: &str) -> bool {
+    line.trim_end()
+        .trim_end_matches(['"', '\'', ')', ']', '\u{201D}', '\u{2019}'])
+        .ends_with(['.', '!', '?', ':'])
+}
+
+/// Split text into sentences at `.`, `!` or `?` followed by a capitalised word
+fn split_sentences(text: &str) -> Vec<&str> {
+    const ABBREVIATIONS: &[&str] = &[
+        "e.g.", "i.e.", "et al.", "etc.", "vs.", "cf.", "Fig.", "Eq.", "Dr.", "Mr.", "Mrs.",
+        "Ms.", "No.", "Sec.", "Ch.",
+    ];
+
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+
+    for (idx, &(pos, c)) in chars.iter().enumerate() {
+        if !matches!(c, '.' | '!' | '?') {
+            continue;
+        }
+        let end = pos + c.len_utf8();
+        let next_is_space = chars.get(idx + 1).is_some_and(|(_, n)| n.is_whitespace());
+        let next_word_capitalised = chars[idx + 1..]
+            .iter()
+            .find(|(_, n)| !n.is_whitespace())
+            .is_some_and(|(_, n)| n.is_uppercase() || n.is_ascii_digit() || *n == '"');
+        let abbreviation = ABBREVIATIONS.iter().any(|a| text[start..end].ends_with(a));
+
+        if next_is_space && next_word_capitalised && !abbreviation {
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    if !text[start..].trim().is_empty() {
+        sentences.push(text[start..].trim());
+    }
+
+    sentences
+}
+
+/// Tidy text extracted from a PDF: rejoin words hyphenated across line breaks and
+/// collapse runs of spaces and blank lines. Page breaks (form feeds) are kept.
+fn clean_pdf_text(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+    let spaces = regex::Regex::new(r"[ \t\u{00A0}]+").unwrap();
+    let text = spaces.replace_all(&text, " ");
+    let trailing = regex::Regex::new(r" +\n").unwrap();
+    let text = trailing.replace_all(&text, "\n");
+
+    // A lowercase continuation means the hyphen only split the word. Keep it when
+    // either side is itself part of a compound ("state-of-\nthe-art").
+    let hyphenated = regex::Regex::new(r"(\w+(?:-\w+)*)- ?\n ?(\p{Ll}\w*)(-?)").unwrap();
+    let text = hyphenated.replace_all(&text, |caps: &regex::Captures| {
+        let compound = caps[1].contains('-') || !caps[3].is_empty();
+        let joiner = if compound { "-" } else { "" };
+        format!("{}{}{}{}", &caps[1], joiner, &caps[2], &caps[3])
+    });
+
+    let blank_lines = regex::Regex::new(r"\n{3,}").unwrap();
+    blank_lines.replace_all(&text, "\n\n").into_owned()
+}
+
+/// Parse PDF document using pdf-extract for text extraction, with OCR fallback.
+/// Pages in `options.ocr_pages` use that text instead of being OCR'd, and other pages
+/// without text are only OCR'd if `options.ocr_uncached_pages` is set.
+async fn parse_pdf(
+    content: &[u8],
+    pdf_path: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<Page>, DocumentMetadata), AppError> {
+    let cached_ocr = &options.ocr_pages;
     tracing::info!("Parsing PDF document ({} bytes)...", content.len());
 
-    // Try to extract text from PDF
-    let text = match pdf_extract::extract_text_from_mem(content) {
-        Ok(t) => t,
+    // Extract text page by page so pages without a text layer can be OCR'd on their own,
+    // keeping glyph positions to place paragraphs on the page
+    let layouts = match extract_page_layouts(content) {
+        Ok(layouts) => layouts,
         Err(e) => {
             tracing::warn!("PDF text extraction failed: {}", e);
-            String::new()
+            Vec::new()
         }
     };
+    let mut native_pages: Vec<String> = layouts.iter().map(|l| clean_pdf_text(&l.text)).collect();
+    if let Some(&last_cached) = cached_ocr.keys().max() {
+        if native_pages.len() < last_cached as usize {
+            native_pages.resize(last_cached as usize, String::new());
+        }
+    }
 
-    // Check if we got any meaningful text (at least 10 chars of actual content)
-    let clean_text = text.trim();
-    let has_content = clean_text.len() > 10
-        && clean_text.chars().filter(|c| c.is_alphabetic()).count() > 5;
-
-    if !has_content {
+    if options.ocr_uncached_pages
+        && cached_ocr.is_empty()
+        && !native_pages.iter().any(|t| has_meaningful_text(t))
+    {
         tracing::info!("PDF has no extractable text, attempting OCR...");
 
         // Try OCR as fallback
         let ocr_config = super::ocr::OcrConfig::default();
         match super::ocr::ocr_pdf(pdf_path, &ocr_config).await {
             Ok(ocr_result) if ocr_result.success => {
-                tracing::info!("OCR successful: {} chars from {} pages",
-                    ocr_result.text.len(), ocr_result.page_count);
+                tracing::info!("OCR successful: {} chars from {} pages at {} DPI",
+                    ocr_result.text.len(), ocr_result.page_count, ocr_result.dpi);
 
                 // Parse OCR text into pages
-                let ocr_text = ocr_result.text;
+                let ocr_text = clean_pdf_text(&ocr_result.text);
                 let word_count = ocr_text.split_whitespace().count() as u32;
 
                 // Split by page markers or treat as single page
@@ -101,21 +387,13 @@ async fn parse_pdf(content: &[u8], pdf_path: &str) -> Result<(Vec<Page>, Documen
                     .enumerate()
                     .filter(|(_, p)| !p.trim().is_empty())
                     .map(|(i, page_text)| {
-                        let paragraphs: Vec<Paragraph> = page_text
-                            .split("\n\n")
-                            .enumerate()
-                            .filter(|(_, p)| !p.trim().is_empty())
-                            .map(|(j, p)| Paragraph {
-                                id: format!("p{}-{}", i + 1, j + 1),
-                                text: p.trim().to_string(),
-                                bounding_box: None,
-                            })
-                            .collect();
+                        let paragraphs = split_paragraphs(page_text, &format!("p{}-", i + 1));
 
                         Page {
                             number: (i + 1) as u32,
                             text: page_text.trim().to_string(),
                             paragraphs,
+                            source: TextSource::Ocr,
                         }
                     })
                     .collect();
@@ -153,6 +431,7 @@ async fn parse_pdf(content: &[u8], pdf_path: &str) -> Result<(Vec<Page>, Documen
                             text: "Scanned PDF - OCR unsuccessful".to_string(),
                             bounding_box: None,
                         }],
+                        source: TextSource::Empty,
                     }],
                     DocumentMetadata {
                         page_count: 1,
@@ -180,6 +459,7 @@ async fn parse_pdf(content: &[u8], pdf_path: &str) -> Result<(Vec<Page>, Documen
                             text: "PDF content could not be extracted".to_string(),
                             bounding_box: None,
                         }],
+                        source: TextSource::Empty,
                     }],
                     DocumentMetadata {
                         page_count: 1,
@@ -190,58 +470,98 @@ async fn parse_pdf(content: &[u8], pdf_path: &str) -> Result<(Vec<Page>, Documen
         }
     }
 
-    // Split text into pages by form feed characters or large gaps
-    let raw_pages: Vec<&str> = text.split('\u{0C}').collect();
-    let word_count = text.split_whitespace().count() as u32;
-
-    let pages: Vec<Page> = raw_pages
+    // Mixed or already OCR'd document: OCR only the pages that have no usable text layer
+    // and no cached text
+    let mut ocr_pages: HashMap<u32, String> = cached_ocr
+        .iter()
+        .map(|(&page, text)| (page, clean_pdf_text(text)))
+        .collect();
+    let scanned: Vec<u32> = native_pages
         .iter()
         .enumerate()
-        .filter(|(_, p)| !p.trim().is_empty())
-        .map(|(i, page_text)| {
-            let paragraphs: Vec<Paragraph> = page_text
-                .split("\n\n")
-                .enumerate()
-                .filter(|(_, p)| !p.trim().is_empty())
-                .map(|(j, p)| Paragraph {
-                    id: format!("p{}-{}", i + 1, j + 1),
-                    text: p.trim().to_string(),
-                    bounding_box: None,
-                })
-                .collect();
-
-            Page {
-                number: (i + 1) as u32,
-                text: page_text.trim().to_string(),
-                paragraphs,
-            }
-        })
+        .filter(|(_, t)| !has_meaningful_text(t))
+        .map(|(i, _)| (i + 1) as u32)
+        .filter(|page| !ocr_pages.contains_key(page))
         .collect();
+    if !scanned.is_empty() && !options.ocr_uncached_pages {
+        tracing::info!("Leaving {} pages without text or cached OCR empty", scanned.len());
+    } else if !scanned.is_empty() {
+        tracing::info!(
+            "OCR'ing {} of {} pages without a text layer",
+            scanned.len(),
+            native_pages.len()
+        );
+        let ocr_config = super::ocr::OcrConfig::default();
+        for page in scanned {
+            match super::ocr::ocr_pdf_page(pdf_path, page, &ocr_config).await {
+                Ok(result) => {
+                    tracing::debug!("OCR'd page {} at {} DPI", page, result.dpi);
+                    ocr_pages.insert(page, clean_pdf_text(&result.text));
+                }
+                Err(e) => tracing::warn!("OCR failed for page {}: {}", page, e),
+            }
+        }
+    }
 
+    let pages = build_pdf_pages(&native_pages, &layouts, &ocr_pages);
+    let word_count = pages
+        .iter()
+        .map(|p| p.text.split_whitespace().count() as u32)
+        .sum();
     let page_count = pages.len() as u32;
 
     Ok((
-        if pages.is_empty() {
-            vec![Page {
-                number: 1,
-                text: text.clone(),
-                paragraphs: vec![Paragraph {
-                    id: "p1".to_string(),
-                    text,
-                    bounding_box: None,
-                }],
-            }]
-        } else {
-            pages
-        },
+        pages,
         DocumentMetadata {
-            page_count: if page_count == 0 { 1 } else { page_count },
+            page_count,
             word_count,
             ..Default::default()
         },
     ))
 }
 
+/// Whether extracted text has real content (at least 10 chars with some letters)
+fn has_meaningful_text(text: &str) -> bool {
+    let text = text.trim();
+    text.len() > 10 && text.chars().filter(|c| c.is_alphabetic()).count() > 5
+}
+
+/// Assemble PDF pages, preferring native text and falling back to OCR text per page.
+/// Paragraphs of native text are placed on the page using the page's layout.
+fn build_pdf_pages(
+    native_pages: &[String],
+    layouts: &[PageLayout],
+    ocr_pages: &HashMap<u32, String>,
+) -> Vec<Page> {
+    native_pages
+        .iter()
+        .enumerate()
+        .map(|(i, native)| {
+            let number = (i + 1) as u32;
+            let ocr = ocr_pages.get(&number).filter(|t| !t.trim().is_empty());
+
+            let (text, source) = match ocr {
+                _ if has_meaningful_text(native) => (native.as_str(), TextSource::Native),
+                Some(ocr) => (ocr.as_str(), TextSource::Ocr),
+                None if !native.trim().is_empty() => (native.as_str(), TextSource::Native),
+                None => ("", TextSource::Empty),
+            };
+
+            let mut paragraphs = split_paragraphs(text, &format!("p{}-", number));
+            if let (TextSource::Native, Some(layout)) = (source, layouts.get(i)) {
+                layout.locate(&mut paragraphs);
+            }
+
+            Page {
+                number,
+                text: text.trim().to_string(),
+                paragraphs,
+                source,
+            }
+        })
+        .collect()
+}
+
 /// Parse Markdown document
 async fn parse_markdown(content: &[u8]) -> Result<(Vec<Page>, DocumentMetadata), AppError> {
     use pulldown_cmark::{Event, Parser, TagEnd};
@@ -301,6 +621,7 @@ async fn parse_markdown(content: &[u8]) -> Result<(Vec<Page>, DocumentMetadata),
             number: 1,
             text: full_text,
             paragraphs,
+            source: TextSource::Native,
         }],
         DocumentMetadata {
             page_count: 1,
@@ -311,35 +632,203 @@ async fn parse_markdown(content: &[u8]) -> Result<(Vec<Page>, DocumentMetadata),
 }
 
 /// Parse plain text document
-async fn parse_txt(content: &[u8]) -> Result<(Vec<Page>, DocumentMetadata), AppError> {
-    let text = String::from_utf8_lossy(content).to_string();
+async fn parse_txt(content: Vec<u8>) -> Result<(Vec<Page>, DocumentMetadata), AppError> {
+    // Reuse the buffer for valid UTF-8 instead of copying it
+    let text = String::from_utf8(content)
+        .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
     let word_count = text.split_whitespace().count() as u32;
 
-    let paragraphs: Vec<Paragraph> = text
-        .split("\n\n")
+    let paragraphs = split_paragraphs(&text, "p");
+
+    Ok((
+        vec![Page {
+            number: 1,
+            text,
+            paragraphs,
+            source: TextSource::Native,
+        }],
+        DocumentMetadata {
+            page_count: 1,
+            word_count,
+            ..Default::default()
+        },
+    ))
+}
+
+/// Parse a Word document. Body paragraphs and table rows become paragraphs in document
+/// order, each row with its cells separated by tabs.
+async fn parse_docx(content: &[u8]) -> Result<(Vec<Page>, DocumentMetadata), AppError> {
+    use docx_rs::DocumentChild;
+
+    let docx = docx_rs::read_docx(content)
+        .map_err(|e| DocumentError::ParseError(format!("DOCX: {}", e)))?;
+
+    let mut texts = Vec::new();
+    for child in &docx.document.children {
+        match child {
+            DocumentChild::Paragraph(paragraph) => texts.push(docx_paragraph_text(paragraph)),
+            DocumentChild::Table(table) => docx_table_rows(table, &mut texts),
+            DocumentChild::StructuredDataTag(tag) => docx_tag_texts(tag, &mut texts),
+            _ => {}
+        }
+    }
+
+    let paragraphs: Vec<Paragraph> = texts
+        .iter()
+        .map(|text| text.trim())
+        .filter(|text| !text.is_empty())
         .enumerate()
-        .filter(|(_, p)| !p.trim().is_empty())
-        .map(|(i, p)| Paragraph {
+        .map(|(i, text)| Paragraph {
             id: format!("p{}", i + 1),
-            text: p.trim().to_string(),
+            text: text.to_string(),
             bounding_box: None,
         })
         .collect();
 
+    let full_text = paragraphs
+        .iter()
+        .map(|p| p.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let word_count = full_text.split_whitespace().count() as u32;
+    let (title, subject) = docx_core_properties(content);
+
     Ok((
         vec![Page {
             number: 1,
-            text,
+            text: full_text,
             paragraphs,
+            source: TextSource::Native,
         }],
         DocumentMetadata {
             page_count: 1,
             word_count,
+            title,
+            subject,
             ..Default::default()
         },
     ))
 }
 
+/// Text of a DOCX paragraph's runs in order, including inserted and linked runs
+fn docx_paragraph_text(paragraph: &docx_rs::Paragraph) -> String {
+    use docx_rs::{InsertChild, ParagraphChild};
+
+    fn collect(children: &[ParagraphChild], text: &mut String) {
+        for child in children {
+            match child {
+                ParagraphChild::Run(run) => docx_run_text(run, text),
+                ParagraphChild::Insert(insert) => {
+                    for child in &insert.children {
+                        if let InsertChild::Run(run) = child {
+                            docx_run_text(run, text);
+                        }
+                    }
+                }
+                ParagraphChild::Hyperlink(link) => collect(&link.children, text),
+                _ => {}
+            }
+        }
+    }
+
+    let mut text = String::new();
+    collect(&paragraph.children, &mut text);
+    text
+}
+
+fn docx_run_text(run: &docx_rs::Run, text: &mut String) {
+    use docx_rs::RunChild;
+
+    for child in &run.children {
+        match child {
+            RunChild::Text(t) => text.push_str(&t.text),
+            RunChild::Tab(_) | RunChild::PTab(_) => text.push('\t'),
+            RunChild::Break(_) | RunChild::CarriageReturn(_) => text.push('\n'),
+            _ => {}
+        }
+    }
+}
+
+/// Append one text per table row, joining its non-empty cells
+fn docx_table_rows(table: &docx_rs::Table, texts: &mut Vec<String>) {
+    use docx_rs::{TableCellContent, TableChild, TableRowChild};
+
+    for TableChild::TableRow(row) in &table.rows {
+        let mut cells = Vec::new();
+        for TableRowChild::TableCell(cell) in &row.cells {
+            let mut parts = Vec::new();
+            for content in &cell.children {
+                match content {
+                    TableCellContent::Paragraph(paragraph) => {
+                        parts.push(docx_paragraph_text(paragraph))
+                    }
+                    // Nested tables are flattened into the cell
+                    TableCellContent::Table(nested) => docx_table_rows(nested, &mut parts),
+                    _ => {}
+                }
+            }
+            let cell_text = parts
+                .iter()
+                .map(|part| part.trim())
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            if !cell_text.is_empty() {
+                cells.push(cell_text);
+            }
+        }
+        texts.push(cells.join("\t"));
+    }
+}
+
+/// Append the paragraphs and table rows inside a content control
+fn docx_tag_texts(tag: &docx_rs::StructuredDataTag, texts: &mut Vec<String>) {
+    use docx_rs::StructuredDataTagChild;
+
+    for child in &tag.children {
+        match child {
+            StructuredDataTagChild::Paragraph(paragraph) => {
+                texts.push(docx_paragraph_text(paragraph))
+            }
+            StructuredDataTagChild::Table(table) => docx_table_rows(table, texts),
+            StructuredDataTagChild::StructuredDataTag(inner) => docx_tag_texts(inner, texts),
+            _ => {}
+        }
+    }
+}
+
+/// Title and subject from a DOCX package's `docProps/core.xml`, which docx-rs doesn't read
+fn docx_core_properties(content: &[u8]) -> (Option<String>, Option<String>) {
+    use std::io::Read;
+
+    let read_xml = || {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(content)).ok()?;
+        let mut xml = String::new();
+        archive
+            .by_name("docProps/core.xml")
+            .ok()?
+            .read_to_string(&mut xml)
+            .ok()?;
+        Some(xml)
+    };
+    let Some(xml) = read_xml() else {
+        return (None, None);
+    };
+    let Ok(doc) = roxmltree::Document::parse(&xml) else {
+        return (None, None);
+    };
+
+    let property = |name: &str| {
+        doc.descendants()
+            .find(|n| n.tag_name().name() == name)
+            .and_then(|n| n.text())
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .map(str::to_string)
+    };
+    (property("title"), property("subject"))
+}
+
 fn extract_title(pages: &[Page], path: &Path) -> String {
     if let Some(first_page) = pages.first() {
         if let Some(first_para) = first_page.paragraphs.first() {
@@ -519,3 +1008,406 @@ fn detect_category(pages: &[Page]) -> Category {
         Category::Unknown
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::BoundingBox;
+
+    #[tokio::test]
+    async fn test_streamed_parse_matches_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.txt");
+        let mut content = b"Intro paragraph.\n\nBody with caf\xc3\xa9.\n\n".to_vec();
+        content.extend_from_slice(b"Broken \xff byte.");
+        std::fs::write(&path, &content).unwrap();
+
+        let doc = parse_document(path.to_str().unwrap()).await.unwrap();
+
+        assert_eq!(doc.id, generate_document_id(&content));
+        assert_eq!(doc.pages[0].text, String::from_utf8_lossy(&content));
+        let paragraphs: Vec<&str> = doc.pages[0].paragraphs.iter().map(|p| p.text.as_str()).collect();
+        assert_eq!(paragraphs, ["Intro paragraph.", "Body with café.", "Broken \u{FFFD} byte."]);
+        assert_eq!(doc.metadata.word_count, 8);
+    }
+
+    #[test]
+    fn test_paragraph_ids_matched_by_text() {
+        let paragraph = |id: &str, text: &str| Paragraph {
+            id: id.to_string(),
+            text: text.to_string(),
+            bounding_box: None,
+        };
+        let old = [
+            paragraph("p1-1", "Introduction."),
+            paragraph("p1-2", "Repeated line."),
+            paragraph("p1-3", "Deleted later."),
+            paragraph("p2-1", "Repeated   LINE."),
+        ];
+        let new = [
+            paragraph("p-a", "A new opening."),
+            paragraph("p-b", "introduction."),
+            paragraph("p-c", "Repeated line."),
+            paragraph("p-c-2", "Repeated line."),
+        ];
+
+        assert_eq!(
+            match_paragraph_ids(&old, &new),
+            ParagraphIdMap::from([
+                ("p1-1".to_string(), "p-b".to_string()),
+                ("p1-2".to_string(), "p-c".to_string()),
+                ("p2-1".to_string(), "p-c-2".to_string()),
+            ])
+        );
+    }
+
+    fn texts(paragraphs: &[Paragraph]) -> Vec<&str> {
+        paragraphs.iter().map(|p| p.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_wrapped_text_splits_on_short_sentence_lines() {
+        let text = "The method builds on prior work in sparse attention and extends it\n\
+                    to streaming inputs without retraining the base model at all.\n\
+                    Results are reported below.\n\
+                    We evaluate on three benchmarks covering summarisation, retrieval\n\
+                    and question answering, using the same hyperparameters for each.\n\
+                    Scores improve throughout.\n\
+                    Limitations remain for very long documents with many tables.";
+
+        let paragraphs = split_paragraphs(text, "p1-");
+        assert_eq!(paragraphs.len(), 3);
+        assert!(paragraphs[0].text.starts_with("The method"));
+        assert!(paragraphs[0].text.ends_with("Results are reported below."));
+        assert!(paragraphs[1].text.starts_with("We evaluate"));
+        assert!(paragraphs[2].text.starts_with("Limitations"));
+        assert!(paragraphs.iter().all(|p| !p.text.contains('\n')));
+        let ids: Vec<&str> = paragraphs.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["p1-1", "p1-1.2", "p1-1.3"]);
+    }
+
+    #[test]
+    fn test_wrapped_text_splits_on_indentation() {
+        let text = concat!(
+            "    First paragraph opens with an indent and then wraps onto\n",
+            "a second line that is not indented, as printed books do\n",
+            "    Second paragraph is also indented on its first line and\n",
+            "wraps in exactly the same way across several lines of text\n",
+            "with no blank line between the two paragraphs",
+        );
+
+        let paragraphs = split_paragraphs(text, "p");
+        assert_eq!(paragraphs.len(), 2);
+        assert!(paragraphs[1].text.starts_with("Second paragraph"));
+    }
+
+    #[test]
+    fn test_long_uncued_text_split_into_sentence_groups() {
+        let sentence = "Each sentence here is written to be about the same length as the others.";
+        let text = vec![sentence; 40].join(" ");
+        // Re-wrap into fixed-width lines with no paragraph cues
+        let wrapped: Vec<String> = text
+            .as_bytes()
+            .chunks(70)
+            .map(|c| String::from_utf8_lossy(c).into_owned())
+            .collect();
+
+        let paragraphs = split_paragraphs(&wrapped.join("\n"), "p");
+        assert!(paragraphs.len() >= 4);
+        assert!(paragraphs.iter().all(|p| p.text.len() <= MAX_PARAGRAPH_CHARS));
+    }
+
+    #[test]
+    fn test_blank_line_paragraphs_unchanged() {
+        let text = "Intro.\n\nJane Doe\n1 Main Street\nSpringfield\n\nClosing line.";
+        let paragraphs = split_paragraphs(text, "p");
+        assert_eq!(
+            texts(&paragraphs),
+            ["Intro.", "Jane Doe\n1 Main Street\nSpringfield", "Closing line."]
+        );
+        assert_eq!(
+            split_sentences("See Fig. 2 for details. Then stop."),
+            ["See Fig. 2 for details.", "Then stop."]
+        );
+    }
+
+    #[test]
+    fn test_clean_pdf_text_rejoins_hyphenated_words() {
+        assert_eq!(clean_pdf_text("deep convolu-\ntion layers"), "deep convolution layers");
+        assert_eq!(clean_pdf_text("a state-of-the-art model"), "a state-of-the-art model");
+        assert_eq!(clean_pdf_text("state-of-\nthe-art"), "state-of-the-art");
+        assert_eq!(clean_pdf_text("well-\nknown-ish"), "well-known-ish");
+        // Capitalised continuations are separate words, e.g. list items
+        assert_eq!(clean_pdf_text("pre-\nTraining"), "pre-\nTraining");
+    }
+
+    #[test]
+    fn test_clean_pdf_text_normalizes_whitespace() {
+        let text = "Title  with\t gaps   \r\n\n\n\nBody line\u{00A0}\u{00A0}here\n\u{0C}Next";
+        assert_eq!(clean_pdf_text(text), "Title with gaps\n\nBody line here\n\u{0C}Next");
+    }
+
+    #[test]
+    fn test_normalize_text_expands_ligatures() {
+        assert_eq!(normalize_text("\u{FB01}lter"), "filter");
+        assert_eq!(normalize_text("\u{FB02}ow \u{FB00}ect e\u{FB03}cient"), "flow ffect efficient");
+        assert_eq!(normalize_text("caf\u{0065}\u{0301}"), "caf\u{00E9}");
+        assert_eq!(normalize_text("plain ascii"), "plain ascii");
+    }
+
+    #[test]
+    fn test_normalize_text_idempotent() {
+        for text in ["\u{FB01}lter", "x\u{00B2} \u{2163} \u{FF21}", "e\u{0301}t\u{00E9}", ""] {
+            let once = normalize_text(text);
+            assert_eq!(normalize_text(&once), once);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_normalization_can_be_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ligatures.txt");
+        std::fs::write(&path, "The \u{FB01}lter stage.").unwrap();
+        let path = path.to_str().unwrap();
+
+        let (normalized, _) = parse_document_with_id_map(path, &ParseOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(normalized.pages[0].paragraphs[0].text, "The filter stage.");
+
+        let raw_options = ParseOptions {
+            normalize_unicode: false,
+            ..Default::default()
+        };
+        let (raw, _) = parse_document_with_id_map(path, &raw_options).await.unwrap();
+        assert_eq!(raw.pages[0].paragraphs[0].text, "The \u{FB01}lter stage.");
+    }
+
+    /// Write a Letter-size PDF with one page per entry of `pages`, each a list of
+    /// paragraphs drawn as lines of 12pt text from (72, 700) down
+    fn write_text_pdf(path: &Path, pages: &[&[&[&str]]]) {
+        use lopdf::content::{Content, Operation};
+        use lopdf::{dictionary, Object, Stream};
+
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+
+        let mut kids = Vec::new();
+        for paragraphs in pages {
+            let mut operations = Vec::new();
+            let mut y = 700;
+            for lines in paragraphs.iter() {
+                for line in lines.iter() {
+                    operations.extend([
+                        Operation::new("BT", vec![]),
+                        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                        Operation::new("Td", vec![72.into(), y.into()]),
+                        Operation::new("Tj", vec![Object::string_literal(*line)]),
+                        Operation::new("ET", vec![]),
+                    ]);
+                    y -= 14;
+                }
+                y -= 28;
+            }
+            let content = Content { operations }.encode().unwrap();
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content));
+            kids.push(Object::Reference(doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            })));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pdf_pages_parsed_separately_with_paragraph_boxes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("three-pages.pdf");
+        write_text_pdf(
+            &path,
+            &[
+                &[
+                    &["Attention lets every position of a sequence", "look at every other."],
+                    &["The second paragraph of the first page."],
+                ],
+                &[&["Results are reported on the second page."]],
+                &[
+                    &["Conclusions close the paper on its third page."],
+                    &["References follow the conclusions."],
+                ],
+            ],
+        );
+
+        let doc = parse_document(path.to_str().unwrap()).await.unwrap();
+        assert_eq!(doc.metadata.page_count, 3);
+        let numbers: Vec<u32> = doc.pages.iter().map(|p| p.number).collect();
+        assert_eq!(numbers, [1, 2, 3]);
+        assert!(doc.pages[1].text.contains("second page"));
+        assert!(!doc.pages[0].text.contains("second page"));
+
+        let first = &doc.pages[0].paragraphs;
+        assert_eq!(first.len(), 2);
+        assert!(first[0].text.ends_with("look at every other."));
+        let boxes: Vec<&BoundingBox> =
+            first.iter().map(|p| p.bounding_box.as_ref().unwrap()).collect();
+        // Baseline 700 from the bottom of a 792pt page, 12pt text
+        assert!((boxes[0].x - 72.0).abs() < 0.5);
+        assert!((boxes[0].y - (792.0 - 700.0 - 9.6)).abs() < 0.5);
+        // Two lines: from the first line's top to the second line's descenders
+        assert!((boxes[0].height - (14.0 + 12.0)).abs() < 0.5);
+        assert!(boxes[0].width > 150.0 && boxes[0].width < 540.0);
+        assert!(boxes[1].y > boxes[0].y + boxes[0].height);
+        assert!(doc
+            .pages
+            .iter()
+            .flat_map(|p| &p.paragraphs)
+            .all(|p| p.bounding_box.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_docx_paragraphs_tables_and_properties_parsed() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("draft.docx");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let parts: [(&str, &str); 5] = [
+            (
+                "[Content_Types].xml",
+                r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+                  <Default Extension="rels"
+                    ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+                  <Default Extension="xml" ContentType="application/xml"/>
+                </Types>"#,
+            ),
+            (
+                "_rels/.rels",
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+                  <Relationship Id="rId1" Target="word/document.xml"
+                    Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument"/>
+                </Relationships>"#,
+            ),
+            (
+                "word/_rels/document.xml.rels",
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"/>"#,
+            ),
+            (
+                "word/document.xml",
+                r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+                  <w:body>
+                    <w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr>
+                      <w:r><w:t xml:space="preserve">Attention </w:t></w:r>
+                      <w:r><w:t>Is All You Need</w:t></w:r></w:p>
+                    <w:p><w:r><w:t xml:space="preserve">The Transformer relies on </w:t></w:r>
+                      <w:hyperlink w:anchor="sa"><w:r><w:t>self-attention</w:t></w:r></w:hyperlink>
+                      <w:r><w:t>.</w:t></w:r></w:p>
+                    <w:p/>
+                    <w:tbl>
+                      <w:tr><w:tc><w:p><w:r><w:t>Model</w:t></w:r></w:p></w:tc>
+                        <w:tc><w:p><w:r><w:t>BLEU</w:t></w:r></w:p></w:tc></w:tr>
+                      <w:tr><w:tc><w:p><w:r><w:t>Transformer</w:t></w:r></w:p></w:tc>
+                        <w:tc><w:p><w:r><w:t>28.4</w:t></w:r></w:p></w:tc></w:tr>
+                    </w:tbl>
+                  </w:body>
+                </w:document>"#,
+            ),
+            (
+                "docProps/core.xml",
+                r#"<cp:coreProperties
+                  xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties"
+                  xmlns:dc="http://purl.org/dc/elements/1.1/">
+                  <dc:title>Attention Paper Draft</dc:title>
+                  <dc:subject>Sequence transduction</dc:subject>
+                </cp:coreProperties>"#,
+            ),
+        ];
+        for (name, xml) in parts {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(xml.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let doc = parse_document(path.to_str().unwrap()).await.unwrap();
+        let paragraphs = &doc.pages[0].paragraphs;
+        assert_eq!(paragraphs[0].text, "Attention Is All You Need");
+        assert_eq!(paragraphs[1].text, "The Transformer relies on self-attention.");
+        // Table cells are kept, one paragraph per row
+        assert_eq!(paragraphs[3].text, "Transformer\t28.4");
+        assert_eq!(paragraphs.len(), 4);
+        assert_eq!(doc.metadata.word_count, 14);
+
+        assert_eq!(doc.title, "Attention Paper Draft");
+        assert_eq!(doc.metadata.title.as_deref(), Some("Attention Paper Draft"));
+        assert_eq!(doc.metadata.subject.as_deref(), Some("Sequence transduction"));
+    }
+
+    #[tokio::test]
+    async fn test_uncached_pages_left_empty_without_ocr() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("partly-scanned.pdf");
+        write_text_pdf(&path, &[&[&["A native text layer on the first page."]], &[], &[]]);
+
+        let options = ParseOptions {
+            ocr_pages: HashMap::from([(2, "Cached text recognized on page two.".to_string())]),
+            ocr_uncached_pages: false,
+            ..Default::default()
+        };
+        let (doc, _) = parse_document_with_id_map(path.to_str().unwrap(), &options)
+            .await
+            .unwrap();
+
+        let sources: Vec<TextSource> = doc.pages.iter().map(|p| p.source).collect();
+        assert_eq!(sources, [TextSource::Native, TextSource::Ocr, TextSource::Empty]);
+        assert_eq!(doc.pages[1].text, "Cached text recognized on page two.");
+    }
+
+    #[test]
+    fn test_mixed_pdf_pages_labeled_by_source() {
+        let native_pages = vec![
+            "Introduction to the method and its motivation.".to_string(),
+            "  \n".to_string(),
+            "Results section with a long native text layer.".to_string(),
+            "".to_string(),
+        ];
+        let ocr_pages =
+            HashMap::from([(2, "Scanned figure caption recognised by OCR.".to_string())]);
+
+        let pages = build_pdf_pages(&native_pages, &[], &ocr_pages);
+
+        let sources: Vec<(u32, TextSource)> = pages.iter().map(|p| (p.number, p.source)).collect();
+        assert_eq!(
+            sources,
+            [
+                (1, TextSource::Native),
+                (2, TextSource::Ocr),
+                (3, TextSource::Native),
+                (4, TextSource::Empty),
+            ]
+        );
+        assert_eq!(pages[1].text, "Scanned figure caption recognised by OCR.");
+        assert_eq!(pages[1].paragraphs[0].id, "p2-1");
+        assert!(pages[3].paragraphs.is_empty());
+    }
+}
@@ -0,0 +1,339 @@
+//! Replaying queued edit operations onto a PDF
+//!
+//! Operations are applied in the order they were queued, so a page number refers to
+//! the document as left by the operations before it. Text and watermarks are drawn
+//! into the page content, highlights and notes are added as annotations, and page
+//! deletion, insertion and rotation edit the page tree. Any other operation fails the
+//! save rather than being dropped.
+
+use super::editor::{
+    check_pages, inherited_page_attribute, media_box_size, pdf_error, pdf_text_string,
+    EditorError, PDFEditOperation, WatermarkPosition,
+};
+use super::flatten::{hex_color, Canvas, TextMark, HIGHLIGHT_COLOR, NOTE_COLOR, NOTE_ICON_SIZE};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::{BTreeMap, HashMap};
+
+/// Distance of corner watermarks from the page edges, in points
+const WATERMARK_MARGIN: f32 = 36.0;
+/// Average glyph width of the standard fonts as a fraction of the font size, used to
+/// estimate how wide a watermark is
+const AVERAGE_GLYPH_WIDTH: f32 = 0.5;
+/// Annotation flag asking viewers to print the annotation
+const PRINT_FLAG: i64 = 4;
+
+/// Apply `operations` to `doc` in order. Fails with `PageOutOfRange` if an operation
+/// targets a page the document does not have at that point, and with
+/// `UnsupportedOperation` if it can't be written to a PDF.
+pub fn apply_operations(
+    doc: &mut Document,
+    operations: &[PDFEditOperation],
+) -> Result<(), EditorError> {
+    // Marks are collected per page and written once, so each page's content is only
+    // wrapped one time however many operations draw on it
+    let mut canvases: HashMap<ObjectId, Canvas> = HashMap::new();
+
+    for operation in operations {
+        let pages = doc.get_pages();
+        check_pages(operation, pages.len() as u32)?;
+
+        match operation {
+            PDFEditOperation::AddText {
+                page,
+                x,
+                y,
+                text,
+                font_size,
+                font_family,
+                color,
+            } => {
+                let page_id = pages[page];
+                let canvas = canvases
+                    .entry(page_id)
+                    .or_insert_with(|| Canvas::new(doc, page_id));
+                canvas.text(
+                    doc,
+                    &TextMark {
+                        text,
+                        base_font: standard_font(font_family),
+                        size: *font_size,
+                        color: hex_color(color).unwrap_or_default(),
+                        opacity: None,
+                        matrix: [1.0, 0.0, 0.0, 1.0, *x, *y],
+                    },
+                );
+            }
+            PDFEditOperation::AddWatermark {
+                text,
+                font_size,
+                color,
+                opacity,
+                position,
+                pages: targets,
+            } => {
+                let targets = targets.clone().unwrap_or_else(|| pages.keys().copied().collect());
+                for page in targets {
+                    let page_id = pages[&page];
+                    let (width, height) = media_box_size(doc, page_id);
+                    let canvas = canvases
+                        .entry(page_id)
+                        .or_insert_with(|| Canvas::new(doc, page_id));
+                    canvas.text(
+                        doc,
+                        &TextMark {
+                            text,
+                            base_font: "Helvetica",
+                            size: *font_size,
+                            color: hex_color(color).unwrap_or_default(),
+                            opacity: Some(opacity.clamp(0.0, 1.0)),
+                            matrix: watermark_matrix(position, text, *font_size, width, height),
+                        },
+                    );
+                }
+            }
+            PDFEditOperation::AddHighlight {
+                page,
+                x,
+                y,
+                width,
+                height,
+                color,
+            } => {
+                let (x1, y1) = (x + width, y + height);
+                let color = hex_color(color).unwrap_or(HIGHLIGHT_COLOR);
+                let highlight = dictionary! {
+                    "Subtype" => "Highlight",
+                    "Rect" => reals(&[*x, *y, x1, y1]),
+                    // Upper left, upper right, lower left, lower right
+                    "QuadPoints" => reals(&[*x, y1, x1, y1, *x, *y, x1, *y]),
+                    "C" => reals(&color),
+                };
+                add_annotation(doc, pages[page], highlight)?;
+            }
+            PDFEditOperation::AddAnnotation {
+                page,
+                x,
+                y,
+                content,
+                author,
+            } => {
+                let mut note = dictionary! {
+                    "Subtype" => "Text",
+                    "Rect" => reals(&[*x, *y, x + NOTE_ICON_SIZE, y + NOTE_ICON_SIZE]),
+                    "Contents" => pdf_text_string(content),
+                    "C" => reals(&NOTE_COLOR),
+                };
+                if let Some(author) = author {
+                    note.set("T", pdf_text_string(author));
+                }
+                add_annotation(doc, pages[page], note)?;
+            }
+            PDFEditOperation::DeletePage { page } => {
+                canvases.remove(&pages[page]);
+                doc.delete_pages(&[*page]);
+            }
+            PDFEditOperation::InsertPage {
+                after_page,
+                width,
+                height,
+            } => insert_blank_page(doc, &pages, *after_page, *width, *height)?,
+            PDFEditOperation::RotatePage { page, degrees } => {
+                rotate_page(doc, pages[page], *degrees)?;
+            }
+            other => {
+                return Err(EditorError::UnsupportedOperation(format!(
+                    "Saving {} to a PDF",
+                    unsupported_name(other)
+                )));
+            }
+        }
+    }
+
+    for (page_id, canvas) in canvases {
+        canvas.finish(doc, page_id)?;
+    }
+    Ok(())
+}
+
+/// Add `annotation` to the end of a page's `/Annots`
+fn add_annotation(
+    doc: &mut Document,
+    page_id: ObjectId,
+    mut annotation: Dictionary,
+) -> Result<(), EditorError> {
+    annotation.set("Type", "Annot");
+    annotation.set("F", PRINT_FLAG);
+    annotation.set("P", page_id);
+    let reference = Object::Reference(doc.add_object(annotation));
+
+    let page = doc.get_dictionary(page_id).map_err(pdf_error)?;
+    match page.get(b"Annots").ok().cloned() {
+        Some(Object::Reference(annots_id)) => doc
+            .get_object_mut(annots_id)
+            .and_then(Object::as_array_mut)
+            .map_err(pdf_error)?
+            .push(reference),
+        Some(Object::Array(mut annots)) => {
+            annots.push(reference);
+            doc.get_dictionary_mut(page_id).map_err(pdf_error)?.set("Annots", annots);
+        }
+        _ => {
+            doc.get_dictionary_mut(page_id)
+                .map_err(pdf_error)?
+                .set("Annots", vec![reference]);
+        }
+    }
+    Ok(())
+}
+
+fn reals(values: &[f32]) -> Vec<Object> {
+    values.iter().map(|v| Object::Real(*v)).collect()
+}
+
+/// What the user queued, for operations `apply_operations` can't write
+fn unsupported_name(operation: &PDFEditOperation) -> &'static str {
+    match operation {
+        PDFEditOperation::AddImage { .. } => "images",
+        PDFEditOperation::AddShape { .. } => "shapes",
+        PDFEditOperation::AddLine { .. } => "lines",
+        PDFEditOperation::Redact { .. } => "redactions",
+        PDFEditOperation::AddSignature { .. } => "signatures",
+        PDFEditOperation::FillFormField { .. } => "form field values",
+        PDFEditOperation::AddLink { .. } => "links",
+        PDFEditOperation::AddBookmark { .. } => "bookmarks",
+        _ => "this operation",
+    }
+}
+
+/// The standard font closest to a requested family
+fn standard_font(family: &str) -> &'static str {
+    let family = family.to_lowercase();
+    if family.contains("times") || (family.contains("serif") && !family.contains("sans")) {
+        "Times-Roman"
+    } else if family.contains("courier") || family.contains("mono") {
+        "Courier"
+    } else {
+        "Helvetica"
+    }
+}
+
+/// Text matrix placing a watermark on a `width` x `height` page
+fn watermark_matrix(
+    position: &WatermarkPosition,
+    text: &str,
+    font_size: f32,
+    width: f32,
+    height: f32,
+) -> [f32; 6] {
+    let text_width = text.chars().count() as f32 * font_size * AVERAGE_GLYPH_WIDTH;
+    let left = WATERMARK_MARGIN;
+    let right = width - WATERMARK_MARGIN - text_width;
+    let bottom = WATERMARK_MARGIN;
+    let top = height - WATERMARK_MARGIN - font_size;
+
+    let (x, y) = match position {
+        WatermarkPosition::Center => ((width - text_width) / 2.0, (height - font_size) / 2.0),
+        WatermarkPosition::TopLeft => (left, top),
+        WatermarkPosition::TopRight => (right, top),
+        WatermarkPosition::BottomLeft => (left, bottom),
+        WatermarkPosition::BottomRight => (right, bottom),
+        WatermarkPosition::Diagonal => {
+            // Rotate 45 degrees about the page centre, keeping the text centred on it
+            let (sin, cos) = std::f32::consts::FRAC_PI_4.sin_cos();
+            let (half_w, half_h) = (text_width / 2.0, font_size / 2.0);
+            let x = width / 2.0 - half_w * cos + half_h * sin;
+            let y = height / 2.0 - half_w * sin - half_h * cos;
+            return [cos, sin, -sin, cos, x, y];
+        }
+    };
+    [1.0, 0.0, 0.0, 1.0, x, y]
+}
+
+/// Add a blank page after page `after_page` (0 for the front), in the same page tree
+/// node as its neighbour, and update the page counts above it
+fn insert_blank_page(
+    doc: &mut Document,
+    pages: &BTreeMap<u32, ObjectId>,
+    after_page: u32,
+    width: f32,
+    height: f32,
+) -> Result<(), EditorError> {
+    let (parent_id, index) = match pages.get(&after_page.max(1)) {
+        Some(&neighbour) => {
+            let parent_id = doc
+                .get_dictionary(neighbour)
+                .and_then(|page| page.get(b"Parent"))
+                .and_then(Object::as_reference)
+                .map_err(pdf_error)?;
+            let missing = || EditorError::InvalidDocument("Page missing from its parent".into());
+            let position = kids(doc, parent_id)?
+                .iter()
+                .position(|kid| kid.as_reference().ok() == Some(neighbour))
+                .ok_or_else(missing)?;
+            (parent_id, if after_page == 0 { position } else { position + 1 })
+        }
+        // No pages left to sit next to, so add it to the root of the page tree
+        None => {
+            let root = doc
+                .catalog()
+                .and_then(|catalog| catalog.get(b"Pages"))
+                .and_then(Object::as_reference)
+                .map_err(pdf_error)?;
+            (root, 0)
+        }
+    };
+
+    let contents = doc.add_object(Stream::new(dictionary! {}, Vec::new()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => parent_id,
+        "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+        "Resources" => dictionary! {},
+        "Contents" => contents,
+    });
+    kids_mut(doc, parent_id)?.insert(index, Object::Reference(page_id));
+
+    // Every node from the parent up to the root now holds one more page
+    let mut node = Some(parent_id);
+    for _ in 0..32 {
+        let Some(node_id) = node else { break };
+        let tree = doc.get_dictionary_mut(node_id).map_err(pdf_error)?;
+        let count = tree.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+        tree.set("Count", count + 1);
+        node = tree.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    Ok(())
+}
+
+fn kids(doc: &Document, node_id: ObjectId) -> Result<&Vec<Object>, EditorError> {
+    doc.get_dictionary(node_id)
+        .and_then(|node| node.get(b"Kids"))
+        .and_then(Object::as_array)
+        .map_err(pdf_error)
+}
+
+fn kids_mut(doc: &mut Document, node_id: ObjectId) -> Result<&mut Vec<Object>, EditorError> {
+    doc.get_dictionary_mut(node_id)
+        .and_then(|node| node.get_mut(b"Kids"))
+        .and_then(Object::as_array_mut)
+        .map_err(pdf_error)
+}
+
+/// Turn a page clockwise by `degrees` on top of any rotation it already has
+fn rotate_page(doc: &mut Document, page_id: ObjectId, degrees: i32) -> Result<(), EditorError> {
+    if degrees % 90 != 0 {
+        return Err(EditorError::UnsupportedOperation(format!(
+            "Pages can only be rotated by multiples of 90 degrees, not {}",
+            degrees
+        )));
+    }
+    let current = inherited_page_attribute(doc, page_id, b"Rotate")
+        .and_then(|rotate| rotate.as_i64().ok())
+        .unwrap_or(0);
+    let rotation = (current + i64::from(degrees)).rem_euclid(360);
+
+    doc.get_dictionary_mut(page_id)
+        .map_err(pdf_error)?
+        .set("Rotate", rotation);
+    Ok(())
+}
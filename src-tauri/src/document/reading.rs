@@ -0,0 +1,83 @@
+//! Reading sessions and the habits derived from them
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// How a document was being read during a session
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadingMode {
+    /// Read aloud by text-to-speech
+    Tts,
+    /// Read on screen by the user
+    Manual,
+}
+
+impl ReadingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Tts => "tts",
+            Self::Manual => "manual",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "tts" => Some(Self::Tts),
+            "manual" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// A finished stretch of reading
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReadingSession {
+    pub id: String,
+    pub document_id: String,
+    pub mode: ReadingMode,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    /// Pages shown or spoken during the session
+    pub pages: Vec<u32>,
+    pub words_read: u32,
+}
+
+impl ReadingSession {
+    pub fn duration_secs(&self) -> u64 {
+        (self.ended_at - self.started_at).num_seconds().max(0) as u64
+    }
+}
+
+/// Reading totals for one document
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReadingAnalytics {
+    pub document_id: String,
+    pub session_count: usize,
+    pub total_seconds: u64,
+    /// Distinct pages covered by any session, ascending
+    pub pages_read: Vec<u32>,
+    pub words_read: u32,
+    /// Words per minute over all sessions; `None` until something has been read
+    pub average_wpm: Option<f32>,
+}
+
+impl ReadingAnalytics {
+    pub fn from_sessions(document_id: &str, sessions: &[ReadingSession]) -> Self {
+        let total_seconds: u64 = sessions.iter().map(ReadingSession::duration_secs).sum();
+        let words_read: u32 = sessions.iter().map(|s| s.words_read).sum();
+        let pages_read: BTreeSet<u32> =
+            sessions.iter().flat_map(|s| s.pages.iter().copied()).collect();
+
+        Self {
+            document_id: document_id.to_string(),
+            session_count: sessions.len(),
+            total_seconds,
+            pages_read: pages_read.into_iter().collect(),
+            words_read,
+            average_wpm: (total_seconds > 0 && words_read > 0)
+                .then(|| words_read as f32 * 60.0 / total_seconds as f32),
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! Telling scanned PDFs from digital ones before they are opened
+//!
+//! A few pages spread through the file are sampled. A page looks scanned when its
+//! content stream draws almost no text but images cover most of it; a scan that was
+//! already OCR'd keeps its invisible text layer and counts as digital. Nothing is
+//! rendered or OCR'd, so the check is quick even on long documents.
+
+use super::editor::media_box_size;
+use crate::error::{AppError, DocumentError};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Object, Stream};
+use serde::{Deserialize, Serialize};
+
+/// Most pages sampled from one document
+const MAX_SAMPLED_PAGES: usize = 5;
+/// Glyphs a page must draw to count as having a text layer
+const MIN_TEXT_GLYPHS: usize = 10;
+/// Share of a text-less page images must cover for it to look scanned
+const SCANNED_IMAGE_COVERAGE: f32 = 0.5;
+/// How deeply form XObjects drawn inside one another are followed
+const MAX_FORM_DEPTH: usize = 4;
+
+/// What sampling one page found
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PageSample {
+    /// 1-based page number
+    pub page: u32,
+    /// Glyphs drawn by the page's text operators, visible or not
+    pub glyphs: usize,
+    /// Share of the page covered by images, from 0 to 1
+    pub image_coverage: f32,
+    pub scanned: bool,
+}
+
+/// Whether a PDF looks scanned, and the pages that decided it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanStatus {
+    pub page_count: u32,
+    /// Share of the sampled pages that look scanned, from 0 to 1
+    pub likelihood: f32,
+    /// Whether most sampled pages look scanned, so opening the PDF will need OCR
+    pub scanned: bool,
+    pub samples: Vec<PageSample>,
+}
+
+/// Sample a few pages of the PDF at `path` to tell whether it is scanned
+pub fn is_scanned_pdf(path: &str) -> Result<ScanStatus, AppError> {
+    if !std::path::Path::new(path).exists() {
+        return Err(DocumentError::FileNotFound(path.to_string()).into());
+    }
+    let doc = lopdf::Document::load(path)
+        .map_err(|e| DocumentError::ParseError(format!("{}: {}", path, e)))?;
+
+    let pages = doc.get_pages();
+    let page_count = pages.len() as u32;
+    let samples: Vec<PageSample> = sample_pages(page_count, MAX_SAMPLED_PAGES)
+        .into_iter()
+        .filter_map(|page| Some(sample_page(&doc, page, *pages.get(&page)?)))
+        .collect();
+
+    let scanned_pages = samples.iter().filter(|s| s.scanned).count();
+    let likelihood = if samples.is_empty() {
+        0.0
+    } else {
+        scanned_pages as f32 / samples.len() as f32
+    };
+    Ok(ScanStatus {
+        page_count,
+        likelihood,
+        scanned: likelihood >= 0.5,
+        samples,
+    })
+}
+
+/// Up to `max` page numbers spread evenly from the first page to the last
+fn sample_pages(page_count: u32, max: usize) -> Vec<u32> {
+    let max = max as u32;
+    if page_count <= max {
+        return (1..=page_count).collect();
+    }
+    (0..max)
+        .map(|i| 1 + i * (page_count - 1) / (max - 1))
+        .collect()
+}
+
+fn sample_page(doc: &lopdf::Document, page: u32, page_id: lopdf::ObjectId) -> PageSample {
+    let drawn = walk_page(doc, page_id);
+    let (width, height) = media_box_size(doc, page_id);
+    let image_coverage = (drawn.image_area / (width * height).max(1.0)).min(1.0);
+    PageSample {
+        page,
+        glyphs: drawn.glyphs,
+        image_coverage,
+        scanned: drawn.glyphs < MIN_TEXT_GLYPHS && image_coverage >= SCANNED_IMAGE_COVERAGE,
+    }
+}
+
+/// Image XObjects a page draws, in drawing order, each once
+pub(super) fn page_images(doc: &lopdf::Document, page_id: lopdf::ObjectId) -> Vec<&Stream> {
+    let mut images: Vec<&Stream> = Vec::new();
+    for image in walk_page(doc, page_id).images {
+        if !images.iter().any(|seen| std::ptr::eq(*seen, image)) {
+            images.push(image);
+        }
+    }
+    images
+}
+
+fn walk_page(doc: &lopdf::Document, page_id: lopdf::ObjectId) -> Drawn<'_> {
+    let mut drawn = Drawn::default();
+    let mut resources: Vec<&Dictionary> = Vec::new();
+    if let Ok((own, inherited)) = doc.get_page_resources(page_id) {
+        resources.extend(own);
+        resources.extend(inherited.into_iter().filter_map(|id| doc.get_dictionary(id).ok()));
+    }
+    match doc.get_page_content(page_id) {
+        Ok(content) => drawn.walk(doc, &content, &resources, IDENTITY, 0),
+        Err(e) => tracing::debug!("Could not read content of page object {:?}: {}", page_id, e),
+    }
+    drawn
+}
+
+/// Transformation matrix `[a b c d e f]` as in a PDF `cm` operator
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// `m` applied within the space `ctm` maps to the page
+fn concat(m: Matrix, ctm: Matrix) -> Matrix {
+    let [a, b, c, d, e, f] = m;
+    let [aa, bb, cc, dd, ee, ff] = ctm;
+    [
+        a * aa + b * cc,
+        a * bb + b * dd,
+        c * aa + d * cc,
+        c * bb + d * dd,
+        e * aa + f * cc + ee,
+        e * bb + f * dd + ff,
+    ]
+}
+
+/// Text and images a content stream draws
+#[derive(Default)]
+struct Drawn<'a> {
+    glyphs: usize,
+    /// Page area covered by images, in square points
+    image_area: f32,
+    images: Vec<&'a Stream>,
+}
+
+impl<'a> Drawn<'a> {
+    fn walk(
+        &mut self,
+        doc: &'a lopdf::Document,
+        content: &[u8],
+        resources: &[&'a Dictionary],
+        mut ctm: Matrix,
+        depth: usize,
+    ) {
+        let Ok(content) = Content::decode(content) else {
+            return;
+        };
+        let mut saved = Vec::new();
+        for op in &content.operations {
+            match op.operator.as_str() {
+                "q" => saved.push(ctm),
+                "Q" => ctm = saved.pop().unwrap_or(ctm),
+                "cm" => {
+                    if let Some(m) = matrix(&op.operands) {
+                        ctm = concat(m, ctm);
+                    }
+                }
+                "Tj" | "'" | "\"" | "TJ" => {
+                    self.glyphs += op.operands.iter().map(glyphs).sum::<usize>();
+                }
+                "Do" => {
+                    let name = op.operands.first().and_then(|name| name.as_name().ok());
+                    if let Some(xobject) = name.and_then(|name| xobject(doc, resources, name)) {
+                        self.draw(doc, xobject, resources, ctm, depth);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Draw an image or form XObject; images fill the unit square `ctm` maps
+    fn draw(
+        &mut self,
+        doc: &'a lopdf::Document,
+        xobject: &'a Stream,
+        resources: &[&'a Dictionary],
+        ctm: Matrix,
+        depth: usize,
+    ) {
+        match xobject.dict.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Image") => {
+                let [a, b, c, d, ..] = ctm;
+                self.image_area += (a * d - b * c).abs();
+                self.images.push(xobject);
+            }
+            Ok(b"Form") if depth < MAX_FORM_DEPTH => {
+                let form_matrix = xobject
+                    .dict
+                    .get(b"Matrix")
+                    .ok()
+                    .and_then(|m| matrix(m.as_array().ok()?))
+                    .unwrap_or(IDENTITY);
+                let mut form_resources: Vec<&'a Dictionary> = xobject
+                    .dict
+                    .get(b"Resources")
+                    .ok()
+                    .and_then(|r| doc.dereference(r).ok()?.1.as_dict().ok())
+                    .into_iter()
+                    .collect();
+                form_resources.extend_from_slice(resources);
+                let content = xobject
+                    .decompressed_content()
+                    .unwrap_or_else(|_| xobject.content.clone());
+                self.walk(doc, &content, &form_resources, concat(form_matrix, ctm), depth + 1);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The six numbers of a `cm` operator or `/Matrix` entry
+fn matrix(operands: &[Object]) -> Option<Matrix> {
+    let numbers: Vec<f32> = operands.iter().filter_map(|n| n.as_float().ok()).collect();
+    numbers.try_into().ok()
+}
+
+/// Glyphs in a text operand: the bytes of a string, or of each string in a `TJ` array
+fn glyphs(operand: &Object) -> usize {
+    match operand {
+        Object::String(bytes, _) => bytes.len(),
+        Object::Array(parts) => parts.iter().map(glyphs).sum(),
+        _ => 0,
+    }
+}
+
+/// The XObject called `name` in the first resource dictionary that has one
+fn xobject<'a>(
+    doc: &'a lopdf::Document,
+    resources: &[&'a Dictionary],
+    name: &[u8],
+) -> Option<&'a Stream> {
+    resources.iter().find_map(|resources| {
+        let (_, xobjects) = doc.dereference(resources.get(b"XObject").ok()?).ok()?;
+        let (_, xobject) = doc.dereference(xobjects.as_dict().ok()?.get(name).ok()?).ok()?;
+        xobject.as_stream().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::Operation;
+    use lopdf::dictionary;
+
+    /// Write a Letter-size PDF whose pages each show `text`, or draw a full-page image
+    /// when `text` is `None`
+    fn write_pdf(path: &std::path::Path, pages: &[Option<&str>]) {
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let image_id = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            vec![128],
+        ));
+
+        let mut kids = Vec::new();
+        for text in pages {
+            let operations = match text {
+                Some(text) => vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                    Operation::new("Td", vec![72.into(), 700.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(*text)]),
+                    Operation::new("ET", vec![]),
+                ],
+                None => vec![
+                    Operation::new("q", vec![]),
+                    Operation::new(
+                        "cm",
+                        vec![612.into(), 0.into(), 0.into(), 792.into(), 0.into(), 0.into()],
+                    ),
+                    Operation::new("Do", vec!["Im1".into()]),
+                    Operation::new("Q", vec![]),
+                ],
+            };
+            let content = Content { operations }.encode().unwrap();
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content));
+            kids.push(Object::Reference(doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "Resources" => dictionary! {
+                    "Font" => dictionary! { "F1" => font_id },
+                    "XObject" => dictionary! { "Im1" => image_id },
+                },
+            })));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_text_pdf_is_digital_and_image_pdf_is_scanned() {
+        let dir = tempfile::tempdir().unwrap();
+        let line = "The quick brown fox jumps over the lazy dog.";
+
+        let text_pdf = dir.path().join("text.pdf");
+        write_pdf(&text_pdf, &[Some(line), Some(line), Some(line)]);
+        let status = is_scanned_pdf(text_pdf.to_str().unwrap()).unwrap();
+        assert_eq!(status.page_count, 3);
+        assert_eq!(status.samples.len(), 3);
+        assert!(!status.scanned);
+        assert_eq!(status.likelihood, 0.0);
+        assert!(status.samples.iter().all(|s| s.glyphs == line.len()));
+
+        let image_pdf = dir.path().join("scan.pdf");
+        write_pdf(&image_pdf, &[None, None]);
+        let status = is_scanned_pdf(image_pdf.to_str().unwrap()).unwrap();
+        assert!(status.scanned);
+        assert_eq!(status.likelihood, 1.0);
+        assert!(status
+            .samples
+            .iter()
+            .all(|s| s.glyphs == 0 && (s.image_coverage - 1.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_long_mixed_pdf_is_sampled_across_its_pages() {
+        assert_eq!(sample_pages(12, 5), [1, 3, 6, 9, 12]);
+        assert_eq!(sample_pages(2, 5), [1, 2]);
+
+        // A digital document with a few scanned pages, e.g. signed forms, at the end
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mixed.pdf");
+        let mut pages = vec![Some("Chapter text that came straight from a word processor."); 9];
+        pages.extend([None, None, None]);
+        write_pdf(&path, &pages);
+
+        let status = is_scanned_pdf(path.to_str().unwrap()).unwrap();
+        let scanned: Vec<u32> =
+            status.samples.iter().filter(|s| s.scanned).map(|s| s.page).collect();
+        assert_eq!(scanned, [12]);
+        assert!((status.likelihood - 0.2).abs() < 1e-6);
+        assert!(!status.scanned);
+    }
+}
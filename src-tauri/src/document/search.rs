@@ -0,0 +1,102 @@
+//! Text search over parsed document pages
+
+use super::Document;
+use serde::{Deserialize, Serialize};
+
+/// One occurrence of the query on a page
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TextMatch {
+    /// Character offsets into the page text, as used by selections
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// Occurrences of the query on one page, in reading order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PageMatches {
+    pub page: u32,
+    pub matches: Vec<TextMatch>,
+}
+
+/// Find every non-overlapping occurrence of `query` in the document's page text.
+/// Pages without a match are left out.
+pub fn search_document(doc: &Document, query: &str, case_sensitive: bool) -> Vec<PageMatches> {
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            // Map one character to one so offsets stay aligned with the page text
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+    let needle: Vec<char> = query.chars().map(fold).collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    doc.pages
+        .iter()
+        .filter_map(|page| {
+            let text: Vec<char> = page.text.chars().map(fold).collect();
+            let mut matches = Vec::new();
+            let mut start = 0;
+            while start + needle.len() <= text.len() {
+                if text[start..start + needle.len()] == needle[..] {
+                    matches.push(TextMatch {
+                        start_offset: start,
+                        end_offset: start + needle.len(),
+                    });
+                    start += needle.len();
+                } else {
+                    start += 1;
+                }
+            }
+            (!matches.is_empty()).then_some(PageMatches {
+                page: page.number,
+                matches,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    /// Document with one paragraph per page
+    fn document(pages: &[&str]) -> Document {
+        let pages: Vec<&[&str]> = pages.iter().map(std::slice::from_ref).collect();
+        test_document(&pages)
+    }
+
+    #[test]
+    fn test_matches_reported_per_page_with_character_offsets() {
+        let doc = document(&[
+            "Attention is all you need.",
+            "No match here.",
+            "Über attention: self-ATTENTION and attention heads.",
+        ]);
+
+        let found = search_document(&doc, "attention", false);
+        let offsets = |m: &PageMatches| -> Vec<(usize, usize)> {
+            m.matches.iter().map(|t| (t.start_offset, t.end_offset)).collect()
+        };
+        assert_eq!(found.iter().map(|m| m.page).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(offsets(&found[0]), [(0, 9)]);
+        // "Ü" is two bytes but one character
+        assert_eq!(offsets(&found[1]), [(5, 14), (21, 30), (35, 44)]);
+
+        let exact = search_document(&doc, "attention", true);
+        assert_eq!(exact.len(), 1);
+        assert_eq!(offsets(&exact[0]), [(5, 14), (35, 44)]);
+    }
+
+    #[test]
+    fn test_matches_do_not_overlap() {
+        let doc = document(&["aaaa"]);
+        let found = search_document(&doc, "aa", true);
+        assert_eq!(found[0].matches.len(), 2);
+        assert!(search_document(&doc, "", false).is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+//! Split documents into sections shared by navigation, summaries and difficulty estimates
+
+use super::{get_outline, Document, Paragraph, TOCEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Paragraphs compared on each side of a candidate boundary
+const SIMILARITY_WINDOW: usize = 2;
+
+/// Vocabulary overlap below which neighbouring paragraphs start a new section
+const SIMILARITY_THRESHOLD: f32 = 0.1;
+
+/// Fewest paragraphs in a section found by similarity
+const MIN_SECTION_PARAGRAPHS: usize = 2;
+
+/// Words used for titles of sections found without headings
+const TITLE_WORDS: usize = 8;
+
+/// A run of paragraphs under one heading or topic
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Section {
+    pub title: String,
+    /// Heading depth (1 = top level); 0 for sections found without a heading
+    pub level: usize,
+    /// Page the section starts on
+    pub page: u32,
+    /// Paragraph range in document order across pages, end exclusive
+    pub start_paragraph: usize,
+    pub end_paragraph: usize,
+    pub paragraph_ids: Vec<String>,
+}
+
+impl Section {
+    /// Text of the section's paragraphs, separated by blank lines
+    pub fn text(&self, doc: &Document) -> String {
+        doc.paragraphs()[self.start_paragraph..self.end_paragraph]
+            .iter()
+            .map(|(_, p)| p.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Group paragraphs under the document's headings, or by topic when it has none
+pub fn segment_sections(doc: &Document) -> Vec<Section> {
+    let paragraphs = doc.paragraphs();
+    if paragraphs.is_empty() {
+        return Vec::new();
+    }
+
+    let mut starts = heading_starts(doc, &paragraphs);
+    if starts.is_empty() {
+        starts = similarity_starts(&paragraphs);
+    } else if starts[0].0 > 0 {
+        starts.insert(0, (0, 0, "Front matter".to_string()));
+    }
+
+    let ends: Vec<usize> = starts
+        .iter()
+        .skip(1)
+        .map(|(start, _, _)| *start)
+        .chain(std::iter::once(paragraphs.len()))
+        .collect();
+    starts
+        .into_iter()
+        .zip(ends)
+        .map(|((start, level, title), end)| Section {
+            title,
+            level,
+            page: paragraphs[start].0,
+            start_paragraph: start,
+            end_paragraph: end,
+            paragraph_ids: paragraphs[start..end].iter().map(|(_, p)| p.id.clone()).collect(),
+        })
+        .collect()
+}
+
+/// First paragraph, heading level and title of each outline entry that resolves
+fn heading_starts(doc: &Document, paragraphs: &[(u32, &Paragraph)]) -> Vec<(usize, usize, String)> {
+    fn flatten(entries: &[TOCEntry], level: usize, out: &mut Vec<(usize, TOCEntry)>) {
+        for entry in entries {
+            out.push((level, entry.clone()));
+            flatten(&entry.children, level + 1, out);
+        }
+    }
+    let mut entries = Vec::new();
+    flatten(&get_outline(doc), 1, &mut entries);
+
+    let mut starts: Vec<(usize, usize, String)> = entries
+        .into_iter()
+        .filter_map(|(level, entry)| {
+            let by_anchor = entry
+                .href
+                .strip_prefix('#')
+                .and_then(|id| paragraphs.iter().position(|(_, p)| p.id == id));
+            let by_page = || entry.page.and_then(|n| paragraphs.iter().position(|(p, _)| *p == n));
+            by_anchor.or_else(by_page).map(|start| (start, level, entry.title))
+        })
+        .collect();
+    starts.sort_by_key(|(start, _, _)| *start);
+    starts.dedup_by_key(|(start, _, _)| *start);
+    starts
+}
+
+/// Section starts where the vocabulary of neighbouring paragraphs stops overlapping
+fn similarity_starts(paragraphs: &[(u32, &Paragraph)]) -> Vec<(usize, usize, String)> {
+    let terms: Vec<HashSet<String>> = paragraphs.iter().map(|(_, p)| terms(&p.text)).collect();
+    let window = |range: std::ops::Range<usize>| -> HashSet<&String> {
+        terms[range].iter().flatten().collect()
+    };
+
+    let mut starts = vec![0];
+    for i in MIN_SECTION_PARAGRAPHS..paragraphs.len() {
+        let current = *starts.last().unwrap();
+        if i - current < MIN_SECTION_PARAGRAPHS || paragraphs.len() - i < MIN_SECTION_PARAGRAPHS {
+            continue;
+        }
+        let before = window(i.saturating_sub(SIMILARITY_WINDOW).max(current)..i);
+        let after = window(i..(i + SIMILARITY_WINDOW).min(paragraphs.len()));
+        let union = before.union(&after).count();
+        let overlap = before.intersection(&after).count() as f32 / union.max(1) as f32;
+        if union > 0 && overlap < SIMILARITY_THRESHOLD {
+            starts.push(i);
+        }
+    }
+
+    starts
+        .into_iter()
+        .map(|start| (start, 0, opening_words(&paragraphs[start].1.text)))
+        .collect()
+}
+
+/// Lowercased content words of at least four letters
+fn terms(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 4)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn opening_words(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.len() > TITLE_WORDS {
+        format!("{}…", words[..TITLE_WORDS].join(" "))
+    } else {
+        words.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{test_document, DocumentType};
+
+    fn document(path: &str, doc_type: DocumentType, texts: &[&str]) -> Document {
+        Document {
+            doc_type,
+            path: path.to_string(),
+            ..test_document(&[texts])
+        }
+    }
+
+    #[test]
+    fn test_sections_align_with_headings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("paper.md");
+        std::fs::write(
+            &path,
+            "Draft notes.\n\n# Introduction\n\nWhy it matters.\n\n## Background\n\n\
+             Prior work.\n\nMore prior work.\n\n# Method\n\nWhat we did.",
+        )
+        .unwrap();
+        let doc = document(
+            path.to_str().unwrap(),
+            DocumentType::Markdown,
+            &[
+                "Draft notes.",
+                "Introduction",
+                "Why it matters.",
+                "Background",
+                "Prior work.",
+                "More prior work.",
+                "Method",
+                "What we did.",
+            ],
+        );
+
+        let sections = segment_sections(&doc);
+        let summary: Vec<(&str, usize, usize, usize)> = sections
+            .iter()
+            .map(|s| (s.title.as_str(), s.level, s.start_paragraph, s.end_paragraph))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Front matter", 0, 0, 1),
+                ("Introduction", 1, 1, 3),
+                ("Background", 2, 3, 6),
+                ("Method", 1, 6, 8),
+            ]
+        );
+        assert_eq!(sections[2].paragraph_ids, ["p4", "p5", "p6"]);
+        assert_eq!(sections[2].text(&doc), "Background\n\nPrior work.\n\nMore prior work.");
+    }
+
+    #[test]
+    fn test_unheaded_document_split_by_topic() {
+        let doc = document(
+            "missing.txt",
+            DocumentType::Txt,
+            &[
+                "Cats sleep most of the afternoon in sunny windows.",
+                "Sleeping cats prefer warm windows and quiet afternoons.",
+                "Most cats nap through the warm afternoon.",
+                "Database indexes speed up query lookups considerably.",
+                "Query planners choose which database indexes to scan.",
+                "Without indexes every query scans the whole table.",
+            ],
+        );
+
+        let sections = segment_sections(&doc);
+        assert_eq!(sections.len(), 2);
+        assert_eq!((sections[0].start_paragraph, sections[0].end_paragraph), (0, 3));
+        assert_eq!((sections[1].start_paragraph, sections[1].end_paragraph), (3, 6));
+        assert_eq!(sections[1].level, 0);
+        assert_eq!(sections[1].title, "Database indexes speed up query lookups considerably.");
+    }
+}
@@ -0,0 +1,224 @@
+//! Resolve frontend selection offsets to document text
+
+use super::{Document, Page, Paragraph};
+use crate::error::DocumentError;
+use serde::{Deserialize, Serialize};
+
+/// Characters of surrounding text returned on each side of a selection
+const NEIGHBOR_CHARS: usize = 200;
+
+/// A selected range of page text with its surroundings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SelectionContext {
+    pub page: u32,
+    /// Character offsets into the page text
+    pub start_offset: usize,
+    pub end_offset: usize,
+    pub text: String,
+    /// Paragraph containing the start of the selection
+    pub paragraph_id: Option<String>,
+    /// Page text just before the selection
+    pub before: String,
+    /// Page text just after the selection
+    pub after: String,
+}
+
+/// Resolve a character range on a page to its text, paragraph and neighboring text
+pub fn resolve_selection(
+    doc: &Document,
+    page: u32,
+    start: usize,
+    end: usize,
+) -> Result<SelectionContext, DocumentError> {
+    let page_data = doc
+        .pages
+        .iter()
+        .find(|p| p.number == page)
+        .ok_or(DocumentError::PageNotFound(page))?;
+
+    let chars: Vec<char> = page_data.text.chars().collect();
+    if start > end || end > chars.len() {
+        return Err(DocumentError::InvalidSelection {
+            start,
+            end,
+            page_length: chars.len(),
+        });
+    }
+
+    let slice = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+    Ok(SelectionContext {
+        page,
+        start_offset: start,
+        end_offset: end,
+        text: slice(start, end),
+        paragraph_id: paragraph_at(page_data, start),
+        before: slice(start.saturating_sub(NEIGHBOR_CHARS), start),
+        after: slice(end, (end + NEIGHBOR_CHARS).min(chars.len())),
+    })
+}
+
+/// A word of page text, located within its paragraph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParagraphWord {
+    pub paragraph_id: String,
+    /// Index of the word within the paragraph
+    pub word_index: u32,
+    /// Character offset of the word within the paragraph
+    pub character_offset: u32,
+}
+
+/// The whitespace-separated words of a paragraph's text, in order
+pub fn paragraph_words(paragraph_id: &str, text: &str) -> Vec<ParagraphWord> {
+    word_spans(text)
+        .into_iter()
+        .enumerate()
+        .map(|(word_index, (word_start, _))| ParagraphWord {
+            paragraph_id: paragraph_id.to_string(),
+            word_index: word_index as u32,
+            character_offset: word_start as u32,
+        })
+        .collect()
+}
+
+/// The words of a page covered by a character range, in reading order. Words the range
+/// cuts through are included, so they line up with the words of the selected text.
+pub fn selected_words(page: &Page, start: usize, end: usize) -> Vec<ParagraphWord> {
+    let mut words = Vec::new();
+    for (paragraph, from, to) in paragraph_spans(page) {
+        if to <= start || from >= end {
+            continue;
+        }
+        let spans = word_spans(&paragraph.text);
+        for (word_index, (word_start, word_end)) in spans.into_iter().enumerate() {
+            if from + word_end > start && from + word_start < end {
+                words.push(ParagraphWord {
+                    paragraph_id: paragraph.id.clone(),
+                    word_index: word_index as u32,
+                    character_offset: word_start as u32,
+                });
+            }
+        }
+    }
+    words
+}
+
+/// Character ranges of the whitespace-separated words of `text`
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut word_start = None;
+    for (i, c) in text.chars().enumerate() {
+        match (c.is_whitespace(), word_start) {
+            (true, Some(from)) => {
+                spans.push((from, i));
+                word_start = None;
+            }
+            (false, None) => word_start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(from) = word_start {
+        spans.push((from, text.chars().count()));
+    }
+    spans
+}
+
+/// Paragraphs found in the page text, with their character ranges
+fn paragraph_spans(page: &Page) -> Vec<(&Paragraph, usize, usize)> {
+    let mut spans = Vec::new();
+    let mut cursor = 0;
+    for paragraph in &page.paragraphs {
+        let Some(found) = page.text[cursor..].find(paragraph.text.as_str()) else {
+            continue;
+        };
+        let byte_start = cursor + found;
+        let start = page.text[..byte_start].chars().count();
+        spans.push((paragraph, start, start + paragraph.text.chars().count()));
+        cursor = byte_start + paragraph.text.len();
+    }
+    spans
+}
+
+/// Id of the paragraph covering a character offset, or the last one starting before it
+fn paragraph_at(page: &Page, offset: usize) -> Option<String> {
+    let mut preceding = None;
+    for (paragraph, start, end) in paragraph_spans(page) {
+        if start > offset {
+            break;
+        }
+        if offset < end {
+            return Some(paragraph.id.clone());
+        }
+        preceding = Some(paragraph.id.clone());
+    }
+    preceding
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    fn document() -> Document {
+        test_document(&[&["Première partie.", "The second paragraph holds the answer."]])
+    }
+
+    #[test]
+    fn test_selection_resolves_to_text_and_paragraph() {
+        let doc = document();
+
+        // "Première" has a multi-byte character, so offsets are in characters
+        let first = resolve_selection(&doc, 1, 0, 8).unwrap();
+        assert_eq!(first.text, "Première");
+        assert_eq!(first.paragraph_id.as_deref(), Some("p1"));
+        assert_eq!(first.before, "");
+        assert!(first.after.starts_with(" partie.\n\nThe second"));
+
+        let answer = resolve_selection(&doc, 1, 49, 55).unwrap();
+        assert_eq!(answer.text, "answer");
+        assert_eq!(answer.paragraph_id.as_deref(), Some("p2"));
+        assert!(answer.before.ends_with("holds the "));
+        assert_eq!(answer.after, ".");
+
+        // The blank line between paragraphs belongs to the one before it
+        let gap = resolve_selection(&doc, 1, 16, 17).unwrap();
+        assert_eq!(gap.paragraph_id.as_deref(), Some("p1"));
+    }
+
+    #[test]
+    fn test_out_of_range_selection_is_rejected() {
+        let doc = document();
+        let page_length = doc.pages[0].text.chars().count();
+
+        assert!(matches!(
+            resolve_selection(&doc, 1, 10, page_length + 1),
+            Err(DocumentError::InvalidSelection { page_length: len, .. }) if len == page_length
+        ));
+        assert!(matches!(
+            resolve_selection(&doc, 1, 5, 4),
+            Err(DocumentError::InvalidSelection { .. })
+        ));
+        assert!(matches!(
+            resolve_selection(&doc, 2, 0, 1),
+            Err(DocumentError::PageNotFound(2))
+        ));
+        assert!(resolve_selection(&doc, 1, page_length, page_length).is_ok());
+    }
+
+    #[test]
+    fn test_selected_words_are_indexed_within_their_paragraph() {
+        let doc = document();
+        let word = |paragraph_id: &str, word_index, character_offset| ParagraphWord {
+            paragraph_id: paragraph_id.to_string(),
+            word_index,
+            character_offset,
+        };
+
+        // "partie.\n\nThe sec" cuts into "second", which still counts as a word
+        assert_eq!(
+            selected_words(&doc.pages[0], 9, 25),
+            vec![word("p1", 1, 9), word("p2", 0, 0), word("p2", 1, 4)]
+        );
+        assert_eq!(selected_words(&doc.pages[0], 49, 55), vec![word("p2", 5, 31)]);
+        assert!(selected_words(&doc.pages[0], 16, 18).is_empty());
+    }
+}
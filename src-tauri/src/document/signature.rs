@@ -0,0 +1,700 @@
+//! Read-only checks of digital signatures in PDFs
+//!
+//! Each signature dictionary names the byte ranges of the file it covers and carries
+//! a detached CMS (PKCS#7) blob. The check hashes those ranges, compares the result
+//! with the message digest the signer committed to, and verifies the signature over
+//! that digest with the public key in the signer's certificate. Together these show
+//! whether the signed bytes have changed since signing. It does not validate the
+//! certificate chain, so it says nothing about whether the signer can be trusted.
+
+use super::editor::EditorError;
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::{SignedData, SignerIdentifier};
+use der::asn1::{
+    Any, BmpString, Ia5StringRef, ObjectIdentifier, OctetString, PrintableStringRef,
+    TeletexStringRef, Utf8StringRef,
+};
+use der::{Decode, Encode, SliceReader, Tag, Tagged};
+use lopdf::{Dictionary, Object, ObjectId};
+use p256::pkcs8::DecodePublicKey;
+use rsa::Pkcs1v15Sign;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use std::collections::HashSet;
+use x509_cert::ext::pkix::SubjectKeyIdentifier;
+use x509_cert::name::Name;
+use x509_cert::Certificate;
+
+const OID_SIGNED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+const OID_MESSAGE_DIGEST: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+const OID_COMMON_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.3");
+const OID_SUBJECT_KEY_IDENTIFIER: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.14");
+const OID_SHA1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.14.3.2.26");
+const OID_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+const OID_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.2");
+const OID_SHA512: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.3");
+const OID_RSA_ENCRYPTION: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const OID_RSASSA_PSS: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.10");
+const OID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+
+/// What a check found about one signature
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignatureStatus {
+    /// Name of the signature form field
+    pub field_name: Option<String>,
+    /// Common name from the signing certificate, or the `/Name` the signer gave
+    pub signer: Option<String>,
+    /// Signing time claimed in the signature dictionary (`/M`)
+    pub signing_time: Option<String>,
+    /// Whether the signed byte ranges reach the end of the file, i.e. nothing was
+    /// appended after signing
+    pub covers_whole_document: bool,
+    /// Whether the signed bytes still match the signed digest and the signature over
+    /// it verifies with the signer's certificate; `None` if it couldn't be checked
+    pub intact: Option<bool>,
+    pub digest_algorithm: Option<String>,
+    /// Why the signature couldn't be checked
+    pub error: Option<String>,
+}
+
+/// Check every signature in a PDF, in the order they were applied
+pub fn verify_signatures(path: &str) -> Result<Vec<SignatureStatus>, EditorError> {
+    let bytes = std::fs::read(path).map_err(|e| EditorError::IoError(e.to_string()))?;
+    let doc = lopdf::Document::load_mem(&bytes)
+        .map_err(|e| EditorError::InvalidDocument(e.to_string()))?;
+
+    let mut statuses: Vec<(u64, SignatureStatus)> = signature_dictionaries(&doc)
+        .into_iter()
+        .map(|(field_name, sig)| check_signature(&bytes, field_name, sig))
+        .collect();
+    statuses.sort_by_key(|(end, _)| *end);
+    Ok(statuses.into_iter().map(|(_, status)| status).collect())
+}
+
+/// Signature dictionaries with the names of the form fields holding them
+fn signature_dictionaries(doc: &lopdf::Document) -> Vec<(Option<String>, &Dictionary)> {
+    let mut found = Vec::new();
+    let mut seen: HashSet<ObjectId> = HashSet::new();
+
+    for object in doc.objects.values() {
+        let Ok(field) = object.as_dict() else {
+            continue;
+        };
+        if field.get(b"FT").and_then(Object::as_name).ok() != Some(b"Sig".as_slice()) {
+            continue;
+        }
+        let name = field
+            .get(b"T")
+            .ok()
+            .and_then(|t| lopdf::decode_text_string(t).ok());
+        match field.get(b"V") {
+            Ok(Object::Reference(id)) => {
+                if let Ok(sig) = doc.get_dictionary(*id) {
+                    seen.insert(*id);
+                    found.push((name, sig));
+                }
+            }
+            Ok(Object::Dictionary(sig)) => found.push((name, sig)),
+            _ => {}
+        }
+    }
+
+    // Signatures not attached to a form field
+    for (id, object) in &doc.objects {
+        if let Ok(sig) = object.as_dict() {
+            let is_signature = sig.has(b"ByteRange") && sig.has(b"Contents");
+            if is_signature && !seen.contains(id) && !sig.has(b"FT") {
+                found.push((None, sig));
+            }
+        }
+    }
+    found
+}
+
+/// Check one signature; returns the end of its signed range with the status
+fn check_signature(
+    bytes: &[u8],
+    field_name: Option<String>,
+    sig: &Dictionary,
+) -> (u64, SignatureStatus) {
+    let text = |key: &[u8]| sig.get(key).ok().and_then(|v| lopdf::decode_text_string(v).ok());
+    let mut status = SignatureStatus {
+        field_name,
+        signer: text(b"Name"),
+        signing_time: text(b"M"),
+        covers_whole_document: false,
+        intact: None,
+        digest_algorithm: None,
+        error: None,
+    };
+
+    let contents = match sig.get(b"Contents").and_then(Object::as_str) {
+        Ok(contents) => contents,
+        Err(_) => {
+            status.error = Some("The signature has no /Contents".to_string());
+            return (0, status);
+        }
+    };
+    let ranges = match byte_ranges(sig, bytes, contents) {
+        Ok(ranges) => ranges,
+        Err(reason) => {
+            status.error = Some(reason);
+            return (0, status);
+        }
+    };
+    let end = ranges.last().map_or(0, |(start, len)| start + len);
+    status.covers_whole_document = bytes[end..].iter().all(u8::is_ascii_whitespace);
+
+    let cms = match parse_cms(contents) {
+        Ok(cms) => cms,
+        Err(reason) => {
+            status.error = Some(reason);
+            return (end as u64, status);
+        }
+    };
+    let certificate_name = cms.certificate.as_ref().map(|c| &c.tbs_certificate.subject);
+    if let Some(signer) = certificate_name.and_then(common_name) {
+        status.signer = Some(signer);
+    }
+
+    let Some(algorithm) = DigestAlgorithm::from_oid(&cms.digest_oid) else {
+        status.error = Some(format!("Unsupported digest algorithm {}", cms.digest_oid));
+        return (end as u64, status);
+    };
+    status.digest_algorithm = Some(algorithm.name().to_string());
+
+    let signed: Vec<&[u8]> = ranges
+        .iter()
+        .map(|&(start, len)| &bytes[start..start + len])
+        .collect();
+    let actual = algorithm.hash(&signed);
+    // With signed attributes the signature covers them and they carry the digest of
+    // the signed bytes; without, the signature covers that digest directly
+    let (digest_matches, signed_message) = match &cms.signed_attrs {
+        Some(attrs) => match &cms.message_digest {
+            Some(expected) => (*expected == actual, algorithm.hash(&[attrs])),
+            None => {
+                status.error = Some("The signature has no signed message digest".to_string());
+                return (end as u64, status);
+            }
+        },
+        None => (true, actual),
+    };
+    if !digest_matches {
+        status.intact = Some(false);
+        return (end as u64, status);
+    }
+
+    let Some(certificate) = &cms.certificate else {
+        status.error = Some("The signature does not include the signer's certificate".to_string());
+        return (end as u64, status);
+    };
+    match verify_signer(certificate, algorithm, &cms, &signed_message) {
+        Ok(verified) => status.intact = Some(verified),
+        Err(reason) => status.error = Some(reason),
+    }
+    (end as u64, status)
+}
+
+/// `/ByteRange` as (start, length) pairs. A PDF signature signs everything but its
+/// own `/Contents`, so there must be two ranges starting at the beginning of the file
+/// whose gap is exactly the hex string holding `contents`.
+fn byte_ranges(
+    sig: &Dictionary,
+    bytes: &[u8],
+    contents: &[u8],
+) -> Result<Vec<(usize, usize)>, String> {
+    let invalid = || "The signature has an invalid /ByteRange".to_string();
+    let values: Vec<usize> = sig
+        .get(b"ByteRange")
+        .and_then(Object::as_array)
+        .map_err(|_| invalid())?
+        .iter()
+        .map(|n| n.as_i64().ok().and_then(|n| usize::try_from(n).ok()))
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)?;
+
+    let [0, first_len, second_start, second_len] = values[..] else {
+        return Err(invalid());
+    };
+    let in_file = second_start
+        .checked_add(second_len)
+        .is_some_and(|end| end <= bytes.len());
+    if first_len >= second_start || !in_file {
+        return Err(invalid());
+    }
+
+    let gap = &bytes[first_len..second_start];
+    let hex = gap
+        .strip_prefix(b"<")
+        .and_then(|gap| gap.strip_suffix(b">"))
+        .filter(|hex| hex.len() % 2 == 0)
+        .ok_or_else(|| "The /ByteRange gap is not the /Contents string".to_string())?;
+    if decode_hex(hex).as_deref() != Some(contents) {
+        return Err("The /ByteRange gap is not the /Contents string".to_string());
+    }
+    Ok(vec![(0, first_len), (second_start, second_len)])
+}
+
+fn decode_hex(hex: &[u8]) -> Option<Vec<u8>> {
+    hex.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Digest algorithms a signer can commit to
+#[derive(Debug, Clone, Copy)]
+enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    fn from_oid(oid: &ObjectIdentifier) -> Option<Self> {
+        [
+            (OID_SHA1, Self::Sha1),
+            (OID_SHA256, Self::Sha256),
+            (OID_SHA384, Self::Sha384),
+            (OID_SHA512, Self::Sha512),
+        ]
+        .into_iter()
+        .find_map(|(known, algorithm)| (known == *oid).then_some(algorithm))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Sha1 => "SHA-1",
+            Self::Sha256 => "SHA-256",
+            Self::Sha384 => "SHA-384",
+            Self::Sha512 => "SHA-512",
+        }
+    }
+
+    fn hash(self, parts: &[&[u8]]) -> Vec<u8> {
+        fn hash<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.finalize().to_vec()
+        }
+
+        match self {
+            Self::Sha1 => hash::<Sha1>(parts),
+            Self::Sha256 => hash::<Sha256>(parts),
+            Self::Sha384 => hash::<Sha384>(parts),
+            Self::Sha512 => hash::<Sha512>(parts),
+        }
+    }
+
+    fn pkcs1v15(self) -> Pkcs1v15Sign {
+        match self {
+            Self::Sha1 => Pkcs1v15Sign::new::<Sha1>(),
+            Self::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+            Self::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+            Self::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+        }
+    }
+}
+
+/// The parts of a CMS SignedData blob needed to check a signature
+struct CmsSignature {
+    digest_oid: ObjectIdentifier,
+    message_digest: Option<Vec<u8>>,
+    /// DER encoding of the signed attributes, which the signature covers
+    signed_attrs: Option<Vec<u8>>,
+    signature_oid: ObjectIdentifier,
+    signature: Vec<u8>,
+    /// The signer's certificate, when the blob includes it
+    certificate: Option<Certificate>,
+}
+
+fn parse_cms(blob: &[u8]) -> Result<CmsSignature, String> {
+    let malformed = || "The signature is not a readable CMS SignedData blob".to_string();
+
+    // /Contents is padded with zeros after the blob
+    let mut reader = SliceReader::new(blob).map_err(|_| malformed())?;
+    let content_info = ContentInfo::decode(&mut reader).map_err(|_| malformed())?;
+    if content_info.content_type != OID_SIGNED_DATA {
+        return Err(malformed());
+    }
+    let signed_data: SignedData = content_info.content.decode_as().map_err(|_| malformed())?;
+    let signer_info = signed_data.signer_infos.0.iter().next().ok_or_else(malformed)?;
+
+    let message_digest = signer_info
+        .signed_attrs
+        .iter()
+        .flat_map(|attrs| attrs.iter())
+        .find(|attr| attr.oid == OID_MESSAGE_DIGEST)
+        .and_then(|attr| attr.values.iter().next())
+        .and_then(|value| value.decode_as::<OctetString>().ok())
+        .map(|digest| digest.into_bytes());
+    let signed_attrs = signer_info
+        .signed_attrs
+        .as_ref()
+        .map(|attrs| attrs.to_der())
+        .transpose()
+        .map_err(|_| malformed())?;
+
+    let mut certificates = signed_data
+        .certificates
+        .iter()
+        .flat_map(|set| set.0.iter())
+        .filter_map(|choice| match choice {
+            CertificateChoices::Certificate(certificate) => Some(certificate),
+            _ => None,
+        });
+    let certificate = match &signer_info.sid {
+        SignerIdentifier::IssuerAndSerialNumber(id) => certificates
+            .filter(|c| c.tbs_certificate.serial_number == id.serial_number)
+            .find(|c| c.tbs_certificate.issuer == id.issuer),
+        SignerIdentifier::SubjectKeyIdentifier(id) => {
+            certificates.find(|c| subject_key_identifier(c).as_ref() == Some(id))
+        }
+    };
+
+    Ok(CmsSignature {
+        digest_oid: signer_info.digest_alg.oid,
+        message_digest,
+        signed_attrs,
+        signature_oid: signer_info.signature_algorithm.oid,
+        signature: signer_info.signature.as_bytes().to_vec(),
+        certificate: certificate.cloned(),
+    })
+}
+
+fn subject_key_identifier(certificate: &Certificate) -> Option<SubjectKeyIdentifier> {
+    certificate
+        .tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|ext| ext.extn_id == OID_SUBJECT_KEY_IDENTIFIER)
+        .and_then(|ext| SubjectKeyIdentifier::from_der(ext.extn_value.as_bytes()).ok())
+}
+
+/// Verify the signature over `message` with the certificate's public key. Returns an
+/// error for keys and schemes that can't be checked.
+fn verify_signer(
+    certificate: &Certificate,
+    algorithm: DigestAlgorithm,
+    cms: &CmsSignature,
+    message: &[u8],
+) -> Result<bool, String> {
+    let key_info = &certificate.tbs_certificate.subject_public_key_info;
+    let key_der = key_info
+        .to_der()
+        .map_err(|e| format!("The signer's public key is unreadable: {}", e))?;
+
+    if key_info.algorithm.oid == OID_RSA_ENCRYPTION {
+        if cms.signature_oid == OID_RSASSA_PSS {
+            return Err("RSA-PSS signatures are not supported".to_string());
+        }
+        let key = rsa::RsaPublicKey::from_public_key_der(&key_der)
+            .map_err(|e| format!("The signer's RSA key is unreadable: {}", e))?;
+        return Ok(key.verify(algorithm.pkcs1v15(), message, &cms.signature).is_ok());
+    }
+
+    if key_info.algorithm.oid == OID_EC_PUBLIC_KEY {
+        if let Ok(key) = p256::ecdsa::VerifyingKey::from_public_key_der(&key_der) {
+            let Ok(signature) = p256::ecdsa::Signature::from_der(&cms.signature) else {
+                return Ok(false);
+            };
+            return Ok(key.verify_prehash(message, &signature).is_ok());
+        }
+        if let Ok(key) = p384::ecdsa::VerifyingKey::from_public_key_der(&key_der) {
+            let Ok(signature) = p384::ecdsa::Signature::from_der(&cms.signature) else {
+                return Ok(false);
+            };
+            return Ok(key.verify_prehash(message, &signature).is_ok());
+        }
+        return Err("Only P-256 and P-384 ECDSA keys are supported".to_string());
+    }
+
+    Err(format!("Unsupported public key algorithm {}", key_info.algorithm.oid))
+}
+
+fn common_name(name: &Name) -> Option<String> {
+    name.0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .find(|attr| attr.oid == OID_COMMON_NAME)
+        .and_then(|attr| decode_string(&attr.value))
+}
+
+fn decode_string(value: &Any) -> Option<String> {
+    match value.tag() {
+        Tag::Utf8String => value.decode_as::<Utf8StringRef>().ok().map(|s| s.to_string()),
+        Tag::PrintableString => value
+            .decode_as::<PrintableStringRef>()
+            .ok()
+            .map(|s| s.to_string()),
+        Tag::Ia5String => value.decode_as::<Ia5StringRef>().ok().map(|s| s.to_string()),
+        Tag::TeletexString => value.decode_as::<TeletexStringRef>().ok().map(|s| s.to_string()),
+        Tag::BmpString => value.decode_as::<BmpString>().ok().map(|s| s.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cms::cert::IssuerAndSerialNumber;
+    use cms::content_info::CmsVersion;
+    use cms::signed_data::{CertificateSet, EncapsulatedContentInfo, SignerInfo, SignerInfos};
+    use der::asn1::{BitString, SetOfVec};
+    use lopdf::{dictionary, StringFormat};
+    use p256::ecdsa::signature::hazmat::PrehashSigner;
+    use p256::ecdsa::SigningKey;
+    use p256::pkcs8::EncodePublicKey;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use x509_cert::attr::Attribute;
+    use x509_cert::certificate::{TbsCertificate, Version};
+    use x509_cert::serial_number::SerialNumber;
+    use x509_cert::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+    use x509_cert::time::Validity;
+
+    const OID_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+    const OID_ECDSA_WITH_SHA256: ObjectIdentifier =
+        ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+
+    fn algorithm(oid: ObjectIdentifier) -> AlgorithmIdentifierOwned {
+        AlgorithmIdentifierOwned {
+            oid,
+            parameters: None,
+        }
+    }
+
+    fn signer_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32].into()).unwrap()
+    }
+
+    /// A certificate for `signer_key`. Nothing checks the issuer's signature on it.
+    fn certificate() -> Certificate {
+        let public_key = signer_key().verifying_key().to_public_key_der().unwrap();
+        Certificate {
+            tbs_certificate: TbsCertificate {
+                version: Version::V3,
+                serial_number: SerialNumber::new(&[0x2A]).unwrap(),
+                signature: algorithm(OID_ECDSA_WITH_SHA256),
+                issuer: Name::from_str("CN=Test CA").unwrap(),
+                validity: Validity::from_now(Duration::from_secs(3600)).unwrap(),
+                subject: Name::from_str("CN=Ada Lovelace").unwrap(),
+                subject_public_key_info: SubjectPublicKeyInfoOwned::from_der(
+                    public_key.as_bytes(),
+                )
+                .unwrap(),
+                issuer_unique_id: None,
+                subject_unique_id: None,
+                extensions: None,
+            },
+            signature_algorithm: algorithm(OID_ECDSA_WITH_SHA256),
+            signature: BitString::from_bytes(&[0]).unwrap(),
+        }
+    }
+
+    /// A detached SignedData blob committing to `digest`, signed by `key` and carrying
+    /// the certificate for `signer_key`
+    fn cms(digest: &[u8], key: &SigningKey) -> Vec<u8> {
+        let certificate = certificate();
+        let message_digest = Attribute {
+            oid: OID_MESSAGE_DIGEST,
+            values: SetOfVec::try_from(vec![
+                Any::encode_from(&OctetString::new(digest).unwrap()).unwrap()
+            ])
+            .unwrap(),
+        };
+        let signed_attrs = SetOfVec::try_from(vec![message_digest]).unwrap();
+        let signature: p256::ecdsa::Signature = key
+            .sign_prehash(&Sha256::digest(signed_attrs.to_der().unwrap()))
+            .unwrap();
+
+        let signer_info = SignerInfo {
+            version: CmsVersion::V1,
+            sid: SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+                issuer: certificate.tbs_certificate.issuer.clone(),
+                serial_number: certificate.tbs_certificate.serial_number.clone(),
+            }),
+            digest_alg: algorithm(OID_SHA256),
+            signed_attrs: Some(signed_attrs),
+            signature_algorithm: algorithm(OID_ECDSA_WITH_SHA256),
+            signature: OctetString::new(signature.to_der().as_bytes()).unwrap(),
+            unsigned_attrs: None,
+        };
+        let signed_data = SignedData {
+            version: CmsVersion::V1,
+            digest_algorithms: SetOfVec::try_from(vec![algorithm(OID_SHA256)]).unwrap(),
+            encap_content_info: EncapsulatedContentInfo {
+                econtent_type: OID_DATA,
+                econtent: None,
+            },
+            certificates: Some(CertificateSet(
+                SetOfVec::try_from(vec![CertificateChoices::Certificate(certificate)])
+                    .unwrap(),
+            )),
+            crls: None,
+            signer_infos: SignerInfos(SetOfVec::try_from(vec![signer_info]).unwrap()),
+        };
+        ContentInfo {
+            content_type: OID_SIGNED_DATA,
+            content: Any::encode_from(&signed_data).unwrap(),
+        }
+        .to_der()
+        .unwrap()
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> usize {
+        haystack
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .unwrap()
+    }
+
+    fn signed_pdf() -> Vec<u8> {
+        signed_pdf_by(&signer_key())
+    }
+
+    /// Write a one-page PDF signed the way signing tools do: reserve /Contents, fill
+    /// in /ByteRange around it, then embed a signature by `key` over those ranges
+    fn signed_pdf_by(key: &SigningKey) -> Vec<u8> {
+        const PLACEHOLDER: usize = 1024;
+
+        let mut doc = lopdf::Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let unset = Object::Integer(1_000_000_000);
+        let sig_id = doc.add_object(dictionary! {
+            "Type" => "Sig",
+            "Filter" => "Adobe.PPKLite",
+            "SubFilter" => "adbe.pkcs7.detached",
+            "Name" => Object::string_literal("Signer field name"),
+            "M" => Object::string_literal("D:20240102030405Z"),
+            "ByteRange" => vec![0.into(), unset.clone(), unset.clone(), unset],
+            "Contents" => Object::String(vec![0; PLACEHOLDER], StringFormat::Hexadecimal),
+        });
+        let field_id = doc.add_object(dictionary! {
+            "FT" => "Sig",
+            "T" => Object::string_literal("Approval"),
+            "V" => sig_id,
+        });
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "AcroForm" => dictionary! { "Fields" => vec![field_id.into()] },
+        });
+        doc.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+
+        let placeholder = format!("<{}>", "0".repeat(PLACEHOLDER * 2));
+        let contents_start = find(&bytes, placeholder.as_bytes());
+        let contents_end = contents_start + placeholder.len();
+        let after = bytes.len() - contents_end;
+
+        let range_start = find(&bytes, b"[0 1000000000 1000000000 1000000000]");
+        let range = format!("[0 {} {} {}]", contents_start, contents_end, after);
+        let padded = format!("{:<36}", range);
+        bytes[range_start..range_start + 36].copy_from_slice(padded.as_bytes());
+
+        let digest = Sha256::new()
+            .chain_update(&bytes[..contents_start])
+            .chain_update(&bytes[contents_end..])
+            .finalize();
+        let hex: String = cms(&digest, key).iter().map(|b| format!("{:02X}", b)).collect();
+        bytes[contents_start + 1..contents_start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+        bytes
+    }
+
+    fn verify(bytes: &[u8]) -> Vec<SignatureStatus> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), bytes).unwrap();
+        verify_signatures(file.path().to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_signed_pdf_reported_intact() {
+        let statuses = verify(&signed_pdf());
+        assert_eq!(
+            statuses,
+            [SignatureStatus {
+                field_name: Some("Approval".to_string()),
+                signer: Some("Ada Lovelace".to_string()),
+                signing_time: Some("D:20240102030405Z".to_string()),
+                covers_whole_document: true,
+                intact: Some(true),
+                digest_algorithm: Some("SHA-256".to_string()),
+                error: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_tampered_pdf_reported_invalid() {
+        let mut tampered = signed_pdf();
+        let date = find(&tampered, b"D:2024");
+        tampered[date + 5] = b'5';
+
+        let status = &verify(&tampered)[0];
+        assert_eq!(status.intact, Some(false));
+        assert_eq!(status.signing_time.as_deref(), Some("D:20250102030405Z"));
+    }
+
+    #[test]
+    fn test_appended_update_reported_as_partial_coverage() {
+        let mut updated = signed_pdf();
+        updated.extend_from_slice(b"% incremental update\n");
+
+        let status = &verify(&updated)[0];
+        assert_eq!(status.intact, Some(true));
+        assert!(!status.covers_whole_document);
+    }
+
+    #[test]
+    fn test_signature_by_another_key_reported_invalid() {
+        let other = SigningKey::from_bytes(&[9; 32].into()).unwrap();
+
+        let status = &verify(&signed_pdf_by(&other))[0];
+        assert_eq!(status.intact, Some(false));
+        assert_eq!(status.error, None);
+    }
+
+    #[test]
+    fn test_byte_range_gap_must_be_contents() {
+        let bytes = b"head<0102>tail";
+        let sig = |range: [i64; 4]| {
+            dictionary! { "ByteRange" => range.iter().map(|&n| n.into()).collect::<Vec<Object>>() }
+        };
+
+        let ranges = byte_ranges(&sig([0, 4, 10, 4]), bytes, &[1, 2]).unwrap();
+        assert_eq!(ranges, [(0, 4), (10, 4)]);
+        // Leaves unsigned bytes outside the hex string
+        assert!(byte_ranges(&sig([0, 3, 10, 4]), bytes, &[1, 2]).is_err());
+        // Signs part of the hex string
+        assert!(byte_ranges(&sig([0, 5, 10, 4]), bytes, &[1, 2]).is_err());
+        // The gap holds a different value than /Contents
+        assert!(byte_ranges(&sig([0, 4, 10, 4]), bytes, &[1, 3]).is_err());
+        // Doesn't start at the beginning of the file
+        assert!(byte_ranges(&sig([1, 3, 10, 4]), bytes, &[1, 2]).is_err());
+    }
+}
@@ -38,6 +38,22 @@ pub enum DocumentError {
 
     #[error("Invalid document ID")]
     InvalidId,
+
+    #[error("Page {0} not found")]
+    PageNotFound(u32),
+
+    #[error("Metadata key is empty")]
+    EmptyMetadataKey,
+
+    #[error("Tag is empty")]
+    EmptyTag,
+
+    #[error("Invalid selection {start}..{end} on a page of {page_length} characters")]
+    InvalidSelection {
+        start: usize,
+        end: usize,
+        page_length: usize,
+    },
 }
 
 /// Annotation-related errors
@@ -46,8 +62,26 @@ pub enum AnnotationError {
     #[error("Annotation not found: {0}")]
     NotFound(String),
 
-    #[error("Invalid text range")]
-    InvalidRange,
+    #[error("Bookmark not found: {0}")]
+    BookmarkNotFound(String),
+
+    #[error("Comment not found: {0}")]
+    CommentNotFound(String),
+
+    #[error("Comment is empty")]
+    EmptyComment,
+
+    #[error("Invalid text range: start {start} is after end {end}")]
+    InvalidRange { start: usize, end: usize },
+
+    #[error("Text range ends at {end}, past the page's {page_length} characters")]
+    RangeOutOfBounds { end: usize, page_length: usize },
+
+    #[error("Note is {length} characters, over the {max} character limit")]
+    NoteTooLong { length: usize, max: usize },
+
+    #[error("Note is empty")]
+    EmptyNote,
 
     #[error("Document not found for annotation")]
     DocumentNotFound,
@@ -62,11 +96,17 @@ pub enum LlmError {
     #[error("Model file not found: {0}")]
     ModelNotFound(String),
 
+    #[error("Unknown model: {0}")]
+    UnknownModel(String),
+
     #[error("Inference error: {0}")]
     InferenceError(String),
 
     #[error("Context too long")]
     ContextTooLong,
+
+    #[error("Model does not accept images: {0}")]
+    VisionUnsupported(String),
 }
 
 /// Storage-related errors
@@ -80,6 +120,12 @@ pub enum StorageError {
 
     #[error("Serialization error: {0}")]
     Serialization(String),
+
+    #[error("Unknown setting: {0}")]
+    UnknownSetting(String),
+
+    #[error("Invalid value for setting {key}: {reason}")]
+    InvalidSetting { key: String, reason: String },
 }
 
 // Implement serialization for Tauri commands
@@ -10,6 +10,7 @@ pub mod annotation;
 pub mod llm;
 pub mod voice;
 pub mod storage;
+pub mod settings;
 pub mod error;
 
 use tauri::Manager;
@@ -59,12 +60,27 @@ pub fn run() {
         .manage(commands::editor::EditorManager::new())
         .manage(commands::voice::VoiceManagerState::new())
         .manage(commands::llm::LLMState::new())
+        .manage(commands::annotation::AnnotationState::new())
+        .manage(commands::document::LibraryWatchers::new())
+        .manage(commands::document::OcrJobs::new())
         .setup(|app| {
             // Initialize storage on startup
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 if let Err(e) = storage::init_database(&app_handle).await {
                     tracing::error!("Failed to initialize database: {}", e);
+                    return;
+                }
+                commands::document::resume_watched_folders(&app_handle);
+                commands::llm::restore_llm_provider(&app_handle);
+                if let Err(e) = commands::voice::restore_voice_settings(&app_handle).await {
+                    tracing::warn!("Failed to restore the voice settings: {}", e);
+                }
+                let db = app_handle.state::<storage::Database>();
+                match commands::document::backfill_fingerprints(&db).await {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("Fingerprinted {} existing documents", n),
+                    Err(e) => tracing::warn!("Failed to fingerprint existing documents: {}", e),
                 }
             });
             Ok(())
@@ -72,30 +88,97 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // Document commands
             commands::document::open_document,
+            commands::document::get_document_id,
+            commands::document::is_scanned_pdf,
             commands::document::get_document_content,
             commands::document::get_document_metadata,
+            commands::document::set_document_meta,
+            commands::document::get_document_meta,
+            commands::document::add_tag,
+            commands::document::get_tags,
+            commands::document::get_document_outline,
+            commands::document::get_document_sections,
+            commands::document::get_section_difficulty,
+            commands::document::get_selection_context,
+            commands::document::search_in_document,
+            commands::document::get_document_links,
+            commands::document::compare_document_versions,
+            commands::document::regenerate_paragraph_ids,
+            commands::document::start_manual_reading,
+            commands::document::record_page_read,
+            commands::document::stop_manual_reading,
+            commands::document::get_reading_analytics,
+            commands::document::get_page_sources,
+            commands::document::ocr_document,
+            commands::document::cancel_ocr,
+            commands::document::import_folder,
+            commands::document::watch_folder,
+            commands::document::unwatch_folder,
+            commands::document::get_watched_folders,
             commands::document::get_recent_documents,
+            commands::document::delete_document,
 
             // Annotation commands
             commands::annotation::add_annotation,
+            commands::annotation::add_page_note,
             commands::annotation::get_annotations,
+            commands::annotation::get_annotations_timeline,
+            commands::annotation::search_all_annotations,
+            commands::annotation::get_annotations_by_color,
+            commands::annotation::get_annotation_color_counts,
             commands::annotation::update_annotation,
             commands::annotation::delete_annotation,
+            commands::annotation::get_bookmarks,
+            commands::annotation::next_bookmark,
+            commands::annotation::previous_bookmark,
+            commands::annotation::delete_bookmark,
+            commands::annotation::add_comment,
+            commands::annotation::get_comment_thread,
+            commands::annotation::delete_comment,
+            commands::annotation::get_annotation_config,
+            commands::annotation::set_annotation_config,
             commands::annotation::export_annotations,
+            commands::annotation::export_annotations_to_anki,
+            commands::annotation::export_study_page,
 
             // LLM commands
             commands::llm::query_llm,
+            commands::llm::query_llm_stream,
+            commands::llm::cancel_llm_request,
+            commands::llm::preview_llm_prompt,
+            commands::llm::query_llm_followup,
+            commands::llm::simplify_explanation,
             commands::llm::explain_text,
             commands::llm::generate_code,
+            commands::llm::generate_flashcards,
+            commands::llm::suggest_tags,
+            commands::llm::detect_headings_with_llm,
+            commands::llm::explain_latex_error,
+            commands::llm::get_document_summary,
+            commands::llm::translate_document,
+            commands::llm::get_translation,
+            commands::llm::describe_images,
+            commands::llm::get_image_descriptions,
             commands::llm::get_model_status,
             commands::llm::get_available_providers,
             commands::llm::get_provider_models,
+            commands::llm::estimate_query_cost,
             commands::llm::set_llm_config,
+            commands::llm::switch_llm_provider,
+            commands::llm::set_llm_fallback_chain,
+            commands::llm::get_generation_params,
+            commands::llm::set_generation_params,
             commands::llm::get_llm_config,
+            commands::llm::get_effective_llm_config,
+            commands::llm::set_llm_audit_enabled,
+            commands::llm::get_llm_audit,
+            commands::llm::clear_llm_audit,
             commands::llm::test_llm_connection,
 
             // Document Editor commands
             commands::editor::open_editor,
+            commands::editor::open_merged_editor,
+            commands::editor::get_merged_source_ranges,
             commands::editor::close_editor,
             commands::editor::has_unsaved_changes,
             commands::editor::get_operation_count,
@@ -104,27 +187,46 @@ pub fn run() {
             commands::editor::clear_operations,
             commands::editor::save_document,
             commands::editor::add_pdf_operation,
+            commands::editor::get_operations,
+            commands::editor::export_operations,
+            commands::editor::import_operations,
+            commands::editor::apply_common_operation,
             commands::editor::get_pdf_operations,
+            commands::editor::get_pdf_page_count,
+            commands::editor::get_pdf_page_size,
+            commands::editor::flatten_pdf_annotations,
             commands::editor::add_text_operation,
+            commands::editor::insert_code_snippet,
             commands::editor::get_text_content,
             commands::editor::set_text_content,
             commands::editor::get_word_stats,
             commands::editor::render_markdown_preview,
+            commands::editor::render_markdown_preview_blocks,
+            commands::editor::set_preview_allowlist,
             commands::editor::add_docx_operation,
+            commands::editor::get_docx_model,
             commands::editor::add_latex_operation,
             commands::editor::get_latex_completions,
+            commands::editor::add_bib_entry,
             commands::editor::add_epub_operation,
+            commands::editor::get_epub_model,
             commands::editor::merge_pdfs,
             commands::editor::split_pdf,
             commands::editor::extract_pdf_pages,
             commands::editor::compress_pdf,
             commands::editor::pdf_to_images,
+            commands::editor::render_pdf_page,
+            commands::editor::set_pdf_metadata,
+            commands::editor::verify_pdf_signatures,
             commands::editor::images_to_pdf,
+            commands::editor::get_output_settings,
+            commands::editor::set_output_settings,
             commands::editor::convert_markdown_to_pdf,
             commands::editor::convert_markdown_to_docx,
             commands::editor::convert_docx_to_pdf,
             commands::editor::convert_latex_to_pdf,
             commands::editor::convert_txt_to_markdown,
+            commands::editor::combine_documents_to_pdf,
             commands::editor::compile_to_pdf,
 
             // Voice commands
@@ -133,20 +235,35 @@ pub fn run() {
             commands::voice::initialize_voice,
             commands::voice::is_voice_initialized,
             commands::voice::get_voice_state,
+            commands::voice::reset_voice,
             commands::voice::start_voice_listening,
             commands::voice::stop_voice_listening,
             commands::voice::parse_voice_command,
             commands::voice::speak_text,
             commands::voice::start_reading,
+            commands::voice::read_selection,
             commands::voice::stop_reading,
             commands::voice::get_reading_position,
+            commands::voice::position_from_word_timing,
             commands::voice::set_reading_speed,
+            commands::voice::set_reading_wpm,
+            commands::voice::get_reading_wpm,
+            commands::voice::calibrate_reading_speed,
+            commands::voice::add_pronunciation,
+            commands::voice::list_pronunciations,
             commands::voice::get_available_voices,
+            commands::voice::export_narration_by_chapter,
+            commands::voice::preview_voice,
+            commands::voice::voice_self_test,
             commands::voice::get_stt_languages,
             commands::voice::is_voice_model_available,
             commands::voice::download_voice_model,
             commands::voice::process_voice_command,
             commands::voice::get_word_timings,
+            // Settings commands
+            commands::settings::get_all_settings,
+            commands::settings::set_setting,
+            commands::settings::reset_settings,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,191 @@
+//! Opt-in audit trail of LLM requests and responses, for debugging prompt quality
+
+use super::prompts;
+use super::providers::{
+    redact_secrets, ChatImage, ChatMessage, LLMClient, LLMError, ProviderConfig,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+/// Sampling parameters a request was sent with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuditParams {
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub top_p: Option<f32>,
+    pub stop: Vec<String>,
+}
+
+/// One recorded LLM call, with secrets redacted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmAuditEntry {
+    pub id: String,
+    /// RFC 3339 time the request was sent
+    pub timestamp: String,
+    pub provider: String,
+    pub model: String,
+    pub params: AuditParams,
+    pub messages: Vec<ChatMessage>,
+    pub response: Option<String>,
+    pub error: Option<String>,
+    /// Estimated tokens in the response
+    pub response_tokens: usize,
+    pub latency_ms: u64,
+}
+
+/// Where audit entries are written
+pub type AuditSink = Arc<dyn Fn(LlmAuditEntry) + Send + Sync>;
+
+/// Wraps a client and records every call it makes
+pub struct AuditingClient {
+    inner: Box<dyn LLMClient>,
+    sink: AuditSink,
+}
+
+impl AuditingClient {
+    pub fn new(inner: Box<dyn LLMClient>, sink: AuditSink) -> Self {
+        Self { inner, sink }
+    }
+
+    fn record(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+        result: &Result<String, LLMError>,
+        started: (chrono::DateTime<chrono::Utc>, Instant),
+    ) {
+        let (timestamp, start) = started;
+        let provider = serde_json::to_value(&config.provider)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let response = result.as_ref().ok().map(|text| redact_secrets(text));
+
+        (self.sink)(LlmAuditEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: timestamp.to_rfc3339(),
+            provider,
+            model: config.model.clone(),
+            params: AuditParams {
+                max_tokens: config.max_tokens,
+                temperature: config.temperature,
+                top_p: config.top_p,
+                stop: config.stop.clone(),
+            },
+            messages: messages
+                .into_iter()
+                .map(|m| ChatMessage {
+                    role: m.role,
+                    content: redact_secrets(&m.content),
+                })
+                .collect(),
+            response_tokens: response.as_deref().map_or(0, prompts::estimate_tokens),
+            response,
+            error: result.as_ref().err().map(|e| redact_secrets(&e.to_string())),
+            latency_ms: start.elapsed().as_millis() as u64,
+        });
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMClient for AuditingClient {
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let started = (chrono::Utc::now(), Instant::now());
+        let result = self.inner.chat(messages.clone(), config).await;
+        self.record(messages, config, &result, started);
+        result
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+        tokens: mpsc::UnboundedSender<String>,
+    ) -> Result<String, LLMError> {
+        let started = (chrono::Utc::now(), Instant::now());
+        let result = self.inner.chat_stream(messages.clone(), config, tokens).await;
+        self.record(messages, config, &result, started);
+        result
+    }
+
+    async fn chat_with_image(
+        &self,
+        messages: Vec<ChatMessage>,
+        image: &ChatImage,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let started = (chrono::Utc::now(), Instant::now());
+        let result = self.inner.chat_with_image(messages.clone(), image, config).await;
+        self.record(messages, config, &result, started);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct EchoClient;
+
+    #[async_trait::async_trait]
+    impl LLMClient for EchoClient {
+        async fn chat(
+            &self,
+            messages: Vec<ChatMessage>,
+            _config: &ProviderConfig,
+        ) -> Result<String, LLMError> {
+            match messages.last() {
+                Some(m) if m.content == "fail" => {
+                    Err(LLMError::ApiError("bad key sk-live-1234567890".to_string()))
+                }
+                Some(m) => Ok(format!("echo: {}", m.content)),
+                None => Ok(String::new()),
+            }
+        }
+    }
+
+    fn user(content: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+        }]
+    }
+
+    #[tokio::test]
+    async fn test_each_call_is_recorded_with_secrets_redacted() {
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let sink_entries = entries.clone();
+        let client = AuditingClient::new(
+            Box::new(EchoClient),
+            Arc::new(move |entry| sink_entries.lock().unwrap().push(entry)),
+        );
+        let config = ProviderConfig {
+            temperature: 0.1,
+            ..Default::default()
+        };
+
+        client.chat(user("my key is sk-abcdefghijkl"), &config).await.unwrap();
+        client.chat(user("fail"), &config).await.unwrap_err();
+
+        let entries = entries.lock().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].provider, "openai");
+        assert_eq!(entries[0].model, config.model);
+        assert_eq!(entries[0].params.temperature, 0.1);
+        assert_eq!(entries[0].messages[0].content, "my key is ***");
+        assert_eq!(entries[0].response.as_deref(), Some("echo: my key is ***"));
+        assert!(entries[0].response_tokens > 0);
+
+        assert_eq!(entries[1].response, None);
+        let error = entries[1].error.as_deref().unwrap();
+        assert!(error.contains("***") && !error.contains("sk-live"));
+    }
+}
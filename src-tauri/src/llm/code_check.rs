@@ -0,0 +1,223 @@
+//! Quick syntax checks of generated code, reported as warnings rather than failures
+//!
+//! Only the fenced code blocks of a response are checked (or the whole response when it
+//! has none), skipping blocks tagged with another language such as shell commands. Rust
+//! is parsed with `syn`; Python with the `ast` module of a local `python3` when built with
+//! the `python-check` feature. Other languages aren't checked.
+
+use serde::{Deserialize, Serialize};
+
+/// A syntax problem found in generated code
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeWarning {
+    /// 1-based line in the generated text, when known
+    pub line: Option<usize>,
+    /// 1-based column, when known
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// Checks one block of code; `None` when the checker couldn't run
+type Checker = fn(&str) -> Option<Result<(), CodeWarning>>;
+
+/// Fence tags for languages with more than one name
+const RUST_TAGS: &[&str] = &["rust", "rs"];
+const PYTHON_TAGS: &[&str] = &["python", "py", "python3"];
+
+/// Check the code blocks in `code`, or `None` when there's no checker for `language`
+pub fn check_syntax(language: &str, code: &str) -> Option<Vec<CodeWarning>> {
+    let language = language.trim().to_lowercase();
+    let (tags, check): (&[&str], Checker) = match language.as_str() {
+        "rust" | "rs" => (RUST_TAGS, check_rust),
+        #[cfg(feature = "python-check")]
+        "python" | "py" | "python3" => (PYTHON_TAGS, check_python),
+        _ => return None,
+    };
+
+    let mut warnings = Vec::new();
+    for (offset, block) in code_blocks(code, tags) {
+        match check(&block)? {
+            Ok(()) => {}
+            Err(mut warning) => {
+                warning.line = warning.line.map(|line| line + offset);
+                warnings.push(warning);
+            }
+        }
+    }
+    Some(warnings)
+}
+
+/// The code of a generated answer without the prose around it: its blocks in `language`
+/// or untagged, joined by blank lines. Text without any such block is returned whole.
+pub fn extract_code(language: &str, code: &str) -> String {
+    let language = language.trim().to_lowercase();
+    let own_tag = [language.as_str()];
+    let tags = match language.as_str() {
+        "rust" | "rs" => RUST_TAGS,
+        "python" | "py" | "python3" => PYTHON_TAGS,
+        _ => &own_tag,
+    };
+
+    let blocks: Vec<String> = code_blocks(code, tags).into_iter().map(|(_, b)| b).collect();
+    if blocks.is_empty() {
+        return code.trim().to_string();
+    }
+    blocks.join("\n\n").trim_matches('\n').trim_end().to_string()
+}
+
+/// Code blocks fenced with one of `tags` or with none, each with the number of lines
+/// before it. Text without any fences is one block.
+fn code_blocks(code: &str, tags: &[&str]) -> Vec<(usize, String)> {
+    let mut blocks = Vec::new();
+    let mut fenced = false;
+    // First line, whether it's checked, and lines of the block being read
+    let mut open: Option<(usize, bool, Vec<&str>)> = None;
+
+    for (i, line) in code.lines().enumerate() {
+        if let Some(tag) = line.trim_start().strip_prefix("```") {
+            fenced = true;
+            match open.take() {
+                None => {
+                    let tag = tag.split_whitespace().next().unwrap_or("").to_lowercase();
+                    open = Some((i + 1, tag.is_empty() || tags.contains(&tag.as_str()), vec![]));
+                }
+                Some((start, true, lines)) => blocks.push((start, lines.join("\n"))),
+                Some(_) => {}
+            }
+        } else if let Some((_, _, lines)) = &mut open {
+            lines.push(line);
+        }
+    }
+    // An unclosed fence runs to the end, as when the answer was cut off
+    if let Some((start, true, lines)) = open {
+        blocks.push((start, lines.join("\n")));
+    }
+
+    if !fenced {
+        blocks.push((0, code.to_string()));
+    }
+    blocks
+}
+
+/// Parse as a Rust file, or failing that as the statements of a function body
+fn check_rust(code: &str) -> Option<Result<(), CodeWarning>> {
+    let Err(error) = syn::parse_file(code) else {
+        return Some(Ok(()));
+    };
+    if syn::parse_str::<syn::Block>(&format!("{{\n{}\n}}", code)).is_ok() {
+        return Some(Ok(()));
+    }
+
+    let start = error.span().start();
+    Some(Err(CodeWarning {
+        line: (start.line > 0).then_some(start.line),
+        column: (start.line > 0).then_some(start.column + 1),
+        message: error.to_string(),
+    }))
+}
+
+/// Parse with `python3 -c 'ast.parse(...)'`; `None` when Python can't be run
+#[cfg(feature = "python-check")]
+fn check_python(code: &str) -> Option<Result<(), CodeWarning>> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    const SCRIPT: &str = "import ast, sys\n\
+        try:\n    ast.parse(sys.stdin.read())\n\
+        except SyntaxError as e:\n    print(e.lineno or 0, e.offset or 0, e.msg)";
+
+    let mut child = Command::new("python3")
+        .args(["-c", SCRIPT])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| tracing::debug!("Cannot run python3 to check code: {}", e))
+        .ok()?;
+    child.stdin.take()?.write_all(code.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    // Nothing printed when the code parsed, else "<line> <offset> <message>"
+    let report = String::from_utf8_lossy(&output.stdout);
+    let mut parts = report.trim().splitn(3, ' ');
+    let Some(line) = parts.next().filter(|line| !line.is_empty()) else {
+        return Some(Ok(()));
+    };
+    let position = |n: &str| n.parse::<usize>().ok().filter(|&n| n > 0);
+    Some(Err(CodeWarning {
+        line: position(line),
+        column: parts.next().and_then(position),
+        message: parts.next().unwrap_or("invalid syntax").to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_broken_rust_gets_a_syntax_warning() {
+        let response = "Here is the attention score:\n\n\
+            ```rust\n\
+            fn score(q: f32, k: f32) -> f32 {\n    \
+                let scaled = q * k / ;\n    \
+                scaled\n\
+            }\n\
+            ```\n";
+        let warnings = check_syntax("Rust", response).unwrap();
+        assert_eq!(warnings.len(), 1);
+        // Line 5 of the response, where the expression is missing
+        assert_eq!(warnings[0].line, Some(5));
+        assert!(warnings[0].column.is_some());
+    }
+
+    #[test]
+    fn test_valid_rust_has_no_warnings() {
+        let response = "Install with:\n\n```bash\ncargo add ndarray && echo {\n```\n\n\
+            ```rust\n\
+            /// Softmax of `xs`\n\
+            pub fn softmax(xs: &[f32]) -> Vec<f32> {\n    \
+                let sum: f32 = xs.iter().map(|x| x.exp()).sum();\n    \
+                xs.iter().map(|x| x.exp() / sum).collect()\n\
+            }\n\
+            ```\n\nExample usage:\n\n\
+            ```\nlet p = softmax(&[1.0, 2.0]);\nprintln!(\"{:?}\", p);\n```";
+        assert_eq!(check_syntax("rust", response), Some(vec![]));
+
+        // Unfenced code is checked whole
+        assert_eq!(check_syntax("rs", "let total: u32 = (1..=10).sum();"), Some(vec![]));
+        assert_eq!(check_syntax("rs", "let total = ;").map(|w| w.len()), Some(1));
+    }
+
+    #[test]
+    fn test_extracted_code_drops_prose_and_other_languages() {
+        let response = "Install with:\n\n```bash\npip install numpy\n```\n\n\
+            ```py\ndef relu(x):\n    return max(x, 0)\n```\n\nExample:\n\n\
+            ```\nprint(relu(-1))\n```\n";
+        assert_eq!(
+            extract_code("Python", response),
+            "def relu(x):\n    return max(x, 0)\n\nprint(relu(-1))"
+        );
+        assert_eq!(extract_code("go", "\nx := 1\n\n"), "x := 1");
+    }
+
+    #[test]
+    fn test_languages_without_a_checker_are_not_checked() {
+        assert_eq!(check_syntax("haskell", "main = putStrLn \"hi\""), None);
+    }
+
+    #[cfg(feature = "python-check")]
+    #[test]
+    fn test_broken_python_gets_a_syntax_warning() {
+        // Skipped where Python isn't installed
+        let Some(warnings) = check_syntax("python", "```python\ndef f(x):\n    return (x\n```")
+        else {
+            return;
+        };
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(check_syntax("python", "def f(x):\n    return x * 2\n"), Some(vec![]));
+    }
+}
@@ -1,14 +1,17 @@
 //! LLM integration module
 
+pub mod audit;
+pub mod code_check;
 pub mod prompts;
 pub mod providers;
+pub mod tokens;
 
 pub use providers::{LLMProvider, ProviderConfig, AvailableModels, ModelInfo, get_available_models};
 
 use serde::{Deserialize, Serialize};
 
 /// Query mode for LLM interactions
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMode {
     /// Quick, direct answer
@@ -21,6 +24,40 @@ pub enum QueryMode {
     GenerateCode,
 }
 
+impl QueryMode {
+    pub const ALL: [QueryMode; 4] = [
+        Self::QuickAnswer,
+        Self::Explain,
+        Self::Summarize,
+        Self::GenerateCode,
+    ];
+}
+
+/// Per-mode sampling overrides; unset fields keep the provider config's value
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GenerationParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub stop: Option<Vec<String>>,
+}
+
+impl GenerationParams {
+    /// Layer these overrides over a base provider config
+    pub fn apply_to(&self, config: &ProviderConfig) -> ProviderConfig {
+        let mut config = config.clone();
+        if let Some(temperature) = self.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(top_p) = self.top_p {
+            config.top_p = Some(top_p);
+        }
+        if let Some(stop) = &self.stop {
+            config.stop = stop.clone();
+        }
+        config
+    }
+}
+
 /// Response from LLM query
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmResponse {
@@ -45,6 +82,9 @@ pub struct CodeGenerationRequest {
     pub framework: Option<String>,
     /// Reference to paper section
     pub section_reference: Option<String>,
+    /// Check the generated code's syntax and attach any problems as warnings
+    #[serde(default)]
+    pub validate: bool,
 }
 
 /// Generated code snippet
@@ -60,6 +100,17 @@ pub struct CodeSnippet {
     pub description: String,
     /// Reference to paper section it implements
     pub section_reference: Option<String>,
+    /// Syntax problems found in the code; `None` when it wasn't checked
+    pub warnings: Option<Vec<code_check::CodeWarning>>,
+}
+
+/// Study flashcard generated from a document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Flashcard {
+    /// Question grounded in the document
+    pub question: String,
+    /// Answer to the question
+    pub answer: String,
 }
 
 /// LLM model status
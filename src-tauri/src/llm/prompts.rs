@@ -59,6 +59,89 @@ Create a structured summary including:
 
 Keep the summary concise but informative, suitable for a busy researcher."#;
 
+/// System prompt for the short summary generated when a document is opened
+pub const SHORT_SUMMARY_PROMPT: &str = r#"You are a research assistant writing a one-line abstract of a document.
+
+Guidelines:
+- Respond with a single sentence of at most 40 words
+- State what the document is about and its main point or finding
+- Do not add headings, bullet points, quotes, or any preamble"#;
+
+/// System prompt for flashcard generation
+pub const FLASHCARD_PROMPT: &str = r#"You are a study assistant creating flashcards from a research paper or academic document.
+
+Guidelines:
+- Every question must be answerable from the provided document
+- Keep questions specific and answers short (one to three sentences)
+- Cover key concepts, definitions, methods, and results
+- Respond with ONLY a JSON array of objects with "question" and "answer" string fields, no other text"#;
+
+/// System prompt for classifying short paragraphs of a PDF as section headings
+pub const HEADING_PROMPT: &str = r#"You are reconstructing the table of contents of a document whose headings were lost during text extraction.
+
+Guidelines:
+- You are given short paragraphs in reading order, each with an "id" and its "text"
+- Mark only paragraphs that title a section or subsection, not list items, captions, or sentence fragments
+- Give each heading a "level": 1 for top-level sections, 2 for subsections, and so on; follow section numbers when present
+- Respond with ONLY a JSON array of objects with "id" (string) and "level" (number) fields, no other text"#;
+
+/// System prompt for describing a figure or photo from a document
+pub const IMAGE_DESCRIPTION_PROMPT: &str = r#"You are describing images from a research paper or academic document for a reader who cannot see them.
+
+Guidelines:
+- Say what kind of image it is (chart, diagram, table, photo, equation) and what it shows
+- For charts, give the axes, the main trends, and any notable values
+- For diagrams, describe the parts and how they connect
+- Respond with two to four plain sentences, without a preamble"#;
+
+/// System prompt for suggesting topic tags for a document
+pub const TAG_PROMPT: &str = r#"You are a librarian tagging documents in a personal research library.
+
+Guidelines:
+- Suggest short topic tags of one to three words, in lowercase
+- Prefer specific subjects, methods, and fields over generic words like "paper" or "research"
+- Order tags from most to least relevant
+- Respond with ONLY a JSON array of strings, no other text"#;
+
+/// System prompt for suggesting fixes to errors from a LaTeX compile
+pub const LATEX_FIX_PROMPT: &str = r#"You are helping someone fix a LaTeX document that failed to compile.
+
+Guidelines:
+- You are given the errors pdflatex reported, each with its line number and the source text leading up to it
+- Explain the most likely cause of each error in one or two plain sentences
+- Show the corrected source for each line you would change
+- If one mistake causes several errors, say so instead of repeating the fix"#;
+
+/// System prompt for translating one paragraph of a document
+pub fn translation_prompt(language: &str) -> String {
+    format!(
+        "You are translating a document paragraph by paragraph for a bilingual reader.\n\n\
+         Guidelines:\n\
+         - Translate the paragraph you are given into {}\n\
+         - Keep the meaning, tone and technical terms exact\n\
+         - Leave math, code and citations unchanged\n\
+         - Respond with ONLY the translated paragraph, no notes or quotation marks",
+        language
+    )
+}
+
+/// Follow-up asking for a prior answer to be rephrased for a given audience
+pub fn simplify_request(previous_answer: &str, level: &str) -> String {
+    format!(
+        "Rephrase your explanation below for a {} reader.\n\
+         - Keep every point accurate and do not add new claims\n\
+         - Replace jargon with plain words, or define it the first time it appears\n\
+         - Use shorter sentences and a concrete example or analogy where it helps\n\n\
+         Explanation:\n{}",
+        level, previous_answer
+    )
+}
+
+/// Rough token count for a piece of text (about four characters per token)
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
 /// Build a prompt with context
 pub fn build_prompt(system: &str, context: &str, user_query: &str) -> String {
     format!(
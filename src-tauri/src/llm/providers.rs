@@ -10,6 +10,8 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
 
 /// Available LLM providers
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -41,8 +43,29 @@ pub struct ProviderConfig {
     pub model: String,
     pub max_tokens: u32,
     pub temperature: f32,
+    /// Nucleus sampling cutoff, sent only when set
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    /// Sequences that end generation
+    #[serde(default)]
+    pub stop: Vec<String>,
     pub organization: Option<String>,
+    /// Extra headers sent with every request (gateways, proxies, org routing)
     pub headers: HashMap<String, String>,
+    /// Attempts made at a request before a rate limit or network error is returned
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Wait before the first retry in milliseconds, doubling with each retry after it
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+    500
 }
 
 impl Default for ProviderConfig {
@@ -54,8 +77,12 @@ impl Default for ProviderConfig {
             model: "gpt-4o-mini".to_string(),
             max_tokens: 2048,
             temperature: 0.7,
+            top_p: None,
+            stop: Vec::new(),
             organization: None,
             headers: HashMap::new(),
+            max_attempts: default_max_attempts(),
+            retry_base_delay_ms: default_retry_base_delay_ms(),
         }
     }
 }
@@ -113,7 +140,7 @@ impl ProviderConfig {
         Self {
             provider: LLMProvider::Ollama,
             api_key: None,
-            api_url: Some("http://localhost:11434/v1".to_string()),
+            api_url: Some(OLLAMA_URL.to_string()),
             model: model.to_string(),
             max_tokens: 4096,
             temperature: 0.7,
@@ -436,12 +463,27 @@ pub fn get_available_models(provider: &LLMProvider) -> AvailableModels {
 }
 
 /// Chat message for API requests
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
 }
 
+/// An image sent along with a chat message, for vision models
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatImage {
+    /// e.g. `image/png`
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl ChatImage {
+    fn base64(&self) -> String {
+        use base64::Engine;
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+}
+
 /// LLM API client trait
 #[async_trait::async_trait]
 pub trait LLMClient: Send + Sync {
@@ -450,6 +492,49 @@ pub trait LLMClient: Send + Sync {
         messages: Vec<ChatMessage>,
         config: &ProviderConfig,
     ) -> Result<String, LLMError>;
+
+    /// Chat, sending the answer through `tokens` as it arrives.
+    ///
+    /// Returns the full answer. Providers without streaming support send it as one token.
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+        tokens: mpsc::UnboundedSender<String>,
+    ) -> Result<String, LLMError> {
+        let answer = self.chat(messages, config).await?;
+        let _ = tokens.send(answer.clone());
+        Ok(answer)
+    }
+
+    /// Chat with `image` attached to the last message, for vision models.
+    ///
+    /// Providers without image input fail with an `ApiError`.
+    async fn chat_with_image(
+        &self,
+        messages: Vec<ChatMessage>,
+        image: &ChatImage,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let _ = (messages, image);
+        Err(LLMError::ApiError(format!("{:?} does not accept images", config.provider)))
+    }
+}
+
+/// Add `config.headers` to a request, skipping any that would replace a required header
+fn with_custom_headers(
+    mut request: reqwest::RequestBuilder,
+    config: &ProviderConfig,
+    required: &[&str],
+) -> reqwest::RequestBuilder {
+    for (name, value) in &config.headers {
+        if required.iter().any(|r| r.eq_ignore_ascii_case(name)) {
+            tracing::warn!("Ignoring custom header {} that would replace a required one", name);
+            continue;
+        }
+        request = request.header(name, value);
+    }
+    request
 }
 
 /// LLM errors
@@ -461,8 +546,12 @@ pub enum LLMError {
     #[error("Invalid API key")]
     InvalidApiKey,
 
-    #[error("Rate limited: {0}")]
-    RateLimited(String),
+    #[error("Rate limited: {message}")]
+    RateLimited {
+        message: String,
+        /// How long the server asked clients to wait, from `Retry-After`
+        retry_after: Option<Duration>,
+    },
 
     #[error("Model not found: {0}")]
     ModelNotFound(String),
@@ -474,8 +563,201 @@ pub enum LLMError {
     ContextTooLong,
 }
 
+/// Mask API keys and tokens in text bound for logs or error messages
+pub fn redact_secrets(text: &str) -> String {
+    static PATTERNS: std::sync::OnceLock<Vec<regex::Regex>> = std::sync::OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        [
+            // Query parameters, e.g. Gemini's `?key=`
+            r"(?i)([?&](?:key|api_key|apikey|access_token|token)=)[^&\s)\x22']+",
+            // Header and JSON `key: value` pairs, then any other bearer token
+            concat!(
+                r#"(?i)("?(?:x-api-key|api[_-]?key|authorization)"?\s*[:=]\s*"?)"#,
+                r#"(?:bearer\s+)?[^"\s,}]+"#,
+            ),
+            r"(?i)(bearer\s+)[\w.~+/=-]+",
+            // Bare keys in the common OpenAI/Anthropic and Google formats
+            r"()\bsk-[\w-]{8,}",
+            r"()\bAIza[\w-]{20,}",
+        ]
+        .iter()
+        .map(|p| regex::Regex::new(p).unwrap())
+        .collect()
+    });
+
+    patterns.iter().fold(text.to_string(), |text, re| {
+        re.replace_all(&text, "${1}***").into_owned()
+    })
+}
+
+/// Network error with any key in the request URL masked
+fn network_error(e: reqwest::Error) -> LLMError {
+    LLMError::NetworkError(redact_secrets(&e.to_string()))
+}
+
+/// Pass a successful response through, or turn a failed one into an error.
+/// HTTP 429 becomes `RateLimited` so callers can back off or fail over.
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, LLMError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = retry_after(response.headers());
+    let error_text = redact_secrets(&response.text().await.unwrap_or_default());
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(LLMError::RateLimited {
+            message: error_text,
+            retry_after,
+        });
+    }
+    Err(LLMError::ApiError(format!("HTTP {}: {}", status, error_text)))
+}
+
+/// Wait a `Retry-After` header asks for, given in seconds or as an HTTP date
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or_default())
+}
+
+/// Longest wait between attempts, however long the server asks for
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Run `attempt` until it succeeds or fails for good. Rate limits and network errors
+/// are retried up to `config.max_attempts` attempts in all, waiting as long as the
+/// server's `Retry-After` asks or else backing off exponentially from
+/// `config.retry_base_delay_ms`. Other errors are returned at once.
+pub async fn with_retry<T, F, Fut>(config: &ProviderConfig, mut attempt: F) -> Result<T, LLMError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, LLMError>>,
+{
+    let mut attempts = 1;
+    loop {
+        match attempt().await {
+            Err(e) if e.is_transient() && attempts < config.max_attempts => {
+                let delay = retry_delay(&e, config.retry_base_delay_ms, attempts);
+                tracing::warn!(
+                    "{:?} request failed ({}), retrying in {} ms",
+                    config.provider,
+                    e,
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                attempts += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Wait before retrying after the `attempt`th failure
+fn retry_delay(error: &LLMError, base_delay_ms: u64, attempt: u32) -> Duration {
+    if let LLMError::RateLimited {
+        retry_after: Some(wait),
+        ..
+    } = error
+    {
+        return (*wait).min(MAX_RETRY_DELAY);
+    }
+    let backoff = base_delay_ms.saturating_mul(1 << (attempt - 1).min(16));
+    // A random half of the backoff keeps clients that failed together from retrying
+    // in step
+    let jittered = backoff / 2 + fastrand::u64(0..=backoff / 2);
+    Duration::from_millis(jittered).min(MAX_RETRY_DELAY)
+}
+
+/// Splits a streamed body into lines. Bytes are held until their line is complete, so
+/// frames and characters split across network reads come out whole.
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Add the next chunk of the body, returning every line it completes
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+        lines
+    }
+}
+
+/// What one line of a stream means for the answer
+enum StreamStep {
+    Token(String),
+    Skip,
+    Done,
+}
+
+/// Read a server-sent event stream, sending each token `parse` finds in the event data
+/// to `tokens`. Returns the full answer once the stream or the listener ends.
+async fn read_sse(
+    response: reqwest::Response,
+    tokens: &mpsc::UnboundedSender<String>,
+    mut parse: impl FnMut(&str) -> Result<StreamStep, LLMError>,
+) -> Result<String, LLMError> {
+    read_lines(response, tokens, |line| match line.strip_prefix("data:") {
+        Some(data) => parse(data.trim_start()),
+        None => Ok(StreamStep::Skip),
+    })
+    .await
+}
+
+/// Read a streamed response line by line, sending each token `parse` finds to `tokens`
+async fn read_lines(
+    mut response: reqwest::Response,
+    tokens: &mpsc::UnboundedSender<String>,
+    mut parse: impl FnMut(&str) -> Result<StreamStep, LLMError>,
+) -> Result<String, LLMError> {
+    let mut answer = String::new();
+    let mut buffer = LineBuffer::default();
+    while let Some(chunk) = response.chunk().await.map_err(network_error)? {
+        for line in buffer.push(&chunk) {
+            match parse(&line)? {
+                StreamStep::Token(token) => {
+                    answer.push_str(&token);
+                    // The receiver going away means the caller stopped listening
+                    if tokens.send(token).is_err() {
+                        return Ok(answer);
+                    }
+                }
+                StreamStep::Skip => {}
+                StreamStep::Done => return Ok(answer),
+            }
+        }
+    }
+    Ok(answer)
+}
+
+/// JSON payload of a streamed event or line
+fn stream_json(data: &str) -> Result<serde_json::Value, LLMError> {
+    serde_json::from_str(data).map_err(|e| LLMError::ApiError(redact_secrets(&e.to_string())))
+}
+
+impl LLMError {
+    /// Whether another provider might succeed where this one failed
+    pub fn should_fail_over(&self) -> bool {
+        !matches!(self, LLMError::ContextTooLong)
+    }
+
+    /// Whether the same request might succeed if sent again shortly
+    pub fn is_transient(&self) -> bool {
+        matches!(self, LLMError::RateLimited { .. } | LLMError::NetworkError(_))
+    }
+}
+
 // ─── OpenAI-compatible client ──────────────────────────────────────────
 
+const OPENAI_HEADERS: &[&str] = &["Authorization", "Content-Type", "OpenAI-Organization"];
+
 pub struct OpenAIClient {
     client: reqwest::Client,
 }
@@ -493,51 +775,42 @@ impl OpenAIClient {
             .clone()
             .unwrap_or_else(|| "https://api.openai.com/v1".to_string())
     }
-}
 
-#[async_trait::async_trait]
-impl LLMClient for OpenAIClient {
-    async fn chat(
-        &self,
-        messages: Vec<ChatMessage>,
-        config: &ProviderConfig,
-    ) -> Result<String, LLMError> {
+    /// Authenticated POST to the chat completions endpoint
+    fn post(&self, config: &ProviderConfig) -> Result<reqwest::RequestBuilder, LLMError> {
         let api_url = format!("{}/chat/completions", self.get_api_url(config));
         let api_key = config.api_key.as_ref().ok_or(LLMError::InvalidApiKey)?;
 
-        let body = serde_json::json!({
-            "model": config.model,
-            "messages": messages,
-            "max_tokens": config.max_tokens,
-            "temperature": config.temperature,
-        });
-
-        let response = self
-            .client
-            .post(&api_url)
+        let mut request = with_custom_headers(self.client.post(&api_url), config, OPENAI_HEADERS)
             .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| LLMError::NetworkError(e.to_string()))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            if status.as_u16() == 429 {
-                return Err(LLMError::RateLimited(error_text));
-            }
-            return Err(LLMError::ApiError(format!(
-                "HTTP {}: {}",
-                status, error_text
-            )));
+            .header("Content-Type", "application/json");
+        if let Some(organization) = &config.organization {
+            request = request.header("OpenAI-Organization", organization);
         }
+        Ok(request)
+    }
+
+    /// Send a chat completions request and return the answer
+    async fn complete(
+        &self,
+        body: &serde_json::Value,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let response = with_retry(config, || async {
+            let response = self
+                .post(config)?
+                .json(body)
+                .send()
+                .await
+                .map_err(network_error)?;
+            check_status(response).await
+        })
+        .await?;
 
         let result: serde_json::Value = response
             .json()
             .await
-            .map_err(|e| LLMError::ApiError(e.to_string()))?;
+            .map_err(|e| LLMError::ApiError(redact_secrets(&e.to_string())))?;
 
         result["choices"][0]["message"]["content"]
             .as_str()
@@ -546,209 +819,729 @@ impl LLMClient for OpenAIClient {
     }
 }
 
-// ─── Gemini client ─────────────────────────────────────────────────────
+#[async_trait::async_trait]
+impl LLMClient for OpenAIClient {
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        self.complete(&openai_body(&messages, config), config).await
+    }
 
-pub struct GeminiClient {
+    async fn chat_with_image(
+        &self,
+        messages: Vec<ChatMessage>,
+        image: &ChatImage,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let mut body = openai_body(&messages, config);
+        if let Some(last) = body["messages"].as_array_mut().and_then(|m| m.last_mut()) {
+            let text = last["content"].take();
+            let url = format!("data:{};base64,{}", image.mime_type, image.base64());
+            last["content"] = serde_json::json!([
+                {"type": "text", "text": text},
+                {"type": "image_url", "image_url": {"url": url}},
+            ]);
+        }
+        self.complete(&body, config).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+        tokens: mpsc::UnboundedSender<String>,
+    ) -> Result<String, LLMError> {
+        let mut body = openai_body(&messages, config);
+        body["stream"] = true.into();
+
+        let response = with_retry(config, || async {
+            let response = self
+                .post(config)?
+                .json(&body)
+                .send()
+                .await
+                .map_err(network_error)?;
+            check_status(response).await
+        })
+        .await?;
+
+        // One `data: {json}` line per delta, ending with `data: [DONE]`
+        read_sse(response, &tokens, |data| {
+            if data == "[DONE]" {
+                return Ok(StreamStep::Done);
+            }
+            Ok(match stream_json(data)?["choices"][0]["delta"]["content"].as_str() {
+                Some(token) => StreamStep::Token(token.to_string()),
+                None => StreamStep::Skip,
+            })
+        })
+        .await
+    }
+}
+
+/// Chat completions request body (also used by OpenAI-compatible providers)
+fn openai_body(messages: &[ChatMessage], config: &ProviderConfig) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "model": config.model,
+        "messages": messages,
+        "max_tokens": config.max_tokens,
+        "temperature": config.temperature,
+    });
+    if let Some(top_p) = config.top_p {
+        body["top_p"] = top_p.into();
+    }
+    if !config.stop.is_empty() {
+        body["stop"] = config.stop.clone().into();
+    }
+    body
+}
+
+// ─── Ollama client ─────────────────────────────────────────────────────
+
+/// Where a local Ollama server listens unless configured otherwise
+pub const OLLAMA_URL: &str = "http://localhost:11434";
+
+/// Context window Ollama runs a model with unless the model sets its own
+const OLLAMA_DEFAULT_CONTEXT: u32 = 2048;
+
+/// Client for Ollama's native API, which streams newline-delimited JSON
+pub struct OllamaClient {
     client: reqwest::Client,
 }
 
-impl GeminiClient {
+impl OllamaClient {
     pub fn new() -> Self {
         Self {
             client: reqwest::Client::new(),
         }
     }
-}
 
-#[async_trait::async_trait]
-impl LLMClient for GeminiClient {
-    async fn chat(
+    /// Server root from `config.api_url`, which may end in `/api` or in the `/v1` of
+    /// Ollama's OpenAI-compatible endpoint
+    fn base_url(config: &ProviderConfig) -> String {
+        let url = config.api_url.as_deref().unwrap_or(OLLAMA_URL).trim_end_matches('/');
+        url.strip_suffix("/v1")
+            .or_else(|| url.strip_suffix("/api"))
+            .unwrap_or(url)
+            .to_string()
+    }
+
+    /// Send `request`, explaining a refused connection as the server not running
+    async fn send(
+        request: reqwest::RequestBuilder,
+        base_url: &str,
+    ) -> Result<reqwest::Response, LLMError> {
+        request.send().await.map_err(|e| {
+            if e.is_connect() || e.is_timeout() {
+                LLMError::NetworkError(format!(
+                    "Cannot reach Ollama at {}. Is it running? Start it with `ollama serve`.",
+                    base_url
+                ))
+            } else {
+                network_error(e)
+            }
+        })
+    }
+
+    async fn post_chat(
         &self,
-        messages: Vec<ChatMessage>,
+        body: &serde_json::Value,
         config: &ProviderConfig,
-    ) -> Result<String, LLMError> {
-        let api_key = config.api_key.as_ref().ok_or(LLMError::InvalidApiKey)?;
-        let api_url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-            config.model, api_key
+    ) -> Result<reqwest::Response, LLMError> {
+        let base_url = Self::base_url(config);
+        with_retry(config, || async {
+            let request = with_custom_headers(
+                self.client.post(format!("{}/api/chat", base_url)),
+                config,
+                &["Content-Type"],
+            )
+            .json(body);
+            check_status(Self::send(request, &base_url).await?).await
+        })
+        .await
+    }
+
+    /// Models pulled onto the server, from `/api/tags`
+    pub async fn list_models(&self, config: &ProviderConfig) -> Result<Vec<ModelInfo>, LLMError> {
+        let base_url = Self::base_url(config);
+        let request = with_custom_headers(
+            self.client.get(format!("{}/api/tags", base_url)),
+            config,
+            &[],
         );
+        let response = check_status(Self::send(request, &base_url).await?).await?;
+        let result: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| LLMError::ApiError(redact_secrets(&e.to_string())))?;
 
-        let contents: Vec<serde_json::Value> = messages
+        let models = result["models"]
+            .as_array()
+            .ok_or_else(|| LLMError::ApiError("Invalid response format".to_string()))?;
+        Ok(models
             .iter()
-            .filter(|m| m.role != "system")
-            .map(|m| {
-                serde_json::json!({
-                    "role": if m.role == "assistant" { "model" } else { "user" },
-                    "parts": [{"text": m.content}]
+            .filter_map(|model| {
+                let name = model["name"].as_str()?;
+                let details = &model["details"];
+                let description = [&details["parameter_size"], &details["quantization_level"]]
+                    .iter()
+                    .filter_map(|detail| detail.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                // Vision models carry an image encoder, listed as an extra model family
+                let supports_vision = details["families"].as_array().is_some_and(|families| {
+                    families.iter().any(|f| matches!(f.as_str(), Some("clip" | "mllama")))
+                });
+                Some(ModelInfo {
+                    id: name.to_string(),
+                    name: name.trim_end_matches(":latest").to_string(),
+                    description,
+                    context_length: OLLAMA_DEFAULT_CONTEXT,
+                    supports_vision,
+                    supports_code: true,
+                    cost_per_1k_input: None,
+                    cost_per_1k_output: None,
                 })
             })
-            .collect();
-
-        // Include system instruction if present
-        let system_instruction = messages
-            .iter()
-            .find(|m| m.role == "system")
-            .map(|m| serde_json::json!({"parts": [{"text": m.content}]}));
-
-        let mut body = serde_json::json!({
-            "contents": contents,
-            "generationConfig": {
-                "maxOutputTokens": config.max_tokens,
-                "temperature": config.temperature,
-            }
-        });
-
-        if let Some(sys) = system_instruction {
-            body["system_instruction"] = sys;
-        }
-
-        let response = self
-            .client
-            .post(&api_url)
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| LLMError::NetworkError(e.to_string()))?;
+            .collect())
+    }
 
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(LLMError::ApiError(error_text));
-        }
+    /// Send a non-streaming chat request and return the answer
+    async fn complete(
+        &self,
+        body: &serde_json::Value,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let response = self.post_chat(body, config).await?;
 
         let result: serde_json::Value = response
             .json()
             .await
-            .map_err(|e| LLMError::ApiError(e.to_string()))?;
+            .map_err(|e| LLMError::ApiError(redact_secrets(&e.to_string())))?;
 
-        result["candidates"][0]["content"]["parts"][0]["text"]
+        result["message"]["content"]
             .as_str()
             .map(|s| s.to_string())
             .ok_or_else(|| LLMError::ApiError("Invalid response format".to_string()))
     }
 }
 
-// ─── Anthropic client ──────────────────────────────────────────────────
-
-pub struct AnthropicClient {
-    client: reqwest::Client,
-}
-
-impl AnthropicClient {
-    pub fn new() -> Self {
-        Self {
-            client: reqwest::Client::new(),
-        }
+impl Default for OllamaClient {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[async_trait::async_trait]
-impl LLMClient for AnthropicClient {
+impl LLMClient for OllamaClient {
     async fn chat(
         &self,
         messages: Vec<ChatMessage>,
         config: &ProviderConfig,
     ) -> Result<String, LLMError> {
-        let api_key = config.api_key.as_ref().ok_or(LLMError::InvalidApiKey)?;
-        let api_url = "https://api.anthropic.com/v1/messages";
+        self.complete(&ollama_body(&messages, config, false), config).await
+    }
 
-        let system_msg = messages
-            .iter()
-            .find(|m| m.role == "system")
-            .map(|m| m.content.clone());
+    async fn chat_with_image(
+        &self,
+        messages: Vec<ChatMessage>,
+        image: &ChatImage,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let mut body = ollama_body(&messages, config, false);
+        if let Some(last) = body["messages"].as_array_mut().and_then(|m| m.last_mut()) {
+            last["images"] = serde_json::json!([image.base64()]);
+        }
+        self.complete(&body, config).await
+    }
 
-        let chat_messages: Vec<serde_json::Value> = messages
-            .iter()
-            .filter(|m| m.role != "system")
-            .map(|m| {
-                serde_json::json!({
-                    "role": m.role,
-                    "content": m.content
-                })
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+        tokens: mpsc::UnboundedSender<String>,
+    ) -> Result<String, LLMError> {
+        let response = self.post_chat(&ollama_body(&messages, config, true), config).await?;
+
+        // One JSON object per line, the last with `"done": true`
+        read_lines(response, &tokens, |line| {
+            if line.is_empty() {
+                return Ok(StreamStep::Skip);
+            }
+            let chunk = stream_json(line)?;
+            if let Some(message) = chunk["error"].as_str() {
+                return Err(LLMError::ApiError(redact_secrets(message)));
+            }
+            if chunk["done"].as_bool() == Some(true) {
+                return Ok(StreamStep::Done);
+            }
+            Ok(match chunk["message"]["content"].as_str() {
+                Some(token) if !token.is_empty() => StreamStep::Token(token.to_string()),
+                _ => StreamStep::Skip,
             })
-            .collect();
+        })
+        .await
+    }
+}
 
-        let mut body = serde_json::json!({
-            "model": config.model,
-            "max_tokens": config.max_tokens,
-            "messages": chat_messages,
-        });
+fn ollama_body(
+    messages: &[ChatMessage],
+    config: &ProviderConfig,
+    stream: bool,
+) -> serde_json::Value {
+    let mut options = serde_json::json!({
+        "temperature": config.temperature,
+        "num_predict": config.max_tokens,
+    });
+    if let Some(top_p) = config.top_p {
+        options["top_p"] = top_p.into();
+    }
+    if !config.stop.is_empty() {
+        options["stop"] = config.stop.clone().into();
+    }
+    serde_json::json!({
+        "model": config.model,
+        "messages": messages,
+        "stream": stream,
+        "options": options,
+    })
+}
 
-        if let Some(sys) = system_msg {
-            body["system"] = serde_json::Value::String(sys);
-        }
+// ─── Gemini client ─────────────────────────────────────────────────────
 
-        let response = self
-            .client
-            .post(api_url)
-            .header("x-api-key", api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| LLMError::NetworkError(e.to_string()))?;
+pub struct GeminiClient {
+    client: reqwest::Client,
+}
 
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(LLMError::ApiError(error_text));
+impl GeminiClient {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
         }
+    }
+
+    /// POST to `method` of the configured model, e.g. `generateContent`
+    fn post(
+        &self,
+        config: &ProviderConfig,
+        method: &str,
+    ) -> Result<reqwest::RequestBuilder, LLMError> {
+        let api_key = config.api_key.as_ref().ok_or(LLMError::InvalidApiKey)?;
+        let api_url = format!(
+            "{}/models/{}:{}",
+            config
+                .api_url
+                .as_deref()
+                .unwrap_or("https://generativelanguage.googleapis.com/v1beta"),
+            config.model,
+            method
+        );
+        tracing::debug!("Gemini request to {}", api_url);
+
+        Ok(
+            with_custom_headers(self.client.post(&api_url), config, &["Content-Type"])
+                .query(&[("key", api_key)])
+                .header("Content-Type", "application/json"),
+        )
+    }
+
+    /// Send a `generateContent` request and return the answer
+    async fn complete(
+        &self,
+        body: &serde_json::Value,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let response = with_retry(config, || async {
+            let response = self
+                .post(config, "generateContent")?
+                .json(body)
+                .send()
+                .await
+                .map_err(network_error)?;
+            check_status(response).await
+        })
+        .await?;
 
         let result: serde_json::Value = response
             .json()
             .await
-            .map_err(|e| LLMError::ApiError(e.to_string()))?;
+            .map_err(|e| LLMError::ApiError(redact_secrets(&e.to_string())))?;
 
-        result["content"][0]["text"]
+        result["candidates"][0]["content"]["parts"][0]["text"]
             .as_str()
             .map(|s| s.to_string())
             .ok_or_else(|| LLMError::ApiError("Invalid response format".to_string()))
     }
 }
 
-// ─── AWS Bedrock client ────────────────────────────────────────────────
-
-pub struct BedrockClient;
-
-impl BedrockClient {
-    pub fn new() -> Self {
-        Self
-    }
-}
-
 #[async_trait::async_trait]
-impl LLMClient for BedrockClient {
+impl LLMClient for GeminiClient {
     async fn chat(
         &self,
         messages: Vec<ChatMessage>,
         config: &ProviderConfig,
     ) -> Result<String, LLMError> {
-        use aws_sdk_bedrockruntime::types::{
-            ContentBlock, ConversationRole, Message, SystemContentBlock,
-        };
-
-        let aws_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-        let client = aws_sdk_bedrockruntime::Client::new(&aws_config);
+        self.complete(&gemini_body(&messages, config), config).await
+    }
 
-        // Extract system message
-        let system_msgs: Vec<SystemContentBlock> = messages
-            .iter()
-            .filter(|m| m.role == "system")
-            .map(|m| SystemContentBlock::Text(m.content.clone()))
-            .collect();
+    async fn chat_with_image(
+        &self,
+        messages: Vec<ChatMessage>,
+        image: &ChatImage,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let mut body = gemini_body(&messages, config);
+        if let Some(last) = body["contents"].as_array_mut().and_then(|c| c.last_mut()) {
+            if let Some(parts) = last["parts"].as_array_mut() {
+                parts.push(serde_json::json!({
+                    "inline_data": {"mime_type": image.mime_type, "data": image.base64()}
+                }));
+            }
+        }
+        self.complete(&body, config).await
+    }
 
-        // Convert chat messages
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+        tokens: mpsc::UnboundedSender<String>,
+    ) -> Result<String, LLMError> {
+        let body = gemini_body(&messages, config);
+
+        let response = with_retry(config, || async {
+            let response = self
+                .post(config, "streamGenerateContent")?
+                .query(&[("alt", "sse")])
+                .json(&body)
+                .send()
+                .await
+                .map_err(network_error)?;
+            check_status(response).await
+        })
+        .await?;
+
+        // Each event is a partial response carrying the next piece of text; the
+        // stream simply ends after the last one
+        read_sse(response, &tokens, |data| {
+            let event = stream_json(data)?;
+            if let Some(message) = event["error"]["message"].as_str() {
+                return Err(LLMError::ApiError(redact_secrets(message)));
+            }
+            Ok(
+                match event["candidates"][0]["content"]["parts"][0]["text"].as_str() {
+                    Some(token) => StreamStep::Token(token.to_string()),
+                    None => StreamStep::Skip,
+                },
+            )
+        })
+        .await
+    }
+}
+
+fn gemini_body(messages: &[ChatMessage], config: &ProviderConfig) -> serde_json::Value {
+    let contents: Vec<serde_json::Value> = messages
+        .iter()
+        .filter(|m| m.role != "system")
+        .map(|m| {
+            serde_json::json!({
+                "role": if m.role == "assistant" { "model" } else { "user" },
+                "parts": [{"text": m.content}]
+            })
+        })
+        .collect();
+
+    // Include system instruction if present
+    let system_instruction = messages
+        .iter()
+        .find(|m| m.role == "system")
+        .map(|m| serde_json::json!({"parts": [{"text": m.content}]}));
+
+    let mut body = serde_json::json!({
+        "contents": contents,
+        "generationConfig": {
+            "maxOutputTokens": config.max_tokens,
+            "temperature": config.temperature,
+        }
+    });
+
+    if let Some(top_p) = config.top_p {
+        body["generationConfig"]["topP"] = top_p.into();
+    }
+    if !config.stop.is_empty() {
+        body["generationConfig"]["stopSequences"] = config.stop.clone().into();
+    }
+    if let Some(sys) = system_instruction {
+        body["system_instruction"] = sys;
+    }
+    body
+}
+
+// ─── Anthropic client ──────────────────────────────────────────────────
+
+const ANTHROPIC_HEADERS: &[&str] = &["x-api-key", "anthropic-version", "Content-Type"];
+
+pub struct AnthropicClient {
+    client: reqwest::Client,
+}
+
+impl AnthropicClient {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Authenticated POST to the messages endpoint
+    fn post(&self, config: &ProviderConfig) -> Result<reqwest::RequestBuilder, LLMError> {
+        let api_key = config.api_key.as_ref().ok_or(LLMError::InvalidApiKey)?;
+        let api_url = format!(
+            "{}/messages",
+            config.api_url.as_deref().unwrap_or("https://api.anthropic.com/v1")
+        );
+
+        Ok(
+            with_custom_headers(self.client.post(&api_url), config, ANTHROPIC_HEADERS)
+                .header("x-api-key", api_key)
+                .header("anthropic-version", "2023-06-01")
+                .header("Content-Type", "application/json"),
+        )
+    }
+
+    /// Send a messages request and return the answer
+    async fn complete(
+        &self,
+        body: &serde_json::Value,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let response = with_retry(config, || async {
+            let response = self
+                .post(config)?
+                .json(body)
+                .send()
+                .await
+                .map_err(network_error)?;
+            check_status(response).await
+        })
+        .await?;
+
+        let result: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| LLMError::ApiError(redact_secrets(&e.to_string())))?;
+
+        result["content"][0]["text"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| LLMError::ApiError("Invalid response format".to_string()))
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMClient for AnthropicClient {
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        self.complete(&anthropic_body(&messages, config), config).await
+    }
+
+    async fn chat_with_image(
+        &self,
+        messages: Vec<ChatMessage>,
+        image: &ChatImage,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let mut body = anthropic_body(&messages, config);
+        if let Some(last) = body["messages"].as_array_mut().and_then(|m| m.last_mut()) {
+            let text = last["content"].take();
+            last["content"] = serde_json::json!([
+                {
+                    "type": "image",
+                    "source": {
+                        "type": "base64",
+                        "media_type": image.mime_type,
+                        "data": image.base64(),
+                    },
+                },
+                {"type": "text", "text": text},
+            ]);
+        }
+        self.complete(&body, config).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+        tokens: mpsc::UnboundedSender<String>,
+    ) -> Result<String, LLMError> {
+        let mut body = anthropic_body(&messages, config);
+        body["stream"] = true.into();
+
+        let response = with_retry(config, || async {
+            let response = self
+                .post(config)?
+                .json(&body)
+                .send()
+                .await
+                .map_err(network_error)?;
+            check_status(response).await
+        })
+        .await?;
+
+        // Text arrives in `content_block_delta` events; errors can still come as an
+        // `error` event after the stream has started
+        read_sse(response, &tokens, |data| {
+            let event = stream_json(data)?;
+            Ok(match event["type"].as_str() {
+                Some("content_block_delta") => match event["delta"]["text"].as_str() {
+                    Some(token) => StreamStep::Token(token.to_string()),
+                    None => StreamStep::Skip,
+                },
+                Some("message_stop") => StreamStep::Done,
+                Some("error") => {
+                    let message =
+                        redact_secrets(event["error"]["message"].as_str().unwrap_or(data));
+                    return Err(match event["error"]["type"].as_str() {
+                        Some("rate_limit_error" | "overloaded_error") => {
+                            LLMError::RateLimited {
+                                message,
+                                retry_after: None,
+                            }
+                        }
+                        _ => LLMError::ApiError(message),
+                    });
+                }
+                _ => StreamStep::Skip,
+            })
+        })
+        .await
+    }
+}
+
+fn anthropic_body(messages: &[ChatMessage], config: &ProviderConfig) -> serde_json::Value {
+    let system_msg = messages
+        .iter()
+        .find(|m| m.role == "system")
+        .map(|m| m.content.clone());
+
+    let chat_messages: Vec<serde_json::Value> = messages
+        .iter()
+        .filter(|m| m.role != "system")
+        .map(|m| {
+            serde_json::json!({
+                "role": m.role,
+                "content": m.content
+            })
+        })
+        .collect();
+
+    let mut body = serde_json::json!({
+        "model": config.model,
+        "max_tokens": config.max_tokens,
+        "temperature": config.temperature,
+        "messages": chat_messages,
+    });
+
+    if let Some(top_p) = config.top_p {
+        body["top_p"] = top_p.into();
+    }
+    if !config.stop.is_empty() {
+        body["stop_sequences"] = config.stop.clone().into();
+    }
+    if let Some(sys) = system_msg {
+        body["system"] = serde_json::Value::String(sys);
+    }
+    body
+}
+
+// ─── AWS Bedrock client ────────────────────────────────────────────────
+
+/// Headers the Bedrock client or SigV4 signing sets; `x-amz-*` headers are protected too
+const BEDROCK_HEADERS: &[&str] = &["Authorization", "Host", "Content-Type", "Content-Length"];
+
+/// Valid `config.headers` that would not replace a header Bedrock requests depend on.
+/// The SDK panics on an invalid header, so those are skipped here.
+fn bedrock_custom_headers(config: &ProviderConfig) -> Vec<(String, String)> {
+    let mut headers = Vec::new();
+    for (name, value) in &config.headers {
+        let signing = name.get(..6).is_some_and(|p| p.eq_ignore_ascii_case("x-amz-"));
+        if signing || BEDROCK_HEADERS.iter().any(|r| r.eq_ignore_ascii_case(name)) {
+            tracing::warn!("Ignoring custom header {} that would replace a required one", name);
+            continue;
+        }
+        let valid = reqwest::header::HeaderName::try_from(name.as_str()).is_ok()
+            && reqwest::header::HeaderValue::try_from(value.as_str()).is_ok();
+        if !valid {
+            tracing::warn!("Ignoring invalid custom header {}", name);
+            continue;
+        }
+        headers.push((name.clone(), value.clone()));
+    }
+    headers
+}
+
+pub struct BedrockClient;
+
+impl BedrockClient {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Send a Converse request, with `image` before the text of the last message
+    async fn converse(
+        &self,
+        messages: &[ChatMessage],
+        image: Option<&ChatImage>,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        use aws_sdk_bedrockruntime::primitives::Blob;
+        use aws_sdk_bedrockruntime::types::{
+            ContentBlock, ConversationRole, ImageBlock, ImageFormat, ImageSource, Message,
+            SystemContentBlock,
+        };
+
+        let aws_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let client = aws_sdk_bedrockruntime::Client::new(&aws_config);
+
+        // Extract system message
+        let system_msgs: Vec<SystemContentBlock> = messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .map(|m| SystemContentBlock::Text(m.content.clone()))
+            .collect();
+
+        let image = image
+            .map(|image| {
+                ImageBlock::builder()
+                    .format(ImageFormat::from(image.mime_type.trim_start_matches("image/")))
+                    .source(ImageSource::Bytes(Blob::new(image.data.clone())))
+                    .build()
+                    .map_err(|e| LLMError::ApiError(e.to_string()))
+            })
+            .transpose()?;
+        let last = messages.iter().rposition(|m| m.role != "system");
+
+        // Convert chat messages
         let bedrock_messages: Vec<Message> = messages
             .iter()
-            .filter(|m| m.role != "system")
-            .map(|m| {
+            .enumerate()
+            .filter(|(_, m)| m.role != "system")
+            .map(|(i, m)| {
                 let role = if m.role == "assistant" {
                     ConversationRole::Assistant
                 } else {
                     ConversationRole::User
                 };
-                Message::builder()
-                    .role(role)
+                let mut message = Message::builder().role(role);
+                if let Some(image) = image.as_ref().filter(|_| Some(i) == last) {
+                    message = message.content(ContentBlock::Image(image.clone()));
+                }
+                message
                     .content(ContentBlock::Text(m.content.clone()))
                     .build()
                     .expect("valid message")
@@ -765,17 +1558,29 @@ impl LLMClient for BedrockClient {
         }
 
         // Set inference config
-        req = req.inference_config(
-            aws_sdk_bedrockruntime::types::InferenceConfiguration::builder()
-                .max_tokens(config.max_tokens as i32)
-                .temperature(config.temperature)
-                .build(),
-        );
+        let mut inference = aws_sdk_bedrockruntime::types::InferenceConfiguration::builder()
+            .max_tokens(config.max_tokens as i32)
+            .temperature(config.temperature)
+            .set_top_p(config.top_p);
+        if !config.stop.is_empty() {
+            inference = inference.set_stop_sequences(Some(config.stop.clone()));
+        }
+        req = req.inference_config(inference.build());
 
+        // Custom headers are added before SigV4 signing so they're covered by the signature
+        let headers = bedrock_custom_headers(config);
         let response = req
+            .customize()
+            .mutate_request(move |request| {
+                for (name, value) in &headers {
+                    request.headers_mut().insert(name.clone(), value.clone());
+                }
+            })
             .send()
             .await
-            .map_err(|e| LLMError::ApiError(format!("Bedrock error: {}", e)))?;
+            .map_err(|e| {
+                LLMError::ApiError(redact_secrets(&format!("Bedrock error: {}", e)))
+            })?;
 
         // Extract text from response
         let output = response
@@ -810,6 +1615,141 @@ impl LLMClient for BedrockClient {
     }
 }
 
+#[async_trait::async_trait]
+impl LLMClient for BedrockClient {
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        self.converse(&messages, None, config).await
+    }
+
+    async fn chat_with_image(
+        &self,
+        messages: Vec<ChatMessage>,
+        image: &ChatImage,
+        config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        self.converse(&messages, Some(image), config).await
+    }
+}
+
+// ─── Fallback chain ────────────────────────────────────────────────────
+
+/// Tries an ordered chain of providers, failing over to the next on provider errors.
+///
+/// Each link uses its own `ProviderConfig`; the config passed to `chat` is ignored.
+pub struct FallbackClient {
+    chain: Vec<(Box<dyn LLMClient>, ProviderConfig)>,
+}
+
+impl FallbackClient {
+    pub fn new(chain: Vec<(LLMProvider, ProviderConfig)>) -> Self {
+        Self::from_clients(
+            chain
+                .into_iter()
+                .map(|(provider, config)| (create_client(&provider), config))
+                .collect(),
+        )
+    }
+
+    /// Build a chain from already-constructed clients
+    pub fn from_clients(chain: Vec<(Box<dyn LLMClient>, ProviderConfig)>) -> Self {
+        Self { chain }
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMClient for FallbackClient {
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        _config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let mut last_error = LLMError::ApiError("Fallback chain is empty".to_string());
+
+        for (index, (client, config)) in self.chain.iter().enumerate() {
+            match client.chat(messages.clone(), config).await {
+                Ok(answer) => {
+                    tracing::info!(
+                        "LLM request served by {:?} ({}), link {} of {}",
+                        config.provider,
+                        config.model,
+                        index + 1,
+                        self.chain.len()
+                    );
+                    return Ok(answer);
+                }
+                Err(e) if e.should_fail_over() => {
+                    tracing::warn!("{:?} failed, trying next provider: {}", config.provider, e);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        _config: &ProviderConfig,
+        tokens: mpsc::UnboundedSender<String>,
+    ) -> Result<String, LLMError> {
+        let mut last_error = LLMError::ApiError("Fallback chain is empty".to_string());
+
+        for (client, config) in &self.chain {
+            // Relay tokens so a provider that fails after streaming part of an answer isn't
+            // followed by another's whole answer
+            let (relay, mut relayed) = mpsc::unbounded_channel();
+            let mut streamed = false;
+            let forward = async {
+                while let Some(token) = relayed.recv().await {
+                    streamed = true;
+                    let _ = tokens.send(token);
+                }
+            };
+            let (result, ()) =
+                tokio::join!(client.chat_stream(messages.clone(), config, relay), forward);
+
+            match result {
+                Ok(answer) => return Ok(answer),
+                Err(e) if e.should_fail_over() && !streamed => {
+                    tracing::warn!("{:?} failed, trying next provider: {}", config.provider, e);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    async fn chat_with_image(
+        &self,
+        messages: Vec<ChatMessage>,
+        image: &ChatImage,
+        _config: &ProviderConfig,
+    ) -> Result<String, LLMError> {
+        let mut last_error = LLMError::ApiError("Fallback chain is empty".to_string());
+
+        for (client, config) in &self.chain {
+            match client.chat_with_image(messages.clone(), image, config).await {
+                Ok(answer) => return Ok(answer),
+                Err(e) if e.should_fail_over() => {
+                    tracing::warn!("{:?} failed, trying next provider: {}", config.provider, e);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
 // ─── Factory ───────────────────────────────────────────────────────────
 
 /// Create appropriate client for provider
@@ -819,10 +1759,744 @@ pub fn create_client(provider: &LLMProvider) -> Box<dyn LLMClient> {
         | LLMProvider::Groq
         | LLMProvider::AzureOpenAI
         | LLMProvider::Custom
-        | LLMProvider::Ollama
         | LLMProvider::Local => Box::new(OpenAIClient::new()),
+        LLMProvider::Ollama => Box::new(OllamaClient::new()),
         LLMProvider::Gemini => Box::new(GeminiClient::new()),
         LLMProvider::Anthropic => Box::new(AnthropicClient::new()),
         LLMProvider::Bedrock => Box::new(BedrockClient::new()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails or succeeds with a fixed outcome
+    struct StubClient {
+        reply: Result<&'static str, fn() -> LLMError>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMClient for StubClient {
+        async fn chat(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _config: &ProviderConfig,
+        ) -> Result<String, LLMError> {
+            self.reply.map(str::to_string).map_err(|e| e())
+        }
+    }
+
+    fn link(
+        reply: Result<&'static str, fn() -> LLMError>,
+        provider: LLMProvider,
+    ) -> (Box<dyn LLMClient>, ProviderConfig) {
+        (
+            Box::new(StubClient { reply }),
+            ProviderConfig {
+                provider,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_fallback_uses_secondary_when_primary_fails() {
+        let client = FallbackClient::from_clients(vec![
+            link(
+                Err(|| LLMError::RateLimited {
+                    message: "slow down".to_string(),
+                    retry_after: None,
+                }),
+                LLMProvider::OpenAI,
+            ),
+            link(Err(|| LLMError::NetworkError("down".to_string())), LLMProvider::Gemini),
+            link(Ok("from anthropic"), LLMProvider::Anthropic),
+        ]);
+
+        let answer = client.chat(vec![], &ProviderConfig::default()).await.unwrap();
+        assert_eq!(answer, "from anthropic");
+    }
+
+    /// Streams the start of an answer, then is rate limited
+    struct InterruptedClient;
+
+    #[async_trait::async_trait]
+    impl LLMClient for InterruptedClient {
+        async fn chat(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _config: &ProviderConfig,
+        ) -> Result<String, LLMError> {
+            unreachable!("only streamed")
+        }
+
+        async fn chat_stream(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _config: &ProviderConfig,
+            tokens: mpsc::UnboundedSender<String>,
+        ) -> Result<String, LLMError> {
+            let _ = tokens.send("Attention is".to_string());
+            Err(LLMError::RateLimited {
+                message: "overloaded".to_string(),
+                retry_after: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_stream_fails_over_only_before_first_token() {
+        let config = ProviderConfig::default();
+
+        let client = FallbackClient::from_clients(vec![
+            link(Err(|| LLMError::NetworkError("down".to_string())), LLMProvider::OpenAI),
+            link(Ok("from gemini"), LLMProvider::Gemini),
+        ]);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        assert_eq!(client.chat_stream(vec![], &config, tx).await.unwrap(), "from gemini");
+        assert_eq!(rx.recv().await.as_deref(), Some("from gemini"));
+
+        let (interrupted, _) = link(Ok("unused"), LLMProvider::Anthropic);
+        let client = FallbackClient::from_clients(vec![
+            (Box::new(InterruptedClient), config.clone()),
+            (interrupted, config.clone()),
+        ]);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let result = client.chat_stream(vec![], &config, tx).await;
+        assert!(matches!(result, Err(LLMError::RateLimited { .. })));
+        assert_eq!(rx.recv().await.as_deref(), Some("Attention is"));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_stops_on_non_provider_error() {
+        let client = FallbackClient::from_clients(vec![
+            link(Err(|| LLMError::ContextTooLong), LLMProvider::OpenAI),
+            link(Ok("unused"), LLMProvider::Gemini),
+        ]);
+
+        let result = client.chat(vec![], &ProviderConfig::default()).await;
+        assert!(matches!(result, Err(LLMError::ContextTooLong)));
+
+        let empty = FallbackClient::from_clients(vec![]);
+        assert!(empty.chat(vec![], &ProviderConfig::default()).await.is_err());
+    }
+
+    #[test]
+    fn test_request_bodies_carry_top_p_and_stop() {
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: "Be brief.".to_string(),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: "Hi".to_string(),
+            },
+        ];
+        let config = ProviderConfig {
+            temperature: 0.2,
+            top_p: Some(0.5),
+            stop: vec!["END".to_string()],
+            ..Default::default()
+        };
+
+        let openai = openai_body(&messages, &config);
+        assert_eq!(openai["top_p"], 0.5);
+        assert_eq!(openai["stop"], serde_json::json!(["END"]));
+
+        let gemini = gemini_body(&messages, &config);
+        assert_eq!(gemini["generationConfig"]["topP"], 0.5);
+        assert_eq!(gemini["generationConfig"]["stopSequences"], serde_json::json!(["END"]));
+
+        let anthropic = anthropic_body(&messages, &config);
+        assert_eq!(anthropic["top_p"], 0.5);
+        assert_eq!(anthropic["stop_sequences"], serde_json::json!(["END"]));
+        assert_eq!(anthropic["temperature"].as_f64().unwrap() as f32, 0.2);
+
+        // Unset parameters are left to the provider's defaults
+        let plain = openai_body(&messages, &ProviderConfig::default());
+        assert!(plain.get("top_p").is_none());
+        assert!(plain.get("stop").is_none());
+    }
+
+    /// Answers one request with `body`, handing back the raw request head
+    async fn capture_request(
+        body: &'static str,
+    ) -> (String, tokio::sync::oneshot::Receiver<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                head.extend_from_slice(&buf[..n]);
+            }
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            let _ = tx.send(String::from_utf8_lossy(&head).to_lowercase());
+        });
+
+        (url, rx)
+    }
+
+    /// Config with a gateway header plus an attempt to replace the provider's auth header
+    fn config_with_headers(provider: LLMProvider, url: String, auth: &str) -> ProviderConfig {
+        ProviderConfig {
+            provider,
+            api_url: Some(url),
+            api_key: Some("secret".to_string()),
+            headers: HashMap::from([
+                ("X-Gateway-Org".to_string(), "research".to_string()),
+                (auth.to_string(), "hijacked".to_string()),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openai_sends_custom_headers() {
+        let (url, head) =
+            capture_request(r#"{"choices":[{"message":{"content":"ok"}}]}"#).await;
+        let config = config_with_headers(LLMProvider::OpenAI, url, "Authorization");
+
+        let answer = OpenAIClient::new().chat(vec![], &config).await.unwrap();
+        let head = head.await.unwrap();
+
+        assert_eq!(answer, "ok");
+        assert!(head.contains("x-gateway-org: research"));
+        assert!(head.contains("authorization: bearer secret"));
+        assert!(!head.contains("hijacked"));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_sends_custom_headers() {
+        let (url, head) = capture_request(r#"{"content":[{"text":"ok"}]}"#).await;
+        let config = config_with_headers(LLMProvider::Anthropic, url, "X-Api-Key");
+
+        let answer = AnthropicClient::new().chat(vec![], &config).await.unwrap();
+        let head = head.await.unwrap();
+
+        assert_eq!(answer, "ok");
+        assert!(head.starts_with("post /v1/messages"));
+        assert!(head.contains("x-gateway-org: research"));
+        assert!(head.contains("x-api-key: secret"));
+        assert!(!head.contains("hijacked"));
+    }
+
+    #[test]
+    fn test_bedrock_skips_invalid_and_protected_headers() {
+        let config = ProviderConfig {
+            provider: LLMProvider::Bedrock,
+            headers: HashMap::from([
+                ("X-Gateway-Org".to_string(), "research".to_string()),
+                ("authorization".to_string(), "hijacked".to_string()),
+                ("Host".to_string(), "example.com".to_string()),
+                ("X-Amz-Security-Token".to_string(), "forged".to_string()),
+                ("bad header".to_string(), "x".to_string()),
+                ("X-Multiline".to_string(), "a\r\nb".to_string()),
+            ]),
+            ..Default::default()
+        };
+
+        let headers = bedrock_custom_headers(&config);
+        assert_eq!(headers, [("X-Gateway-Org".to_string(), "research".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_openai_organization_header_only_when_set() {
+        let reply = r#"{"choices":[{"message":{"content":"ok"}}]}"#;
+        let config = |url: String, organization: Option<&str>| ProviderConfig {
+            api_url: Some(url),
+            api_key: Some("secret".to_string()),
+            organization: organization.map(str::to_string),
+            ..Default::default()
+        };
+
+        let (url, head) = capture_request(reply).await;
+        OpenAIClient::new()
+            .chat(vec![], &config(url, Some("org-123")))
+            .await
+            .unwrap();
+        assert!(head.await.unwrap().contains("openai-organization: org-123"));
+
+        let (url, head) = capture_request(reply).await;
+        OpenAIClient::new().chat(vec![], &config(url, None)).await.unwrap();
+        assert!(!head.await.unwrap().contains("openai-organization"));
+    }
+
+    /// Answers one request with `status` and a body written in `parts`, pausing between
+    /// parts so each arrives as a separate read
+    async fn serve_in_parts(status: &'static str, parts: Vec<&'static [u8]>) -> String {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            read_request(&mut socket).await;
+
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n",
+                status
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            for part in parts {
+                socket.write_all(part).await.unwrap();
+                socket.flush().await.unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        });
+
+        url
+    }
+
+    /// Read the whole request so closing the socket does not reset the connection
+    async fn read_request(socket: &mut tokio::net::TcpStream) -> Vec<u8> {
+        use tokio::io::AsyncReadExt;
+
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_lowercase();
+            let Some(head_end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let length = text
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .map_or(0, |n| n.trim().parse::<usize>().unwrap());
+            if n == 0 || request.len() >= head_end + 4 + length {
+                break;
+            }
+        }
+        request
+    }
+
+    /// Answers successive requests with `responses` in turn, each a status line with any
+    /// extra headers and a body, and records when each request arrived
+    async fn serve_sequence(
+        responses: Vec<(&'static str, &'static str)>,
+    ) -> (String, std::sync::Arc<std::sync::Mutex<Vec<std::time::Instant>>>) {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let arrivals = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let recorded = arrivals.clone();
+        tokio::spawn(async move {
+            for (head, body) in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                read_request(&mut socket).await;
+                recorded.lock().unwrap().push(std::time::Instant::now());
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    head,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, arrivals)
+    }
+
+    /// Answers one request with `body`, handing back the JSON body of the request
+    async fn capture_body(
+        body: &'static str,
+    ) -> (String, tokio::sync::oneshot::Receiver<serde_json::Value>) {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut socket).await;
+            let start = request.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            let _ = tx.send(serde_json::from_slice(&request[start..]).unwrap());
+        });
+
+        (url, rx)
+    }
+
+    #[tokio::test]
+    async fn test_images_are_sent_in_each_providers_format() {
+        let image = ChatImage {
+            mime_type: "image/png".to_string(),
+            data: b"png bytes".to_vec(),
+        };
+        let encoded = "cG5nIGJ5dGVz";
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: "Describe figures.".to_string(),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: "What does this show?".to_string(),
+            },
+        ];
+        let ask = |client: Box<dyn LLMClient>, provider: LLMProvider, url: String| {
+            let messages = messages.clone();
+            let image = image.clone();
+            async move {
+                let config = ProviderConfig {
+                    provider,
+                    api_url: Some(url),
+                    api_key: Some("secret".to_string()),
+                    model: "vision".to_string(),
+                    ..Default::default()
+                };
+                client.chat_with_image(messages, &image, &config).await.unwrap()
+            }
+        };
+
+        let (url, body) = capture_body(r#"{"choices":[{"message":{"content":"a"}}]}"#).await;
+        assert_eq!(ask(Box::new(OpenAIClient::new()), LLMProvider::OpenAI, url).await, "a");
+        let content = &body.await.unwrap()["messages"][1]["content"];
+        assert_eq!(content[0]["text"], "What does this show?");
+        assert_eq!(
+            content[1]["image_url"]["url"],
+            format!("data:image/png;base64,{}", encoded)
+        );
+
+        let (url, body) = capture_body(r#"{"content":[{"text":"b"}]}"#).await;
+        assert_eq!(ask(Box::new(AnthropicClient::new()), LLMProvider::Anthropic, url).await, "b");
+        let content = &body.await.unwrap()["messages"][0]["content"];
+        assert_eq!(content[0]["source"]["media_type"], "image/png");
+        assert_eq!(content[0]["source"]["data"], encoded);
+        assert_eq!(content[1]["text"], "What does this show?");
+
+        let reply = r#"{"candidates":[{"content":{"parts":[{"text":"c"}]}}]}"#;
+        let (url, body) = capture_body(reply).await;
+        assert_eq!(ask(Box::new(GeminiClient::new()), LLMProvider::Gemini, url).await, "c");
+        let parts = &body.await.unwrap()["contents"][0]["parts"];
+        assert_eq!(parts[0]["text"], "What does this show?");
+        assert_eq!(parts[1]["inline_data"]["data"], encoded);
+
+        let (url, body) = capture_body(r#"{"message":{"content":"d"},"done":true}"#).await;
+        assert_eq!(ask(Box::<OllamaClient>::default(), LLMProvider::Ollama, url).await, "d");
+        assert_eq!(body.await.unwrap()["messages"][1]["images"][0], encoded);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_requests_are_retried_with_backoff() {
+        let (url, arrivals) = serve_sequence(vec![
+            ("429 Too Many Requests", "slow down"),
+            ("429 Too Many Requests\r\nRetry-After: 1", "slow down"),
+            ("200 OK", r#"{"choices":[{"message":{"content":"ok"}}]}"#),
+        ])
+        .await;
+        let config = ProviderConfig {
+            api_url: Some(url),
+            api_key: Some("secret".to_string()),
+            retry_base_delay_ms: 200,
+            ..Default::default()
+        };
+
+        let answer = OpenAIClient::new().chat(vec![], &config).await.unwrap();
+        assert_eq!(answer, "ok");
+
+        let arrivals = arrivals.lock().unwrap();
+        assert_eq!(arrivals.len(), 3);
+        // At least half the base delay, then the second the server asked for
+        assert!(arrivals[1] - arrivals[0] >= Duration::from_millis(100));
+        assert!(arrivals[2] - arrivals[1] >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up_after_max_attempts_and_skips_permanent_errors() {
+        let config = ProviderConfig {
+            retry_base_delay_ms: 1,
+            ..Default::default()
+        };
+        let attempts_until = |error: fn() -> LLMError| {
+            let config = &config;
+            async move {
+                let calls = std::sync::atomic::AtomicU32::new(0);
+                let calls = &calls;
+                let result: Result<(), LLMError> = with_retry(config, || async move {
+                    calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    Err(error())
+                })
+                .await;
+                assert!(result.is_err());
+                calls.load(std::sync::atomic::Ordering::SeqCst)
+            }
+        };
+
+        assert_eq!(attempts_until(|| LLMError::NetworkError("reset".to_string())).await, 3);
+        assert_eq!(attempts_until(|| LLMError::InvalidApiKey).await, 1);
+        assert_eq!(attempts_until(|| LLMError::ContextTooLong).await, 1);
+    }
+
+    #[test]
+    fn test_retry_after_in_seconds_or_as_a_date() {
+        use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        let date = chrono::Utc::now() + chrono::Duration::seconds(30);
+        let date = date.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(&date).unwrap());
+        let wait = retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_secs(28) && wait <= Duration::from_secs(30));
+    }
+
+    /// Stream from `client` against `url`, returning the answer and the tokens sent
+    async fn stream_from(
+        client: &dyn LLMClient,
+        provider: LLMProvider,
+        url: String,
+    ) -> Result<(String, Vec<String>), LLMError> {
+        // One attempt, as the servers here answer a single request
+        let config = ProviderConfig {
+            provider,
+            api_url: Some(url),
+            api_key: Some("secret".to_string()),
+            max_attempts: 1,
+            ..Default::default()
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let answer = client.chat_stream(vec![], &config, tx).await?;
+        let mut tokens = Vec::new();
+        while let Ok(token) = rx.try_recv() {
+            tokens.push(token);
+        }
+        Ok((answer, tokens))
+    }
+
+    #[tokio::test]
+    async fn test_stream_tokens_in_order_across_split_frames() {
+        // Frames split mid-line and "é" split between its two bytes
+        let url = serve_in_parts(
+            "200 OK",
+            vec![
+                b"data: {\"choices\":[{\"delta\":{\"content\":\"Caf\\u00e9 \"}}]}\r\n\r\nda",
+                b"ta: {\"choices\":[{\"delta\":{\"content\":\"cr\xc3",
+                b"\xa8me\"}}]}\r\n\r\ndata: {\"choices\":[{\"delta\":{}}]}\n\n",
+                b"data: [DONE]\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"late\"}}]}\n\n",
+            ],
+        )
+        .await;
+        let (answer, tokens) = stream_from(&OpenAIClient::new(), LLMProvider::OpenAI, url)
+            .await
+            .unwrap();
+        assert_eq!(tokens, ["Café ", "crème"]);
+        assert_eq!(answer, "Café crème");
+
+        let url = serve_in_parts(
+            "200 OK",
+            vec![
+                b"event: message_start\ndata: {\"type\":\"message_start\"}\n\n",
+                b"event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"del",
+                b"ta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\nevent: ping\n",
+                b"data: {\"type\":\"ping\"}\n\nevent: content_block_delta\n\
+                  data: {\"type\":\"content_block_delta\",\"delta\":{\"text\":\", world\"}}\n\n",
+                b"event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+            ],
+        )
+        .await;
+        let (answer, tokens) =
+            stream_from(&AnthropicClient::new(), LLMProvider::Anthropic, url)
+                .await
+                .unwrap();
+        assert_eq!(tokens, ["Hello", ", world"]);
+        assert_eq!(answer, "Hello, world");
+
+        let url = serve_in_parts(
+            "200 OK",
+            vec![
+                b"data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"One\"}]}}]}\r\n",
+                b"\r\ndata: {\"candidates\":[{\"content\":{\"parts\":[{\"te",
+                b"xt\":\" two\"}]},\"finishReason\":\"STOP\"}]}\r\n\r\n",
+            ],
+        )
+        .await;
+        let (answer, tokens) = stream_from(&GeminiClient::new(), LLMProvider::Gemini, url)
+            .await
+            .unwrap();
+        assert_eq!(tokens, ["One", " two"]);
+        assert_eq!(answer, "One two");
+    }
+
+    #[tokio::test]
+    async fn test_stream_rate_limits_are_reported() {
+        let clients: [(&dyn LLMClient, LLMProvider); 3] = [
+            (&OpenAIClient::new(), LLMProvider::OpenAI),
+            (&AnthropicClient::new(), LLMProvider::Anthropic),
+            (&GeminiClient::new(), LLMProvider::Gemini),
+        ];
+        for (client, provider) in clients {
+            let url = serve_in_parts("429 Too Many Requests", vec![b"slow down"]).await;
+            let result = stream_from(client, provider.clone(), url).await;
+            assert!(
+                matches!(&result, Err(LLMError::RateLimited { message, .. })
+                    if message == "slow down"),
+                "{:?}: {:?}",
+                provider,
+                result
+            );
+        }
+
+        // Anthropic can also report overload once the stream has started
+        let url = serve_in_parts(
+            "200 OK",
+            vec![
+                b"data: {\"type\":\"content_block_delta\",\"delta\":{\"text\":\"Par\"}}\n\n",
+                b"event: error\ndata: {\"type\":\"error\",\"error\":\
+                  {\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n",
+            ],
+        )
+        .await;
+        let result = stream_from(&AnthropicClient::new(), LLMProvider::Anthropic, url).await;
+        assert!(matches!(
+            result,
+            Err(LLMError::RateLimited { message, .. }) if message == "Overloaded"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ollama_chat_stream_and_installed_models() {
+        let (url, head) = capture_request(
+            r#"{"model":"llama3.2","message":{"role":"assistant","content":"ok"},"done":true}"#,
+        )
+        .await;
+        let config = ProviderConfig {
+            api_url: Some(url),
+            ..ProviderConfig::ollama("llama3.2")
+        };
+        let answer = OllamaClient::new().chat(vec![], &config).await.unwrap();
+        assert_eq!(answer, "ok");
+        // The `/v1` of the OpenAI-compatible endpoint is dropped
+        assert!(head.await.unwrap().starts_with("post /api/chat "));
+
+        let url = serve_in_parts(
+            "200 OK",
+            vec![
+                b"{\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},",
+                b"\"done\":false}\n{\"message\":{\"role\":\"assistant\",\"con",
+                b"tent\":\"lo\"},\"done\":false}\n",
+                b"{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true}\n",
+            ],
+        )
+        .await;
+        let (answer, tokens) = stream_from(&OllamaClient::new(), LLMProvider::Ollama, url)
+            .await
+            .unwrap();
+        assert_eq!(tokens, ["Hel", "lo"]);
+        assert_eq!(answer, "Hello");
+
+        let (url, head) = capture_request(
+            r#"{"models":[{"name":"llama3.2:latest","details":{"parameter_size":"3.2B",
+                "quantization_level":"Q4_K_M"}},{"name":"qwen2.5-coder:7b","details":{}},
+                {"name":"llava:7b","details":{"families":["llama","clip"]}}]}"#,
+        )
+        .await;
+        let config = ProviderConfig {
+            api_url: Some(url),
+            ..ProviderConfig::ollama("")
+        };
+        let models = OllamaClient::new().list_models(&config).await.unwrap();
+        assert!(head.await.unwrap().starts_with("get /api/tags "));
+        let ids: Vec<&str> = models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["llama3.2:latest", "qwen2.5-coder:7b", "llava:7b"]);
+        assert_eq!(models[0].name, "llama3.2");
+        assert_eq!(models[0].description, "3.2B, Q4_K_M");
+        let vision: Vec<bool> = models.iter().map(|m| m.supports_vision).collect();
+        assert_eq!(vision, [false, false, true]);
+    }
+
+    #[tokio::test]
+    async fn test_ollama_unreachable_server_is_a_network_error() {
+        // A port nothing is listening on
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let config = ProviderConfig {
+            api_url: Some(url.clone()),
+            retry_base_delay_ms: 1,
+            ..ProviderConfig::ollama("llama3.2")
+        };
+
+        let result = OllamaClient::new().chat(vec![], &config).await;
+        assert!(
+            matches!(&result, Err(LLMError::NetworkError(message))
+                if message.contains("Cannot reach Ollama") && message.contains(&url)),
+            "{:?}",
+            result
+        );
+        let result = OllamaClient::new().list_models(&config).await;
+        assert!(matches!(result, Err(LLMError::NetworkError(_))));
+    }
+
+    #[test]
+    fn test_gemini_url_key_is_redacted() {
+        let url = "https://generativelanguage.googleapis.com/v1beta/models/gemini-pro\
+                   :generateContent?key=AIzaSyD-not-a-real-key-123456";
+        let message = format!("error sending request for url ({})", url);
+
+        let redacted = redact_secrets(&message);
+        assert!(redacted.ends_with(":generateContent?key=***)"), "{}", redacted);
+        assert!(!redacted.contains("AIza"));
+    }
+
+    #[test]
+    fn test_error_bodies_and_headers_are_redacted() {
+        let body = r#"{"error": "Incorrect API key provided: sk-proj-abcdef123456"}"#;
+        assert!(!redact_secrets(body).contains("abcdef"));
+        assert_eq!(
+            redact_secrets("Authorization: Bearer abc.def-123"),
+            "Authorization: ***"
+        );
+        assert_eq!(redact_secrets(r#"{"api_key": "hunter22"}"#), r#"{"api_key": "***"}"#);
+        assert_eq!(redact_secrets("no secrets here"), "no secrets here");
+    }
+
+    #[tokio::test]
+    async fn test_network_error_masks_key_in_url() {
+        // Nothing listens on port 1, so the request fails with the URL in the error
+        let err = reqwest::Client::new()
+            .post("http://127.0.0.1:1/v1beta/models/gemini-pro:generateContent?key=AIzaSecret")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("AIzaSecret"));
+
+        let message = network_error(err).to_string();
+        assert!(message.contains("key=***"), "{}", message);
+        assert!(!message.contains("AIzaSecret"));
+    }
+}
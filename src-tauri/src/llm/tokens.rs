@@ -0,0 +1,144 @@
+//! Token counts and costs estimated before a query is sent
+
+use super::{LLMProvider, ModelInfo};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Letters of a word covered by one token of a BPE vocabulary; shorter words are
+/// usually a single token, longer ones split into common stems and endings
+const BPE_CHARS_PER_TOKEN: usize = 10;
+
+/// Tokens per word for the SentencePiece tokenizers of open models (about 4 per 3 words)
+const TOKENS_PER_WORD: f64 = 4.0 / 3.0;
+
+/// Pieces a BPE tokenizer splits text into before merging, as in OpenAI's `cl100k`
+/// pattern: contractions, words with one leading space or symbol, up to three digits,
+/// runs of punctuation and whitespace
+fn bpe_pieces() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"'(?:[sdmt]|ll|ve|re)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}",
+            r"| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+",
+        ))
+        .unwrap()
+    })
+}
+
+/// Estimate how many tokens `text` takes for `provider`.
+///
+/// Hosted models with byte-pair vocabularies (OpenAI, Anthropic, Gemini, Bedrock) get
+/// a `tiktoken`-style approximation; local and other open models a per-word estimate.
+pub fn estimate_tokens(text: &str, provider: &LLMProvider) -> u32 {
+    match provider {
+        LLMProvider::OpenAI
+        | LLMProvider::AzureOpenAI
+        | LLMProvider::Anthropic
+        | LLMProvider::Gemini
+        | LLMProvider::Bedrock => bpe_pieces()
+            .find_iter(text)
+            .map(|m| piece_tokens(m.as_str()))
+            .sum(),
+        LLMProvider::Local | LLMProvider::Ollama | LLMProvider::Groq | LLMProvider::Custom => {
+            (text.split_whitespace().count() as f64 * TOKENS_PER_WORD).ceil() as u32
+        }
+    }
+}
+
+/// Tokens for one pre-tokenized piece
+fn piece_tokens(piece: &str) -> u32 {
+    let ascii_tokens = |chars: usize| 1 + chars.saturating_sub(1) / BPE_CHARS_PER_TOKEN;
+
+    let word = piece.trim_start_matches(|c: char| !c.is_alphanumeric());
+    if word.is_empty() {
+        // Whitespace, or a run of punctuation
+        let symbols = piece.trim().chars().count();
+        return ascii_tokens(symbols) as u32;
+    }
+    if word.chars().all(|c| c.is_ascii_digit()) {
+        return 1;
+    }
+
+    // Characters outside ASCII (accents, CJK) are rarely merged with their neighbours
+    let ascii = word.chars().filter(char::is_ascii).count();
+    let other = word.chars().count() - ascii;
+    let ascii = if ascii > 0 { ascii_tokens(ascii) } else { 0 };
+    (ascii + other) as u32
+}
+
+/// Dollar cost of a query with `model`, or `None` for models without pricing
+pub fn estimate_cost(input_tokens: u32, output_tokens: u32, model: &ModelInfo) -> Option<f64> {
+    let input = model.cost_per_1k_input? * f64::from(input_tokens) / 1000.0;
+    let output = model.cost_per_1k_output? * f64::from(output_tokens) / 1000.0;
+    Some(input + output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Within `tolerance` (a fraction) of `expected`
+    fn assert_close(estimate: u32, expected: u32, tolerance: f64) {
+        let error = (f64::from(estimate) - f64::from(expected)).abs() / f64::from(expected);
+        assert!(error <= tolerance, "estimated {} tokens, expected {}", estimate, expected);
+    }
+
+    #[test]
+    fn test_bpe_estimates_near_known_counts() {
+        // Token counts from OpenAI's cl100k_base tokenizer
+        let known = [
+            ("Hello world", 2),
+            ("The quick brown fox jumps over the lazy dog.", 10),
+            (
+                "Attention is all you need. The dominant sequence transduction models are \
+                 based on complex recurrent or convolutional neural networks that include \
+                 an encoder and a decoder.",
+                30,
+            ),
+            (
+                "fn main() {\n    let x: Vec<u32> = (0..10).map(|i| i * 2).collect();\n    \
+                 println!(\"{:?}\", x);\n}",
+                37,
+            ),
+            (
+                "The model achieved 28.4 BLEU on the WMT 2014 English-to-German translation \
+                 task, improving over the existing best results by over 2 BLEU.",
+                36,
+            ),
+            ("深度学习模型在自然语言处理中取得了巨大成功。", 23),
+            ("Le café était délicieux, mais l'addition était très élevée.", 18),
+        ];
+        for (text, expected) in known {
+            assert_close(estimate_tokens(text, &LLMProvider::OpenAI), expected, 0.15);
+            assert_close(estimate_tokens(text, &LLMProvider::Anthropic), expected, 0.15);
+        }
+        assert_eq!(estimate_tokens("", &LLMProvider::OpenAI), 0);
+    }
+
+    #[test]
+    fn test_local_models_use_word_estimate() {
+        let text = "one two three four five six";
+        assert_eq!(estimate_tokens(text, &LLMProvider::Ollama), 8);
+        assert_eq!(estimate_tokens(text, &LLMProvider::Local), 8);
+    }
+
+    #[test]
+    fn test_cost_only_for_priced_models() {
+        let mut model = ModelInfo {
+            id: "gpt-4o".to_string(),
+            name: "GPT-4o".to_string(),
+            description: String::new(),
+            context_length: 128_000,
+            supports_vision: true,
+            supports_code: true,
+            cost_per_1k_input: Some(0.005),
+            cost_per_1k_output: Some(0.015),
+        };
+        let cost = estimate_cost(2000, 500, &model).unwrap();
+        assert!((cost - 0.0175).abs() < 1e-9);
+
+        model.cost_per_1k_input = None;
+        model.cost_per_1k_output = None;
+        assert_eq!(estimate_cost(2000, 500, &model), None);
+    }
+}
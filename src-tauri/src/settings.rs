@@ -0,0 +1,100 @@
+//! App-wide settings, kept in the `settings` table as JSON values
+//!
+//! Only the settings listed in `SETTINGS` can be saved; each has a type, checked when
+//! it is set, and a default used until it is.
+
+use serde_json::Value;
+
+/// Generate a document's summary in the background when it is opened
+pub const AUTO_SUMMARY: &str = "auto_summary";
+/// Reading speed, in words per minute, for new reading sessions
+pub const DEFAULT_WPM: &str = "default_wpm";
+/// Folder converted and exported files are written to; empty for next to the source
+pub const OUTPUT_DIR: &str = "output_dir";
+/// File name for converted files, see `OutputSettings::filename_template`
+pub const OUTPUT_FILENAME_TEMPLATE: &str = "output_filename_template";
+
+/// Last measured pace of the TTS voice. The app records it rather than the user
+/// choosing it, so it is not one of `SETTINGS` and survives a reset.
+pub const WPM_CALIBRATION: &str = "wpm_calibration";
+
+/// Type and default value of a setting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingKind {
+    Bool(bool),
+    /// A number between `min` and `max`, inclusive
+    Number { default: f64, min: f64, max: f64 },
+    Text(&'static str),
+}
+
+/// A setting the app knows about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Setting {
+    pub key: &'static str,
+    pub kind: SettingKind,
+}
+
+/// Every setting that can be saved
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: AUTO_SUMMARY,
+        kind: SettingKind::Bool(true),
+    },
+    Setting {
+        key: DEFAULT_WPM,
+        kind: SettingKind::Number {
+            default: 175.0,
+            min: 60.0,
+            max: 600.0,
+        },
+    },
+    Setting {
+        key: OUTPUT_DIR,
+        kind: SettingKind::Text(""),
+    },
+    Setting {
+        key: OUTPUT_FILENAME_TEMPLATE,
+        kind: SettingKind::Text("{name}.{ext}"),
+    },
+];
+
+impl Setting {
+    /// The setting saved under `key`, if it is a known one
+    pub fn find(key: &str) -> Option<&'static Setting> {
+        SETTINGS.iter().find(|setting| setting.key == key)
+    }
+
+    pub fn default_value(&self) -> Value {
+        match self.kind {
+            SettingKind::Bool(default) => default.into(),
+            SettingKind::Number { default, .. } => default.into(),
+            SettingKind::Text(default) => default.into(),
+        }
+    }
+
+    /// Check `value` has the setting's type and range, describing the problem if not
+    pub fn validate(&self, value: &Value) -> Result<(), String> {
+        match (self.kind, value) {
+            (SettingKind::Bool(_), Value::Bool(_)) | (SettingKind::Text(_), Value::String(_)) => {
+                Ok(())
+            }
+            (SettingKind::Number { min, max, .. }, Value::Number(n)) => {
+                let n = n.as_f64().unwrap_or(f64::NAN);
+                if (min..=max).contains(&n) {
+                    Ok(())
+                } else {
+                    Err(format!("{} is outside {} to {}", n, min, max))
+                }
+            }
+            (kind, _) => Err(format!(
+                "expected {}, got {}",
+                match kind {
+                    SettingKind::Bool(_) => "true or false",
+                    SettingKind::Number { .. } => "a number",
+                    SettingKind::Text(_) => "text",
+                },
+                value
+            )),
+        }
+    }
+}
@@ -1,9 +1,27 @@
 //! Storage and persistence module
 
-use crate::annotation::{Annotation, AnnotationUpdate};
-use crate::document::{Document, RecentDocument};
-use crate::error::{AppError, StorageError};
+use crate::annotation::comments::Comment;
+use crate::annotation::{
+    Annotation, AnnotationKind, AnnotationSearchHit, AnnotationUpdate, Bookmark, ColorCount,
+    HighlightColor, ParagraphRemap,
+};
+use crate::document::parser::ParagraphIdMap;
+use crate::document::{
+    Document, ImageDescription, OutputSettings, PageSource, PdfMetadataUpdate, ReadingMode,
+    ReadingSession, RecentDocument, TextSource,
+};
+use crate::error::{AppError, DocumentError, StorageError};
+use crate::llm::providers::{ChatMessage, LLMProvider, ProviderConfig};
+use crate::llm::audit::LlmAuditEntry;
+use crate::llm::Flashcard;
+use crate::settings::{
+    Setting, OUTPUT_DIR, OUTPUT_FILENAME_TEMPLATE, SETTINGS, WPM_CALIBRATION,
+};
+use crate::voice::{Pronunciation, WpmCalibration};
 use rusqlite::{params, Connection};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Mutex;
 use tauri::{AppHandle, Manager};
@@ -11,11 +29,11 @@ use uuid::Uuid;
 
 /// Database connection wrapper
 pub struct Database {
-    conn: Mutex<Connection>,
+    pub(crate) conn: Mutex<Connection>,
 }
 
 impl Database {
-    fn new(conn: Connection) -> Self {
+    pub(crate) fn new(conn: Connection) -> Self {
         Self {
             conn: Mutex::new(conn),
         }
@@ -35,15 +53,8 @@ fn get_database_path(app: &AppHandle) -> Result<PathBuf, AppError> {
     Ok(app_data.join("intellidoc.db"))
 }
 
-/// Initialize the database and run migrations
-pub async fn init_database(app: &AppHandle) -> Result<(), AppError> {
-    let db_path = get_database_path(app)?;
-    tracing::info!("Initializing database at {:?}", db_path);
-
-    let conn = Connection::open(&db_path)
-        .map_err(|e| StorageError::Database(e.to_string()))?;
-
-    // Run migrations
+/// Create tables and indexes if they don't exist yet
+pub(crate) fn run_migrations(conn: &Connection) -> Result<(), AppError> {
     conn.execute_batch(
         r#"
         -- Documents table
@@ -57,7 +68,8 @@ pub async fn init_database(app: &AppHandle) -> Result<(), AppError> {
             word_count INTEGER,
             created_at TEXT DEFAULT CURRENT_TIMESTAMP,
             last_opened TEXT,
-            metadata TEXT
+            metadata TEXT,
+            content_fingerprint TEXT
         );
 
         -- Annotations table
@@ -66,13 +78,15 @@ pub async fn init_database(app: &AppHandle) -> Result<(), AppError> {
             document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
             page_number INTEGER NOT NULL,
             paragraph_id TEXT,
-            start_offset INTEGER NOT NULL,
-            end_offset INTEGER NOT NULL,
+            kind TEXT NOT NULL DEFAULT 'highlight',
+            start_offset INTEGER,
+            end_offset INTEGER,
             selected_text TEXT,
             highlight_color TEXT,
             note TEXT,
             created_at TEXT DEFAULT CURRENT_TIMESTAMP,
-            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            paragraph_unmatched INTEGER NOT NULL DEFAULT 0
         );
 
         -- Chat messages table
@@ -97,15 +111,368 @@ pub async fn init_database(app: &AppHandle) -> Result<(), AppError> {
             created_at TEXT DEFAULT CURRENT_TIMESTAMP
         );
 
+        -- Flashcards table
+        CREATE TABLE IF NOT EXISTS flashcards (
+            id TEXT PRIMARY KEY,
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            question TEXT NOT NULL,
+            answer TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- Per-page text origin (native text layer vs OCR)
+        CREATE TABLE IF NOT EXISTS page_sources (
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            page_number INTEGER NOT NULL,
+            source TEXT NOT NULL,
+            PRIMARY KEY (document_id, page_number)
+        );
+
+        -- Text recognized by OCR for each page of scanned documents, reused when they are
+        -- opened again. Not a foreign key, as re-saving a document replaces its row.
+        CREATE TABLE IF NOT EXISTS ocr_pages (
+            document_id TEXT NOT NULL,
+            page_number INTEGER NOT NULL,
+            language TEXT NOT NULL,
+            text TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (document_id, page_number)
+        );
+
+        -- Descriptions of the images on each page, written by a vision model. Not a
+        -- foreign key, as re-saving a document replaces its row.
+        CREATE TABLE IF NOT EXISTS image_descriptions (
+            document_id TEXT NOT NULL,
+            page_number INTEGER NOT NULL,
+            image_index INTEGER NOT NULL,
+            description TEXT NOT NULL,
+            model TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (document_id, page_number, image_index)
+        );
+
+        -- Folders watched for new documents to import
+        CREATE TABLE IF NOT EXISTS watched_folders (
+            path TEXT PRIMARY KEY,
+            added_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- Short auto-generated summaries shown when a document is opened
+        CREATE TABLE IF NOT EXISTS summaries (
+            document_id TEXT PRIMARY KEY REFERENCES documents(id) ON DELETE CASCADE,
+            summary TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- Paragraph translations for bilingual reading
+        CREATE TABLE IF NOT EXISTS translations (
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            paragraph_id TEXT NOT NULL,
+            language TEXT NOT NULL,
+            translation TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (document_id, paragraph_id, language)
+        );
+
+        -- User-defined fields such as reading status, kept apart from parsed metadata
+        CREATE TABLE IF NOT EXISTS document_metadata_kv (
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (document_id, key)
+        );
+
+        -- Topic tags applied to documents
+        CREATE TABLE IF NOT EXISTS document_tags (
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            tag TEXT NOT NULL COLLATE NOCASE,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (document_id, tag)
+        );
+
+        -- Opt-in record of LLM requests and responses
+        CREATE TABLE IF NOT EXISTS llm_audit (
+            id TEXT PRIMARY KEY,
+            timestamp TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            params TEXT NOT NULL,
+            messages TEXT NOT NULL,
+            response TEXT,
+            error TEXT,
+            response_tokens INTEGER NOT NULL DEFAULT 0,
+            latency_ms INTEGER NOT NULL DEFAULT 0
+        );
+
+        -- Settings for each LLM provider used. The active provider is restored on startup.
+        CREATE TABLE IF NOT EXISTS llm_providers (
+            provider TEXT PRIMARY KEY,
+            model TEXT NOT NULL,
+            api_url TEXT,
+            active INTEGER NOT NULL DEFAULT 0
+        );
+
+        -- API keys entered for each LLM provider, so switching back keeps its key
+        CREATE TABLE IF NOT EXISTS provider_keys (
+            provider TEXT PRIMARY KEY,
+            api_key TEXT NOT NULL,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- Spoken forms for terms the TTS voice mispronounces
+        CREATE TABLE IF NOT EXISTS pronunciations (
+            term TEXT PRIMARY KEY COLLATE NOCASE,
+            replacement TEXT NOT NULL,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- Timed reading, spoken or on screen; ended_at stays NULL while in progress
+        CREATE TABLE IF NOT EXISTS reading_sessions (
+            id TEXT PRIMARY KEY,
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            mode TEXT NOT NULL,
+            started_at TEXT NOT NULL,
+            ended_at TEXT,
+            words_read INTEGER NOT NULL DEFAULT 0
+        );
+
+        -- Pages covered during each reading session
+        CREATE TABLE IF NOT EXISTS reading_session_pages (
+            session_id TEXT NOT NULL REFERENCES reading_sessions(id) ON DELETE CASCADE,
+            page_number INTEGER NOT NULL,
+            PRIMARY KEY (session_id, page_number)
+        );
+
+        -- Saved reading positions; bookmarking a position again replaces its label
+        CREATE TABLE IF NOT EXISTS bookmarks (
+            id TEXT PRIMARY KEY,
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            page_number INTEGER NOT NULL,
+            paragraph_id TEXT NOT NULL DEFAULT '',
+            word_index INTEGER NOT NULL DEFAULT 0,
+            label TEXT,
+            created_at TEXT NOT NULL,
+            UNIQUE (document_id, page_number, paragraph_id, word_index)
+        );
+
+        -- Threaded comments on paragraphs; replies point at the comment they answer
+        CREATE TABLE IF NOT EXISTS comments (
+            id TEXT PRIMARY KEY,
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            paragraph_id TEXT NOT NULL,
+            parent_id TEXT REFERENCES comments(id) ON DELETE CASCADE,
+            author TEXT NOT NULL,
+            body TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        -- App-wide settings as JSON values; settings never saved keep their defaults
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
         -- Indexes
         CREATE INDEX IF NOT EXISTS idx_annotations_document ON annotations(document_id);
         CREATE INDEX IF NOT EXISTS idx_chat_document ON chat_messages(document_id);
         CREATE INDEX IF NOT EXISTS idx_code_document ON code_snippets(document_id);
+        CREATE INDEX IF NOT EXISTS idx_flashcards_document ON flashcards(document_id);
         CREATE INDEX IF NOT EXISTS idx_documents_last_opened ON documents(last_opened DESC);
+        CREATE INDEX IF NOT EXISTS idx_reading_sessions_document ON reading_sessions(document_id);
+        CREATE INDEX IF NOT EXISTS idx_comments_paragraph ON comments(document_id, paragraph_id);
+        "#,
+    )
+    .map_err(|e| StorageError::Migration(e.to_string()))?;
+
+    migrate_annotation_kinds(conn)?;
+    migrate_annotation_unmatched_flag(conn)?;
+    migrate_document_fingerprints(conn)?;
+    migrate_annotation_search(conn)?;
+    migrate_llm_provider_keys(conn)?;
+
+    Ok(())
+}
+
+/// Add the `kind` column and make range fields nullable on pre-existing annotation tables
+fn migrate_annotation_kinds(conn: &Connection) -> Result<(), AppError> {
+    let has_kind = conn
+        .prepare("SELECT 1 FROM pragma_table_info('annotations') WHERE name = 'kind'")
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| StorageError::Migration(e.to_string()))?;
+    if has_kind {
+        return Ok(());
+    }
+
+    tracing::info!("Migrating annotations table to support page notes");
+
+    // SQLite cannot relax NOT NULL in place, so rebuild the table
+    conn.execute_batch(
+        r#"
+        BEGIN;
+        CREATE TABLE annotations_new (
+            id TEXT PRIMARY KEY,
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            page_number INTEGER NOT NULL,
+            paragraph_id TEXT,
+            kind TEXT NOT NULL DEFAULT 'highlight',
+            start_offset INTEGER,
+            end_offset INTEGER,
+            selected_text TEXT,
+            highlight_color TEXT,
+            note TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+        INSERT INTO annotations_new
+            (id, document_id, page_number, paragraph_id, kind, start_offset, end_offset,
+             selected_text, highlight_color, note, created_at, updated_at)
+        SELECT id, document_id, page_number, paragraph_id,
+               CASE WHEN highlight_color IS NULL AND note IS NOT NULL
+                    THEN 'range_note' ELSE 'highlight' END,
+               start_offset, end_offset, selected_text, highlight_color, note,
+               created_at, updated_at
+        FROM annotations;
+        DROP TABLE annotations;
+        ALTER TABLE annotations_new RENAME TO annotations;
+        CREATE INDEX IF NOT EXISTS idx_annotations_document ON annotations(document_id);
+        COMMIT;
+        "#,
+    )
+    .map_err(|e| StorageError::Migration(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Add the unmatched-paragraph flag to annotations tables created before it existed
+fn migrate_annotation_unmatched_flag(conn: &Connection) -> Result<(), AppError> {
+    let has_flag = conn
+        .prepare(
+            "SELECT 1 FROM pragma_table_info('annotations') WHERE name = 'paragraph_unmatched'",
+        )
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| StorageError::Migration(e.to_string()))?;
+    if !has_flag {
+        conn.execute(
+            "ALTER TABLE annotations ADD COLUMN paragraph_unmatched INTEGER NOT NULL DEFAULT 0",
+            [],
+        )
+        .map_err(|e| StorageError::Migration(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Add the content fingerprint column to documents tables created before it existed
+fn migrate_document_fingerprints(conn: &Connection) -> Result<(), AppError> {
+    let has_fingerprint = conn
+        .prepare("SELECT 1 FROM pragma_table_info('documents') WHERE name = 'content_fingerprint'")
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| StorageError::Migration(e.to_string()))?;
+    if !has_fingerprint {
+        tracing::info!("Adding content fingerprints to documents table");
+        conn.execute("ALTER TABLE documents ADD COLUMN content_fingerprint TEXT", [])
+            .map_err(|e| StorageError::Migration(e.to_string()))?;
+    }
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_documents_fingerprint ON documents(content_fingerprint)",
+        [],
+    )
+    .map_err(|e| StorageError::Migration(e.to_string()))?;
+
+    // Rows added before the column existed are filled in by
+    // `commands::document::backfill_fingerprints` once storage is up, since computing a
+    // fingerprint means parsing the file
+    Ok(())
+}
+
+/// Move API keys saved in `llm_providers`, where they were kept before `provider_keys`
+/// existed, into `provider_keys` and drop the old column. A key already saved in
+/// `provider_keys` is newer and wins.
+fn migrate_llm_provider_keys(conn: &Connection) -> Result<(), AppError> {
+    let has_api_key = conn
+        .prepare("SELECT 1 FROM pragma_table_info('llm_providers') WHERE name = 'api_key'")
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| StorageError::Migration(e.to_string()))?;
+    if !has_api_key {
+        return Ok(());
+    }
+
+    tracing::info!("Moving saved LLM API keys to the provider_keys table");
+    conn.execute_batch(
+        r#"
+        BEGIN;
+        INSERT INTO provider_keys (provider, api_key)
+        SELECT provider, api_key FROM llm_providers
+        WHERE api_key IS NOT NULL AND api_key != ''
+        ON CONFLICT(provider) DO NOTHING;
+        ALTER TABLE llm_providers DROP COLUMN api_key;
+        COMMIT;
+        "#,
+    )
+    .map_err(|e| StorageError::Migration(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Create the full-text index over annotation notes and selected text, kept in sync by
+/// triggers. Runs after `migrate_annotation_kinds`, which rebuilds the annotations table
+/// and would drop the triggers.
+fn migrate_annotation_search(conn: &Connection) -> Result<(), AppError> {
+    let has_index = conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE name = 'annotations_fts'")
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| StorageError::Migration(e.to_string()))?;
+
+    conn.execute_batch(
+        r#"
+        CREATE VIRTUAL TABLE IF NOT EXISTS annotations_fts USING fts5(
+            selected_text, note,
+            content = 'annotations',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS annotations_fts_insert AFTER INSERT ON annotations BEGIN
+            INSERT INTO annotations_fts (rowid, selected_text, note)
+            VALUES (new.rowid, new.selected_text, new.note);
+        END;
+        CREATE TRIGGER IF NOT EXISTS annotations_fts_delete AFTER DELETE ON annotations BEGIN
+            INSERT INTO annotations_fts (annotations_fts, rowid, selected_text, note)
+            VALUES ('delete', old.rowid, old.selected_text, old.note);
+        END;
+        CREATE TRIGGER IF NOT EXISTS annotations_fts_update
+        AFTER UPDATE OF selected_text, note ON annotations BEGIN
+            INSERT INTO annotations_fts (annotations_fts, rowid, selected_text, note)
+            VALUES ('delete', old.rowid, old.selected_text, old.note);
+            INSERT INTO annotations_fts (rowid, selected_text, note)
+            VALUES (new.rowid, new.selected_text, new.note);
+        END;
         "#,
     )
     .map_err(|e| StorageError::Migration(e.to_string()))?;
 
+    if !has_index {
+        tracing::info!("Indexing existing annotations for search");
+        conn.execute("INSERT INTO annotations_fts (annotations_fts) VALUES ('rebuild')", [])
+            .map_err(|e| StorageError::Migration(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Initialize the database and run migrations
+pub async fn init_database(app: &AppHandle) -> Result<(), AppError> {
+    let db_path = get_database_path(app)?;
+    tracing::info!("Initializing database at {:?}", db_path);
+
+    let conn = Connection::open(&db_path)
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    // Run migrations
+    run_migrations(&conn)?;
+
     // Store database in app state
     app.manage(Database::new(conn));
 
@@ -117,7 +484,10 @@ pub async fn init_database(app: &AppHandle) -> Result<(), AppError> {
 pub async fn add_recent_document(app: &AppHandle, doc: &Document) -> Result<(), AppError> {
     let db = app.state::<Database>();
     let conn = db.conn.lock().unwrap();
+    upsert_document(&conn, doc)
+}
 
+pub(crate) fn upsert_document(conn: &Connection, doc: &Document) -> Result<(), AppError> {
     let authors_json = serde_json::to_string(&doc.authors)
         .map_err(|e| StorageError::Serialization(e.to_string()))?;
     let metadata_json = serde_json::to_string(&doc.metadata)
@@ -127,8 +497,9 @@ pub async fn add_recent_document(app: &AppHandle, doc: &Document) -> Result<(),
     conn.execute(
         r#"
         INSERT OR REPLACE INTO documents 
-        (id, file_path, title, authors, category, page_count, word_count, last_opened, metadata)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, datetime('now'), ?8)
+        (id, file_path, title, authors, category, page_count, word_count, last_opened, metadata,
+         content_fingerprint)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, datetime('now'), ?8, ?9)
         "#,
         params![
             doc.id,
@@ -139,6 +510,7 @@ pub async fn add_recent_document(app: &AppHandle, doc: &Document) -> Result<(),
             doc.metadata.page_count,
             doc.metadata.word_count,
             metadata_json,
+            crate::document::content_fingerprint(doc),
         ],
     )
     .map_err(|e| StorageError::Database(e.to_string()))?;
@@ -146,6 +518,61 @@ pub async fn add_recent_document(app: &AppHandle, doc: &Document) -> Result<(),
     Ok(())
 }
 
+/// Whether a document with this id has been registered
+pub(crate) fn document_exists(conn: &Connection, document_id: &str) -> Result<bool, AppError> {
+    conn.prepare("SELECT 1 FROM documents WHERE id = ?1")
+        .and_then(|mut stmt| stmt.exists([document_id]))
+        .map_err(|e| StorageError::Database(e.to_string()).into())
+}
+
+/// Id of a registered document with the same extracted text, if any
+pub(crate) fn find_document_by_fingerprint(
+    conn: &Connection,
+    fingerprint: &str,
+) -> Result<Option<String>, AppError> {
+    match conn.query_row(
+        "SELECT id FROM documents WHERE content_fingerprint = ?1 LIMIT 1",
+        [fingerprint],
+        |row| row.get(0),
+    ) {
+        Ok(id) => Ok(Some(id)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(StorageError::Database(e.to_string()).into()),
+    }
+}
+
+/// Ids and paths of documents registered before content fingerprints were stored
+pub(crate) fn documents_without_fingerprint(
+    conn: &Connection,
+) -> Result<Vec<(String, String)>, AppError> {
+    let mut stmt = conn
+        .prepare("SELECT id, file_path FROM documents WHERE content_fingerprint IS NULL")
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let documents = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(documents)
+}
+
+/// Record the content fingerprint of a registered document
+pub(crate) fn set_content_fingerprint(
+    conn: &Connection,
+    document_id: &str,
+    fingerprint: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE documents SET content_fingerprint = ?2 WHERE id = ?1",
+        params![document_id, fingerprint],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
 /// Get recent documents
 pub async fn get_recent_documents(
     app: &AppHandle,
@@ -198,27 +625,37 @@ pub async fn get_recent_documents(
 pub async fn save_annotation(app: &AppHandle, annotation: &Annotation) -> Result<(), AppError> {
     let db = app.state::<Database>();
     let conn = db.conn.lock().unwrap();
+    insert_annotation(&conn, annotation)
+}
 
+pub(crate) fn insert_annotation(
+    conn: &Connection,
+    annotation: &Annotation,
+) -> Result<(), AppError> {
     let color = annotation
         .highlight_color
         .as_ref()
-        .map(|c| format!("{:?}", c).to_lowercase());
+        .map(|c| c.key());
+    // Page notes have no selection
+    let selected_text = (annotation.kind != AnnotationKind::PageNote)
+        .then_some(&annotation.selected_text);
 
     conn.execute(
         r#"
         INSERT INTO annotations 
-        (id, document_id, page_number, paragraph_id, start_offset, end_offset, 
+        (id, document_id, page_number, paragraph_id, kind, start_offset, end_offset,
          selected_text, highlight_color, note, created_at, updated_at)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
         "#,
         params![
             annotation.id.to_string(),
             annotation.document_id,
             annotation.page_number,
             annotation.paragraph_id,
+            annotation.kind.as_str(),
             annotation.start_offset,
             annotation.end_offset,
-            annotation.selected_text,
+            selected_text,
             color,
             annotation.note,
             annotation.created_at.to_rfc3339(),
@@ -237,12 +674,20 @@ pub async fn get_annotations(
 ) -> Result<Vec<Annotation>, AppError> {
     let db = app.state::<Database>();
     let conn = db.conn.lock().unwrap();
+    list_annotations(&conn, document_id)
+}
 
+/// Annotations for a document in page order, page notes first on each page
+pub(crate) fn list_annotations(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Vec<Annotation>, AppError> {
     let mut stmt = conn
         .prepare(
             r#"
             SELECT id, document_id, page_number, paragraph_id, start_offset, end_offset,
-                   selected_text, highlight_color, note, created_at, updated_at
+                   selected_text, highlight_color, note, created_at, updated_at, kind,
+                   paragraph_unmatched
             FROM annotations
             WHERE document_id = ?1
             ORDER BY page_number, start_offset
@@ -251,35 +696,7 @@ pub async fn get_annotations(
         .map_err(|e| StorageError::Database(e.to_string()))?;
 
     let annotations = stmt
-        .query_map([document_id], |row| {
-            let color_str: Option<String> = row.get(7)?;
-            let color = color_str.and_then(|c| match c.as_str() {
-                "yellow" => Some(crate::annotation::HighlightColor::Yellow),
-                "green" => Some(crate::annotation::HighlightColor::Green),
-                "blue" => Some(crate::annotation::HighlightColor::Blue),
-                "purple" => Some(crate::annotation::HighlightColor::Purple),
-                "red" => Some(crate::annotation::HighlightColor::Red),
-                _ => None,
-            });
-
-            Ok(Annotation {
-                id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap_or_default(),
-                document_id: row.get(1)?,
-                page_number: row.get(2)?,
-                paragraph_id: row.get(3)?,
-                start_offset: row.get(4)?,
-                end_offset: row.get(5)?,
-                selected_text: row.get(6)?,
-                highlight_color: color,
-                note: row.get(8)?,
-                created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(9)?)
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-                    .unwrap_or_else(|_| chrono::Utc::now()),
-                updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(10)?)
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-                    .unwrap_or_else(|_| chrono::Utc::now()),
-            })
-        })
+        .query_map([document_id], annotation_from_row)
         .map_err(|e| StorageError::Database(e.to_string()))?
         .filter_map(|r| r.ok())
         .collect();
@@ -309,7 +726,7 @@ pub async fn update_annotation(
     let color = annotation
         .highlight_color
         .as_ref()
-        .map(|c| format!("{:?}", c).to_lowercase());
+        .map(|c| c.key());
 
     conn.execute(
         r#"
@@ -340,74 +757,312 @@ pub async fn delete_annotation(app: &AppHandle, id: Uuid) -> Result<(), AppError
     Ok(())
 }
 
-/// Save a chat message
-pub async fn save_chat_message(
-    app: &AppHandle,
+/// Annotations in a document highlighted with the given color
+pub(crate) fn get_annotations_by_color(
+    conn: &Connection,
     document_id: &str,
-    role: &str,
-    content: &str,
-    context_page: Option<u32>,
-) -> Result<(), AppError> {
-    let db = app.state::<Database>();
-    let conn = db.conn.lock().unwrap();
-
-    let id = Uuid::new_v4().to_string();
+    color: &HighlightColor,
+) -> Result<Vec<Annotation>, AppError> {
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT id, document_id, page_number, paragraph_id, start_offset, end_offset,
+                   selected_text, highlight_color, note, created_at, updated_at, kind,
+                   paragraph_unmatched
+            FROM annotations
+            WHERE document_id = ?1 AND highlight_color = ?2
+            ORDER BY page_number, start_offset
+            "#,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
 
-    conn.execute(
-        r#"
-        INSERT INTO chat_messages (id, document_id, role, content, context_page)
-        VALUES (?1, ?2, ?3, ?4, ?5)
-        "#,
-        params![id, document_id, role, content, context_page],
-    )
-    .map_err(|e| StorageError::Database(e.to_string()))?;
+    let annotations = stmt
+        .query_map(params![document_id, color.key()], annotation_from_row)
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
 
-    Ok(())
+    Ok(annotations)
 }
 
-/// Get chat messages for a document
-pub async fn get_chat_messages(
-    app: &AppHandle,
+/// Number of highlighted annotations per color in a document, most used first
+pub(crate) fn get_annotation_color_counts(
+    conn: &Connection,
     document_id: &str,
-    limit: usize,
-) -> Result<Vec<(String, String, String, String)>, AppError> {
-    let db = app.state::<Database>();
-    let conn = db.conn.lock().unwrap();
-
+) -> Result<Vec<ColorCount>, AppError> {
     let mut stmt = conn
         .prepare(
             r#"
-            SELECT id, role, content, timestamp
-            FROM chat_messages
-            WHERE document_id = ?1
-            ORDER BY timestamp ASC
-            LIMIT ?2
+            SELECT highlight_color, COUNT(*) FROM annotations
+            WHERE document_id = ?1 AND highlight_color IS NOT NULL
+            GROUP BY highlight_color
+            ORDER BY COUNT(*) DESC, highlight_color
             "#,
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
 
-    let messages = stmt
-        .query_map(params![document_id, limit], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-                row.get::<_, String>(3)?,
-            ))
+    let counts = stmt
+        .query_map([document_id], |row| {
+            let key: String = row.get(0)?;
+            Ok((key, row.get(1)?))
         })
         .map_err(|e| StorageError::Database(e.to_string()))?
         .filter_map(|r| r.ok())
+        .filter_map(|(key, count)| {
+            HighlightColor::from_key(&key).map(|color| ColorCount { color, count })
+        })
         .collect();
 
-    Ok(messages)
+    Ok(counts)
 }
 
-/// Save a code snippet
-pub async fn save_code_snippet(
-    app: &AppHandle,
-    document_id: &str,
-    language: &str,
-    framework: Option<&str>,
+/// Annotations in any document whose note or selected text matches every word of the
+/// query, best matches first. The last word also matches as a prefix.
+pub(crate) fn search_annotations(
+    conn: &Connection,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<AnnotationSearchHit>, AppError> {
+    // Quote each word so characters with meaning in FTS5 syntax are searched literally
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    let Some(last) = words.last() else {
+        return Ok(Vec::new());
+    };
+    let fts_query = format!("{} {}*", words[..words.len() - 1].join(" "), last);
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT a.id, a.document_id, a.page_number, a.paragraph_id, a.start_offset,
+                   a.end_offset, a.selected_text, a.highlight_color, a.note, a.created_at,
+                   a.updated_at, a.kind, a.paragraph_unmatched,
+                   COALESCE(NULLIF(d.title, ''), d.file_path),
+                   snippet(annotations_fts, -1, '', '', '…', 16)
+            FROM annotations_fts
+            JOIN annotations a ON a.rowid = annotations_fts.rowid
+            JOIN documents d ON d.id = a.document_id
+            WHERE annotations_fts MATCH ?1
+            ORDER BY rank
+            LIMIT ?2
+            "#,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let hits = stmt
+        .query_map(params![fts_query.trim_start(), limit], |row| {
+            Ok(AnnotationSearchHit {
+                annotation: annotation_from_row(row)?,
+                document_title: row.get(13)?,
+                snippet: row.get(14)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(hits)
+}
+
+/// Rewrite annotation paragraph ids from legacy positional ids to stable ids
+pub async fn migrate_annotation_paragraph_ids(
+    app: &AppHandle,
+    document_id: &str,
+    id_map: &ParagraphIdMap,
+) -> Result<ParagraphRemap, AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    remap_paragraph_ids(&conn, document_id, id_map)
+}
+
+/// Point a document's annotations at regenerated paragraph ids, in one transaction.
+///
+/// `old_to_new` must map to every current paragraph id. Annotations on an old id are
+/// moved to its new id; those whose id is neither old nor current are flagged as
+/// unmatched, and keep their id until a later mapping resolves them. Comments on an old
+/// id are moved the same way.
+pub(crate) fn remap_paragraph_ids(
+    conn: &Connection,
+    document_id: &str,
+    old_to_new: &ParagraphIdMap,
+) -> Result<ParagraphRemap, AppError> {
+    let db_error = |e: rusqlite::Error| StorageError::Database(e.to_string());
+    let tx = conn.unchecked_transaction().map_err(db_error)?;
+
+    let annotations: Vec<(String, String, bool)> = tx
+        .prepare(
+            "SELECT id, paragraph_id, paragraph_unmatched FROM annotations
+             WHERE document_id = ?1 AND paragraph_id IS NOT NULL",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([document_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect()
+        })
+        .map_err(db_error)?;
+
+    let current_ids: HashSet<&String> = old_to_new.values().collect();
+    let mut summary = ParagraphRemap::default();
+    {
+        let mut set_paragraph = tx
+            .prepare(
+                "UPDATE annotations SET paragraph_id = ?1, paragraph_unmatched = 0 WHERE id = ?2",
+            )
+            .map_err(db_error)?;
+        let mut set_unmatched = tx
+            .prepare("UPDATE annotations SET paragraph_unmatched = ?1 WHERE id = ?2")
+            .map_err(db_error)?;
+
+        for (id, paragraph_id, unmatched) in &annotations {
+            if let Some(new_id) = old_to_new.get(paragraph_id).filter(|new| *new != paragraph_id) {
+                set_paragraph.execute(params![new_id, id]).map_err(db_error)?;
+                summary.remapped += 1;
+            } else if !current_ids.contains(paragraph_id) {
+                set_unmatched.execute(params![true, id]).map_err(db_error)?;
+                summary.unmatched += 1;
+            } else if *unmatched {
+                // Its paragraph exists again, e.g. after an edit was undone
+                set_unmatched.execute(params![false, id]).map_err(db_error)?;
+            }
+        }
+    }
+
+    let comments: Vec<(String, String)> = tx
+        .prepare("SELECT id, paragraph_id FROM comments WHERE document_id = ?1")
+        .and_then(|mut stmt| {
+            stmt.query_map([document_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+        .map_err(db_error)?;
+    {
+        let mut set_paragraph = tx
+            .prepare("UPDATE comments SET paragraph_id = ?1 WHERE id = ?2")
+            .map_err(db_error)?;
+        for (id, paragraph_id) in &comments {
+            if let Some(new_id) = old_to_new.get(paragraph_id).filter(|new| *new != paragraph_id) {
+                set_paragraph.execute(params![new_id, id]).map_err(db_error)?;
+            }
+        }
+    }
+
+    tx.commit().map_err(db_error)?;
+    Ok(summary)
+}
+
+/// Save a chat message
+pub async fn save_chat_message(
+    app: &AppHandle,
+    document_id: &str,
+    role: &str,
+    content: &str,
+    context_page: Option<u32>,
+) -> Result<(), AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    insert_chat_message(&conn, document_id, role, content, context_page)
+}
+
+/// Insert a chat message row
+pub(crate) fn insert_chat_message(
+    conn: &Connection,
+    document_id: &str,
+    role: &str,
+    content: &str,
+    context_page: Option<u32>,
+) -> Result<(), AppError> {
+    let id = Uuid::new_v4().to_string();
+
+    conn.execute(
+        r#"
+        INSERT INTO chat_messages (id, document_id, role, content, context_page)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+        params![id, document_id, role, content, context_page],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Get the most recent chat turns for a document, oldest first
+pub(crate) fn recent_chat_messages(
+    conn: &Connection,
+    document_id: &str,
+    limit: usize,
+) -> Result<Vec<ChatMessage>, AppError> {
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT role, content FROM (
+                SELECT rowid, role, content, timestamp
+                FROM chat_messages
+                WHERE document_id = ?1
+                ORDER BY timestamp DESC, rowid DESC
+                LIMIT ?2
+            )
+            ORDER BY timestamp ASC, rowid ASC
+            "#,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let messages = stmt
+        .query_map(params![document_id, limit], |row| {
+            Ok(ChatMessage {
+                role: row.get(0)?,
+                content: row.get(1)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(messages)
+}
+
+/// Get chat messages for a document
+pub async fn get_chat_messages(
+    app: &AppHandle,
+    document_id: &str,
+    limit: usize,
+) -> Result<Vec<(String, String, String, String)>, AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT id, role, content, timestamp
+            FROM chat_messages
+            WHERE document_id = ?1
+            ORDER BY timestamp ASC
+            LIMIT ?2
+            "#,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let messages = stmt
+        .query_map(params![document_id, limit], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(messages)
+}
+
+/// Save a code snippet
+pub async fn save_code_snippet(
+    app: &AppHandle,
+    document_id: &str,
+    language: &str,
+    framework: Option<&str>,
     code: &str,
     description: &str,
     section_reference: Option<&str>,
@@ -429,52 +1084,1979 @@ pub async fn save_code_snippet(
     Ok(())
 }
 
-/// Helper to get annotation by ID
-fn get_annotations_by_id(conn: &Connection, id: Uuid) -> Result<Vec<Annotation>, AppError> {
+/// Look up the file path of a stored document
+pub(crate) fn get_document_path(conn: &Connection, document_id: &str) -> Result<String, AppError> {
+    conn.query_row(
+        "SELECT file_path FROM documents WHERE id = ?1",
+        [document_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => DocumentError::InvalidId.into(),
+        e => StorageError::Database(e.to_string()).into(),
+    })
+}
+
+/// Look up the title of a stored document, if it has one
+pub(crate) fn get_document_title(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Option<String>, AppError> {
+    conn.query_row(
+        "SELECT title FROM documents WHERE id = ?1",
+        [document_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => DocumentError::InvalidId.into(),
+        e => StorageError::Database(e.to_string()).into(),
+    })
+}
+
+/// Apply edited title/authors/subject/keywords to a stored document
+pub(crate) fn update_document_metadata(
+    conn: &Connection,
+    document_id: &str,
+    update: &PdfMetadataUpdate,
+) -> Result<(), AppError> {
+    let (title, authors, metadata): (Option<String>, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT title, authors, metadata FROM documents WHERE id = ?1",
+            [document_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::from(DocumentError::InvalidId),
+            e => StorageError::Database(e.to_string()).into(),
+        })?;
+
+    let title = update.title.clone().or(title);
+    let authors = match &update.authors {
+        Some(authors) => Some(
+            serde_json::to_string(authors)
+                .map_err(|e| StorageError::Serialization(e.to_string()))?,
+        ),
+        None => authors,
+    };
+    let mut metadata: serde_json::Value = metadata
+        .and_then(|m| serde_json::from_str(&m).ok())
+        .unwrap_or_else(|| serde_json::json!({}));
+    if let Some(subject) = &update.subject {
+        metadata["subject"] = subject.clone().into();
+    }
+    if let Some(keywords) = &update.keywords {
+        metadata["keywords"] = keywords.clone().into();
+    }
+
+    conn.execute(
+        "UPDATE documents SET title = ?2, authors = ?3, metadata = ?4 WHERE id = ?1",
+        params![document_id, title, authors, metadata.to_string()],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Tables whose rows belong to a document through their `document_id` column
+const DOCUMENT_TABLES: &[&str] = &[
+    "annotations",
+    "chat_messages",
+    "code_snippets",
+    "flashcards",
+    "page_sources",
+    "ocr_pages",
+    "image_descriptions",
+    "summaries",
+    "translations",
+    "document_metadata_kv",
+    "document_tags",
+    "reading_sessions",
+    "bookmarks",
+    "comments",
+];
+
+/// Move a document and everything attached to it to `new_id`, in one transaction. Used
+/// when rewriting the file changes its hash, which is its id.
+pub(crate) fn rekey_document(
+    conn: &Connection,
+    old_id: &str,
+    new_id: &str,
+) -> Result<(), AppError> {
+    let db_error = |e: rusqlite::Error| StorageError::Database(e.to_string());
+    let tx = conn.unchecked_transaction().map_err(db_error)?;
+    // Rows briefly point at an id that doesn't exist yet; check them at commit instead
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON").map_err(db_error)?;
+
+    tx.execute("UPDATE documents SET id = ?2 WHERE id = ?1", params![old_id, new_id])
+        .map_err(db_error)?;
+    for table in DOCUMENT_TABLES {
+        tx.execute(
+            &format!("UPDATE {} SET document_id = ?2 WHERE document_id = ?1", table),
+            params![old_id, new_id],
+        )
+        .map_err(db_error)?;
+    }
+
+    tx.commit().map_err(db_error)?;
+    Ok(())
+}
+
+/// Delete a document and everything attached to it, in one transaction. Rows are
+/// deleted explicitly rather than through `ON DELETE CASCADE`, which only runs when
+/// foreign keys are enforced. Returns whether the document existed.
+pub(crate) fn delete_document(conn: &Connection, document_id: &str) -> Result<bool, AppError> {
+    let db_error = |e: rusqlite::Error| StorageError::Database(e.to_string());
+    let tx = conn.unchecked_transaction().map_err(db_error)?;
+
+    tx.execute(
+        "DELETE FROM reading_session_pages WHERE session_id IN
+         (SELECT id FROM reading_sessions WHERE document_id = ?1)",
+        [document_id],
+    )
+    .map_err(db_error)?;
+    for table in DOCUMENT_TABLES {
+        tx.execute(&format!("DELETE FROM {} WHERE document_id = ?1", table), [document_id])
+            .map_err(db_error)?;
+    }
+    let deleted = tx
+        .execute("DELETE FROM documents WHERE id = ?1", [document_id])
+        .map_err(db_error)?;
+
+    tx.commit().map_err(db_error)?;
+    Ok(deleted > 0)
+}
+
+/// Append an entry to the LLM audit log
+pub(crate) fn insert_llm_audit(conn: &Connection, entry: &LlmAuditEntry) -> Result<(), AppError> {
+    let params_json = serde_json::to_string(&entry.params)
+        .map_err(|e| StorageError::Serialization(e.to_string()))?;
+    let messages_json = serde_json::to_string(&entry.messages)
+        .map_err(|e| StorageError::Serialization(e.to_string()))?;
+
+    conn.execute(
+        r#"
+        INSERT INTO llm_audit
+        (id, timestamp, provider, model, params, messages, response, error, response_tokens,
+         latency_ms)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        "#,
+        params![
+            entry.id,
+            entry.timestamp,
+            entry.provider,
+            entry.model,
+            params_json,
+            messages_json,
+            entry.response,
+            entry.error,
+            entry.response_tokens as i64,
+            entry.latency_ms as i64,
+        ],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Most recent LLM audit entries, newest first
+pub(crate) fn get_llm_audit(
+    conn: &Connection,
+    limit: usize,
+) -> Result<Vec<LlmAuditEntry>, AppError> {
     let mut stmt = conn
         .prepare(
             r#"
-            SELECT id, document_id, page_number, paragraph_id, start_offset, end_offset,
-                   selected_text, highlight_color, note, created_at, updated_at
-            FROM annotations
-            WHERE id = ?1
+            SELECT id, timestamp, provider, model, params, messages, response, error,
+                   response_tokens, latency_ms
+            FROM llm_audit
+            ORDER BY timestamp DESC, rowid DESC
+            LIMIT ?1
             "#,
         )
         .map_err(|e| StorageError::Database(e.to_string()))?;
 
-    let annotations = stmt
-        .query_map([id.to_string()], |row| {
-            let color_str: Option<String> = row.get(7)?;
-            let color = color_str.and_then(|c| match c.as_str() {
-                "yellow" => Some(crate::annotation::HighlightColor::Yellow),
-                "green" => Some(crate::annotation::HighlightColor::Green),
-                "blue" => Some(crate::annotation::HighlightColor::Blue),
-                "purple" => Some(crate::annotation::HighlightColor::Purple),
-                "red" => Some(crate::annotation::HighlightColor::Red),
-                _ => None,
-            });
-
-            Ok(Annotation {
-                id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap_or_default(),
-                document_id: row.get(1)?,
-                page_number: row.get(2)?,
-                paragraph_id: row.get(3)?,
-                start_offset: row.get(4)?,
-                end_offset: row.get(5)?,
-                selected_text: row.get(6)?,
-                highlight_color: color,
-                note: row.get(8)?,
-                created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(9)?)
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-                    .unwrap_or_else(|_| chrono::Utc::now()),
-                updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(10)?)
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-                    .unwrap_or_else(|_| chrono::Utc::now()),
+    let entries = stmt
+        .query_map([limit], |row| {
+            let params: String = row.get(4)?;
+            let messages: String = row.get(5)?;
+            Ok(LlmAuditEntry {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                provider: row.get(2)?,
+                model: row.get(3)?,
+                params: serde_json::from_str(&params).unwrap_or_default(),
+                messages: serde_json::from_str(&messages).unwrap_or_default(),
+                response: row.get(6)?,
+                error: row.get(7)?,
+                response_tokens: row.get::<_, i64>(8)? as usize,
+                latency_ms: row.get::<_, i64>(9)? as u64,
             })
         })
         .map_err(|e| StorageError::Database(e.to_string()))?
         .filter_map(|r| r.ok())
         .collect();
 
-    Ok(annotations)
+    Ok(entries)
+}
+
+/// Delete every LLM audit entry, returning how many were removed
+pub(crate) fn clear_llm_audit(conn: &Connection) -> Result<usize, AppError> {
+    conn.execute("DELETE FROM llm_audit", [])
+        .map_err(|e| StorageError::Database(e.to_string()).into())
+}
+
+/// Add a pronunciation, replacing any existing entry for the same term in any case
+pub(crate) fn save_pronunciation(
+    conn: &Connection,
+    pronunciation: &Pronunciation,
+) -> Result<(), AppError> {
+    conn.execute(
+        r#"
+        INSERT INTO pronunciations (term, replacement) VALUES (?1, ?2)
+        ON CONFLICT(term) DO UPDATE SET term = excluded.term, replacement = excluded.replacement
+        "#,
+        params![pronunciation.term, pronunciation.replacement],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Get the pronunciation lexicon, ordered by term
+pub(crate) fn get_pronunciations(conn: &Connection) -> Result<Vec<Pronunciation>, AppError> {
+    let mut stmt = conn
+        .prepare("SELECT term, replacement FROM pronunciations ORDER BY term")
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let pronunciations = stmt
+        .query_map([], |row| {
+            Ok(Pronunciation {
+                term: row.get(0)?,
+                replacement: row.get(1)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(pronunciations)
+}
+
+/// Name an LLM provider is stored under, matching its serialized form
+fn llm_provider_key(provider: &LLMProvider) -> String {
+    serde_json::to_value(provider)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn llm_provider_from_row(row: &rusqlite::Row) -> rusqlite::Result<Option<ProviderConfig>> {
+    let provider: String = row.get(0)?;
+    let Ok(provider) = serde_json::from_value(serde_json::Value::String(provider)) else {
+        return Ok(None);
+    };
+    Ok(Some(ProviderConfig {
+        provider,
+        model: row.get(1)?,
+        api_url: row.get(2)?,
+        ..Default::default()
+    }))
+}
+
+/// Save an LLM provider's model and URL and make it the active provider. Other
+/// providers keep their saved settings. API keys are saved with `save_provider_key`.
+pub(crate) fn save_active_llm_provider(
+    conn: &Connection,
+    config: &ProviderConfig,
+) -> Result<(), AppError> {
+    let db_error = |e: rusqlite::Error| StorageError::Database(e.to_string());
+    let tx = conn.unchecked_transaction().map_err(db_error)?;
+
+    tx.execute("UPDATE llm_providers SET active = 0", []).map_err(db_error)?;
+    tx.execute(
+        r#"
+        INSERT INTO llm_providers (provider, model, api_url, active)
+        VALUES (?1, ?2, ?3, 1)
+        ON CONFLICT(provider) DO UPDATE SET
+            model = excluded.model,
+            api_url = excluded.api_url,
+            active = 1
+        "#,
+        params![llm_provider_key(&config.provider), config.model, config.api_url],
+    )
+    .map_err(db_error)?;
+
+    tx.commit().map_err(db_error)?;
+    Ok(())
+}
+
+/// Saved settings for one LLM provider, if it has been used
+pub(crate) fn get_llm_provider(
+    conn: &Connection,
+    provider: &LLMProvider,
+) -> Result<Option<ProviderConfig>, AppError> {
+    match conn.query_row(
+        "SELECT provider, model, api_url FROM llm_providers WHERE provider = ?1",
+        [llm_provider_key(provider)],
+        llm_provider_from_row,
+    ) {
+        Ok(config) => Ok(config),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(StorageError::Database(e.to_string()).into()),
+    }
+}
+
+/// Saved settings of the provider made active last, if any
+pub(crate) fn get_active_llm_provider(
+    conn: &Connection,
+) -> Result<Option<ProviderConfig>, AppError> {
+    match conn.query_row(
+        "SELECT provider, model, api_url FROM llm_providers WHERE active = 1",
+        [],
+        llm_provider_from_row,
+    ) {
+        Ok(config) => Ok(config),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(StorageError::Database(e.to_string()).into()),
+    }
+}
+
+/// Save the API key for an LLM provider, replacing any key it had
+pub(crate) fn save_provider_key(
+    conn: &Connection,
+    provider: &LLMProvider,
+    api_key: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        r#"
+        INSERT INTO provider_keys (provider, api_key) VALUES (?1, ?2)
+        ON CONFLICT(provider) DO UPDATE SET
+            api_key = excluded.api_key,
+            updated_at = CURRENT_TIMESTAMP
+        "#,
+        params![llm_provider_key(provider), api_key],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// API key saved for an LLM provider, if any
+pub(crate) fn get_provider_key(
+    conn: &Connection,
+    provider: &LLMProvider,
+) -> Result<Option<String>, AppError> {
+    match conn.query_row(
+        "SELECT api_key FROM provider_keys WHERE provider = ?1",
+        [llm_provider_key(provider)],
+        |row| row.get(0),
+    ) {
+        Ok(key) => Ok(Some(key)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(StorageError::Database(e.to_string()).into()),
+    }
+}
+
+/// Every saved API key, with the provider it belongs to
+pub(crate) fn get_provider_keys(conn: &Connection) -> Result<Vec<(LLMProvider, String)>, AppError> {
+    let db_error = |e: rusqlite::Error| StorageError::Database(e.to_string());
+    let mut stmt = conn
+        .prepare("SELECT provider, api_key FROM provider_keys")
+        .map_err(db_error)?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .map_err(db_error)?;
+
+    let mut keys = Vec::new();
+    for row in rows {
+        let (provider, key) = row.map_err(db_error)?;
+        // Keys for providers this build no longer knows are skipped
+        if let Ok(provider) = serde_json::from_value(serde_json::Value::String(provider)) {
+            keys.push((provider, key));
+        }
+    }
+    Ok(keys)
+}
+
+/// Value of a setting: the saved one, else the setting's default. Keys the app does not
+/// know read as `T::default()`.
+pub(crate) fn get_setting<T: DeserializeOwned + Default>(
+    conn: &Connection,
+    key: &str,
+) -> Result<T, AppError> {
+    let Some(setting) = Setting::find(key) else {
+        return Ok(T::default());
+    };
+    let value = saved_setting(conn, key)?.unwrap_or_else(|| setting.default_value());
+    serde_json::from_value(value).map_err(|e| {
+        StorageError::Serialization(format!("Setting {} has an unexpected type: {}", key, e)).into()
+    })
+}
+
+/// Save a setting, checking the key is known and the value has the setting's type
+pub(crate) fn set_setting<T: Serialize>(
+    conn: &Connection,
+    key: &str,
+    value: &T,
+) -> Result<(), AppError> {
+    let setting = Setting::find(key).ok_or_else(|| StorageError::UnknownSetting(key.to_string()))?;
+    let value =
+        serde_json::to_value(value).map_err(|e| StorageError::Serialization(e.to_string()))?;
+    setting
+        .validate(&value)
+        .map_err(|reason| StorageError::InvalidSetting {
+            key: key.to_string(),
+            reason,
+        })?;
+    save_setting(conn, key, &value)
+}
+
+fn save_setting(conn: &Connection, key: &str, value: &Value) -> Result<(), AppError> {
+    conn.execute(
+        r#"
+        INSERT INTO settings (key, value) VALUES (?1, ?2)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP
+        "#,
+        params![key, value.to_string()],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Every known setting with its current value
+pub(crate) fn get_all_settings(conn: &Connection) -> Result<BTreeMap<String, Value>, AppError> {
+    let mut settings = BTreeMap::new();
+    for setting in SETTINGS {
+        let value = saved_setting(conn, setting.key)?.unwrap_or_else(|| setting.default_value());
+        settings.insert(setting.key.to_string(), value);
+    }
+    Ok(settings)
+}
+
+/// Where conversions write, from the output directory and file name template settings
+pub(crate) fn get_output_settings(conn: &Connection) -> Result<OutputSettings, AppError> {
+    let default_dir: String = get_setting(conn, OUTPUT_DIR)?;
+    let filename_template: String = get_setting(conn, OUTPUT_FILENAME_TEMPLATE)?;
+    let default = OutputSettings::default();
+    Ok(OutputSettings {
+        default_dir: (!default_dir.is_empty()).then_some(default_dir),
+        filename_template: if filename_template.trim().is_empty() {
+            default.filename_template
+        } else {
+            filename_template
+        },
+    })
+}
+
+/// Save the output directory and file name template settings
+pub(crate) fn set_output_settings(
+    conn: &Connection,
+    settings: &OutputSettings,
+) -> Result<(), AppError> {
+    set_setting(conn, OUTPUT_DIR, &settings.default_dir.as_deref().unwrap_or(""))?;
+    set_setting(conn, OUTPUT_FILENAME_TEMPLATE, &settings.filename_template)
+}
+
+/// Forget every saved setting, so all read as their defaults again
+pub(crate) fn reset_settings(conn: &Connection) -> Result<usize, AppError> {
+    conn.execute("DELETE FROM settings WHERE key != ?1", params![WPM_CALIBRATION])
+        .map_err(|e| StorageError::Database(e.to_string()).into())
+}
+
+/// The last saved measurement of the TTS voice's pace
+pub(crate) fn get_wpm_calibration(conn: &Connection) -> Result<Option<WpmCalibration>, AppError> {
+    let saved = saved_setting(conn, WPM_CALIBRATION)?;
+    Ok(saved.and_then(|value| serde_json::from_value(value).ok()))
+}
+
+pub(crate) fn set_wpm_calibration(
+    conn: &Connection,
+    calibration: &WpmCalibration,
+) -> Result<(), AppError> {
+    let value =
+        serde_json::to_value(calibration).map_err(|e| StorageError::Serialization(e.to_string()))?;
+    save_setting(conn, WPM_CALIBRATION, &value)
+}
+
+/// The saved JSON value of a setting, if it has been set and still parses
+fn saved_setting(conn: &Connection, key: &str) -> Result<Option<Value>, AppError> {
+    let saved: Option<String> = match conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        params![key],
+        |row| row.get(0),
+    ) {
+        Ok(value) => Some(value),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(StorageError::Database(e.to_string()).into()),
+    };
+    Ok(saved.and_then(|value| serde_json::from_str(&value).ok()))
+}
+
+/// Save a bookmark, returning it as stored. Bookmarking an already bookmarked position
+/// keeps the existing bookmark and gives it the new label, if one is given.
+pub(crate) fn save_bookmark(conn: &Connection, bookmark: &Bookmark) -> Result<Bookmark, AppError> {
+    let (id, label, created_at): (String, Option<String>, String) = conn
+        .query_row(
+            r#"
+            INSERT INTO bookmarks
+            (id, document_id, page_number, paragraph_id, word_index, label, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT(document_id, page_number, paragraph_id, word_index)
+            DO UPDATE SET label = COALESCE(excluded.label, label)
+            RETURNING id, label, created_at
+            "#,
+            params![
+                bookmark.id.to_string(),
+                bookmark.document_id,
+                bookmark.page_number,
+                bookmark.paragraph_id,
+                bookmark.word_index,
+                bookmark.label,
+                bookmark.created_at.to_rfc3339(),
+            ],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(Bookmark {
+        id: Uuid::parse_str(&id).unwrap_or(bookmark.id),
+        label,
+        created_at: chrono::DateTime::parse_from_rfc3339(&created_at)
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+            .unwrap_or(bookmark.created_at),
+        ..bookmark.clone()
+    })
+}
+
+/// Bookmarks in a document by page. Paragraph ids carry no order, so callers put each
+/// page's bookmarks in reading order with `ParagraphOrder::sort`.
+pub(crate) fn get_bookmarks(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Vec<Bookmark>, AppError> {
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT id, page_number, paragraph_id, word_index, label, created_at
+            FROM bookmarks
+            WHERE document_id = ?1
+            ORDER BY page_number, created_at
+            "#,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let bookmarks = stmt
+        .query_map([document_id], |row| {
+            Ok(Bookmark {
+                id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap_or_default(),
+                document_id: document_id.to_string(),
+                page_number: row.get(1)?,
+                paragraph_id: row.get(2)?,
+                word_index: row.get(3)?,
+                label: row.get(4)?,
+                created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
+                    .map(|dt| dt.with_timezone(&chrono::Utc))
+                    .unwrap_or_else(|_| chrono::Utc::now()),
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(bookmarks)
+}
+
+/// Delete a bookmark, returning whether it existed
+pub(crate) fn delete_bookmark(conn: &Connection, id: Uuid) -> Result<bool, AppError> {
+    let deleted = conn
+        .execute("DELETE FROM bookmarks WHERE id = ?1", [id.to_string()])
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(deleted > 0)
+}
+
+/// Save a new comment or reply
+pub(crate) fn save_comment(conn: &Connection, comment: &Comment) -> Result<(), AppError> {
+    conn.execute(
+        r#"
+        INSERT INTO comments
+        (id, document_id, paragraph_id, parent_id, author, body, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        "#,
+        params![
+            comment.id.to_string(),
+            comment.document_id,
+            comment.paragraph_id,
+            comment.parent_id.map(|id| id.to_string()),
+            comment.author,
+            comment.body,
+            comment.created_at.to_rfc3339(),
+        ],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+    Ok(())
+}
+
+const COMMENT_COLUMNS: &str =
+    "id, document_id, paragraph_id, parent_id, author, body, created_at";
+
+fn comment_from_row(row: &rusqlite::Row) -> rusqlite::Result<Comment> {
+    let parent_id: Option<String> = row.get(3)?;
+    Ok(Comment {
+        id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap_or_default(),
+        document_id: row.get(1)?,
+        paragraph_id: row.get(2)?,
+        parent_id: parent_id.and_then(|id| Uuid::parse_str(&id).ok()),
+        author: row.get(4)?,
+        body: row.get(5)?,
+        created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+            .unwrap_or_else(|_| chrono::Utc::now()),
+    })
+}
+
+/// A single comment, if it exists
+pub(crate) fn get_comment(conn: &Connection, id: Uuid) -> Result<Option<Comment>, AppError> {
+    match conn.query_row(
+        &format!("SELECT {} FROM comments WHERE id = ?1", COMMENT_COLUMNS),
+        [id.to_string()],
+        comment_from_row,
+    ) {
+        Ok(comment) => Ok(Some(comment)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(StorageError::Database(e.to_string()).into()),
+    }
+}
+
+/// Every comment and reply on a paragraph, oldest first
+pub(crate) fn get_paragraph_comments(
+    conn: &Connection,
+    document_id: &str,
+    paragraph_id: &str,
+) -> Result<Vec<Comment>, AppError> {
+    let mut stmt = conn
+        .prepare(&format!(
+            r#"
+            SELECT {} FROM comments
+            WHERE document_id = ?1 AND paragraph_id = ?2
+            ORDER BY created_at, rowid
+            "#,
+            COMMENT_COLUMNS
+        ))
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let comments = stmt
+        .query_map([document_id, paragraph_id], comment_from_row)
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(comments)
+}
+
+/// Delete a comment together with every reply beneath it, returning how many comments
+/// were removed
+pub(crate) fn delete_comment(conn: &Connection, id: Uuid) -> Result<usize, AppError> {
+    let db_error = |e: rusqlite::Error| StorageError::Database(e.to_string());
+    let tx = conn.unchecked_transaction().map_err(db_error)?;
+
+    // Foreign keys are not enforced, so replies are found and removed here rather than
+    // by cascade
+    let thread: Vec<String> = {
+        let mut stmt = tx
+            .prepare(
+                r#"
+                WITH RECURSIVE thread(id) AS (
+                    SELECT id FROM comments WHERE id = ?1
+                    UNION
+                    SELECT comments.id FROM comments JOIN thread ON comments.parent_id = thread.id
+                )
+                SELECT id FROM thread
+                "#,
+            )
+            .map_err(db_error)?;
+        let ids = stmt
+            .query_map([id.to_string()], |row| row.get(0))
+            .map_err(db_error)?
+            .collect::<Result<_, _>>()
+            .map_err(db_error)?;
+        ids
+    };
+    for comment_id in &thread {
+        tx.execute("DELETE FROM comments WHERE id = ?1", [comment_id]).map_err(db_error)?;
+    }
+
+    tx.commit().map_err(db_error)?;
+    Ok(thread.len())
+}
+
+/// Open a reading session, returning its id
+pub(crate) fn start_reading_session(
+    conn: &Connection,
+    document_id: &str,
+    mode: ReadingMode,
+    started_at: chrono::DateTime<chrono::Utc>,
+) -> Result<String, AppError> {
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO reading_sessions (id, document_id, mode, started_at) VALUES (?1, ?2, ?3, ?4)",
+        params![id, document_id, mode.as_str(), started_at.to_rfc3339()],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(id)
+}
+
+/// Note pages covered by a reading session; pages already recorded are ignored
+pub(crate) fn record_reading_pages(
+    conn: &Connection,
+    session_id: &str,
+    pages: &[u32],
+) -> Result<(), AppError> {
+    let mut stmt = conn
+        .prepare(
+            "INSERT OR IGNORE INTO reading_session_pages (session_id, page_number) VALUES (?1, ?2)",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    for page in pages {
+        stmt.execute(params![session_id, page])
+            .map_err(|e| StorageError::Database(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Document of a reading session still in progress and the pages it has covered so far
+pub(crate) fn get_open_reading_session(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<(String, Vec<u32>), AppError> {
+    let document_id: String = conn
+        .query_row(
+            "SELECT document_id FROM reading_sessions WHERE id = ?1 AND ended_at IS NULL",
+            [session_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::from(DocumentError::InvalidId),
+            e => StorageError::Database(e.to_string()).into(),
+        })?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT page_number FROM reading_session_pages WHERE session_id = ?1
+             ORDER BY page_number",
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    let pages = stmt
+        .query_map([session_id], |row| row.get(0))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok((document_id, pages))
+}
+
+/// Close a reading session. Fails with `InvalidId` if it does not exist or already ended.
+pub(crate) fn finish_reading_session(
+    conn: &Connection,
+    session_id: &str,
+    ended_at: chrono::DateTime<chrono::Utc>,
+    words_read: u32,
+) -> Result<(), AppError> {
+    let updated = conn
+        .execute(
+            "UPDATE reading_sessions SET ended_at = ?2, words_read = ?3
+             WHERE id = ?1 AND ended_at IS NULL",
+            params![session_id, ended_at.to_rfc3339(), words_read],
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    if updated == 0 {
+        return Err(DocumentError::InvalidId.into());
+    }
+
+    Ok(())
+}
+
+/// Finished reading sessions for a document, oldest first
+pub(crate) fn get_reading_sessions(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Vec<ReadingSession>, AppError> {
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT s.id, s.mode, s.started_at, s.ended_at, s.words_read,
+                   GROUP_CONCAT(p.page_number)
+            FROM reading_sessions s
+            LEFT JOIN reading_session_pages p ON p.session_id = s.id
+            WHERE s.document_id = ?1 AND s.ended_at IS NOT NULL
+            GROUP BY s.id
+            ORDER BY s.started_at
+            "#,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let timestamp = |value: String| {
+        chrono::DateTime::parse_from_rfc3339(&value)
+            .map(|t| t.with_timezone(&chrono::Utc))
+            .unwrap_or_default()
+    };
+    let sessions = stmt
+        .query_map([document_id], |row| {
+            let mode: String = row.get(1)?;
+            let pages: Option<String> = row.get(5)?;
+            let mut pages: Vec<u32> = pages
+                .iter()
+                .flat_map(|p| p.split(','))
+                .filter_map(|p| p.parse().ok())
+                .collect();
+            pages.sort_unstable();
+
+            Ok(ReadingSession {
+                id: row.get(0)?,
+                document_id: document_id.to_string(),
+                mode: ReadingMode::parse(&mode).unwrap_or(ReadingMode::Manual),
+                started_at: timestamp(row.get(2)?),
+                ended_at: timestamp(row.get(3)?),
+                pages,
+                words_read: row.get(4)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(sessions)
+}
+
+/// Save generated flashcards for a document
+pub(crate) fn insert_flashcards(
+    conn: &Connection,
+    document_id: &str,
+    flashcards: &[Flashcard],
+) -> Result<(), AppError> {
+    for card in flashcards {
+        conn.execute(
+            r#"
+            INSERT INTO flashcards (id, document_id, question, answer)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            params![Uuid::new_v4().to_string(), document_id, card.question, card.answer],
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Record where each page's text came from, replacing any previous record
+pub async fn save_page_sources(
+    app: &AppHandle,
+    document_id: &str,
+    sources: &[PageSource],
+) -> Result<(), AppError> {
+    let db = app.state::<Database>();
+    let conn = db.conn.lock().unwrap();
+    replace_page_sources(&conn, document_id, sources)
+}
+
+pub(crate) fn replace_page_sources(
+    conn: &Connection,
+    document_id: &str,
+    sources: &[PageSource],
+) -> Result<(), AppError> {
+    conn.execute("DELETE FROM page_sources WHERE document_id = ?1", [document_id])
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    for source in sources {
+        conn.execute(
+            "INSERT INTO page_sources (document_id, page_number, source) VALUES (?1, ?2, ?3)",
+            params![document_id, source.page, source.source.as_str()],
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Cache the OCR text of one page, replacing what was recognized before
+pub(crate) fn save_ocr_page(
+    conn: &Connection,
+    document_id: &str,
+    page: u32,
+    language: &str,
+    text: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        r#"
+        INSERT OR REPLACE INTO ocr_pages (document_id, page_number, language, text)
+        VALUES (?1, ?2, ?3, ?4)
+        "#,
+        params![document_id, page, language, text],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// Cached OCR text of a document's pages, by page number
+pub(crate) fn get_ocr_pages(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<HashMap<u32, String>, AppError> {
+    let mut stmt = conn
+        .prepare("SELECT page_number, text FROM ocr_pages WHERE document_id = ?1")
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let pages = stmt
+        .query_map([document_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(pages)
+}
+
+/// Save the description of one image, replacing any written before
+pub(crate) fn save_image_description(
+    conn: &Connection,
+    document_id: &str,
+    description: &ImageDescription,
+) -> Result<(), AppError> {
+    conn.execute(
+        r#"
+        INSERT OR REPLACE INTO image_descriptions
+            (document_id, page_number, image_index, description, model)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+        params![
+            document_id,
+            description.page,
+            description.image_index,
+            description.description,
+            description.model
+        ],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// Saved image descriptions for a document, in page and image order
+pub(crate) fn get_image_descriptions(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Vec<ImageDescription>, AppError> {
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT page_number, image_index, description, model FROM image_descriptions
+            WHERE document_id = ?1
+            ORDER BY page_number, image_index
+            "#,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let descriptions = stmt
+        .query_map([document_id], |row| {
+            Ok(ImageDescription {
+                page: row.get(0)?,
+                image_index: row.get(1)?,
+                description: row.get(2)?,
+                model: row.get(3)?,
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(descriptions)
+}
+
+/// Update a document's page and word counts after its text changed
+pub(crate) fn update_document_counts(
+    conn: &Connection,
+    document_id: &str,
+    page_count: u32,
+    word_count: u32,
+) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE documents SET page_count = ?2, word_count = ?3 WHERE id = ?1",
+        params![document_id, page_count, word_count],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// Recorded per-page text sources for a document, in page order
+pub(crate) fn get_page_sources(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Vec<PageSource>, AppError> {
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT page_number, source FROM page_sources
+            WHERE document_id = ?1
+            ORDER BY page_number
+            "#,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let sources = stmt
+        .query_map([document_id], |row| {
+            let source: String = row.get(1)?;
+            Ok(PageSource {
+                page: row.get(0)?,
+                source: TextSource::parse(&source).unwrap_or_default(),
+            })
+        })
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(sources)
+}
+
+/// Remember a folder to watch for new documents
+pub(crate) fn add_watched_folder(conn: &Connection, path: &str) -> Result<(), AppError> {
+    conn.execute("INSERT OR IGNORE INTO watched_folders (path) VALUES (?1)", [path])
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// Stop remembering a watched folder
+pub(crate) fn remove_watched_folder(conn: &Connection, path: &str) -> Result<(), AppError> {
+    conn.execute("DELETE FROM watched_folders WHERE path = ?1", [path])
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// Watched folders in the order they were added
+pub(crate) fn get_watched_folders(conn: &Connection) -> Result<Vec<String>, AppError> {
+    let mut stmt = conn
+        .prepare("SELECT path FROM watched_folders ORDER BY added_at, path")
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let folders = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(folders)
+}
+
+/// Cache a document's summary, replacing any previous one
+pub(crate) fn save_summary(
+    conn: &Connection,
+    document_id: &str,
+    summary: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO summaries (document_id, summary) VALUES (?1, ?2)",
+        params![document_id, summary],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Cached summary for a document, if one has been generated
+pub(crate) fn get_summary(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<Option<String>, AppError> {
+    match conn.query_row(
+        "SELECT summary FROM summaries WHERE document_id = ?1",
+        [document_id],
+        |row| row.get(0),
+    ) {
+        Ok(summary) => Ok(Some(summary)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(StorageError::Database(e.to_string()).into()),
+    }
+}
+
+/// Store a paragraph's translation, replacing any previous one for the language
+pub(crate) fn save_translation(
+    conn: &Connection,
+    document_id: &str,
+    paragraph_id: &str,
+    language: &str,
+    translation: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO translations (document_id, paragraph_id, language, translation)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![document_id, paragraph_id, language, translation],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Stored translation of a paragraph, if it has been translated into the language
+pub(crate) fn get_translation(
+    conn: &Connection,
+    document_id: &str,
+    paragraph_id: &str,
+    language: &str,
+) -> Result<Option<String>, AppError> {
+    match conn.query_row(
+        "SELECT translation FROM translations
+         WHERE document_id = ?1 AND paragraph_id = ?2 AND language = ?3",
+        [document_id, paragraph_id, language],
+        |row| row.get(0),
+    ) {
+        Ok(translation) => Ok(Some(translation)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(StorageError::Database(e.to_string()).into()),
+    }
+}
+
+/// Ids of a document's paragraphs already translated into the language
+pub(crate) fn get_translated_paragraph_ids(
+    conn: &Connection,
+    document_id: &str,
+    language: &str,
+) -> Result<HashSet<String>, AppError> {
+    let mut stmt = conn
+        .prepare("SELECT paragraph_id FROM translations WHERE document_id = ?1 AND language = ?2")
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let ids = stmt
+        .query_map([document_id, language], |row| row.get(0))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(ids)
+}
+
+/// Set a custom metadata field on a document, replacing its previous value
+pub(crate) fn set_document_meta(
+    conn: &Connection,
+    document_id: &str,
+    key: &str,
+    value: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO document_metadata_kv (document_id, key, value) VALUES (?1, ?2, ?3)
+         ON CONFLICT (document_id, key)
+         DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        params![document_id, key, value],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Custom metadata fields of a document, by key
+pub(crate) fn get_document_meta(
+    conn: &Connection,
+    document_id: &str,
+) -> Result<BTreeMap<String, String>, AppError> {
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM document_metadata_kv WHERE document_id = ?1")
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let fields = stmt
+        .query_map([document_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(fields)
+}
+
+/// Tag a document; adding a tag it already has, in any case, does nothing
+pub(crate) fn add_tag(conn: &Connection, document_id: &str, tag: &str) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR IGNORE INTO document_tags (document_id, tag) VALUES (?1, ?2)",
+        params![document_id, tag],
+    )
+    .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// A document's tags, alphabetically
+pub(crate) fn get_tags(conn: &Connection, document_id: &str) -> Result<Vec<String>, AppError> {
+    let mut stmt = conn
+        .prepare("SELECT tag FROM document_tags WHERE document_id = ?1 ORDER BY tag")
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let tags = stmt
+        .query_map([document_id], |row| row.get(0))
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(tags)
+}
+
+/// Build an annotation from a row selected in the standard column order
+fn annotation_from_row(row: &rusqlite::Row) -> rusqlite::Result<Annotation> {
+    let color_str: Option<String> = row.get(7)?;
+    let color = color_str.and_then(|c| HighlightColor::from_key(&c));
+
+    Ok(Annotation {
+        id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap_or_default(),
+        document_id: row.get(1)?,
+        page_number: row.get(2)?,
+        paragraph_id: row.get(3)?,
+        paragraph_unmatched: row.get(12)?,
+        kind: AnnotationKind::parse(&row.get::<_, String>(11)?).unwrap_or_default(),
+        start_offset: row.get(4)?,
+        end_offset: row.get(5)?,
+        selected_text: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+        highlight_color: color,
+        note: row.get(8)?,
+        created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(9)?)
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+            .unwrap_or_else(|_| chrono::Utc::now()),
+        updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(10)?)
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+            .unwrap_or_else(|_| chrono::Utc::now()),
+    })
+}
+
+/// Helper to get annotation by ID
+fn get_annotations_by_id(conn: &Connection, id: Uuid) -> Result<Vec<Annotation>, AppError> {
+    let mut stmt = conn
+        .prepare(
+            r#"
+            SELECT id, document_id, page_number, paragraph_id, start_offset, end_offset,
+                   selected_text, highlight_color, note, created_at, updated_at, kind,
+                   paragraph_unmatched
+            FROM annotations
+            WHERE id = ?1
+            "#,
+        )
+        .map_err(|e| StorageError::Database(e.to_string()))?;
+
+    let annotations = stmt
+        .query_map([id.to_string()], annotation_from_row)
+        .map_err(|e| StorageError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(annotations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annotations_resolve_after_id_migration() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO documents (id, file_path) VALUES ('doc1', 'paper.txt');
+            INSERT INTO annotations (id, document_id, page_number, paragraph_id, start_offset, end_offset, selected_text)
+            VALUES ('a1', 'doc1', 1, 'p2', 0, 5, 'Hello');
+            "#,
+        )
+        .unwrap();
+
+        let stable_id = format!("p-{}", crate::document::parser::paragraph_content_hash("Hello there"));
+        let id_map = ParagraphIdMap::from([
+            ("p1".to_string(), "p-aaaa".to_string()),
+            ("p2".to_string(), stable_id.clone()),
+        ]);
+
+        assert_eq!(remap_paragraph_ids(&conn, "doc1", &id_map).unwrap().remapped, 1);
+        // Running again is a no-op since stable ids never collide with legacy ones
+        assert_eq!(
+            remap_paragraph_ids(&conn, "doc1", &id_map).unwrap(),
+            ParagraphRemap::default()
+        );
+
+        let paragraph_id: String = conn
+            .query_row("SELECT paragraph_id FROM annotations WHERE id = 'a1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(paragraph_id, stable_id);
+    }
+
+    #[test]
+    fn test_remap_moves_annotations_and_flags_unmatched() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO documents (id, file_path) VALUES ('doc1', 'paper.txt');
+            INSERT INTO annotations (id, document_id, page_number, paragraph_id, start_offset, end_offset, selected_text)
+            VALUES ('kept', 'doc1', 1, 'p-old-intro', 0, 5, 'Intro'),
+                   ('gone', 'doc1', 2, 'p-old-removed', 0, 5, 'Cut'),
+                   ('same', 'doc1', 2, 'p-unchanged', 0, 5, 'Same');
+            "#,
+        )
+        .unwrap();
+        let comment = Comment::new(
+            "doc1".into(),
+            "p-old-intro".into(),
+            None,
+            "Ada".into(),
+            "Nice opening".into(),
+        );
+        save_comment(&conn, &comment).unwrap();
+
+        let paragraph = |id: &str, text: &str| crate::document::Paragraph {
+            id: id.to_string(),
+            text: text.to_string(),
+            bounding_box: None,
+        };
+        let old = [
+            paragraph("p-old-intro", "We study reading."),
+            paragraph("p-old-removed", "A paragraph that was cut."),
+            paragraph("p-unchanged", "Unchanged."),
+        ];
+        let new = [
+            paragraph("p-new-intro", "We study  reading."),
+            paragraph("p-unchanged", "Unchanged."),
+        ];
+        let id_map = crate::document::parser::match_paragraph_ids(&old, &new);
+
+        let summary = remap_paragraph_ids(&conn, "doc1", &id_map).unwrap();
+        assert_eq!(summary, ParagraphRemap { remapped: 1, unmatched: 1 });
+
+        let annotations = list_annotations(&conn, "doc1").unwrap();
+        let find = |text: &str| annotations.iter().find(|a| a.selected_text == text).unwrap();
+        assert_eq!(find("Intro").paragraph_id.as_deref(), Some("p-new-intro"));
+        assert!(!find("Intro").paragraph_unmatched);
+        assert_eq!(find("Cut").paragraph_id.as_deref(), Some("p-old-removed"));
+        assert!(find("Cut").paragraph_unmatched);
+        assert!(!find("Same").paragraph_unmatched);
+        assert_eq!(get_comment(&conn, comment.id).unwrap().unwrap().paragraph_id, "p-new-intro");
+    }
+
+    #[test]
+    fn test_delete_document_removes_attached_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO documents (id, file_path) VALUES ('doc1', 'a.txt'), ('doc2', 'b.txt')",
+        )
+        .unwrap();
+        for document_id in ["doc1", "doc2"] {
+            let comment =
+                Comment::new(document_id.into(), "p1".into(), None, "Ada".into(), "Hm".into());
+            save_comment(&conn, &comment).unwrap();
+            let session =
+                start_reading_session(&conn, document_id, ReadingMode::Manual, chrono::Utc::now())
+                    .unwrap();
+            record_reading_pages(&conn, &session, &[1]).unwrap();
+        }
+
+        assert!(delete_document(&conn, "doc1").unwrap());
+        assert!(!delete_document(&conn, "doc1").unwrap());
+
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT COUNT(*) FROM comments WHERE document_id = 'doc1'"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM comments"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM reading_session_pages"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM documents"), 1);
+    }
+
+    #[test]
+    fn test_reading_session_records_duration_and_pages() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute("INSERT INTO documents (id, file_path) VALUES ('doc1', 'book.epub')", [])
+            .unwrap();
+
+        let start = chrono::DateTime::parse_from_rfc3339("2024-05-01T20:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let session = start_reading_session(&conn, "doc1", ReadingMode::Manual, start).unwrap();
+        record_reading_pages(&conn, &session, &[3, 4]).unwrap();
+        record_reading_pages(&conn, &session, &[4, 5]).unwrap();
+        assert_eq!(
+            get_open_reading_session(&conn, &session).unwrap(),
+            ("doc1".to_string(), vec![3, 4, 5])
+        );
+        // Still in progress, so not counted yet
+        assert!(get_reading_sessions(&conn, "doc1").unwrap().is_empty());
+
+        let end = start + chrono::Duration::minutes(6);
+        finish_reading_session(&conn, &session, end, 1500).unwrap();
+        assert!(finish_reading_session(&conn, &session, end, 1500).is_err());
+
+        let spoken = start_reading_session(&conn, "doc1", ReadingMode::Tts, end).unwrap();
+        record_reading_pages(&conn, &spoken, &[5]).unwrap();
+        finish_reading_session(&conn, &spoken, end + chrono::Duration::minutes(4), 1000).unwrap();
+
+        let sessions = get_reading_sessions(&conn, "doc1").unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].mode, ReadingMode::Manual);
+        assert_eq!(sessions[0].duration_secs(), 360);
+        assert_eq!(sessions[0].pages, [3, 4, 5]);
+
+        let analytics = crate::document::ReadingAnalytics::from_sessions("doc1", &sessions);
+        assert_eq!(analytics.total_seconds, 600);
+        assert_eq!(analytics.pages_read, [3, 4, 5]);
+        assert_eq!(analytics.average_wpm, Some(250.0));
+    }
+
+    #[test]
+    fn test_bookmarks_persist_and_list_by_page() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute("INSERT INTO documents (id, file_path) VALUES ('doc1', 'paper.pdf')", [])
+            .unwrap();
+
+        let bookmark = |page, word, label: Option<&str>| {
+            Bookmark::new("doc1".into(), page, "p-1".into(), word, label.map(String::from))
+        };
+        let late = save_bookmark(&conn, &bookmark(7, 3, None)).unwrap();
+        let early = save_bookmark(&conn, &bookmark(2, 0, Some("Definition"))).unwrap();
+
+        // Same position again: relabelled, not duplicated
+        let relabelled = save_bookmark(&conn, &bookmark(7, 3, Some("Proof"))).unwrap();
+        assert_eq!(relabelled.id, late.id);
+        assert_eq!(relabelled.label.as_deref(), Some("Proof"));
+
+        let stored = get_bookmarks(&conn, "doc1").unwrap();
+        assert_eq!(stored, vec![early.clone(), relabelled]);
+
+        assert!(delete_bookmark(&conn, early.id).unwrap());
+        assert!(!delete_bookmark(&conn, early.id).unwrap());
+        assert_eq!(get_bookmarks(&conn, "doc1").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_comment_replies_load_in_order_and_delete_with_parent() {
+        use crate::annotation::comments::build_threads;
+
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute("INSERT INTO documents (id, file_path) VALUES ('doc1', 'paper.pdf')", [])
+            .unwrap();
+
+        let comment = |parent: Option<&Comment>, author: &str, body: &str| {
+            let comment = Comment::new(
+                "doc1".into(),
+                "p-1".into(),
+                parent.map(|p| p.id),
+                author.into(),
+                body.into(),
+            );
+            save_comment(&conn, &comment).unwrap();
+            comment
+        };
+        let question = comment(None, "ana", "Is this bound tight?");
+        let answer = comment(Some(&question), "ben", "Only for convex losses.");
+        let follow_up = comment(Some(&answer), "ana", "Which lemma shows that?");
+        let other = comment(None, "ben", "Typo in eq. 3");
+        let second_answer = comment(Some(&question), "cy", "See the appendix.");
+
+        let threads = build_threads(get_paragraph_comments(&conn, "doc1", "p-1").unwrap());
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].comment, question);
+        assert_eq!(threads[1].comment, other);
+        let replies: Vec<_> = threads[0].replies.iter().map(|r| &r.comment).collect();
+        assert_eq!(replies, [&answer, &second_answer]);
+        assert_eq!(threads[0].replies[0].replies[0].comment, follow_up);
+        assert_eq!(get_comment(&conn, follow_up.id).unwrap().unwrap().author, "ana");
+
+        // Deleting the question takes its whole thread with it
+        assert_eq!(delete_comment(&conn, question.id).unwrap(), 4);
+        assert!(get_comment(&conn, follow_up.id).unwrap().is_none());
+        assert_eq!(get_paragraph_comments(&conn, "doc1", "p-1").unwrap(), vec![other]);
+        assert_eq!(delete_comment(&conn, question.id).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_page_sources_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute("INSERT INTO documents (id, file_path) VALUES ('doc1', 'scan.pdf')", [])
+            .unwrap();
+
+        let sources = [
+            PageSource { page: 2, source: TextSource::Ocr },
+            PageSource { page: 1, source: TextSource::Native },
+            PageSource { page: 3, source: TextSource::Empty },
+        ];
+        replace_page_sources(&conn, "doc1", &sources).unwrap();
+        // Re-recording replaces rather than duplicates
+        replace_page_sources(&conn, "doc1", &sources).unwrap();
+
+        let stored = get_page_sources(&conn, "doc1").unwrap();
+        assert_eq!(
+            stored.iter().map(|s| (s.page, s.source)).collect::<Vec<_>>(),
+            [(1, TextSource::Native), (2, TextSource::Ocr), (3, TextSource::Empty)]
+        );
+        assert!(get_page_sources(&conn, "other").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_custom_metadata_round_trips_and_overwrites() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute("INSERT INTO documents (id, file_path) VALUES ('doc1', 'paper.pdf')", [])
+            .unwrap();
+
+        set_document_meta(&conn, "doc1", "read_status", "unread").unwrap();
+        set_document_meta(&conn, "doc1", "priority", "high").unwrap();
+        set_document_meta(&conn, "doc1", "read_status", "finished").unwrap();
+
+        assert_eq!(
+            get_document_meta(&conn, "doc1").unwrap(),
+            BTreeMap::from([
+                ("priority".to_string(), "high".to_string()),
+                ("read_status".to_string(), "finished".to_string()),
+            ])
+        );
+        assert!(get_document_meta(&conn, "other").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_annotation_search_spans_documents() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO documents (id, file_path, title)
+             VALUES ('attn', 'attention.pdf', 'Attention Is All You Need'),
+                    ('bert', 'bert.pdf', NULL);",
+        )
+        .unwrap();
+
+        let highlight = |doc: &str, page: u32, text: &str, note: Option<&str>| {
+            let color = Some(HighlightColor::Yellow);
+            let note = note.map(str::to_string);
+            Annotation::new(doc.into(), page, 0, 10, text.into(), color, note)
+        };
+        let annotations = [
+            highlight("attn", 4, "scaled dot-product attention", Some("Why divide by √d?")),
+            highlight("attn", 7, "positional encodings", None),
+            highlight("bert", 2, "masked language model", Some("Compare with scaled scores")),
+            Annotation::page_note("bert".into(), 5, "Fine-tuning recipe".into()),
+        ];
+        for annotation in &annotations {
+            insert_annotation(&conn, annotation).unwrap();
+        }
+
+        let hits = search_annotations(&conn, "scaled", 10).unwrap();
+        let mut found: Vec<_> = hits
+            .iter()
+            .map(|h| (h.document_title.as_str(), h.annotation.page_number))
+            .collect();
+        found.sort();
+        assert_eq!(found, [("Attention Is All You Need", 4), ("bert.pdf", 2)]);
+
+        // Notes match too, words need not be adjacent and the last one is a prefix
+        let hits = search_annotations(&conn, "recipe fine-tun", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].annotation.id, annotations[3].id);
+        assert!(hits[0].snippet.contains("Fine-tuning"));
+
+        // Edits and deletions are reflected in the index
+        conn.execute(
+            "UPDATE annotations SET note = 'Positional sinusoids' WHERE id = ?1",
+            [annotations[1].id.to_string()],
+        )
+        .unwrap();
+        assert_eq!(search_annotations(&conn, "sinusoids", 10).unwrap().len(), 1);
+        conn.execute("DELETE FROM annotations WHERE document_id = 'attn'", []).unwrap();
+        assert_eq!(search_annotations(&conn, "scaled", 10).unwrap().len(), 1);
+        assert!(search_annotations(&conn, "\"unbalanced", 10).unwrap().is_empty());
+        assert!(search_annotations(&conn, "  ", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_metadata_edit_mirrors_into_documents() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO documents (id, file_path, title, metadata)
+             VALUES ('doc1', 'paper.pdf', 'Draft', '{\"page_count\":3,\"keywords\":[]}')",
+            [],
+        )
+        .unwrap();
+
+        let update = PdfMetadataUpdate {
+            subject: Some("Optics".to_string()),
+            keywords: Some(vec!["lasers".to_string()]),
+            ..Default::default()
+        };
+        update_document_metadata(&conn, "doc1", &update).unwrap();
+
+        let (title, metadata): (String, String) = conn
+            .query_row("SELECT title, metadata FROM documents WHERE id = 'doc1'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        let metadata: serde_json::Value = serde_json::from_str(&metadata).unwrap();
+        assert_eq!(title, "Draft");
+        assert_eq!(metadata["subject"], "Optics");
+        assert_eq!(metadata["keywords"], serde_json::json!(["lasers"]));
+        assert_eq!(metadata["page_count"], 3);
+        assert!(update_document_metadata(&conn, "missing", &update).is_err());
+    }
+
+    #[test]
+    fn test_rekeyed_document_keeps_its_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO documents (id, file_path) VALUES ('old', 'paper.pdf');
+             INSERT INTO annotations (id, document_id, page_number) VALUES ('a1', 'old', 1);
+             INSERT INTO summaries (document_id, summary) VALUES ('old', 'About optics.');
+             INSERT INTO documents (id, file_path) VALUES ('other', 'other.pdf');
+             INSERT INTO annotations (id, document_id, page_number) VALUES ('a2', 'other', 1);",
+        )
+        .unwrap();
+
+        rekey_document(&conn, "old", "new").unwrap();
+
+        let owner = |sql: &str| -> String { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(owner("SELECT id FROM documents WHERE file_path = 'paper.pdf'"), "new");
+        assert_eq!(owner("SELECT document_id FROM annotations WHERE id = 'a1'"), "new");
+        assert_eq!(owner("SELECT document_id FROM annotations WHERE id = 'a2'"), "other");
+        assert_eq!(owner("SELECT document_id FROM summaries"), "new");
+
+        // Every table keyed by document is moved
+        let keyed: Vec<String> = conn
+            .prepare(
+                "SELECT m.name FROM sqlite_master m, pragma_table_info(m.name) c
+                 WHERE m.type = 'table' AND c.name = 'document_id' ORDER BY m.name",
+            )
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let mut moved = DOCUMENT_TABLES.to_vec();
+        moved.sort();
+        assert_eq!(keyed, moved);
+    }
+
+    #[test]
+    fn test_llm_audit_round_trip_and_clear() {
+        use crate::llm::audit::AuditParams;
+
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+
+        let entry = |id: &str, timestamp: &str| LlmAuditEntry {
+            id: id.to_string(),
+            timestamp: timestamp.to_string(),
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            params: AuditParams {
+                max_tokens: 256,
+                temperature: 0.2,
+                top_p: Some(0.9),
+                stop: vec!["END".to_string()],
+            },
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hi".to_string(),
+            }],
+            response: Some("Hello".to_string()),
+            error: None,
+            response_tokens: 2,
+            latency_ms: 40,
+        };
+        insert_llm_audit(&conn, &entry("a", "2026-01-01T00:00:00+00:00")).unwrap();
+        insert_llm_audit(&conn, &entry("b", "2026-01-02T00:00:00+00:00")).unwrap();
+
+        let stored = get_llm_audit(&conn, 10).unwrap();
+        let newer = entry("b", "2026-01-02T00:00:00+00:00");
+        assert_eq!(stored, [newer, entry("a", "2026-01-01T00:00:00+00:00")]);
+        assert_eq!(get_llm_audit(&conn, 1).unwrap().len(), 1);
+
+        assert_eq!(clear_llm_audit(&conn).unwrap(), 2);
+        assert!(get_llm_audit(&conn, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pronunciations_replace_case_insensitively() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+
+        let entry = |term: &str, replacement: &str| Pronunciation {
+            term: term.to_string(),
+            replacement: replacement.to_string(),
+        };
+        save_pronunciation(&conn, &entry("SQL", "S Q L")).unwrap();
+        save_pronunciation(&conn, &entry("PyTorch", "pie torch")).unwrap();
+        save_pronunciation(&conn, &entry("sql", "sequel")).unwrap();
+
+        assert_eq!(
+            get_pronunciations(&conn).unwrap(),
+            [entry("PyTorch", "pie torch"), entry("sql", "sequel")]
+        );
+    }
+
+    #[test]
+    fn test_settings_round_trip_with_defaults() {
+        use crate::settings::{AUTO_SUMMARY, DEFAULT_WPM, OUTPUT_DIR};
+
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+
+        // Nothing saved yet: known keys read as their defaults, unknown keys as T's
+        assert!(get_setting::<bool>(&conn, AUTO_SUMMARY).unwrap());
+        assert_eq!(get_setting::<f64>(&conn, DEFAULT_WPM).unwrap(), 175.0);
+        assert_eq!(get_setting::<String>(&conn, "no_such_setting").unwrap(), "");
+        assert_eq!(get_setting::<u32>(&conn, "no_such_setting").unwrap(), 0);
+
+        set_setting(&conn, AUTO_SUMMARY, &false).unwrap();
+        set_setting(&conn, DEFAULT_WPM, &240).unwrap();
+        set_setting(&conn, OUTPUT_DIR, &"/home/me/exports").unwrap();
+        assert!(!get_setting::<bool>(&conn, AUTO_SUMMARY).unwrap());
+        assert_eq!(get_setting::<u32>(&conn, DEFAULT_WPM).unwrap(), 240);
+        assert_eq!(get_setting::<String>(&conn, OUTPUT_DIR).unwrap(), "/home/me/exports");
+
+        assert!(matches!(
+            set_setting(&conn, "no_such_setting", &true),
+            Err(AppError::Storage(StorageError::UnknownSetting(_)))
+        ));
+        assert!(matches!(
+            set_setting(&conn, AUTO_SUMMARY, &"yes"),
+            Err(AppError::Storage(StorageError::InvalidSetting { .. }))
+        ));
+        assert!(set_setting(&conn, DEFAULT_WPM, &5000).is_err());
+
+        let all = get_all_settings(&conn).unwrap();
+        assert_eq!(all.len(), crate::settings::SETTINGS.len());
+        assert_eq!(all[DEFAULT_WPM], serde_json::json!(240));
+
+        assert_eq!(reset_settings(&conn).unwrap(), 3);
+        assert_eq!(get_setting::<f64>(&conn, DEFAULT_WPM).unwrap(), 175.0);
+        assert_eq!(get_setting::<String>(&conn, OUTPUT_DIR).unwrap(), "");
+    }
+
+    #[test]
+    fn test_wpm_calibration_survives_settings_reset() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        assert!(get_wpm_calibration(&conn).unwrap().is_none());
+
+        let calibration = WpmCalibration {
+            provider: crate::voice::TTSProvider::PiperLocal {
+                model_path: "voices/en_US-lessac-medium.onnx".to_string(),
+            },
+            base_wpm: 182.5,
+            words: 52,
+            duration_ms: 17_100,
+            calibrated_at: chrono::Utc::now(),
+        };
+        set_wpm_calibration(&conn, &calibration).unwrap();
+        set_setting(&conn, crate::settings::DEFAULT_WPM, &240).unwrap();
+
+        assert_eq!(reset_settings(&conn).unwrap(), 1);
+        let saved = get_wpm_calibration(&conn).unwrap().unwrap();
+        assert_eq!(saved.base_wpm, 182.5);
+        assert_eq!(saved.provider, calibration.provider);
+    }
+
+    #[test]
+    fn test_output_settings_kept_as_settings() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        assert_eq!(get_output_settings(&conn).unwrap(), OutputSettings::default());
+
+        let settings = OutputSettings {
+            default_dir: Some("/home/me/exports".to_string()),
+            filename_template: "{name}-{date}.{ext}".to_string(),
+        };
+        set_output_settings(&conn, &settings).unwrap();
+        assert_eq!(get_output_settings(&conn).unwrap(), settings);
+        // The folder is the same setting the settings screen edits
+        assert_eq!(get_setting::<String>(&conn, OUTPUT_DIR).unwrap(), "/home/me/exports");
+
+        set_setting(&conn, OUTPUT_DIR, &"").unwrap();
+        assert_eq!(get_output_settings(&conn).unwrap().default_dir, None);
+    }
+
+    #[test]
+    fn test_annotation_color_counts_and_filter() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute("INSERT INTO documents (id, file_path) VALUES ('doc1', 'paper.txt')", [])
+            .unwrap();
+
+        let colors = [
+            Some("yellow"),
+            Some("green"),
+            Some("yellow"),
+            Some("#FF8800"),
+            Some("#ff8800"),
+            Some("#ff8800"),
+            None,
+        ];
+        for (i, color) in colors.iter().enumerate() {
+            conn.execute(
+                r#"
+                INSERT INTO annotations (id, document_id, page_number, start_offset, end_offset,
+                                         selected_text, highlight_color, created_at, updated_at)
+                VALUES (?1, 'doc1', 1, ?2, ?2, '', ?3, '', '')
+                "#,
+                params![Uuid::new_v4().to_string(), i, color.map(str::to_lowercase)],
+            )
+            .unwrap();
+        }
+
+        let counts = get_annotation_color_counts(&conn, "doc1").unwrap();
+        assert_eq!(
+            counts,
+            [
+                ColorCount { color: HighlightColor::Custom("#ff8800".into()), count: 3 },
+                ColorCount { color: HighlightColor::Yellow, count: 2 },
+                ColorCount { color: HighlightColor::Green, count: 1 },
+            ]
+        );
+
+        let yellow = get_annotations_by_color(&conn, "doc1", &HighlightColor::Yellow).unwrap();
+        assert_eq!(yellow.len(), 2);
+        assert!(yellow.iter().all(|a| a.highlight_color == Some(HighlightColor::Yellow)));
+
+        // Custom colors match regardless of hex case
+        let custom = HighlightColor::Custom("#FF8800".into());
+        let orange = get_annotations_by_color(&conn, "doc1", &custom).unwrap();
+        assert_eq!(orange.len(), 3);
+        assert!(get_annotations_by_color(&conn, "doc1", &HighlightColor::Red)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_page_notes_save_and_load_without_offsets() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute("INSERT INTO documents (id, file_path) VALUES ('doc1', 'paper.txt')", [])
+            .unwrap();
+
+        let highlight = Annotation::new(
+            "doc1".into(),
+            2,
+            5,
+            12,
+            "some text".into(),
+            Some(HighlightColor::Green),
+            None,
+        );
+        let page_note = Annotation::page_note("doc1".into(), 2, "Re-read this page".into());
+        insert_annotation(&conn, &highlight).unwrap();
+        insert_annotation(&conn, &page_note).unwrap();
+
+        let loaded = list_annotations(&conn, "doc1").unwrap();
+        assert_eq!(loaded.len(), 2);
+
+        let note = &loaded[0];
+        assert_eq!(note.id, page_note.id);
+        assert_eq!(note.kind, AnnotationKind::PageNote);
+        assert_eq!((note.start_offset, note.end_offset), (None, None));
+        assert_eq!(note.selected_text, "");
+        assert_eq!(note.note.as_deref(), Some("Re-read this page"));
+
+        assert_eq!(loaded[1].kind, AnnotationKind::Highlight);
+        assert_eq!((loaded[1].start_offset, loaded[1].end_offset), (Some(5), Some(12)));
+    }
+
+    #[test]
+    fn test_annotation_kind_migration_keeps_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE documents (
+                id TEXT PRIMARY KEY,
+                file_path TEXT NOT NULL,
+                last_opened TEXT
+            );
+            CREATE TABLE annotations (
+                id TEXT PRIMARY KEY,
+                document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+                page_number INTEGER NOT NULL,
+                paragraph_id TEXT,
+                start_offset INTEGER NOT NULL,
+                end_offset INTEGER NOT NULL,
+                selected_text TEXT,
+                highlight_color TEXT,
+                note TEXT,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO documents (id, file_path) VALUES ('doc1', 'paper.txt');
+            INSERT INTO annotations (id, document_id, page_number, start_offset, end_offset,
+                                     selected_text, highlight_color, note)
+            VALUES ('a1', 'doc1', 1, 0, 4, 'text', 'red', NULL),
+                   ('a2', 'doc1', 1, 6, 9, 'more', NULL, 'why?');
+            "#,
+        )
+        .unwrap();
+
+        run_migrations(&conn).unwrap();
+        // Idempotent on an already migrated table
+        run_migrations(&conn).unwrap();
+
+        let loaded = list_annotations(&conn, "doc1").unwrap();
+        let kinds: Vec<_> = loaded.iter().map(|a| (a.selected_text.as_str(), a.kind)).collect();
+        assert_eq!(
+            kinds,
+            [("text", AnnotationKind::Highlight), ("more", AnnotationKind::RangeNote)]
+        );
+
+        let page_note = Annotation::page_note("doc1".into(), 1, "Summary".into());
+        insert_annotation(&conn, &page_note).unwrap();
+        assert_eq!(list_annotations(&conn, "doc1").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_llm_provider_keys_moved_out_of_provider_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        let openai = llm_provider_key(&LLMProvider::OpenAI);
+        let groq = llm_provider_key(&LLMProvider::Groq);
+        conn.execute_batch(
+            r#"
+            CREATE TABLE llm_providers (
+                provider TEXT PRIMARY KEY,
+                model TEXT NOT NULL,
+                api_key TEXT,
+                api_url TEXT,
+                active INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE provider_keys (
+                provider TEXT PRIMARY KEY,
+                api_key TEXT NOT NULL,
+                updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+            "#,
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO llm_providers (provider, model, api_key, active)
+             VALUES (?1, 'gpt-4o', 'sk-old', 1), (?2, 'llama3', 'gsk-old', 0)",
+            params![openai, groq],
+        )
+        .unwrap();
+        save_provider_key(&conn, &LLMProvider::Groq, "gsk-new").unwrap();
+
+        run_migrations(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+
+        let mut keys = get_provider_keys(&conn).unwrap();
+        keys.sort_by_key(|(_, key)| key.clone());
+        assert_eq!(
+            keys,
+            [
+                (LLMProvider::Groq, "gsk-new".to_string()),
+                (LLMProvider::OpenAI, "sk-old".to_string())
+            ]
+        );
+        let active = get_active_llm_provider(&conn).unwrap().unwrap();
+        assert_eq!(active.model, "gpt-4o");
+        let has_api_key: bool = conn
+            .prepare("SELECT 1 FROM pragma_table_info('llm_providers') WHERE name = 'api_key'")
+            .and_then(|mut stmt| stmt.exists([]))
+            .unwrap();
+        assert!(!has_api_key);
+    }
 }
@@ -77,6 +77,11 @@ impl AudioCapture {
         Ok(rx)
     }
 
+    /// Flag the capture thread polls; clearing it stops capture from another task
+    pub fn recording_flag(&self) -> Arc<AtomicBool> {
+        self.is_recording.clone()
+    }
+
     /// Stop capturing audio
     pub fn stop_capture(&mut self) {
         self.is_recording.store(false, Ordering::SeqCst);
@@ -294,6 +299,38 @@ pub fn detect_voice_activity(samples: &[f32], threshold: f32) -> VadResult {
     }
 }
 
+/// RMS energy above which captured audio counts as speech
+pub const VAD_THRESHOLD: f32 = 0.01;
+
+/// Measures how long captured audio has gone without speech. Time is counted in
+/// samples rather than wall-clock time, so it follows the audio actually captured.
+#[derive(Debug, Clone)]
+pub struct IdleTimer {
+    timeout_ms: u64,
+    sample_rate: u32,
+    silent_samples: u64,
+}
+
+impl IdleTimer {
+    pub fn new(timeout: std::time::Duration, sample_rate: u32) -> Self {
+        Self {
+            timeout_ms: timeout.as_millis() as u64,
+            sample_rate,
+            silent_samples: 0,
+        }
+    }
+
+    /// Account for a chunk of captured mono audio. Returns true once silence has
+    /// lasted the whole timeout; any speech restarts the count.
+    pub fn observe(&mut self, samples: &[f32]) -> bool {
+        match detect_voice_activity(samples, VAD_THRESHOLD) {
+            VadResult::Speech => self.silent_samples = 0,
+            VadResult::Silence => self.silent_samples += samples.len() as u64,
+        }
+        self.silent_samples * 1000 >= self.timeout_ms * self.sample_rate as u64
+    }
+}
+
 /// Resample audio to target sample rate
 pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
     if from_rate == to_rate {
@@ -424,6 +461,23 @@ impl AudioBuffer {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_idle_timer_counts_silent_audio_time() {
+        let mut timer = IdleTimer::new(std::time::Duration::from_secs(1), 16000);
+        let silence = vec![0.0f32; 4000];
+        let speech = vec![0.5f32; 4000];
+
+        // 0.75s of silence, then speech restarts the count
+        for _ in 0..3 {
+            assert!(!timer.observe(&silence));
+        }
+        assert!(!timer.observe(&speech));
+        for _ in 0..3 {
+            assert!(!timer.observe(&silence));
+        }
+        assert!(timer.observe(&silence));
+    }
+
     #[test]
     fn test_vad() {
         let silence = vec![0.0f32; 100];
@@ -0,0 +1,248 @@
+//! Narrating a whole document as an audiobook, one audio file per chapter
+
+use super::audio::{f32_to_i16, resample, stereo_to_mono};
+use super::captions::vtt_timestamp;
+use super::{apply_pronunciations, prepare_narration, NarrationOptions, Pronunciation};
+use super::{TextToSpeech, VoiceError};
+use crate::document::{segment_sections, Document, Section};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Silence between paragraphs of a chapter, in milliseconds
+const PARAGRAPH_PAUSE_MS: u64 = 400;
+/// Longest chapter title kept in a file name, in characters
+const MAX_TITLE_CHARS: usize = 48;
+/// Name of the chapter index written next to the chapter files
+pub const CHAPTER_INDEX_FILE: &str = "chapters.txt";
+
+/// A chapter written to its own audio file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChapterAudio {
+    /// 1-based position of the chapter in the book
+    pub number: usize,
+    pub title: String,
+    pub path: String,
+    /// Where the chapter starts when the files are played back to back
+    pub start_ms: u64,
+    pub duration_ms: u64,
+}
+
+/// Chapters of `doc`: each top-level section together with the subsections under it.
+/// Sections found without headings are chapters of their own.
+pub fn chapters(doc: &Document) -> Vec<Section> {
+    let sections = segment_sections(doc);
+    let top = sections.iter().map(|s| s.level).filter(|&level| level > 0).min();
+
+    let mut chapters: Vec<Section> = Vec::new();
+    for section in sections {
+        match chapters.last_mut() {
+            Some(chapter) if top.is_some_and(|top| section.level > top) => {
+                chapter.end_paragraph = section.end_paragraph;
+                chapter.paragraph_ids.extend(section.paragraph_ids);
+            }
+            _ => chapters.push(section),
+        }
+    }
+    chapters
+}
+
+/// File name for a chapter, e.g. `chapter_02_related_work.wav`
+pub fn chapter_file_name(number: usize, title: &str, extension: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    let slug: String = slug.chars().take(MAX_TITLE_CHARS).collect();
+    let slug = match slug.trim_end_matches('_') {
+        "" => "untitled",
+        slug => slug,
+    };
+    format!("chapter_{:02}_{}.{}", number, slug, extension)
+}
+
+/// Narrate each chapter of `doc` with `tts` and write it to `output_dir` as a WAV file.
+/// Chapters with nothing left to read once narration options are applied are skipped.
+pub async fn narrate_chapters(
+    tts: &dyn TextToSpeech,
+    doc: &Document,
+    options: &NarrationOptions,
+    pronunciations: &[Pronunciation],
+    output_dir: &Path,
+) -> Result<Vec<ChapterAudio>, VoiceError> {
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut written: Vec<ChapterAudio> = Vec::new();
+    for chapter in chapters(doc) {
+        let paragraphs: Vec<String> = chapter
+            .text(doc)
+            .split("\n\n")
+            .map(|p| apply_pronunciations(&prepare_narration(p, options), pronunciations))
+            .filter(|p| !p.trim().is_empty())
+            .collect();
+        if paragraphs.is_empty() {
+            continue;
+        }
+
+        let mut samples = Vec::new();
+        let mut sample_rate = None;
+        for paragraph in &paragraphs {
+            let audio = tts.synthesize(paragraph).await?;
+            let mono = match audio.channels {
+                2 => stereo_to_mono(&audio.samples),
+                _ => audio.samples,
+            };
+            // The first paragraph sets the rate the rest of the chapter is written at
+            let rate = *sample_rate.get_or_insert(audio.sample_rate);
+            if !samples.is_empty() {
+                let pause = (u64::from(rate) * PARAGRAPH_PAUSE_MS / 1000) as usize;
+                samples.resize(samples.len() + pause, 0.0);
+            }
+            samples.extend(resample(&mono, audio.sample_rate, rate));
+        }
+        let sample_rate = sample_rate.unwrap_or(22050);
+
+        let number = written.len() + 1;
+        let path = output_dir.join(chapter_file_name(number, &chapter.title, "wav"));
+        write_wav(&path, &samples, sample_rate)?;
+
+        let start_ms = written.last().map_or(0, |c| c.start_ms + c.duration_ms);
+        written.push(ChapterAudio {
+            number,
+            title: chapter.title,
+            path: path.to_string_lossy().into_owned(),
+            start_ms,
+            duration_ms: samples.len() as u64 * 1000 / u64::from(sample_rate.max(1)),
+        });
+    }
+    Ok(written)
+}
+
+/// Write mono samples as a 16-bit PCM WAV file
+fn write_wav(path: &Path, samples: &[f32], sample_rate: u32) -> Result<(), VoiceError> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let audio_error = |e: hound::Error| VoiceError::AudioError(e.to_string());
+
+    let mut writer = hound::WavWriter::create(path, spec).map_err(audio_error)?;
+    for sample in f32_to_i16(samples) {
+        writer.write_sample(sample).map_err(audio_error)?;
+    }
+    writer.finalize().map_err(audio_error)
+}
+
+/// Chapter list in the `HH:MM:SS.mmm Title` form m4b tools read when the chapter files
+/// are joined into one audiobook
+pub fn chapter_index(chapters: &[ChapterAudio]) -> String {
+    chapters
+        .iter()
+        .map(|c| format!("{} {}\n", vtt_timestamp(c.start_ms), c.title))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::providers::VoiceInfo;
+    use crate::voice::{AudioChunk, AudioData, WordTiming};
+    use tokio::sync::mpsc;
+
+    /// TTS producing 10 ms of audio per character
+    struct LengthTts;
+
+    #[async_trait::async_trait]
+    impl TextToSpeech for LengthTts {
+        async fn synthesize(&self, text: &str) -> Result<AudioData, VoiceError> {
+            Ok(AudioData {
+                samples: vec![0.25; text.chars().count() * 160],
+                sample_rate: 16000,
+                channels: 1,
+            })
+        }
+
+        async fn synthesize_stream(
+            &self,
+            _text: &str,
+        ) -> Result<mpsc::Receiver<AudioChunk>, VoiceError> {
+            Ok(mpsc::channel(1).1)
+        }
+
+        async fn get_word_timings(&self, _text: &str) -> Result<Vec<WordTiming>, VoiceError> {
+            Ok(Vec::new())
+        }
+
+        async fn stop(&mut self) -> Result<(), VoiceError> {
+            Ok(())
+        }
+
+        fn available_voices(&self) -> Vec<VoiceInfo> {
+            Vec::new()
+        }
+
+        fn set_rate(&mut self, _rate: f32) {}
+
+        fn set_voice(&mut self, _voice_id: &str) -> Result<(), VoiceError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_two_chapters_written_to_named_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("book.md");
+        std::fs::write(
+            &source,
+            "# Chapter 1: Beginnings\n\nIt was a quiet morning.\n\n## A Detour\n\n\
+             The road turned west.\n\n# Chapter 2: The End?\n\nEverything was still.",
+        )
+        .unwrap();
+        let doc = crate::document::parser::parse_document(source.to_str().unwrap())
+            .await
+            .unwrap();
+        let output = dir.path().join("narration");
+
+        let written = narrate_chapters(&LengthTts, &doc, &NarrationOptions::default(), &[], &output)
+            .await
+            .unwrap();
+
+        let names: Vec<String> = written
+            .iter()
+            .map(|c| Path::new(&c.path).file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            ["chapter_01_chapter_1_beginnings.wav", "chapter_02_chapter_2_the_end.wav"]
+        );
+        assert_eq!(written[1].title, "Chapter 2: The End?");
+
+        for chapter in &written {
+            let reader = hound::WavReader::open(&chapter.path).unwrap();
+            assert_eq!(reader.spec().sample_rate, 16000);
+            assert_eq!(u64::from(reader.duration()) * 1000 / 16000, chapter.duration_ms);
+        }
+        // The first chapter holds its subsection, so it is the longer one
+        assert!(written[0].duration_ms > written[1].duration_ms);
+        assert_eq!(written[1].start_ms, written[0].duration_ms);
+        assert_eq!(
+            chapter_index(&written),
+            format!(
+                "00:00:00.000 Chapter 1: Beginnings\n{} Chapter 2: The End?\n",
+                vtt_timestamp(written[0].duration_ms)
+            )
+        );
+    }
+
+    #[test]
+    fn test_chapter_file_names_are_safe() {
+        assert_eq!(chapter_file_name(3, "  Über / Alles!  ", "wav"), "chapter_03_über_alles.wav");
+        assert_eq!(chapter_file_name(12, "???", "wav"), "chapter_12_untitled.wav");
+        assert!(chapter_file_name(1, &"long ".repeat(40), "wav").len() < 70);
+    }
+}
@@ -0,0 +1,160 @@
+//! Measuring how fast the current voice really speaks, so a target words-per-minute
+//! maps onto the right speaking rate
+
+use super::{TTSProvider, TextToSpeech, VoiceError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// Passage timed during calibration; plain prose so the pace matches ordinary reading
+pub const CALIBRATION_SAMPLE: &str = "Reading aloud at a steady pace helps the listener \
+    follow the argument of a document. Each sentence is spoken clearly, with short pauses \
+    between ideas, so that the meaning of the text is easy to understand. This passage is \
+    used to measure how quickly the selected voice speaks.";
+
+/// Words per minute at 1.0x that a working voice can plausibly measure
+const PLAUSIBLE_WPM: RangeInclusive<f32> = 60.0..=400.0;
+
+/// Measured speaking pace of a TTS voice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WpmCalibration {
+    /// Voice the measurement was taken with
+    pub provider: TTSProvider,
+    /// Words per minute at a 1.0x speaking rate
+    pub base_wpm: f32,
+    pub words: usize,
+    /// Length of the synthesized sample
+    pub duration_ms: u64,
+    pub calibrated_at: DateTime<Utc>,
+}
+
+/// Synthesize `CALIBRATION_SAMPLE` at 1.0x with `tts`, the engine for `provider`, and
+/// work out its words per minute from the length of the audio
+pub async fn calibrate(
+    tts: &mut dyn TextToSpeech,
+    provider: &TTSProvider,
+) -> Result<WpmCalibration, VoiceError> {
+    tts.set_rate(1.0);
+    let audio = tts.synthesize(CALIBRATION_SAMPLE).await?;
+
+    let frames = audio.samples.len() as f64 / f64::from(audio.channels.max(1));
+    let seconds = frames / f64::from(audio.sample_rate.max(1));
+    if seconds <= 0.0 {
+        return Err(VoiceError::TTSError("Calibration produced no audio".to_string()));
+    }
+
+    let words = CALIBRATION_SAMPLE.split_whitespace().count();
+    let base_wpm = (words as f64 * 60.0 / seconds) as f32;
+    if !PLAUSIBLE_WPM.contains(&base_wpm) {
+        return Err(VoiceError::TTSError(format!(
+            "Calibration measured an implausible {:.0} words per minute",
+            base_wpm
+        )));
+    }
+
+    Ok(WpmCalibration {
+        provider: provider.clone(),
+        base_wpm,
+        words,
+        duration_ms: (seconds * 1000.0) as u64,
+        calibrated_at: Utc::now(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::providers::VoiceInfo;
+    use crate::voice::{AudioChunk, AudioData, VoiceConfig, WordTiming};
+    use tokio::sync::mpsc;
+
+    /// TTS speaking `wpm` words per minute at 1.0x, faster or slower with its rate
+    struct TimedTts {
+        wpm: f32,
+        rate: f32,
+    }
+
+    #[async_trait::async_trait]
+    impl TextToSpeech for TimedTts {
+        async fn synthesize(&self, text: &str) -> Result<AudioData, VoiceError> {
+            let words = text.split_whitespace().count() as f32;
+            let seconds = words * 60.0 / (self.wpm * self.rate);
+            Ok(AudioData {
+                samples: vec![0.0; (seconds * 22050.0) as usize],
+                sample_rate: 22050,
+                channels: 1,
+            })
+        }
+
+        async fn synthesize_stream(
+            &self,
+            _text: &str,
+        ) -> Result<mpsc::Receiver<AudioChunk>, VoiceError> {
+            Ok(mpsc::channel(1).1)
+        }
+
+        async fn get_word_timings(&self, _text: &str) -> Result<Vec<WordTiming>, VoiceError> {
+            Ok(Vec::new())
+        }
+
+        async fn stop(&mut self) -> Result<(), VoiceError> {
+            Ok(())
+        }
+
+        fn available_voices(&self) -> Vec<VoiceInfo> {
+            Vec::new()
+        }
+
+        fn set_rate(&mut self, rate: f32) {
+            self.rate = rate;
+        }
+
+        fn set_voice(&mut self, _voice_id: &str) -> Result<(), VoiceError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calibration_measures_pace_at_normal_rate() {
+        let provider = TTSProvider::ESpeakNG {
+            voice: "en".to_string(),
+        };
+        let mut tts = TimedTts {
+            wpm: 190.0,
+            rate: 2.0,
+        };
+
+        let calibration = calibrate(&mut tts, &provider).await.unwrap();
+        assert!((calibration.base_wpm - 190.0).abs() < 1.0, "{}", calibration.base_wpm);
+        assert!(PLAUSIBLE_WPM.contains(&calibration.base_wpm));
+        assert_eq!(calibration.words, CALIBRATION_SAMPLE.split_whitespace().count());
+
+        let mut garbled = TimedTts {
+            wpm: 5000.0,
+            rate: 1.0,
+        };
+        assert!(matches!(
+            calibrate(&mut garbled, &provider).await,
+            Err(VoiceError::TTSError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_wpm_conversions_use_calibration_for_its_voice() {
+        let mut config = VoiceConfig::default();
+        let mut tts = TimedTts {
+            wpm: 220.0,
+            rate: 1.0,
+        };
+        config.wpm_calibration = Some(calibrate(&mut tts, &config.tts_provider).await.unwrap());
+
+        assert!((config.wpm_to_rate(220.0) - 1.0).abs() < 0.01);
+        assert!((config.rate_to_wpm(1.5) - 330.0).abs() < 1.0);
+
+        // A calibration taken with another voice no longer applies
+        config.tts_provider = TTSProvider::ESpeakNG {
+            voice: "en".to_string(),
+        };
+        assert_eq!(config.base_wpm(), config.tts_provider.base_wpm());
+    }
+}
@@ -0,0 +1,187 @@
+//! Live captions built from the word timings of text being read aloud
+
+use super::WordTiming;
+use serde::{Deserialize, Serialize};
+
+/// Most words shown in one caption cue
+const MAX_CUE_WORDS: usize = 7;
+/// Longest a cue stays on screen, in milliseconds
+const MAX_CUE_MS: u64 = 3500;
+
+/// A group of consecutive words shown together as one caption
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CaptionCue {
+    /// 1-based position of the cue in the reading
+    pub index: usize,
+    /// Milliseconds from the start of reading
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+/// Groups timed words into caption cues as they are read, keeping every finished cue
+/// so the whole reading can be written out as WebVTT at the end
+#[derive(Debug, Default)]
+pub struct CaptionBuilder {
+    pending: Vec<WordTiming>,
+    cues: Vec<CaptionCue>,
+}
+
+impl CaptionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the next word read, returning a cue if this word completes one. A cue ends
+    /// at a sentence end, or when it reaches the word or duration limit.
+    pub fn push(&mut self, word: &WordTiming) -> Option<CaptionCue> {
+        if let Some(first) = self.pending.first() {
+            // Keep the cue within its duration limit by starting a new one
+            if word.end_ms.saturating_sub(first.start_ms) > MAX_CUE_MS {
+                let cue = self.flush();
+                self.pending.push(word.clone());
+                return cue;
+            }
+        }
+
+        self.pending.push(word.clone());
+        let sentence_end = word.word.ends_with(['.', '!', '?']);
+        if sentence_end || self.pending.len() >= MAX_CUE_WORDS {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Close the cue in progress, if any, once reading has ended
+    pub fn finish(&mut self) -> Option<CaptionCue> {
+        self.flush()
+    }
+
+    /// Every cue completed so far, in reading order
+    pub fn cues(&self) -> &[CaptionCue] {
+        &self.cues
+    }
+
+    fn flush(&mut self) -> Option<CaptionCue> {
+        let first = self.pending.first()?;
+        let last = self.pending.last()?;
+        // Cues never overlap, even if the engine's word timings do
+        let previous_end = self.cues.last().map_or(0, |cue| cue.end_ms);
+        let start_ms = first.start_ms.max(previous_end);
+        let cue = CaptionCue {
+            index: self.cues.len() + 1,
+            start_ms,
+            end_ms: last.end_ms.max(start_ms),
+            text: self
+                .pending
+                .iter()
+                .map(|w| w.word.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        self.pending.clear();
+        self.cues.push(cue.clone());
+        Some(cue)
+    }
+}
+
+/// WebVTT timestamp (`HH:MM:SS.mmm`) for a time in milliseconds
+pub fn vtt_timestamp(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// A complete WebVTT file for `cues`
+pub fn to_webvtt(cues: &[CaptionCue]) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for cue in cues {
+        vtt.push_str(&format!(
+            "\n{}\n{} --> {}\n{}\n",
+            cue.index,
+            vtt_timestamp(cue.start_ms),
+            vtt_timestamp(cue.end_ms),
+            cue.text
+        ));
+    }
+    vtt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn timed_words(text: &str, ms_per_word: u64) -> Vec<WordTiming> {
+        text.split_whitespace()
+            .enumerate()
+            .map(|(i, word)| WordTiming {
+                word: word.to_string(),
+                start_ms: ms_per_word * i as u64,
+                end_ms: ms_per_word * (i as u64 + 1) - 20,
+                confidence: 1.0,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_cues_emitted_in_order_with_valid_timecodes() {
+        let words = timed_words(
+            "Attention is all you need. The dominant sequence transduction models are \
+             based on complex recurrent or convolutional networks",
+            400,
+        );
+        let (tx, mut rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            for word in words {
+                tx.send(word).await.unwrap();
+            }
+        });
+
+        let mut captions = CaptionBuilder::new();
+        let mut emitted = Vec::new();
+        while let Some(word) = rx.recv().await {
+            emitted.extend(captions.push(&word));
+        }
+        emitted.extend(captions.finish());
+
+        let texts: Vec<_> = emitted.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "Attention is all you need.",
+                "The dominant sequence transduction models are based",
+                "on complex recurrent or convolutional networks",
+            ]
+        );
+        assert_eq!(emitted, captions.cues());
+        for (i, cue) in emitted.iter().enumerate() {
+            assert_eq!(cue.index, i + 1);
+            assert!(cue.start_ms < cue.end_ms);
+            assert!(cue.end_ms - cue.start_ms <= MAX_CUE_MS);
+        }
+        assert!(emitted.windows(2).all(|w| w[0].end_ms <= w[1].start_ms));
+
+        let vtt = to_webvtt(&emitted);
+        assert!(vtt.starts_with("WEBVTT\n\n1\n00:00:00.000 --> 00:00:01.980\nAttention"));
+        assert!(vtt.contains("\n3\n00:00:04.800 --> 00:00:07.180\non complex"));
+    }
+
+    #[test]
+    fn test_slow_words_split_by_duration() {
+        let mut captions = CaptionBuilder::new();
+        for word in timed_words("one two three four", 1500) {
+            captions.push(&word);
+        }
+        captions.finish();
+
+        let texts: Vec<_> = captions.cues().iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["one two", "three four"]);
+        assert_eq!(vtt_timestamp(3_725_042), "01:02:05.042");
+    }
+}
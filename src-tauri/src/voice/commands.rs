@@ -13,6 +13,11 @@ pub enum VoiceCommand {
         content: String,
     },
 
+    /// "Bookmark this" / "Mark this as [label]" - Save the current reading position
+    Bookmark {
+        label: Option<String>,
+    },
+
     /// "Highlight this" - Highlight current selection/sentence
     Highlight {
         color: Option<String>,
@@ -155,6 +160,11 @@ impl VoiceCommandParser {
             return VoiceCommand::AskQuestion { question };
         }
 
+        // Bookmark command
+        if let Some(label) = self.parse_bookmark_command(&lower, text) {
+            return VoiceCommand::Bookmark { label };
+        }
+
         // Highlight command
         if let Some(color) = self.parse_highlight_command(&lower) {
             return VoiceCommand::Highlight { color };
@@ -273,6 +283,37 @@ impl VoiceCommandParser {
         None
     }
 
+    /// Parse bookmark command, with an optional label after "as", "called" or ':'
+    fn parse_bookmark_command(&self, lower: &str, original: &str) -> Option<Option<String>> {
+        let prefixes = [
+            "add a bookmark",
+            "add bookmark",
+            "bookmark this",
+            "bookmark",
+            "mark this",
+            "mark here",
+        ];
+
+        // Whole words only, so "bookmarks are useful" is not a command
+        let prefix = prefixes.iter().find(|p| {
+            lower
+                .strip_prefix(*p)
+                .is_some_and(|rest| !rest.starts_with(char::is_alphanumeric))
+        })?;
+        let rest = original[prefix.len()..].trim_start();
+
+        let rest_lower = rest.to_lowercase();
+        let label = ["as ", "called ", "named ", ":"]
+            .iter()
+            .find(|marker| rest_lower.starts_with(*marker))
+            .map(|marker| rest[marker.len()..].trim())
+            .unwrap_or(rest)
+            .trim_end_matches(['.', '!'])
+            .trim();
+
+        Some((!label.is_empty()).then(|| label.to_string()))
+    }
+
     /// Parse highlight command
     fn parse_highlight_command(&self, lower: &str) -> Option<Option<String>> {
         if lower.contains("highlight") {
@@ -633,6 +674,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bookmark_commands() {
+        let parser = VoiceCommandParser::default();
+        let label = |text: &str| match parser.parse(text) {
+            VoiceCommand::Bookmark { label } => label,
+            other => panic!("Expected Bookmark for {:?}, got {:?}", text, other),
+        };
+
+        assert_eq!(label("Bookmark this"), None);
+        assert_eq!(label("mark this."), None);
+        assert_eq!(label("Bookmark this as Key Proof"), Some("Key Proof".to_string()));
+        assert_eq!(label("bookmark: lemma 3"), Some("lemma 3".to_string()));
+        assert_eq!(label("add a bookmark called ending"), Some("ending".to_string()));
+
+        assert!(matches!(parser.parse("bookmarks are useful"), VoiceCommand::FreeText { .. }));
+    }
+
     #[test]
     fn test_question_detection() {
         let parser = VoiceCommandParser::default();
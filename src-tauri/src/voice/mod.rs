@@ -7,16 +7,26 @@
 //! - Reading position synchronization
 
 pub mod audio;
+pub mod audiobook;
+pub mod calibration;
+pub mod captions;
 pub mod commands;
+pub mod narration;
 pub mod providers;
+pub mod self_test;
 
+use crate::document::{paragraph_words, ParagraphWord};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, oneshot, RwLock};
 
+pub use calibration::WpmCalibration;
+pub use captions::{CaptionBuilder, CaptionCue};
 pub use commands::{SummarizeScope, VoiceCommand, VoiceCommandParser};
+pub use narration::{apply_pronunciations, prepare_narration, NarrationOptions, Pronunciation};
 pub use providers::{STTProvider, TTSProvider, SpeechToText, TextToSpeech};
+pub use self_test::{SelfTestStage, VoiceSelfTestReport};
 
 // ============================================================================
 // Configuration
@@ -45,6 +55,49 @@ pub struct VoiceConfig {
     pub noise_suppression: bool,
     /// Continuous listening mode
     pub continuous_listening: bool,
+    /// Seconds without detected speech before listening stops on its own (0 = never)
+    #[serde(default = "default_listening_timeout_secs")]
+    pub listening_timeout_secs: u64,
+    /// Minimum transcription confidence (0.0 to 1.0) for dictated notes
+    #[serde(default = "default_note_confidence_threshold")]
+    pub note_confidence_threshold: f32,
+    /// Content skipped or abbreviated when reading aloud
+    #[serde(default)]
+    pub narration: NarrationOptions,
+    /// Measured pace of the TTS voice, preferred over the provider's estimate
+    #[serde(default)]
+    pub wpm_calibration: Option<WpmCalibration>,
+}
+
+impl VoiceConfig {
+    /// Words per minute at a 1.0x rate: the calibrated pace if it was measured with the
+    /// current voice, otherwise the provider's estimate
+    pub fn base_wpm(&self) -> f32 {
+        match &self.wpm_calibration {
+            Some(calibration) if calibration.provider == self.tts_provider => {
+                calibration.base_wpm
+            }
+            _ => self.tts_provider.base_wpm(),
+        }
+    }
+
+    /// Convert a target words-per-minute into a speaking rate multiplier
+    pub fn wpm_to_rate(&self, wpm: f32) -> f32 {
+        (wpm / self.base_wpm()).clamp(0.25, 3.0)
+    }
+
+    /// Convert a speaking rate multiplier into words per minute
+    pub fn rate_to_wpm(&self, rate: f32) -> f32 {
+        rate * self.base_wpm()
+    }
+}
+
+fn default_note_confidence_threshold() -> f32 {
+    0.6
+}
+
+fn default_listening_timeout_secs() -> u64 {
+    30
 }
 
 impl Default for VoiceConfig {
@@ -65,6 +118,10 @@ impl Default for VoiceConfig {
             auto_punctuation: true,
             noise_suppression: true,
             continuous_listening: false,
+            listening_timeout_secs: default_listening_timeout_secs(),
+            note_confidence_threshold: default_note_confidence_threshold(),
+            narration: NarrationOptions::default(),
+            wpm_calibration: None,
         }
     }
 }
@@ -114,7 +171,7 @@ impl WhisperModel {
 // ============================================================================
 
 /// Reading position for cursor synchronization
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
 pub struct ReadingPosition {
     /// Document ID
     pub document_id: String,
@@ -124,7 +181,7 @@ pub struct ReadingPosition {
     pub paragraph_id: String,
     /// Word index within the paragraph
     pub word_index: u32,
-    /// Character offset within the word
+    /// Character offset of the word within the paragraph
     pub character_offset: u32,
     /// Timestamp in milliseconds from start of reading
     pub timestamp_ms: u64,
@@ -162,6 +219,31 @@ pub struct TranscriptionResult {
     pub words: Vec<WordTiming>,
 }
 
+impl TranscriptionResult {
+    /// Words transcribed with confidence below `threshold`
+    pub fn low_confidence_words(&self, threshold: f32) -> Vec<String> {
+        self.words
+            .iter()
+            .filter(|w| w.confidence < threshold)
+            .map(|w| w.word.clone())
+            .collect()
+    }
+
+    /// Whether the transcription is reliable enough to act on without confirmation.
+    ///
+    /// Both the overall confidence and the mean word confidence must meet `threshold`.
+    pub fn is_confident(&self, threshold: f32) -> bool {
+        if self.confidence < threshold {
+            return false;
+        }
+        if self.words.is_empty() {
+            return true;
+        }
+        let mean = self.words.iter().map(|w| w.confidence).sum::<f32>() / self.words.len() as f32;
+        mean >= threshold
+    }
+}
+
 // ============================================================================
 // Audio Data
 // ============================================================================
@@ -213,6 +295,10 @@ pub enum VoiceAction {
         content: String,
         color: Option<String>,
     },
+    /// A bookmark was saved at the current position
+    BookmarkCreated {
+        bookmark: crate::annotation::Bookmark,
+    },
     /// Add a highlight at the current position
     AddHighlight {
         position: ReadingPosition,
@@ -236,6 +322,11 @@ pub enum VoiceAction {
     AdjustSpeed {
         speed: f32,
     },
+    /// Ask the user to repeat unclear dictation
+    RequestRepeat {
+        heard: String,
+        unclear_words: Vec<String>,
+    },
 }
 
 // ============================================================================
@@ -257,6 +348,37 @@ pub enum VoiceState {
     Reading,
 }
 
+/// Reading position for the `index`-th timed word of spoken content, exactly as
+/// emitted while that word is read. `words` places each spoken word in its paragraph;
+/// words past its end continue the last paragraph, or `start`'s if it is empty.
+/// Returns `None` past the last word.
+pub fn position_from_word_timing(
+    start: &ReadingPosition,
+    words: &[ParagraphWord],
+    index: usize,
+    timings: &[WordTiming],
+) -> Option<ReadingPosition> {
+    let timing = timings.get(index)?;
+    let (paragraph_id, word_index, character_offset) = match (words.get(index), words.last()) {
+        (Some(word), _) => (word.paragraph_id.clone(), word.word_index, word.character_offset),
+        (None, Some(last)) => (
+            last.paragraph_id.clone(),
+            last.word_index + (index - words.len()) as u32 + 1,
+            last.character_offset,
+        ),
+        (None, None) => (start.paragraph_id.clone(), index as u32, 0),
+    };
+
+    Some(ReadingPosition {
+        document_id: start.document_id.clone(),
+        page: start.page,
+        paragraph_id,
+        word_index,
+        character_offset,
+        timestamp_ms: timing.start_ms,
+    })
+}
+
 /// Voice interaction manager
 pub struct VoiceManager {
     /// Configuration
@@ -269,12 +391,19 @@ pub struct VoiceManager {
     command_parser: VoiceCommandParser,
     /// Current reading position
     current_position: Arc<RwLock<Option<ReadingPosition>>>,
+    /// Word timings of the content last passed to `read_content`
+    word_timings: Vec<WordTiming>,
+    /// Where that content starts, and where each of its words sits in its paragraph
+    spoken_start: Option<ReadingPosition>,
+    spoken_words: Vec<ParagraphWord>,
     /// Current state
     state: Arc<RwLock<VoiceState>>,
     /// Transcription sender
     transcription_tx: Option<mpsc::Sender<TranscriptionResult>>,
     /// Position update sender
     position_tx: Option<mpsc::Sender<ReadingPosition>>,
+    /// Spoken forms substituted before synthesis
+    pronunciations: Vec<Pronunciation>,
 }
 
 impl VoiceManager {
@@ -288,12 +417,28 @@ impl VoiceManager {
             tts: None,
             command_parser,
             current_position: Arc::new(RwLock::new(None)),
+            word_timings: Vec::new(),
+            spoken_start: None,
+            spoken_words: Vec::new(),
             state: Arc::new(RwLock::new(VoiceState::Idle)),
             transcription_tx: None,
             position_tx: None,
+            pronunciations: Vec::new(),
         }
     }
 
+    /// Create a voice manager with already-constructed providers
+    pub fn with_providers(
+        config: VoiceConfig,
+        stt: Box<dyn SpeechToText>,
+        tts: Box<dyn TextToSpeech>,
+    ) -> Self {
+        let mut manager = Self::new(config);
+        manager.stt = Some(stt);
+        manager.tts = Some(tts);
+        manager
+    }
+
     /// Initialize providers based on configuration
     pub async fn initialize(&mut self) -> Result<(), VoiceError> {
         // Initialize STT provider
@@ -313,6 +458,16 @@ impl VoiceManager {
 
     /// Start listening for voice input
     pub async fn start_listening(&mut self) -> Result<mpsc::Receiver<TranscriptionResult>, VoiceError> {
+        let (rx, _timed_out) = self.start_listening_with_timeout().await?;
+        Ok(rx)
+    }
+
+    /// Start listening for voice input, also returning a receiver that fires if
+    /// listening stops on its own after `listening_timeout_secs` without speech.
+    /// The manager is back in `Idle` by the time it fires.
+    pub async fn start_listening_with_timeout(
+        &mut self,
+    ) -> Result<(mpsc::Receiver<TranscriptionResult>, oneshot::Receiver<()>), VoiceError> {
         let stt = self.stt.as_mut().ok_or(VoiceError::NotInitialized)?;
 
         let mut state = self.state.write().await;
@@ -322,9 +477,30 @@ impl VoiceManager {
         *state = VoiceState::Listening;
         drop(state);
 
+        // Without a timeout the sender is dropped and the watcher below never fires
+        let (provider_tx, provider_rx) = oneshot::channel();
+        if self.config.listening_timeout_secs > 0 {
+            let timeout = std::time::Duration::from_secs(self.config.listening_timeout_secs);
+            stt.set_idle_timeout(timeout, provider_tx);
+        }
+
         let rx = stt.start_listening().await?;
+
+        let (timed_out_tx, timed_out_rx) = oneshot::channel();
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            if provider_rx.await.is_ok() {
+                let mut s = state.write().await;
+                if *s == VoiceState::Listening {
+                    *s = VoiceState::Idle;
+                }
+                drop(s);
+                let _ = timed_out_tx.send(());
+            }
+        });
+
         tracing::info!("Started voice listening");
-        Ok(rx)
+        Ok((rx, timed_out_rx))
     }
 
     /// Stop listening for voice input
@@ -350,11 +526,53 @@ impl VoiceManager {
         &mut self,
         content: &str,
         start_position: ReadingPosition,
+    ) -> Result<mpsc::Receiver<ReadingPosition>, VoiceError> {
+        let content = prepare_narration(content, &self.config.narration);
+        let words = paragraph_words(&start_position.paragraph_id, &content);
+        let rx = self
+            .speak_timed(&content, start_position, words, VoiceState::Reading)
+            .await?;
+
+        tracing::info!("Started reading content");
+        Ok(rx)
+    }
+
+    /// Read a short selection aloud with cursor synchronization. Unlike `read_content`
+    /// the text is spoken as selected, and the manager is `Speaking` rather than `Reading`.
+    /// `words` places each selected word in its paragraph, as the selection may span several.
+    pub async fn speak_selection(
+        &mut self,
+        text: &str,
+        start_position: ReadingPosition,
+        words: Vec<ParagraphWord>,
+    ) -> Result<mpsc::Receiver<ReadingPosition>, VoiceError> {
+        if text.trim().is_empty() {
+            return Err(VoiceError::InvalidState("Nothing selected to read".to_string()));
+        }
+
+        let rx = self
+            .speak_timed(text, start_position, words, VoiceState::Speaking)
+            .await?;
+
+        tracing::info!("Started reading selection");
+        Ok(rx)
+    }
+
+    /// Synthesize `content` and report each word's position while the manager stays in
+    /// `active`, returning to `Idle` after the last word
+    async fn speak_timed(
+        &mut self,
+        content: &str,
+        start_position: ReadingPosition,
+        words: Vec<ParagraphWord>,
+        active: VoiceState,
     ) -> Result<mpsc::Receiver<ReadingPosition>, VoiceError> {
         let tts = self.tts.as_mut().ok_or(VoiceError::NotInitialized)?;
+        let content = apply_pronunciations(content, &self.pronunciations);
+        let content = content.as_str();
 
         let mut state = self.state.write().await;
-        *state = VoiceState::Reading;
+        *state = active;
         drop(state);
 
         // Store starting position
@@ -368,6 +586,9 @@ impl VoiceManager {
 
         // Get word timings from TTS
         let word_timings = tts.get_word_timings(content).await?;
+        self.word_timings = word_timings.clone();
+        self.spoken_start = Some(start_position.clone());
+        self.spoken_words = words.clone();
 
         // Start synthesis and playback
         let audio_rx = tts.synthesize_stream(content).await?;
@@ -375,15 +596,11 @@ impl VoiceManager {
         // Spawn task to handle position updates
         let current_position = self.current_position.clone();
         let state = self.state.clone();
-        let document_id = start_position.document_id.clone();
-        let page = start_position.page;
-        let paragraph_id = start_position.paragraph_id.clone();
 
         tokio::spawn(async move {
-            let mut word_index = 0u32;
             let start_time = std::time::Instant::now();
 
-            for timing in word_timings {
+            for (word_index, timing) in word_timings.iter().enumerate() {
                 // Wait until it's time for this word
                 let target_time = std::time::Duration::from_millis(timing.start_ms);
                 let elapsed = start_time.elapsed();
@@ -392,18 +609,15 @@ impl VoiceManager {
                 }
 
                 // Check if still reading
-                if *state.read().await != VoiceState::Reading {
+                if *state.read().await != active {
                     break;
                 }
 
                 // Update position
-                let position = ReadingPosition {
-                    document_id: document_id.clone(),
-                    page,
-                    paragraph_id: paragraph_id.clone(),
-                    word_index,
-                    character_offset: 0,
-                    timestamp_ms: timing.start_ms,
+                let Some(position) =
+                    position_from_word_timing(&start_position, &words, word_index, &word_timings)
+                else {
+                    break;
                 };
 
                 // Update stored position
@@ -416,18 +630,15 @@ impl VoiceManager {
                 if tx.send(position).await.is_err() {
                     break;
                 }
-
-                word_index += 1;
             }
 
             // Mark as idle when done
             let mut s = state.write().await;
-            if *s == VoiceState::Reading {
+            if *s == active {
                 *s = VoiceState::Idle;
             }
         });
 
-        tracing::info!("Started reading content");
         Ok(rx)
     }
 
@@ -452,7 +663,7 @@ impl VoiceManager {
         *state = VoiceState::Speaking;
         drop(state);
 
-        let audio = tts.synthesize(text).await?;
+        let audio = tts.synthesize(&apply_pronunciations(text, &self.pronunciations)).await?;
 
         // Play audio
         audio::play_audio(&audio).await?;
@@ -463,17 +674,107 @@ impl VoiceManager {
         Ok(())
     }
 
+    /// Synthesize `phrase` and transcribe it back to check both providers work together.
+    /// Only runs while idle, so it does not disturb listening or reading.
+    pub async fn self_test(&self, phrase: &str) -> Result<VoiceSelfTestReport, VoiceError> {
+        let tts = self.tts.as_deref().ok_or(VoiceError::NotInitialized)?;
+        let stt = self.stt.as_deref().ok_or(VoiceError::NotInitialized)?;
+        if *self.state.read().await != VoiceState::Idle {
+            return Err(VoiceError::InvalidState("Voice is busy".to_string()));
+        }
+
+        Ok(self_test::run_self_test(tts, stt, phrase).await)
+    }
+
+    /// Stop all listening, reading and speaking and return to `Idle`,
+    /// whatever state the manager is in. Provider errors are logged, not returned.
+    pub async fn reset(&mut self) {
+        // Flip state first so background reading tasks stop at their next word
+        *self.state.write().await = VoiceState::Idle;
+
+        if let Some(stt) = self.stt.as_mut() {
+            if let Err(e) = stt.stop_listening().await {
+                tracing::warn!("Failed to stop STT during reset: {}", e);
+            }
+        }
+        if let Some(tts) = self.tts.as_mut() {
+            if let Err(e) = tts.stop().await {
+                tracing::warn!("Failed to stop TTS during reset: {}", e);
+            }
+        }
+
+        self.transcription_tx = None;
+        self.position_tx = None;
+        self.word_timings.clear();
+        self.spoken_start = None;
+        self.spoken_words.clear();
+        *self.current_position.write().await = None;
+
+        tracing::info!("Voice manager reset");
+    }
+
+    /// Force the state machine into `state`
+    #[cfg(test)]
+    pub(crate) async fn force_state(&self, state: VoiceState) {
+        *self.state.write().await = state;
+    }
+
     /// Get current reading position
     pub async fn get_reading_position(&self) -> Option<ReadingPosition> {
         self.current_position.read().await.clone()
     }
 
+    /// Word timings of the content being read, indexed by `ReadingPosition::word_index`
+    pub fn word_timings(&self) -> &[WordTiming] {
+        &self.word_timings
+    }
+
+    /// Map a word of the content being read back to its reading position. The word must
+    /// belong to the document, page and one of the paragraphs being read.
+    pub fn position_for_word(
+        &self,
+        document_id: &str,
+        page: u32,
+        paragraph_id: &str,
+        word_index: u32,
+    ) -> Result<ReadingPosition, VoiceError> {
+        let start = self
+            .spoken_start
+            .as_ref()
+            .ok_or_else(|| VoiceError::InvalidState("No content has been read".to_string()))?;
+        if start.document_id != document_id || start.page != page {
+            return Err(VoiceError::InvalidState(format!(
+                "Page {} of {} is not being read",
+                page, document_id
+            )));
+        }
+
+        (0..self.word_timings.len())
+            .filter_map(|index| {
+                position_from_word_timing(start, &self.spoken_words, index, &self.word_timings)
+            })
+            .find(|position| {
+                position.paragraph_id == paragraph_id && position.word_index == word_index
+            })
+            .ok_or_else(|| {
+                VoiceError::InvalidState(format!(
+                    "Word {} of paragraph {} is not being read",
+                    word_index, paragraph_id
+                ))
+            })
+    }
+
     /// Update configuration
     pub fn update_config(&mut self, config: VoiceConfig) {
         self.config = config;
         self.command_parser = VoiceCommandParser::new(self.config.language.clone());
     }
 
+    /// Replace the pronunciation lexicon applied before synthesis
+    pub fn set_pronunciations(&mut self, pronunciations: Vec<Pronunciation>) {
+        self.pronunciations = pronunciations;
+    }
+
     /// Check if providers are initialized
     pub fn is_initialized(&self) -> bool {
         self.stt.is_some() && self.tts.is_some()
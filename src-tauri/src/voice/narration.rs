@@ -0,0 +1,280 @@
+//! Narration Preprocessing
+//!
+//! Rewrites document text before synthesis so code, equations, reference lists and
+//! figure captions are announced with a short placeholder instead of read verbatim,
+//! and user-defined pronunciations replace terms the voice gets wrong.
+
+use crate::document::editor::{find_math_spans, MathKind};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Which parts of a document to skip when reading aloud
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct NarrationOptions {
+    /// Replace fenced code blocks with "Code block."
+    pub skip_code: bool,
+    /// Replace inline and display math with "equation"
+    pub skip_equations: bool,
+    /// Drop the references / bibliography section
+    pub skip_references: bool,
+    /// Replace figure and table captions with "Figure caption."
+    pub skip_captions: bool,
+}
+
+impl NarrationOptions {
+    fn any(&self) -> bool {
+        self.skip_code || self.skip_equations || self.skip_references || self.skip_captions
+    }
+}
+
+const CODE_PLACEHOLDER: &str = "Code block.";
+const EQUATION_PLACEHOLDER: &str = "equation";
+const REFERENCES_PLACEHOLDER: &str = "References omitted.";
+const CAPTION_PLACEHOLDER: &str = "Figure caption.";
+
+fn references_heading() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)^(#{1,6}\s*)?([0-9]+\.?|[IVX]+\.)?\s*",
+            r"(references|bibliography|works cited|literature cited)\s*:?\s*$",
+        ))
+        .unwrap()
+    })
+}
+
+fn caption() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\s*(Figure|Fig\.|Table)\s*\d+[a-z]?\s*[.:|]").unwrap())
+}
+
+/// Markdown heading level of a line, if it is a heading
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    ((1..=6).contains(&level) && line[level..].starts_with(' ')).then_some(level)
+}
+
+/// Rewrite `text` for narration according to `options`
+pub fn prepare_narration(text: &str, options: &NarrationOptions) -> String {
+    if !options.any() {
+        return text.to_string();
+    }
+
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+    // Heading level of the references section being skipped (0 for a plain-text heading)
+    let mut in_references: Option<usize> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let is_fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+
+        if let Some(level) = in_references {
+            // A markdown heading at the same or a higher level ends the section
+            match heading_level(line) {
+                Some(next) if level > 0 && next <= level && !in_fence => in_references = None,
+                _ => {
+                    in_fence ^= is_fence;
+                    continue;
+                }
+            }
+        }
+
+        if options.skip_code && (in_fence || is_fence) {
+            if is_fence && !in_fence {
+                lines.push(CODE_PLACEHOLDER.to_string());
+            }
+            in_fence ^= is_fence;
+            continue;
+        }
+        in_fence ^= is_fence;
+
+        if options.skip_references && !in_fence && references_heading().is_match(line.trim()) {
+            in_references = Some(heading_level(line).unwrap_or(0));
+            lines.push(REFERENCES_PLACEHOLDER.to_string());
+            continue;
+        }
+        if options.skip_captions && !in_fence && caption().is_match(line) {
+            lines.push(CAPTION_PLACEHOLDER.to_string());
+            continue;
+        }
+        lines.push(line.to_string());
+    }
+
+    let text = lines.join("\n");
+    if options.skip_equations {
+        replace_math(&text)
+    } else {
+        text
+    }
+}
+
+/// A user-defined spoken form for a term, e.g. "SQL" read as "sequel"
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Pronunciation {
+    pub term: String,
+    pub replacement: String,
+}
+
+/// Replace each whole-word, case-insensitive occurrence of a lexicon term with its
+/// replacement. Longer terms win when terms overlap; replacements are not rescanned.
+pub fn apply_pronunciations(text: &str, lexicon: &[Pronunciation]) -> String {
+    let mut terms: Vec<&Pronunciation> = lexicon.iter().filter(|p| !p.term.is_empty()).collect();
+    if terms.is_empty() {
+        return text.to_string();
+    }
+    terms.sort_by_key(|p| std::cmp::Reverse(p.term.len()));
+
+    let pattern = terms
+        .iter()
+        .map(|p| regex::escape(&p.term))
+        .collect::<Vec<_>>()
+        .join("|");
+    let Ok(re) = Regex::new(&format!("(?i){}", pattern)) else {
+        return text.to_string();
+    };
+
+    // Word boundaries are checked by hand so terms like "C++" still match
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut from = 0;
+    while let Some(m) = re.find_at(text, from) {
+        let before = text[..m.start()].chars().next_back();
+        let after = text[m.end()..].chars().next();
+        let first = m.as_str().chars().next();
+        let end = m.as_str().chars().next_back();
+        let splits_word = (is_word(before) && is_word(first)) || (is_word(after) && is_word(end));
+
+        if !splits_word {
+            let found = m.as_str().to_lowercase();
+            if let Some(p) = terms.iter().find(|p| p.term.to_lowercase() == found) {
+                out.push_str(&text[last..m.start()]);
+                out.push_str(&p.replacement);
+                last = m.end();
+                from = m.end();
+                continue;
+            }
+        }
+        // Retry one character further on, where a shorter term may fit
+        from = m.start() + m.as_str().chars().next().map_or(1, char::len_utf8);
+        if from > text.len() {
+            break;
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+fn replace_math(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for span in find_math_spans(text) {
+        out.push_str(&text[last..span.start]);
+        out.push_str(match span.kind {
+            MathKind::Inline => EQUATION_PLACEHOLDER,
+            MathKind::Display => "Equation.",
+        });
+        last = span.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAPER: &str = "# Method\n\
+        We sort the input with $O(n \\log n)$ cost:\n\
+        \n\
+        ```python\n\
+        def sort(xs):\n\
+        \x20   return sorted(xs)\n\
+        ```\n\
+        \n\
+        Figure 2: Runtime of the sort.\n\
+        Figure 2 shows the runtime.\n\
+        \n\
+        ## References\n\
+        [1] Knuth, The Art of Computer Programming.\n\
+        \n\
+        # Appendix\n\
+        Extra notes.";
+
+    fn all() -> NarrationOptions {
+        NarrationOptions {
+            skip_code: true,
+            skip_equations: true,
+            skip_references: true,
+            skip_captions: true,
+        }
+    }
+
+    #[test]
+    fn test_code_block_replaced_by_placeholder() {
+        let options = NarrationOptions {
+            skip_code: true,
+            ..Default::default()
+        };
+        let narration = prepare_narration(PAPER, &options);
+
+        assert!(narration.contains("cost:\n\nCode block.\n\nFigure 2: Runtime"));
+        assert!(!narration.contains("def sort") && !narration.contains("```"));
+        // Other categories are untouched
+        assert!(narration.contains("$O(n \\log n)$") && narration.contains("Knuth"));
+    }
+
+    #[test]
+    fn test_each_category_can_be_skipped() {
+        let narration = prepare_narration(PAPER, &all());
+
+        assert!(narration.contains("with equation cost"));
+        assert!(narration.contains("Figure caption.\nFigure 2 shows the runtime."));
+        assert!(narration.contains("References omitted."));
+        assert!(!narration.contains("Knuth"));
+        // The section after the references is still read
+        assert!(narration.ends_with("# Appendix\nExtra notes."));
+    }
+
+    #[test]
+    fn test_plain_text_references_run_to_end() {
+        let text = "Results were good.\nREFERENCES\n1. Smith 2020.\n2. Doe 2021.";
+        assert_eq!(
+            prepare_narration(text, &all()),
+            "Results were good.\nReferences omitted."
+        );
+    }
+
+    #[test]
+    fn test_pronunciations_match_whole_words_case_insensitively() {
+        let lexicon = [
+            Pronunciation {
+                term: "SQL".to_string(),
+                replacement: "sequel".to_string(),
+            },
+            Pronunciation {
+                term: "PyTorch".to_string(),
+                replacement: "pie torch".to_string(),
+            },
+            Pronunciation {
+                term: "C++".to_string(),
+                replacement: "C plus plus".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            apply_pronunciations("Use sql and PYTORCH, not MySQL or C++.", &lexicon),
+            "Use sequel and pie torch, not MySQL or C plus plus."
+        );
+        assert_eq!(apply_pronunciations("SQLite", &lexicon), "SQLite");
+        assert_eq!(apply_pronunciations("no terms", &[]), "no terms");
+    }
+
+    #[test]
+    fn test_disabled_options_leave_text_unchanged() {
+        assert_eq!(prepare_narration(PAPER, &NarrationOptions::default()), PAPER);
+    }
+}
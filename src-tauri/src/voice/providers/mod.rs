@@ -10,7 +10,8 @@ pub mod piper;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
 
 use crate::voice::{AudioChunk, AudioData, TranscriptionResult, VoiceError, WhisperModel, WordTiming};
 
@@ -63,7 +64,7 @@ pub enum STTProvider {
 }
 
 /// Text-to-Speech provider options
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum TTSProvider {
     /// Local Piper TTS
@@ -113,8 +114,62 @@ pub enum TTSProvider {
     },
 }
 
+impl TTSProvider {
+    /// Approximate words per minute at a 1.0x speaking rate, calibrated per provider
+    pub fn base_wpm(&self) -> f32 {
+        match self {
+            TTSProvider::PiperLocal { .. } => 165.0,
+            TTSProvider::CoquiLocal { .. } => 155.0,
+            // eSpeak's own default rate is 175 wpm
+            TTSProvider::ESpeakNG { .. } => 175.0,
+            TTSProvider::OpenAITTS { .. } => 160.0,
+            TTSProvider::AWSPolly { .. } => 155.0,
+            TTSProvider::GoogleTTS { .. } => 160.0,
+            TTSProvider::AzureTTS { .. } => 150.0,
+            TTSProvider::ElevenLabs { .. } => 150.0,
+        }
+    }
+
+    /// Convert a target words-per-minute into a speaking rate multiplier
+    pub fn wpm_to_rate(&self, wpm: f32) -> f32 {
+        (wpm / self.base_wpm()).clamp(0.25, 3.0)
+    }
+
+    /// Convert a speaking rate multiplier into words per minute
+    pub fn rate_to_wpm(&self, rate: f32) -> f32 {
+        rate * self.base_wpm()
+    }
+
+    /// The same provider configured to speak with `voice_id`.
+    /// Piper voices are looked up next to the current model file.
+    pub fn with_voice(&self, voice_id: &str) -> TTSProvider {
+        let mut provider = self.clone();
+        match &mut provider {
+            TTSProvider::PiperLocal { model_path } => {
+                let dir = std::path::Path::new(model_path.as_str())
+                    .parent()
+                    .unwrap_or(std::path::Path::new("voice_models/piper"));
+                *model_path = dir
+                    .join(format!("{}.onnx", voice_id))
+                    .to_string_lossy()
+                    .to_string();
+            }
+            TTSProvider::CoquiLocal { model_name } => *model_name = voice_id.to_string(),
+            TTSProvider::ESpeakNG { voice } | TTSProvider::OpenAITTS { voice, .. } => {
+                *voice = voice_id.to_string()
+            }
+            TTSProvider::AWSPolly { voice_id: id, .. }
+            | TTSProvider::ElevenLabs { voice_id: id, .. } => *id = voice_id.to_string(),
+            TTSProvider::GoogleTTS { voice_name, .. } | TTSProvider::AzureTTS { voice_name, .. } => {
+                *voice_name = voice_id.to_string()
+            }
+        }
+        provider
+    }
+}
+
 /// AWS Polly engine types
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum PollyEngine {
     #[default]
@@ -144,6 +199,11 @@ pub trait SpeechToText: Send + Sync {
 
     /// Get supported languages
     fn supported_languages(&self) -> Vec<String>;
+
+    /// Have the next listening session stop on its own after `timeout` without
+    /// detected speech, firing `on_timeout` when it does. Providers without voice
+    /// activity detection ignore this and listen until stopped.
+    fn set_idle_timeout(&mut self, _timeout: Duration, _on_timeout: oneshot::Sender<()>) {}
 }
 
 /// Text-to-Speech trait for all providers
@@ -315,3 +375,67 @@ pub fn get_default_model_path(provider_type: &str) -> Option<String> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wpm_rate_round_trip() {
+        let providers = [
+            TTSProvider::PiperLocal {
+                model_path: String::new(),
+            },
+            TTSProvider::ESpeakNG {
+                voice: "en".to_string(),
+            },
+            TTSProvider::AzureTTS {
+                subscription_key: String::new(),
+                region: String::new(),
+                voice_name: String::new(),
+            },
+        ];
+
+        for provider in &providers {
+            for wpm in [80.0, 150.0, 220.0, 300.0] {
+                let rate = provider.wpm_to_rate(wpm);
+                assert!((provider.rate_to_wpm(rate) - wpm).abs() < 0.5);
+            }
+            assert!((provider.wpm_to_rate(provider.base_wpm()) - 1.0).abs() < f32::EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_wpm_rate_clamped() {
+        let provider = TTSProvider::ESpeakNG {
+            voice: "en".to_string(),
+        };
+        assert_eq!(provider.wpm_to_rate(10.0), 0.25);
+        assert_eq!(provider.wpm_to_rate(10_000.0), 3.0);
+    }
+
+    #[test]
+    fn test_with_voice() {
+        let piper = TTSProvider::PiperLocal {
+            model_path: "models/piper/en_US-lessac-medium.onnx".to_string(),
+        };
+        match piper.with_voice("en_GB-alba-medium") {
+            TTSProvider::PiperLocal { model_path } => {
+                assert_eq!(model_path, "models/piper/en_GB-alba-medium.onnx")
+            }
+            other => panic!("Expected Piper, got {:?}", other),
+        }
+
+        let polly = TTSProvider::AWSPolly {
+            region: String::new(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            voice_id: "Joanna".to_string(),
+            engine: PollyEngine::Neural,
+        };
+        assert!(matches!(
+            polly.with_voice("Matthew"),
+            TTSProvider::AWSPolly { voice_id, .. } if voice_id == "Matthew"
+        ));
+    }
+}
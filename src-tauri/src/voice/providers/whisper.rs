@@ -5,12 +5,290 @@
 use async_trait::async_trait;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
 
-use crate::voice::audio::{AudioCapture, AudioConfig};
+use crate::voice::audio::{AudioCapture, AudioConfig, IdleTimer};
 use crate::voice::providers::SpeechToText;
 use crate::voice::{TranscriptionResult, VoiceError, WhisperModel, WordTiming};
 
+/// Samples per transcription window (~2 seconds at 16kHz)
+const WINDOW_SAMPLES: usize = 32000;
+
+/// Samples shared by consecutive windows (~0.5 seconds), longer than most words
+const OVERLAP_SAMPLES: usize = 8000;
+
+const SAMPLES_PER_MS: u64 = 16;
+
+/// Recently committed words kept for aligning the next window's seam
+const SEAM_WORDS: usize = 4;
+
+/// How far past the last committed word a repeated word may start and still be
+/// treated as a duplicate
+const SEAM_SLACK_MS: u64 = 300;
+
+/// A slice of the live audio stream to transcribe
+#[derive(Debug, Clone)]
+struct AudioWindow {
+    /// Stream time of the first sample
+    start_ms: u64,
+    /// Words starting at or after this time are left to the next window, which
+    /// hears them in full. `None` for the last window.
+    commit_before_ms: Option<u64>,
+    samples: Vec<f32>,
+}
+
+/// Cuts a continuous sample stream into overlapping windows
+struct StreamWindows {
+    buffer: Vec<f32>,
+    /// Stream position of `buffer[0]`, in samples
+    offset: u64,
+}
+
+impl StreamWindows {
+    fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Add captured samples, returning every window that is now complete
+    fn push(&mut self, samples: &[f32]) -> Vec<AudioWindow> {
+        self.buffer.extend_from_slice(samples);
+
+        let mut windows = Vec::new();
+        while self.buffer.len() >= WINDOW_SAMPLES {
+            let hop = WINDOW_SAMPLES - OVERLAP_SAMPLES;
+            windows.push(AudioWindow {
+                start_ms: self.offset / SAMPLES_PER_MS,
+                commit_before_ms: Some((self.offset + hop as u64) / SAMPLES_PER_MS),
+                samples: self.buffer[..WINDOW_SAMPLES].to_vec(),
+            });
+            self.buffer.drain(..hop);
+            self.offset += hop as u64;
+        }
+        windows
+    }
+
+    /// The remaining audio, including the overlap of the last full window
+    fn finish(&mut self) -> Option<AudioWindow> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let window = AudioWindow {
+            start_ms: self.offset / SAMPLES_PER_MS,
+            commit_before_ms: None,
+            samples: std::mem::take(&mut self.buffer),
+        };
+        self.offset += window.samples.len() as u64;
+        Some(window)
+    }
+}
+
+/// Stitches per-window transcriptions into one stream without losing or repeating
+/// words at the seams
+#[derive(Default)]
+struct SeamMerger {
+    /// Last committed words, in stream time
+    tail: Vec<WordTiming>,
+}
+
+impl SeamMerger {
+    /// Turn a window's transcription into the words not yet emitted, in stream time
+    fn merge(&mut self, window: &AudioWindow, result: TranscriptionResult) -> TranscriptionResult {
+        if result.words.is_empty() {
+            return self.merge_untimed(window, result);
+        }
+
+        let words: Vec<WordTiming> = result
+            .words
+            .into_iter()
+            .map(|w| WordTiming {
+                start_ms: w.start_ms + window.start_ms,
+                end_ms: w.end_ms + window.start_ms,
+                ..w
+            })
+            .collect();
+
+        let skip = self.seam_overlap(&words);
+        let committed: Vec<WordTiming> = words
+            .into_iter()
+            .skip(skip)
+            .take_while(|w| window.commit_before_ms.map_or(true, |cut| w.start_ms < cut))
+            .collect();
+
+        self.tail.extend(committed.iter().cloned());
+        let excess = self.tail.len().saturating_sub(SEAM_WORDS);
+        self.tail.drain(..excess);
+
+        TranscriptionResult {
+            text: committed.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" "),
+            is_final: result.is_final,
+            confidence: committed.iter().map(|w| w.confidence).sum::<f32>()
+                / committed.len().max(1) as f32,
+            timestamp_ms: window.start_ms,
+            words: committed,
+        }
+    }
+
+    /// Without word timings nothing can be held back for the next window, so the whole
+    /// text is committed once the words repeating the last window are dropped
+    fn merge_untimed(
+        &mut self,
+        window: &AudioWindow,
+        result: TranscriptionResult,
+    ) -> TranscriptionResult {
+        let words: Vec<&str> = result.text.split_whitespace().collect();
+        let skip = self.text_overlap(&words, |_| true);
+        let committed = &words[skip..];
+
+        self.tail.extend(committed.iter().map(|word| WordTiming {
+            word: word.to_string(),
+            start_ms: window.start_ms,
+            end_ms: window.start_ms,
+            confidence: result.confidence,
+        }));
+        let excess = self.tail.len().saturating_sub(SEAM_WORDS);
+        self.tail.drain(..excess);
+
+        TranscriptionResult {
+            text: committed.join(" "),
+            timestamp_ms: window.start_ms,
+            ..result
+        }
+    }
+
+    /// Number of leading words that repeat (or are fragments of) committed words
+    fn seam_overlap(&self, words: &[WordTiming]) -> usize {
+        let Some(last) = self.tail.last() else {
+            return 0;
+        };
+
+        // Timing: words centred before the last committed word ended were already heard
+        let by_time = words
+            .iter()
+            .take_while(|w| (w.start_ms + w.end_ms) / 2 < last.end_ms)
+            .count();
+
+        let texts: Vec<&str> = words.iter().map(|w| w.word.as_str()).collect();
+        let by_text =
+            self.text_overlap(&texts, |k| words[k - 1].start_ms < last.end_ms + SEAM_SLACK_MS);
+
+        by_time.max(by_text)
+    }
+
+    /// Length of the longest committed suffix `words` opens with, allowing the first
+    /// word to be a cut-off fragment. `in_reach(k)` tells whether the first `k` words
+    /// start early enough to be repeats.
+    fn text_overlap(&self, words: &[&str], in_reach: impl Fn(usize) -> bool) -> usize {
+        (1..=self.tail.len().min(words.len()))
+            .rev()
+            .find(|&k| {
+                let suffix = &self.tail[self.tail.len() - k..];
+                in_reach(k)
+                    && suffix.iter().zip(&words[..k]).enumerate().all(|(i, (old, new))| {
+                        let (old, new) = (normalize_word(&old.word), normalize_word(new));
+                        old == new || (i == 0 && !new.is_empty() && old.ends_with(&new))
+                    })
+            })
+            .unwrap_or(0)
+    }
+}
+
+fn normalize_word(word: &str) -> String {
+    word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Join Whisper's subword tokens into words: a token opening with a space starts a new
+/// word and any other continues the previous one
+#[cfg(any(feature = "whisper", test))]
+fn group_tokens(tokens: impl IntoIterator<Item = WordTiming>) -> Vec<WordTiming> {
+    let mut words: Vec<WordTiming> = Vec::new();
+    for token in tokens {
+        let starts_word = token.word.starts_with(char::is_whitespace);
+        let text = token.word.trim().to_string();
+        if text.is_empty() {
+            continue;
+        }
+        match words.last_mut() {
+            Some(word) if !starts_word => {
+                word.word.push_str(&text);
+                word.end_ms = token.end_ms;
+                word.confidence = word.confidence.min(token.confidence);
+            }
+            _ => words.push(WordTiming { word: text, ..token }),
+        }
+    }
+    words
+}
+
+/// Ends a listening session after a stretch of captured audio without speech
+struct IdleStop {
+    timer: IdleTimer,
+    on_timeout: oneshot::Sender<()>,
+    /// Recording flag of the audio capture, cleared to release the microphone
+    recording: Arc<AtomicBool>,
+}
+
+/// Transcribe captured audio window by window until listening is stopped or, with
+/// `idle`, until no speech has been heard for its timeout
+async fn run_listening<F, Fut>(
+    mut audio_rx: mpsc::Receiver<Vec<f32>>,
+    is_listening: Arc<AtomicBool>,
+    mut idle: Option<IdleStop>,
+    tx: mpsc::Sender<TranscriptionResult>,
+    transcribe_window: F,
+) where
+    F: Fn(AudioWindow) -> Fut,
+    Fut: std::future::Future<Output = Result<(AudioWindow, TranscriptionResult), VoiceError>>,
+{
+    let mut windows = StreamWindows::new();
+    let mut merger = SeamMerger::default();
+
+    while is_listening.load(Ordering::SeqCst) {
+        tokio::select! {
+            Some(samples) = audio_rx.recv() => {
+                if idle.as_mut().is_some_and(|idle| idle.timer.observe(&samples)) {
+                    tracing::info!("No speech detected, stopping listening");
+                    is_listening.store(false, Ordering::SeqCst);
+                    let idle = idle.take().unwrap();
+                    idle.recording.store(false, Ordering::SeqCst);
+                    let _ = idle.on_timeout.send(());
+                }
+
+                // Process each window once enough audio has arrived
+                for window in windows.push(&samples) {
+                    match transcribe_window(window).await {
+                        Ok((window, result)) => {
+                            let result = merger.merge(&window, result);
+                            if !result.text.is_empty() {
+                                let _ = tx.send(result).await;
+                            }
+                        }
+                        Err(e) => {
+                            tracing::error!("Transcription error: {}", e);
+                        }
+                    }
+                }
+            }
+            _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
+                // Periodic check
+            }
+        }
+    }
+
+    // Process any remaining audio, including words held back at the last seam
+    if let Some(window) = windows.finish() {
+        if let Ok((window, result)) = transcribe_window(window).await {
+            let result = merger.merge(&window, result);
+            if !result.text.is_empty() {
+                let _ = tx.send(result).await;
+            }
+        }
+    }
+}
+
 /// Whisper STT provider
 pub struct WhisperSTT {
     /// Path to the model file
@@ -25,6 +303,8 @@ pub struct WhisperSTT {
     language: String,
     /// Whether to translate to English
     translate: bool,
+    /// Silence timeout and notifier for the next listening session
+    idle_timeout: Option<(Duration, oneshot::Sender<()>)>,
 }
 
 impl WhisperSTT {
@@ -42,6 +322,7 @@ impl WhisperSTT {
             audio_capture: None,
             language: "en".to_string(),
             translate: false,
+            idle_timeout: None,
         })
     }
 
@@ -87,7 +368,7 @@ impl WhisperSTT {
             .map_err(|e| VoiceError::STTError(e.to_string()))?;
 
         let mut text = String::new();
-        let mut words = Vec::new();
+        let mut tokens = Vec::new();
 
         for i in 0..num_segments {
             let segment_text = state.full_get_segment_text(i)
@@ -101,10 +382,10 @@ impl WhisperSTT {
             for j in 0..num_tokens {
                 if let Ok(token_data) = state.full_get_token_data(i, j) {
                     if let Ok(token_text) = state.full_get_token_text(i, j) {
-                        let token_text = token_text.trim();
-                        if !token_text.is_empty() && !token_text.starts_with('[') {
-                            words.push(WordTiming {
-                                word: token_text.to_string(),
+                        // Special tokens such as [_BEG_] carry no speech
+                        if !token_text.trim_start().starts_with('[') {
+                            tokens.push(WordTiming {
+                                word: token_text,
                                 start_ms: (token_data.t0 * 10) as u64, // Convert to ms
                                 end_ms: (token_data.t1 * 10) as u64,
                                 confidence: token_data.p,
@@ -114,6 +395,7 @@ impl WhisperSTT {
                 }
             }
         }
+        let words = group_tokens(tokens);
 
         Ok(TranscriptionResult {
             text: text.trim().to_string(),
@@ -153,76 +435,40 @@ impl SpeechToText for WhisperSTT {
             channels: 1,
             buffer_size: 1024,
         };
-        let mut audio_capture = AudioCapture::new(config);
+        let mut audio_capture = AudioCapture::new(config.clone());
         let audio_rx = audio_capture.start_capture()?;
-        self.audio_capture = Some(audio_capture);
 
         // Create transcription channel
         let (tx, rx) = mpsc::channel(100);
 
-        // Spawn processing task
         let is_listening = self.is_listening.clone();
         let model_path = self.model_path.clone();
         let language = self.language.clone();
         let translate = self.translate;
 
-        tokio::spawn(async move {
-            let mut audio_buffer: Vec<f32> = Vec::new();
-            let mut audio_rx = audio_rx;
-
-            // Buffer size for ~2 seconds of audio at 16kHz
-            let buffer_threshold = 32000;
-
-            while is_listening.load(Ordering::SeqCst) {
-                tokio::select! {
-                    Some(samples) = audio_rx.recv() => {
-                        audio_buffer.extend(samples);
-
-                        // Process when we have enough audio
-                        if audio_buffer.len() >= buffer_threshold {
-                            // Create a temporary instance for transcription
-                            // In production, this should use a shared context
-                            if let Ok(whisper) = WhisperSTT::new(&model_path, WhisperModel::Base).await {
-                                let mut whisper = whisper;
-                                whisper.language = language.clone();
-                                whisper.translate = translate;
-
-                                match whisper.transcribe_with_whisper(&audio_buffer).await {
-                                    Ok(result) => {
-                                        if !result.text.is_empty() {
-                                            let _ = tx.send(result).await;
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::error!("Transcription error: {}", e);
-                                    }
-                                }
-                            }
+        let idle = self.idle_timeout.take().map(|(timeout, on_timeout)| IdleStop {
+            timer: IdleTimer::new(timeout, config.sample_rate),
+            on_timeout,
+            recording: audio_capture.recording_flag(),
+        });
+        self.audio_capture = Some(audio_capture);
 
-                            // Keep last 0.5 seconds for context
-                            let keep_samples = 8000;
-                            if audio_buffer.len() > keep_samples {
-                                audio_buffer = audio_buffer[audio_buffer.len() - keep_samples..].to_vec();
-                            }
-                        }
-                    }
-                    _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)) => {
-                        // Periodic check
-                    }
-                }
+        // Create a temporary instance for transcription
+        // In production, this should use a shared context
+        let transcribe_window = move |window: AudioWindow| {
+            let model_path = model_path.clone();
+            let language = language.clone();
+            async move {
+                let mut whisper = WhisperSTT::new(&model_path, WhisperModel::Base).await?;
+                whisper.language = language;
+                whisper.translate = translate;
+                let result = whisper.transcribe_with_whisper(&window.samples).await?;
+                Ok::<_, VoiceError>((window, result))
             }
+        };
 
-            // Process any remaining audio
-            if !audio_buffer.is_empty() {
-                if let Ok(whisper) = WhisperSTT::new(&model_path, WhisperModel::Base).await {
-                    if let Ok(result) = whisper.transcribe_with_whisper(&audio_buffer).await {
-                        if !result.text.is_empty() {
-                            let _ = tx.send(result).await;
-                        }
-                    }
-                }
-            }
-        });
+        // Spawn processing task
+        tokio::spawn(run_listening(audio_rx, is_listening, idle, tx, transcribe_window));
 
         tracing::info!("Started Whisper listening");
         Ok(rx)
@@ -284,6 +530,10 @@ impl SpeechToText for WhisperSTT {
             "th".to_string(), // Thai
         ]
     }
+
+    fn set_idle_timeout(&mut self, timeout: Duration, on_timeout: oneshot::Sender<()>) {
+        self.idle_timeout = Some((timeout, on_timeout));
+    }
 }
 
 /// Download Whisper model if not present
@@ -351,6 +601,217 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_silent_stream_stops_listening_after_idle_timeout() {
+        let (audio_tx, audio_rx) = mpsc::channel(100);
+        let (tx, mut rx) = mpsc::channel(10);
+        let (on_timeout, timed_out) = oneshot::channel();
+        let is_listening = Arc::new(AtomicBool::new(true));
+        let recording = Arc::new(AtomicBool::new(true));
+        let idle = IdleStop {
+            timer: IdleTimer::new(Duration::from_secs(2), 16000),
+            on_timeout,
+            recording: recording.clone(),
+        };
+
+        // Ten seconds of silence in 100ms chunks, far more than the timeout needs
+        for _ in 0..100 {
+            audio_tx.send(vec![0.0f32; 1600]).await.unwrap();
+        }
+        let transcribe_window = |window: AudioWindow| async move {
+            let result = TranscriptionResult {
+                text: String::new(),
+                is_final: true,
+                confidence: 0.0,
+                timestamp_ms: 0,
+                words: Vec::new(),
+            };
+            Ok((window, result))
+        };
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            run_listening(audio_rx, is_listening.clone(), Some(idle), tx, transcribe_window),
+        )
+        .await
+        .expect("listening should stop on its own");
+
+        assert!(timed_out.await.is_ok());
+        assert!(!is_listening.load(Ordering::SeqCst));
+        assert!(!recording.load(Ordering::SeqCst));
+        assert!(rx.recv().await.is_none());
+        // Stopped once two seconds of audio were silent, not at the end of the stream
+        assert!(audio_tx.send(vec![0.0f32; 1600]).await.is_err());
+    }
+
+    /// Word of synthetic continuous speech, in stream time
+    struct Spoken {
+        word: &'static str,
+        start_ms: u64,
+        end_ms: u64,
+    }
+
+    /// Stand-in for Whisper: reports the words a window overlaps, with words cut by
+    /// the window edges truncated and timings shifted by a little jitter
+    fn fake_transcribe(speech: &[Spoken], window: &AudioWindow) -> TranscriptionResult {
+        let start = window.start_ms;
+        let end = start + window.samples.len() as u64 / SAMPLES_PER_MS;
+        let mut words = Vec::new();
+        for (i, w) in speech.iter().enumerate() {
+            if w.end_ms <= start || w.start_ms >= end {
+                continue;
+            }
+            let (from, to) = (w.start_ms.max(start), w.end_ms.min(end));
+            let chars: Vec<char> = w.word.chars().collect();
+            let share = |ms: u64| (ms * chars.len() as u64 / (w.end_ms - w.start_ms)) as usize;
+            let text: String = chars[share(from - w.start_ms)..share(to - w.start_ms)]
+                .iter()
+                .collect();
+            if text.is_empty() {
+                continue;
+            }
+            let jitter = (i as u64 * 7) % 40;
+            words.push(WordTiming {
+                word: text,
+                start_ms: (from - start + jitter).saturating_sub(20),
+                end_ms: (to - start + jitter).saturating_sub(20),
+                confidence: 0.9,
+            });
+        }
+        TranscriptionResult {
+            text: words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" "),
+            is_final: true,
+            confidence: 0.9,
+            timestamp_ms: 0,
+            words,
+        }
+    }
+
+    #[test]
+    fn test_continuous_speech_has_no_lost_or_repeated_words_at_seams() {
+        let vocabulary = [
+            "the", "transformer", "uses", "attention", "to", "weigh", "every", "token",
+            "against", "all", "others", "very", "very", "efficiently",
+        ];
+        // Words of 250-420ms with short pauses, so many straddle window boundaries
+        let mut speech = Vec::new();
+        let mut t = 0;
+        for i in 0..60 {
+            let length = 250 + (i as u64 * 37) % 170;
+            speech.push(Spoken {
+                word: vocabulary[i % vocabulary.len()],
+                start_ms: t,
+                end_ms: t + length,
+            });
+            t += length + 40 + (i as u64 * 13) % 60;
+        }
+        let total_samples = (t + 200) * SAMPLES_PER_MS;
+
+        let mut windows = StreamWindows::new();
+        let mut merger = SeamMerger::default();
+        let mut heard = Vec::new();
+        let mut fed = 0;
+        while fed < total_samples {
+            let chunk = 1024.min(total_samples - fed) as usize;
+            fed += chunk as u64;
+            for window in windows.push(&vec![0.0; chunk]) {
+                let result = merger.merge(&window, fake_transcribe(&speech, &window));
+                heard.extend(result.words.into_iter().map(|w| w.word));
+            }
+        }
+        let window = windows.finish().unwrap();
+        let result = merger.merge(&window, fake_transcribe(&speech, &window));
+        heard.extend(result.words.into_iter().map(|w| w.word));
+
+        let spoken: Vec<&str> = speech.iter().map(|w| w.word).collect();
+        assert_eq!(heard, spoken);
+    }
+
+    #[test]
+    fn test_seam_drops_repeated_words_when_timings_drift() {
+        let word = |w: &str, start_ms, end_ms| WordTiming {
+            word: w.to_string(),
+            start_ms,
+            end_ms,
+            confidence: 1.0,
+        };
+        let mut merger = SeamMerger::default();
+        let first = AudioWindow {
+            start_ms: 0,
+            commit_before_ms: Some(1500),
+            samples: Vec::new(),
+        };
+        let result = TranscriptionResult {
+            text: String::new(),
+            is_final: true,
+            confidence: 1.0,
+            timestamp_ms: 0,
+            words: vec![word("gradient", 900, 1300), word("descent", 1550, 1900)],
+        };
+        assert_eq!(merger.merge(&first, result).text, "gradient");
+
+        // The next window re-hears "gradient" late enough that timing alone misses it
+        let second = AudioWindow {
+            start_ms: 1500,
+            commit_before_ms: Some(3000),
+            samples: Vec::new(),
+        };
+        let result = TranscriptionResult {
+            text: String::new(),
+            is_final: true,
+            confidence: 1.0,
+            timestamp_ms: 0,
+            words: vec![word("Gradient,", 0, 250), word("descent", 260, 500)],
+        };
+        let merged = merger.merge(&second, result);
+        assert_eq!(merged.text, "descent");
+        assert_eq!(merged.words[0].start_ms, 1760);
+    }
+
+    #[test]
+    fn test_subword_tokens_grouped_into_words() {
+        let token = |w: &str, start_ms, end_ms, confidence| WordTiming {
+            word: w.to_string(),
+            start_ms,
+            end_ms,
+            confidence,
+        };
+        let words = group_tokens([
+            token(" Trans", 0, 200, 0.9),
+            token("former", 200, 450, 0.6),
+            token(" models", 500, 800, 0.8),
+            token(",", 800, 820, 0.95),
+        ]);
+
+        let summary: Vec<_> =
+            words.iter().map(|w| (w.word.as_str(), w.start_ms, w.end_ms)).collect();
+        assert_eq!(summary, [("Transformer", 0, 450), ("models,", 500, 820)]);
+        assert_eq!(words[0].confidence, 0.6);
+    }
+
+    #[test]
+    fn test_untimed_windows_drop_words_repeated_at_the_seam() {
+        let untimed = |text: &str| TranscriptionResult {
+            text: text.to_string(),
+            is_final: true,
+            confidence: 0.8,
+            timestamp_ms: 0,
+            words: Vec::new(),
+        };
+        let window = |start_ms| AudioWindow {
+            start_ms,
+            commit_before_ms: Some(start_ms + 1500),
+            samples: Vec::new(),
+        };
+        let mut merger = SeamMerger::default();
+
+        let first = merger.merge(&window(0), untimed("attention weighs every token"));
+        assert_eq!(first.text, "attention weighs every token");
+        let second = merger.merge(&window(1500), untimed("every token against the rest"));
+        assert_eq!(second.text, "against the rest");
+        assert_eq!(second.timestamp_ms, 1500);
+    }
+
     #[test]
     fn test_supported_languages() {
         let whisper = WhisperSTT {
@@ -360,6 +821,7 @@ mod tests {
             audio_capture: None,
             language: "en".to_string(),
             translate: false,
+            idle_timeout: None,
         };
 
         let languages = whisper.supported_languages();
@@ -0,0 +1,239 @@
+//! Round-trip check of the voice setup: a known phrase is synthesized with TTS and the
+//! audio transcribed back with STT
+
+use super::{SpeechToText, TextToSpeech};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::time::Instant;
+
+/// Phrase spoken when the caller gives none
+pub const DEFAULT_SELF_TEST_PHRASE: &str = "The quick brown fox jumps over the lazy dog.";
+/// Word similarity from which the transcription counts as a match
+pub const MIN_SELF_TEST_SIMILARITY: f32 = 0.8;
+
+/// Stage of the round trip that failed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SelfTestStage {
+    /// Synthesis failed or produced no audio
+    Tts,
+    /// Transcribing the synthesized audio failed
+    Stt,
+    /// Both stages ran but the transcription did not match the phrase
+    Match,
+}
+
+/// Outcome of a voice self-test
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VoiceSelfTestReport {
+    pub phrase: String,
+    /// What STT heard; `None` if it did not run or failed
+    pub transcript: Option<String>,
+    /// Word similarity between phrase and transcript (0.0 to 1.0)
+    pub similarity: f32,
+    pub passed: bool,
+    /// First stage that failed, if any
+    pub failed_stage: Option<SelfTestStage>,
+    /// Error from the failed stage
+    pub error: Option<String>,
+    pub tts_ms: u64,
+    /// `None` when TTS failed and STT never ran
+    pub stt_ms: Option<u64>,
+}
+
+/// Synthesize `phrase` with `tts`, transcribe the audio with `stt` and compare the
+/// result with the phrase. Provider errors are reported, not returned.
+pub async fn run_self_test(
+    tts: &dyn TextToSpeech,
+    stt: &dyn SpeechToText,
+    phrase: &str,
+) -> VoiceSelfTestReport {
+    let mut report = VoiceSelfTestReport {
+        phrase: phrase.to_string(),
+        transcript: None,
+        similarity: 0.0,
+        passed: false,
+        failed_stage: None,
+        error: None,
+        tts_ms: 0,
+        stt_ms: None,
+    };
+
+    let start = Instant::now();
+    let synthesized = tts.synthesize(phrase).await;
+    report.tts_ms = start.elapsed().as_millis() as u64;
+    let audio = match synthesized {
+        Ok(audio) if !audio.samples.is_empty() => audio,
+        Ok(_) => {
+            report.failed_stage = Some(SelfTestStage::Tts);
+            report.error = Some("TTS produced no audio".to_string());
+            return report;
+        }
+        Err(e) => {
+            report.failed_stage = Some(SelfTestStage::Tts);
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+
+    let start = Instant::now();
+    let transcribed = stt.transcribe(&audio.samples, audio.sample_rate).await;
+    report.stt_ms = Some(start.elapsed().as_millis() as u64);
+    let transcript = match transcribed {
+        Ok(result) => result.text,
+        Err(e) => {
+            report.failed_stage = Some(SelfTestStage::Stt);
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+
+    report.similarity = word_similarity(phrase, &transcript);
+    report.passed = report.similarity >= MIN_SELF_TEST_SIMILARITY;
+    if !report.passed {
+        report.failed_stage = Some(SelfTestStage::Match);
+    }
+    report.transcript = Some(transcript);
+    report
+}
+
+/// Similarity of the words of `a` and `b`, ignoring case and punctuation
+fn word_similarity(a: &str, b: &str) -> f32 {
+    let words = |text: &str| {
+        text.split_whitespace()
+            .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let a: Vec<&str> = a.iter().map(String::as_str).collect();
+    let b: Vec<&str> = b.iter().map(String::as_str).collect();
+    TextDiff::from_slices(&a, &b).ratio()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::providers::VoiceInfo;
+    use crate::voice::{AudioChunk, AudioData, TranscriptionResult, VoiceError, WordTiming};
+    use tokio::sync::mpsc;
+
+    /// TTS producing one sample per character, or failing when `fail` is set
+    struct MockTts {
+        fail: bool,
+    }
+
+    /// STT that hears `heard` whatever audio it is given
+    struct MockStt {
+        heard: &'static str,
+    }
+
+    #[async_trait::async_trait]
+    impl TextToSpeech for MockTts {
+        async fn synthesize(&self, text: &str) -> Result<AudioData, VoiceError> {
+            if self.fail {
+                return Err(VoiceError::TTSError("voice model missing".to_string()));
+            }
+            Ok(AudioData {
+                samples: vec![0.1; text.len()],
+                sample_rate: 22050,
+                channels: 1,
+            })
+        }
+
+        async fn synthesize_stream(
+            &self,
+            _text: &str,
+        ) -> Result<mpsc::Receiver<AudioChunk>, VoiceError> {
+            Ok(mpsc::channel(1).1)
+        }
+
+        async fn get_word_timings(&self, _text: &str) -> Result<Vec<WordTiming>, VoiceError> {
+            Ok(Vec::new())
+        }
+
+        async fn stop(&mut self) -> Result<(), VoiceError> {
+            Ok(())
+        }
+
+        fn available_voices(&self) -> Vec<VoiceInfo> {
+            Vec::new()
+        }
+
+        fn set_rate(&mut self, _rate: f32) {}
+
+        fn set_voice(&mut self, _voice_id: &str) -> Result<(), VoiceError> {
+            Ok(())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SpeechToText for MockStt {
+        async fn start_listening(
+            &mut self,
+        ) -> Result<mpsc::Receiver<TranscriptionResult>, VoiceError> {
+            Ok(mpsc::channel(1).1)
+        }
+
+        async fn stop_listening(&mut self) -> Result<(), VoiceError> {
+            Ok(())
+        }
+
+        async fn transcribe(
+            &self,
+            audio: &[f32],
+            _sample_rate: u32,
+        ) -> Result<TranscriptionResult, VoiceError> {
+            assert!(!audio.is_empty());
+            Ok(TranscriptionResult {
+                text: self.heard.to_string(),
+                is_final: true,
+                confidence: 0.9,
+                timestamp_ms: 0,
+                words: Vec::new(),
+            })
+        }
+
+        fn is_listening(&self) -> bool {
+            false
+        }
+
+        fn supported_languages(&self) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_matches_despite_case_and_punctuation() {
+        let tts = MockTts { fail: false };
+        let stt = MockStt {
+            heard: " the quick brown fox jumps over the lazy dog",
+        };
+
+        let report = run_self_test(&tts, &stt, DEFAULT_SELF_TEST_PHRASE).await;
+        assert!(report.passed);
+        assert_eq!(report.similarity, 1.0);
+        assert_eq!(report.failed_stage, None);
+        assert!(report.stt_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_mismatch_and_failed_stage_reported() {
+        let stt = MockStt {
+            heard: "a slow green turtle",
+        };
+        let report = run_self_test(&MockTts { fail: false }, &stt, DEFAULT_SELF_TEST_PHRASE).await;
+        assert!(!report.passed);
+        assert!(report.similarity < MIN_SELF_TEST_SIMILARITY);
+        assert_eq!(report.failed_stage, Some(SelfTestStage::Match));
+        assert_eq!(report.transcript.as_deref(), Some("a slow green turtle"));
+
+        let report = run_self_test(&MockTts { fail: true }, &stt, DEFAULT_SELF_TEST_PHRASE).await;
+        assert_eq!(report.failed_stage, Some(SelfTestStage::Tts));
+        assert_eq!(report.error.as_deref(), Some("TTS error: voice model missing"));
+        assert_eq!(report.stt_ms, None);
+    }
+}
@@ -83,7 +83,9 @@ async fn test_document_category_detection() {
 
 #[test]
 fn test_annotation_types() {
-    use intellidoc_reader_lib::annotation::{Annotation, HighlightColor, AnnotationUpdate};
+    use intellidoc_reader_lib::annotation::{
+        Annotation, AnnotationKind, AnnotationUpdate, HighlightColor,
+    };
     use uuid::Uuid;
     use chrono::Utc;
 
@@ -92,8 +94,10 @@ fn test_annotation_types() {
         document_id: "test-doc".to_string(),
         page_number: 1,
         paragraph_id: None,
-        start_offset: 0,
-        end_offset: 10,
+        paragraph_unmatched: false,
+        kind: AnnotationKind::Highlight,
+        start_offset: Some(0),
+        end_offset: Some(10),
         selected_text: "Test text".to_string(),
         highlight_color: Some(HighlightColor::Yellow),
         note: None,
@@ -158,6 +162,156 @@ fn test_editor_basic() {
     std::fs::remove_file(&test_path).ok();
 }
 
+#[test]
+fn test_math_detection() {
+    use intellidoc_reader_lib::document::editor::{find_math_spans, MathKind, TextEditor};
+
+    let source = "Energy is $E=mc^2$, costs $5 or $10.\n\n$$\\int_0^1 x\\,dx$$\n\n`echo $HOME$`\n\n```\nprice = $a$\n```\n";
+    let spans = find_math_spans(source);
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].kind, MathKind::Inline);
+    assert_eq!(spans[0].tex, "E=mc^2");
+    assert_eq!(spans[1].kind, MathKind::Display);
+    assert_eq!(spans[1].tex, "\\int_0^1 x\\,dx");
+
+    let test_path = temp_path("math_preview.md");
+    std::fs::write(&test_path, "Let $a*b$ be *big*.\n\n\\[ x < y \\]\n\n`$x$`").unwrap();
+    let editor = TextEditor::new(&test_path).unwrap();
+    let html = editor.render_markdown_preview();
+    assert!(html.contains("<span class=\"math math-inline\">\\(a*b\\)</span>"));
+    assert!(html.contains("<div class=\"math math-display\">\\[x &lt; y\\]</div>"));
+    assert!(html.contains("<code>$x$</code>"));
+    println!("✓ Math spans detected outside code");
+
+    std::fs::remove_file(&test_path).ok();
+}
+
+#[tokio::test]
+async fn test_stable_paragraph_ids() {
+    let test_path = temp_path("test_stable_ids.txt");
+    std::fs::write(&test_path, "First paragraph.\n\nSecond  paragraph.\n\nFirst paragraph.").unwrap();
+
+    let first = parser::parse_document(&test_path).await.unwrap();
+    let second = parser::parse_document(&test_path).await.unwrap();
+    let ids = |doc: &intellidoc_reader_lib::document::Document| -> Vec<String> {
+        doc.pages[0].paragraphs.iter().map(|p| p.id.clone()).collect()
+    };
+    assert_eq!(ids(&first), ids(&second));
+
+    // Duplicate paragraphs get distinct ids
+    let first_ids = ids(&first);
+    assert_ne!(first_ids[0], first_ids[2]);
+
+    // Shifting paragraphs (and reflowing whitespace) keeps existing ids
+    std::fs::write(&test_path, "New intro.\n\nFirst paragraph.\n\nSecond paragraph.\n\nFirst paragraph.").unwrap();
+    let options = intellidoc_reader_lib::document::ParseOptions::default();
+    let (shifted, id_map) = parser::parse_document_with_id_map(&test_path, &options)
+        .await
+        .unwrap();
+    assert_eq!(&ids(&shifted)[1..], &first_ids[..]);
+    assert_eq!(id_map.get("p2"), Some(&first_ids[0]));
+
+    println!("✓ Stable paragraph ids: {:?}", first_ids);
+
+    std::fs::remove_file(&test_path).ok();
+}
+
+#[tokio::test]
+async fn test_save_refuses_external_modification() {
+    use intellidoc_reader_lib::document::editor::{DocumentEditor, EditorError, TextEditor};
+
+    let test_path = temp_path("test_external_change.txt");
+    std::fs::write(&test_path, "original").unwrap();
+
+    let mut editor = TextEditor::new(&test_path).unwrap();
+    editor.set_content("edited in app".to_string());
+
+    // Another program rewrites the file while it's open
+    std::fs::write(&test_path, "edited elsewhere").unwrap();
+
+    let result = editor.save(false).await;
+    assert!(matches!(result, Err(EditorError::ExternallyModified(_))));
+    assert_eq!(std::fs::read_to_string(&test_path).unwrap(), "edited elsewhere");
+
+    editor.save(true).await.unwrap();
+    assert_eq!(std::fs::read_to_string(&test_path).unwrap(), "edited in app");
+
+    // After a successful save the new content is the baseline
+    editor.set_content("second edit".to_string());
+    editor.save(false).await.unwrap();
+    println!("✓ Save refuses external modifications without force");
+
+    std::fs::remove_file(&test_path).ok();
+    std::fs::remove_file(format!("{}.backup", test_path)).ok();
+}
+
+#[tokio::test]
+async fn test_file_watcher_reports_changes() {
+    use intellidoc_reader_lib::document::editor::watch_file;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let test_path = temp_path("test_watched_file.txt");
+    std::fs::write(&test_path, "before").unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let _watcher = watch_file(&test_path, move || {
+        let _ = tx.send(());
+    })
+    .unwrap();
+
+    std::fs::write(&test_path, "after").unwrap();
+
+    rx.recv_timeout(Duration::from_secs(5))
+        .expect("expected a change notification");
+    println!("✓ File watcher reports external changes");
+
+    std::fs::remove_file(&test_path).ok();
+}
+
+#[tokio::test]
+async fn test_markdown_outline() {
+    use intellidoc_reader_lib::document::get_outline;
+
+    let test_path = temp_path("test_outline.md");
+    std::fs::write(&test_path, r#"# Introduction
+
+Opening words.
+
+## Background
+
+### Prior `work`
+
+Details.
+
+## Motivation
+
+# Method
+"#).unwrap();
+
+    let doc = parser::parse_document(&test_path).await.unwrap();
+    let outline = get_outline(&doc);
+
+    let titles: Vec<&str> = outline.iter().map(|e| e.title.as_str()).collect();
+    assert_eq!(titles, ["Introduction", "Method"]);
+
+    let intro = &outline[0];
+    let sections: Vec<&str> = intro.children.iter().map(|e| e.title.as_str()).collect();
+    assert_eq!(sections, ["Background", "Motivation"]);
+    assert_eq!(intro.children[0].children[0].title, "Prior `work`");
+    assert!(outline[1].children.is_empty());
+
+    // Every heading targets its paragraph on the page
+    let background = &intro.children[0];
+    let paragraph = &doc.pages[0].paragraphs[2];
+    assert_eq!(paragraph.text, "Background");
+    assert_eq!(background.href, format!("#{}", paragraph.id));
+    assert_eq!(background.page, Some(1));
+    println!("✓ Markdown outline: {} top-level entries", outline.len());
+
+    std::fs::remove_file(&test_path).ok();
+}
+
 #[test]
 fn test_llm_prompts() {
     use intellidoc_reader_lib::llm::prompts::{PROFESSOR_PROMPT, CODE_GENERATOR_PROMPT};
@@ -218,6 +372,161 @@ fn test_available_models() {
     println!("✓ Bedrock models: {} available", bedrock_models.models.len());
 }
 
+#[tokio::test]
+async fn test_combine_markdown_and_pdf() {
+    use intellidoc_reader_lib::document::ConversionUtils;
+
+    fn page_count(path: &str) -> usize {
+        lopdf::Document::load(path).unwrap().get_pages().len()
+    }
+
+    let notes_md = temp_path("intellidoc_combine_notes.md");
+    let long_md = temp_path("intellidoc_combine_long.md");
+    let existing_pdf = temp_path("intellidoc_combine_existing.pdf");
+    let notes_pdf = temp_path("intellidoc_combine_notes.pdf");
+    let output = temp_path("intellidoc_combine_output.pdf");
+
+    std::fs::write(&notes_md, "# Notes\n\nA short summary.\n\n- first\n- second\n").unwrap();
+    let long: String = (1..=120).map(|i| format!("Paragraph {}.\n\n", i)).collect();
+    std::fs::write(&long_md, long).unwrap();
+
+    // Build the "existing" PDF and a reference conversion of the notes
+    ConversionUtils::markdown_to_pdf(&long_md, &existing_pdf).await.unwrap();
+    ConversionUtils::markdown_to_pdf(&notes_md, &notes_pdf).await.unwrap();
+    let expected = page_count(&notes_pdf) + page_count(&existing_pdf);
+    assert!(page_count(&existing_pdf) > 1);
+
+    let missing = temp_path("intellidoc_combine_missing.md");
+    let report = ConversionUtils::combine_to_pdf(
+        vec![notes_md.clone(), missing.clone(), existing_pdf.clone()],
+        output.clone(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(report.included, vec![notes_md.clone(), existing_pdf.clone()]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].input, missing);
+    assert_eq!(page_count(&output), expected);
+
+    for path in [&notes_md, &long_md, &existing_pdf, &notes_pdf, &output] {
+        let _ = std::fs::remove_file(path);
+    }
+
+    println!("✓ Combined markdown and PDF into {} pages", expected);
+}
+
+#[tokio::test]
+async fn test_pdf_edits_written_on_save() {
+    use intellidoc_reader_lib::document::editor::DocumentEditor;
+    use intellidoc_reader_lib::document::{
+        ConversionUtils, PDFEditOperation, PDFEditor, WatermarkPosition,
+    };
+    use lopdf::Object;
+
+    let source_md = temp_path("intellidoc_pdf_edit_source.md");
+    let source = temp_path("intellidoc_pdf_edit_source.pdf");
+    let output = temp_path("intellidoc_pdf_edit_output.pdf");
+    let paragraphs: String = (1..=120).map(|i| format!("Paragraph {}.\n\n", i)).collect();
+    std::fs::write(&source_md, paragraphs).unwrap();
+    ConversionUtils::markdown_to_pdf(&source_md, &source).await.unwrap();
+
+    let original = lopdf::Document::load(&source).unwrap();
+    let page_count = original.get_pages().len();
+    assert!(page_count >= 3);
+    let second_page_text = original.extract_text(&[2]).unwrap();
+    let deleted_line = second_page_text.lines().find(|l| l.contains("Paragraph")).unwrap();
+
+    let mut editor = PDFEditor::new(&source).unwrap();
+    editor
+        .add_operations(vec![
+            PDFEditOperation::AddText {
+                page: 1,
+                x: 72.0,
+                y: 760.0,
+                text: "Reviewed".to_string(),
+                font_size: 14.0,
+                font_family: "Helvetica".to_string(),
+                color: "#FF0000".to_string(),
+            },
+            PDFEditOperation::DeletePage { page: 2 },
+            PDFEditOperation::RotatePage { page: 1, degrees: 90 },
+            PDFEditOperation::InsertPage {
+                after_page: 0,
+                width: 595.0,
+                height: 842.0,
+            },
+            PDFEditOperation::AddWatermark {
+                text: "DRAFT".to_string(),
+                font_size: 48.0,
+                color: "#CCCCCC".to_string(),
+                opacity: 0.3,
+                position: WatermarkPosition::Diagonal,
+                pages: None,
+            },
+        ])
+        .unwrap();
+    editor.save_as(&output).await.unwrap();
+
+    let saved = lopdf::Document::load(&output).unwrap();
+    let pages = saved.get_pages();
+    assert_eq!(pages.len(), page_count);
+    let root = saved.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap();
+    let count = saved.get_dictionary(root).unwrap().get(b"Count").unwrap().as_i64().unwrap();
+    assert_eq!(count, page_count as i64);
+
+    // The inserted blank page comes first, followed by the rotated, annotated page 1
+    let page = |number: u32| saved.get_dictionary(pages[&number]).unwrap();
+    let media_box: Vec<f32> = page(1)
+        .get(b"MediaBox")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| n.as_float().unwrap())
+        .collect();
+    assert_eq!(media_box, [0.0, 0.0, 595.0, 842.0]);
+    assert_eq!(page(2).get(b"Rotate").and_then(Object::as_i64).unwrap(), 90);
+
+    let content = |number: u32| {
+        String::from_utf8_lossy(&saved.get_page_content(pages[&number]).unwrap()).into_owned()
+    };
+    let first = content(2);
+    assert!(first.contains("1 0 0 rg"));
+    assert!(first.contains("1 0 0 1 72 760 Tm\n(Reviewed) Tj"));
+    assert!(saved.extract_text(&[2]).unwrap().contains("Paragraph 1."));
+    for number in 1..=page_count as u32 {
+        assert!(content(number).contains("(DRAFT) Tj"), "no watermark on page {}", number);
+        let text = saved.extract_text(&[number]).unwrap_or_default();
+        assert!(!text.contains(deleted_line), "deleted page still present");
+    }
+
+    // Nothing is written if the source no longer has a queued operation's page
+    let mut editor = PDFEditor::new(&source).unwrap();
+    editor
+        .add_operation(PDFEditOperation::RotatePage {
+            page: page_count as u32,
+            degrees: 180,
+        })
+        .unwrap();
+    std::fs::write(&source_md, "Just one page.").unwrap();
+    ConversionUtils::markdown_to_pdf(&source_md, &source).await.unwrap();
+    let _ = std::fs::remove_file(&output);
+    let result = editor.save_as(&output).await;
+    assert!(matches!(
+        result,
+        Err(intellidoc_reader_lib::document::EditorError::PageOutOfRange(page))
+            if page == page_count as u32
+    ));
+    assert!(!std::path::Path::new(&output).exists());
+
+    for path in [&source_md, &source, &output] {
+        let _ = std::fs::remove_file(path);
+    }
+
+    println!("✓ PDF edits written to {} pages", page_count);
+}
+
 fn main() {
     println!("Run with: cargo test --test integration_test -- --nocapture");
 }
@@ -184,19 +184,15 @@ export function ChatPanel({ document, onClose }: ChatPanelProps) {
     setIsLoading(true);
 
     try {
-      // Get document context (first few pages or current page)
-      const context = document.pages
-        .slice(0, 3)
-        .map((p) => p.text)
-        .join("\n\n");
-
       const queryMode: QueryMode =
         mode === "explain" ? "explain" : "quick_answer";
 
+      // Without a context the backend sends the start of the document, as the
+      // prompt preview shows
       const response = await invoke<{ answer: string }>("query_llm", {
         question: userMessage.content,
-        context,
         mode: queryMode,
+        documentId: document.id,
       });
 
       const assistantMessage: ChatMessage = {
@@ -33,6 +33,7 @@ interface BoundingBox {
 interface DocumentMetadata {
   page_count: number;
   word_count: number;
+  title: string | null;
   creation_date: string | null;
   modification_date: string | null;
   subject: string | null;